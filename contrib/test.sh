@@ -2,7 +2,9 @@
 
 set -e
 
-FEATURES="compiler serde rand"
+FEATURES="compiler serde rand bdd registry gen-vectors bip39-test-utils"
+# Features whose dependencies need a newer compiler than the MSRV
+NON_MSRV_FEATURES="encrypt tracing arbitrary"
 
 cargo update -p serde --precise 1.0.142
 cargo update -p serde_derive --precise 1.0.142
@@ -27,6 +29,8 @@ if [ "$MSRV" = true ]; then
     cargo update -p once_cell --precise 1.13.1
     cargo update -p bzip2 --precise 0.4.2
     cargo update -p which --precise 4.3.0
+else
+    FEATURES="$FEATURES $NON_MSRV_FEATURES"
 fi
 
 # Format if told to
@@ -69,6 +73,7 @@ then
     cargo run --example verify_tx > /dev/null
     cargo run --example xpub_descriptors
     cargo run --example taproot --features=compiler
+    cargo run --example gen_vectors --features=gen-vectors > /dev/null
 fi
 
 # Bench if told to (this only works with the nightly toolchain)
@@ -69,6 +69,7 @@ fn p2sh_p2wsh<C: Verification>(secp: &Secp256k1<C>) -> Address {
     let address = Descriptor::<DescriptorPublicKey>::from_str(&s)
         .unwrap()
         .at_derivation_index(5)
+        .unwrap()
         .derived_descriptor(secp)
         .unwrap()
         .address(&elements::AddressParams::ELEMENTS)
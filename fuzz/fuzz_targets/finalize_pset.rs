@@ -0,0 +1,83 @@
+extern crate elements_miniscript as miniscript;
+
+use miniscript::elements::encode;
+use miniscript::elements::hashes::Hash;
+use miniscript::elements::pset::PartiallySignedTransaction as Psbt;
+use miniscript::elements::secp256k1_zkp::Secp256k1;
+use miniscript::elements::BlockHash;
+use miniscript::psbt::PsbtExt;
+
+fn do_test(data: &[u8]) {
+    // The finalizer must not panic on any PSET, however malformed
+    let psbt: Psbt = match encode::deserialize(data) {
+        Ok(psbt) => psbt,
+        Err(_) => return,
+    };
+    let secp = Secp256k1::verification_only();
+    let genesis_hash = BlockHash::all_zeros();
+
+    let _ = psbt.extract(&secp, genesis_hash);
+    for idx in 0..psbt.inputs().len() + 1 {
+        let _ = psbt.clone().finalize_inp(&secp, idx, genesis_hash);
+    }
+    if let Ok(finalized) = psbt.clone().finalize(&secp, genesis_hash) {
+        let _ = finalized.extract(&secp, genesis_hash);
+    }
+    let _ = psbt.finalize_mall(&secp, genesis_hash);
+}
+
+#[cfg(feature = "afl")]
+extern crate afl;
+#[cfg(feature = "afl")]
+fn main() {
+    afl::read_stdio_bytes(|data| {
+        do_test(&data);
+    });
+}
+
+#[cfg(feature = "honggfuzz")]
+#[macro_use]
+extern crate honggfuzz;
+#[cfg(feature = "honggfuzz")]
+fn main() {
+    loop {
+        fuzz!(|data| {
+            do_test(data);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use miniscript::elements::{confidential, pset, OutPoint, TxOut};
+    use miniscript::{bitcoin, Descriptor};
+    use std::str::FromStr;
+
+    #[test]
+    fn test() {
+        let desc = Descriptor::<bitcoin::PublicKey>::from_str(
+            "elwsh(pk(028c28a97bf8298bc0d23d8c749452a32e694b65e30a9472a3954ab30fe5324caa))",
+        )
+        .unwrap();
+        let mut psbt = Psbt::new_v2();
+        let mut input = pset::Input::from_prevout(OutPoint::default());
+        input.witness_utxo = Some(TxOut {
+            script_pubkey: desc.script_pubkey(),
+            value: confidential::Value::Explicit(1000),
+            ..Default::default()
+        });
+        input.witness_script = Some(desc.explicit_script().unwrap());
+        psbt.add_input(input);
+        psbt.add_output(pset::Output::from_txout(TxOut::default()));
+
+        let bytes = encode::serialize(&psbt);
+        do_test(&bytes);
+        // Single byte mutations
+        for i in 0..bytes.len() {
+            let mut mutated = bytes.clone();
+            mutated[i] ^= 0x01;
+            do_test(&mutated);
+        }
+    }
+}
@@ -0,0 +1,346 @@
+extern crate arbitrary;
+extern crate elements_miniscript as miniscript;
+
+use std::collections::HashMap;
+
+use arbitrary::{Arbitrary, Unstructured};
+use miniscript::descriptor::DescriptorType;
+use miniscript::elements::hashes::{hash160, ripemd160, sha256, Hash};
+use miniscript::elements::secp256k1_zkp::{self, KeyPair, Message, Scalar, Secp256k1, SecretKey};
+use miniscript::elements::sighash::{Prevouts, SigHashCache};
+use miniscript::elements::taproot::TapLeafHash;
+use miniscript::elements::{
+    self, confidential, EcdsaSigHashType, LockTime, PackedLockTime, SchnorrSigHashType, Sequence,
+};
+use miniscript::policy::concrete::{DescriptorCtx, Policy};
+use miniscript::{
+    bitcoin, hash256, Descriptor, ElementsSig, Interpreter, NoExt, Preimage32, Satisfier, Segwitv0,
+    TranslatePk, Translator, TxEnv,
+};
+
+// Timelocks which every descriptor with height based timelocks satisfies
+const SEQUENCE: u32 = 0xffff;
+const LOCK_TIME: u32 = 499_999_999;
+
+/// Derives keys and hash preimages from the names in the descriptor, and
+/// keeps those selected by the masks in translation order
+struct Keys {
+    secret_keys: HashMap<bitcoin::PublicKey, SecretKey>,
+    preimages: HashMap<Vec<u8>, Preimage32>,
+    signers: u64,
+    known_hashes: u64,
+    n_keys: usize,
+    n_hashes: usize,
+}
+
+fn preimage(name: &str) -> Preimage32 {
+    sha256::Hash::hash(name.as_bytes()).into_inner()
+}
+
+fn selected(mask: u64, idx: usize) -> bool {
+    mask >> (idx % 64) & 1 == 1
+}
+
+impl Keys {
+    fn new(signers: u64, known_hashes: u64) -> Self {
+        Keys {
+            secret_keys: HashMap::new(),
+            preimages: HashMap::new(),
+            signers,
+            known_hashes,
+            n_keys: 0,
+            n_hashes: 0,
+        }
+    }
+
+    fn add_preimage(&mut self, hash: &[u8], preimage: Preimage32) {
+        if selected(self.known_hashes, self.n_hashes) {
+            self.preimages.insert(hash.to_vec(), preimage);
+        }
+        self.n_hashes += 1;
+    }
+}
+
+impl Translator<String, bitcoin::PublicKey, ()> for Keys {
+    fn pk(&mut self, pk: &String) -> Result<bitcoin::PublicKey, ()> {
+        let secp = Secp256k1::signing_only();
+        let sk = SecretKey::from_slice(&preimage(pk)).map_err(|_| ())?;
+        let pk = bitcoin::PublicKey::new(secp256k1_zkp::PublicKey::from_secret_key(&secp, &sk));
+        if selected(self.signers, self.n_keys) {
+            self.secret_keys.insert(pk, sk);
+        }
+        self.n_keys += 1;
+        Ok(pk)
+    }
+
+    fn sha256(&mut self, name: &String) -> Result<sha256::Hash, ()> {
+        let preimage = preimage(name);
+        let hash = sha256::Hash::hash(&preimage);
+        self.add_preimage(&hash[..], preimage);
+        Ok(hash)
+    }
+
+    fn hash256(&mut self, name: &String) -> Result<hash256::Hash, ()> {
+        let preimage = preimage(name);
+        let hash = hash256::Hash::hash(&preimage);
+        self.add_preimage(&hash[..], preimage);
+        Ok(hash)
+    }
+
+    fn ripemd160(&mut self, name: &String) -> Result<ripemd160::Hash, ()> {
+        let preimage = preimage(name);
+        let hash = ripemd160::Hash::hash(&preimage);
+        self.add_preimage(&hash[..], preimage);
+        Ok(hash)
+    }
+
+    fn hash160(&mut self, name: &String) -> Result<hash160::Hash, ()> {
+        let preimage = preimage(name);
+        let hash = hash160::Hash::hash(&preimage);
+        self.add_preimage(&hash[..], preimage);
+        Ok(hash)
+    }
+}
+
+/// Signs for the selected keys of the descriptor and knows the selected
+/// preimages
+struct FuzzSatisfier<'a> {
+    secp: Secp256k1<secp256k1_zkp::All>,
+    keys: &'a Keys,
+    desc: &'a Descriptor<bitcoin::PublicKey, NoExt>,
+    tx: &'a elements::Transaction,
+    utxos: &'a [elements::TxOut],
+}
+
+impl<'a> FuzzSatisfier<'a> {
+    fn preimage<H: Hash>(&self, hash: &H) -> Option<Preimage32> {
+        self.keys.preimages.get(&hash[..].to_vec()).cloned()
+    }
+}
+
+impl<'a> Satisfier<bitcoin::PublicKey> for FuzzSatisfier<'a> {
+    fn lookup_ecdsa_sig(&self, pk: &bitcoin::PublicKey) -> Option<ElementsSig> {
+        let sk = self.keys.secret_keys.get(pk)?;
+        let script_code = self.desc.script_code().ok()?;
+        let cache = SigHashCache::new(self.tx);
+        let sighash = match self.desc.desc_type() {
+            DescriptorType::Bare
+            | DescriptorType::Sh
+            | DescriptorType::Pkh
+            | DescriptorType::ShSortedMulti => {
+                cache.legacy_sighash(0, &script_code, EcdsaSigHashType::All)
+            }
+            _ => SigHashCache::new(self.tx).segwitv0_sighash(
+                0,
+                &script_code,
+                self.utxos[0].value,
+                EcdsaSigHashType::All,
+            ),
+        };
+        let msg = Message::from_slice(&sighash[..]).unwrap();
+        Some((self.secp.sign_ecdsa(&msg, sk), EcdsaSigHashType::All))
+    }
+
+    fn lookup_tap_leaf_script_sig(
+        &self,
+        pk: &bitcoin::PublicKey,
+        leaf_hash: &TapLeafHash,
+    ) -> Option<elements::SchnorrSig> {
+        let sk = self.keys.secret_keys.get(pk)?;
+        let sighash = SigHashCache::new(self.tx)
+            .taproot_script_spend_signature_hash(
+                0,
+                &Prevouts::All(self.utxos),
+                *leaf_hash,
+                SchnorrSigHashType::Default,
+                elements::BlockHash::all_zeros(),
+            )
+            .unwrap();
+        let msg = Message::from_slice(&sighash[..]).unwrap();
+        let keypair = KeyPair::from_secret_key(&self.secp, sk);
+        Some(elements::SchnorrSig {
+            sig: self.secp.sign_schnorr_no_aux_rand(&msg, &keypair),
+            hash_ty: SchnorrSigHashType::Default,
+        })
+    }
+
+    fn lookup_tap_key_spend_sig(&self) -> Option<elements::SchnorrSig> {
+        let tr = match *self.desc {
+            Descriptor::Tr(ref tr) => tr,
+            _ => return None,
+        };
+        let sk = self.keys.secret_keys.get(tr.internal_key())?;
+        let tweak = Scalar::from_be_bytes(tr.spend_info().tap_tweak().into_inner()).ok()?;
+        let keypair = KeyPair::from_secret_key(&self.secp, sk)
+            .add_xonly_tweak(&self.secp, &tweak)
+            .ok()?;
+        let sighash = SigHashCache::new(self.tx)
+            .taproot_key_spend_signature_hash(
+                0,
+                &Prevouts::All(self.utxos),
+                SchnorrSigHashType::Default,
+                elements::BlockHash::all_zeros(),
+            )
+            .unwrap();
+        let msg = Message::from_slice(&sighash[..]).unwrap();
+        Some(elements::SchnorrSig {
+            sig: self.secp.sign_schnorr_no_aux_rand(&msg, &keypair),
+            hash_ty: SchnorrSigHashType::Default,
+        })
+    }
+
+    fn lookup_sha256(&self, hash: &sha256::Hash) -> Option<Preimage32> {
+        self.preimage(hash)
+    }
+
+    fn lookup_hash256(&self, hash: &hash256::Hash) -> Option<Preimage32> {
+        self.preimage(hash)
+    }
+
+    fn lookup_ripemd160(&self, hash: &ripemd160::Hash) -> Option<Preimage32> {
+        self.preimage(hash)
+    }
+
+    fn lookup_hash160(&self, hash: &hash160::Hash) -> Option<Preimage32> {
+        self.preimage(hash)
+    }
+
+    fn check_older(&self, n: Sequence) -> bool {
+        <Sequence as Satisfier<bitcoin::PublicKey>>::check_older(&Sequence(SEQUENCE), n)
+    }
+
+    fn check_after(&self, n: LockTime) -> bool {
+        let lock_time = LockTime::from_consensus(LOCK_TIME);
+        <LockTime as Satisfier<bitcoin::PublicKey>>::check_after(&lock_time, n)
+    }
+}
+
+/// An arbitrary policy, the descriptor to compile it into and the keys and
+/// preimages known to the satisfier
+fn arbitrary_input(
+    u: &mut Unstructured,
+) -> arbitrary::Result<(Policy<String>, DescriptorCtx<String>, Keys)> {
+    let policy = Policy::arbitrary(u)?;
+    let desc_ctx = match u.int_in_range(0..=4u8)? {
+        0 => DescriptorCtx::Bare,
+        1 => DescriptorCtx::Sh,
+        2 => DescriptorCtx::Wsh,
+        3 => DescriptorCtx::ShWsh,
+        _ => DescriptorCtx::Tr(Some(u.arbitrary()?)),
+    };
+    Ok((policy, desc_ctx, Keys::new(u.arbitrary()?, u.arbitrary()?)))
+}
+
+fn do_test(data: &[u8]) {
+    let mut u = Unstructured::new(data);
+    let (policy, desc_ctx, mut keys) = match arbitrary_input(&mut u) {
+        Ok(input) => input,
+        Err(_) => return,
+    };
+    let desc = match policy.compile_to_descriptor::<Segwitv0>(desc_ctx) {
+        Ok(desc) => desc,
+        Err(_) => return,
+    };
+    satisfy_interpret(&desc, &mut keys);
+}
+
+fn satisfy_interpret(desc: &Descriptor<String, NoExt>, keys: &mut Keys) {
+    let desc = match desc.translate_pk(keys) {
+        Ok(desc) => desc,
+        Err(_) => return,
+    };
+    if desc.desc_type() == DescriptorType::Cov {
+        return; // needs covenant lookups
+    }
+
+    let utxos = vec![elements::TxOut {
+        script_pubkey: desc.script_pubkey(),
+        value: confidential::Value::Explicit(100_000),
+        ..Default::default()
+    }];
+    let mut tx = elements::Transaction {
+        version: 2,
+        lock_time: PackedLockTime(LOCK_TIME),
+        input: vec![elements::TxIn {
+            sequence: Sequence(SEQUENCE),
+            ..Default::default()
+        }],
+        output: vec![elements::TxOut::default()],
+    };
+    let (witness, script_sig) = {
+        let satisfier = FuzzSatisfier {
+            secp: Secp256k1::new(),
+            keys,
+            desc: &desc,
+            tx: &tx,
+            utxos: &utxos,
+        };
+        match desc.get_satisfaction(&satisfier) {
+            Ok(sat) => sat,
+            Err(_) => return,
+        }
+    };
+    tx.input[0].script_sig = script_sig;
+    tx.input[0].witness.script_witness = witness;
+
+    // Every produced satisfaction must pass the interpreter
+    let secp = Secp256k1::verification_only();
+    let txin = &tx.input[0];
+    let interpreter = Interpreter::from_txdata(
+        &utxos[0].script_pubkey,
+        &txin.script_sig,
+        &txin.witness.script_witness,
+        txin.sequence,
+        LockTime::from(tx.lock_time),
+    )
+    .expect("satisfaction is decoded by the interpreter");
+    let env = TxEnv::new(&tx, &utxos, 0).expect("input and utxos match");
+    for elem in interpreter.iter(&secp, &env, elements::BlockHash::all_zeros()) {
+        elem.expect("satisfaction is accepted by the interpreter");
+    }
+}
+
+#[cfg(feature = "afl")]
+extern crate afl;
+#[cfg(feature = "afl")]
+fn main() {
+    afl::read_stdio_bytes(|data| {
+        do_test(&data);
+    });
+}
+
+#[cfg(feature = "honggfuzz")]
+#[macro_use]
+extern crate honggfuzz;
+#[cfg(feature = "honggfuzz")]
+fn main() {
+    loop {
+        fuzz!(|data| {
+            do_test(data);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn check_desc(s: &str, signers: u64, known_hashes: u64) {
+        let desc = Descriptor::<String, NoExt>::from_str(s).unwrap();
+        satisfy_interpret(&desc, &mut Keys::new(signers, known_hashes));
+    }
+
+    #[test]
+    fn test() {
+        do_test(b"");
+        do_test(b"\x09\x02\x01\x02\x02\x02\x03\x02\xff\xff");
+        check_desc("elwsh(or_d(pk(A),and_v(v:pk(B),older(144))))", !0, !0);
+        check_desc("elwsh(or_d(pk(A),and_v(v:pk(B),older(144))))", 0b10, 0);
+        check_desc("elsh(and_v(v:pk(A),sha256(H)))", !0, !0);
+        check_desc("elpkh(A)", !0, 0);
+        check_desc("eltr(A)", !0, 0);
+        check_desc("eltr(A,{pk(B),and_v(v:pk(C),after(100))})", 0b100, 0);
+    }
+}
@@ -0,0 +1,124 @@
+// Miniscript
+// Written in 2023 by
+//     The Elements Miniscript developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Key Aliases
+//!
+//! Display adapter that replaces keys with human readable names, e.g. for
+//! audit reports of federation descriptors.
+//!
+
+use std::convert::Infallible;
+use std::fmt;
+
+use crate::{MiniscriptKey, TranslatePk, Translator};
+
+/// Displays a descriptor, miniscript or policy with keys replaced by aliases.
+///
+/// Keys for which the alias provider returns `None` are displayed as usual.
+/// The output is meant for humans and, depending on the aliases used, need
+/// not parse back into the original type.
+pub struct Aliased<'a, T, Pk> {
+    inner: &'a T,
+    alias: &'a dyn Fn(&Pk) -> Option<String>,
+}
+
+impl<'a, T, Pk> Aliased<'a, T, Pk> {
+    /// Wraps `inner`, looking up each of its keys with `alias`
+    pub fn new(inner: &'a T, alias: &'a dyn Fn(&Pk) -> Option<String>) -> Self {
+        Aliased { inner, alias }
+    }
+}
+
+impl<'a, T, Pk> fmt::Display for Aliased<'a, T, Pk>
+where
+    Pk: MiniscriptKey,
+    T: TranslatePk<Pk, String>,
+    T::Output: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut t = AliasTranslator(self.alias);
+        match self.inner.translate_pk(&mut t) {
+            Ok(aliased) => fmt::Display::fmt(&aliased, f),
+            Err(e) => match e {},
+        }
+    }
+}
+
+struct AliasTranslator<'a, Pk>(&'a dyn Fn(&Pk) -> Option<String>);
+
+impl<'a, Pk: MiniscriptKey> Translator<Pk, String, Infallible> for AliasTranslator<'a, Pk> {
+    fn pk(&mut self, pk: &Pk) -> Result<String, Infallible> {
+        Ok((self.0)(pk).unwrap_or_else(|| pk.to_string()))
+    }
+
+    fn sha256(&mut self, sha256: &Pk::Sha256) -> Result<String, Infallible> {
+        Ok(sha256.to_string())
+    }
+
+    fn hash256(&mut self, hash256: &Pk::Hash256) -> Result<String, Infallible> {
+        Ok(hash256.to_string())
+    }
+
+    fn ripemd160(&mut self, ripemd160: &Pk::Ripemd160) -> Result<String, Infallible> {
+        Ok(ripemd160.to_string())
+    }
+
+    fn hash160(&mut self, hash160: &Pk::Hash160) -> Result<String, Infallible> {
+        Ok(hash160.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use bitcoin::PublicKey;
+
+    use super::*;
+    use crate::policy::{concrete, Liftable};
+    use crate::{Descriptor, NoExt};
+
+    #[test]
+    fn aliased_display() {
+        let alice = "020202020202020202020202020202020202020202020202020202020202020202";
+        let bob = "03a0434d9e47f3c86235477c7b1ae6ae5d3442d49b1943c2b752a68e2a47e247c7";
+        let alias = |pk: &PublicKey| {
+            if pk.to_string() == alice {
+                Some(String::from("alice"))
+            } else {
+                None
+            }
+        };
+
+        let desc =
+            Descriptor::<PublicKey, NoExt>::from_str(&format!("elwsh(multi(1,{},{}))", alice, bob))
+                .unwrap();
+        assert_eq!(
+            format!("{:#}", Aliased::new(&desc, &alias)),
+            format!("elwsh(multi(1,alice,{}))", bob)
+        );
+
+        let policy =
+            concrete::Policy::<PublicKey>::from_str(&format!("or(pk({}),pk({}))", bob, alice))
+                .unwrap();
+        assert_eq!(
+            Aliased::new(&policy, &alias).to_string(),
+            format!("or(1@pk({}),1@pk(alice))", bob)
+        );
+        assert_eq!(
+            Aliased::new(&policy.lift().unwrap(), &alias).to_string(),
+            format!("or(pk({}),pk(alice))", bob)
+        );
+    }
+}
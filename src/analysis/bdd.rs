@@ -0,0 +1,407 @@
+// Miniscript
+// Written in 2023 by
+//     The Elements Miniscript developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Binary Decision Diagrams
+//!
+//! Reduced ordered binary decision diagrams of semantic policies, over
+//! literals for the keys, hashes and timelocks of the policies. Unlike
+//! comparing normalized policies, they answer equivalence and implication
+//! queries exactly, e.g. `or(and(A,B),and(A,C))` is equivalent to
+//! `and(A,or(B,C))`.
+//!
+//! Timelocks are not independent literals: `after(200)` implies
+//! `after(100)`, and timelocks in blocks and in seconds exclude each other.
+//! Queries take this into account.
+//!
+//! The size of a BDD may be exponential in the size of its policy, so all
+//! BDDs of a [`BddManager`] are bounded by a total number of nodes.
+//!
+
+use std::collections::{BTreeMap, HashMap};
+use std::{error, fmt};
+
+use elements::{LockTime, PackedLockTime, Sequence};
+
+use crate::descriptor::SpendHash;
+use crate::policy::semantic::Policy;
+use crate::MiniscriptKey;
+
+/// A literal of a policy
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Literal<Pk: MiniscriptKey> {
+    /// A signature of the key
+    Key(Pk),
+    /// A preimage of the hash
+    Hash(SpendHash<Pk>),
+    /// An absolute timelock
+    After(PackedLockTime),
+    /// A relative timelock
+    Older(Sequence),
+}
+
+/// Error building a BDD
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BddError {
+    /// The manager would hold more nodes than its limit
+    NodeLimitExceeded(usize),
+}
+
+impl fmt::Display for BddError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            BddError::NodeLimitExceeded(n) => write!(f, "BDD exceeds the limit of {} nodes", n),
+        }
+    }
+}
+
+impl error::Error for BddError {}
+
+/// A BDD in a [`BddManager`]. BDDs are canonical: two BDDs of the same
+/// manager are equal if and only if they represent the same function of the
+/// literals.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Bdd(usize);
+
+impl Bdd {
+    /// The unsatisfiable BDD
+    pub const FALSE: Bdd = Bdd(0);
+    /// The trivially satisfiable BDD
+    pub const TRUE: Bdd = Bdd(1);
+}
+
+// A decision on `var`, going to `lo` when it is false and `hi` when true
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Node {
+    var: usize,
+    lo: usize,
+    hi: usize,
+}
+
+// Terminals come after every literal in the order
+const TERMINAL_VAR: usize = usize::max_value();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Op {
+    And,
+    Or,
+}
+
+/// Builds and queries BDDs sharing their nodes and literals
+#[derive(Debug, Clone)]
+pub struct BddManager<Pk: MiniscriptKey> {
+    literals: Vec<Literal<Pk>>,
+    vars: BTreeMap<Literal<Pk>, usize>,
+    nodes: Vec<Node>,
+    unique: HashMap<Node, usize>,
+    apply_memo: HashMap<(Op, usize, usize), usize>,
+    not_memo: HashMap<usize, usize>,
+    node_limit: usize,
+}
+
+impl<Pk: MiniscriptKey> BddManager<Pk> {
+    /// A manager holding at most `node_limit` nodes, terminals included
+    pub fn new(node_limit: usize) -> Self {
+        let terminal = Node {
+            var: TERMINAL_VAR,
+            lo: 0,
+            hi: 0,
+        };
+        BddManager {
+            literals: vec![],
+            vars: BTreeMap::new(),
+            nodes: vec![terminal, Node { hi: 1, ..terminal }],
+            unique: HashMap::new(),
+            apply_memo: HashMap::new(),
+            not_memo: HashMap::new(),
+            node_limit,
+        }
+    }
+
+    /// Number of nodes of all BDDs of the manager, terminals included
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// The literals of the BDDs, in their order in the diagrams
+    pub fn literals(&self) -> &[Literal<Pk>] {
+        &self.literals
+    }
+
+    fn node(&mut self, var: usize, lo: usize, hi: usize) -> Result<usize, BddError> {
+        if lo == hi {
+            return Ok(lo);
+        }
+        let node = Node { var, lo, hi };
+        if let Some(&id) = self.unique.get(&node) {
+            return Ok(id);
+        }
+        if self.nodes.len() >= self.node_limit {
+            return Err(BddError::NodeLimitExceeded(self.node_limit));
+        }
+        self.nodes.push(node);
+        self.unique.insert(node, self.nodes.len() - 1);
+        Ok(self.nodes.len() - 1)
+    }
+
+    /// The BDD of a single literal
+    pub fn literal(&mut self, lit: Literal<Pk>) -> Result<Bdd, BddError> {
+        let var = match self.vars.get(&lit) {
+            Some(&var) => var,
+            None => {
+                self.literals.push(lit.clone());
+                self.vars.insert(lit, self.literals.len() - 1);
+                self.literals.len() - 1
+            }
+        };
+        self.node(var, 0, 1).map(Bdd)
+    }
+
+    fn apply(&mut self, op: Op, a: usize, b: usize) -> Result<usize, BddError> {
+        let (absorbing, neutral) = match op {
+            Op::And => (0, 1),
+            Op::Or => (1, 0),
+        };
+        if a == absorbing || b == absorbing {
+            return Ok(absorbing);
+        } else if a == neutral || a == b {
+            return Ok(b);
+        } else if b == neutral {
+            return Ok(a);
+        }
+        let key = (op, a.min(b), a.max(b));
+        if let Some(&id) = self.apply_memo.get(&key) {
+            return Ok(id);
+        }
+        let (na, nb) = (self.nodes[a], self.nodes[b]);
+        let var = na.var.min(nb.var);
+        let (alo, ahi) = if na.var == var {
+            (na.lo, na.hi)
+        } else {
+            (a, a)
+        };
+        let (blo, bhi) = if nb.var == var {
+            (nb.lo, nb.hi)
+        } else {
+            (b, b)
+        };
+        let lo = self.apply(op, alo, blo)?;
+        let hi = self.apply(op, ahi, bhi)?;
+        let id = self.node(var, lo, hi)?;
+        self.apply_memo.insert(key, id);
+        Ok(id)
+    }
+
+    /// The conjunction of `a` and `b`
+    pub fn and(&mut self, a: Bdd, b: Bdd) -> Result<Bdd, BddError> {
+        self.apply(Op::And, a.0, b.0).map(Bdd)
+    }
+
+    /// The disjunction of `a` and `b`
+    pub fn or(&mut self, a: Bdd, b: Bdd) -> Result<Bdd, BddError> {
+        self.apply(Op::Or, a.0, b.0).map(Bdd)
+    }
+
+    /// The negation of `a`
+    pub fn not(&mut self, a: Bdd) -> Result<Bdd, BddError> {
+        if a.0 <= 1 {
+            return Ok(Bdd(1 - a.0));
+        }
+        if let Some(&id) = self.not_memo.get(&a.0) {
+            return Ok(Bdd(id));
+        }
+        let n = self.nodes[a.0];
+        let lo = self.not(Bdd(n.lo))?;
+        let hi = self.not(Bdd(n.hi))?;
+        let id = self.node(n.var, lo.0, hi.0)?;
+        self.not_memo.insert(a.0, id);
+        Ok(Bdd(id))
+    }
+
+    /// The BDD of `policy`
+    pub fn from_policy(&mut self, policy: &Policy<Pk>) -> Result<Bdd, BddError> {
+        match *policy {
+            Policy::Unsatisfiable => Ok(Bdd::FALSE),
+            Policy::Trivial => Ok(Bdd::TRUE),
+            Policy::Key(ref pk) => self.literal(Literal::Key(pk.clone())),
+            Policy::After(t) => self.literal(Literal::After(t)),
+            Policy::Older(t) => self.literal(Literal::Older(t)),
+            Policy::Sha256(ref h) => self.literal(Literal::Hash(SpendHash::Sha256(h.clone()))),
+            Policy::Hash256(ref h) => self.literal(Literal::Hash(SpendHash::Hash256(h.clone()))),
+            Policy::Ripemd160(ref h) => {
+                self.literal(Literal::Hash(SpendHash::Ripemd160(h.clone())))
+            }
+            Policy::Hash160(ref h) => self.literal(Literal::Hash(SpendHash::Hash160(h.clone()))),
+            Policy::Ext(ref e) => match *e {},
+            Policy::Threshold(k, ref subs) => {
+                // at_least[j]: at least j of the subs so far are satisfied
+                let mut at_least = vec![Bdd::FALSE; k + 1];
+                at_least[0] = Bdd::TRUE;
+                for sub in subs {
+                    let sub = self.from_policy(sub)?;
+                    for j in (1..=k).rev() {
+                        let with_sub = self.and(sub, at_least[j - 1])?;
+                        at_least[j] = self.or(at_least[j], with_sub)?;
+                    }
+                }
+                Ok(at_least[k])
+            }
+        }
+    }
+
+    // The relations between the timelock literals: a timelock implies the
+    // smaller ones of the same unit and excludes those of the other unit
+    fn timelock_constraints(&mut self) -> Result<Bdd, BddError> {
+        fn unit_value<Pk: MiniscriptKey>(lit: &Literal<Pk>) -> Option<(bool, bool, u32)> {
+            match *lit {
+                Literal::After(t) => match LockTime::from(t) {
+                    LockTime::Blocks(h) => Some((false, false, h.to_consensus_u32())),
+                    LockTime::Seconds(s) => Some((false, true, s.to_consensus_u32())),
+                },
+                Literal::Older(t) => Some((true, t.is_time_locked(), t.0 & 0xffff)),
+                Literal::Key(..) | Literal::Hash(..) => None,
+            }
+        }
+
+        let timelocks: Vec<_> = self
+            .literals
+            .iter()
+            .enumerate()
+            .filter_map(|(var, lit)| unit_value(lit).map(|uv| (var, uv)))
+            .collect();
+        let mut constraints = Bdd::TRUE;
+        for &(a, (a_rel, a_secs, a_value)) in &timelocks {
+            for &(b, (b_rel, b_secs, b_value)) in &timelocks {
+                if a == b || a_rel != b_rel || (a_secs == b_secs && a_value < b_value) {
+                    continue;
+                }
+                let lit_a = Bdd(self.node(a, 0, 1)?);
+                let not_a = self.not(lit_a)?;
+                let lit_b = Bdd(self.node(b, 0, 1)?);
+                let constraint = if a_secs == b_secs {
+                    self.or(not_a, lit_b)?
+                } else {
+                    let not_b = self.not(lit_b)?;
+                    self.or(not_a, not_b)?
+                };
+                constraints = self.and(constraints, constraint)?;
+            }
+        }
+        Ok(constraints)
+    }
+
+    /// Whether `a` can be satisfied
+    pub fn is_satisfiable(&mut self, a: Bdd) -> Result<bool, BddError> {
+        let constraints = self.timelock_constraints()?;
+        Ok(self.and(constraints, a)? != Bdd::FALSE)
+    }
+
+    /// Whether every satisfaction of `a` satisfies `b`
+    pub fn implies(&mut self, a: Bdd, b: Bdd) -> Result<bool, BddError> {
+        let not_b = self.not(b)?;
+        let counterexample = self.and(a, not_b)?;
+        Ok(!self.is_satisfiable(counterexample)?)
+    }
+
+    /// Whether `a` and `b` have the same satisfactions
+    pub fn equivalent(&mut self, a: Bdd, b: Bdd) -> Result<bool, BddError> {
+        Ok(self.implies(a, b)? && self.implies(b, a)?)
+    }
+}
+
+/// Whether every satisfaction of `a` satisfies `b`, with BDDs of at most
+/// `node_limit` nodes
+pub fn implies<Pk: MiniscriptKey>(
+    a: &Policy<Pk>,
+    b: &Policy<Pk>,
+    node_limit: usize,
+) -> Result<bool, BddError> {
+    let mut manager = BddManager::new(node_limit);
+    let a = manager.from_policy(a)?;
+    let b = manager.from_policy(b)?;
+    manager.implies(a, b)
+}
+
+/// Whether `a` and `b` have the same satisfactions, with BDDs of at most
+/// `node_limit` nodes
+pub fn equivalent<Pk: MiniscriptKey>(
+    a: &Policy<Pk>,
+    b: &Policy<Pk>,
+    node_limit: usize,
+) -> Result<bool, BddError> {
+    let mut manager = BddManager::new(node_limit);
+    let a = manager.from_policy(a)?;
+    let b = manager.from_policy(b)?;
+    manager.equivalent(a, b)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn policy(s: &str) -> Policy<String> {
+        Policy::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn bdd_equivalence() {
+        let a = policy("or(and(pk(A),pk(B)),and(pk(A),pk(C)))");
+        let b = policy("and(pk(A),or(pk(B),pk(C)))");
+        assert_eq!(equivalent(&a, &b, 1000), Ok(true));
+
+        let a = policy("thresh(2,pk(A),pk(B),pk(C))");
+        let b = policy("or(and(pk(A),pk(B)),and(pk(C),or(pk(A),pk(B))))");
+        assert_eq!(equivalent(&a, &b, 1000), Ok(true));
+        assert_eq!(implies(&policy("and(pk(A),pk(B))"), &a, 1000), Ok(true));
+        assert_eq!(implies(&a, &policy("pk(A)"), 1000), Ok(false));
+
+        let mut manager = BddManager::new(1000);
+        let x = manager.from_policy(&a).unwrap();
+        let y = manager.from_policy(&b).unwrap();
+        assert_eq!(x, y);
+        let too_many = Policy::Threshold(4, vec![Policy::Key("A".to_owned())]);
+        assert_eq!(manager.from_policy(&too_many), Ok(Bdd::FALSE));
+    }
+
+    #[test]
+    fn bdd_timelocks() {
+        let a = policy("and(pk(A),after(200))");
+        let b = policy("and(pk(A),after(100))");
+        assert_eq!(implies(&a, &b, 1000), Ok(true));
+        assert_eq!(implies(&b, &a, 1000), Ok(false));
+
+        let mixed = policy("and(after(100),after(500000000))");
+        let mut manager = BddManager::new(1000);
+        let mixed = manager.from_policy(&mixed).unwrap();
+        assert_eq!(manager.is_satisfiable(mixed), Ok(false));
+
+        // Relative timelocks do not imply absolute ones
+        assert_eq!(
+            implies(&policy("older(200)"), &policy("after(100)"), 1000),
+            Ok(false)
+        );
+    }
+
+    #[test]
+    fn bdd_node_limit() {
+        let keys: Vec<_> = (0..20).map(|i| format!("pk(K{})", i)).collect();
+        let large = policy(&format!("thresh(10,{})", keys.join(",")));
+        assert_eq!(
+            equivalent(&large, &large, 20),
+            Err(BddError::NodeLimitExceeded(20))
+        );
+        assert_eq!(equivalent(&large, &large, 10_000), Ok(true));
+    }
+}
@@ -0,0 +1,21 @@
+// Miniscript
+// Written in 2023 by
+//     The Elements Miniscript developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Policy Analysis
+//!
+//! Exact, but potentially expensive, analyses of spending policies. These
+//! are only available with the `bdd` feature.
+//!
+
+pub mod bdd;
@@ -0,0 +1,302 @@
+// Miniscript
+// Written in 2023 by
+//     The Elements Miniscript developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # BIP-39 Test Utilities
+//!
+//! Builds descriptors from a BIP-39 mnemonic and the standard BIP-84 and
+//! BIP-86 derivation paths, using Liquid's coin type, so that examples and
+//! integration tests can use realistic wallets. Only available with the
+//! `bip39-test-utils` feature.
+//!
+//! Mnemonics are parsed by the `bip39` crate, which checks their words
+//! against the English wordlist and verifies their checksum. Mnemonics and
+//! passphrases are normalized to NFKD before computing the seed.
+//!
+
+use std::{error, fmt};
+
+use bitcoin::secp256k1::Secp256k1;
+use bitcoin::util::bip32::{self, DerivationPath, ExtendedPrivKey, Fingerprint};
+use bitcoin::Network;
+use elements::secp256k1_zkp;
+
+pub use crate::descriptor::derivation::LIQUID_COIN_TYPE;
+use crate::descriptor::derivation::{self, BIP84_PURPOSE, BIP86_PURPOSE};
+use crate::descriptor::{ConversionError, DefiniteDescriptorKey, DescriptorPublicKey, KeyMap};
+use crate::{Descriptor, Error};
+
+/// A standard single-key descriptor template
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Template {
+    /// `elwpkh` descriptors at BIP-84 paths
+    Wpkh,
+    /// Key spend only `eltr` descriptors at BIP-86 paths
+    Tr,
+}
+
+impl Template {
+    /// The BIP-43 purpose of the derivation path
+    pub fn purpose(self) -> u32 {
+        match self {
+            Template::Wpkh => BIP84_PURPOSE,
+            Template::Tr => BIP86_PURPOSE,
+        }
+    }
+}
+
+impl fmt::Display for Template {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Template::Wpkh => f.write_str("elwpkh"),
+            Template::Tr => f.write_str("eltr"),
+        }
+    }
+}
+
+/// Errors of the BIP-39 wallet helpers
+#[derive(Debug)]
+pub enum MnemonicError {
+    /// The mnemonic has a wrong number of words, unknown words or a wrong
+    /// checksum
+    Mnemonic(::bip39::Error),
+    /// The keys of the wallet cannot be derived
+    Bip32(bip32::Error),
+    /// The descriptor cannot be derived at an index
+    Conversion(ConversionError),
+    /// The descriptor of the wallet cannot be parsed
+    Descriptor(Error),
+}
+
+impl fmt::Display for MnemonicError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            MnemonicError::Mnemonic(ref e) => write!(f, "invalid mnemonic: {}", e),
+            MnemonicError::Bip32(ref e) => fmt::Display::fmt(e, f),
+            MnemonicError::Conversion(ref e) => fmt::Display::fmt(e, f),
+            MnemonicError::Descriptor(ref e) => fmt::Display::fmt(e, f),
+        }
+    }
+}
+
+impl error::Error for MnemonicError {
+    fn cause(&self) -> Option<&dyn error::Error> {
+        match *self {
+            MnemonicError::Mnemonic(ref e) => Some(e),
+            MnemonicError::Bip32(ref e) => Some(e),
+            MnemonicError::Conversion(ref e) => Some(e),
+            MnemonicError::Descriptor(ref e) => Some(e),
+        }
+    }
+}
+
+#[doc(hidden)]
+impl From<::bip39::Error> for MnemonicError {
+    fn from(e: ::bip39::Error) -> MnemonicError {
+        MnemonicError::Mnemonic(e)
+    }
+}
+
+#[doc(hidden)]
+impl From<bip32::Error> for MnemonicError {
+    fn from(e: bip32::Error) -> MnemonicError {
+        MnemonicError::Bip32(e)
+    }
+}
+
+#[doc(hidden)]
+impl From<ConversionError> for MnemonicError {
+    fn from(e: ConversionError) -> MnemonicError {
+        MnemonicError::Conversion(e)
+    }
+}
+
+#[doc(hidden)]
+impl From<Error> for MnemonicError {
+    fn from(e: Error) -> MnemonicError {
+        MnemonicError::Descriptor(e)
+    }
+}
+
+/// The BIP-39 seed of `mnemonic` and `passphrase`
+///
+/// # Errors
+/// When the mnemonic does not have 12, 15, 18, 21 or 24 words of the English
+/// wordlist, or its checksum is wrong.
+pub fn mnemonic_to_seed(mnemonic: &str, passphrase: &str) -> Result<[u8; 64], MnemonicError> {
+    let mnemonic = ::bip39::Mnemonic::parse(mnemonic)?;
+    Ok(mnemonic.to_seed(passphrase))
+}
+
+/// A wallet derived from a BIP-39 mnemonic
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MnemonicWallet {
+    master: ExtendedPrivKey,
+}
+
+impl MnemonicWallet {
+    /// Create the wallet of `mnemonic` and `passphrase`, see
+    /// [`mnemonic_to_seed`]. Keys are encoded for `network`, which also
+    /// selects the coin type: [`LIQUID_COIN_TYPE`] for
+    /// [`Network::Bitcoin`] and `1` for all test networks.
+    pub fn new(mnemonic: &str, passphrase: &str, network: Network) -> Result<Self, MnemonicError> {
+        let seed = mnemonic_to_seed(mnemonic, passphrase)?;
+        let master = ExtendedPrivKey::new_master(network, &seed)?;
+        Ok(MnemonicWallet { master })
+    }
+
+    /// The master extended private key
+    pub fn master_xpriv(&self) -> ExtendedPrivKey {
+        self.master
+    }
+
+    /// The fingerprint of the master key
+    pub fn fingerprint(&self) -> Fingerprint {
+        self.master.fingerprint(&Secp256k1::signing_only())
+    }
+
+    /// The coin type of the derivation paths
+    pub fn coin_type(&self) -> u32 {
+        derivation::coin_type(self.master.network)
+    }
+
+    /// The account derivation path `m/purpose'/coin_type'/account'`
+    ///
+    /// # Errors
+    /// If `account` ≥ 2^31
+    pub fn account_path(
+        &self,
+        template: Template,
+        account: u32,
+    ) -> Result<DerivationPath, MnemonicError> {
+        Ok(derivation::account_path(
+            template.purpose(),
+            self.master.network,
+            account,
+        )?)
+    }
+
+    /// The descriptor of the receive or change addresses of `account`,
+    /// together with the private keys of the descriptor
+    pub fn descriptor(
+        &self,
+        template: Template,
+        account: u32,
+        change: bool,
+    ) -> Result<(Descriptor<DescriptorPublicKey>, KeyMap), MnemonicError> {
+        let path = self.account_path(template, account)?;
+        let xpriv = self.master.derive_priv(&Secp256k1::signing_only(), &path)?;
+        let s = format!(
+            "{}([{}/{}]{}/{}/*)",
+            template,
+            self.fingerprint(),
+            &path.to_string()[2..],
+            xpriv,
+            change as u32,
+        );
+        Ok(Descriptor::parse_descriptor(
+            &secp256k1_zkp::Secp256k1::signing_only(),
+            &s,
+        )?)
+    }
+
+    /// The descriptor of the `index`th receive or change address of
+    /// `account`
+    pub fn definite_descriptor(
+        &self,
+        template: Template,
+        account: u32,
+        change: bool,
+        index: u32,
+    ) -> Result<Descriptor<DefiniteDescriptorKey>, MnemonicError> {
+        let (desc, _) = self.descriptor(template, account, change)?;
+        Ok(desc.at_derivation_index(index)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use elements::hashes::hex::ToHex;
+
+    use super::*;
+    use crate::descriptor::DescriptorSecretKey;
+
+    const MNEMONIC: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+    #[test]
+    fn bip39_seed() {
+        // First test vector of BIP-39
+        assert_eq!(
+            mnemonic_to_seed(MNEMONIC, "TREZOR").unwrap().to_hex(),
+            "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e53495531f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04"
+        );
+        let wallet = MnemonicWallet::new(MNEMONIC, "TREZOR", Network::Bitcoin).unwrap();
+        assert_eq!(
+            wallet.master_xpriv().to_string(),
+            "xprv9s21ZrQH143K3h3fDYiay8mocZ3afhfULfb5GX8kCBdno77K4HiA15Tg23wpbeF1pLfs1c5SPmYHrEpTuuRhxMwvKDwqdKiGJS9XFKzUsAF"
+        );
+
+        // Words and checksums are checked
+        let bad_checksum = MNEMONIC.replace("about", "abandon");
+        let unknown_word = MNEMONIC.replace("about", "abcdef");
+        for mnemonic in &["abandon about", &bad_checksum, &unknown_word] {
+            match mnemonic_to_seed(mnemonic, "") {
+                Err(MnemonicError::Mnemonic(_)) => {}
+                other => panic!("unexpected {:?}", other),
+            }
+        }
+
+        // Passphrases are normalized
+        assert_eq!(
+            mnemonic_to_seed(MNEMONIC, "p\u{e4}ssword").unwrap(),
+            mnemonic_to_seed(MNEMONIC, "pa\u{308}ssword").unwrap()
+        );
+    }
+
+    #[test]
+    fn wallet_descriptors() {
+        let wallet = MnemonicWallet::new(MNEMONIC, "", Network::Bitcoin).unwrap();
+        let (desc, keymap) = wallet.descriptor(Template::Wpkh, 0, false).unwrap();
+        let desc_str = desc.to_string();
+        assert!(desc_str.starts_with(&format!(
+            "elwpkh([{}/84'/1776'/0']xpub",
+            wallet.fingerprint()
+        )));
+        assert!(desc_str.contains("/0/*)#"));
+        assert_eq!(keymap.len(), 1);
+        let sk = keymap.values().next().unwrap();
+        match *sk {
+            DescriptorSecretKey::XPrv(ref xkey) => {
+                assert_eq!(xkey.derivation_path.to_string(), "m/0");
+            }
+            _ => panic!("expected an extended private key"),
+        }
+
+        let (tr, _) = wallet.descriptor(Template::Tr, 1, true).unwrap();
+        assert!(tr.to_string().contains("/86'/1776'/1']xpub"));
+        assert!(tr.to_string().contains("/1/*)#"));
+
+        // Test networks use coin type 1
+        let testnet = MnemonicWallet::new(MNEMONIC, "", Network::Testnet).unwrap();
+        let desc = testnet
+            .definite_descriptor(Template::Wpkh, 0, false, 5)
+            .unwrap();
+        assert!(desc.to_string().contains("/84'/1'/0']tpub"));
+        assert_eq!(
+            desc,
+            Descriptor::<DefiniteDescriptorKey>::from_str(&desc.to_string()).unwrap()
+        );
+    }
+}
@@ -0,0 +1,413 @@
+// Miniscript
+// Written in 2023 by
+//     The Elements Miniscript developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Confidential Outputs
+//!
+//! Size estimation for Elements transaction outputs. Together with
+//! [`crate::Descriptor::max_satisfaction_weight`] this allows a wallet to
+//! compute fees for a transaction before blinding it, e.g. when deciding
+//! on the amount of a change output.
+//!
+//! Also contains helpers for converting between unconfidential and
+//! confidential addresses, and for their compact QR code representation.
+//!
+
+use elements::address::Payload;
+use elements::{secp256k1_zkp, Address, AddressParams};
+
+use crate::miniscript::limits::WITNESS_SCALE_FACTOR;
+use crate::util::varint_len;
+use crate::Error;
+
+/// Size in bytes of an explicit value: a prefix byte followed by a
+/// big-endian 64-bit amount
+pub const EXPLICIT_VALUE_SIZE: usize = 9;
+
+/// Size in bytes of a Pedersen commitment, blinded asset generator,
+/// or nonce public key
+pub const COMMITMENT_SIZE: usize = 33;
+
+/// Size in bytes of the rangeproof produced under current Liquid
+/// parameters: a 52-bit proof with exponent 0 and a minimum value of 1,
+/// as created by Elements Core
+pub const RANGEPROOF_SIZE: usize = 4174;
+
+/// Size in bytes of the surjection proof produced under current Liquid
+/// parameters. Elements Core proves against at most 3 inputs, which is
+/// also the size used whenever more inputs are available
+pub const SURJECTION_PROOF_SIZE: usize = 131;
+
+/// Estimate the weight in weight units that an output with a
+/// `script_len`-byte `script_pubkey` adds to a transaction.
+///
+/// If `blinded` is set, the value is committed to and a rangeproof and
+/// nonce are included. If `asset_blinded` is set, the asset is blinded
+/// and a surjection proof is included. Explicit assets and blinded assets
+/// have the same size, so only the surjection proof is accounted for.
+///
+/// The output witness is counted at witness discount, so this assumes that
+/// the transaction is serialized with witness data, which is always the
+/// case for transactions with blinded outputs.
+pub fn estimate_output_size(script_len: usize, blinded: bool, asset_blinded: bool) -> usize {
+    let (value_len, nonce_len, rangeproof_len) = if blinded {
+        (COMMITMENT_SIZE, COMMITMENT_SIZE, RANGEPROOF_SIZE)
+    } else {
+        (EXPLICIT_VALUE_SIZE, 1, 0)
+    };
+    let surjection_proof_len = if asset_blinded {
+        SURJECTION_PROOF_SIZE
+    } else {
+        0
+    };
+    let base_size = COMMITMENT_SIZE + value_len + nonce_len + varint_len(script_len) + script_len;
+    let witness_size = varint_len(surjection_proof_len)
+        + surjection_proof_len
+        + varint_len(rangeproof_len)
+        + rangeproof_len;
+    WITNESS_SCALE_FACTOR * base_size + witness_size
+}
+
+/// Size in bytes of the fields of an input besides its scriptSig: the
+/// previous outpoint and the sequence number
+const INPUT_BASE_SIZE: usize = 32 + 4 + 4;
+
+/// Size in bytes of the length prefixes of the empty issuance rangeproofs
+/// and peg-in witness in the witness of an input
+pub(crate) const INPUT_WITNESS_OVERHEAD: usize = 3;
+
+/// Size in bytes of the length prefixes of the empty surjection proof and
+/// rangeproof in the witness of an explicit output
+pub(crate) const OUTPUT_WITNESS_OVERHEAD: usize = 2;
+
+/// Size in bytes of the version, flags and locktime of a transaction
+const TX_BASE_SIZE: usize = 4 + 1 + 4;
+
+/// Estimate the weight in weight units that an input without issuance adds to
+/// a transaction, given the `satisfaction_weight` of its scriptSig and
+/// witness as returned by [`crate::Descriptor::max_satisfaction_weight`].
+///
+/// The satisfaction weight must include the length of the witness stack,
+/// which is not the case for pre-segwit descriptors. These need one more
+/// weight unit, see [`crate::Descriptor::max_confidential_tx_weight`].
+pub fn estimate_input_weight(satisfaction_weight: usize) -> usize {
+    WITNESS_SCALE_FACTOR * INPUT_BASE_SIZE + satisfaction_weight + INPUT_WITNESS_OVERHEAD
+}
+
+/// The weight of the explicit fee output of a transaction, which has an
+/// empty `script_pubkey`
+pub fn fee_output_weight() -> usize {
+    estimate_output_size(0, false, false)
+}
+
+/// Estimate the weight of a transaction with the input weights `inputs`,
+/// from [`estimate_input_weight`], and the output weights `outputs`, from
+/// [`estimate_output_size`]. The fee output must be one of the outputs.
+pub fn estimate_tx_weight<I, O>(inputs: I, outputs: O) -> usize
+where
+    I: IntoIterator<Item = usize>,
+    O: IntoIterator<Item = usize>,
+{
+    let (mut n_inputs, mut n_outputs, mut weight) = (0, 0, 0);
+    for input in inputs {
+        n_inputs += 1;
+        weight += input;
+    }
+    for output in outputs {
+        n_outputs += 1;
+        weight += output;
+    }
+    weight + WITNESS_SCALE_FACTOR * (TX_BASE_SIZE + varint_len(n_inputs) + varint_len(n_outputs))
+}
+
+/// Parse an address for the network given by `params`.
+///
+/// Besides the usual representation this also accepts the all-uppercase
+/// form of bech32 and blech32 addresses produced by [`to_qr_string`]. As
+/// for any segwit address, the checksum must be b(l)ech32 for witness
+/// version 0 and b(l)ech32m for later versions.
+pub fn parse_address(s: &str, params: &'static AddressParams) -> Result<Address, Error> {
+    match Address::parse_with_params(s, params) {
+        // Blech32 decoding does not accept the uppercase QR form
+        Err(..) if s.bytes().all(|b| !b.is_ascii_lowercase()) => {
+            Address::parse_with_params(&s.to_ascii_lowercase(), params)
+                .map_err(Error::ElementsAddrError)
+        }
+        res => res.map_err(Error::ElementsAddrError),
+    }
+}
+
+/// Parse an unconfidential address and blind it with `blinder`.
+///
+/// # Errors
+/// If `s` is already a confidential address, since it then most likely
+/// is not the address the caller meant.
+pub fn to_confidential(
+    s: &str,
+    blinder: secp256k1_zkp::PublicKey,
+    params: &'static AddressParams,
+) -> Result<Address, Error> {
+    let address = parse_address(s, params)?;
+    if address.is_blinded() {
+        return Err(Error::AddressBlinding {
+            expected_blinded: false,
+        });
+    }
+    Ok(address.to_confidential(blinder))
+}
+
+/// Parse a confidential address and strip its blinding key.
+///
+/// # Errors
+/// If `s` is not a confidential address.
+pub fn to_unconfidential(s: &str, params: &'static AddressParams) -> Result<Address, Error> {
+    let address = parse_address(s, params)?;
+    if !address.is_blinded() {
+        return Err(Error::AddressBlinding {
+            expected_blinded: true,
+        });
+    }
+    Ok(address.to_unconfidential())
+}
+
+/// Format an address for a QR code.
+///
+/// Bech32 and blech32 addresses are case insensitive and are uppercased, so
+/// that they can be encoded in the denser alphanumeric mode. Base58
+/// addresses are case sensitive and are returned as usual.
+pub fn to_qr_string(address: &Address) -> String {
+    match address.payload {
+        Payload::WitnessProgram { .. } => address.to_string().to_ascii_uppercase(),
+        Payload::PubkeyHash(..) | Payload::ScriptHash(..) => address.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use elements::confidential::{AssetBlindingFactor, ValueBlindingFactor};
+    use elements::encode::serialize;
+    use elements::secp256k1_zkp::{PublicKey, Secp256k1, SecretKey};
+    use elements::{confidential, Address, AddressParams, AssetId, Script, TxOut, TxOutSecrets};
+
+    use super::*;
+    use crate::{Descriptor, NoExt};
+
+    fn output_weight(txout: &TxOut) -> usize {
+        let mut txout = txout.clone();
+        let witness = serialize(&txout.witness);
+        txout.witness = Default::default();
+        4 * serialize(&txout).len() + witness.len()
+    }
+
+    #[test]
+    fn explicit_output_size() {
+        for len in &[0, 22, 34, 300] {
+            let txout = TxOut {
+                asset: confidential::Asset::Explicit(AssetId::default()),
+                value: confidential::Value::Explicit(1000),
+                nonce: confidential::Nonce::Null,
+                script_pubkey: Script::from(vec![0x51; *len]),
+                witness: Default::default(),
+            };
+            assert_eq!(
+                estimate_output_size(*len, false, false),
+                output_weight(&txout)
+            );
+        }
+    }
+
+    #[test]
+    fn confidential_output_size() {
+        let secp = Secp256k1::new();
+        let mut rng = actual_rand::thread_rng();
+        let pk = bitcoin::PublicKey::from_private_key(
+            &secp,
+            &bitcoin::PrivateKey::from_slice(&[1; 32], bitcoin::Network::Bitcoin).unwrap(),
+        );
+        let blinder = SecretKey::from_slice(&[3; 32]).unwrap();
+        let blinder = PublicKey::from_secret_key(&secp, &blinder);
+        let address = Address::p2wpkh(&pk, Some(blinder), &AddressParams::LIQUID);
+        let asset = AssetId::default();
+
+        for n_inputs in 1..5 {
+            let spent = vec![
+                TxOutSecrets::new(
+                    asset,
+                    AssetBlindingFactor::new(&mut rng),
+                    10_000,
+                    ValueBlindingFactor::new(&mut rng),
+                );
+                n_inputs
+            ];
+            let (txout, ..) = TxOut::new_not_last_confidential(
+                &mut rng,
+                &secp,
+                1000,
+                address.clone(),
+                asset,
+                &spent,
+            )
+            .unwrap();
+            let n = std::cmp::min(n_inputs, 3);
+            let surjection_proof_size = 2 + (n + 7) / 8 + 32 * (n + 1);
+            let expected = estimate_output_size(22, true, true) - SURJECTION_PROOF_SIZE
+                + surjection_proof_size;
+            assert_eq!(expected, output_weight(&txout));
+        }
+    }
+
+    #[test]
+    fn confidential_tx_weight() {
+        let secp = Secp256k1::new();
+        let mut rng = actual_rand::thread_rng();
+        let pk = bitcoin::PublicKey::from_private_key(
+            &secp,
+            &bitcoin::PrivateKey::from_slice(&[1; 32], bitcoin::Network::Bitcoin).unwrap(),
+        );
+        let blinder = SecretKey::from_slice(&[3; 32]).unwrap();
+        let blinder = PublicKey::from_secret_key(&secp, &blinder);
+        let desc =
+            Descriptor::<bitcoin::PublicKey, NoExt>::from_str(&format!("elwpkh({})", pk)).unwrap();
+        let address = desc
+            .blinded_address(blinder, &AddressParams::LIQUID)
+            .unwrap();
+        let asset = AssetId::default();
+
+        // Surjection proofs against 3 inputs have the estimated size
+        let spent = vec![
+            TxOutSecrets::new(
+                asset,
+                AssetBlindingFactor::new(&mut rng),
+                10_000,
+                ValueBlindingFactor::new(&mut rng),
+            );
+            3
+        ];
+        let mut output = vec![];
+        for _ in 0..2 {
+            let (txout, ..) = TxOut::new_not_last_confidential(
+                &mut rng,
+                &secp,
+                1000,
+                address.clone(),
+                asset,
+                &spent,
+            )
+            .unwrap();
+            output.push(txout);
+        }
+        output.push(TxOut::new_fee(100, asset));
+        let txin = elements::TxIn {
+            previous_output: elements::OutPoint::default(),
+            is_pegin: false,
+            script_sig: Script::new(),
+            sequence: elements::Sequence::MAX,
+            asset_issuance: Default::default(),
+            witness: elements::TxInWitness {
+                script_witness: vec![vec![0; 72], pk.to_bytes()],
+                ..Default::default()
+            },
+        };
+        let tx = elements::Transaction {
+            version: 2,
+            lock_time: elements::PackedLockTime::ZERO,
+            input: vec![txin; 3],
+            output,
+        };
+        assert_eq!(desc.max_confidential_tx_weight(3, 2).unwrap(), tx.weight());
+    }
+
+    #[test]
+    fn address_conversions() {
+        let secp = Secp256k1::new();
+        let blinder = PublicKey::from_secret_key(&secp, &SecretKey::from_slice(&[3; 32]).unwrap());
+        let other = PublicKey::from_secret_key(&secp, &SecretKey::from_slice(&[4; 32]).unwrap());
+        let params = &AddressParams::LIQUID;
+
+        for desc in &[
+            "elwpkh(020202020202020202020202020202020202020202020202020202020202020202)",
+            "elsh(wpkh(020202020202020202020202020202020202020202020202020202020202020202))",
+            "eltr(020202020202020202020202020202020202020202020202020202020202020202)",
+        ] {
+            let desc = Descriptor::<bitcoin::PublicKey, NoExt>::from_str(desc).unwrap();
+            let unconf = desc.address(params).unwrap();
+            let conf = desc.blinded_address(blinder, params).unwrap();
+
+            assert_eq!(
+                to_confidential(&unconf.to_string(), blinder, params).unwrap(),
+                conf
+            );
+            assert_eq!(
+                to_unconfidential(&conf.to_string(), params).unwrap(),
+                unconf
+            );
+            assert_eq!(
+                to_confidential(&conf.to_string(), blinder, params),
+                Err(Error::AddressBlinding {
+                    expected_blinded: false
+                })
+            );
+            assert_eq!(
+                to_unconfidential(&unconf.to_string(), params),
+                Err(Error::AddressBlinding {
+                    expected_blinded: true
+                })
+            );
+
+            for addr in &[&unconf, &conf] {
+                assert_eq!(&parse_address(&to_qr_string(addr), params).unwrap(), *addr);
+                assert!(parse_address(&addr.to_string(), &AddressParams::ELEMENTS).is_err());
+            }
+
+            desc.verify_address(&unconf, None).unwrap();
+            desc.verify_address(&conf, Some(blinder)).unwrap();
+            assert!(desc.verify_address(&conf, None).is_err());
+            assert!(desc.verify_address(&conf, Some(other)).is_err());
+        }
+    }
+
+    #[test]
+    fn address_qr_string() {
+        let desc = Descriptor::<bitcoin::PublicKey, NoExt>::from_str(
+            "elwpkh(020202020202020202020202020202020202020202020202020202020202020202)",
+        )
+        .unwrap();
+        let addr = desc.address(&AddressParams::LIQUID).unwrap();
+        assert_eq!(to_qr_string(&addr), addr.to_string().to_uppercase());
+
+        let desc = Descriptor::<bitcoin::PublicKey, NoExt>::from_str(
+            "elpkh(020202020202020202020202020202020202020202020202020202020202020202)",
+        )
+        .unwrap();
+        let addr = desc.address(&AddressParams::LIQUID).unwrap();
+        assert_eq!(to_qr_string(&addr), addr.to_string());
+    }
+
+    #[test]
+    fn address_wrong_checksum_variant() {
+        use bitcoin::bech32::{self, ToBase32, Variant};
+
+        // A v0 program must not be encoded with bech32m
+        let mut data = vec![bech32::u5::try_from_u8(0).unwrap()];
+        data.extend([1u8; 20].to_base32());
+        let s = bech32::encode("ex", data, Variant::Bech32m).unwrap();
+        assert_eq!(
+            parse_address(&s, &AddressParams::LIQUID)
+                .unwrap_err()
+                .to_string(),
+            "v0 witness program must use b(l)ech32 not b(l)ech32m"
+        );
+    }
+}
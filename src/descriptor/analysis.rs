@@ -0,0 +1,225 @@
+// Miniscript
+// Written in 2023 by
+//     The Elements Miniscript developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Descriptor Analysis
+//!
+//! A report of all the checks of [`Descriptor::sanity_check`] for every
+//! script of a descriptor, with the sizes and limits they are based on,
+//! instead of the first failing check only.
+//!
+
+use super::{Descriptor, ShInner, SortedMultiVec, WshInner};
+use crate::{AnalysisError, Extension, Miniscript, MiniscriptKey, ScriptContext, Terminal};
+
+/// The analysis of a descriptor, see [`Descriptor::analyze`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Analysis {
+    /// The analysis of each script of the descriptor: the witness or redeem
+    /// script, or the taproot leaves in the depth first order of
+    /// [`Tr::iter_scripts`](super::Tr::iter_scripts). Descriptors of a
+    /// single key have no script.
+    pub scripts: Vec<ScriptAnalysis>,
+    /// Upper bound on the weight of a satisfying witness, see
+    /// [`Descriptor::max_satisfaction_weight`], `None` if the descriptor
+    /// cannot be satisfied
+    pub max_satisfaction_weight: Option<usize>,
+}
+
+impl Analysis {
+    /// Whether no script has any issue
+    pub fn is_sane(&self) -> bool {
+        self.scripts.iter().all(|s| s.issues().is_empty())
+    }
+}
+
+/// The analysis of a script of a descriptor
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptAnalysis {
+    /// Index of the taproot leaf, `None` for other scripts
+    pub leaf: Option<usize>,
+    /// Whether every satisfaction requires a signature
+    pub requires_sig: bool,
+    /// Whether satisfactions cannot be malleated by third parties
+    pub non_malleable: bool,
+    /// Whether every spend path is within the script size, opcode and stack
+    /// limits of the script context
+    pub within_resource_limits: bool,
+    /// Whether a spend path combines height-based and time-based timelocks,
+    /// so can never be satisfied
+    pub mixed_timelocks: bool,
+    /// Whether a key appears more than once
+    pub repeated_keys: bool,
+    /// Size of the script, in bytes
+    pub script_size: usize,
+    /// Maximum number of executed opcodes counting towards the limit of 201,
+    /// `None` if the script cannot be satisfied
+    pub op_count: Option<usize>,
+    /// Maximum number of witness elements of a satisfaction, including the
+    /// script itself, `None` if the script cannot be satisfied
+    pub max_witness_elements: Option<usize>,
+    /// Maximum size of a satisfaction, in bytes, see
+    /// [`Miniscript::max_satisfaction_size`], `None` if the script cannot
+    /// be satisfied
+    pub max_satisfaction_size: Option<usize>,
+}
+
+impl ScriptAnalysis {
+    fn new<Pk, Ctx, Ext>(ms: &Miniscript<Pk, Ctx, Ext>, leaf: Option<usize>) -> Self
+    where
+        Pk: MiniscriptKey,
+        Ctx: ScriptContext,
+        Ext: Extension,
+    {
+        ScriptAnalysis {
+            leaf,
+            requires_sig: ms.requires_sig(),
+            non_malleable: ms.is_non_malleable(),
+            within_resource_limits: ms.within_resource_limits(),
+            mixed_timelocks: ms.has_mixed_timelocks(),
+            repeated_keys: ms.has_repeated_keys(),
+            script_size: ms.script_size(),
+            op_count: ms.ext.ops.op_count(),
+            max_witness_elements: ms.max_satisfaction_witness_elements().ok(),
+            max_satisfaction_size: ms.max_satisfaction_size().ok(),
+        }
+    }
+
+    fn sorted_multi<Pk: MiniscriptKey, Ctx: ScriptContext>(smv: &SortedMultiVec<Pk, Ctx>) -> Self {
+        // Sorting the keys does not change any of the properties
+        let ms: Miniscript<Pk, Ctx> =
+            Miniscript::from_ast(Terminal::Multi(smv.k, smv.pks.clone())).expect("Must typecheck");
+        Self::new(&ms, None)
+    }
+
+    /// The issues of the script, in the order [`Descriptor::sanity_check`]
+    /// checks them
+    pub fn issues(&self) -> Vec<AnalysisError> {
+        let mut issues = vec![];
+        if !self.requires_sig {
+            issues.push(AnalysisError::SiglessBranch);
+        }
+        if !self.non_malleable {
+            issues.push(AnalysisError::Malleable);
+        }
+        if !self.within_resource_limits {
+            issues.push(AnalysisError::BranchExceedResouceLimits);
+        }
+        if self.repeated_keys {
+            issues.push(AnalysisError::RepeatedPubkeys);
+        }
+        if self.mixed_timelocks {
+            issues.push(AnalysisError::HeightTimelockCombination);
+        }
+        issues
+    }
+}
+
+impl<Pk: MiniscriptKey, Ext: Extension> Descriptor<Pk, Ext> {
+    /// Analyze every script of the descriptor.
+    ///
+    /// Unlike [`Descriptor::sanity_check`], which fails on the first issue,
+    /// this reports all issues of all scripts, together with their sizes, so
+    /// that wallets can show which spend paths are affected and why.
+    pub fn analyze(&self) -> Analysis {
+        let scripts = match *self {
+            Descriptor::Bare(ref bare) => vec![ScriptAnalysis::new(bare.as_inner(), None)],
+            Descriptor::Pkh(..) | Descriptor::Wpkh(..) => vec![],
+            Descriptor::Wsh(ref wsh) => match *wsh.as_inner() {
+                WshInner::SortedMulti(ref smv) => vec![ScriptAnalysis::sorted_multi(smv)],
+                WshInner::Ms(ref ms) => vec![ScriptAnalysis::new(ms, None)],
+            },
+            Descriptor::Sh(ref sh) => match *sh.as_inner() {
+                ShInner::Wsh(ref wsh) => match *wsh.as_inner() {
+                    WshInner::SortedMulti(ref smv) => vec![ScriptAnalysis::sorted_multi(smv)],
+                    WshInner::Ms(ref ms) => vec![ScriptAnalysis::new(ms, None)],
+                },
+                ShInner::Wpkh(..) => vec![],
+                ShInner::SortedMulti(ref smv) => vec![ScriptAnalysis::sorted_multi(smv)],
+                ShInner::Ms(ref ms) => vec![ScriptAnalysis::new(ms, None)],
+            },
+            Descriptor::LegacyCSFSCov(ref cov) => vec![ScriptAnalysis::new(cov.to_ms(), None)],
+            Descriptor::Tr(ref tr) => tr
+                .iter_scripts()
+                .enumerate()
+                .map(|(leaf, (_, ms))| ScriptAnalysis::new(ms, Some(leaf)))
+                .collect(),
+            Descriptor::TrExt(ref tr) => tr
+                .iter_scripts()
+                .enumerate()
+                .map(|(leaf, (_, ms))| ScriptAnalysis::new(ms, Some(leaf)))
+                .collect(),
+        };
+        Analysis {
+            scripts,
+            max_satisfaction_weight: self.max_satisfaction_weight().ok(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::NoExt;
+
+    #[test]
+    fn analyze() {
+        let desc =
+            Descriptor::<String, NoExt>::from_str("elwsh(or_d(pk(A),and_v(v:pk(B),older(144))))")
+                .unwrap();
+        let analysis = desc.analyze();
+        assert!(analysis.is_sane());
+        assert_eq!(analysis.scripts.len(), 1);
+        let script = &analysis.scripts[0];
+        assert_eq!(script.leaf, None);
+        assert_eq!(script.script_size, 77);
+        assert_eq!(script.op_count, Some(6));
+        assert_eq!(script.max_witness_elements, Some(3));
+        assert!(analysis.max_satisfaction_weight.is_some());
+
+        // Every issue is reported, not only the first one
+        let desc = Descriptor::<String, NoExt>::from_str(
+            "elwsh(or_i(and_v(v:after(500000001),after(100)),and_v(v:pk(A),pk(A))))",
+        )
+        .unwrap();
+        let analysis = desc.analyze();
+        assert!(!analysis.is_sane());
+        assert_eq!(
+            analysis.scripts[0].issues(),
+            vec![
+                AnalysisError::SiglessBranch,
+                AnalysisError::RepeatedPubkeys,
+                AnalysisError::HeightTimelockCombination,
+            ]
+        );
+        assert!(desc.sanity_check().is_err());
+
+        // Taproot leaves are analyzed separately
+        let desc =
+            Descriptor::<String, NoExt>::from_str("eltr(A,{pk(B),and_v(v:pk(C),older(144))})")
+                .unwrap();
+        let analysis = desc.analyze();
+        let leaves: Vec<_> = analysis.scripts.iter().map(|s| s.leaf).collect();
+        assert_eq!(leaves, vec![Some(0), Some(1)]);
+        assert!(analysis.is_sane());
+        assert!(analysis.scripts[1].op_count > analysis.scripts[0].op_count);
+
+        let desc = Descriptor::<String, NoExt>::from_str("elwpkh(A)").unwrap();
+        assert!(desc.analyze().scripts.is_empty());
+        assert!(desc.analyze().is_sane());
+        let desc = Descriptor::<String, NoExt>::from_str("elsh(sortedmulti(2,A,B,C))").unwrap();
+        assert!(desc.analyze().is_sane());
+    }
+}
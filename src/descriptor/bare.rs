@@ -27,6 +27,8 @@ use super::ELMTS_STR;
 use crate::descriptor::checksum;
 use crate::expression::{self, FromTree};
 use crate::miniscript::context::ScriptContext;
+use crate::miniscript::limits::WITNESS_SCALE_FACTOR;
+use crate::miniscript::satisfy::{checked_ecdsa_sig, Timelocks};
 use crate::policy::{semantic, Liftable};
 use crate::util::{varint_len, witness_to_scriptsig};
 use crate::{
@@ -77,7 +79,7 @@ impl<Pk: MiniscriptKey> Bare<Pk> {
     /// When the descriptor is impossible to safisfy (ex: sh(OP_FALSE)).
     pub fn max_satisfaction_weight(&self) -> Result<usize, Error> {
         let scriptsig_len = self.ms.max_satisfaction_size()?;
-        Ok(4 * (varint_len(scriptsig_len) + scriptsig_len))
+        Ok(WITNESS_SCALE_FACTOR * (varint_len(scriptsig_len) + scriptsig_len))
     }
 }
 
@@ -104,9 +106,7 @@ impl<Pk: MiniscriptKey + ToPublicKey> Bare<Pk> {
     where
         S: Satisfier<Pk>,
     {
-        let ms = self.ms.satisfy(satisfier)?;
-        let script_sig = witness_to_scriptsig(&ms);
-        let witness = vec![];
+        let (witness, script_sig, _) = self.satisfy_timelocked(satisfier, false)?;
         Ok((witness, script_sig))
     }
 
@@ -117,10 +117,24 @@ impl<Pk: MiniscriptKey + ToPublicKey> Bare<Pk> {
     where
         S: Satisfier<Pk>,
     {
-        let ms = self.ms.satisfy_malleable(satisfier)?;
+        let (witness, script_sig, _) = self.satisfy_timelocked(satisfier, true)?;
+        Ok((witness, script_sig))
+    }
+
+    // Helper function for the satisfaction methods, also returning the
+    // timelocks of the chosen spend path
+    pub(super) fn satisfy_timelocked<S>(
+        &self,
+        satisfier: S,
+        allow_mall: bool,
+    ) -> Result<(Vec<Vec<u8>>, Script, Timelocks), Error>
+    where
+        S: Satisfier<Pk>,
+    {
+        let (ms, timelocks) = self.ms.satisfy_timelocked(satisfier, allow_mall)?;
         let script_sig = witness_to_scriptsig(&ms);
         let witness = vec![];
-        Ok((witness, script_sig))
+        Ok((witness, script_sig, timelocks))
     }
 }
 
@@ -224,7 +238,7 @@ impl<Pk: MiniscriptKey> Pkh<Pk> {
     /// sighash suffix. Includes the weight of the VarInts encoding the
     /// scriptSig and witness stack length.
     pub fn max_satisfaction_weight(&self) -> usize {
-        4 * (1 + 73 + BareCtx::pk_len(&self.pk))
+        WITNESS_SCALE_FACTOR * (1 + 73 + BareCtx::pk_len(&self.pk))
     }
 }
 
@@ -267,7 +281,7 @@ impl<Pk: MiniscriptKey + ToPublicKey> Pkh<Pk> {
     where
         S: Satisfier<Pk>,
     {
-        if let Some(sig) = satisfier.lookup_ecdsa_sig(&self.pk) {
+        if let Some(sig) = checked_ecdsa_sig(&satisfier, &self.pk)? {
             let sig_vec = elementssig_to_rawsig(&sig);
             let script_sig = script::Builder::new()
                 .push_slice(&sig_vec[..])
@@ -0,0 +1,823 @@
+// Miniscript
+// Written in 2023 by
+//     The Elements Miniscript developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Batch Derivation
+//!
+//! Derive the script pubkeys of a ranged descriptor at consecutive indices,
+//! as needed by wallet scanners.
+//!
+
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Range;
+
+use bitcoin::util::bip32::{self, ChildNumber};
+use elements::hashes::{hash160, sha256, Hash};
+use elements::opcodes::all::{
+    OP_CHECKSIG, OP_DUP, OP_EQUAL, OP_EQUALVERIFY, OP_HASH160, OP_PUSHBYTES_0, OP_PUSHBYTES_20,
+    OP_PUSHBYTES_32,
+};
+use elements::{secp256k1_zkp as secp256k1, Script};
+
+use super::{
+    aggregate_keys, ConversionError, DescriptorPublicKey, DescriptorType, SinglePubKey, Wildcard,
+};
+use crate::extensions::ParseableExt;
+use crate::{Descriptor, Extension, ToPublicKey, TranslatePk, Translator};
+
+/// A key of the descriptor, with all derivation steps but the wildcard done
+#[derive(Debug, Clone, Copy)]
+enum PartialKey {
+    /// A key without wildcard
+    Fixed(bitcoin::PublicKey),
+    /// The parent of the keys of an unhardened wildcard
+    Parent(bip32::ExtendedPubKey),
+}
+
+impl PartialKey {
+    fn new<C: secp256k1::Verification>(
+        secp: &secp256k1::Secp256k1<C>,
+        pk: &DescriptorPublicKey,
+    ) -> Result<Self, ConversionError> {
+        match *pk {
+            DescriptorPublicKey::Single(ref single) => match single.key {
+                SinglePubKey::FullKey(pk) => Ok(PartialKey::Fixed(pk)),
+                SinglePubKey::XOnly(xpk) => Ok(PartialKey::Fixed(xpk.to_public_key())),
+            },
+            DescriptorPublicKey::MultiXPub(..) => Err(ConversionError::MultiKey),
+            DescriptorPublicKey::MuSig(..) => unreachable!("split into its keys"),
+            DescriptorPublicKey::XPub(ref xpk) => {
+                let xpub = xpk
+                    .xkey
+                    .derive_pub(secp, &xpk.derivation_path)
+                    .map_err(|_| ConversionError::HardenedChild)?;
+                match xpk.wildcard {
+                    Wildcard::None => {
+                        Ok(PartialKey::Fixed(bitcoin::PublicKey::new(xpub.public_key)))
+                    }
+                    Wildcard::Unhardened => Ok(PartialKey::Parent(xpub)),
+                    Wildcard::Hardened => Err(ConversionError::HardenedChild),
+                }
+            }
+        }
+    }
+
+    // Any valid key will do as a placeholder, but the keys of the
+    // descriptor are unlikely to collide with other data of the script
+    fn placeholder(&self) -> bitcoin::PublicKey {
+        match *self {
+            PartialKey::Fixed(pk) => pk,
+            PartialKey::Parent(xpub) => bitcoin::PublicKey::new(xpub.public_key),
+        }
+    }
+
+    fn derive<C: secp256k1::Verification>(
+        &self,
+        secp: &secp256k1::Secp256k1<C>,
+        child: ChildNumber,
+    ) -> Result<bitcoin::PublicKey, ConversionError> {
+        match *self {
+            PartialKey::Fixed(pk) => Ok(pk),
+            PartialKey::Parent(xpub) => {
+                let xpub = xpub
+                    .ckd_pub(secp, child)
+                    .map_err(|_| ConversionError::HardenedChild)?;
+                Ok(bitcoin::PublicKey::new(xpub.public_key))
+            }
+        }
+    }
+}
+
+/// A key occurrence of the descriptor
+#[derive(Debug, Clone)]
+enum KeySlot {
+    /// A single key
+    Single(PartialKey),
+    /// The keys of a MuSig aggregate, which are aggregated once derived
+    MuSig(Vec<PartialKey>),
+}
+
+/// The position of a key push, or of a key hash push, in a script
+#[derive(Debug, Clone, Copy)]
+struct Patch {
+    /// The key slot whose key is pushed
+    slot: usize,
+    /// The offset of the pushed bytes
+    offset: usize,
+    /// Whether the hash160 of the key is pushed
+    hashed: bool,
+}
+
+/// An explicit script with the keys at known positions
+#[derive(Debug, Clone)]
+struct ScriptTemplate {
+    /// The script, patched in place at every index
+    script: Vec<u8>,
+    patches: Vec<Patch>,
+    /// Whether the keys are sorted as in `sortedmulti`, in which case the
+    /// patches are ordered by offset and receive the keys in sorted order
+    sorted: bool,
+    sort_buf: Vec<[u8; 33]>,
+    /// How the script is committed to by the script pubkey
+    wrap: DescriptorType,
+}
+
+/// How the script pubkey of a descriptor is computed from its derived keys
+#[derive(Debug, Clone)]
+enum SpkTemplate {
+    /// The script pubkey only commits to the hash of the single key
+    KeyHash(DescriptorType),
+    /// The script pubkey commits to a script containing the keys
+    Script(ScriptTemplate),
+    /// The derived descriptor is built to compute the script pubkey, for
+    /// descriptors whose script pubkey is not a hash of the key bytes, e.g.
+    /// taproot ones, and for scripts where the keys cannot be located
+    Rebuild,
+}
+
+impl SpkTemplate {
+    /// The template of `placeholder`, the descriptor with the keys of
+    /// `slots` replaced by [`PartialKey::placeholder`]
+    fn new<Ext: Extension + ParseableExt>(
+        placeholder: &Descriptor<bitcoin::PublicKey, Ext>,
+        slots: &[KeySlot],
+    ) -> Self {
+        let mut keys = Vec::with_capacity(slots.len());
+        for slot in slots {
+            match *slot {
+                KeySlot::Single(ref partial) => keys.push(partial.placeholder()),
+                KeySlot::MuSig(..) => return SpkTemplate::Rebuild,
+            }
+        }
+        let ty = placeholder.desc_type();
+        let sorted = match ty {
+            DescriptorType::Pkh | DescriptorType::Wpkh | DescriptorType::ShWpkh => {
+                return SpkTemplate::KeyHash(ty);
+            }
+            DescriptorType::Bare
+            | DescriptorType::Sh
+            | DescriptorType::Wsh
+            | DescriptorType::ShWsh => false,
+            DescriptorType::ShSortedMulti
+            | DescriptorType::WshSortedMulti
+            | DescriptorType::ShWshSortedMulti => true,
+            DescriptorType::LegacyPegin
+            | DescriptorType::Pegin
+            | DescriptorType::Cov
+            | DescriptorType::Tr => return SpkTemplate::Rebuild,
+        };
+        let script = match placeholder.explicit_script() {
+            Ok(script) => script.into_bytes(),
+            Err(..) => return SpkTemplate::Rebuild,
+        };
+
+        let mut patches = Vec::with_capacity(keys.len());
+        for (slot, pk) in keys.iter().enumerate() {
+            let key = pk.to_bytes();
+            let hash = hash160::Hash::hash(&key);
+            let mut found = push_offsets(&script, &key)
+                .map(|offset| (offset, false))
+                .chain(push_offsets(&script, &hash[..]).map(|offset| (offset, true)));
+            // Every key must be pushed exactly once, otherwise the
+            // placeholders collide with another key or with other data
+            match (found.next(), found.next()) {
+                (Some((offset, hashed)), None) => patches.push(Patch {
+                    slot,
+                    offset,
+                    hashed,
+                }),
+                _ => return SpkTemplate::Rebuild,
+            }
+        }
+        if sorted {
+            if patches.iter().any(|p| p.hashed || !keys[p.slot].compressed) {
+                return SpkTemplate::Rebuild;
+            }
+            patches.sort_by_key(|p| p.offset);
+        }
+        SpkTemplate::Script(ScriptTemplate {
+            script,
+            patches,
+            sorted,
+            sort_buf: vec![],
+            wrap: ty,
+        })
+    }
+}
+
+impl ScriptTemplate {
+    /// Patch `keys` into the script and write the script pubkey into `spk`
+    fn write_spk(&mut self, keys: &[bitcoin::PublicKey], spk: &mut Vec<u8>) {
+        if self.sorted {
+            self.sort_buf.clear();
+            self.sort_buf
+                .extend(self.patches.iter().map(|p| keys[p.slot].inner.serialize()));
+            self.sort_buf.sort_unstable();
+            for (patch, key) in self.patches.iter().zip(&self.sort_buf) {
+                self.script[patch.offset..patch.offset + key.len()].copy_from_slice(key);
+            }
+        } else {
+            for patch in &self.patches {
+                let pk = &keys[patch.slot];
+                let dst = &mut self.script[patch.offset..];
+                if patch.hashed {
+                    dst[..20].copy_from_slice(&key_hash(pk)[..]);
+                } else if pk.compressed {
+                    dst[..33].copy_from_slice(&pk.inner.serialize());
+                } else {
+                    dst[..65].copy_from_slice(&pk.inner.serialize_uncompressed());
+                }
+            }
+        }
+
+        spk.clear();
+        match self.wrap {
+            DescriptorType::Bare => spk.extend_from_slice(&self.script),
+            DescriptorType::Sh | DescriptorType::ShSortedMulti => {
+                write_p2sh(&hash160::Hash::hash(&self.script), spk)
+            }
+            DescriptorType::Wsh | DescriptorType::WshSortedMulti => {
+                write_p2wsh(&sha256::Hash::hash(&self.script), spk)
+            }
+            _ => {
+                write_p2wsh(&sha256::Hash::hash(&self.script), spk);
+                let hash = hash160::Hash::hash(spk);
+                spk.clear();
+                write_p2sh(&hash, spk);
+            }
+        }
+    }
+}
+
+// The offsets of the pushes of `data` in `script`
+fn push_offsets<'a>(script: &'a [u8], data: &'a [u8]) -> impl Iterator<Item = usize> + 'a {
+    script
+        .windows(data.len() + 1)
+        .enumerate()
+        .filter(move |&(_, w)| w[0] as usize == data.len() && &w[1..] == data)
+        .map(|(i, _)| i + 1)
+}
+
+// The hash160 of a key, without allocating its serialization
+fn key_hash(pk: &bitcoin::PublicKey) -> hash160::Hash {
+    if pk.compressed {
+        hash160::Hash::hash(&pk.inner.serialize())
+    } else {
+        hash160::Hash::hash(&pk.inner.serialize_uncompressed())
+    }
+}
+
+fn write_p2sh(hash: &hash160::Hash, spk: &mut Vec<u8>) {
+    spk.push(OP_HASH160.into_u8());
+    spk.push(OP_PUSHBYTES_20.into_u8());
+    spk.extend_from_slice(&hash[..]);
+    spk.push(OP_EQUAL.into_u8());
+}
+
+fn write_p2wsh(hash: &sha256::Hash, spk: &mut Vec<u8>) {
+    spk.push(OP_PUSHBYTES_0.into_u8());
+    spk.push(OP_PUSHBYTES_32.into_u8());
+    spk.extend_from_slice(&hash[..]);
+}
+
+fn write_p2wpkh(hash: &hash160::Hash, spk: &mut Vec<u8>) {
+    spk.push(OP_PUSHBYTES_0.into_u8());
+    spk.push(OP_PUSHBYTES_20.into_u8());
+    spk.extend_from_slice(&hash[..]);
+}
+
+/// The keys of a descriptor derived up to their wildcards, in the order in
+/// which [`TranslatePk`] visits them
+///
+/// Deriving the descriptor at an index only derives the last step of every
+/// key into a reused buffer. The script pubkey is then written by patching
+/// these keys into a [`SpkTemplate`], without building the derived
+/// descriptor, or looking up or cloning the [`DescriptorPublicKey`]s.
+struct PartialKeys {
+    slots: Result<Vec<KeySlot>, ConversionError>,
+    template: SpkTemplate,
+    derived: Vec<bitcoin::PublicKey>,
+    musig: Vec<bitcoin::PublicKey>,
+}
+
+impl PartialKeys {
+    fn new<C: secp256k1::Verification, Ext: Extension + ParseableExt>(
+        secp: &secp256k1::Secp256k1<C>,
+        descriptor: &Descriptor<DescriptorPublicKey, Ext>,
+    ) -> Self {
+        let mut recorder = Recorder {
+            secp,
+            slots: vec![],
+        };
+        let (slots, template) = match descriptor.translate_pk(&mut recorder) {
+            Ok(placeholder) => {
+                let template = SpkTemplate::new(&placeholder, &recorder.slots);
+                (Ok(recorder.slots), template)
+            }
+            Err(e) => (Err(e), SpkTemplate::Rebuild),
+        };
+        PartialKeys {
+            slots,
+            template,
+            derived: vec![],
+            musig: vec![],
+        }
+    }
+
+    /// Derive the keys at `index` into the reused buffer
+    fn derive<C: secp256k1::Verification>(
+        &mut self,
+        secp: &secp256k1::Secp256k1<C>,
+        index: u32,
+    ) -> Result<(), ConversionError> {
+        let slots = self.slots.as_ref().map_err(|e| *e)?;
+        let child =
+            ChildNumber::from_normal_idx(index).map_err(|_| ConversionError::HardenedChild)?;
+        self.derived.clear();
+        for slot in slots {
+            let pk = match *slot {
+                KeySlot::Single(ref partial) => partial.derive(secp, child)?,
+                KeySlot::MuSig(ref partials) => {
+                    self.musig.clear();
+                    for partial in partials {
+                        self.musig.push(partial.derive(secp, child)?);
+                    }
+                    aggregate_keys(secp, &self.musig)
+                }
+            };
+            self.derived.push(pk);
+        }
+        Ok(())
+    }
+
+    /// The descriptor with the keys of the last successful derivation
+    fn replay<Ext: Extension + ParseableExt>(
+        &self,
+        descriptor: &Descriptor<DescriptorPublicKey, Ext>,
+    ) -> Descriptor<bitcoin::PublicKey, Ext> {
+        descriptor
+            .translate_pk(&mut Replayer {
+                keys: &self.derived,
+                next: 0,
+            })
+            .expect("replaying derived keys cannot fail")
+    }
+
+    /// Write the script pubkey with the keys of the last successful
+    /// derivation into `spk`
+    fn write_spk<Ext: Extension + ParseableExt>(
+        &mut self,
+        descriptor: &Descriptor<DescriptorPublicKey, Ext>,
+        spk: &mut Vec<u8>,
+    ) {
+        spk.clear();
+        match self.template {
+            SpkTemplate::KeyHash(ty) => {
+                let hash = key_hash(&self.derived[0]);
+                match ty {
+                    DescriptorType::Pkh => {
+                        spk.push(OP_DUP.into_u8());
+                        spk.push(OP_HASH160.into_u8());
+                        spk.push(OP_PUSHBYTES_20.into_u8());
+                        spk.extend_from_slice(&hash[..]);
+                        spk.push(OP_EQUALVERIFY.into_u8());
+                        spk.push(OP_CHECKSIG.into_u8());
+                    }
+                    DescriptorType::Wpkh => write_p2wpkh(&hash, spk),
+                    _ => {
+                        write_p2wpkh(&hash, spk);
+                        let hash = hash160::Hash::hash(spk);
+                        spk.clear();
+                        write_p2sh(&hash, spk);
+                    }
+                }
+            }
+            SpkTemplate::Script(ref mut template) => template.write_spk(&self.derived, spk),
+            SpkTemplate::Rebuild => {
+                spk.extend_from_slice(self.replay(descriptor).script_pubkey().as_bytes())
+            }
+        }
+    }
+}
+
+/// Lending iterator over the script pubkeys of a descriptor at a range of
+/// derivation indices, see [`Descriptor::address_batch_iter`]
+///
+/// Every key is derived up to its wildcard only once, and then only the
+/// last derivation step is done per index, into buffers reused across
+/// indices. The script pubkey is written into a reused buffer as well: the
+/// derived keys are patched into the script, which is hashed without
+/// building the derived descriptor. Only taproot, covenant and MuSig
+/// descriptors are derived in full at every index.
+///
+/// Items borrow from the iterator, so it does not implement [`Iterator`];
+/// use [`AddressBatchIter::next_spk`] in a `while let` loop instead.
+pub struct AddressBatchIter<'desc, 'secp, C: secp256k1::Verification, Ext: Extension> {
+    descriptor: &'desc Descriptor<DescriptorPublicKey, Ext>,
+    secp: &'secp secp256k1::Secp256k1<C>,
+    indices: Range<u32>,
+    keys: PartialKeys,
+    spk: Vec<u8>,
+    derived: bool,
+}
+
+impl<'desc, 'secp, C, Ext> AddressBatchIter<'desc, 'secp, C, Ext>
+where
+    C: secp256k1::Verification,
+    Ext: Extension + ParseableExt,
+{
+    /// The derivation index and script pubkey of the next item
+    ///
+    /// # Errors
+    /// When a key has hardened derivation steps or the index is hardened.
+    /// The failing index is skipped.
+    pub fn next_spk(&mut self) -> Option<Result<(u32, &[u8]), ConversionError>> {
+        let index = self.indices.next()?;
+        match self.keys.derive(self.secp, index) {
+            Ok(()) => {
+                self.derived = true;
+                self.keys.write_spk(self.descriptor, &mut self.spk);
+                Some(Ok((index, &self.spk)))
+            }
+            Err(e) => {
+                self.derived = false;
+                Some(Err(e))
+            }
+        }
+    }
+
+    /// The derived descriptor of the last item returned by
+    /// [`AddressBatchIter::next_spk`], if it was derived successfully.
+    /// It is built from the already derived keys on every call.
+    pub fn descriptor(&self) -> Option<Descriptor<bitcoin::PublicKey, Ext>> {
+        if self.derived {
+            Some(self.keys.replay(self.descriptor))
+        } else {
+            None
+        }
+    }
+}
+
+impl<Ext: Extension + ParseableExt> Descriptor<DescriptorPublicKey, Ext> {
+    /// Iterate over the script pubkeys of this descriptor at each index of
+    /// `range`, reusing the key derivations shared by all indices. If the
+    /// descriptor has no wildcard, only the index `0` is visited.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use elements_miniscript::{Descriptor, DescriptorPublicKey, bitcoin::secp256k1::Secp256k1};
+    /// # use core::str::FromStr;
+    /// let descriptor = Descriptor::<DescriptorPublicKey>::from_str("elwpkh(tpubD6NzVbkrYhZ4XgiXtGrdW5XDAPFCL9h7we1vwNCpn8tGbBcgfVYjXyhWo4E1xkh56hjod1RhGjxbaTLV3X4FyWuejifB9jusQ46QzG87VKp/0/*)").unwrap();
+    /// let secp = Secp256k1::verification_only();
+    /// let mut iter = descriptor.address_batch_iter(&secp, 0..10);
+    /// while let Some(item) = iter.next_spk() {
+    ///     let (index, spk) = item.unwrap();
+    ///     assert_eq!(spk, descriptor.derived_descriptor(&secp, index).unwrap().script_pubkey().as_bytes());
+    /// }
+    /// ```
+    pub fn address_batch_iter<'desc, 'secp, C: secp256k1::Verification>(
+        &'desc self,
+        secp: &'secp secp256k1::Secp256k1<C>,
+        range: Range<u32>,
+    ) -> AddressBatchIter<'desc, 'secp, C, Ext> {
+        AddressBatchIter {
+            descriptor: self,
+            secp,
+            indices: if self.has_wildcard() { range } else { 0..1 },
+            keys: PartialKeys::new(secp, self),
+            spk: vec![],
+            derived: false,
+        }
+    }
+}
+
+/// Memoized script pubkeys of a descriptor at arbitrary derivation indices,
+/// see [`Descriptor::derivation_cache`]
+///
+/// Like [`AddressBatchIter`], every key is derived up to its wildcard only
+/// once, and script pubkeys are computed without building the derived
+/// descriptor. In addition, the script pubkey of every index is kept, so that
+/// deriving it again only costs a lookup, and script pubkeys can be mapped
+/// back to their index.
+pub struct DerivedDescriptorCache<'desc, 'secp, C: secp256k1::Verification, Ext: Extension> {
+    descriptor: &'desc Descriptor<DescriptorPublicKey, Ext>,
+    secp: &'secp secp256k1::Secp256k1<C>,
+    keys: PartialKeys,
+    spk: Vec<u8>,
+    spks: BTreeMap<u32, Script>,
+    indices: HashMap<Script, u32>,
+}
+
+impl<'desc, 'secp, C, Ext> DerivedDescriptorCache<'desc, 'secp, C, Ext>
+where
+    C: secp256k1::Verification,
+    Ext: Extension + ParseableExt,
+{
+    // Descriptors without wildcard only have the index 0
+    fn normalize(&self, index: u32) -> u32 {
+        if self.descriptor.has_wildcard() {
+            index
+        } else {
+            0
+        }
+    }
+
+    /// The descriptor derived at `index`, reusing the cached key derivations
+    ///
+    /// # Errors
+    /// When a key has hardened derivation steps or the index is hardened.
+    pub fn derived_descriptor(
+        &mut self,
+        index: u32,
+    ) -> Result<Descriptor<bitcoin::PublicKey, Ext>, ConversionError> {
+        let index = self.normalize(index);
+        self.keys.derive(self.secp, index)?;
+        Ok(self.keys.replay(self.descriptor))
+    }
+
+    /// The script pubkey of the descriptor derived at `index`, computed on
+    /// the first call for the index only
+    ///
+    /// # Errors
+    /// When a key has hardened derivation steps or the index is hardened.
+    pub fn script_pubkey(&mut self, index: u32) -> Result<&Script, ConversionError> {
+        let index = self.normalize(index);
+        if !self.spks.contains_key(&index) {
+            self.keys.derive(self.secp, index)?;
+            self.keys.write_spk(self.descriptor, &mut self.spk);
+            let spk = Script::from(self.spk.clone());
+            self.indices.entry(spk.clone()).or_insert(index);
+            self.spks.insert(index, spk);
+        }
+        Ok(&self.spks[&index])
+    }
+
+    /// The smallest derivation index of the cached script pubkeys with
+    /// script pubkey `spk`, if any
+    pub fn index_of(&self, spk: &Script) -> Option<u32> {
+        self.indices.get(spk).copied()
+    }
+
+    /// The number of cached script pubkeys
+    pub fn len(&self) -> usize {
+        self.spks.len()
+    }
+
+    /// Whether no script pubkey is cached
+    pub fn is_empty(&self) -> bool {
+        self.spks.is_empty()
+    }
+}
+
+impl<Ext: Extension + ParseableExt> Descriptor<DescriptorPublicKey, Ext> {
+    /// A cache of the script pubkeys of this descriptor, for scanners that
+    /// derive the same indices repeatedly or out of order. For a single pass
+    /// over consecutive indices, [`Descriptor::address_batch_iter`] does not
+    /// keep the scripts in memory.
+    pub fn derivation_cache<'desc, 'secp, C: secp256k1::Verification>(
+        &'desc self,
+        secp: &'secp secp256k1::Secp256k1<C>,
+    ) -> DerivedDescriptorCache<'desc, 'secp, C, Ext> {
+        DerivedDescriptorCache {
+            descriptor: self,
+            secp,
+            keys: PartialKeys::new(secp, self),
+            spk: vec![],
+            spks: BTreeMap::new(),
+            indices: HashMap::new(),
+        }
+    }
+}
+
+/// Records the partial keys of a descriptor, translating it into the
+/// descriptor of their placeholders
+struct Recorder<'secp, C: secp256k1::Verification> {
+    secp: &'secp secp256k1::Secp256k1<C>,
+    slots: Vec<KeySlot>,
+}
+
+impl<'secp, C: secp256k1::Verification>
+    Translator<DescriptorPublicKey, bitcoin::PublicKey, ConversionError> for Recorder<'secp, C>
+{
+    fn pk(&mut self, pk: &DescriptorPublicKey) -> Result<bitcoin::PublicKey, ConversionError> {
+        if let DescriptorPublicKey::MuSig(ref musig) = *pk {
+            let partials = musig
+                .keys()
+                .iter()
+                .map(|k| PartialKey::new(self.secp, k))
+                .collect::<Result<Vec<_>, _>>()?;
+            let keys = partials
+                .iter()
+                .map(PartialKey::placeholder)
+                .collect::<Vec<_>>();
+            self.slots.push(KeySlot::MuSig(partials));
+            return Ok(aggregate_keys(self.secp, &keys));
+        }
+        let partial = PartialKey::new(self.secp, pk)?;
+        self.slots.push(KeySlot::Single(partial));
+        Ok(partial.placeholder())
+    }
+
+    translate_hash_clone!(DescriptorPublicKey, bitcoin::PublicKey, ConversionError);
+}
+
+/// Hands out derived keys in the order recorded by [`Recorder`]
+struct Replayer<'a> {
+    keys: &'a [bitcoin::PublicKey],
+    next: usize,
+}
+
+impl<'a> Translator<DescriptorPublicKey, bitcoin::PublicKey, ConversionError> for Replayer<'a> {
+    fn pk(&mut self, _: &DescriptorPublicKey) -> Result<bitcoin::PublicKey, ConversionError> {
+        // The translation visits the keys in the same order as the recording
+        let pk = self.keys[self.next];
+        self.next += 1;
+        Ok(pk)
+    }
+
+    translate_hash_clone!(DescriptorPublicKey, bitcoin::PublicKey, ConversionError);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn address_batch_iter() {
+        let secp = secp256k1::Secp256k1::verification_only();
+        let xpub = "tpubD6NzVbkrYhZ4XgiXtGrdW5XDAPFCL9h7we1vwNCpn8tGbBcgfVYjXyhWo4E1xkh56hjod1RhGjxbaTLV3X4FyWuejifB9jusQ46QzG87VKp";
+        let single = "020202020202020202020202020202020202020202020202020202020202020202";
+        let desc = Descriptor::<DescriptorPublicKey>::from_str(&format!(
+            "elwsh(multi(2,{}/1/*,{}/2,{}))",
+            xpub, xpub, single
+        ))
+        .unwrap();
+
+        let mut iter = desc.address_batch_iter(&secp, 5..25);
+        let mut count = 0;
+        while let Some(item) = iter.next_spk() {
+            let (index, spk) = item.unwrap();
+            let expected = desc.derived_descriptor(&secp, index).unwrap();
+            assert_eq!(spk, expected.script_pubkey().as_bytes());
+            assert_eq!(iter.descriptor(), Some(expected));
+            count += 1;
+        }
+        assert_eq!(count, 20);
+        assert_eq!(
+            desc.find_derivation_index_for_spk(
+                &secp,
+                &desc.derived_descriptor(&secp, 17).unwrap().script_pubkey(),
+                0..20
+            )
+            .unwrap()
+            .map(|(index, _)| index),
+            Some(17)
+        );
+
+        // Descriptors without wildcard have a single item
+        let fixed =
+            Descriptor::<DescriptorPublicKey>::from_str(&format!("elwpkh({}/3)", xpub)).unwrap();
+        let mut iter = fixed.address_batch_iter(&secp, 10..20);
+        assert_eq!(iter.next_spk().unwrap().unwrap().0, 0);
+        assert!(iter.next_spk().is_none());
+
+        // Hardened derivation fails for every index
+        let hardened =
+            Descriptor::<DescriptorPublicKey>::from_str(&format!("elwpkh({}/1h/*)", xpub)).unwrap();
+        let mut iter = hardened.address_batch_iter(&secp, 0..2);
+        assert_eq!(iter.next_spk(), Some(Err(ConversionError::HardenedChild)));
+        assert_eq!(iter.descriptor(), None);
+        assert_eq!(iter.next_spk(), Some(Err(ConversionError::HardenedChild)));
+        assert_eq!(iter.next_spk(), None);
+
+        // Keys are replayed in order through taproot trees and aggregates
+        let desc = Descriptor::<DescriptorPublicKey>::from_str(&format!(
+            "eltr({}/0/*,{{pk(musig({}/1/*,{})),and_v(v:pk({}/2/*),older(10))}})",
+            xpub, xpub, single, xpub
+        ))
+        .unwrap();
+        let mut iter = desc.address_batch_iter(&secp, 0..5);
+        while let Some(item) = iter.next_spk() {
+            let (index, spk) = item.unwrap();
+            let expected = desc.derived_descriptor(&secp, index).unwrap();
+            assert_eq!(spk, expected.script_pubkey().as_bytes());
+            assert_eq!(iter.descriptor(), Some(expected));
+        }
+    }
+
+    #[test]
+    fn spk_templates() {
+        let secp = secp256k1::Secp256k1::verification_only();
+        let xpub = "tpubD6NzVbkrYhZ4XgiXtGrdW5XDAPFCL9h7we1vwNCpn8tGbBcgfVYjXyhWo4E1xkh56hjod1RhGjxbaTLV3X4FyWuejifB9jusQ46QzG87VKp";
+        let single = "020202020202020202020202020202020202020202020202020202020202020202";
+        let uncompressed = "04a34b99f22c790c4e36b2b3c2c35a36db06226e41c692fc82b8b56ac1c540c5bd5b8dec5235a0fa8722476c7709c02559e3aa73aa03918ba2d492eea75abea235";
+        // The parent key of `xpub/1/*`, colliding with its placeholder
+        let parent = bip32::ExtendedPubKey::from_str(xpub)
+            .unwrap()
+            .ckd_pub(&secp, ChildNumber::from_normal_idx(1).unwrap())
+            .unwrap()
+            .public_key;
+
+        let check = |desc: &str, patched: bool| {
+            let desc = Descriptor::<DescriptorPublicKey>::from_str(desc).unwrap();
+            let mut iter = desc.address_batch_iter(&secp, 0..10);
+            match iter.keys.template {
+                SpkTemplate::Rebuild => assert!(!patched, "{} is rebuilt", desc),
+                _ => assert!(patched, "{} is patched", desc),
+            }
+            while let Some(item) = iter.next_spk() {
+                let (index, spk) = item.unwrap();
+                let expected = desc.derived_descriptor(&secp, index).unwrap();
+                assert_eq!(spk, expected.script_pubkey().as_bytes(), "{}", desc);
+            }
+        };
+        check(&format!("elpkh({}/*)", xpub), true);
+        check(&format!("elwpkh({}/*)", xpub), true);
+        check(&format!("elsh(wpkh({}/*))", xpub), true);
+        check(&format!("elpk({}/*)", xpub), true);
+        check(
+            &format!("elsh(multi(1,{}/1/*,{}))", xpub, uncompressed),
+            true,
+        );
+        check(
+            &format!(
+                "elwsh(or_d(pk({}/1/*),and_v(v:pkh({}/2/*),older(10))))",
+                xpub, xpub
+            ),
+            true,
+        );
+        check(&format!("elsh(wsh(pkh({}/*)))", xpub), true);
+        check(
+            &format!("elsh(sortedmulti(2,{}/1/*,{}/2/*,{}))", xpub, xpub, single),
+            true,
+        );
+        check(
+            &format!("elwsh(sortedmulti(2,{}/1/*,{}/2/*,{}))", xpub, xpub, single),
+            true,
+        );
+        check(
+            &format!("elsh(wsh(sortedmulti(1,{}/1/*,{}/2/*)))", xpub, xpub),
+            true,
+        );
+        check(
+            &format!("elsh(sortedmulti(1,{}/1/*,{}))", xpub, uncompressed),
+            false,
+        );
+        check(&format!("elwsh(multi(1,{}/1/*,{}))", xpub, parent), false);
+        check(&format!("eltr({}/*)", xpub), false);
+    }
+
+    #[test]
+    fn derivation_cache() {
+        let secp = secp256k1::Secp256k1::verification_only();
+        let xpub = "tpubD6NzVbkrYhZ4XgiXtGrdW5XDAPFCL9h7we1vwNCpn8tGbBcgfVYjXyhWo4E1xkh56hjod1RhGjxbaTLV3X4FyWuejifB9jusQ46QzG87VKp";
+        let desc = Descriptor::<DescriptorPublicKey>::from_str(&format!(
+            "elwsh(multi(1,{}/1/*,{}/2/*))",
+            xpub, xpub
+        ))
+        .unwrap();
+
+        let mut cache = desc.derivation_cache(&secp);
+        assert!(cache.is_empty());
+        for &index in &[7, 3, 7, 1_000_000, 3] {
+            let expected = desc.derived_descriptor(&secp, index).unwrap();
+            assert_eq!(
+                *cache.script_pubkey(index).unwrap(),
+                expected.script_pubkey()
+            );
+            assert_eq!(cache.derived_descriptor(index).unwrap(), expected);
+        }
+        assert_eq!(cache.len(), 3);
+        let spk = desc.derived_descriptor(&secp, 3).unwrap().script_pubkey();
+        assert_eq!(cache.index_of(&spk), Some(3));
+        let spk = desc.derived_descriptor(&secp, 4).unwrap().script_pubkey();
+        assert_eq!(cache.index_of(&spk), None);
+        assert_eq!(
+            cache.script_pubkey(1 << 31),
+            Err(ConversionError::HardenedChild)
+        );
+        assert_eq!(cache.len(), 3);
+
+        // Descriptors without wildcard have a single script pubkey
+        let fixed =
+            Descriptor::<DescriptorPublicKey>::from_str(&format!("elwpkh({}/3)", xpub)).unwrap();
+        let mut cache = fixed.derivation_cache(&secp);
+        let spk = cache.script_pubkey(10).unwrap().clone();
+        assert_eq!(*cache.script_pubkey(20).unwrap(), spk);
+        assert_eq!(cache.index_of(&spk), Some(0));
+        assert_eq!(cache.len(), 1);
+    }
+}
@@ -56,6 +56,7 @@ use crate::miniscript::lex::{lex, Token as Tk, TokenIter};
 use crate::miniscript::limits::{
     MAX_OPS_PER_SCRIPT, MAX_SCRIPT_SIZE, MAX_STANDARD_P2WSH_SCRIPT_SIZE,
 };
+use crate::miniscript::satisfy::{checked_ecdsa_sig, Timelocks};
 use crate::miniscript::{decode, types};
 use crate::util::varint_len;
 use crate::{
@@ -138,6 +139,20 @@ impl<Pk: MiniscriptKey, Ext: Extension> LegacyCSFSCov<Pk, Ext> {
 
     /// Create a satisfaction for the Covenant Descriptor
     pub fn satisfy<S: Satisfier<Pk>>(&self, s: S, allow_mall: bool) -> Result<Vec<Vec<u8>>, Error>
+    where
+        Pk: ToPublicKey,
+        Ext: ParseableExt,
+    {
+        self.satisfy_timelocked(s, allow_mall).map(|(wit, _)| wit)
+    }
+
+    // Helper function for `satisfy`, also returning the timelocks of the
+    // chosen spend path
+    pub(crate) fn satisfy_timelocked<S: Satisfier<Pk>>(
+        &self,
+        s: S,
+        allow_mall: bool,
+    ) -> Result<(Vec<Vec<u8>>, Timelocks), Error>
     where
         Pk: ToPublicKey,
         Ext: ParseableExt,
@@ -158,9 +173,8 @@ impl<Pk: MiniscriptKey, Ext: Extension> LegacyCSFSCov<Pk, Ext> {
             let n_locktime = s.lookup_nlocktime().ok_or(MissingSighashItem(9))?;
             let sighash_ty = s.lookup_sighashu32().ok_or(MissingSighashItem(10))?;
 
-            let (sig, hash_ty) = s
-                .lookup_ecdsa_sig(&self.pk)
-                .ok_or(CovError::MissingCovSignature)?;
+            let (sig, hash_ty) =
+                checked_ecdsa_sig(&s, &self.pk)?.ok_or(CovError::MissingCovSignature)?;
             // Hashtype must be the same
             if sighash_ty != hash_ty.as_u32() {
                 return Err(CovError::CovenantSighashTypeMismatch)?;
@@ -182,13 +196,9 @@ impl<Pk: MiniscriptKey, Ext: Extension> LegacyCSFSCov<Pk, Ext> {
             ]
         };
 
-        let ms_wit = if !allow_mall {
-            self.ms.satisfy(s)?
-        } else {
-            self.ms.satisfy_malleable(s)?
-        };
+        let (ms_wit, timelocks) = self.ms.satisfy_timelocked(s, allow_mall)?;
         wit.extend(ms_wit);
-        Ok(wit)
+        Ok((wit, timelocks))
     }
 
     /// Script code for signing with covenant publickey.
@@ -407,9 +417,7 @@ where
         S: Satisfier<Pk>,
         Ext: ParseableExt,
     {
-        let mut witness = self.satisfy(satisfier, /*allow_mall*/ false)?;
-        witness.push(self.encode().into_bytes());
-        let script_sig = Script::new();
+        let (witness, script_sig, _) = self.get_satisfaction_timelocked(satisfier, false)?;
         Ok((witness, script_sig))
     }
 
@@ -449,10 +457,26 @@ where
         S: Satisfier<Pk>,
         Ext: ParseableExt,
     {
-        let mut witness = self.satisfy(satisfier, /*allow_mall*/ true)?;
+        let (witness, script_sig, _) = self.get_satisfaction_timelocked(satisfier, true)?;
+        Ok((witness, script_sig))
+    }
+
+    // Helper function for the satisfaction methods, also returning the
+    // timelocks of the chosen spend path
+    pub(crate) fn get_satisfaction_timelocked<S>(
+        &self,
+        satisfier: S,
+        allow_mall: bool,
+    ) -> Result<(Vec<Vec<u8>>, Script, Timelocks), Error>
+    where
+        Pk: ToPublicKey,
+        S: Satisfier<Pk>,
+        Ext: ParseableExt,
+    {
+        let (mut witness, timelocks) = self.satisfy_timelocked(satisfier, allow_mall)?;
         witness.push(self.encode().into_bytes());
         let script_sig = Script::new();
-        Ok((witness, script_sig))
+        Ok((witness, script_sig, timelocks))
     }
 }
 
@@ -41,6 +41,12 @@
 //! After all the miniscript fragments are evaluated, we concat
 //! all the items using OP_CAT to obtain a Sighash on which we
 //! which we verify using CHECKSIGFROMSTACK
+//!
+//! The descriptor is written `elcovwsh(K,ms)`, see [`LegacyCSFSCov`] and
+//! [`LegacyCSFSCov::cov_script_code`]. The sighash items are provided by the
+//! covenant lookups of [`crate::Satisfier`], from `lookup_nversion` to
+//! `lookup_sighashu32`. [`LegacyCovSatisfier`] computes all of them from the
+//! spending transaction.
 
 mod cov;
 mod error;
@@ -260,7 +266,16 @@ mod tests {
         };
 
         // A pair of satisfiers is also a satisfier
-        let (wit, ss) = desc.get_satisfaction((cov_sat, pk_sat))?;
+        let (wit, ss) = desc.get_satisfaction((&cov_sat, &pk_sat))?;
+
+        // All sighash items are needed
+        struct VersionOnly;
+        impl Satisfier<bitcoin::PublicKey> for VersionOnly {
+            fn lookup_nversion(&self) -> Option<u32> {
+                Some(2)
+            }
+        }
+        assert!(desc.get_satisfaction((VersionOnly, &pk_sat)).is_err());
         let interpreter = Interpreter::from_txdata(
             &desc.script_pubkey(),
             &ss,
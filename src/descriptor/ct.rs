@@ -0,0 +1,619 @@
+// Miniscript
+// Written in 2023 by
+//     The Elements Miniscript developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Confidential Descriptors
+//!
+//! Descriptors of the form `ct(KEY,DESC)`, with the blinding key of the
+//! outputs of `DESC`, as used by Elements Core. The blinding key is either
+//! a SLIP-77 master blinding key, `slip77(HEX)`, or an ELIP-150 view key,
+//! from which a blinding key is derived for each script pubkey.
+//!
+//! Other ways of deriving blinding keys can be used to compute confidential
+//! addresses through the [`BlindingKeyDerivation`] trait.
+//!
+
+use std::str::FromStr;
+use std::{error, fmt};
+
+use bitcoin::hashes::{sha256, Hash, HashEngine};
+use elements::secp256k1_zkp::{self, Scalar, Secp256k1, Signing, Verification};
+use elements::slip77::MasterBlindingKey;
+use elements::{encode, Script};
+
+use super::checksum::{self, verify_checksum};
+use super::{
+    ConversionError, DefiniteDescriptorKey, Descriptor, DescriptorPublicKey, DescriptorSecretKey,
+};
+use crate::extensions::{CovExtArgs, CovenantExt, ParseableExt};
+use crate::{Error, Extension, MiniscriptKey, ToPublicKey};
+
+/// The tag of the ELIP-150 tweak of view keys
+const ELIP150_TAG: &[u8] = b"CT-Blinding-Key/1.0";
+
+/// A way of deriving the blinding key of outputs from their script pubkey
+pub trait BlindingKeyDerivation {
+    /// The blinding public key of outputs with `script_pubkey`
+    fn blinding_key<C: Signing + Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        script_pubkey: &Script,
+    ) -> Result<secp256k1_zkp::PublicKey, Error>;
+
+    /// The blinding private key of outputs with `script_pubkey`, or `None`
+    /// if only the public key can be derived
+    fn blinding_private_key<C: Signing + Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        script_pubkey: &Script,
+    ) -> Result<Option<secp256k1_zkp::SecretKey>, Error>;
+}
+
+/// SLIP-77: the blinding private key is derived from the master blinding key
+impl BlindingKeyDerivation for MasterBlindingKey {
+    fn blinding_key<C: Signing + Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        script_pubkey: &Script,
+    ) -> Result<secp256k1_zkp::PublicKey, Error> {
+        let sk = self.derive_blinding_key(script_pubkey);
+        Ok(secp256k1_zkp::PublicKey::from_secret_key(secp, &sk))
+    }
+
+    fn blinding_private_key<C: Signing + Verification>(
+        &self,
+        _secp: &Secp256k1<C>,
+        script_pubkey: &Script,
+    ) -> Result<Option<secp256k1_zkp::SecretKey>, Error> {
+        Ok(Some(self.derive_blinding_key(script_pubkey)))
+    }
+}
+
+/// A bare blinding public key, used for all outputs
+impl BlindingKeyDerivation for secp256k1_zkp::PublicKey {
+    fn blinding_key<C: Signing + Verification>(
+        &self,
+        _secp: &Secp256k1<C>,
+        _script_pubkey: &Script,
+    ) -> Result<secp256k1_zkp::PublicKey, Error> {
+        Ok(*self)
+    }
+
+    fn blinding_private_key<C: Signing + Verification>(
+        &self,
+        _secp: &Secp256k1<C>,
+        _script_pubkey: &Script,
+    ) -> Result<Option<secp256k1_zkp::SecretKey>, Error> {
+        Ok(None)
+    }
+}
+
+/// A bare blinding private key, used for all outputs
+impl BlindingKeyDerivation for secp256k1_zkp::SecretKey {
+    fn blinding_key<C: Signing + Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        _script_pubkey: &Script,
+    ) -> Result<secp256k1_zkp::PublicKey, Error> {
+        Ok(secp256k1_zkp::PublicKey::from_secret_key(secp, self))
+    }
+
+    fn blinding_private_key<C: Signing + Verification>(
+        &self,
+        _secp: &Secp256k1<C>,
+        _script_pubkey: &Script,
+    ) -> Result<Option<secp256k1_zkp::SecretKey>, Error> {
+        Ok(Some(*self))
+    }
+}
+
+/// The ELIP-150 tweak of the view public key `view_key` for outputs with
+/// `script_pubkey`: the tagged hash of the key and the length-prefixed
+/// script.
+fn elip150_tweak(
+    view_key: &secp256k1_zkp::PublicKey,
+    script_pubkey: &Script,
+) -> Result<Scalar, Error> {
+    let tag = sha256::Hash::hash(ELIP150_TAG);
+    let mut engine = sha256::Hash::engine();
+    engine.input(&tag[..]);
+    engine.input(&tag[..]);
+    engine.input(&view_key.serialize());
+    engine.input(&encode::serialize(script_pubkey));
+    let hash = sha256::Hash::from_engine(engine);
+    Scalar::from_be_bytes(hash.into_inner())
+        .map_err(|_| Error::Unexpected(String::from("ELIP-150 tweak out of range")))
+}
+
+/// The public key of an ELIP-150 view key, which must not be ranged
+fn view_public_key<C: Verification>(
+    secp: &Secp256k1<C>,
+    key: &DescriptorPublicKey,
+) -> Result<secp256k1_zkp::PublicKey, Error> {
+    if key.has_wildcard() {
+        return Err(Error::BadDescriptor(String::from(
+            "view keys cannot have wildcards",
+        )));
+    }
+    key.clone()
+        .at_derivation_index(0)
+        .and_then(|key| key.derive_public_key(secp))
+        .map(|pk| pk.inner)
+        .map_err(|e| Error::BadDescriptor(format!("invalid view key: {:?}", e)))
+}
+
+/// The private key of an ELIP-150 view key, which must not be ranged
+fn view_private_key<C: Signing>(
+    secp: &Secp256k1<C>,
+    key: &DescriptorSecretKey,
+) -> Result<secp256k1_zkp::SecretKey, Error> {
+    match *key {
+        DescriptorSecretKey::Single(ref single) => Ok(single.key.inner),
+        DescriptorSecretKey::XPrv(ref xprv) => {
+            if xprv.wildcard != super::Wildcard::None {
+                return Err(Error::BadDescriptor(String::from(
+                    "view keys cannot have wildcards",
+                )));
+            }
+            xprv.xkey
+                .derive_priv(secp, &xprv.derivation_path)
+                .map(|xprv| xprv.private_key)
+                .map_err(|e| Error::BadDescriptor(format!("invalid view key: {}", e)))
+        }
+    }
+}
+
+/// The blinding key of a [`ConfidentialDescriptor`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BlindingKey {
+    /// A SLIP-77 master blinding key, `slip77(HEX)`
+    Slip77(MasterBlindingKey),
+    /// An ELIP-150 public view key, from which blinding public keys are
+    /// derived by tweaking it with the script pubkey
+    View(DescriptorPublicKey),
+    /// An ELIP-150 private view key, from which blinding private keys are
+    /// derived by tweaking it with the script pubkey
+    ViewPrivate(DescriptorSecretKey),
+}
+
+impl BlindingKeyDerivation for BlindingKey {
+    fn blinding_key<C: Signing + Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        script_pubkey: &Script,
+    ) -> Result<secp256k1_zkp::PublicKey, Error> {
+        match *self {
+            BlindingKey::Slip77(ref mbk) => mbk.blinding_key(secp, script_pubkey),
+            BlindingKey::View(ref pk) => {
+                let view_key = view_public_key(secp, pk)?;
+                let tweak = elip150_tweak(&view_key, script_pubkey)?;
+                view_key
+                    .add_exp_tweak(secp, &tweak)
+                    .map_err(|e| Error::Unexpected(e.to_string()))
+            }
+            BlindingKey::ViewPrivate(..) => {
+                let sk = self
+                    .blinding_private_key(secp, script_pubkey)?
+                    .expect("private view keys derive private keys");
+                Ok(secp256k1_zkp::PublicKey::from_secret_key(secp, &sk))
+            }
+        }
+    }
+
+    fn blinding_private_key<C: Signing + Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        script_pubkey: &Script,
+    ) -> Result<Option<secp256k1_zkp::SecretKey>, Error> {
+        match *self {
+            BlindingKey::Slip77(ref mbk) => mbk.blinding_private_key(secp, script_pubkey),
+            BlindingKey::View(..) => Ok(None),
+            BlindingKey::ViewPrivate(ref sk) => {
+                let view_sk = view_private_key(secp, sk)?;
+                let view_key = secp256k1_zkp::PublicKey::from_secret_key(secp, &view_sk);
+                let tweak = elip150_tweak(&view_key, script_pubkey)?;
+                view_sk
+                    .add_tweak(&tweak)
+                    .map(Some)
+                    .map_err(|e| Error::Unexpected(e.to_string()))
+            }
+        }
+    }
+}
+
+impl fmt::Display for BlindingKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            BlindingKey::Slip77(ref mbk) => write!(f, "slip77({})", mbk.0.display_secret()),
+            BlindingKey::View(ref pk) => pk.fmt(f),
+            BlindingKey::ViewPrivate(ref sk) => sk.fmt(f),
+        }
+    }
+}
+
+impl FromStr for BlindingKey {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        if s.starts_with("slip77(") && s.ends_with(')') {
+            let hex = &s["slip77(".len()..s.len() - 1];
+            return secp256k1_zkp::SecretKey::from_str(hex)
+                .map(|sk| BlindingKey::Slip77(MasterBlindingKey(sk)))
+                .map_err(|e| Error::BadDescriptor(format!("invalid slip77 key: {}", e)));
+        }
+        let secp = Secp256k1::new();
+        if let Ok(sk) = DescriptorSecretKey::from_str(s) {
+            view_private_key(&secp, &sk)?;
+            Ok(BlindingKey::ViewPrivate(sk))
+        } else if let Ok(pk) = DescriptorPublicKey::from_str(s) {
+            view_public_key(&secp, &pk)?;
+            Ok(BlindingKey::View(pk))
+        } else {
+            Err(Error::Unexpected(format!(
+                "{} while parsing blinding key",
+                s
+            )))
+        }
+    }
+}
+
+/// A descriptor with the key blinding its outputs, `ct(KEY,DESC)`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfidentialDescriptor<Pk: MiniscriptKey, T: Extension = CovenantExt<CovExtArgs>> {
+    /// The blinding key
+    pub key: BlindingKey,
+    /// The descriptor of the unblinded outputs
+    pub descriptor: Descriptor<Pk, T>,
+}
+
+impl<Pk: MiniscriptKey, T: Extension> ConfidentialDescriptor<Pk, T> {
+    /// Create a confidential descriptor blinding the outputs of `descriptor`
+    /// with `key`
+    pub fn new(key: BlindingKey, descriptor: Descriptor<Pk, T>) -> Self {
+        ConfidentialDescriptor { key, descriptor }
+    }
+}
+
+impl<Pk: MiniscriptKey + ToPublicKey, T: Extension + ParseableExt> ConfidentialDescriptor<Pk, T> {
+    /// The blinding public key of the outputs of this descriptor, or of
+    /// any output with `script_pubkey` if it is not this descriptor's
+    pub fn blinding_key<C: Signing + Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        script_pubkey: &Script,
+    ) -> Result<secp256k1_zkp::PublicKey, Error> {
+        self.key.blinding_key(secp, script_pubkey)
+    }
+
+    /// The blinded address of this descriptor
+    ///
+    /// # Errors
+    /// For raw/bare descriptors that don't have an address, or invalid
+    /// view keys.
+    pub fn address<C: Signing + Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        params: &'static elements::AddressParams,
+    ) -> Result<elements::Address, Error> {
+        self.descriptor
+            .confidential_address(secp, &self.key, params)
+    }
+}
+
+/// An error deriving an address of a ranged descriptor, see
+/// [`Descriptor::derived_confidential_address`]
+#[derive(Debug, PartialEq)]
+pub enum DerivedAddressError {
+    /// The derivation index is hardened, ≥ 2^31
+    HardenedIndex(u32),
+    /// A key has hardened derivation steps, e.g. a hardened wildcard, or
+    /// multiple derivation paths
+    Key(ConversionError),
+    /// The derived descriptor has no address, or its blinding key could not
+    /// be derived
+    Address(Error),
+}
+
+impl fmt::Display for DerivedAddressError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            DerivedAddressError::HardenedIndex(index) => {
+                write!(f, "hardened derivation index {}", index)
+            }
+            DerivedAddressError::Key(ref e) => write!(f, "cannot derive key: {}", e),
+            DerivedAddressError::Address(ref e) => write!(f, "cannot derive address: {}", e),
+        }
+    }
+}
+
+impl error::Error for DerivedAddressError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            DerivedAddressError::HardenedIndex(..) => None,
+            DerivedAddressError::Key(ref e) => Some(e),
+            DerivedAddressError::Address(ref e) => Some(e),
+        }
+    }
+}
+
+impl<T: Extension + ParseableExt> Descriptor<DescriptorPublicKey, T> {
+    /// The blinded address at derivation `index`, with the blinding key
+    /// derived by `key` from the derived script pubkey. This combines
+    /// [`Descriptor::derived_descriptor`] and
+    /// [`Descriptor::confidential_address`], without panicking on hardened
+    /// indexes.
+    pub fn derived_confidential_address<C, K>(
+        &self,
+        secp: &Secp256k1<C>,
+        index: u32,
+        key: &K,
+        params: &'static elements::AddressParams,
+    ) -> Result<elements::Address, DerivedAddressError>
+    where
+        C: Signing + Verification,
+        K: BlindingKeyDerivation,
+    {
+        if index >= 1 << 31 {
+            return Err(DerivedAddressError::HardenedIndex(index));
+        }
+        self.derived_descriptor(secp, index)
+            .map_err(DerivedAddressError::Key)?
+            .confidential_address(secp, key, params)
+            .map_err(DerivedAddressError::Address)
+    }
+}
+
+impl<T: Extension + ParseableExt> ConfidentialDescriptor<DescriptorPublicKey, T> {
+    /// The blinded address at derivation `index`, see
+    /// [`Descriptor::derived_confidential_address`]
+    pub fn derived_address<C: Signing + Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        index: u32,
+        params: &'static elements::AddressParams,
+    ) -> Result<elements::Address, DerivedAddressError> {
+        self.descriptor
+            .derived_confidential_address(secp, index, &self.key, params)
+    }
+
+    /// Replaces all wildcards of the descriptor with `index`, see
+    /// [`Descriptor::at_derivation_index`]. Blinding keys are derived
+    /// from the script pubkey, so the key is kept as is.
+    ///
+    /// # Errors
+    ///
+    /// If the descriptor is a multipath descriptor
+    ///
+    /// # Panics
+    ///
+    /// If index ≥ 2^31
+    pub fn at_derivation_index(
+        &self,
+        index: u32,
+    ) -> Result<ConfidentialDescriptor<DefiniteDescriptorKey, T>, ConversionError> {
+        Ok(ConfidentialDescriptor {
+            key: self.key.clone(),
+            descriptor: self.descriptor.at_derivation_index(index)?,
+        })
+    }
+}
+
+impl<Pk: MiniscriptKey, T: Extension> fmt::Display for ConfidentialDescriptor<Pk, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use fmt::Write;
+        let mut wrapped_f = checksum::Formatter::new(f);
+        write!(wrapped_f, "ct({},{:#})", self.key, self.descriptor)?;
+        wrapped_f.write_checksum_if_not_alt()
+    }
+}
+
+impl_from_str!(
+    ;T; Extension,
+    ConfidentialDescriptor<Pk, T>,
+    type Err = Error;,
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let desc_str = verify_checksum(s)?;
+        if !desc_str.starts_with("ct(") || !desc_str.ends_with(')') {
+            return Err(Error::BadDescriptor(String::from(
+                "Not a confidential descriptor",
+            )));
+        }
+        let inner = &desc_str[3..desc_str.len() - 1];
+        // The key ends at the first comma outside of parentheses. The
+        // descriptor is parsed from its string since taproot trees cannot be
+        // parsed as expression trees.
+        let mut depth = 0;
+        let comma = inner
+            .char_indices()
+            .find(|&(_, ch)| {
+                match ch {
+                    '(' => depth += 1,
+                    ')' => depth -= 1,
+                    _ => {}
+                }
+                ch == ',' && depth == 0
+            })
+            .map(|(i, _)| i)
+            .ok_or_else(|| Error::BadDescriptor(String::from("ct() requires two arguments")))?;
+        let key = BlindingKey::from_str(&inner[..comma])?;
+        let descriptor = Descriptor::<Pk, T>::from_str(&inner[comma + 1..])?;
+        Ok(ConfidentialDescriptor { key, descriptor })
+    }
+);
+
+serde_string_impl_pk!(ConfidentialDescriptor, "a confidential descriptor", T; Extension);
+
+#[cfg(test)]
+mod tests {
+    use elements::AddressParams;
+
+    use super::*;
+
+    const MBK: &str = "b2396b3ee20509cdb64fe24180a14a72dbd671728eaa49bac69d2bdecb5f5a04";
+
+    #[test]
+    fn ct_slip77_roundtrip() {
+        let secp = Secp256k1::new();
+        let s = format!(
+            "ct(slip77({}),elwpkh(03daed4f2be3a8bf278e70132fb0beb7522f570e144bf615c07e996d443dee8729))",
+            MBK
+        );
+        let desc = ConfidentialDescriptor::<bitcoin::PublicKey>::from_str(&s).unwrap();
+        let displayed = desc.to_string();
+        assert!(displayed.starts_with(&s));
+        assert_eq!(
+            ConfidentialDescriptor::<bitcoin::PublicKey>::from_str(&displayed).unwrap(),
+            desc
+        );
+        assert!(
+            ConfidentialDescriptor::<bitcoin::PublicKey>::from_str(&format!("{}q", displayed))
+                .is_err()
+        );
+
+        let spk = desc.descriptor.script_pubkey();
+        let mbk = MasterBlindingKey(secp256k1_zkp::SecretKey::from_str(MBK).unwrap());
+        let blinder =
+            secp256k1_zkp::PublicKey::from_secret_key(&secp, &mbk.derive_blinding_key(&spk));
+        assert_eq!(desc.blinding_key(&secp, &spk).unwrap(), blinder);
+        let address = desc.address(&secp, &AddressParams::LIQUID).unwrap();
+        assert_eq!(address.blinding_pubkey, Some(blinder));
+        assert_eq!(address.script_pubkey(), spk);
+    }
+
+    #[test]
+    fn ct_ranged_tr() {
+        let secp = Secp256k1::new();
+        let s = format!(
+            "ct(slip77({}),eltr([73c5da0a/86'/0'/0']xpub6BgBgsespWvERF3LHQu6CnqdvfEvtMcQjYrcRzx53QJjSxarj2afYWcLteoGVky7D3UKDP9QyrLprQ3VCECoY49yfdDEHGCtMMj92pReUsQ/0/*,{{pk(03daed4f2be3a8bf278e70132fb0beb7522f570e144bf615c07e996d443dee8729),pk(028c28a97bf8298bc0d23d8c749452a32e694b65e30a9472a3954ab30fe5324caa)}}))",
+            MBK
+        );
+        let desc = ConfidentialDescriptor::<DescriptorPublicKey>::from_str(&s).unwrap();
+        assert!(desc.to_string().starts_with(&s));
+
+        let a0 = desc
+            .at_derivation_index(0)
+            .unwrap()
+            .address(&secp, &AddressParams::LIQUID)
+            .unwrap();
+        let a1 = desc
+            .at_derivation_index(1)
+            .unwrap()
+            .address(&secp, &AddressParams::LIQUID)
+            .unwrap();
+        assert_ne!(a0.script_pubkey(), a1.script_pubkey());
+        assert_ne!(a0.blinding_pubkey, a1.blinding_pubkey);
+        assert_eq!(
+            desc.derived_address(&secp, 1, &AddressParams::LIQUID),
+            Ok(a1)
+        );
+        assert_eq!(
+            desc.derived_address(&secp, 1 << 31, &AddressParams::LIQUID),
+            Err(DerivedAddressError::HardenedIndex(1 << 31))
+        );
+        let hardened =
+            ConfidentialDescriptor::<DescriptorPublicKey>::from_str(&s.replace("/0/*", "/0/*'"))
+                .unwrap();
+        assert_eq!(
+            hardened.derived_address(&secp, 0, &AddressParams::LIQUID),
+            Err(DerivedAddressError::Key(ConversionError::HardenedChild))
+        );
+
+        assert!(
+            ConfidentialDescriptor::<DescriptorPublicKey>::from_str(&format!(
+                "ct(slip77({}))",
+                MBK
+            ))
+            .is_err()
+        );
+        assert!(ConfidentialDescriptor::<DescriptorPublicKey>::from_str(
+            "ct(slip77(00),elwpkh(03daed4f2be3a8bf278e70132fb0beb7522f570e144bf615c07e996d443dee8729))"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn ct_elip150_view_keys() {
+        let secp = Secp256k1::new();
+        let desc = "elwpkh(03daed4f2be3a8bf278e70132fb0beb7522f570e144bf615c07e996d443dee8729)";
+        let sk = secp256k1_zkp::SecretKey::from_slice(&[1; 32]).unwrap();
+        let wif = bitcoin::PrivateKey::new(sk, bitcoin::Network::Bitcoin);
+        let pk = secp256k1_zkp::PublicKey::from_secret_key(&secp, &sk);
+
+        let private = format!("ct({},{})", wif, desc);
+        let private = ConfidentialDescriptor::<bitcoin::PublicKey>::from_str(&private).unwrap();
+        let public = format!("ct({},{})", pk, desc);
+        let public = ConfidentialDescriptor::<bitcoin::PublicKey>::from_str(&public).unwrap();
+        for desc in &[&private, &public] {
+            assert_eq!(
+                ConfidentialDescriptor::<bitcoin::PublicKey>::from_str(&desc.to_string()).unwrap(),
+                **desc
+            );
+        }
+
+        let spk = public.descriptor.script_pubkey();
+        let blinder = public.blinding_key(&secp, &spk).unwrap();
+        assert_ne!(blinder, pk);
+        assert_eq!(private.blinding_key(&secp, &spk).unwrap(), blinder);
+        assert_eq!(public.key.blinding_private_key(&secp, &spk).unwrap(), None);
+        let blinding_sk = private
+            .key
+            .blinding_private_key(&secp, &spk)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            secp256k1_zkp::PublicKey::from_secret_key(&secp, &blinding_sk),
+            blinder
+        );
+        assert_eq!(
+            public
+                .address(&secp, &AddressParams::LIQUID)
+                .unwrap()
+                .blinding_pubkey,
+            Some(blinder)
+        );
+        // Each script pubkey gets its own blinding key
+        let other = Script::from(vec![0x51]);
+        assert_ne!(public.blinding_key(&secp, &other).unwrap(), blinder);
+
+        // Extended view keys, with unhardened derivation steps
+        let xprv = "xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi";
+        let xpub = "xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8";
+        let private = BlindingKey::from_str(&format!("{}/0/1", xprv)).unwrap();
+        let public = BlindingKey::from_str(&format!("{}/0/1", xpub)).unwrap();
+        assert_eq!(
+            private.blinding_key(&secp, &spk).unwrap(),
+            public.blinding_key(&secp, &spk).unwrap()
+        );
+        assert!(BlindingKey::from_str(&format!("{}/0/*", xpub)).is_err());
+        assert!(BlindingKey::from_str(&format!("{}/0/*", xprv)).is_err());
+        assert!(BlindingKey::from_str(&format!("{}/0'", xpub)).is_err());
+
+        // Bare keys and SLIP-77 through the trait
+        let descriptor = Descriptor::<bitcoin::PublicKey>::from_str(desc).unwrap();
+        assert_eq!(
+            descriptor
+                .confidential_address(&secp, &pk, &AddressParams::LIQUID)
+                .unwrap()
+                .blinding_pubkey,
+            Some(pk)
+        );
+        let mbk = MasterBlindingKey(secp256k1_zkp::SecretKey::from_str(MBK).unwrap());
+        assert_eq!(
+            descriptor
+                .confidential_address(&secp, &mbk, &AddressParams::LIQUID)
+                .unwrap()
+                .blinding_pubkey,
+            Some(mbk.blinding_key(&secp, &spk).unwrap())
+        );
+    }
+}
@@ -0,0 +1,261 @@
+// Miniscript
+// Written in 2023 by
+//     The Elements Miniscript developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Liquid Derivation Paths
+//!
+//! Standard single-key wallet descriptors at the BIP-84 and BIP-86
+//! derivation paths with Liquid's SLIP-44 coin type, in the spirit of the
+//! wallet templates of bdk for Bitcoin.
+//!
+
+use bitcoin::secp256k1::{Secp256k1, Signing};
+use bitcoin::util::bip32::{
+    ChildNumber, DerivationPath, ExtendedPrivKey, ExtendedPubKey, Fingerprint,
+};
+use bitcoin::Network;
+
+use super::checksum::desc_checksum;
+use super::{Descriptor, DescriptorPublicKey, DescriptorXKey, Wildcard};
+use crate::Error;
+
+/// SLIP-44 coin type of Liquid
+pub const LIQUID_COIN_TYPE: u32 = 1776;
+
+/// SLIP-44 coin type of all test networks
+pub const TESTNET_COIN_TYPE: u32 = 1;
+
+/// BIP-43 purpose of BIP-84 `elwpkh` wallets
+pub const BIP84_PURPOSE: u32 = 84;
+
+/// BIP-43 purpose of BIP-86 `eltr` wallets
+pub const BIP86_PURPOSE: u32 = 86;
+
+/// Derivation step of receive addresses below the account key
+pub const RECEIVE_CHAIN: u32 = 0;
+
+/// Derivation step of change addresses below the account key
+pub const CHANGE_CHAIN: u32 = 1;
+
+/// The coin type of `network`: [`LIQUID_COIN_TYPE`] for
+/// [`Network::Bitcoin`], which stands for Liquid mainnet in extended keys,
+/// and [`TESTNET_COIN_TYPE`] otherwise
+pub fn coin_type(network: Network) -> u32 {
+    match network {
+        Network::Bitcoin => LIQUID_COIN_TYPE,
+        _ => TESTNET_COIN_TYPE,
+    }
+}
+
+/// The account derivation path `m/purpose'/coin_type'/account'`
+///
+/// # Errors
+/// If `purpose` or `account` ≥ 2^31, so that they cannot be hardened
+pub fn account_path(purpose: u32, network: Network, account: u32) -> Result<DerivationPath, Error> {
+    let hardened = |index| {
+        ChildNumber::from_hardened_idx(index)
+            .map_err(|_| Error::BadDescriptor(format!("hardened derivation step {} ≥ 2^31", index)))
+    };
+    Ok(DerivationPath::from(vec![
+        hardened(purpose)?,
+        hardened(coin_type(network))?,
+        hardened(account)?,
+    ]))
+}
+
+/// A standard wallet template, see [`liquid_bip84`] and [`liquid_bip86`]
+///
+/// The methods building paths and descriptors fail if the account is
+/// ≥ 2^31.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LiquidTemplate {
+    purpose: u32,
+    account: u32,
+}
+
+/// The `elwpkh` wallet of `account` at `m/84'/1776'/account'`
+pub fn liquid_bip84(account: u32) -> LiquidTemplate {
+    LiquidTemplate {
+        purpose: BIP84_PURPOSE,
+        account,
+    }
+}
+
+/// The key spend only `eltr` wallet of `account` at `m/86'/1776'/account'`
+pub fn liquid_bip86(account: u32) -> LiquidTemplate {
+    LiquidTemplate {
+        purpose: BIP86_PURPOSE,
+        account,
+    }
+}
+
+impl LiquidTemplate {
+    /// The BIP-43 purpose of the template
+    pub fn purpose(&self) -> u32 {
+        self.purpose
+    }
+
+    /// The account of the template
+    pub fn account(&self) -> u32 {
+        self.account
+    }
+
+    /// The account derivation path on `network`
+    pub fn account_path(&self, network: Network) -> Result<DerivationPath, Error> {
+        account_path(self.purpose, network, self.account)
+    }
+
+    /// The descriptors of the account of the master key `master`. The
+    /// descriptors only contain the account extended public key.
+    pub fn for_master_xpriv<C: Signing>(
+        &self,
+        secp: &Secp256k1<C>,
+        master: &ExtendedPrivKey,
+    ) -> Result<DescriptorPair, Error> {
+        let path = self.account_path(master.network)?;
+        let account_xpriv = master
+            .derive_priv(secp, &path)
+            .map_err(|e| Error::Unexpected(e.to_string()))?;
+        let account_xpub = ExtendedPubKey::from_priv(secp, &account_xpriv);
+        self.for_account_xpub(account_xpub, master.fingerprint(secp))
+    }
+
+    /// The descriptors of the account extended public key `account_xpub`,
+    /// derived from the master key with fingerprint `fingerprint`
+    pub fn for_account_xpub(
+        &self,
+        account_xpub: ExtendedPubKey,
+        fingerprint: Fingerprint,
+    ) -> Result<DescriptorPair, Error> {
+        let origin = (fingerprint, self.account_path(account_xpub.network)?);
+        let key = |chain| {
+            DescriptorPublicKey::XPub(DescriptorXKey {
+                origin: Some(origin.clone()),
+                xkey: account_xpub,
+                derivation_path: DerivationPath::from(vec![ChildNumber::Normal { index: chain }]),
+                wildcard: Wildcard::Unhardened,
+            })
+        };
+        let descriptor = |chain| match self.purpose {
+            BIP84_PURPOSE => Descriptor::new_wpkh(key(chain)),
+            _ => Descriptor::new_tr(key(chain), None),
+        };
+        Ok(DescriptorPair {
+            receive: descriptor(RECEIVE_CHAIN)?,
+            change: descriptor(CHANGE_CHAIN)?,
+            fingerprint,
+            account_path: origin.1,
+            account_xpub,
+        })
+    }
+}
+
+/// The receive and change descriptors of an account
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DescriptorPair {
+    /// Descriptor of receive addresses, at the `0/*` chain of the account
+    pub receive: Descriptor<DescriptorPublicKey>,
+    /// Descriptor of change addresses, at the `1/*` chain of the account
+    pub change: Descriptor<DescriptorPublicKey>,
+    fingerprint: Fingerprint,
+    account_path: DerivationPath,
+    account_xpub: ExtendedPubKey,
+}
+
+impl DescriptorPair {
+    /// The BIP-389 multipath descriptor `...xpub/<0;1>/*` covering both
+    /// chains, with its checksum. Parsing it and splitting it with
+    /// [`Descriptor::into_single_descriptors`] gives the `receive` and
+    /// `change` descriptors.
+    pub fn to_multipath_string(&self) -> String {
+        let fragment = match self.receive {
+            Descriptor::Wpkh(..) => "elwpkh",
+            _ => "eltr",
+        };
+        let desc = format!(
+            "{}([{}/{}]{}/<{};{}>/*)",
+            fragment,
+            self.fingerprint,
+            &self.account_path.to_string()[2..],
+            self.account_xpub,
+            RECEIVE_CHAIN,
+            CHANGE_CHAIN,
+        );
+        let checksum = desc_checksum(&desc).expect("descriptor has only valid characters");
+        format!("{}#{}", desc, checksum)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::descriptor::DescriptorType;
+
+    #[test]
+    fn liquid_templates() {
+        let secp = Secp256k1::new();
+        let master = ExtendedPrivKey::new_master(Network::Bitcoin, &[7; 32]).unwrap();
+        let fingerprint = master.fingerprint(&secp);
+
+        let pair = liquid_bip84(0).for_master_xpriv(&secp, &master).unwrap();
+        assert_eq!(pair.receive.desc_type(), DescriptorType::Wpkh);
+        let receive = pair.receive.to_string();
+        assert!(receive.starts_with(&format!("elwpkh([{}/84'/1776'/0']xpub", fingerprint)));
+        assert!(receive.contains("/0/*)#"));
+        assert!(pair.change.to_string().contains("/1/*)#"));
+        assert_eq!(
+            pair.receive,
+            Descriptor::<DescriptorPublicKey>::from_str(&receive).unwrap()
+        );
+
+        let multipath = pair.to_multipath_string();
+        let (desc, checksum) = multipath.split_at(multipath.len() - 9);
+        assert!(desc.ends_with("/<0;1>/*)"));
+        assert_eq!(&checksum[1..], desc_checksum(desc).unwrap());
+        let single = Descriptor::<DescriptorPublicKey>::from_str(&multipath)
+            .unwrap()
+            .into_single_descriptors()
+            .unwrap();
+        assert_eq!(single, vec![pair.receive.clone(), pair.change.clone()]);
+
+        // Watch-only wallets from the account key are the same
+        let path = liquid_bip86(3).account_path(Network::Bitcoin).unwrap();
+        assert_eq!(path.to_string(), "m/86'/1776'/3'");
+        let xpub = ExtendedPubKey::from_priv(&secp, &master.derive_priv(&secp, &path).unwrap());
+        let pair = liquid_bip86(3).for_account_xpub(xpub, fingerprint).unwrap();
+        assert_eq!(
+            pair,
+            liquid_bip86(3).for_master_xpriv(&secp, &master).unwrap()
+        );
+        assert_eq!(pair.change.desc_type(), DescriptorType::Tr);
+        assert!(pair.to_multipath_string().starts_with("eltr("));
+
+        // Test networks use coin type 1
+        let testnet = ExtendedPrivKey::new_master(Network::Testnet, &[7; 32]).unwrap();
+        let pair = liquid_bip84(1).for_master_xpriv(&secp, &testnet).unwrap();
+        assert!(pair.receive.to_string().contains("/84'/1'/1']tpub"));
+
+        // Accounts must be below 2^31 to be hardened
+        assert!(liquid_bip84(1 << 31)
+            .account_path(Network::Bitcoin)
+            .is_err());
+        assert!(liquid_bip84(1 << 31)
+            .for_master_xpriv(&secp, &master)
+            .is_err());
+        assert!(liquid_bip84((1 << 31) - 1)
+            .for_master_xpriv(&secp, &master)
+            .is_ok());
+    }
+}
@@ -0,0 +1,286 @@
+// Miniscript
+// Written in 2023 by
+//     The Elements Miniscript developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Hardware Signer Requests
+//!
+//! Payloads for "verify address on device" flows, in the style of BIP-388
+//! wallet policies: the descriptor is sent as a template with `@i` key
+//! placeholders, and the keys separately with the origins the device checks
+//! against its own master key.
+//!
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use bitcoin::util::bip32;
+use elements::hashes::{hash160, ripemd160, sha256};
+
+use super::{Descriptor, DescriptorPublicKey, Wildcard};
+use crate::{Error, Extension, MiniscriptKey, TranslatePk, Translator};
+
+/// An extended key of a descriptor, as a hardware signer verifies it
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct KeyInfo {
+    /// The fingerprint of the master key and the path from it to `xkey`,
+    /// if known
+    pub origin: Option<bip32::KeySource>,
+    /// The extended key the addresses are derived from
+    pub xkey: bip32::ExtendedPubKey,
+}
+
+impl fmt::Display for KeyInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some((ref fingerprint, ref path)) = self.origin {
+            write!(f, "[{}", fingerprint)?;
+            for child in path {
+                write!(f, "/{}", child)?;
+            }
+            f.write_str("]")?;
+        }
+        write!(f, "{}", self.xkey)
+    }
+}
+
+/// Maps the keys of a descriptor to the [`KeyInfo`] a hardware signer can
+/// verify, e.g. keys without origin to the origins registered on the device
+pub trait KeyProvider<Pk: MiniscriptKey> {
+    /// The key information of `pk`, `None` if it is unknown
+    fn key_info(&self, pk: &Pk) -> Option<KeyInfo>;
+}
+
+impl<Pk: MiniscriptKey> KeyProvider<Pk> for BTreeMap<Pk, KeyInfo> {
+    fn key_info(&self, pk: &Pk) -> Option<KeyInfo> {
+        self.get(pk).cloned()
+    }
+}
+
+impl<Pk: MiniscriptKey, F: Fn(&Pk) -> Option<KeyInfo>> KeyProvider<Pk> for F {
+    fn key_info(&self, pk: &Pk) -> Option<KeyInfo> {
+        self(pk)
+    }
+}
+
+/// Takes the key information from the extended keys of the descriptor
+/// themselves. Single keys and MuSig keys are unknown.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct EmbeddedOrigins;
+
+impl KeyProvider<DescriptorPublicKey> for EmbeddedOrigins {
+    fn key_info(&self, pk: &DescriptorPublicKey) -> Option<KeyInfo> {
+        match *pk {
+            DescriptorPublicKey::Single(..) | DescriptorPublicKey::MuSig(..) => None,
+            DescriptorPublicKey::XPub(ref xpub) => Some(KeyInfo {
+                origin: xpub.origin.clone(),
+                xkey: xpub.xkey,
+            }),
+            DescriptorPublicKey::MultiXPub(ref xpub) => Some(KeyInfo {
+                origin: xpub.origin.clone(),
+                xkey: xpub.xkey,
+            }),
+        }
+    }
+}
+
+/// A request to display the address of a descriptor on a hardware signer,
+/// see [`Descriptor::display_address_request`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DisplayAddressRequest {
+    /// The descriptor without checksum, with every key replaced by `@i`
+    /// followed by its derivation steps, e.g. `elwpkh(@0/<0;1>/*)`
+    pub template: String,
+    /// The key information of the placeholders, `@i` being `keys[i]`
+    pub keys: Vec<KeyInfo>,
+    /// The derivation index of the address
+    pub index: u32,
+}
+
+impl<Ext: Extension> Descriptor<DescriptorPublicKey, Ext> {
+    /// The payload to verify the address at `index` on a hardware signer,
+    /// with the key information looked up in `provider`.
+    ///
+    /// Keys with the same key information share a placeholder. Descriptors
+    /// without wildcards have a single address, for which `index` is
+    /// ignored by the device.
+    ///
+    /// # Errors
+    /// If `index` is hardened, a key is unknown to `provider` or has
+    /// hardened wildcard derivation, which the device cannot perform from the
+    /// extended public key.
+    pub fn display_address_request<P>(
+        &self,
+        provider: &P,
+        index: u32,
+    ) -> Result<DisplayAddressRequest, Error>
+    where
+        P: KeyProvider<DescriptorPublicKey>,
+    {
+        if index >= 1 << 31 {
+            return Err(Error::BadDescriptor(format!(
+                "hardened index {} cannot be derived on the device",
+                index
+            )));
+        }
+        let mut t = Placeholders {
+            provider,
+            keys: vec![],
+        };
+        let template = self.translate_pk(&mut t)?;
+        Ok(DisplayAddressRequest {
+            template: format!("{:#}", template),
+            keys: t.keys,
+            index,
+        })
+    }
+}
+
+// Replaces the keys of a descriptor with placeholders, collecting their
+// key information
+struct Placeholders<'a, P> {
+    provider: &'a P,
+    keys: Vec<KeyInfo>,
+}
+
+impl<'a, P: KeyProvider<DescriptorPublicKey>> Translator<DescriptorPublicKey, String, Error>
+    for Placeholders<'a, P>
+{
+    fn pk(&mut self, pk: &DescriptorPublicKey) -> Result<String, Error> {
+        let info = self
+            .provider
+            .key_info(pk)
+            .ok_or_else(|| Error::BadDescriptor(format!("no key information for {}", pk)))?;
+        let idx = match self.keys.iter().position(|k| *k == info) {
+            Some(idx) => idx,
+            None => {
+                self.keys.push(info);
+                self.keys.len() - 1
+            }
+        };
+        Ok(format!("@{}{}", idx, derivation_suffix(pk)?))
+    }
+
+    fn sha256(&mut self, sha256: &sha256::Hash) -> Result<String, Error> {
+        Ok(sha256.to_string())
+    }
+
+    fn hash256(&mut self, hash256: &crate::hash256::Hash) -> Result<String, Error> {
+        Ok(hash256.to_string())
+    }
+
+    fn ripemd160(&mut self, ripemd160: &ripemd160::Hash) -> Result<String, Error> {
+        Ok(ripemd160.to_string())
+    }
+
+    fn hash160(&mut self, hash160: &hash160::Hash) -> Result<String, Error> {
+        Ok(hash160.to_string())
+    }
+}
+
+// The derivation steps written after the extended key of `pk`, e.g. `/0/*`
+fn derivation_suffix(pk: &DescriptorPublicKey) -> Result<String, Error> {
+    let (xkey, wildcard) = match *pk {
+        DescriptorPublicKey::Single(..) => return Ok(String::new()),
+        DescriptorPublicKey::XPub(ref xpub) => (xpub.xkey, xpub.wildcard),
+        DescriptorPublicKey::MultiXPub(ref xpub) => (xpub.xkey, xpub.wildcard),
+        DescriptorPublicKey::MuSig(..) => {
+            return Err(Error::BadDescriptor(format!(
+                "musig() key {} cannot be displayed on the device",
+                pk
+            )))
+        }
+    };
+    if wildcard == Wildcard::Hardened {
+        return Err(Error::BadDescriptor(format!(
+            "hardened derivation of {} cannot be performed on the device",
+            pk
+        )));
+    }
+    // The display of the key is `[origin]xkey` followed by the steps
+    let s = pk.to_string();
+    let xkey = xkey.to_string();
+    let start = s.find(&xkey).expect("extended key is displayed") + xkey.len();
+    Ok(s[start..].to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::NoExt;
+
+    const XPUB: &str = "tpubD6NzVbkrYhZ4XHndKkuB8FifXm8r5FQHwrN6oZuWCz13qb93rtgKvD4PQsqC4HP4yhV3tA2fqr2RbY5mNXfM7RxXUoeABoDtsFUq2zJq6YK";
+    const XPUB2: &str = "tpubD6NzVbkrYhZ4WQdzxL7NmJN7b85ePo4p6RSj9QQHF7te2RR9iUeVSGgnGkoUsB9LBRosgvNbjRv9bcsJgzgBd7QKuxDm23ZewkTRzNSLEDr";
+
+    #[test]
+    fn display_address_request() {
+        let desc = Descriptor::<DescriptorPublicKey, NoExt>::from_str(&format!(
+            "elwsh(or_d(pk([d34db33f/48'/1776'/0']{}/<0;1>/*),and_v(v:pk({}/0/*),older(144))))",
+            XPUB, XPUB2
+        ))
+        .unwrap();
+        let req = desc.display_address_request(&EmbeddedOrigins, 7).unwrap();
+        assert_eq!(
+            req.template,
+            "elwsh(or_d(pk(@0/<0;1>/*),and_v(v:pk(@1/0/*),older(144))))"
+        );
+        assert_eq!(
+            req.keys.iter().map(|k| k.to_string()).collect::<Vec<_>>(),
+            vec![
+                format!("[d34db33f/48'/1776'/0']{}", XPUB),
+                XPUB2.to_string()
+            ]
+        );
+        assert_eq!(req.index, 7);
+        assert!(desc
+            .display_address_request(&EmbeddedOrigins, 1 << 31)
+            .is_err());
+
+        // The same key on different paths shares a placeholder, and keys
+        // without origin can be looked up from the device registration
+        let desc = Descriptor::<DescriptorPublicKey, NoExt>::from_str(&format!(
+            "elwsh(multi(1,{}/0/*,{}/1/*))",
+            XPUB, XPUB
+        ))
+        .unwrap();
+        let registered = |pk: &DescriptorPublicKey| {
+            EmbeddedOrigins.key_info(pk).map(|info| KeyInfo {
+                origin: Some((
+                    bip32::Fingerprint::from_str("deadbeef").unwrap(),
+                    bip32::DerivationPath::from_str("m/48'/1'").unwrap(),
+                )),
+                ..info
+            })
+        };
+        let req = desc.display_address_request(&registered, 0).unwrap();
+        assert_eq!(req.template, "elwsh(multi(1,@0/0/*,@0/1/*))");
+        assert_eq!(req.keys.len(), 1);
+        assert_eq!(
+            req.keys[0].to_string(),
+            format!("[deadbeef/48'/1']{}", XPUB)
+        );
+
+        // Single keys and hardened derivation are not supported
+        let single = Descriptor::<DescriptorPublicKey, NoExt>::from_str(
+            "elwpkh(028c28a97bf8298bc0d23d8c749452a32e694b65e30a9472a3954ab30fe5324caa)",
+        )
+        .unwrap();
+        assert!(single.display_address_request(&EmbeddedOrigins, 0).is_err());
+        let hardened =
+            Descriptor::<DescriptorPublicKey, NoExt>::from_str(&format!("elwpkh({}/0/*h)", XPUB))
+                .unwrap();
+        assert!(hardened
+            .display_address_request(&EmbeddedOrigins, 0)
+            .is_err());
+    }
+}
@@ -0,0 +1,270 @@
+// Miniscript
+// Written in 2023 by
+//     The Elements Miniscript developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Descriptor Explanation
+//!
+//! Indented multi-line dumps of descriptors, for command line tools and bug
+//! reports where the one-line `Display` output of large covenants is
+//! unreadable.
+//!
+
+use std::fmt::{self, Write};
+
+use super::{Descriptor, ShInner, SortedMultiVec, TapTree, WshInner};
+use crate::miniscript::ScriptContext;
+use crate::{Extension, Miniscript, MiniscriptKey, Terminal};
+
+/// Keys and hashes longer than this are abbreviated
+const MAX_ITEM_LEN: usize = 20;
+
+impl<Pk: MiniscriptKey, Ext: Extension> Descriptor<Pk, Ext> {
+    /// An indented tree dump of the descriptor, one fragment per line.
+    ///
+    /// Each Miniscript fragment is shown with its type, e.g. `B/onduesm`,
+    /// its script size and its satisfaction cost in witness bytes, when it
+    /// is satisfiable. Keys and hashes are abbreviated, so the dump cannot
+    /// be parsed back; use `Display` for that. The format is meant to be
+    /// read and may change between versions.
+    pub fn explain(&self) -> String {
+        let mut out = String::new();
+        match *self {
+            Descriptor::Bare(ref bare) => {
+                line(&mut out, 0, "elbare");
+                explain_ms(&mut out, 1, bare.as_inner());
+            }
+            Descriptor::Pkh(ref pkh) => {
+                line(&mut out, 0, format!("elpkh({})", abbrev(pkh.as_inner())));
+            }
+            Descriptor::Wpkh(ref wpkh) => {
+                line(&mut out, 0, format!("elwpkh({})", abbrev(wpkh.as_inner())));
+            }
+            Descriptor::Sh(ref sh) => {
+                line(&mut out, 0, "elsh");
+                match *sh.as_inner() {
+                    ShInner::Wsh(ref wsh) => {
+                        line(&mut out, 1, "wsh");
+                        explain_wsh(&mut out, 2, wsh.as_inner());
+                    }
+                    ShInner::Wpkh(ref wpkh) => {
+                        line(&mut out, 1, format!("wpkh({})", abbrev(wpkh.as_inner())));
+                    }
+                    ShInner::SortedMulti(ref smv) => explain_sortedmulti(&mut out, 1, smv),
+                    ShInner::Ms(ref ms) => explain_ms(&mut out, 1, ms),
+                }
+            }
+            Descriptor::Wsh(ref wsh) => {
+                line(&mut out, 0, "elwsh");
+                explain_wsh(&mut out, 1, wsh.as_inner());
+            }
+            Descriptor::Tr(ref tr) => {
+                line(&mut out, 0, "eltr");
+                line(
+                    &mut out,
+                    1,
+                    format!("internal key {}", abbrev(tr.internal_key())),
+                );
+                if let Some(ref tree) = *tr.taptree() {
+                    explain_taptree(&mut out, 1, tree);
+                }
+            }
+            Descriptor::TrExt(ref tr) => {
+                line(&mut out, 0, "eltr");
+                line(
+                    &mut out,
+                    1,
+                    format!("internal key {}", abbrev(tr.internal_key())),
+                );
+                if let Some(ref tree) = *tr.taptree() {
+                    explain_taptree(&mut out, 1, tree);
+                }
+            }
+            Descriptor::LegacyCSFSCov(ref cov) => {
+                line(&mut out, 0, "elcovwsh");
+                line(&mut out, 1, format!("covenant key {}", abbrev(cov.pk())));
+                explain_ms(&mut out, 1, cov.to_ms());
+            }
+        }
+        out
+    }
+}
+
+fn line<S: AsRef<str>>(out: &mut String, depth: usize, s: S) {
+    for _ in 0..depth {
+        out.push_str("  ");
+    }
+    out.push_str(s.as_ref());
+    out.push('\n');
+}
+
+// The display of `item`, abbreviated if it is long
+fn abbrev<T: fmt::Display>(item: &T) -> String {
+    let s = item.to_string();
+    let n = s.chars().count();
+    if n <= MAX_ITEM_LEN {
+        s
+    } else {
+        let head: String = s.chars().take(8).collect();
+        let tail: String = s.chars().skip(n - 4).collect();
+        format!("{}..{}", head, tail)
+    }
+}
+
+fn abbrev_list<T: fmt::Display>(k: usize, items: &[T]) -> String {
+    let mut s = k.to_string();
+    for item in items {
+        s.push(',');
+        s.push_str(&abbrev(item));
+    }
+    s
+}
+
+fn explain_wsh<Pk: MiniscriptKey>(out: &mut String, depth: usize, inner: &WshInner<Pk>) {
+    match *inner {
+        WshInner::SortedMulti(ref smv) => explain_sortedmulti(out, depth, smv),
+        WshInner::Ms(ref ms) => explain_ms(out, depth, ms),
+    }
+}
+
+fn explain_sortedmulti<Pk: MiniscriptKey, Ctx: ScriptContext>(
+    out: &mut String,
+    depth: usize,
+    smv: &SortedMultiVec<Pk, Ctx>,
+) {
+    line(
+        out,
+        depth,
+        format!("sortedmulti({})", abbrev_list(smv.k, &smv.pks)),
+    );
+}
+
+fn explain_taptree<Pk: MiniscriptKey, Ext: Extension>(
+    out: &mut String,
+    depth: usize,
+    tree: &TapTree<Pk, Ext>,
+) {
+    match *tree {
+        TapTree::Tree(ref left, ref right) => {
+            line(out, depth, "branch");
+            explain_taptree(out, depth + 1, left);
+            explain_taptree(out, depth + 1, right);
+        }
+        TapTree::Leaf(ref ms) => {
+            line(out, depth, "leaf");
+            explain_ms(out, depth + 1, ms);
+        }
+        TapTree::Hidden(ref hash) => line(out, depth, format!("hidden({})", abbrev(hash))),
+    }
+}
+
+fn explain_ms<Pk, Ctx, Ext>(out: &mut String, depth: usize, ms: &Miniscript<Pk, Ctx, Ext>)
+where
+    Pk: MiniscriptKey,
+    Ctx: ScriptContext,
+    Ext: Extension,
+{
+    let name = match ms.node {
+        Terminal::True => String::from("1"),
+        Terminal::False => String::from("0"),
+        Terminal::PkK(ref pk) => format!("pk_k({})", abbrev(pk)),
+        Terminal::PkH(ref pk) => format!("pk_h({})", abbrev(pk)),
+        Terminal::RawPkH(ref pkh) => format!("pk_h({})", abbrev(pkh)),
+        Terminal::After(t) => format!("after({})", t),
+        Terminal::Older(t) => format!("older({})", t),
+        Terminal::Sha256(ref h) => format!("sha256({})", abbrev(h)),
+        Terminal::Hash256(ref h) => format!("hash256({})", abbrev(h)),
+        Terminal::Ripemd160(ref h) => format!("ripemd160({})", abbrev(h)),
+        Terminal::Hash160(ref h) => format!("hash160({})", abbrev(h)),
+        Terminal::Ext(ref e) => e.to_string(),
+        Terminal::Alt(..) => String::from("a:"),
+        Terminal::Swap(..) => String::from("s:"),
+        Terminal::Check(..) => String::from("c:"),
+        Terminal::DupIf(..) => String::from("d:"),
+        Terminal::Verify(..) => String::from("v:"),
+        Terminal::NonZero(..) => String::from("j:"),
+        Terminal::ZeroNotEqual(..) => String::from("n:"),
+        Terminal::AndV(..) => String::from("and_v"),
+        Terminal::AndB(..) => String::from("and_b"),
+        Terminal::AndOr(..) => String::from("andor"),
+        Terminal::OrB(..) => String::from("or_b"),
+        Terminal::OrD(..) => String::from("or_d"),
+        Terminal::OrC(..) => String::from("or_c"),
+        Terminal::OrI(..) => String::from("or_i"),
+        Terminal::Thresh(k, ref subs) => format!("thresh({} of {})", k, subs.len()),
+        Terminal::Multi(k, ref keys) => format!("multi({})", abbrev_list(k, keys)),
+        Terminal::MultiA(k, ref keys) => format!("multi_a({})", abbrev_list(k, keys)),
+    };
+    let mut s = format!("{} [{}] size={}", name, ms.ty, ms.script_size());
+    if let Some((sat_size, _)) = ms.ext.max_sat_size {
+        write!(s, " sat={}", sat_size).expect("writing to a string");
+    }
+    line(out, depth, s);
+    for sub in ms.branches() {
+        explain_ms(out, depth + 1, sub);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::NoExt;
+
+    const K1: &str = "028c28a97bf8298bc0d23d8c749452a32e694b65e30a9472a3954ab30fe5324caa";
+    const K2: &str = "03daed4f2be3a8bf278e70132fb0beb7522f570e144bf615c07e996d443dee8729";
+
+    #[test]
+    fn explain_descriptors() {
+        let wsh = Descriptor::<bitcoin::PublicKey, NoExt>::from_str(&format!(
+            "elwsh(or_d(pk({}),and_v(v:pk({}),older(144))))",
+            K1, K2
+        ))
+        .unwrap();
+        assert_eq!(
+            wsh.explain(),
+            "elwsh
+  or_d [B/fsm] size=77 sat=74
+    c: [B/onduesm] size=35 sat=73
+      pk_k(028c28a9..4caa) [K/onduesm] size=34 sat=73
+    and_v [B/onfsm] size=39 sat=73
+      v: [V/onfsm] size=35 sat=73
+        c: [B/onduesm] size=35 sat=73
+          pk_k(03daed4f..8729) [K/onduesm] size=34 sat=73
+      older(144) [B/zfm] size=4 sat=0
+"
+        );
+
+        let tr = Descriptor::<bitcoin::PublicKey>::from_str(&format!(
+            "eltr({},{{pk({}),and_v(v:pk({}),num64_eq(out_v(0),1000))}})",
+            K1, K2, K1
+        ))
+        .unwrap();
+        assert_eq!(
+            tr.explain(),
+            "eltr
+  internal key 028c28a9..4caa
+  branch
+    leaf
+      c: [B/onduesm] size=34 sat=66
+        pk_k(03daed4f..8729) [K/onduesm] size=33 sat=66
+    leaf
+      and_v [B/onufsm] size=48 sat=66
+        v: [V/onfsm] size=34 sat=66
+          c: [B/onduesm] size=34 sat=66
+            pk_k(028c28a9..4caa) [K/onduesm] size=33 sat=66
+        num64_eq(out_v(0),1000) [B/zufm] size=14 sat=0
+"
+        );
+    }
+}
@@ -0,0 +1,381 @@
+// Miniscript
+// Written in 2023 by
+//     The Elements Miniscript developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Wallet Exports
+//!
+//! A document bundling everything a watch-only wallet needs to find and
+//! unblind its outputs: the confidential receive and change descriptors,
+//! which share the blinding key, and the height to scan from.
+//!
+
+use std::{error, fmt};
+
+use super::{BlindingKey, ConfidentialDescriptor, DescriptorPublicKey, Wildcard};
+use crate::extensions::{CovExtArgs, CovenantExt, ParseableExt};
+use crate::{Extension, ForEachKey};
+
+/// The version of the [`WalletExport`] format written by this library, and
+/// the only one it imports
+pub const WALLET_EXPORT_VERSION: u32 = 1;
+
+/// The confidential descriptors of a watch-only wallet with its metadata.
+///
+/// With the `serde` feature, exports serialize to a map with a `version`
+/// field and the field names below, in which the descriptors are serialized
+/// as strings with checksum. Deserializing rejects other versions and
+/// exports failing [`WalletExport::validate`], as well as unknown and
+/// duplicate fields. The change descriptor and the label may be omitted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WalletExport<T: Extension = CovenantExt<CovExtArgs>> {
+    /// The descriptor of the receive addresses
+    pub descriptor: ConfidentialDescriptor<DescriptorPublicKey, T>,
+    /// The descriptor of the change addresses, if the wallet has any
+    pub change_descriptor: Option<ConfidentialDescriptor<DescriptorPublicKey, T>>,
+    /// Height of the first block which may contain outputs of the wallet
+    pub birthday_height: u32,
+    /// A name for the wallet chosen by the user
+    pub label: Option<String>,
+}
+
+/// An export which cannot be imported, see [`WalletExport::validate`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WalletExportError {
+    /// The export was written in an unknown version of the format
+    UnsupportedVersion(u32),
+    /// A descriptor has no wildcard, so can have a single address only
+    NotRanged,
+    /// A descriptor has multiple derivation paths, instead of separate
+    /// receive and change descriptors
+    Multipath,
+    /// A descriptor has a hardened wildcard, which cannot be derived from
+    /// the public keys
+    HardenedWildcard,
+    /// The receive and change descriptors are blinded by different keys
+    BlindingKeyMismatch,
+    /// The receive and change descriptors are the same
+    SameDescriptors,
+}
+
+impl fmt::Display for WalletExportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            WalletExportError::UnsupportedVersion(v) => {
+                write!(f, "unsupported wallet export version {}", v)
+            }
+            WalletExportError::NotRanged => f.write_str("descriptor has no wildcard"),
+            WalletExportError::Multipath => f.write_str("descriptor has multiple derivation paths"),
+            WalletExportError::HardenedWildcard => f.write_str("descriptor has hardened wildcard"),
+            WalletExportError::BlindingKeyMismatch => {
+                f.write_str("receive and change descriptors have different blinding keys")
+            }
+            WalletExportError::SameDescriptors => {
+                f.write_str("receive and change descriptors are the same")
+            }
+        }
+    }
+}
+
+impl error::Error for WalletExportError {}
+
+impl<T: Extension + ParseableExt> WalletExport<T> {
+    /// Create an export of the wallet with receive `descriptor` and
+    /// optional `change_descriptor`, born at `birthday_height`, without
+    /// label
+    ///
+    /// # Errors
+    /// If the export is not valid, see [`WalletExport::validate`].
+    pub fn new(
+        descriptor: ConfidentialDescriptor<DescriptorPublicKey, T>,
+        change_descriptor: Option<ConfidentialDescriptor<DescriptorPublicKey, T>>,
+        birthday_height: u32,
+    ) -> Result<Self, WalletExportError> {
+        let export = WalletExport {
+            descriptor,
+            change_descriptor,
+            birthday_height,
+            label: None,
+        };
+        export.validate()?;
+        Ok(export)
+    }
+
+    /// The key blinding the outputs of both descriptors
+    pub fn blinding_key(&self) -> &BlindingKey {
+        &self.descriptor.key
+    }
+
+    /// Check that a watch-only wallet can derive the addresses of the
+    /// export: the descriptors have non-hardened wildcards and a single
+    /// derivation path, and the change descriptor is another descriptor
+    /// blinded by the same key.
+    pub fn validate(&self) -> Result<(), WalletExportError> {
+        check_ranged(&self.descriptor)?;
+        if let Some(ref change) = self.change_descriptor {
+            check_ranged(change)?;
+            if change.key != self.descriptor.key {
+                return Err(WalletExportError::BlindingKeyMismatch);
+            }
+            if change.descriptor == self.descriptor.descriptor {
+                return Err(WalletExportError::SameDescriptors);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn check_ranged<T: Extension + ParseableExt>(
+    desc: &ConfidentialDescriptor<DescriptorPublicKey, T>,
+) -> Result<(), WalletExportError> {
+    let desc = &desc.descriptor;
+    if !desc.has_wildcard() {
+        return Err(WalletExportError::NotRanged);
+    }
+    if desc.is_multipath() {
+        return Err(WalletExportError::Multipath);
+    }
+    if desc.for_any_key(has_hardened_wildcard) {
+        return Err(WalletExportError::HardenedWildcard);
+    }
+    Ok(())
+}
+
+fn has_hardened_wildcard(pk: &DescriptorPublicKey) -> bool {
+    match *pk {
+        DescriptorPublicKey::Single(..) => false,
+        DescriptorPublicKey::XPub(ref xpub) => xpub.wildcard == Wildcard::Hardened,
+        DescriptorPublicKey::MultiXPub(ref xpub) => xpub.wildcard == Wildcard::Hardened,
+        DescriptorPublicKey::MuSig(ref musig) => musig.keys().iter().any(has_hardened_wildcard),
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T: Extension> crate::serde::Serialize for WalletExport<T> {
+    fn serialize<S: crate::serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use crate::serde::ser::SerializeStruct;
+
+        let mut s = serializer.serialize_struct("WalletExport", 5)?;
+        s.serialize_field("version", &WALLET_EXPORT_VERSION)?;
+        s.serialize_field("descriptor", &self.descriptor.to_string())?;
+        s.serialize_field(
+            "change_descriptor",
+            &self.change_descriptor.as_ref().map(|d| d.to_string()),
+        )?;
+        s.serialize_field("birthday_height", &self.birthday_height)?;
+        s.serialize_field("label", &self.label)?;
+        s.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T: Extension + ParseableExt> crate::serde::Deserialize<'de> for WalletExport<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: crate::serde::de::Deserializer<'de>,
+    {
+        use std::marker::PhantomData;
+        use std::str::FromStr;
+
+        use crate::serde::de::{self, MapAccess};
+
+        const FIELDS: &[&str] = &[
+            "version",
+            "descriptor",
+            "change_descriptor",
+            "birthday_height",
+            "label",
+        ];
+
+        fn parse<T: Extension, E: de::Error>(
+            s: &str,
+        ) -> Result<ConfidentialDescriptor<DescriptorPublicKey, T>, E> {
+            ConfidentialDescriptor::from_str(s).map_err(E::custom)
+        }
+
+        fn set<V, E: de::Error>(field: &mut Option<V>, name: &'static str, v: V) -> Result<(), E> {
+            if field.is_some() {
+                return Err(E::duplicate_field(name));
+            }
+            *field = Some(v);
+            Ok(())
+        }
+
+        struct Visitor<T>(PhantomData<T>);
+
+        impl<'de, T: Extension + ParseableExt> de::Visitor<'de> for Visitor<T> {
+            type Value = WalletExport<T>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+                formatter.write_str("a wallet export")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut version = None;
+                let mut descriptor = None;
+                let mut change_descriptor = None;
+                let mut birthday_height = None;
+                let mut label = None;
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "version" => set(&mut version, "version", map.next_value::<u32>()?)?,
+                        "descriptor" => {
+                            set(&mut descriptor, "descriptor", map.next_value::<String>()?)?
+                        }
+                        "change_descriptor" => set(
+                            &mut change_descriptor,
+                            "change_descriptor",
+                            map.next_value::<Option<String>>()?,
+                        )?,
+                        "birthday_height" => {
+                            set(&mut birthday_height, "birthday_height", map.next_value()?)?
+                        }
+                        "label" => set(&mut label, "label", map.next_value::<Option<String>>()?)?,
+                        _ => return Err(de::Error::unknown_field(&key, FIELDS)),
+                    }
+                }
+                let version = version.ok_or_else(|| de::Error::missing_field("version"))?;
+                if version != WALLET_EXPORT_VERSION {
+                    return Err(de::Error::custom(WalletExportError::UnsupportedVersion(
+                        version,
+                    )));
+                }
+                let descriptor =
+                    descriptor.ok_or_else(|| de::Error::missing_field("descriptor"))?;
+                let export = WalletExport {
+                    descriptor: parse(&descriptor)?,
+                    change_descriptor: match change_descriptor {
+                        Some(Some(ref s)) => Some(parse(s)?),
+                        Some(None) | None => None,
+                    },
+                    birthday_height: birthday_height
+                        .ok_or_else(|| de::Error::missing_field("birthday_height"))?,
+                    label: label.unwrap_or(None),
+                };
+                export.validate().map_err(de::Error::custom)?;
+                Ok(export)
+            }
+        }
+
+        deserializer.deserialize_struct("WalletExport", FIELDS, Visitor(PhantomData))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    const MBK: &str = "slip77(b2396b3ee20509cdb64fe24180a14a72dbd671728eaa49bac69d2bdecb5f5a04)";
+    const XPUB: &str = "xpub6BgBgsespWvERF3LHQu6CnqdvfEvtMcQjYrcRzx53QJjSxarj2afYWcLteoGVky7D3UKDP9QyrLprQ3VCECoY49yfdDEHGCtMMj92pReUsQ";
+
+    fn ct(key: &str, path: &str) -> ConfidentialDescriptor<DescriptorPublicKey> {
+        ConfidentialDescriptor::from_str(&format!("ct({},elwpkh({}{}))", key, XPUB, path)).unwrap()
+    }
+
+    #[test]
+    fn validate() {
+        let export = WalletExport::new(ct(MBK, "/0/*"), Some(ct(MBK, "/1/*")), 1_000).unwrap();
+        assert_eq!(*export.blinding_key(), ct(MBK, "/0/*").key);
+        assert!(WalletExport::new(ct(MBK, "/0/*"), None, 0).is_ok());
+
+        assert_eq!(
+            WalletExport::new(ct(MBK, "/0/0"), None, 0),
+            Err(WalletExportError::NotRanged)
+        );
+        assert_eq!(
+            WalletExport::new(ct(MBK, "/<0;1>/*"), None, 0),
+            Err(WalletExportError::Multipath)
+        );
+        assert_eq!(
+            WalletExport::new(ct(MBK, "/0/*h"), None, 0),
+            Err(WalletExportError::HardenedWildcard)
+        );
+        assert_eq!(
+            WalletExport::new(ct(MBK, "/0/*"), Some(ct(MBK, "/0/*")), 0),
+            Err(WalletExportError::SameDescriptors)
+        );
+        let other = "slip77(0000000000000000000000000000000000000000000000000000000000000001)";
+        assert_eq!(
+            WalletExport::new(ct(MBK, "/0/*"), Some(ct(other, "/1/*")), 0),
+            Err(WalletExportError::BlindingKeyMismatch)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn export_serde() {
+        let mut export = WalletExport::new(ct(MBK, "/0/*"), Some(ct(MBK, "/1/*")), 1_000).unwrap();
+        export.label = Some("watch-only".to_owned());
+        let json = serde_json::to_value(&export).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "version": 1,
+                "descriptor": ct(MBK, "/0/*").to_string(),
+                "change_descriptor": ct(MBK, "/1/*").to_string(),
+                "birthday_height": 1_000,
+                "label": "watch-only",
+            })
+        );
+        assert_eq!(
+            serde_json::from_value::<WalletExport>(json).unwrap(),
+            export
+        );
+
+        let json = serde_json::json!({
+            "version": 1,
+            "descriptor": ct(MBK, "/0/*").to_string(),
+            "birthday_height": 1_000,
+        });
+        let export = serde_json::from_value::<WalletExport>(json).unwrap();
+        assert_eq!(export.change_descriptor, None);
+        assert_eq!(export.label, None);
+
+        // Unknown versions and invalid exports are rejected
+        let json = serde_json::json!({
+            "version": 2,
+            "descriptor": ct(MBK, "/0/*").to_string(),
+            "birthday_height": 1_000,
+        });
+        assert!(serde_json::from_value::<WalletExport>(json).is_err());
+        let json = serde_json::json!({
+            "version": 1,
+            "descriptor": ct(MBK, "/0/*").to_string(),
+            "change_descriptor": ct(MBK, "/0/*").to_string(),
+            "birthday_height": 1_000,
+        });
+        assert!(serde_json::from_value::<WalletExport>(json).is_err());
+        let json = serde_json::json!({
+            "descriptor": ct(MBK, "/0/*").to_string(),
+            "birthday_height": 1_000,
+        });
+        assert!(serde_json::from_value::<WalletExport>(json).is_err());
+
+        // As are unknown and duplicate fields
+        let json = serde_json::json!({
+            "version": 1,
+            "descriptor": ct(MBK, "/0/*").to_string(),
+            "birthday_height": 1_000,
+            "birthday": 1_000,
+        });
+        let err = serde_json::from_value::<WalletExport>(json).unwrap_err();
+        assert!(err.to_string().contains("unknown field `birthday`"));
+        let json = format!(
+            r#"{{"version":1,"descriptor":"{}","birthday_height":1000,"label":"a","label":"b"}}"#,
+            ct(MBK, "/0/*")
+        );
+        let err = serde_json::from_str::<WalletExport>(&json).unwrap_err();
+        assert!(err.to_string().contains("duplicate field `label`"));
+    }
+}
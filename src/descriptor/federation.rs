@@ -0,0 +1,364 @@
+// Miniscript
+// Written in 2023 by
+//     The Elements Miniscript developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Federation Descriptors
+//!
+//! Bitcoin descriptors of the watchman script of a federated peg: the
+//! functionaries can spend with a threshold of their keys, and after a
+//! relative timelock a threshold of emergency keys can spend as well.
+//!
+//! The parameters of a federation are serialized as
+//! `watchman(thresh(k,fed_keys...),thresh(m,emer_keys...),older(csv))#checksum`
+//! so that they can be published and audited, and the descriptors are built
+//! from them deterministically.
+//!
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::str::FromStr;
+
+use bitcoin::{self, Sequence};
+
+use super::checksum::{desc_checksum, verify_checksum};
+use crate::expression::{self, Tree};
+use crate::{BtcDescriptor, Error};
+
+/// Keys of the functionaries of the Liquid federation, taken from the
+/// functionary codebase. Not public until they are checked against the
+/// federation script on chain.
+// TODO: Verify the keys against the on-chain federation script
+pub(crate) const LIQUID_FUNCTIONARY_KEYS: [&str; 15] = [
+    "020e0338c96a8870479f2396c373cc7696ba124e8635d41b0ea581112b67817261",
+    "02675333a4e4b8fb51d9d4e22fa5a8eaced3fdac8a8cbf9be8c030f75712e6af99",
+    "02896807d54bc55c24981f24a453c60ad3e8993d693732288068a23df3d9f50d48",
+    "029e51a5ef5db3137051de8323b001749932f2ff0d34c82e96a2c2461de96ae56c",
+    "02a4e1a9638d46923272c266631d94d36bdb03a64ee0e14c7518e49d2f29bc4010",
+    "02f8a00b269f8c5e59c67d36db3cdc11b11b21f64b4bffb2815e9100d9aa8daf07",
+    "03079e252e85abffd3c401a69b087e590a9b86f33f574f08129ccbd3521ecf516b",
+    "03111cf405b627e22135b3b3733a4a34aa5723fb0f58379a16d32861bf576b0ec2",
+    "0318f331b3e5d38156da6633b31929c5b220349859cc9ca3d33fb4e68aa0840174",
+    "03230dae6b4ac93480aeab26d000841298e3b8f6157028e47b0897c1e025165de1",
+    "035abff4281ff00660f99ab27bb53e6b33689c2cd8dcd364bc3c90ca5aea0d71a6",
+    "03bd45cddfacf2083b14310ae4a84e25de61e451637346325222747b157446614c",
+    "03cc297026b06c71cbfa52089149157b5ff23de027ac5ab781800a578192d17546",
+    "03d3bde5d63bdb3a6379b461be64dad45eabff42f758543a9645afd42f6d424828",
+    "03ed1e8d5109c9ed66f7941bc53cc71137baa76d50d274bda8d5e8ffbd6e61fe9a",
+];
+
+/// Emergency keys of the Liquid federation, see [`LIQUID_FUNCTIONARY_KEYS`]
+pub(crate) const LIQUID_EMERGENCY_KEYS: [&str; 3] = [
+    "03aab896d53a8e7d6433137bbba940f9c521e085dd07e60994579b64a6d992cf79",
+    "0291b7d0b1b692f8f524516ed950872e5da10fb1b808b5a526dedc6fed1cf29807",
+    "0386aa9372fbab374593466bc5451dc59954e90787f08060964d95c87ef34ca5bb",
+];
+
+/// The maximum number of keys of each threshold, the limit of
+/// `OP_CHECKMULTISIG` used by the legacy watchman script
+pub const MAX_WATCHMAN_KEYS: usize = 20;
+
+/// Internal key of the taproot watchman descriptor, the provably
+/// unspendable point `H` of BIP-341, so that only the script paths can spend
+pub const UNSPENDABLE_INTERNAL_KEY: &str =
+    "0250929b74c1a04954b78b4b6035e97a5e078a5a0f28ec96d547bfee9ace803ac0";
+
+/// The parameters of the watchman script of a federation
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct WatchmanParams {
+    functionaries: Vec<bitcoin::PublicKey>,
+    threshold: usize,
+    emergency_keys: Vec<bitcoin::PublicKey>,
+    emergency_threshold: usize,
+    csv: u16,
+}
+
+impl WatchmanParams {
+    /// Create the parameters of a federation. The keys are used in the order
+    /// given, which determines the script.
+    ///
+    /// # Errors
+    /// If a threshold is zero or larger than its number of keys, if there
+    /// are more than [`MAX_WATCHMAN_KEYS`] functionary or emergency keys, if
+    /// a key is uncompressed or used twice, or if `csv` is zero.
+    pub fn new(
+        functionaries: Vec<bitcoin::PublicKey>,
+        threshold: usize,
+        emergency_keys: Vec<bitcoin::PublicKey>,
+        emergency_threshold: usize,
+        csv: u16,
+    ) -> Result<Self, Error> {
+        if functionaries.len() > MAX_WATCHMAN_KEYS || emergency_keys.len() > MAX_WATCHMAN_KEYS {
+            return Err(Error::BadDescriptor(format!(
+                "{} functionary and {} emergency keys, at most {} of each",
+                functionaries.len(),
+                emergency_keys.len(),
+                MAX_WATCHMAN_KEYS
+            )));
+        }
+        if threshold == 0 || threshold > functionaries.len() {
+            return Err(Error::BadDescriptor(format!(
+                "functionary threshold {} of {} keys",
+                threshold,
+                functionaries.len()
+            )));
+        }
+        if emergency_threshold == 0 || emergency_threshold > emergency_keys.len() {
+            return Err(Error::BadDescriptor(format!(
+                "emergency threshold {} of {} keys",
+                emergency_threshold,
+                emergency_keys.len()
+            )));
+        }
+        if csv == 0 {
+            return Err(Error::BadDescriptor("zero emergency timelock".to_owned()));
+        }
+        let mut seen = BTreeSet::new();
+        for pk in functionaries.iter().chain(&emergency_keys) {
+            if !pk.compressed {
+                return Err(Error::BadDescriptor(format!("uncompressed key {}", pk)));
+            }
+            if !seen.insert(pk) {
+                return Err(Error::BadDescriptor(format!("duplicate key {}", pk)));
+            }
+        }
+        Ok(WatchmanParams {
+            functionaries,
+            threshold,
+            emergency_keys,
+            emergency_threshold,
+            csv,
+        })
+    }
+
+    /// The parameters of the Liquid federation: 11 of the 15 functionaries,
+    /// or 2 of the 3 emergency keys after 4032 blocks, see
+    /// [`LIQUID_FUNCTIONARY_KEYS`]
+    pub(crate) fn liquid() -> Self {
+        let keys = |keys: &[&str]| {
+            keys.iter()
+                .map(|pk| bitcoin::PublicKey::from_str(pk).expect("valid key"))
+                .collect()
+        };
+        WatchmanParams::new(
+            keys(&LIQUID_FUNCTIONARY_KEYS),
+            11,
+            keys(&LIQUID_EMERGENCY_KEYS),
+            2,
+            4032,
+        )
+        .expect("valid parameters")
+    }
+
+    /// The functionary keys
+    pub fn functionaries(&self) -> &[bitcoin::PublicKey] {
+        &self.functionaries
+    }
+
+    /// The number of functionary signatures needed to spend
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    /// The emergency keys
+    pub fn emergency_keys(&self) -> &[bitcoin::PublicKey] {
+        &self.emergency_keys
+    }
+
+    /// The number of emergency signatures needed to spend
+    pub fn emergency_threshold(&self) -> usize {
+        self.emergency_threshold
+    }
+
+    /// The relative timelock in blocks after which the emergency keys can
+    /// spend
+    pub fn csv(&self) -> Sequence {
+        Sequence::from_height(self.csv)
+    }
+}
+
+fn join(keys: &[bitcoin::PublicKey]) -> String {
+    let keys: Vec<_> = keys.iter().map(ToString::to_string).collect();
+    keys.join(",")
+}
+
+impl fmt::Display for WatchmanParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let desc = format!(
+            "watchman(thresh({},{}),thresh({},{}),older({}))",
+            self.threshold,
+            join(&self.functionaries),
+            self.emergency_threshold,
+            join(&self.emergency_keys),
+            self.csv
+        );
+        let checksum = desc_checksum(&desc).map_err(|_| fmt::Error)?;
+        write!(f, "{}#{}", desc, checksum)
+    }
+}
+
+fn parse_thresh(top: &Tree<'_>) -> Result<(usize, Vec<bitcoin::PublicKey>), Error> {
+    if top.name != "thresh" || top.args.is_empty() {
+        return Err(Error::Unexpected(format!(
+            "{}({} args) while parsing watchman thresh",
+            top.name,
+            top.args.len()
+        )));
+    }
+    let k = expression::terminal(&top.args[0], expression::parse_num)?;
+    let keys = top.args[1..]
+        .iter()
+        .map(|arg| expression::terminal(arg, bitcoin::PublicKey::from_str))
+        .collect::<Result<_, _>>()?;
+    Ok((k, keys))
+}
+
+impl FromStr for WatchmanParams {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let desc_str = verify_checksum(s)?;
+        let top = Tree::from_str(desc_str)?;
+        if top.name != "watchman" || top.args.len() != 3 || top.args[2].name != "older" {
+            return Err(Error::Unexpected(format!(
+                "{}({} args) while parsing watchman parameters",
+                top.name,
+                top.args.len()
+            )));
+        }
+        let (threshold, functionaries) = parse_thresh(&top.args[0])?;
+        let (emergency_threshold, emergency_keys) = parse_thresh(&top.args[1])?;
+        let csv = expression::terminal(
+            top.args[2]
+                .args
+                .first()
+                .ok_or_else(|| Error::Unexpected("older without timelock".to_owned()))?,
+            expression::parse_num,
+        )?;
+        WatchmanParams::new(
+            functionaries,
+            threshold,
+            emergency_keys,
+            emergency_threshold,
+            csv,
+        )
+    }
+}
+
+/// The watchman descriptors of a federation, see [`watchman_descriptor`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WatchmanDescriptors {
+    /// The Liquid-style `wsh` descriptor,
+    /// `wsh(or_d(multi(k,fed_keys...),and_v(v:older(csv),multi(m,emer_keys...))))`
+    pub legacy: BtcDescriptor<bitcoin::PublicKey>,
+    /// Its taproot successor, with the unspendable internal key
+    /// [`UNSPENDABLE_INTERNAL_KEY`] and both spending conditions as leaves
+    pub taproot: BtcDescriptor<bitcoin::PublicKey>,
+}
+
+/// Build the watchman descriptors of the federation with parameters
+/// `params`. The same parameters always give the same descriptors.
+///
+/// # Errors
+/// If a descriptor fails to parse, which [`WatchmanParams::new`] rules out
+/// for all parameters it accepts.
+pub fn watchman_descriptor(params: &WatchmanParams) -> Result<WatchmanDescriptors, Error> {
+    let fed = join(&params.functionaries);
+    let emer = join(&params.emergency_keys);
+    let legacy = format!(
+        "wsh(or_d(multi({},{}),and_v(v:older({}),multi({},{}))))",
+        params.threshold, fed, params.csv, params.emergency_threshold, emer
+    );
+    let taproot = format!(
+        "tr({},{{multi_a({},{}),and_v(v:multi_a({},{}),older({}))}})",
+        UNSPENDABLE_INTERNAL_KEY,
+        params.threshold,
+        fed,
+        params.emergency_threshold,
+        emer,
+        params.csv
+    );
+    Ok(WatchmanDescriptors {
+        legacy: BtcDescriptor::from_str(&legacy)?,
+        taproot: BtcDescriptor::from_str(&taproot)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use elements::secp256k1_zkp;
+
+    use super::*;
+    use crate::descriptor::pegin::LegacyPegin;
+    use crate::DescriptorPublicKey;
+
+    #[test]
+    fn watchman_descriptors() {
+        let liquid = WatchmanParams::liquid();
+        assert_eq!(liquid.functionaries().len(), 15);
+        assert_eq!(liquid.csv(), Sequence::from_consensus(4032));
+
+        // The parameters roundtrip through their audit serialization
+        let serialized = liquid.to_string();
+        assert!(serialized.starts_with("watchman(thresh(11,020e0338"));
+        assert_eq!(WatchmanParams::from_str(&serialized).unwrap(), liquid);
+        let corrupted = serialized.replacen("older(4032)", "older(4033)", 1);
+        assert!(WatchmanParams::from_str(&corrupted).is_err());
+
+        // The legacy descriptor is the script of the legacy pegins
+        let descs = watchman_descriptor(&liquid).unwrap();
+        let legacy = descs.legacy.to_string();
+        assert!(legacy.starts_with("wsh(or_d(multi(11,020e0338"));
+        assert!(legacy.contains("and_v(v:older(4032),multi(2,03aab896"));
+        assert_eq!(descs, watchman_descriptor(&liquid).unwrap());
+        let user_desc = crate::Descriptor::from_str(
+            "elwpkh(02d7924d4f7d43ea965a465ae3095ff41131e5946f3c85f79e44adbcf8e27e080e)",
+        )
+        .unwrap();
+        let pegin = LegacyPegin::<DescriptorPublicKey>::new_legacy_fed(user_desc);
+        let script = &legacy["wsh(".len()..legacy.find(")#").unwrap()];
+        // Legacy pegins mark functionary keys with an `f` and emergency keys
+        // with a `u`
+        let pegin = pegin.to_string().replace(",f0", ",0").replace(",u0", ",0");
+        assert!(pegin.starts_with(&format!("legacy_pegin({},", script)));
+        let taproot = descs.taproot.to_string();
+        assert!(taproot.starts_with(&format!("tr({},", UNSPENDABLE_INTERNAL_KEY)));
+        assert!(taproot.contains("{multi_a(11,"));
+        descs.taproot.sanity_check().unwrap();
+
+        // Invalid parameters
+        let keys = liquid.functionaries().to_vec();
+        let emer = liquid.emergency_keys().to_vec();
+        assert!(WatchmanParams::new(keys.clone(), 16, emer.clone(), 2, 4032).is_err());
+        assert!(WatchmanParams::new(keys.clone(), 11, emer.clone(), 0, 4032).is_err());
+        assert!(WatchmanParams::new(keys.clone(), 11, emer.clone(), 2, 0).is_err());
+        assert!(WatchmanParams::new(keys.clone(), 11, keys[..2].to_vec(), 2, 4032).is_err());
+
+        // At most 20 keys of each threshold, the limit of the legacy script
+        let secp = secp256k1_zkp::Secp256k1::signing_only();
+        let many: Vec<_> = (1..=22u8)
+            .map(|i| {
+                let sk = secp256k1_zkp::SecretKey::from_slice(&[i; 32]).unwrap();
+                bitcoin::PublicKey::new(sk.public_key(&secp))
+            })
+            .collect();
+        let params = WatchmanParams::new(many[..20].to_vec(), 20, emer.clone(), 2, 4032).unwrap();
+        watchman_descriptor(&params)
+            .unwrap()
+            .legacy
+            .sanity_check()
+            .unwrap();
+        assert!(WatchmanParams::new(many[..21].to_vec(), 11, emer.clone(), 2, 4032).is_err());
+        assert!(WatchmanParams::new(keys, 11, many[..21].to_vec(), 2, 4032).is_err());
+        assert!(WatchmanParams::new(many[..21].to_vec(), 21, emer, 2, 4032).is_err());
+    }
+}
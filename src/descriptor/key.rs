@@ -1,12 +1,13 @@
 use std::str::FromStr;
 use std::{error, fmt};
 
-use bitcoin::util::bip32;
+use bitcoin::util::{base58, bip32};
 use bitcoin::{self, XpubIdentifier};
 use elements::hashes::hex::FromHex;
 use elements::hashes::{hash160, ripemd160, sha256, Hash, HashEngine};
 use elements::secp256k1_zkp::{Secp256k1, Signing, Verification};
 
+use super::aggregate_keys;
 use crate::{hash256, MiniscriptKey, ToPublicKey};
 
 /// Single public key without any origin or range information
@@ -26,10 +27,14 @@ pub enum DescriptorPublicKey {
     Single(SinglePub),
     /// Extended public key (xpub).
     XPub(DescriptorXKey<bip32::ExtendedPubKey>),
+    /// Extended public key with multiple derivation paths (BIP-389).
+    MultiXPub(DescriptorMultiXKey<bip32::ExtendedPubKey>),
+    /// MuSig2 aggregate of keys (BIP-390), only valid in taproot.
+    MuSig(DescriptorMuSigKey),
 }
 
 /// The descriptor secret key, either a single private key or an xprv.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum DescriptorSecretKey {
     /// Single private key.
     Single(SinglePriv),
@@ -47,7 +52,7 @@ pub struct SinglePub {
 }
 
 /// A descriptor [`bitcoin::PrivateKey`] with optional origin information.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct SinglePriv {
     /// Origin information (fingerprint and derivation path).
     pub origin: Option<(bip32::Fingerprint, bip32::DerivationPath)>,
@@ -68,6 +73,115 @@ pub struct DescriptorXKey<K: InnerXKey> {
     pub wildcard: Wildcard,
 }
 
+/// The derivation paths of a multipath extended key, which all have the
+/// same length.
+#[derive(Debug, Eq, PartialEq, Clone, Ord, PartialOrd, Hash)]
+pub struct DerivPaths(Vec<bip32::DerivationPath>);
+
+impl DerivPaths {
+    /// Create the derivation paths of a multipath key.
+    ///
+    /// Returns `None` if there are fewer than two paths or if their lengths
+    /// differ.
+    pub fn new(paths: Vec<bip32::DerivationPath>) -> Option<DerivPaths> {
+        if paths.len() < 2 || paths.iter().any(|p| p.len() != paths[0].len()) {
+            None
+        } else {
+            Some(DerivPaths(paths))
+        }
+    }
+
+    /// The derivation paths
+    pub fn paths(&self) -> &Vec<bip32::DerivationPath> {
+        &self.0
+    }
+
+    /// Consume and return the derivation paths
+    pub fn into_paths(self) -> Vec<bip32::DerivationPath> {
+        self.0
+    }
+}
+
+/// An extended key with origin, multiple derivation paths, and wildcard,
+/// written with a `<a;b;..>` step as in BIP-389.
+#[derive(Debug, Eq, PartialEq, Clone, Ord, PartialOrd, Hash)]
+pub struct DescriptorMultiXKey<K: InnerXKey> {
+    /// Origin information
+    pub origin: Option<(bip32::Fingerprint, bip32::DerivationPath)>,
+    /// The extended key
+    pub xkey: K,
+    /// The derivation paths
+    pub derivation_paths: DerivPaths,
+    /// Whether the descriptor is wildcard
+    pub wildcard: Wildcard,
+}
+
+/// The keys of a MuSig2 aggregate key, written `musig(KEY,KEY,..)` as in
+/// BIP-390. The keys are derived first and then aggregated, so
+/// `musig(xpub1/*,xpub2/*)` is ranged, but derivation from the aggregate
+/// key, as in `musig(xpub1,xpub2)/*`, is not supported.
+#[derive(Debug, Eq, PartialEq, Clone, Ord, PartialOrd, Hash)]
+pub struct DescriptorMuSigKey {
+    keys: Vec<DescriptorPublicKey>,
+}
+
+impl DescriptorMuSigKey {
+    /// Create the aggregate of `keys`.
+    ///
+    /// Returns an error if there are no keys, if a key is a MuSig key itself,
+    /// an x-only or an uncompressed key, or if multipath keys have different
+    /// numbers of derivation paths.
+    pub fn new(keys: Vec<DescriptorPublicKey>) -> Result<Self, DescriptorKeyParseError> {
+        if keys.is_empty() {
+            return Err(DescriptorKeyParseError("musig() requires at least one key"));
+        }
+        let mut num_paths = None;
+        for key in &keys {
+            match *key {
+                DescriptorPublicKey::MuSig(..) => {
+                    return Err(DescriptorKeyParseError("musig() cannot be nested"))
+                }
+                DescriptorPublicKey::Single(SinglePub {
+                    key: SinglePubKey::XOnly(..),
+                    ..
+                }) => {
+                    return Err(DescriptorKeyParseError(
+                        "x-only keys cannot be aggregated with musig()",
+                    ))
+                }
+                DescriptorPublicKey::Single(SinglePub {
+                    key: SinglePubKey::FullKey(ref pk),
+                    ..
+                }) if !pk.compressed => {
+                    return Err(DescriptorKeyParseError(
+                        "uncompressed keys cannot be aggregated with musig()",
+                    ))
+                }
+                DescriptorPublicKey::MultiXPub(ref xpub) => {
+                    let len = xpub.derivation_paths.paths().len();
+                    if *num_paths.get_or_insert(len) != len {
+                        return Err(DescriptorKeyParseError(
+                            "musig() keys must have the same number of derivation paths",
+                        ));
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(DescriptorMuSigKey { keys })
+    }
+
+    /// The aggregated keys, in the order they are written
+    pub fn keys(&self) -> &[DescriptorPublicKey] {
+        &self.keys
+    }
+
+    /// Consume and return the aggregated keys
+    pub fn into_keys(self) -> Vec<DescriptorPublicKey> {
+        self.keys
+    }
+}
+
 /// A [`DescriptorPublicKey`] without any wildcards.
 #[derive(Debug, Eq, PartialEq, Clone, Ord, PartialOrd, Hash)]
 pub struct DefiniteDescriptorKey(DescriptorPublicKey);
@@ -221,6 +335,111 @@ impl error::Error for DescriptorKeyParseError {
     }
 }
 
+/// SLIP-132 version of an extended public key, which encodes the script
+/// type the key is meant for in its prefix
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Slip132Version {
+    /// `ypub`, mainnet p2sh-wrapped p2wpkh
+    Ypub,
+    /// `zpub`, mainnet p2wpkh
+    Zpub,
+    /// `Ypub`, mainnet p2sh-wrapped p2wsh multisig
+    YpubMultisig,
+    /// `Zpub`, mainnet p2wsh multisig
+    ZpubMultisig,
+    /// `upub`, testnet p2sh-wrapped p2wpkh
+    Upub,
+    /// `vpub`, testnet p2wpkh
+    Vpub,
+    /// `Upub`, testnet p2sh-wrapped p2wsh multisig
+    UpubMultisig,
+    /// `Vpub`, testnet p2wsh multisig
+    VpubMultisig,
+}
+
+const XPUB_VERSION: [u8; 4] = [0x04, 0x88, 0xb2, 0x1e];
+const TPUB_VERSION: [u8; 4] = [0x04, 0x35, 0x87, 0xcf];
+
+impl Slip132Version {
+    /// The version bytes of the extended key
+    pub fn version_bytes(self) -> [u8; 4] {
+        match self {
+            Slip132Version::Ypub => [0x04, 0x9d, 0x7c, 0xb2],
+            Slip132Version::Zpub => [0x04, 0xb2, 0x47, 0x46],
+            Slip132Version::YpubMultisig => [0x02, 0x95, 0xb4, 0x3f],
+            Slip132Version::ZpubMultisig => [0x02, 0xaa, 0x7e, 0xd3],
+            Slip132Version::Upub => [0x04, 0x4a, 0x52, 0x62],
+            Slip132Version::Vpub => [0x04, 0x5f, 0x1c, 0xf6],
+            Slip132Version::UpubMultisig => [0x02, 0x42, 0x89, 0xef],
+            Slip132Version::VpubMultisig => [0x02, 0x57, 0x54, 0x83],
+        }
+    }
+
+    /// Whether the version is for test networks, i.e. normalizes to `tpub`
+    pub fn is_testnet(self) -> bool {
+        match self {
+            Slip132Version::Ypub
+            | Slip132Version::Zpub
+            | Slip132Version::YpubMultisig
+            | Slip132Version::ZpubMultisig => false,
+            Slip132Version::Upub
+            | Slip132Version::Vpub
+            | Slip132Version::UpubMultisig
+            | Slip132Version::VpubMultisig => true,
+        }
+    }
+
+    fn from_version_bytes(bytes: &[u8]) -> Option<Self> {
+        [
+            Slip132Version::Ypub,
+            Slip132Version::Zpub,
+            Slip132Version::YpubMultisig,
+            Slip132Version::ZpubMultisig,
+            Slip132Version::Upub,
+            Slip132Version::Vpub,
+            Slip132Version::UpubMultisig,
+            Slip132Version::VpubMultisig,
+        ]
+        .iter()
+        .copied()
+        .find(|v| v.version_bytes() == bytes)
+    }
+}
+
+impl fmt::Display for Slip132Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match *self {
+            Slip132Version::Ypub => "ypub",
+            Slip132Version::Zpub => "zpub",
+            Slip132Version::YpubMultisig => "Ypub",
+            Slip132Version::ZpubMultisig => "Zpub",
+            Slip132Version::Upub => "upub",
+            Slip132Version::Vpub => "vpub",
+            Slip132Version::UpubMultisig => "Upub",
+            Slip132Version::VpubMultisig => "Vpub",
+        })
+    }
+}
+
+/// Re-encodes a SLIP-132 extended key at the start of `key_deriv` as `xpub`
+/// or `tpub`, returning `None` for keys that need no normalization.
+fn normalize_slip132(key_deriv: &str) -> Option<(String, Slip132Version)> {
+    let (xkey, deriv) = match key_deriv.find('/') {
+        Some(i) => key_deriv.split_at(i),
+        None => (key_deriv, ""),
+    };
+    // Invalid keys are left to the extended key parser to report
+    let mut data = base58::from_check(xkey).ok().filter(|d| d.len() == 78)?;
+    let version = Slip132Version::from_version_bytes(&data[..4])?;
+    let normalized = if version.is_testnet() {
+        TPUB_VERSION
+    } else {
+        XPUB_VERSION
+    };
+    data[..4].copy_from_slice(&normalized);
+    Some((base58::check_encode_slice(&data) + deriv, version))
+}
+
 impl fmt::Display for DescriptorPublicKey {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match *self {
@@ -243,6 +462,27 @@ impl fmt::Display for DescriptorPublicKey {
                 }
                 Ok(())
             }
+            DescriptorPublicKey::MultiXPub(ref xpub) => {
+                maybe_fmt_master_id(f, &xpub.origin)?;
+                xpub.xkey.fmt(f)?;
+                fmt_derivation_paths(f, xpub.derivation_paths.paths())?;
+                match xpub.wildcard {
+                    Wildcard::None => {}
+                    Wildcard::Unhardened => write!(f, "/*")?,
+                    Wildcard::Hardened => write!(f, "/*h")?,
+                }
+                Ok(())
+            }
+            DescriptorPublicKey::MuSig(ref musig) => {
+                f.write_str("musig(")?;
+                for (i, key) in musig.keys.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+                    key.fmt(f)?;
+                }
+                f.write_str(")")
+            }
         }
     }
 }
@@ -290,10 +530,64 @@ fn fmt_derivation_path(f: &mut fmt::Formatter<'_>, path: &bip32::DerivationPath)
     Ok(())
 }
 
+/// Writes multiple derivation paths of the same length to the formatter,
+/// with the steps where they differ as `<a;b;..>`
+fn fmt_derivation_paths(
+    f: &mut fmt::Formatter<'_>,
+    paths: &[bip32::DerivationPath],
+) -> fmt::Result {
+    for i in 0..paths[0].len() {
+        let first = paths[0][i];
+        if paths.iter().all(|p| p[i] == first) {
+            write!(f, "/{}", first)?;
+        } else {
+            f.write_str("/<")?;
+            for (n, p) in paths.iter().enumerate() {
+                if n > 0 {
+                    f.write_str(";")?;
+                }
+                write!(f, "{}", p[i])?;
+            }
+            f.write_str(">")?;
+        }
+    }
+    Ok(())
+}
+
 impl FromStr for DescriptorPublicKey {
     type Err = DescriptorKeyParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        DescriptorPublicKey::from_str_slip132(s).map(|(pk, _)| pk)
+    }
+}
+
+impl DescriptorPublicKey {
+    /// Parses a key like [`FromStr`], additionally returning the
+    /// [`Slip132Version`] of an extended key that was normalized to an
+    /// `xpub` or `tpub`.
+    ///
+    /// The script type implied by a SLIP-132 version is not checked against
+    /// the descriptor the key is used in, so callers may want to warn about
+    /// normalized keys. [`crate::Descriptor::parse_slip132`] reports them
+    /// for a whole descriptor.
+    pub fn from_str_slip132(
+        s: &str,
+    ) -> Result<(Self, Option<Slip132Version>), DescriptorKeyParseError> {
+        if s.starts_with("musig(") {
+            if !s.ends_with(')') {
+                return Err(DescriptorKeyParseError(
+                    "derivation from musig() keys is not supported",
+                ));
+            }
+            let keys = s[6..s.len() - 1]
+                .split(',')
+                .map(DescriptorPublicKey::from_str)
+                .collect::<Result<Vec<_>, _>>()?;
+            let musig = DescriptorMuSigKey::new(keys)?;
+            return Ok((DescriptorPublicKey::MuSig(musig), None));
+        }
+
         // A "raw" public key without any origin is the least we accept.
         if s.len() < 64 {
             return Err(DescriptorKeyParseError(
@@ -304,15 +598,29 @@ impl FromStr for DescriptorPublicKey {
         let (key_part, origin) = DescriptorXKey::<bip32::ExtendedPubKey>::parse_xkey_origin(s)?;
 
         if key_part.contains("pub") {
-            let (xpub, derivation_path, wildcard) =
-                DescriptorXKey::<bip32::ExtendedPubKey>::parse_xkey_deriv(key_part)?;
+            let normalized = normalize_slip132(key_part);
+            let (xpub, mut derivation_paths, wildcard) =
+                DescriptorXKey::<bip32::ExtendedPubKey>::parse_xkey_multi_deriv(
+                    normalized.as_ref().map_or(key_part, |(k, _)| k),
+                )?;
 
-            Ok(DescriptorPublicKey::XPub(DescriptorXKey {
-                origin,
-                xkey: xpub,
-                derivation_path,
-                wildcard,
-            }))
+            let key = if derivation_paths.len() == 1 {
+                DescriptorPublicKey::XPub(DescriptorXKey {
+                    origin,
+                    xkey: xpub,
+                    derivation_path: derivation_paths.remove(0),
+                    wildcard,
+                })
+            } else {
+                DescriptorPublicKey::MultiXPub(DescriptorMultiXKey {
+                    origin,
+                    xkey: xpub,
+                    derivation_paths: DerivPaths::new(derivation_paths)
+                        .expect("parsed paths have the same length"),
+                    wildcard,
+                })
+            };
+            Ok((key, normalized.map(|(_, v)| v)))
         } else {
             let key = match key_part.len() {
                 64 => {
@@ -341,7 +649,7 @@ impl FromStr for DescriptorPublicKey {
                     ))
                 }
             };
-            Ok(DescriptorPublicKey::Single(SinglePub { key, origin }))
+            Ok((DescriptorPublicKey::Single(SinglePub { key, origin }), None))
         }
     }
 }
@@ -351,12 +659,15 @@ impl FromStr for DescriptorPublicKey {
 pub enum ConversionError {
     /// Attempted to convert a key with hardened derivations to a bitcoin public key
     HardenedChild,
+    /// Attempted to convert a key with multiple derivation paths to a bitcoin public key
+    MultiKey,
 }
 
 impl fmt::Display for ConversionError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.write_str(match *self {
             ConversionError::HardenedChild => "hardened child step in bip32 path",
+            ConversionError::MultiKey => "multiple derivation paths in bip32 key",
         })
     }
 }
@@ -366,15 +677,18 @@ impl error::Error for ConversionError {
         use self::ConversionError::*;
 
         match self {
-            HardenedChild => None,
+            HardenedChild | MultiKey => None,
         }
     }
 }
 
 impl DescriptorPublicKey {
     /// The fingerprint of the master key associated with this key, `0x00000000` if none.
+    ///
+    /// MuSig keys have no master key of their own.
     pub fn master_fingerprint(&self) -> bip32::Fingerprint {
         match *self {
+            DescriptorPublicKey::MuSig(..) => bip32::Fingerprint::default(),
             DescriptorPublicKey::XPub(ref xpub) => {
                 if let Some((fingerprint, _)) = xpub.origin {
                     fingerprint
@@ -382,6 +696,13 @@ impl DescriptorPublicKey {
                     xpub.xkey.fingerprint()
                 }
             }
+            DescriptorPublicKey::MultiXPub(ref xpub) => {
+                if let Some((fingerprint, _)) = xpub.origin {
+                    fingerprint
+                } else {
+                    xpub.xkey.fingerprint()
+                }
+            }
             DescriptorPublicKey::Single(ref single) => {
                 if let Some((fingerprint, _)) = single.origin {
                     fingerprint
@@ -404,22 +725,99 @@ impl DescriptorPublicKey {
     /// For wildcard keys this will return the path up to the wildcard, so you
     /// can get full paths by appending one additional derivation step, according
     /// to the wildcard type (hardened or normal)
+    ///
+    /// For multipath keys this returns the first path, see
+    /// [`DescriptorPublicKey::full_derivation_paths`].
     pub fn full_derivation_path(&self) -> bip32::DerivationPath {
+        self.full_derivation_paths().remove(0)
+    }
+
+    /// Full paths, from the master key. Only multipath keys have more than
+    /// one path.
+    ///
+    /// MuSig keys have an empty path for each path of their keys.
+    pub fn full_derivation_paths(&self) -> Vec<bip32::DerivationPath> {
+        let origin_path = |origin: &Option<bip32::KeySource>| match *origin {
+            Some((_, ref path)) => path.clone(),
+            None => bip32::DerivationPath::from(vec![]),
+        };
         match *self {
             DescriptorPublicKey::XPub(ref xpub) => {
-                let origin_path = if let Some((_, ref path)) = xpub.origin {
-                    path.clone()
-                } else {
-                    bip32::DerivationPath::from(vec![])
-                };
-                origin_path.extend(&xpub.derivation_path)
+                vec![origin_path(&xpub.origin).extend(&xpub.derivation_path)]
             }
-            DescriptorPublicKey::Single(ref single) => {
-                if let Some((_, ref path)) = single.origin {
-                    path.clone()
-                } else {
-                    bip32::DerivationPath::from(vec![])
+            DescriptorPublicKey::MultiXPub(ref xpub) => xpub
+                .derivation_paths
+                .paths()
+                .iter()
+                .map(|p| origin_path(&xpub.origin).extend(p))
+                .collect(),
+            DescriptorPublicKey::Single(ref single) => vec![origin_path(&single.origin)],
+            DescriptorPublicKey::MuSig(ref musig) => {
+                let num_paths = musig
+                    .keys
+                    .iter()
+                    .map(|key| key.full_derivation_paths().len())
+                    .max()
+                    .expect("musig() has keys");
+                vec![bip32::DerivationPath::from(vec![]); num_paths]
+            }
+        }
+    }
+
+    /// Whether or not the key has multiple derivation paths
+    pub fn is_multipath(&self) -> bool {
+        match *self {
+            DescriptorPublicKey::Single(..) | DescriptorPublicKey::XPub(..) => false,
+            DescriptorPublicKey::MultiXPub(..) => true,
+            DescriptorPublicKey::MuSig(ref musig) => musig.keys.iter().any(|k| k.is_multipath()),
+        }
+    }
+
+    /// Get as many keys as there are derivation paths, i.e. one for keys
+    /// without multiple derivation paths.
+    pub fn into_single_keys(self) -> Vec<DescriptorPublicKey> {
+        match self {
+            DescriptorPublicKey::Single(..) | DescriptorPublicKey::XPub(..) => vec![self],
+            DescriptorPublicKey::MultiXPub(xpub) => {
+                let DescriptorMultiXKey {
+                    origin,
+                    xkey,
+                    derivation_paths,
+                    wildcard,
+                } = xpub;
+                derivation_paths
+                    .into_paths()
+                    .into_iter()
+                    .map(|derivation_path| {
+                        DescriptorPublicKey::XPub(DescriptorXKey {
+                            origin: origin.clone(),
+                            xkey,
+                            derivation_path,
+                            wildcard,
+                        })
+                    })
+                    .collect()
+            }
+            DescriptorPublicKey::MuSig(musig) => {
+                if !musig.keys.iter().any(|k| k.is_multipath()) {
+                    return vec![DescriptorPublicKey::MuSig(musig)];
                 }
+                // All multipath keys have the same number of paths
+                let keys: Vec<_> = musig
+                    .keys
+                    .into_iter()
+                    .map(DescriptorPublicKey::into_single_keys)
+                    .collect();
+                let num_paths = keys.iter().map(Vec::len).max().expect("musig() has keys");
+                (0..num_paths)
+                    .map(|i| {
+                        let keys = keys
+                            .iter()
+                            .map(|k| if k.len() == 1 { &k[0] } else { &k[i] }.clone())
+                            .collect();
+                        DescriptorPublicKey::MuSig(DescriptorMuSigKey { keys })
+                    })
+                    .collect()
             }
         }
     }
@@ -435,12 +833,14 @@ impl DescriptorPublicKey {
         match *self {
             DescriptorPublicKey::Single(..) => false,
             DescriptorPublicKey::XPub(ref xpub) => xpub.wildcard != Wildcard::None,
+            DescriptorPublicKey::MultiXPub(ref xpub) => xpub.wildcard != Wildcard::None,
+            DescriptorPublicKey::MuSig(ref musig) => musig.keys.iter().any(|k| k.has_wildcard()),
         }
     }
 
     #[deprecated(note = "use at_derivation_index instead")]
     /// Deprecated name of [`at_derivation_index`].
-    pub fn derive(self, index: u32) -> DefiniteDescriptorKey {
+    pub fn derive(self, index: u32) -> Result<DefiniteDescriptorKey, ConversionError> {
         self.at_derivation_index(index)
     }
 
@@ -453,10 +853,21 @@ impl DescriptorPublicKey {
     /// - If this key is an xpub but does not have a wildcard, returns `self`.
     /// - Otherwise, returns the xpub at derivation `index` (removing the wildcard).
     ///
+    /// The keys of MuSig keys are each replaced by their key at derivation
+    /// `index`.
+    ///
+    /// # Errors
+    ///
+    /// [`ConversionError::MultiKey`] for multipath keys, which must be split
+    /// with [`DescriptorPublicKey::into_single_keys`] first.
+    ///
     /// # Panics
     ///
     /// If `index` ≥ 2^31
-    pub fn at_derivation_index(self, index: u32) -> DefiniteDescriptorKey {
+    pub fn at_derivation_index(self, index: u32) -> Result<DefiniteDescriptorKey, ConversionError> {
+        if self.is_multipath() {
+            return Err(ConversionError::MultiKey);
+        }
         let definite = match self {
             DescriptorPublicKey::Single(_) => self,
             DescriptorPublicKey::XPub(xpub) => {
@@ -476,10 +887,18 @@ impl DescriptorPublicKey {
                     wildcard: Wildcard::None,
                 })
             }
+            DescriptorPublicKey::MultiXPub(..) => unreachable!("checked above"),
+            DescriptorPublicKey::MuSig(musig) => DescriptorPublicKey::MuSig(DescriptorMuSigKey {
+                keys: musig
+                    .keys
+                    .into_iter()
+                    .map(|k| k.at_derivation_index(index).map(DescriptorPublicKey::from))
+                    .collect::<Result<_, _>>()?,
+            }),
         };
 
-        DefiniteDescriptorKey::new(definite)
-            .expect("The key should not contain any wildcards at this point")
+        Ok(DefiniteDescriptorKey::new(definite)
+            .expect("The key should not contain any wildcards at this point"))
     }
 }
 
@@ -571,6 +990,20 @@ impl<K: InnerXKey> DescriptorXKey<K> {
     fn parse_xkey_deriv(
         key_deriv: &str,
     ) -> Result<(K, bip32::DerivationPath, Wildcard), DescriptorKeyParseError> {
+        let (xkey, mut paths, wildcard) = Self::parse_xkey_multi_deriv(key_deriv)?;
+        if paths.len() != 1 {
+            return Err(DescriptorKeyParseError(
+                "Multiple derivation paths are only allowed in public keys",
+            ));
+        }
+        Ok((xkey, paths.remove(0), wildcard))
+    }
+
+    /// Parse an extended key concatenated to a derivation path, which may
+    /// have a single `<a;b;..>` step standing for multiple paths (BIP-389).
+    fn parse_xkey_multi_deriv(
+        key_deriv: &str,
+    ) -> Result<(K, Vec<bip32::DerivationPath>, Wildcard), DescriptorKeyParseError> {
         let mut key_deriv = key_deriv.split('/');
         let xkey_str = key_deriv.next().ok_or(DescriptorKeyParseError(
             "No key found after origin description",
@@ -578,28 +1011,67 @@ impl<K: InnerXKey> DescriptorXKey<K> {
         let xkey = K::from_str(xkey_str)
             .map_err(|_| DescriptorKeyParseError("Error while parsing xkey."))?;
 
+        let parse_child = |p: &str| {
+            bip32::ChildNumber::from_str(p)
+                .map_err(|_| DescriptorKeyParseError("Error while parsing key derivation path"))
+        };
         let mut wildcard = Wildcard::None;
-        let derivation_path = key_deriv
-            .filter_map(|p| {
-                if wildcard == Wildcard::None && p == "*" {
-                    wildcard = Wildcard::Unhardened;
-                    None
-                } else if wildcard == Wildcard::None && (p == "*'" || p == "*h") {
-                    wildcard = Wildcard::Hardened;
-                    None
-                } else if wildcard != Wildcard::None {
-                    Some(Err(DescriptorKeyParseError(
-                        "'*' may only appear as last element in a derivation path.",
-                    )))
-                } else {
-                    Some(bip32::ChildNumber::from_str(p).map_err(|_| {
-                        DescriptorKeyParseError("Error while parsing key derivation path")
-                    }))
+        let mut paths = vec![vec![]];
+        for p in key_deriv {
+            if wildcard == Wildcard::None && p == "*" {
+                wildcard = Wildcard::Unhardened;
+            } else if wildcard == Wildcard::None && (p == "*'" || p == "*h") {
+                wildcard = Wildcard::Hardened;
+            } else if wildcard != Wildcard::None {
+                return Err(DescriptorKeyParseError(
+                    "'*' may only appear as last element in a derivation path.",
+                ));
+            } else if p.starts_with('<') && p.ends_with('>') {
+                if paths.len() > 1 {
+                    return Err(DescriptorKeyParseError(
+                        "Only one multipath step is allowed in a derivation path",
+                    ));
+                }
+                let children = p[1..p.len() - 1]
+                    .split(';')
+                    .map(parse_child)
+                    .collect::<Result<Vec<_>, _>>()?;
+                if children.len() < 2 {
+                    return Err(DescriptorKeyParseError(
+                        "Multipath steps must have at least two indexes",
+                    ));
+                }
+                if children
+                    .iter()
+                    .enumerate()
+                    .any(|(i, c)| children[..i].contains(c))
+                {
+                    return Err(DescriptorKeyParseError(
+                        "Duplicate index in a multipath step",
+                    ));
+                }
+                let prefix = paths.remove(0);
+                paths = children
+                    .into_iter()
+                    .map(|c| {
+                        let mut path = prefix.clone();
+                        path.push(c);
+                        path
+                    })
+                    .collect();
+            } else {
+                let child = parse_child(p)?;
+                for path in &mut paths {
+                    path.push(child);
                 }
-            })
-            .collect::<Result<bip32::DerivationPath, _>>()?;
+            }
+        }
 
-        Ok((xkey, derivation_path, wildcard))
+        Ok((
+            xkey,
+            paths.into_iter().map(bip32::DerivationPath::from).collect(),
+            wildcard,
+        ))
     }
 
     /// Compares this key with a `keysource` and returns the matching derivation path, if any.
@@ -692,12 +1164,15 @@ impl MiniscriptKey for DescriptorPublicKey {
         }
     }
 
+    // MuSig2 aggregate keys are x-only keys, which also restricts them to
+    // taproot
     fn is_x_only_key(&self) -> bool {
         match self {
             DescriptorPublicKey::Single(SinglePub {
                 key: SinglePubKey::XOnly(ref _key),
                 ..
             }) => true,
+            DescriptorPublicKey::MuSig(..) => true,
             _ => false,
         }
     }
@@ -711,6 +1186,10 @@ impl DefiniteDescriptorKey {
     ///
     /// Will return an error if the descriptor key has any hardened derivation steps in its path. To
     /// avoid this error you should replace any such public keys first with [`translate_pk`].
+    /// Multipath keys also return an error, see [`DescriptorPublicKey::into_single_keys`].
+    ///
+    /// MuSig keys return the MuSig2 aggregate of their keys, see
+    /// [`aggregate_keys`](crate::descriptor::aggregate_keys).
     ///
     /// [`translate_pk`]: crate::TranslatePk::translate_pk
     pub fn derive_public_key<C: Verification>(
@@ -722,6 +1201,15 @@ impl DefiniteDescriptorKey {
                 SinglePubKey::FullKey(pk) => Ok(pk),
                 SinglePubKey::XOnly(xpk) => Ok(xpk.to_public_key()),
             },
+            DescriptorPublicKey::MultiXPub(..) => Err(ConversionError::MultiKey),
+            DescriptorPublicKey::MuSig(ref musig) => {
+                let keys = musig
+                    .keys
+                    .iter()
+                    .map(|k| DefiniteDescriptorKey(k.clone()).derive_public_key(secp))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(aggregate_keys(secp, &keys))
+            }
             DescriptorPublicKey::XPub(ref xpk) => match xpk.wildcard {
                 Wildcard::Unhardened | Wildcard::Hardened => {
                     unreachable!("we've excluded this error case")
@@ -739,9 +1227,9 @@ impl DefiniteDescriptorKey {
 
     /// Construct an instance from a descriptor key and a derivation index
     ///
-    /// Returns `None` if the key contains a wildcard
+    /// Returns `None` if the key contains a wildcard or is a multipath key
     fn new(key: DescriptorPublicKey) -> Option<Self> {
-        if key.has_wildcard() {
+        if key.has_wildcard() || key.is_multipath() {
             None
         } else {
             Some(Self(key))
@@ -766,7 +1254,7 @@ impl FromStr for DefiniteDescriptorKey {
         let inner = DescriptorPublicKey::from_str(s)?;
         Ok(
             DefiniteDescriptorKey::new(inner).ok_or(DescriptorKeyParseError(
-                "cannot parse key with a wilcard as a DerivedDescriptorKey",
+                "cannot parse key with a wilcard or multiple derivation paths as a DerivedDescriptorKey",
             ))?,
         )
     }
@@ -828,7 +1316,10 @@ mod test {
 
     use elements::secp256k1_zkp;
 
-    use super::{DescriptorKeyParseError, DescriptorPublicKey, DescriptorSecretKey};
+    use super::{
+        base58, ConversionError, DefiniteDescriptorKey, DescriptorKeyParseError,
+        DescriptorPublicKey, DescriptorSecretKey, Slip132Version,
+    };
 
     #[test]
     fn parse_descriptor_key_errors() {
@@ -991,4 +1482,78 @@ mod test {
             b"\xb0\x59\x11\x6a"
         );
     }
+
+    #[test]
+    fn slip132_normalization() {
+        let with_version = |xkey: &str, version: [u8; 4]| {
+            let mut data = base58::from_check(xkey).unwrap();
+            data[..4].copy_from_slice(&version);
+            base58::check_encode_slice(&data)
+        };
+        let xpub = "xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL";
+        let tpub = "tpubD6NzVbkrYhZ4WQdzxL7NmJN7b85ePo4p6RSj9QQHF7te2RR9iUeVSGgnGkoUsB9LBRosgvNbjRv9bcsJgzgBd7QKuxDm23ZewkTRzNSLEDr";
+
+        let zpub = with_version(xpub, Slip132Version::Zpub.version_bytes());
+        assert!(zpub.starts_with("zpub"));
+        let (key, version) =
+            DescriptorPublicKey::from_str_slip132(&format!("[d34db33f/84'/0'/0']{}/1/*", zpub))
+                .unwrap();
+        assert_eq!(version, Some(Slip132Version::Zpub));
+        assert_eq!(key.to_string(), format!("[d34db33f/84'/0'/0']{}/1/*", xpub));
+
+        let vpub = with_version(tpub, Slip132Version::VpubMultisig.version_bytes());
+        assert!(vpub.starts_with("Vpub"));
+        let key = DescriptorPublicKey::from_str(&vpub).unwrap();
+        assert_eq!(key, DescriptorPublicKey::from_str(tpub).unwrap());
+
+        let (_, version) = DescriptorPublicKey::from_str_slip132(xpub).unwrap();
+        assert_eq!(version, None);
+        let mut bad = zpub.clone();
+        bad.pop();
+        assert!(DescriptorPublicKey::from_str(&bad).is_err());
+    }
+
+    #[test]
+    fn multipath_keys() {
+        let xpub = "xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL";
+        let s = format!("[d34db33f/48'/0'/0']{}/<0;1;5>/2/*", xpub);
+        let key = DescriptorPublicKey::from_str(&s).unwrap();
+        assert!(key.is_multipath());
+        assert!(key.has_wildcard());
+        assert_eq!(key.to_string(), s);
+        assert_eq!(
+            key.full_derivation_paths()
+                .iter()
+                .map(|p| p.to_string())
+                .collect::<Vec<_>>(),
+            vec!["m/48'/0'/0'/0/2", "m/48'/0'/0'/1/2", "m/48'/0'/0'/5/2"]
+        );
+
+        let singles = key.clone().into_single_keys();
+        assert_eq!(singles.len(), 3);
+        assert_eq!(
+            singles[1].to_string(),
+            format!("[d34db33f/48'/0'/0']{}/1/2/*", xpub)
+        );
+        assert!(singles.iter().all(|k| !k.is_multipath()));
+
+        let secp = secp256k1_zkp::Secp256k1::verification_only();
+        assert_eq!(
+            key.clone().at_derivation_index(7),
+            Err(ConversionError::MultiKey)
+        );
+        assert!(DefiniteDescriptorKey::from_str(&format!("{}/<0;1>/2", xpub)).is_err());
+        let definite = singles[1].clone().at_derivation_index(7).unwrap();
+        assert_eq!(
+            definite.to_string(),
+            format!("[d34db33f/48'/0'/0']{}/1/2/7", xpub)
+        );
+        assert!(definite.derive_public_key(&secp).is_ok());
+
+        for bad in &["/<0>/*", "/<0;0>/*", "/<0;1>/<2;3>", "/<0;1>/*/1", "/<0;a>"] {
+            assert!(DescriptorPublicKey::from_str(&format!("{}{}", xpub, bad)).is_err());
+        }
+        let tprv = "tprv8ZgxMBicQKsPcwcD4gSnMti126ZiETsuX7qwrtMypr6FBwAP65puFn4v6c3jrN9VwtMRMph6nyT63NrfUL4C3nBzPcduzVSuHD7zbX2JKVc";
+        assert!(DescriptorSecretKey::from_str(&format!("{}/<0;1>/*", tprv)).is_err());
+    }
 }
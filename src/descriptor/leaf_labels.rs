@@ -0,0 +1,242 @@
+// Miniscript
+// Written in 2023 by
+//     The Elements Miniscript developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Taproot Leaf Labels
+//!
+//! Human readable labels for the leaves of taproot descriptors, written as
+//! comments in front of the leaves, e.g.
+//! `eltr(K,{/*recovery*/and_v(v:pk(A),older(1000)),/*main*/pk(B)})`.
+//! Labels are not part of the descriptor: they are stripped before parsing
+//! and the checksum only covers the descriptor without them.
+//!
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use super::checksum::desc_checksum;
+use super::{Descriptor, TapTree, Tr};
+use crate::{Error, Extension, MiniscriptKey};
+
+/// Displays a descriptor with labels in front of its taproot leaves, see
+/// [`Descriptor::with_leaf_labels`].
+///
+/// With alternate formatting (`{:#}`) the checksum is omitted, otherwise it
+/// is the checksum of the descriptor without labels.
+pub struct LabeledLeaves<'a, Pk: MiniscriptKey, Ext: Extension> {
+    desc: &'a Descriptor<Pk, Ext>,
+    labels: &'a BTreeMap<usize, String>,
+}
+
+fn fmt_tree<Pk: MiniscriptKey, Ext: Extension>(
+    tree: &TapTree<Pk, Ext>,
+    labels: &BTreeMap<usize, String>,
+    leaf: &mut usize,
+    f: &mut fmt::Formatter<'_>,
+) -> fmt::Result {
+    match *tree {
+        TapTree::Tree(ref left, ref right) => {
+            f.write_str("{")?;
+            fmt_tree(left, labels, leaf, f)?;
+            f.write_str(",")?;
+            fmt_tree(right, labels, leaf, f)?;
+            f.write_str("}")
+        }
+        TapTree::Leaf(ref ms) => {
+            if let Some(label) = labels.get(leaf) {
+                // A label cannot end its own comment
+                write!(f, "/*{}*/", label.replace("*/", "* /"))?;
+            }
+            *leaf += 1;
+            write!(f, "{}", ms)
+        }
+        TapTree::Hidden(ref hash) => write!(f, "hidden({})", hash),
+    }
+}
+
+fn fmt_tr<Pk: MiniscriptKey, Ext: Extension>(
+    tr: &Tr<Pk, Ext>,
+    labels: &BTreeMap<usize, String>,
+    f: &mut fmt::Formatter<'_>,
+) -> fmt::Result {
+    write!(f, "eltr({}", tr.internal_key())?;
+    if let Some(ref tree) = *tr.taptree() {
+        f.write_str(",")?;
+        fmt_tree(tree, labels, &mut 0, f)?;
+    }
+    f.write_str(")")
+}
+
+impl<'a, Pk: MiniscriptKey, Ext: Extension> fmt::Display for LabeledLeaves<'a, Pk, Ext> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self.desc {
+            Descriptor::Tr(ref tr) => fmt_tr(tr, self.labels, f)?,
+            Descriptor::TrExt(ref tr) => fmt_tr(tr, self.labels, f)?,
+            ref desc => return fmt::Display::fmt(desc, f),
+        }
+        if !f.alternate() {
+            let checksum = desc_checksum(&format!("{:#}", self.desc)).map_err(|_| fmt::Error)?;
+            write!(f, "#{}", checksum)?;
+        }
+        Ok(())
+    }
+}
+
+// Remove the labels from `s`, returning the descriptor without them and the
+// offset of every label in it. Comments are only recognized at the start of
+// a subtree, so that wildcards such as `xpub/*` are not taken for one.
+fn strip_labels(s: &str) -> Result<(String, Vec<(usize, String)>), Error> {
+    let mut stripped = String::with_capacity(s.len());
+    let mut labels = vec![];
+    let mut rest = s;
+    while let Some(start) = rest.find("/*") {
+        stripped.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        if !(stripped.ends_with('{') || stripped.ends_with(',')) {
+            stripped.push_str("/*");
+            rest = after;
+            continue;
+        }
+        let end = after
+            .find("*/")
+            .ok_or_else(|| Error::BadDescriptor(String::from("Unterminated leaf label")))?;
+        labels.push((stripped.len(), after[..end].trim().to_owned()));
+        rest = after[end + 2..].trim_start();
+    }
+    stripped.push_str(rest);
+    Ok((stripped, labels))
+}
+
+// The offset of every leaf in a taproot descriptor string, `None` for hidden
+// nodes, in the order of `Tr::iter_scripts`
+fn leaf_offsets(s: &str) -> Vec<Option<usize>> {
+    let desc = s.split('#').next().unwrap_or("");
+    if !desc.starts_with("eltr(") {
+        return vec![];
+    }
+    let bytes = desc.as_bytes();
+    let mut offsets = vec![];
+    let mut depth = 0;
+    let mut item_start = false;
+    for (i, &ch) in bytes.iter().enumerate().skip("eltr(".len()) {
+        if item_start && ch != b'{' {
+            offsets.push(if desc[i..].starts_with("hidden(") {
+                None
+            } else {
+                Some(i)
+            });
+        }
+        item_start = false;
+        match ch {
+            b'(' => depth += 1,
+            b')' => depth -= 1,
+            b'{' => item_start = true,
+            b',' if depth == 0 => item_start = true,
+            _ => {}
+        }
+    }
+    offsets
+}
+
+impl<Pk: MiniscriptKey, Ext: Extension> Descriptor<Pk, Ext> {
+    /// Display the descriptor with `labels[i]` written in front of its `i`th
+    /// taproot leaf, in the order of [`Tr::iter_scripts`]. Labels parse back
+    /// with [`Descriptor::from_str_with_leaf_labels`].
+    ///
+    /// Descriptors other than taproot ones are displayed as usual.
+    pub fn with_leaf_labels<'a>(
+        &'a self,
+        labels: &'a BTreeMap<usize, String>,
+    ) -> LabeledLeaves<'a, Pk, Ext> {
+        LabeledLeaves { desc: self, labels }
+    }
+
+    /// Parse a descriptor in which taproot leaves may be preceded by labels
+    /// written as `/*label*/`, and return it with the labels by leaf index,
+    /// in the order of [`Tr::iter_scripts`]. Whitespace around labels is
+    /// ignored.
+    ///
+    /// # Errors
+    /// When a label is not in front of a leaf, or the descriptor without the
+    /// labels does not parse.
+    pub fn from_str_with_leaf_labels(s: &str) -> Result<(Self, BTreeMap<usize, String>), Error>
+    where
+        Self: FromStr<Err = Error>,
+    {
+        let (stripped, labels) = strip_labels(s)?;
+        let leaves = leaf_offsets(&stripped);
+        let mut by_leaf = BTreeMap::new();
+        for (offset, label) in labels {
+            let leaf = leaves
+                .iter()
+                .filter(|o| o.is_some())
+                .position(|o| *o == Some(offset))
+                .ok_or_else(|| {
+                    Error::BadDescriptor(format!("Label '{}' is not in front of a leaf", label))
+                })?;
+            by_leaf.insert(leaf, label);
+        }
+        Ok((Self::from_str(&stripped)?, by_leaf))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DescriptorPublicKey, NoExt};
+
+    type Desc = Descriptor<String, NoExt>;
+
+    #[test]
+    fn leaf_labels_roundtrip() {
+        let s = "eltr(K,{/* recovery */ and_v(v:pk(A),older(1000)),{hidden(0000000000000000000000000000000000000000000000000000000000000000),/*main*/pk(B)}})";
+        let (desc, labels) = Desc::from_str_with_leaf_labels(s).unwrap();
+        assert_eq!(labels.get(&0).map(String::as_str), Some("recovery"));
+        assert_eq!(labels.get(&1).map(String::as_str), Some("main"));
+        assert_eq!(labels.len(), 2);
+
+        let labeled = format!("{:#}", desc.with_leaf_labels(&labels));
+        assert_eq!(labeled, s.replace("/* recovery */ ", "/*recovery*/"));
+        assert_eq!(
+            Desc::from_str_with_leaf_labels(&labeled).unwrap(),
+            (desc.clone(), labels.clone())
+        );
+
+        // The checksum covers the descriptor without labels
+        let checksummed = desc.with_leaf_labels(&labels).to_string();
+        assert!(checksummed.ends_with(&desc.to_string()[desc.to_string().len() - 9..]));
+        assert_eq!(
+            Desc::from_str_with_leaf_labels(&checksummed).unwrap().0,
+            desc
+        );
+
+        // Labels must be in front of a leaf
+        assert!(Desc::from_str_with_leaf_labels("eltr(K,and_v(v:pk(A),/*x*/older(1)))").is_err());
+        assert!(Desc::from_str_with_leaf_labels("elwsh(/*x*/pk(A))").is_err());
+        assert!(Desc::from_str_with_leaf_labels("eltr(K,{/*x pk(A),pk(B)})").is_err());
+    }
+
+    #[test]
+    fn leaf_labels_wildcards() {
+        let xpub = "xpub6BgBgsespWvERF3LHQu6CnqdvfEvtMcQjYrcRzx53QJjSxarj2afYWcLteoGVky7D3UKDP9QyrLprQ3VCECoY49yfdDEHGCtMMj92pReUsQ";
+        let s = format!(
+            "eltr({}/0/*,{{/*a*/pk({}/1/*),pk({}/2/*)}})",
+            xpub, xpub, xpub
+        );
+        let (desc, labels) =
+            Descriptor::<DescriptorPublicKey>::from_str_with_leaf_labels(&s).unwrap();
+        assert_eq!(labels.len(), 1);
+        assert_eq!(format!("{:#}", desc.with_leaf_labels(&labels)), s);
+    }
+}
@@ -23,50 +23,99 @@
 //! these with BIP32 paths, pay-to-contract instructions, etc.
 //!
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt;
 use std::ops::Range;
 use std::str::{self, FromStr};
 use std::sync::Arc;
 
+pub mod derivation;
+pub mod federation;
 pub mod pegin;
 
 use bitcoin::util::address::WitnessVersion;
 use elements::hashes::{hash160, ripemd160, sha256};
-use elements::{secp256k1_zkp as secp256k1, secp256k1_zkp, Script, TxIn};
+use elements::taproot::{LeafVersion, TapLeafHash, TaprootSpendInfo};
+use elements::{
+    secp256k1_zkp as secp256k1, secp256k1_zkp, LockTime, PackedLockTime, Script, Sequence, TxIn,
+};
 use {bitcoin, elements};
 
 use self::checksum::verify_checksum;
 use crate::extensions::{CovExtArgs, ExtParam, ParseableExt};
+use crate::interpreter::{self, HashLockType, Interpreter, KeySigPair, SatisfiedConstraint};
+use crate::miniscript::satisfy::Timelocks;
 use crate::miniscript::{Legacy, Miniscript, Segwitv0};
+use crate::policy::{semantic, Liftable};
 use crate::{
-    expression, hash256, miniscript, BareCtx, CovenantExt, Error, ExtTranslator, Extension,
-    ForEachKey, MiniscriptKey, NoExt, Satisfier, ToPublicKey, TranslateExt, TranslatePk,
-    Translator,
+    expression, hash256, miniscript, util, BareCtx, CovenantExt, Error, ExtParams, ExtTranslator,
+    Extension, ForEachKey, MiniscriptKey, NoExt, Satisfier, SighashPolicy, Terminal, ToPublicKey,
+    TranslateExt, TranslatePk, Translator,
 };
 
+mod analysis;
 mod bare;
+mod batch;
 mod blinded;
 mod csfs_cov;
+mod ct;
+mod device;
+mod explain;
+mod export;
+mod leaf_labels;
+mod musig;
+mod plan;
+mod privacy;
+mod record;
+#[cfg(feature = "encrypt")]
+mod seal;
 mod segwitv0;
 mod sh;
+mod shape;
+mod sighash_lint;
 mod sortedmulti;
+mod spend_path;
 mod tr;
+mod witness_template;
 
 // Descriptor Exports
+pub use self::analysis::{Analysis, ScriptAnalysis};
 pub use self::bare::{Bare, Pkh};
+pub use self::batch::{AddressBatchIter, DerivedDescriptorCache};
 pub use self::blinded::Blinded;
+pub use self::ct::{
+    BlindingKey, BlindingKeyDerivation, ConfidentialDescriptor, DerivedAddressError,
+};
+pub use self::device::{DisplayAddressRequest, EmbeddedOrigins, KeyInfo, KeyProvider};
+pub use self::export::{WalletExport, WalletExportError, WALLET_EXPORT_VERSION};
+pub use self::leaf_labels::LabeledLeaves;
+pub use self::musig::{aggregate_keys, MuSigKeyAgg, MuSigSession};
+pub use self::plan::{Assets, Plan};
+pub use self::privacy::{PrivacyReport, SpendFootprint};
+pub use self::record::{DescriptorRecord, DEFAULT_GAP_LIMIT};
+#[cfg(feature = "encrypt")]
+pub use self::seal::{UnsealError, MAX_ITERATIONS};
 pub use self::segwitv0::{Wpkh, Wsh, WshInner};
 pub use self::sh::{Sh, ShInner};
+pub use self::shape::{DescriptorShape, ErasedKey};
+pub use self::sighash_lint::{SighashHazard, SighashWarning};
 pub use self::sortedmulti::SortedMultiVec;
+pub use self::spend_path::{
+    RefundPath, Requirements, SpendHash, SpendPath, SpendTimelock, MAX_SPEND_PATHS,
+};
+pub use self::witness_template::{Placeholder, PlaceholderSigHash, WitnessTemplate};
 mod checksum;
 mod key;
 pub use self::csfs_cov::{CovError, CovOperations, LegacyCSFSCov, LegacyCovSatisfier};
 pub use self::key::{
-    ConversionError, DefiniteDescriptorKey, DescriptorKeyParseError, DescriptorPublicKey,
-    DescriptorSecretKey, DescriptorXKey, InnerXKey, SinglePriv, SinglePub, SinglePubKey, Wildcard,
+    ConversionError, DefiniteDescriptorKey, DerivPaths, DescriptorKeyParseError,
+    DescriptorMuSigKey, DescriptorMultiXKey, DescriptorPublicKey, DescriptorSecretKey,
+    DescriptorXKey, InnerXKey, SinglePriv, SinglePub, SinglePubKey, Slip132Version, Wildcard,
 };
-pub use self::tr::{TapTree, Tr};
+pub(crate) use self::shape::EraseKeys;
+pub(crate) use self::tr::huffman_tree;
+pub(crate) use self::tr::{leaf_satisfaction_weight, KEY_SPEND_WEIGHT};
+pub use self::tr::{TapTree, TapTreeBuilder, Tr};
 /// Alias type for a map of public key to secret key
 ///
 /// This map is returned whenever a descriptor that contains secrets is parsed using
@@ -75,6 +124,10 @@ pub use self::tr::{TapTree, Tr};
 /// public key from the descriptor.
 pub type KeyMap = HashMap<DescriptorPublicKey, DescriptorSecretKey>;
 
+/// The extended keys of a descriptor which were given with a SLIP-132
+/// version, along with that version, see [`Descriptor::parse_slip132`]
+pub type Slip132Keys = Vec<(DescriptorPublicKey, Slip132Version)>;
+
 /// Elements Descriptor String Prefix
 pub const ELMTS_STR: &str = "el";
 
@@ -421,6 +474,113 @@ impl<Pk: MiniscriptKey, Ext: Extension> Descriptor<Pk, Ext> {
         Ok(Descriptor::TrExt(Tr::new(key, script)?))
     }
 
+    /// Create new tr descriptor with the leaves arranged in a Huffman tree
+    /// by their probabilities, so that likelier leaves have shorter control
+    /// blocks. The tree is built as the policy compiler builds the trees of
+    /// its taproot compilations.
+    ///
+    /// Errors when there are no leaves, when a probability is negative or
+    /// not finite, or when the tree is too deep for taproot
+    pub fn new_tr_with_huffman(
+        key: Pk,
+        weighted_leaves: Vec<(f64, Miniscript<Pk, crate::Tap, NoExt>)>,
+    ) -> Result<Self, Error> {
+        Descriptor::new_tr(key, Some(huffman_tree(weighted_leaves)?))
+    }
+
+    /// Create new tr descriptor with extensions, with the leaves arranged
+    /// in a Huffman tree by their probabilities, see
+    /// [`Descriptor::new_tr_with_huffman`]
+    pub fn new_tr_ext_with_huffman(
+        key: Pk,
+        weighted_leaves: Vec<(f64, Miniscript<Pk, crate::Tap, Ext>)>,
+    ) -> Result<Self, Error> {
+        Descriptor::new_tr_ext(key, Some(huffman_tree(weighted_leaves)?))
+    }
+
+    // Downcasts. The descriptor is handed back unchanged on mismatch, as
+    // with `Arc::try_unwrap`, so the large error is intended.
+
+    /// Converts into the inner descriptor if this is a bare descriptor,
+    /// returning the descriptor unchanged otherwise
+    #[allow(clippy::result_large_err)]
+    pub fn try_into_bare(self) -> Result<Bare<Pk>, Self> {
+        match self {
+            Descriptor::Bare(inner) => Ok(inner),
+            desc => Err(desc),
+        }
+    }
+
+    /// Converts into the inner descriptor if this is a pkh descriptor,
+    /// returning the descriptor unchanged otherwise
+    #[allow(clippy::result_large_err)]
+    pub fn try_into_pkh(self) -> Result<Pkh<Pk>, Self> {
+        match self {
+            Descriptor::Pkh(inner) => Ok(inner),
+            desc => Err(desc),
+        }
+    }
+
+    /// Converts into the inner descriptor if this is a wpkh descriptor,
+    /// returning the descriptor unchanged otherwise
+    #[allow(clippy::result_large_err)]
+    pub fn try_into_wpkh(self) -> Result<Wpkh<Pk>, Self> {
+        match self {
+            Descriptor::Wpkh(inner) => Ok(inner),
+            desc => Err(desc),
+        }
+    }
+
+    /// Converts into the inner descriptor if this is an sh descriptor,
+    /// returning the descriptor unchanged otherwise
+    #[allow(clippy::result_large_err)]
+    pub fn try_into_sh(self) -> Result<Sh<Pk>, Self> {
+        match self {
+            Descriptor::Sh(inner) => Ok(inner),
+            desc => Err(desc),
+        }
+    }
+
+    /// Converts into the inner descriptor if this is a wsh descriptor,
+    /// returning the descriptor unchanged otherwise
+    #[allow(clippy::result_large_err)]
+    pub fn try_into_wsh(self) -> Result<Wsh<Pk>, Self> {
+        match self {
+            Descriptor::Wsh(inner) => Ok(inner),
+            desc => Err(desc),
+        }
+    }
+
+    /// Converts into the inner descriptor if this is a tr descriptor,
+    /// returning the descriptor unchanged otherwise
+    #[allow(clippy::result_large_err)]
+    pub fn try_into_tr(self) -> Result<Tr<Pk, NoExt>, Self> {
+        match self {
+            Descriptor::Tr(inner) => Ok(inner),
+            desc => Err(desc),
+        }
+    }
+
+    /// Converts into the inner descriptor if this is a tr with extensions descriptor,
+    /// returning the descriptor unchanged otherwise
+    #[allow(clippy::result_large_err)]
+    pub fn try_into_tr_ext(self) -> Result<Tr<Pk, Ext>, Self> {
+        match self {
+            Descriptor::TrExt(inner) => Ok(inner),
+            desc => Err(desc),
+        }
+    }
+
+    /// Converts into the inner descriptor if this is a legacy covenant descriptor,
+    /// returning the descriptor unchanged otherwise
+    #[allow(clippy::result_large_err)]
+    pub fn try_into_cov(self) -> Result<LegacyCSFSCov<Pk, Ext>, Self> {
+        match self {
+            Descriptor::LegacyCSFSCov(inner) => Ok(inner),
+            desc => Err(desc),
+        }
+    }
+
     /// Get the [DescriptorType] of [Descriptor]
     pub fn desc_type(&self) -> DescriptorType {
         match *self {
@@ -446,6 +606,22 @@ impl<Pk: MiniscriptKey, Ext: Extension> Descriptor<Pk, Ext> {
         }
     }
 
+    /// The sighash types that signatures on this descriptor may use.
+    ///
+    /// All types are allowed, except for covenant descriptors: their
+    /// covenant signature is checked against a sighash built from the whole
+    /// transaction, so only `SIGHASH_ALL` can be used. Pass the result to a
+    /// [`SighashFilter`][crate::SighashFilter] to restrict a satisfier accordingly.
+    pub fn sighash_policy(&self) -> SighashPolicy {
+        match *self {
+            Descriptor::LegacyCSFSCov(..) => SighashPolicy {
+                ecdsa: vec![elements::EcdsaSigHashType::All],
+                schnorr: vec![],
+            },
+            _ => SighashPolicy::any(),
+        }
+    }
+
     /// Return a string without the checksum
     pub fn to_string_no_chksum(&self) -> String {
         format!("{:?}", self)
@@ -472,6 +648,59 @@ impl<Pk: MiniscriptKey, Ext: Extension> Descriptor<Pk, Ext> {
         }
     }
 
+    /// Reports branches of the descriptor which can never be satisfied, such
+    /// as ones combining a height-based and a time-based timelock, and other
+    /// likely mistakes. See [`semantic::Policy::lint`] for details.
+    ///
+    /// Unlike [`Liftable::lift`], this does not fail for descriptors that
+    /// combine heightlocks and timelocks.
+    ///
+    /// # Errors
+    /// When the descriptor cannot be lifted, e.g. because it contains raw
+    /// public key hashes or covenant extensions.
+    pub fn lint(&self) -> Result<Vec<semantic::Lint<Pk>>, Error> {
+        let lift_wsh = |wsh: &Wsh<Pk>| match *wsh.as_inner() {
+            WshInner::SortedMulti(ref smv) => smv.lift(),
+            WshInner::Ms(ref ms) => ms.as_inner().lift(),
+        };
+        let policy = match *self {
+            Descriptor::Bare(ref bare) => bare.as_inner().as_inner().lift()?,
+            Descriptor::Pkh(ref pkh) => pkh.lift()?,
+            Descriptor::Wpkh(ref wpkh) => wpkh.lift()?,
+            Descriptor::Wsh(ref wsh) => lift_wsh(wsh)?,
+            Descriptor::Sh(ref sh) => match *sh.as_inner() {
+                ShInner::Wsh(ref wsh) => lift_wsh(wsh)?,
+                ShInner::Wpkh(ref wpkh) => wpkh.lift()?,
+                ShInner::SortedMulti(ref smv) => smv.lift()?,
+                ShInner::Ms(ref ms) => ms.as_inner().lift()?,
+            },
+            Descriptor::LegacyCSFSCov(..) => self.lift()?,
+            Descriptor::Tr(ref tr) => Self::lift_tr_unchecked(tr)?,
+            Descriptor::TrExt(ref tr) => Self::lift_tr_unchecked(tr)?,
+        };
+        Ok(policy.lint())
+    }
+
+    /// Returns, for every key, how many spend paths of the descriptor use it
+    /// and the size of the smallest quorum it takes part in, to help audit
+    /// the distribution of signing power. See [`semantic::Policy::key_weights`].
+    ///
+    /// # Errors
+    /// When the descriptor cannot be lifted.
+    pub fn key_weights(&self) -> Result<BTreeMap<Pk, semantic::KeyWeight>, Error> {
+        Ok(self.lift()?.key_weights())
+    }
+
+    // Lifts the key spend and all leaves of `tr` into a single disjunction,
+    // skipping the timelock checks of `Liftable::lift`
+    fn lift_tr_unchecked<E: Extension>(tr: &Tr<Pk, E>) -> Result<semantic::Policy<Pk>, Error> {
+        let mut subs = vec![semantic::Policy::Key(tr.internal_key().clone())];
+        for (_depth, ms) in tr.iter_scripts() {
+            subs.push(ms.as_inner().lift()?);
+        }
+        Ok(semantic::Policy::Threshold(1, subs).normalized())
+    }
+
     /// Computes an upper bound on the weight of a satisfying witness to the
     /// transaction.
     ///
@@ -494,6 +723,40 @@ impl<Pk: MiniscriptKey, Ext: Extension> Descriptor<Pk, Ext> {
         };
         Ok(weight)
     }
+
+    /// Computes the nSequence to use for an input spending this descriptor
+    /// along `path`.
+    ///
+    /// `path` is the semantic policy of the branch the spender intends to
+    /// satisfy, for example the lifted policy of a single tap leaf. If it is
+    /// `None`, the lifted policy of the whole descriptor is used. Returns the
+    /// smallest relative timelock of the path's `older()` constraints which
+    /// makes the path satisfiable, or [`Sequence::ENABLE_RBF_NO_LOCKTIME`] if
+    /// the path can be satisfied without one.
+    ///
+    /// # Errors
+    /// When the path cannot be satisfied with any nSequence.
+    pub fn recommended_sequence(
+        &self,
+        path: Option<&semantic::Policy<Pk>>,
+    ) -> Result<Sequence, Error> {
+        let policy = match path {
+            Some(path) => path.clone(),
+            None => self.lift()?,
+        };
+        // `at_age` treats a sequence with the disable flag as satisfying
+        // every `older()`, so check the no-locktime case with zero instead.
+        if !policy.clone().at_age(Sequence::ZERO).is_unsatisfiable() {
+            return Ok(Sequence::ENABLE_RBF_NO_LOCKTIME);
+        }
+        let mut timelocks = policy.relative_timelocks();
+        timelocks.sort_unstable();
+        timelocks
+            .into_iter()
+            .map(Sequence::from_consensus)
+            .find(|seq| !policy.clone().at_age(*seq).is_unsatisfiable())
+            .ok_or(Error::CouldNotSatisfy)
+    }
 }
 
 impl<Pk: MiniscriptKey, Arg: ExtParam> Descriptor<Pk, CovenantExt<Arg>> {
@@ -545,6 +808,26 @@ impl<Pk: MiniscriptKey + ToPublicKey, Ext: Extension + ParseableExt> Descriptor<
         }
     }
 
+    /// Obtains the blinded address for this descriptor, with the blinding
+    /// key derived by `key` from the script pubkey
+    ///
+    /// # Errors
+    /// For raw/bare descriptors that don't have an address, or when `key`
+    /// fails to derive the blinding key.
+    pub fn confidential_address<C, K>(
+        &self,
+        secp: &secp256k1_zkp::Secp256k1<C>,
+        key: &K,
+        params: &'static elements::AddressParams,
+    ) -> Result<elements::Address, Error>
+    where
+        C: secp256k1_zkp::Signing + secp256k1_zkp::Verification,
+        K: BlindingKeyDerivation,
+    {
+        let blinder = key.blinding_key(secp, &self.script_pubkey())?;
+        self.blinded_address(blinder, params)
+    }
+
     /// Obtains an address for this descriptor. For blinding see [`Descriptor::blinded_address`]
     pub fn address(
         &self,
@@ -565,6 +848,39 @@ impl<Pk: MiniscriptKey + ToPublicKey, Ext: Extension + ParseableExt> Descriptor<
         }
     }
 
+    /// Checks that `address` pays to this descriptor and is blinded with
+    /// `blinder`, or is unconfidential if `blinder` is `None`.
+    ///
+    /// # Errors
+    /// [`Error::AddressMismatch`] if either the script pubkey or the blinding
+    /// key differs.
+    pub fn verify_address(
+        &self,
+        address: &elements::Address,
+        blinder: Option<secp256k1_zkp::PublicKey>,
+    ) -> Result<(), Error> {
+        if address.script_pubkey() == self.script_pubkey() && address.blinding_pubkey == blinder {
+            Ok(())
+        } else {
+            Err(Error::AddressMismatch(address.to_string()))
+        }
+    }
+
+    /// A taproot descriptor reduced to the leaf `leaf_hash`, with all other
+    /// nodes of the tree hidden, see [`TapTree::leaf_only`].
+    ///
+    /// The result has the same script pubkey and can satisfy the leaf, e.g.
+    /// for a watchtower which should not learn the other spending conditions.
+    /// Returns `None` for non-taproot descriptors and when there is no such
+    /// leaf.
+    pub fn leaf_only(&self, leaf_hash: &TapLeafHash) -> Option<Self> {
+        match *self {
+            Descriptor::Tr(ref tr) => tr.leaf_only(leaf_hash).map(Descriptor::Tr),
+            Descriptor::TrExt(ref tr) => tr.leaf_only(leaf_hash).map(Descriptor::TrExt),
+            _ => None,
+        }
+    }
+
     /// Computes the scriptpubkey of the descriptor.
     pub fn script_pubkey(&self) -> Script {
         match *self {
@@ -579,6 +895,42 @@ impl<Pk: MiniscriptKey + ToPublicKey, Ext: Extension + ParseableExt> Descriptor<
         }
     }
 
+    /// Computes an upper bound on the weight of a blinded transaction
+    /// spending `num_inputs` outputs of this descriptor to `num_outputs`
+    /// outputs of this descriptor, with blinded values and assets, and an
+    /// explicit fee output.
+    ///
+    /// Unlike [`Descriptor::max_satisfaction_weight`], this includes the
+    /// rangeproofs, surjection proofs and nonce commitments of the outputs.
+    /// The sizes of the proofs are those of [`crate::confidential`].
+    ///
+    /// # Errors
+    /// When the descriptor is impossible to safisfy (ex: sh(OP_FALSE)).
+    pub fn max_confidential_tx_weight(
+        &self,
+        num_inputs: usize,
+        num_outputs: usize,
+    ) -> Result<usize, Error> {
+        let mut satisfaction_weight = self.max_satisfaction_weight()?;
+        // Pre-segwit inputs still have a witness stack in a segwit transaction
+        match *self {
+            Descriptor::Bare(..) | Descriptor::Pkh(..) => satisfaction_weight += 1,
+            Descriptor::Sh(ref sh) => match *sh.as_inner() {
+                ShInner::SortedMulti(..) | ShInner::Ms(..) => satisfaction_weight += 1,
+                ShInner::Wsh(..) | ShInner::Wpkh(..) => {}
+            },
+            _ => {}
+        }
+        let input = crate::confidential::estimate_input_weight(satisfaction_weight);
+        let output =
+            crate::confidential::estimate_output_size(self.script_pubkey().len(), true, true);
+        let outputs = std::iter::repeat(output).take(num_outputs);
+        Ok(crate::confidential::estimate_tx_weight(
+            std::iter::repeat(input).take(num_inputs),
+            outputs.chain(Some(crate::confidential::fee_output_weight())),
+        ))
+    }
+
     /// Computes the scriptSig that will be in place for an unsigned input
     /// spending an output with this descriptor. For pre-segwit descriptors,
     /// which use the scriptSig for signatures, this returns the empty script.
@@ -638,6 +990,41 @@ impl<Pk: MiniscriptKey + ToPublicKey, Ext: Extension + ParseableExt> Descriptor<
         }
     }
 
+    /// The scripts needed to sign for and spend an output of the descriptor,
+    /// which are otherwise returned by [`Descriptor::script_pubkey`],
+    /// [`Descriptor::explicit_script`], [`Descriptor::script_code`] and
+    /// [`Tr::spend_info`]. Unlike the former, this does not fail for
+    /// taproot descriptors, fields not applying to the descriptor are `None`.
+    pub fn spend_info(&self) -> SpendInfo {
+        let (redeem_script, witness_script) = match *self {
+            Descriptor::Bare(..) | Descriptor::Pkh(..) | Descriptor::Wpkh(..) => (None, None),
+            Descriptor::Wsh(ref wsh) => (None, Some(wsh.inner_script())),
+            Descriptor::Sh(ref sh) => match *sh.as_inner() {
+                ShInner::Wsh(ref wsh) => {
+                    let witness_script = wsh.inner_script();
+                    (Some(witness_script.to_v0_p2wsh()), Some(witness_script))
+                }
+                ShInner::Wpkh(..) | ShInner::SortedMulti(..) | ShInner::Ms(..) => {
+                    (Some(sh.inner_script()), None)
+                }
+            },
+            Descriptor::LegacyCSFSCov(ref cov) => (None, Some(cov.inner_script())),
+            Descriptor::Tr(..) | Descriptor::TrExt(..) => (None, None),
+        };
+        let taproot = match *self {
+            Descriptor::Tr(ref tr) => Some(tr.spend_info()),
+            Descriptor::TrExt(ref tr) => Some(tr.spend_info()),
+            _ => None,
+        };
+        SpendInfo {
+            script_pubkey: self.script_pubkey(),
+            redeem_script,
+            witness_script,
+            script_code: self.script_code().ok(),
+            taproot,
+        }
+    }
+
     /// Returns satisfying non-malleable witness and scriptSig to spend an
     /// output controlled by the given descriptor if it possible to
     /// construct one using the satisfier S.
@@ -645,16 +1032,8 @@ impl<Pk: MiniscriptKey + ToPublicKey, Ext: Extension + ParseableExt> Descriptor<
     where
         S: Satisfier<Pk>,
     {
-        match *self {
-            Descriptor::Bare(ref bare) => bare.get_satisfaction(satisfier),
-            Descriptor::Pkh(ref pkh) => pkh.get_satisfaction(satisfier),
-            Descriptor::Wpkh(ref wpkh) => wpkh.get_satisfaction(satisfier),
-            Descriptor::Wsh(ref wsh) => wsh.get_satisfaction(satisfier),
-            Descriptor::Sh(ref sh) => sh.get_satisfaction(satisfier),
-            Descriptor::LegacyCSFSCov(ref cov) => cov.get_satisfaction(satisfier),
-            Descriptor::Tr(ref tr) => tr.get_satisfaction(satisfier),
-            Descriptor::TrExt(ref tr) => tr.get_satisfaction(satisfier),
-        }
+        self.satisfy_with(satisfier, SatisfactionMode::NonMalleable)
+            .map(|sat| (sat.witness, sat.script_sig))
     }
 
     /// Returns a possilbly mallable satisfying non-malleable witness and scriptSig to spend an
@@ -664,16 +1043,178 @@ impl<Pk: MiniscriptKey + ToPublicKey, Ext: Extension + ParseableExt> Descriptor<
     where
         S: Satisfier<Pk>,
     {
-        match *self {
-            Descriptor::Bare(ref bare) => bare.get_satisfaction_mall(satisfier),
-            Descriptor::Pkh(ref pkh) => pkh.get_satisfaction_mall(satisfier),
-            Descriptor::Wpkh(ref wpkh) => wpkh.get_satisfaction_mall(satisfier),
-            Descriptor::Wsh(ref wsh) => wsh.get_satisfaction_mall(satisfier),
-            Descriptor::Sh(ref sh) => sh.get_satisfaction_mall(satisfier),
-            Descriptor::LegacyCSFSCov(ref cov) => cov.get_satisfaction_mall(satisfier),
-            Descriptor::Tr(ref tr) => tr.get_satisfaction_mall(satisfier),
-            Descriptor::TrExt(ref tr) => tr.get_satisfaction_mall(satisfier),
+        self.satisfy_with(satisfier, SatisfactionMode::Malleable)
+            .map(|sat| (sat.witness, sat.script_sig))
+    }
+
+    /// Returns satisfying non-malleable witness and scriptSig to spend an
+    /// output controlled by the given descriptor, along with the nLockTime
+    /// and nSequence required by the chosen spend path, if it possible to
+    /// construct one using the satisfier S.
+    pub fn get_full_satisfaction<S>(&self, satisfier: S) -> Result<DescriptorSatisfaction, Error>
+    where
+        S: Satisfier<Pk>,
+    {
+        self.satisfy_with(satisfier, SatisfactionMode::NonMalleable)
+    }
+
+    /// Returns a possibly malleable satisfying witness and scriptSig to spend
+    /// an output controlled by the given descriptor, along with the nLockTime
+    /// and nSequence required by the chosen spend path, if it possible to
+    /// construct one using the satisfier S.
+    pub fn get_full_satisfaction_mall<S>(
+        &self,
+        satisfier: S,
+    ) -> Result<DescriptorSatisfaction, Error>
+    where
+        S: Satisfier<Pk>,
+    {
+        self.satisfy_with(satisfier, SatisfactionMode::Malleable)
+    }
+
+    /// Returns a satisfying witness and scriptSig to spend an output
+    /// controlled by the given descriptor, along with the nLockTime and
+    /// nSequence required by the chosen spend path, if it possible to
+    /// construct one using the satisfier S.
+    ///
+    /// With [`SatisfactionMode::Malleable`], the cheapest satisfaction is
+    /// returned, which may be malleable. The `mode` of the result tells which
+    /// mode produced it, for callers whose policy refuses malleable spends.
+    /// The other satisfaction methods are shorthands for this one.
+    pub fn satisfy_with<S>(
+        &self,
+        satisfier: S,
+        mode: SatisfactionMode,
+    ) -> Result<DescriptorSatisfaction, Error>
+    where
+        S: Satisfier<Pk>,
+    {
+        trace_span!("satisfy_with", desc_type = ?self.desc_type(), ?mode);
+        let allow_mall = mode == SatisfactionMode::Malleable;
+        let (witness, script_sig, timelocks) = match *self {
+            Descriptor::Bare(ref bare) => bare.satisfy_timelocked(&satisfier, allow_mall)?,
+            Descriptor::Pkh(ref pkh) => {
+                let (witness, script_sig) = pkh.get_satisfaction(&satisfier)?;
+                (witness, script_sig, Timelocks::default())
+            }
+            Descriptor::Wpkh(ref wpkh) => {
+                let (witness, script_sig) = wpkh.get_satisfaction(&satisfier)?;
+                (witness, script_sig, Timelocks::default())
+            }
+            Descriptor::Wsh(ref wsh) => wsh.satisfy_timelocked(&satisfier, allow_mall)?,
+            Descriptor::Sh(ref sh) => sh.satisfy_timelocked(&satisfier, allow_mall)?,
+            Descriptor::LegacyCSFSCov(ref cov) => {
+                cov.get_satisfaction_timelocked(&satisfier, allow_mall)?
+            }
+            Descriptor::Tr(ref tr) => tr::best_tap_spend(tr, &satisfier, allow_mall)?,
+            Descriptor::TrExt(ref tr) => tr::best_tap_spend(tr, &satisfier, allow_mall)?,
+        };
+        let (required_locktime, required_sequence) = timelocks.required()?;
+        Ok(DescriptorSatisfaction {
+            witness,
+            script_sig,
+            required_locktime,
+            required_sequence,
+            mode,
+        })
+    }
+
+    /// Decodes the witness and scriptSig of a spend of this descriptor,
+    /// reporting which keys signed, which preimages were revealed and, for
+    /// taproot script spends, which leaf was executed. This is the read-side
+    /// complement of [`Descriptor::get_satisfaction`].
+    ///
+    /// Signatures are not verified, and timelocks are assumed to be met since
+    /// the corresponding transaction fields are not part of the witness.
+    pub fn parse_witness(
+        &self,
+        witness: &[Vec<u8>],
+        script_sig: &Script,
+    ) -> Result<ParsedSatisfaction<Pk>, interpreter::Error> {
+        let spk = self.script_pubkey();
+        // The highest block height, followed by the highest time below
+        let interpreter = Interpreter::<Ext>::from_txdata_ext(
+            &spk,
+            script_sig,
+            witness,
+            Sequence::MAX,
+            LockTime::from_consensus(499_999_999),
+        )?;
+        let constraints = match interpreter.iter_assume_sigs().collect() {
+            // Every spend path uses either heights or times, so retry with a
+            // time based lock time if the former was wrong
+            Err(interpreter::Error::AbsoluteLocktimeComparisonInvalid(..)) => {
+                let interpreter = Interpreter::<Ext>::from_txdata_ext(
+                    &spk,
+                    script_sig,
+                    witness,
+                    Sequence::MAX,
+                    LockTime::from_consensus(0xFFFF_FFFF),
+                )?;
+                let constraints: Result<Vec<_>, _> = interpreter.iter_assume_sigs().collect();
+                constraints?
+            }
+            res => res?,
+        };
+
+        let leaf_hash = if interpreter.is_taproot_v1_script_spend() {
+            interpreter
+                .script_code()
+                .map(|script| TapLeafHash::from_script(script, LeafVersion::default()))
+        } else {
+            None
+        };
+        let internal_key = match *self {
+            Descriptor::Tr(ref tr) if interpreter.is_taproot_v1_key_spend() => {
+                Some(tr.internal_key())
+            }
+            Descriptor::TrExt(ref tr) if interpreter.is_taproot_v1_key_spend() => {
+                Some(tr.internal_key())
+            }
+            _ => None,
+        };
+        let find_signer = |key_sig: &KeySigPair| -> Result<Pk, interpreter::Error> {
+            if let Some(pk) = internal_key {
+                return Ok(pk.clone());
+            }
+            let mut signer = None;
+            self.for_any_key(|pk| {
+                let matches = match *key_sig {
+                    KeySigPair::Ecdsa(key, _) => pk.to_public_key() == key,
+                    KeySigPair::Schnorr(key, _) => pk.to_x_only_pubkey() == key,
+                };
+                if matches {
+                    signer = Some(pk.clone());
+                }
+                matches
+            });
+            signer.ok_or_else(|| {
+                interpreter::Error::Miniscript(Error::Unexpected(format!(
+                    "signing key {:?} not in descriptor",
+                    key_sig
+                )))
+            })
+        };
+
+        let mut signatures = vec![];
+        let mut preimages = vec![];
+        for constraint in constraints {
+            match constraint {
+                SatisfiedConstraint::PublicKey { key_sig }
+                | SatisfiedConstraint::PublicKeyHash { key_sig, .. } => {
+                    signatures.push((find_signer(&key_sig)?, key_sig))
+                }
+                SatisfiedConstraint::HashLock { hash, preimage } => {
+                    preimages.push((hash, preimage))
+                }
+                _ => {}
+            }
         }
+        Ok(ParsedSatisfaction {
+            signatures,
+            preimages,
+            leaf_hash,
+        })
     }
 
     /// Attempts to produce a non-malleable satisfying witness and scriptSig to spend an
@@ -688,6 +1229,230 @@ impl<Pk: MiniscriptKey + ToPublicKey, Ext: Extension + ParseableExt> Descriptor<
         txin.script_sig = script_sig;
         Ok(())
     }
+
+    /// Returns the redeem script of a `sh(multi)` or `sh(sortedmulti)`
+    /// descriptor. This is the reverse of
+    /// [`Descriptor::from_legacy_p2sh_multisig`].
+    ///
+    /// # Errors
+    /// If the descriptor is not a p2sh multisig descriptor.
+    pub fn to_legacy_p2sh_multisig(&self) -> Result<Script, Error> {
+        if let Descriptor::Sh(ref sh) = *self {
+            match *sh.as_inner() {
+                ShInner::SortedMulti(..) => return Ok(sh.inner_script()),
+                ShInner::Ms(ref ms) => {
+                    if let Terminal::Multi(..) = ms.node {
+                        return Ok(sh.inner_script());
+                    }
+                }
+                ShInner::Wsh(..) | ShInner::Wpkh(..) => {}
+            }
+        }
+        Err(Error::BadDescriptor(format!(
+            "{} is not a legacy p2sh multisig descriptor",
+            self
+        )))
+    }
+}
+
+impl<Ext: Extension> Descriptor<bitcoin::PublicKey, Ext> {
+    /// Create a new `sh(multi)` descriptor from the redeem script of a legacy
+    /// p2sh multisig output. If the keys in the script are sorted as
+    /// described in BIP-67, a `sh(sortedmulti)` descriptor is returned
+    /// instead.
+    ///
+    /// # Errors
+    /// If the script is not a `CHECKMULTISIG` script, or exceeds the p2sh
+    /// resource limits.
+    pub fn from_legacy_p2sh_multisig(redeem_script: &Script) -> Result<Self, Error> {
+        let ms = Miniscript::<bitcoin::PublicKey, Legacy>::parse(redeem_script)?;
+        let (k, pks) = match ms.node {
+            Terminal::Multi(k, ref pks) => (k, pks.clone()),
+            _ => {
+                return Err(Error::BadDescriptor(format!(
+                    "{} is not a legacy p2sh multisig redeem script",
+                    redeem_script
+                )))
+            }
+        };
+        // Same ordering as `SortedMultiVec::sorted_node`
+        let is_sorted = pks
+            .windows(2)
+            .all(|w| w[0].inner.serialize() <= w[1].inner.serialize());
+        if is_sorted {
+            Descriptor::new_sh_sortedmulti(k, pks)
+        } else {
+            Descriptor::new_sh(ms)
+        }
+    }
+}
+
+impl<Ext: ParseableExt> Descriptor<bitcoin::PublicKey, Ext> {
+    /// Infer the descriptor of an output from its script pubkey, e.g. for
+    /// blockchain scanning tools.
+    ///
+    /// Script hash outputs require their `redeem_script` and/or
+    /// `witness_script`, and pkh and wpkh outputs are only recognized if their
+    /// key is one of `keys`. Witness scripts are tried as `elcovwsh`
+    /// covenants first. Scripts are parsed with [`ExtParams::allow_all`], so
+    /// insane Miniscripts are accepted; multisig scripts are returned as
+    /// `multi`, never `sortedmulti`.
+    ///
+    /// # Errors
+    /// For taproot outputs, which cannot be inferred from their script
+    /// pubkey. If the scripts do not match the script pubkey, a script is
+    /// missing or superfluous, or a script is not Miniscript.
+    pub fn from_script(
+        script_pubkey: &Script,
+        redeem_script: Option<&Script>,
+        witness_script: Option<&Script>,
+        keys: &[bitcoin::PublicKey],
+    ) -> Result<Self, Error> {
+        let unexpected = |name: &str, script: Option<&Script>| match script {
+            Some(script) => Err(Error::BadDescriptor(format!(
+                "unexpected {} {} for script pubkey {}",
+                name, script, script_pubkey
+            ))),
+            None => Ok(()),
+        };
+        let find_key = |spk: &Script, desc: fn(bitcoin::PublicKey) -> Result<Self, Error>| {
+            keys.iter()
+                .copied()
+                .find(|pk| desc(*pk).map_or(false, |d| d.script_pubkey() == *spk))
+                .ok_or_else(|| Error::BadDescriptor(format!("no key for script pubkey {}", spk)))
+        };
+        let pkh = |pk| Ok(Descriptor::new_pkh(pk));
+        let wpkh = Descriptor::new_wpkh;
+
+        if util::is_v1_p2tr(script_pubkey) {
+            Err(Error::BadDescriptor(format!(
+                "taproot script pubkey {} cannot be inferred",
+                script_pubkey
+            )))
+        } else if script_pubkey.is_p2pkh() {
+            unexpected("redeem script", redeem_script)?;
+            unexpected("witness script", witness_script)?;
+            Ok(Descriptor::new_pkh(find_key(script_pubkey, pkh)?))
+        } else if script_pubkey.is_v0_p2wpkh() {
+            unexpected("redeem script", redeem_script)?;
+            unexpected("witness script", witness_script)?;
+            Descriptor::new_wpkh(find_key(script_pubkey, wpkh)?)
+        } else if script_pubkey.is_v0_p2wsh() {
+            unexpected("redeem script", redeem_script)?;
+            let witness_script = match_script(witness_script, script_pubkey, Script::to_v0_p2wsh)?;
+            if let Ok(cov) = LegacyCSFSCov::parse_insane(witness_script) {
+                return Ok(Descriptor::LegacyCSFSCov(cov));
+            }
+            Descriptor::new_wsh(Miniscript::parse_with_ext(
+                witness_script,
+                &ExtParams::allow_all(),
+            )?)
+        } else if script_pubkey.is_p2sh() {
+            let redeem_script = match_script(redeem_script, script_pubkey, Script::to_p2sh)?;
+            if redeem_script.is_v0_p2wsh() {
+                let witness_script =
+                    match_script(witness_script, redeem_script, Script::to_v0_p2wsh)?;
+                Descriptor::new_sh_wsh(Miniscript::parse_with_ext(
+                    witness_script,
+                    &ExtParams::allow_all(),
+                )?)
+            } else if redeem_script.is_v0_p2wpkh() {
+                unexpected("witness script", witness_script)?;
+                Descriptor::new_sh_wpkh(find_key(redeem_script, wpkh)?)
+            } else {
+                unexpected("witness script", witness_script)?;
+                Descriptor::new_sh(Miniscript::parse_with_ext(
+                    redeem_script,
+                    &ExtParams::allow_all(),
+                )?)
+            }
+        } else {
+            unexpected("redeem script", redeem_script)?;
+            unexpected("witness script", witness_script)?;
+            Descriptor::new_bare(Miniscript::parse_with_ext(
+                script_pubkey,
+                &ExtParams::allow_all(),
+            )?)
+        }
+    }
+}
+
+// The script which hashes to `hash_script`, with `to_hash_script` computing
+// the latter from the former
+fn match_script<'s>(
+    script: Option<&'s Script>,
+    hash_script: &Script,
+    to_hash_script: fn(&Script) -> Script,
+) -> Result<&'s Script, Error> {
+    match script {
+        Some(script) if to_hash_script(script) == *hash_script => Ok(script),
+        Some(script) => Err(Error::BadDescriptor(format!(
+            "script {} does not match {}",
+            script, hash_script
+        ))),
+        None => Err(Error::BadDescriptor(format!(
+            "missing script for {}",
+            hash_script
+        ))),
+    }
+}
+
+/// A satisfaction of a descriptor, along with the transaction fields that
+/// the chosen spend path requires.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct DescriptorSatisfaction {
+    /// The witness stack of the spending input
+    pub witness: Vec<Vec<u8>>,
+    /// The scriptSig of the spending input
+    pub script_sig: Script,
+    /// The minimum nLockTime of the spending transaction, if the spend path
+    /// uses any `after()` fragments
+    pub required_locktime: Option<PackedLockTime>,
+    /// The minimum nSequence of the spending input, if the spend path uses
+    /// any `older()` fragments
+    pub required_sequence: Option<Sequence>,
+    /// The mode which produced the witness: it may only be malleable if
+    /// this is [`SatisfactionMode::Malleable`]
+    pub mode: SatisfactionMode,
+}
+
+/// Whether a satisfaction may be malleable, see [`Descriptor::satisfy_with`]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub enum SatisfactionMode {
+    /// Only non-malleable satisfactions
+    NonMalleable,
+    /// Possibly malleable satisfactions
+    Malleable,
+}
+
+/// A spend of a descriptor, as decoded from its witness and scriptSig by
+/// [`Descriptor::parse_witness`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ParsedSatisfaction<Pk: MiniscriptKey> {
+    /// The keys which signed, along with their signatures
+    pub signatures: Vec<(Pk, KeySigPair)>,
+    /// The hashlocks which were satisfied, along with the revealed preimages
+    pub preimages: Vec<(HashLockType, [u8; 32])>,
+    /// For taproot script spends, the hash of the executed leaf
+    pub leaf_hash: Option<TapLeafHash>,
+}
+
+/// The scripts needed to sign for and spend an output of a descriptor, see
+/// [`Descriptor::spend_info`]
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SpendInfo {
+    /// The scriptPubkey of the output
+    pub script_pubkey: Script,
+    /// The redeemScript, for `elsh()` descriptors
+    pub redeem_script: Option<Script>,
+    /// The witnessScript, for `elwsh()`, `elsh(wsh())` and covenant
+    /// descriptors
+    pub witness_script: Option<Script>,
+    /// The `scriptCode` of ECDSA sighashes, `None` for taproot descriptors
+    pub script_code: Option<Script>,
+    /// The spend info of taproot descriptors, with the output key and the
+    /// control blocks of the leaves
+    pub taproot: Option<Arc<TaprootSpendInfo>>,
 }
 
 impl<P, Q, Ext> TranslatePk<P, Q> for Descriptor<P, Ext>
@@ -781,29 +1546,101 @@ impl<Ext: Extension + ParseableExt> Descriptor<DescriptorPublicKey, Ext> {
         self.for_any_key(|key| key.has_wildcard())
     }
 
+    /// Whether or not the descriptor has any keys with multiple derivation
+    /// paths, i.e. `/<0;1>`.
+    pub fn is_multipath(&self) -> bool {
+        self.for_any_key(|key| key.is_multipath())
+    }
+
+    /// Get as many descriptors as there are derivation paths in its
+    /// multipath keys, e.g. the receive and change descriptors of
+    /// `elwpkh(xpub/<0;1>/*)`. The `i`-th descriptor has the `i`-th path of
+    /// every multipath key. Descriptors without multipath keys are returned
+    /// alone.
+    ///
+    /// # Errors
+    ///
+    /// If the multipath keys do not all have the same number of paths.
+    pub fn into_single_descriptors(
+        self,
+    ) -> Result<Vec<Descriptor<DescriptorPublicKey, Ext>>, Error> {
+        let mut num_paths = None;
+        let mut consistent = true;
+        self.for_each_key(|key| {
+            if key.is_multipath() {
+                let len = key.full_derivation_paths().len();
+                consistent &= *num_paths.get_or_insert(len) == len;
+            }
+            true
+        });
+        if !consistent {
+            return Err(Error::BadDescriptor(String::from(
+                "multipath keys must have the same number of derivation paths",
+            )));
+        }
+        let num_paths = match num_paths {
+            Some(n) => n,
+            None => return Ok(vec![self]),
+        };
+
+        struct PathSelector(usize);
+
+        impl Translator<DescriptorPublicKey, DescriptorPublicKey, ()> for PathSelector {
+            fn pk(&mut self, pk: &DescriptorPublicKey) -> Result<DescriptorPublicKey, ()> {
+                let mut keys = pk.clone().into_single_keys();
+                if keys.len() == 1 {
+                    Ok(keys.remove(0))
+                } else {
+                    Ok(keys.swap_remove(self.0))
+                }
+            }
+
+            translate_hash_clone!(DescriptorPublicKey, DescriptorPublicKey, ());
+        }
+        Ok((0..num_paths)
+            .map(|i| {
+                self.translate_pk(&mut PathSelector(i))
+                    .expect("selecting a derivation path cannot fail")
+            })
+            .collect())
+    }
+
     /// Replaces all wildcards (i.e. `/*`) in the descriptor with a particular derivation index,
     /// turning it into a *definite* descriptor.
     ///
+    /// # Errors
+    ///
+    /// [`ConversionError::MultiKey`] for multipath descriptors, which must be
+    /// split with [`Descriptor::into_single_descriptors`] first.
+    ///
     /// # Panics
     ///
     /// If index ≥ 2^31
-    pub fn at_derivation_index(&self, index: u32) -> Descriptor<DefiniteDescriptorKey, Ext> {
+    pub fn at_derivation_index(
+        &self,
+        index: u32,
+    ) -> Result<Descriptor<DefiniteDescriptorKey, Ext>, ConversionError> {
         struct Derivator(u32);
 
-        impl Translator<DescriptorPublicKey, DefiniteDescriptorKey, ()> for Derivator {
-            fn pk(&mut self, pk: &DescriptorPublicKey) -> Result<DefiniteDescriptorKey, ()> {
-                Ok(pk.clone().at_derivation_index(self.0))
+        impl Translator<DescriptorPublicKey, DefiniteDescriptorKey, ConversionError> for Derivator {
+            fn pk(
+                &mut self,
+                pk: &DescriptorPublicKey,
+            ) -> Result<DefiniteDescriptorKey, ConversionError> {
+                pk.clone().at_derivation_index(self.0)
             }
 
-            translate_hash_clone!(DescriptorPublicKey, DescriptorPublicKey, ());
+            translate_hash_clone!(DescriptorPublicKey, DescriptorPublicKey, ConversionError);
         }
         self.translate_pk(&mut Derivator(index))
-            .expect("BIP 32 key index substitution cannot fail")
     }
 
     #[deprecated(note = "use at_derivation_index instead")]
     /// Deprecated name for [`at_derivation_index`].
-    pub fn derive(&self, index: u32) -> Descriptor<DefiniteDescriptorKey, Ext> {
+    pub fn derive(
+        &self,
+        index: u32,
+    ) -> Result<Descriptor<DefiniteDescriptorKey, Ext>, ConversionError> {
         self.at_derivation_index(index)
     }
 
@@ -820,7 +1657,7 @@ impl<Ext: Extension + ParseableExt> Descriptor<DescriptorPublicKey, Ext> {
     ///     .expect("Valid ranged descriptor");
     /// # let index = 42;
     /// # let secp = Secp256k1::verification_only();
-    /// let derived_descriptor = descriptor.at_derivation_index(index).derived_descriptor(&secp);
+    /// let derived_descriptor = descriptor.at_derivation_index(index).unwrap().derived_descriptor(&secp);
     /// # assert_eq!(descriptor.derived_descriptor(&secp, index), derived_descriptor);
     /// ```
     ///
@@ -832,13 +1669,14 @@ impl<Ext: Extension + ParseableExt> Descriptor<DescriptorPublicKey, Ext> {
     ///
     /// # Errors
     ///
-    /// This function will return an error if hardened derivation is attempted.
+    /// This function will return an error if hardened derivation is attempted
+    /// or if the descriptor is a multipath descriptor.
     pub fn derived_descriptor<C: secp256k1_zkp::Verification>(
         &self,
         secp: &secp256k1_zkp::Secp256k1<C>,
         index: u32,
     ) -> Result<Descriptor<bitcoin::PublicKey, Ext>, ConversionError> {
-        self.at_derivation_index(index).derived_descriptor(&secp)
+        self.at_derivation_index(index)?.derived_descriptor(&secp)
     }
 
     /// Parse a descriptor that may contain secret keys
@@ -918,16 +1756,60 @@ impl<Ext: Extension + ParseableExt> Descriptor<DescriptorPublicKey, Ext> {
         Ok((descriptor, keymap_pk.0))
     }
 
-    /// Serialize a descriptor to string with its secret keys
-    pub fn to_string_with_secret(&self, key_map: &KeyMap) -> String {
-        struct KeyMapLookUp<'a>(&'a KeyMap);
+    /// Parse a descriptor like [`FromStr`], also returning the extended keys
+    /// which were given with a SLIP-132 version, e.g. as `zpub`, along with
+    /// that version. See [`DescriptorPublicKey::from_str_slip132`].
+    ///
+    /// [`FromStr`] silently normalizes these keys to `xpub` or `tpub`. The
+    /// script type implied by their version is not checked against the
+    /// descriptor, so callers should warn about the returned keys.
+    pub fn parse_slip132(
+        s: &str,
+    ) -> Result<(Descriptor<DescriptorPublicKey, Ext>, Slip132Keys), Error> {
+        struct Recorder(Slip132Keys);
 
-        impl<'a> Translator<DescriptorPublicKey, String, ()> for KeyMapLookUp<'a> {
-            fn pk(&mut self, pk: &DescriptorPublicKey) -> Result<String, ()> {
-                key_to_string(pk, self.0)
+        impl Translator<String, DescriptorPublicKey, Error> for Recorder {
+            fn pk(&mut self, pk: &String) -> Result<DescriptorPublicKey, Error> {
+                let (key, version) = DescriptorPublicKey::from_str_slip132(pk)
+                    .map_err(|e| Error::Unexpected(e.to_string()))?;
+                if let Some(version) = version {
+                    self.0.push((key.clone(), version));
+                }
+                Ok(key)
             }
 
-            fn sha256(&mut self, sha256: &sha256::Hash) -> Result<String, ()> {
+            fn sha256(&mut self, sha256: &String) -> Result<sha256::Hash, Error> {
+                sha256::Hash::from_str(sha256).map_err(|e| Error::Unexpected(e.to_string()))
+            }
+
+            fn hash256(&mut self, hash256: &String) -> Result<hash256::Hash, Error> {
+                hash256::Hash::from_str(hash256).map_err(|e| Error::Unexpected(e.to_string()))
+            }
+
+            fn ripemd160(&mut self, ripemd160: &String) -> Result<ripemd160::Hash, Error> {
+                ripemd160::Hash::from_str(ripemd160).map_err(|e| Error::Unexpected(e.to_string()))
+            }
+
+            fn hash160(&mut self, hash160: &String) -> Result<hash160::Hash, Error> {
+                hash160::Hash::from_str(hash160).map_err(|e| Error::Unexpected(e.to_string()))
+            }
+        }
+
+        let mut keys = Recorder(vec![]);
+        let descriptor = Descriptor::<String, Ext>::from_str(s)?.translate_pk(&mut keys)?;
+        Ok((descriptor, keys.0))
+    }
+
+    /// Serialize a descriptor to string with its secret keys
+    pub fn to_string_with_secret(&self, key_map: &KeyMap) -> String {
+        struct KeyMapLookUp<'a>(&'a KeyMap);
+
+        impl<'a> Translator<DescriptorPublicKey, String, ()> for KeyMapLookUp<'a> {
+            fn pk(&mut self, pk: &DescriptorPublicKey) -> Result<String, ()> {
+                key_to_string(pk, self.0)
+            }
+
+            fn sha256(&mut self, sha256: &sha256::Hash) -> Result<String, ()> {
                 Ok(sha256.to_string())
             }
 
@@ -973,11 +1855,11 @@ impl<Ext: Extension + ParseableExt> Descriptor<DescriptorPublicKey, Ext> {
         script_pubkey: &Script,
         range: Range<u32>,
     ) -> Result<Option<(u32, Descriptor<bitcoin::PublicKey, Ext>)>, ConversionError> {
-        let range = if self.has_wildcard() { range } else { 0..1 };
-
-        for i in range {
-            let concrete = self.derived_descriptor(secp, i)?;
-            if &concrete.script_pubkey() == script_pubkey {
+        let mut iter = self.address_batch_iter(secp, range);
+        while let Some(item) = iter.next_spk() {
+            let (i, spk) = item?;
+            if spk == script_pubkey.as_bytes() {
+                let concrete = iter.descriptor().expect("derived descriptor");
                 return Ok(Some((i, concrete)));
             }
         }
@@ -1003,7 +1885,7 @@ impl<Ext: Extension> Descriptor<DefiniteDescriptorKey, Ext> {
     /// let secp = secp256k1::Secp256k1::verification_only();
     /// let descriptor = Descriptor::<DescriptorPublicKey>::from_str("eltr(xpub6BgBgsespWvERF3LHQu6CnqdvfEvtMcQjYrcRzx53QJjSxarj2afYWcLteoGVky7D3UKDP9QyrLprQ3VCECoY49yfdDEHGCtMMj92pReUsQ/0/*)")
     ///     .expect("Valid ranged descriptor");
-    /// let result = descriptor.at_derivation_index(0).derived_descriptor(&secp).expect("Non-hardened derivation");
+    /// let result = descriptor.at_derivation_index(0).unwrap().derived_descriptor(&secp).expect("Non-hardened derivation");
     /// assert_eq!(result.to_string(), "eltr(03cc8a4bc64d897bddc5fbc2f670f7a8ba0b386779106cf1223c6fc5d7cd6fc115)#hr5pt2wj");
     /// ```
     ///
@@ -1083,6 +1965,65 @@ impl_from_str!(
     }
 );
 
+impl_block_str!(
+    ;T; Extension,
+    Descriptor<Pk, T>,
+    /// Parse a descriptor without checking that it is sane, e.g. to inspect
+    /// a descriptor which [`FromStr`] rejects.
+    ///
+    /// This skips the sanity checks of the taproot leaves, which are parsed
+    /// as by [`Miniscript::from_str_insane`], so that all descriptors are
+    /// parsed alike: like the other miniscripts, they must still type check
+    /// and be valid in their script context. The checksum may be absent, but
+    /// it is verified when present.
+    ///
+    /// The descriptor may be insane, e.g. have malleable spends or repeated
+    /// keys, so it is returned as [`Unchecked`]: use [`Unchecked::validate`]
+    /// to check it, or [`Unchecked::assume_checked`] if the string comes
+    /// from a trusted source.
+    pub fn from_str_unchecked(s: &str) -> Result<Unchecked<Descriptor<Pk, T>>, Error> {
+        let desc_str = verify_checksum(s)?;
+        if !desc_str.starts_with(ELMTS_STR) {
+            return Err(Error::BadDescriptor(String::from(
+                "Not an Elements Descriptor",
+            )));
+        }
+        if desc_str.starts_with(&format!("{}tr", ELMTS_STR)) {
+            let top = tr::parse_tr_tree(desc_str)?;
+            return match Tr::<Pk, NoExt>::from_tree_checked(&top, false) {
+                Ok(tr) => Ok(Unchecked(Descriptor::Tr(tr))),
+                Err(_) => Ok(Unchecked(Descriptor::TrExt(Tr::from_tree_checked(&top, false)?))),
+            };
+        }
+        let top = expression::Tree::from_str(desc_str)?;
+        Ok(Unchecked(expression::FromTree::from_tree(&top)?))
+    }
+);
+
+/// A descriptor which has not been checked to be sane, see
+/// [`Descriptor::from_str_unchecked`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Unchecked<D>(D);
+
+impl<Pk: MiniscriptKey, T: Extension> Unchecked<Descriptor<Pk, T>> {
+    /// Get a reference to the unchecked descriptor
+    pub fn as_unchecked(&self) -> &Descriptor<Pk, T> {
+        &self.0
+    }
+
+    /// Check the descriptor with [`Descriptor::sanity_check`]
+    pub fn validate(self) -> Result<Descriptor<Pk, T>, Error> {
+        self.0.sanity_check()?;
+        Ok(self.0)
+    }
+
+    /// Use the descriptor without checking it. Only do this for descriptors
+    /// from a trusted source, e.g. ones which were checked when stored.
+    pub fn assume_checked(self) -> Descriptor<Pk, T> {
+        self.0
+    }
+}
+
 impl<Pk: MiniscriptKey, T: Extension> fmt::Debug for Descriptor<Pk, T> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match *self {
@@ -1117,7 +2058,6 @@ serde_string_impl_pk!(Descriptor, "a script descriptor", T; Extension);
 
 #[cfg(test)]
 mod tests {
-    use std::cmp;
     use std::collections::HashMap;
     use std::str::FromStr;
 
@@ -1134,33 +2074,18 @@ mod tests {
     use super::tr::Tr;
     use super::*;
     use crate::descriptor::key::Wildcard;
-    use crate::descriptor::{DescriptorPublicKey, DescriptorSecretKey, DescriptorXKey};
+    use crate::descriptor::{DescriptorPublicKey, DescriptorXKey};
     use crate::miniscript::satisfy::ElementsSig;
     #[cfg(feature = "compiler")]
     use crate::policy;
-    use crate::{hex_script, Descriptor, Error, Miniscript, NoExt, Satisfier};
+    use crate::{
+        elementssig_to_rawsig, hex_script, Descriptor, Error, Miniscript, NoExt, Satisfier,
+    };
 
     type StdDescriptor = Descriptor<PublicKey, CovenantExt<CovExtArgs>>;
     const TEST_PK: &'static str =
         "elpk(020000000000000000000000000000000000000000000000000000000000000002)";
 
-    impl cmp::PartialEq for DescriptorSecretKey {
-        fn eq(&self, other: &Self) -> bool {
-            match (self, other) {
-                (&DescriptorSecretKey::Single(ref a), &DescriptorSecretKey::Single(ref b)) => {
-                    a.origin == b.origin && a.key == b.key
-                }
-                (&DescriptorSecretKey::XPrv(ref a), &DescriptorSecretKey::XPrv(ref b)) => {
-                    a.origin == b.origin
-                        && a.xkey == b.xkey
-                        && a.derivation_path == b.derivation_path
-                        && a.wildcard == b.wildcard
-                }
-                _ => false,
-            }
-        }
-    }
-
     fn roundtrip_descriptor(s: &str) {
         let desc = Descriptor::<String>::from_str(&s).unwrap();
         let output = desc.to_string();
@@ -1698,6 +2623,69 @@ mod tests {
         assert_eq!(sig0, sig_b);
     }
 
+    #[test]
+    fn legacy_multi_satisfaction() {
+        let a = bitcoin::PublicKey::from_str(
+            "02937402303919b3a2ee5edd5009f4236f069bf75667b8e6ecf8e5464e20116a0e",
+        )
+        .unwrap();
+        let sig_a = secp256k1_zkp::ecdsa::Signature::from_str("3045022100a7acc3719e9559a59d60d7b2837f9842df30e7edcd754e63227e6168cec72c5d022066c2feba4671c3d99ea75d9976b4da6c86968dbf3bab47b1061e7a1966b1778c").unwrap();
+        let b = bitcoin::PublicKey::from_str(
+            "02eb64639a17f7334bb5a1a3aad857d6fec65faef439db3de72f85c88bc2906ad3",
+        )
+        .unwrap();
+        let sig_b = secp256k1_zkp::ecdsa::Signature::from_str("3044022075b7b65a7e6cd386132c5883c9db15f9a849a0f32bc680e9986398879a57c276022056d94d12255a4424f51c700ac75122cb354895c9f2f88f0cbb47ba05c9c589ba").unwrap();
+        let all = ::elements::EcdsaSigHashType::All;
+
+        let descriptor =
+            Descriptor::<bitcoin::PublicKey>::from_str(&format!("elsh(multi(2,{},{}))", a, b))
+                .unwrap();
+        let mut satisfier = HashMap::new();
+        satisfier.insert(a, (sig_a, all));
+        satisfier.insert(b, (sig_b, all));
+
+        // The CHECKMULTISIG dummy is pushed as OP_0 before the signatures
+        let mut txin = elements_txin(Script::new(), vec![]);
+        descriptor.satisfy(&mut txin, &satisfier).unwrap();
+        assert!(txin.witness.script_witness.is_empty());
+        assert_eq!(
+            txin.script_sig,
+            script::Builder::new()
+                .push_opcode(opcodes::all::OP_PUSHBYTES_0)
+                .push_slice(&elementssig_to_rawsig(&(sig_a, all)))
+                .push_slice(&elementssig_to_rawsig(&(sig_b, all)))
+                .push_slice(descriptor.explicit_script().unwrap().as_bytes())
+                .into_script()
+        );
+
+        // Signatures with a high S value are rejected if they are used
+        let mut high_s = [0; 64];
+        high_s[..32].copy_from_slice(&[1; 32]);
+        high_s[32..48].copy_from_slice(&[0xff; 16]);
+        high_s[47] = 0xfe;
+        let sig_high = secp256k1_zkp::ecdsa::Signature::from_compact(&high_s).unwrap();
+        satisfier.insert(b, (sig_high, all));
+        assert_eq!(
+            descriptor.satisfy(&mut txin, &satisfier),
+            Err(Error::NonCanonicalSig(b))
+        );
+        let descriptor =
+            Descriptor::<bitcoin::PublicKey>::from_str(&format!("elsh(or_d(pk({}),pk({})))", a, b))
+                .unwrap();
+        descriptor.satisfy(&mut txin, &satisfier).unwrap();
+        let high_raw = elementssig_to_rawsig(&(sig_high, all));
+        assert!(!txin.script_sig.instructions().any(|ins| match ins {
+            Ok(script::Instruction::PushBytes(push)) => push == &high_raw[..],
+            _ => false,
+        }));
+        let descriptor =
+            Descriptor::<bitcoin::PublicKey>::from_str(&format!("elpkh({})", b)).unwrap();
+        assert_eq!(
+            descriptor.satisfy(&mut txin, &satisfier),
+            Err(Error::NonCanonicalSig(b))
+        );
+    }
+
     #[test]
     fn test_scriptcode() {
         // P2WPKH (from bip143 test vectors)
@@ -1864,12 +2852,14 @@ mod tests {
             // Same address
             let addr_one = desc_one
                 .at_derivation_index(index)
+                .unwrap()
                 .derived_descriptor(&secp_ctx)
                 .unwrap()
                 .address(&elements::AddressParams::ELEMENTS)
                 .unwrap();
             let addr_two = desc_two
                 .at_derivation_index(index)
+                .unwrap()
                 .derived_descriptor(&secp_ctx)
                 .unwrap()
                 .address(&elements::AddressParams::ELEMENTS)
@@ -1947,7 +2937,7 @@ pk(xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHW
 pk(03f28773c2d975288bc7d1d205c3748651b075fbc6610e58cddeeddf8f19405aa8))";
         let policy: policy::concrete::Policy<DescriptorPublicKey> = descriptor_str.parse().unwrap();
         let descriptor = Descriptor::<_, NoExt>::new_sh(policy.compile().unwrap()).unwrap();
-        let definite_descriptor = descriptor.at_derivation_index(42);
+        let definite_descriptor = descriptor.at_derivation_index(42).unwrap();
 
         let res_descriptor_str = "thresh(2,\
 pk([d34db33f/44'/0'/0']xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL/1/42),\
@@ -2114,4 +3104,823 @@ pk(03f28773c2d975288bc7d1d205c3748651b075fbc6610e58cddeeddf8f19405aa8))";
     fn test_regression_29() {
         let _ = Descriptor::<String>::from_str("eltr(,thresh(1,spk_eq(,00)))");
     }
+
+    #[test]
+    fn recommended_sequence() {
+        let desc = Descriptor::<String>::from_str("elwpkh(A)").unwrap();
+        assert_eq!(
+            desc.recommended_sequence(None).unwrap(),
+            Sequence::ENABLE_RBF_NO_LOCKTIME
+        );
+
+        let desc = Descriptor::<String>::from_str(
+            "elwsh(or_d(pk(A),and_v(v:pk(B),and_v(v:older(10),older(144)))))",
+        )
+        .unwrap();
+        // The key-only branch needs no timelock
+        assert_eq!(
+            desc.recommended_sequence(None).unwrap(),
+            Sequence::ENABLE_RBF_NO_LOCKTIME
+        );
+        // The timelocked branch needs to satisfy all of its `older()`s
+        let path = semantic::Policy::from_str("and(pk(B),older(10),older(144))").unwrap();
+        assert_eq!(
+            desc.recommended_sequence(Some(&path)).unwrap(),
+            Sequence::from_height(144)
+        );
+
+        let desc = Descriptor::<String>::from_str("elwsh(and_v(v:pk(A),older(4194305)))").unwrap();
+        assert_eq!(
+            desc.recommended_sequence(None).unwrap(),
+            Sequence::from_consensus(4194305)
+        );
+
+        let path = semantic::Policy::from_str("and(older(10),older(4194305))").unwrap();
+        assert_eq!(
+            desc.recommended_sequence(Some(&path))
+                .unwrap_err()
+                .to_string(),
+            Error::CouldNotSatisfy.to_string()
+        );
+    }
+
+    #[test]
+    fn full_satisfaction_timelocks() {
+        let secp = secp256k1_zkp::Secp256k1::new();
+        let msg = secp256k1_zkp::Message::from_slice(&b"michael was a message, amusingly"[..])
+            .expect("32 bytes");
+        let mut keys = vec![];
+        let mut sigs = HashMap::new();
+        for i in 1..3 {
+            let sk = secp256k1_zkp::SecretKey::from_slice(&[i; 32]).unwrap();
+            let pk = bitcoin::PublicKey {
+                inner: secp256k1_zkp::PublicKey::from_secret_key(&secp, &sk),
+                compressed: true,
+            };
+            let sig = (secp.sign_ecdsa(&msg, &sk), elements::EcdsaSigHashType::All);
+            keys.push(pk);
+            sigs.insert(pk, sig);
+        }
+        let desc = StdDescriptor::from_str(&format!(
+            "elwsh(or_d(pk({}),and_v(v:pk({}),and_v(v:after(500000),older(144)))))",
+            keys[0], keys[1]
+        ))
+        .unwrap();
+        let age = Sequence::from_height(200);
+        let height = elements::LockTime::from_consensus(600_000);
+
+        // With both keys the cheaper, timelock-free branch is chosen
+        let sat = desc.get_full_satisfaction((&sigs, age, height)).unwrap();
+        assert_eq!(
+            (sat.witness.clone(), sat.script_sig.clone()),
+            desc.get_satisfaction((&sigs, age, height)).unwrap()
+        );
+        assert_eq!(sat.required_locktime, None);
+        assert_eq!(sat.required_sequence, None);
+
+        sigs.remove(&keys[0]);
+        let sat = desc.get_full_satisfaction((&sigs, age, height)).unwrap();
+        assert_eq!(sat.required_locktime, Some(PackedLockTime(500_000)));
+        assert_eq!(sat.required_sequence, Some(Sequence::from_height(144)));
+        let sat = desc
+            .get_full_satisfaction_mall((&sigs, age, height))
+            .unwrap();
+        assert_eq!(sat.required_locktime, Some(PackedLockTime(500_000)));
+        assert_eq!(sat.required_sequence, Some(Sequence::from_height(144)));
+
+        // Unsatisfiable when the timelocks are not met
+        assert!(desc
+            .get_full_satisfaction((&sigs, Sequence::from_height(100), height))
+            .is_err());
+    }
+
+    #[test]
+    fn full_satisfaction_mixed_timelocks() {
+        // Answers every timelock, whatever its unit
+        struct AnyTimelock;
+        impl Satisfier<bitcoin::PublicKey> for AnyTimelock {
+            fn check_older(&self, _: Sequence) -> bool {
+                true
+            }
+            fn check_after(&self, _: elements::LockTime) -> bool {
+                true
+            }
+        }
+
+        // A single nLockTime cannot be both a height and a time
+        let ms = Miniscript::<bitcoin::PublicKey, Segwitv0>::from_str_insane(
+            "and_v(v:after(500000),after(600000000))",
+        )
+        .unwrap();
+        let desc = StdDescriptor::new_wsh(ms).unwrap();
+        assert_eq!(
+            desc.get_full_satisfaction(AnyTimelock),
+            Err(Error::ImpossibleSatisfaction)
+        );
+
+        let ms = Miniscript::<bitcoin::PublicKey, Segwitv0>::from_str_insane(
+            "and_v(v:older(144),older(4194305))",
+        )
+        .unwrap();
+        let desc = StdDescriptor::new_wsh(ms).unwrap();
+        assert_eq!(
+            desc.get_full_satisfaction(AnyTimelock),
+            Err(Error::ImpossibleSatisfaction)
+        );
+
+        // Locks of the same unit take the maximum
+        let ms = Miniscript::<bitcoin::PublicKey, Segwitv0>::from_str_insane(
+            "and_v(v:after(500000),after(400000))",
+        )
+        .unwrap();
+        let desc = StdDescriptor::new_wsh(ms).unwrap();
+        let sat = desc.get_full_satisfaction(AnyTimelock).unwrap();
+        assert_eq!(sat.required_locktime, Some(PackedLockTime(500_000)));
+        assert_eq!(sat.required_sequence, None);
+    }
+
+    #[test]
+    fn satisfy_with_malleability() {
+        use elements::hashes::Hash;
+
+        struct PreimageSat([u8; 32]);
+        impl Satisfier<bitcoin::PublicKey> for PreimageSat {
+            fn lookup_sha256(&self, _: &sha256::Hash) -> Option<[u8; 32]> {
+                Some(self.0)
+            }
+        }
+
+        let hash = sha256::Hash::hash(&[1; 32]);
+        let preimages = PreimageSat([1; 32]);
+        let age = Sequence::from_height(10);
+
+        let desc =
+            StdDescriptor::from_str(&format!("elwsh(or_d(sha256({}),older(10)))", hash)).unwrap();
+        assert!(desc
+            .satisfy_with((&preimages, age), SatisfactionMode::NonMalleable)
+            .is_err());
+        let sat = desc
+            .satisfy_with((&preimages, age), SatisfactionMode::Malleable)
+            .unwrap();
+        assert_eq!(sat.mode, SatisfactionMode::Malleable);
+        assert_eq!(
+            (sat.witness, sat.script_sig),
+            desc.get_satisfaction_mall((&preimages, age)).unwrap()
+        );
+
+        // The mode is that of the call, even if the witness is the same
+        let desc = StdDescriptor::from_str(&format!("elwsh(sha256({}))", hash)).unwrap();
+        let mall = desc
+            .satisfy_with(&preimages, SatisfactionMode::Malleable)
+            .unwrap();
+        let non_mall = desc
+            .satisfy_with(&preimages, SatisfactionMode::NonMalleable)
+            .unwrap();
+        assert_eq!(mall.mode, SatisfactionMode::Malleable);
+        assert_eq!(non_mall.mode, SatisfactionMode::NonMalleable);
+        assert_eq!(mall.witness, non_mall.witness);
+        assert_eq!(non_mall, desc.get_full_satisfaction(&preimages).unwrap());
+    }
+
+    #[test]
+    fn legacy_p2sh_multisig() {
+        let keys: Vec<PublicKey> = [
+            "03c6d6a8d6e5b2f2fc1fc3c2c0d8d4e4fcb6dc8fbc8433b3a1d67a1c8b5b9e0b6a",
+            "02e6642fd69bd211f93f7f1f36ca51a26a5290eb2dd1b0d8279a87bb0d480c8443",
+            "0384526253c27c7aef56c7b71a5cd25bebb66dddda437826defc5b2568bde81f07",
+        ]
+        .iter()
+        .map(|s| PublicKey::from_str(s).unwrap())
+        .collect();
+        let mut sorted_keys = keys.clone();
+        sorted_keys.sort_by_key(|k| k.inner.serialize());
+
+        // Unsorted keys are kept as a plain multi
+        let multi = format!("elsh(multi(2,{},{},{}))", keys[0], keys[1], keys[2]);
+        let desc = StdDescriptor::from_str(&multi).unwrap();
+        let redeem_script = desc.to_legacy_p2sh_multisig().unwrap();
+        let legacy = StdDescriptor::from_legacy_p2sh_multisig(&redeem_script).unwrap();
+        assert_eq!(legacy, desc);
+        assert_eq!(legacy.to_legacy_p2sh_multisig().unwrap(), redeem_script);
+
+        // Sorted keys are detected, in either descriptor form
+        let expected = StdDescriptor::from_str(&format!(
+            "elsh(sortedmulti(2,{},{},{}))",
+            sorted_keys[0], sorted_keys[1], sorted_keys[2]
+        ))
+        .unwrap();
+        for desc in &[
+            format!("elsh(sortedmulti(2,{},{},{}))", keys[0], keys[1], keys[2]),
+            format!(
+                "elsh(multi(2,{},{},{}))",
+                sorted_keys[0], sorted_keys[1], sorted_keys[2]
+            ),
+        ] {
+            let desc = StdDescriptor::from_str(desc).unwrap();
+            let redeem_script = desc.to_legacy_p2sh_multisig().unwrap();
+            let legacy = StdDescriptor::from_legacy_p2sh_multisig(&redeem_script).unwrap();
+            assert_eq!(legacy, expected);
+            assert_eq!(legacy.script_pubkey(), desc.script_pubkey());
+        }
+
+        // Anything else is rejected
+        let wsh = format!("elwsh(multi(2,{},{},{}))", keys[0], keys[1], keys[2]);
+        let desc = StdDescriptor::from_str(&wsh).unwrap();
+        assert!(desc.to_legacy_p2sh_multisig().is_err());
+        let sh = format!("elsh(or_d(pk({}),pk({})))", keys[0], keys[1]);
+        let desc = StdDescriptor::from_str(&sh).unwrap();
+        assert!(desc.to_legacy_p2sh_multisig().is_err());
+        assert!(
+            StdDescriptor::from_legacy_p2sh_multisig(&desc.explicit_script().unwrap()).is_err()
+        );
+    }
+
+    #[test]
+    fn from_script() {
+        let k1 = PublicKey::from_str(
+            "028c28a97bf8298bc0d23d8c749452a32e694b65e30a9472a3954ab30fe5324caa",
+        )
+        .unwrap();
+        let k2 = PublicKey::from_str(
+            "03daed4f2be3a8bf278e70132fb0beb7522f570e144bf615c07e996d443dee8729",
+        )
+        .unwrap();
+        let keys = [k2, k1];
+        let wsh_ms = format!("or_d(pk({}),and_v(v:pk({}),older(144)))", k1, k2);
+        let witness_script = StdDescriptor::from_str(&format!("elwsh({})", wsh_ms))
+            .unwrap()
+            .explicit_script()
+            .unwrap();
+        let wpkh_spk = StdDescriptor::new_wpkh(k1).unwrap().script_pubkey();
+        let cov = StdDescriptor::from_str(&format!("elcovwsh({},pk({}))", k1, k2)).unwrap();
+        let cov_script = cov.explicit_script().unwrap();
+
+        let cases: Vec<(String, Option<&Script>, Option<&Script>)> = vec![
+            (format!("elpk({})", k1), None, None),
+            (format!("elpkh({})", k1), None, None),
+            (format!("elwpkh({})", k1), None, None),
+            (format!("elsh(wpkh({}))", k1), Some(&wpkh_spk), None),
+            (format!("elwsh({})", wsh_ms), None, Some(&witness_script)),
+            (
+                format!("elcovwsh({},pk({}))", k1, k2),
+                None,
+                Some(&cov_script),
+            ),
+        ];
+        for (desc, redeem_script, witness_script) in cases {
+            let desc = StdDescriptor::from_str(&desc).unwrap();
+            let inferred = StdDescriptor::from_script(
+                &desc.script_pubkey(),
+                redeem_script,
+                witness_script,
+                &keys,
+            )
+            .unwrap();
+            assert_eq!(inferred, desc);
+        }
+
+        let sh = StdDescriptor::from_str(&format!("elsh(or_d(pk({}),pk({})))", k1, k2)).unwrap();
+        let redeem_script = sh.explicit_script().unwrap();
+        let inferred =
+            StdDescriptor::from_script(&sh.script_pubkey(), Some(&redeem_script), None, &[])
+                .unwrap();
+        assert_eq!(inferred, sh);
+
+        let sh_wsh = StdDescriptor::from_str(&format!("elsh(wsh({}))", wsh_ms)).unwrap();
+        let redeem_script = witness_script.to_v0_p2wsh();
+        let inferred = StdDescriptor::from_script(
+            &sh_wsh.script_pubkey(),
+            Some(&redeem_script),
+            Some(&witness_script),
+            &[],
+        )
+        .unwrap();
+        assert_eq!(inferred, sh_wsh);
+
+        // Missing keys and scripts, mismatched and superfluous scripts
+        let pkh = StdDescriptor::new_pkh(k1).script_pubkey();
+        assert!(StdDescriptor::from_script(&pkh, None, None, &[k2]).is_err());
+        assert!(StdDescriptor::from_script(&wpkh_spk, None, Some(&witness_script), &keys).is_err());
+        let wsh = witness_script.to_v0_p2wsh();
+        assert!(StdDescriptor::from_script(&wsh, None, None, &keys).is_err());
+        assert!(StdDescriptor::from_script(&wsh, None, Some(&cov_script), &keys).is_err());
+        assert!(StdDescriptor::from_script(&sh_wsh.script_pubkey(), None, None, &keys).is_err());
+        let tr = StdDescriptor::from_str(&format!("eltr({})", k1)).unwrap();
+        assert!(StdDescriptor::from_script(&tr.script_pubkey(), None, None, &keys).is_err());
+    }
+
+    #[test]
+    fn lint() {
+        let a = "03c6d6a8d6e5b2f2fc1fc3c2c0d8d4e4fcb6dc8fbc8433b3a1d67a1c8b5b9e0b6a";
+        let b = "02e6642fd69bd211f93f7f1f36ca51a26a5290eb2dd1b0d8279a87bb0d480c8443";
+        let dead = semantic::Policy::Threshold(
+            2,
+            vec![
+                semantic::Policy::after(100),
+                semantic::Policy::after(500000001),
+            ],
+        );
+
+        // Descriptors combining heightlocks and timelocks cannot be lifted,
+        // but can be linted
+        let desc = StdDescriptor::from_str(&format!(
+            "elwsh(or_d(pk({}),and_v(v:after(100),after(500000001))))",
+            a
+        ))
+        .unwrap();
+        assert!(desc.lift().is_err());
+        assert_eq!(
+            desc.lint().unwrap(),
+            vec![semantic::Lint::ConflictingTimelocks(dead.clone())]
+        );
+
+        let desc = StdDescriptor::from_str(&format!(
+            "elwsh(and_v(v:pk({}),and_v(v:after(100),after(500000001))))",
+            a
+        ))
+        .unwrap();
+        let dead =
+            semantic::Policy::from_str(&format!("and(pk({}),after(100),after(500000001))", a))
+                .unwrap();
+        assert_eq!(
+            desc.lint().unwrap(),
+            vec![semantic::Lint::ConflictingTimelocks(dead)]
+        );
+
+        // Keys may repeat across separate spend paths
+        let desc = StdDescriptor::from_str(&format!(
+            "eltr({},{{pk({}),and_v(v:pk({}),older(10))}})",
+            a, b, b
+        ))
+        .unwrap();
+        assert!(desc.lint().unwrap().is_empty());
+        let desc = StdDescriptor::from_str(&format!(
+            "elwsh(and_v(v:pk({}),or_d(pk({}),pk({}))))",
+            a, a, b
+        ))
+        .unwrap();
+        assert_eq!(desc.lint().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn try_into_variants() {
+        let pk = "03c6d6a8d6e5b2f2fc1fc3c2c0d8d4e4fcb6dc8fbc8433b3a1d67a1c8b5b9e0b6a";
+        let wsh = StdDescriptor::from_str(&format!("elwsh(pk({}))", pk)).unwrap();
+        let tr = StdDescriptor::from_str(&format!("eltr({})", pk)).unwrap();
+
+        assert_eq!(wsh.clone().try_into_tr(), Err(wsh.clone()));
+        assert_eq!(tr.clone().try_into_wsh(), Err(tr.clone()));
+        assert_eq!(Descriptor::Wsh(wsh.clone().try_into_wsh().unwrap()), wsh);
+        assert_eq!(Descriptor::Tr(tr.clone().try_into_tr().unwrap()), tr);
+        assert!(tr.try_into_tr_ext().is_err());
+    }
+
+    #[test]
+    fn parse_witness() {
+        use elements::hashes::Hash;
+
+        struct PreimageSat([u8; 32]);
+        impl Satisfier<bitcoin::PublicKey> for PreimageSat {
+            fn lookup_sha256(&self, _: &sha256::Hash) -> Option<[u8; 32]> {
+                Some(self.0)
+            }
+        }
+
+        let secp = secp256k1_zkp::Secp256k1::new();
+        let msg = secp256k1_zkp::Message::from_slice(&b"michael was a message, amusingly"[..])
+            .expect("32 bytes");
+        let mut keys = vec![];
+        let mut sigs = HashMap::new();
+        for i in 1..3 {
+            let sk = secp256k1_zkp::SecretKey::from_slice(&[i; 32]).unwrap();
+            let pk = bitcoin::PublicKey {
+                inner: secp256k1_zkp::PublicKey::from_secret_key(&secp, &sk),
+                compressed: true,
+            };
+            let sig = (secp.sign_ecdsa(&msg, &sk), elements::EcdsaSigHashType::All);
+            keys.push(pk);
+            sigs.insert(pk, sig);
+        }
+        let preimage = [7; 32];
+        let hash = sha256::Hash::hash(&preimage);
+        let desc = StdDescriptor::from_str(&format!(
+            "elwsh(or_d(pk({}),and_v(v:pk({}),and_v(v:sha256({}),after(500000001)))))",
+            keys[0], keys[1], hash
+        ))
+        .unwrap();
+
+        let (witness, script_sig) = desc.get_satisfaction(&sigs).unwrap();
+        let parsed = desc.parse_witness(&witness, &script_sig).unwrap();
+        assert_eq!(
+            parsed.signatures,
+            vec![(keys[0], KeySigPair::Ecdsa(keys[0], sigs[&keys[0]]))]
+        );
+        assert!(parsed.preimages.is_empty());
+        assert_eq!(parsed.leaf_hash, None);
+
+        // The time based lock time of the second branch is detected
+        sigs.remove(&keys[0]);
+        let lock_time = elements::LockTime::from_consensus(500_000_001);
+        let (witness, script_sig) = desc
+            .get_satisfaction((&sigs, PreimageSat(preimage), lock_time))
+            .unwrap();
+        let parsed = desc.parse_witness(&witness, &script_sig).unwrap();
+        assert_eq!(
+            parsed.signatures,
+            vec![(keys[1], KeySigPair::Ecdsa(keys[1], sigs[&keys[1]]))]
+        );
+        assert_eq!(
+            parsed.preimages,
+            vec![(HashLockType::Sha256(hash), preimage)]
+        );
+
+        // Witnesses for other descriptors are rejected
+        let other = StdDescriptor::from_str(&format!("elwpkh({})", keys[1])).unwrap();
+        assert!(other.parse_witness(&witness, &script_sig).is_err());
+
+        // Taproot script spends report the executed leaf
+        struct TapSat(elements::SchnorrSig);
+        impl Satisfier<bitcoin::PublicKey> for TapSat {
+            fn lookup_tap_leaf_script_sig(
+                &self,
+                _: &bitcoin::PublicKey,
+                _: &TapLeafHash,
+            ) -> Option<elements::SchnorrSig> {
+                Some(self.0)
+            }
+        }
+        let keypair = secp256k1_zkp::KeyPair::from_seckey_slice(&secp, &[2; 32]).unwrap();
+        let sig = elements::SchnorrSig {
+            sig: secp.sign_schnorr_no_aux_rand(&msg, &keypair),
+            hash_ty: elements::SchnorrSigHashType::Default,
+        };
+        let desc = StdDescriptor::from_str(&format!("eltr({},pk({}))", keys[0], keys[1])).unwrap();
+        let (witness, script_sig) = desc.get_satisfaction(TapSat(sig)).unwrap();
+        let parsed = desc.parse_witness(&witness, &script_sig).unwrap();
+        let leaf =
+            Miniscript::<bitcoin::PublicKey, crate::Tap>::from_str(&format!("pk({})", keys[1]))
+                .unwrap()
+                .encode();
+        assert_eq!(
+            parsed.leaf_hash,
+            Some(TapLeafHash::from_script(&leaf, LeafVersion::default()))
+        );
+        assert_eq!(
+            parsed.signatures,
+            vec![(
+                keys[1],
+                KeySigPair::Schnorr(keys[1].to_x_only_pubkey(), sig)
+            )]
+        );
+    }
+
+    #[test]
+    fn key_weights() {
+        let keys: Vec<PublicKey> = [
+            "03c6d6a8d6e5b2f2fc1fc3c2c0d8d4e4fcb6dc8fbc8433b3a1d67a1c8b5b9e0b6a",
+            "02e6642fd69bd211f93f7f1f36ca51a26a5290eb2dd1b0d8279a87bb0d480c8443",
+            "0384526253c27c7aef56c7b71a5cd25bebb66dddda437826defc5b2568bde81f07",
+        ]
+        .iter()
+        .map(|s| PublicKey::from_str(s).unwrap())
+        .collect();
+        let desc = StdDescriptor::from_str(&format!(
+            "eltr({},multi_a(2,{},{}))",
+            keys[0], keys[1], keys[2]
+        ))
+        .unwrap();
+        let weights = desc.key_weights().unwrap();
+        let key_spend = semantic::KeyWeight {
+            spend_paths: 1,
+            min_quorum: 1,
+        };
+        let script_spend = semantic::KeyWeight {
+            spend_paths: 1,
+            min_quorum: 2,
+        };
+        assert_eq!(weights[&keys[0]], key_spend);
+        assert_eq!(weights[&keys[1]], script_spend);
+        assert_eq!(weights[&keys[2]], script_spend);
+    }
+
+    #[test]
+    fn multipath_descriptors() {
+        let secp = secp256k1::Secp256k1::verification_only();
+        let xpub = "[73c5da0a/84'/0'/0']xpub6BgBgsespWvERF3LHQu6CnqdvfEvtMcQjYrcRzx53QJjSxarj2afYWcLteoGVky7D3UKDP9QyrLprQ3VCECoY49yfdDEHGCtMMj92pReUsQ";
+        let desc = Descriptor::<DescriptorPublicKey>::from_str(&format!(
+            "elwsh(multi(1,{}/<0;1>/*,03daed4f2be3a8bf278e70132fb0beb7522f570e144bf615c07e996d443dee8729))",
+            xpub
+        ))
+        .unwrap();
+        assert!(desc.is_multipath());
+        assert_eq!(
+            Descriptor::<DescriptorPublicKey>::from_str(&desc.to_string()).unwrap(),
+            desc
+        );
+        assert_eq!(
+            desc.derived_descriptor(&secp, 0),
+            Err(ConversionError::MultiKey)
+        );
+
+        // Multipath keys never become definite keys, which would panic when
+        // computing the script pubkey
+        let wpkh =
+            Descriptor::<DescriptorPublicKey>::from_str(&format!("elwpkh({}/<0;1>/*)", xpub))
+                .unwrap();
+        assert_eq!(wpkh.at_derivation_index(0), Err(ConversionError::MultiKey));
+        let receive = &wpkh.clone().into_single_descriptors().unwrap()[0];
+        assert_eq!(
+            receive.at_derivation_index(0).unwrap().script_pubkey(),
+            receive
+                .derived_descriptor(&secp, 0)
+                .unwrap()
+                .script_pubkey()
+        );
+
+        let singles = desc.clone().into_single_descriptors().unwrap();
+        assert_eq!(singles.len(), 2);
+        for (i, single) in singles.iter().enumerate() {
+            assert!(!single.is_multipath());
+            let expected = Descriptor::<DescriptorPublicKey>::from_str(&format!(
+                "elwsh(multi(1,{}/{}/*,03daed4f2be3a8bf278e70132fb0beb7522f570e144bf615c07e996d443dee8729))",
+                xpub, i
+            ))
+            .unwrap();
+            assert_eq!(*single, expected);
+        }
+
+        let single = singles[0].clone();
+        assert_eq!(
+            single.clone().into_single_descriptors().unwrap(),
+            vec![single]
+        );
+
+        let mismatched = Descriptor::<DescriptorPublicKey>::from_str(&format!(
+            "elwsh(multi(1,{}/<0;1>/*,{}/<0;1;2>/*))",
+            xpub, xpub
+        ))
+        .unwrap();
+        assert!(mismatched.into_single_descriptors().is_err());
+    }
+
+    #[test]
+    fn parse_slip132() {
+        let xpub = "xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL";
+        let mut data = bitcoin::util::base58::from_check(xpub).unwrap();
+        data[..4].copy_from_slice(&Slip132Version::Zpub.version_bytes());
+        let zpub = bitcoin::util::base58::check_encode_slice(&data);
+
+        let s = format!("elwsh(multi(1,{}/0/*,{}/1/*))", zpub, xpub);
+        let (desc, keys) = Descriptor::<DescriptorPublicKey>::parse_slip132(&s).unwrap();
+        assert_eq!(
+            desc,
+            Descriptor::<DescriptorPublicKey>::from_str(&s).unwrap()
+        );
+        assert_eq!(
+            keys,
+            vec![(
+                DescriptorPublicKey::from_str(&format!("{}/0/*", xpub)).unwrap(),
+                Slip132Version::Zpub
+            )]
+        );
+        let s = format!("elwpkh({}/0/*)", xpub);
+        let (_, keys) = Descriptor::<DescriptorPublicKey>::parse_slip132(&s).unwrap();
+        assert!(keys.is_empty());
+    }
+
+    #[test]
+    fn from_str_unchecked() {
+        let (a, b) = (
+            "028c28a97bf8298bc0d23d8c749452a32e694b65e30a9472a3954ab30fe5324caa",
+            "03daed4f2be3a8bf278e70132fb0beb7522f570e144bf615c07e996d443dee8729",
+        );
+        for s in &[
+            format!("elwsh(or_d(pk({}),and_v(v:pk({}),older(10))))", a, b),
+            format!("elsh(wsh(multi(1,{},{})))", a, b),
+            format!("elsh(wpkh({}))", a),
+            format!("elwsh(sortedmulti(1,{},{}))", a, b),
+            format!("eltr({},{{pk({}),pk({})}})", a, b, a),
+            format!("eltr({},and_v(v:pk({}),l:ver_eq(2)))", a, b),
+        ] {
+            let desc = StdDescriptor::from_str(s).unwrap();
+            let with_checksum = desc.to_string();
+            let unchecked = StdDescriptor::from_str_unchecked(s).unwrap();
+            assert_eq!(unchecked.as_unchecked(), &desc);
+            assert_eq!(unchecked.validate().unwrap(), desc);
+            assert_eq!(
+                StdDescriptor::from_str_unchecked(&with_checksum)
+                    .unwrap()
+                    .assume_checked(),
+                desc
+            );
+            let bad_checksum = format!("{}#00000000", s);
+            assert!(StdDescriptor::from_str(&bad_checksum).is_err());
+            assert!(StdDescriptor::from_str_unchecked(&bad_checksum).is_err());
+        }
+
+        // Insane descriptors are parsed alike in all contexts, but must
+        // type check
+        for s in &[
+            format!("eltr({},multi_a(1,{},{}))", a, b, b),
+            format!("elwsh(multi(1,{},{}))", b, b),
+            format!("elsh(wsh(multi(1,{},{})))", b, b),
+            format!("elsh(multi(1,{},{}))", b, b),
+            format!("elcovwsh({},multi(1,{},{}))", a, b, b),
+        ] {
+            let unchecked = StdDescriptor::from_str_unchecked(s).unwrap();
+            assert!(unchecked.as_unchecked().sanity_check().is_err());
+            assert!(unchecked.clone().validate().is_err());
+            assert_eq!(
+                unchecked.assume_checked().to_string().split('#').next(),
+                Some(&s[..])
+            );
+        }
+        assert!(StdDescriptor::from_str_unchecked(&format!("eltr({},v:pk({}))", a, b)).is_err());
+        assert!(StdDescriptor::from_str_unchecked(&format!("elwsh(v:pk({}))", a)).is_err());
+
+        let ms =
+            Miniscript::<bitcoin::PublicKey, Segwitv0>::from_str(&format!("pk({})", a)).unwrap();
+        let rebuilt = Miniscript::from_components_unchecked(ms.node.clone(), ms.ty, ms.ext);
+        assert_eq!(rebuilt, ms);
+        assert_eq!(rebuilt.encode(), ms.encode());
+    }
+
+    #[test]
+    fn spend_info() {
+        let pk = "020000000000000000000000000000000000000000000000000000000000000002";
+        let xpk = &pk[2..];
+
+        let desc = StdDescriptor::from_str(&format!("elsh(wsh(pk({})))", pk)).unwrap();
+        let info = desc.spend_info();
+        assert_eq!(info.script_pubkey, desc.script_pubkey());
+        assert_eq!(info.witness_script, Some(desc.explicit_script().unwrap()));
+        assert_eq!(
+            info.redeem_script,
+            Some(desc.explicit_script().unwrap().to_v0_p2wsh())
+        );
+        assert_eq!(info.script_code, Some(desc.script_code().unwrap()));
+        assert!(info.taproot.is_none());
+
+        let desc = StdDescriptor::from_str(&format!("elsh(wpkh({}))", pk)).unwrap();
+        let info = desc.spend_info();
+        assert_eq!(info.redeem_script, Some(desc.explicit_script().unwrap()));
+        assert_eq!(info.witness_script, None);
+
+        let desc = StdDescriptor::from_str(&format!("elwpkh({})", pk)).unwrap();
+        let info = desc.spend_info();
+        assert_eq!((info.redeem_script, info.witness_script), (None, None));
+        assert_eq!(info.script_code, Some(desc.script_code().unwrap()));
+
+        // Taproot descriptors have no script code, but spend info
+        let desc =
+            Descriptor::<bitcoin::XOnlyPublicKey>::from_str(&format!("eltr({},pk({}))", xpk, xpk))
+                .unwrap();
+        let info = desc.spend_info();
+        assert!(desc.script_code().is_err());
+        assert_eq!(info.script_code, None);
+        assert_eq!(
+            info.script_pubkey,
+            Script::new_v1_p2tr_tweaked(info.taproot.unwrap().output_key())
+        );
+    }
+
+    #[test]
+    fn musig_keys() {
+        let secp = secp256k1_zkp::Secp256k1::verification_only();
+        let x = [
+            "02f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9",
+            "03dff1d77f2a671c5f36183726db2341be58feae1da2deced843240f7b502ba659",
+            "023590a94e768f8e1815c2f24b4d80a8e3149316c3518ce7b7ad338368d038ca66",
+        ];
+        let pks: Vec<_> = x
+            .iter()
+            .map(|pk| PublicKey::from_str(pk).unwrap())
+            .collect();
+        let agg = |keys: &[PublicKey]| aggregate_keys(&secp, keys).to_x_only_pubkey();
+
+        let s = format!(
+            "eltr(musig({},{}),pk(musig({},{})))",
+            x[0], x[1], x[1], x[2]
+        );
+        let desc = Descriptor::<DescriptorPublicKey>::from_str(&s).unwrap();
+        assert_eq!(
+            desc.to_string(),
+            format!("{}#{}", s, desc_checksum(&s).unwrap())
+        );
+        let expected = Descriptor::<bitcoin::XOnlyPublicKey>::from_str(&format!(
+            "eltr({},pk({}))",
+            agg(&pks[..2]),
+            agg(&pks[1..])
+        ))
+        .unwrap();
+        assert_eq!(
+            desc.derived_descriptor(&secp, 0).unwrap().script_pubkey(),
+            expected.script_pubkey()
+        );
+
+        // Only valid in taproot, not nested and for compressed keys
+        for s in &[
+            format!("elwsh(pk(musig({},{})))", x[0], x[1]),
+            format!("elwpkh(musig({},{}))", x[0], x[1]),
+            format!("eltr(musig({},musig({},{})))", x[0], x[1], x[2]),
+            format!("eltr(musig({},{}))", x[0], &x[1][2..]),
+            format!("eltr(musig({},{})/0/*)", x[0], x[1]),
+            "eltr(musig())".to_string(),
+        ] {
+            assert!(
+                Descriptor::<DescriptorPublicKey>::from_str(s).is_err(),
+                "{}",
+                s
+            );
+        }
+
+        // The keys are derived first and then aggregated
+        let xpub = "tpubD6NzVbkrYhZ4XHndKkuB8FifXm8r5FQHwrN6oZuWCz13qb93rtgKvD4PQsqC4HP4yhV3tA2fqr2RbY5mNXfM7RxXUoeABoDtsFUq2zJq6YK";
+        let desc = Descriptor::<DescriptorPublicKey>::from_str(&format!(
+            "eltr(musig({}/<0;1>/*,{}))",
+            xpub, x[0]
+        ))
+        .unwrap();
+        assert!(desc.has_wildcard() && desc.is_multipath());
+        let descs = desc.into_single_descriptors().unwrap();
+        assert_eq!(
+            descs[1].to_string().split('#').next().unwrap(),
+            format!("eltr(musig({}/1/*,{}))", xpub, x[0])
+        );
+        let derived = bip32::ExtendedPubKey::from_str(xpub)
+            .unwrap()
+            .derive_pub(&secp, &bip32::DerivationPath::from_str("m/1/7").unwrap())
+            .unwrap();
+        let expected = Descriptor::<bitcoin::XOnlyPublicKey>::from_str(&format!(
+            "eltr({})",
+            agg(&[PublicKey::new(derived.public_key), pks[0]])
+        ))
+        .unwrap();
+        assert_eq!(
+            descs[1]
+                .derived_descriptor(&secp, 7)
+                .unwrap()
+                .script_pubkey(),
+            expected.script_pubkey()
+        );
+        assert_eq!(
+            descs[1].derivation_cache(&secp).script_pubkey(7).unwrap(),
+            &expected.script_pubkey()
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn descriptor_serde() {
+        let s = "elwsh(or_d(pk(028c28a97bf8298bc0d23d8c749452a32e694b65e30a9472a3954ab30fe5324caa),and_v(v:pk(03daed4f2be3a8bf278e70132fb0beb7522f570e144bf615c07e996d443dee8729),older(10))))";
+        let desc = StdDescriptor::from_str(s).unwrap();
+        let json = serde_json::to_value(&desc).unwrap();
+        assert_eq!(json, serde_json::json!(desc.to_string()));
+        assert_eq!(serde_json::from_value::<StdDescriptor>(json).unwrap(), desc);
+        // The checksum is verified, and optional
+        let json = serde_json::json!(format!("{}#00000000", s));
+        assert!(serde_json::from_value::<StdDescriptor>(json).is_err());
+        let json = serde_json::json!(s);
+        assert_eq!(serde_json::from_value::<StdDescriptor>(json).unwrap(), desc);
+
+        let ct = ConfidentialDescriptor::<DescriptorPublicKey>::from_str(&format!(
+            "ct(slip77(b2396b3ee20509cdb64fe24180a14a72dbd671728eaa49bac69d2bdecb5f5a04),{})",
+            s
+        ))
+        .unwrap();
+        let json = serde_json::to_value(&ct).unwrap();
+        assert_eq!(json, serde_json::json!(ct.to_string()));
+        assert_eq!(
+            serde_json::from_value::<ConfidentialDescriptor<DescriptorPublicKey>>(json).unwrap(),
+            ct
+        );
+    }
+}
+
+#[cfg(all(test, feature = "unstable"))]
+mod benches {
+    use std::str::FromStr;
+
+    use test::{black_box, Bencher};
+
+    use super::Descriptor;
+    use crate::DescriptorPublicKey;
+
+    const DESC: &str = "eltr([73c5da0a/86'/0'/0']xpub6BgBgsespWvERF3LHQu6CnqdvfEvtMcQjYrcRzx53QJjSxarj2afYWcLteoGVky7D3UKDP9QyrLprQ3VCECoY49yfdDEHGCtMMj92pReUsQ/0/*,{{multi_a(2,028c28a97bf8298bc0d23d8c749452a32e694b65e30a9472a3954ab30fe5324caa,03daed4f2be3a8bf278e70132fb0beb7522f570e144bf615c07e996d443dee8729),and_v(v:pk(028c28a97bf8298bc0d23d8c749452a32e694b65e30a9472a3954ab30fe5324caa),older(144))},and_v(v:pk(03daed4f2be3a8bf278e70132fb0beb7522f570e144bf615c07e996d443dee8729),after(500000))})";
+
+    #[bench]
+    pub fn parse_desc_checked(bh: &mut Bencher) {
+        bh.iter(|| {
+            let desc = Descriptor::<DescriptorPublicKey>::from_str(DESC);
+            black_box(desc).unwrap();
+        });
+    }
+
+    #[bench]
+    pub fn parse_desc_unchecked(bh: &mut Bencher) {
+        bh.iter(|| {
+            let desc = Descriptor::<DescriptorPublicKey>::from_str_unchecked(DESC);
+            black_box(desc).unwrap();
+        });
+    }
 }
@@ -0,0 +1,472 @@
+// Miniscript
+// Written in 2023 by
+//     The Elements Miniscript developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # MuSig2 Key Aggregation
+//!
+//! The `KeyAgg`, `ApplyTweak` and `PartialSigAgg` algorithms of BIP-327,
+//! used for the `musig(KEY,KEY,..)` keys of BIP-390 and to aggregate the
+//! partial signatures of their signers. The Rust bindings of secp256k1-zkp
+//! do not expose its MuSig2 module, so these are implemented here on top of
+//! its point and scalar arithmetic, following the session API of the C
+//! library: a [`MuSigKeyAgg`] is the key aggregation cache, and a
+//! [`MuSigSession`] is the processed aggregate nonce.
+//!
+//! Nonce generation and partial signing are left to the signers.
+//!
+
+use elements::hashes::{sha256, Hash, HashEngine};
+use elements::secp256k1_zkp::{
+    schnorr, Message, Parity, PublicKey, Scalar, Secp256k1, SecretKey, Verification, XOnlyPublicKey,
+};
+use elements::taproot::{TapBranchHash, TapTweakHash};
+
+// The BIP-340 tagged hash of `data`
+fn tagged_hash(tag: &str, data: &[&[u8]]) -> [u8; 32] {
+    let tag = sha256::Hash::hash(tag.as_bytes());
+    let mut engine = sha256::Hash::engine();
+    engine.input(&tag[..]);
+    engine.input(&tag[..]);
+    for d in data {
+        engine.input(d);
+    }
+    sha256::Hash::from_engine(engine).into_inner()
+}
+
+// The order of the curve
+const CURVE_ORDER: [u8; 32] = [
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xfe,
+    0xba, 0xae, 0xdc, 0xe6, 0xaf, 0x48, 0xa0, 0x3b, 0xbf, 0xd2, 0x5e, 0x8c, 0xd0, 0x36, 0x41, 0x41,
+];
+
+// The generator of the curve
+const GENERATOR: [u8; 33] = [
+    0x02, 0x79, 0xbe, 0x66, 0x7e, 0xf9, 0xdc, 0xbb, 0xac, 0x55, 0xa0, 0x62, 0x95, 0xce, 0x87, 0x0b,
+    0x07, 0x02, 0x9b, 0xfc, 0xdb, 0x2d, 0xce, 0x28, 0xd9, 0x59, 0xf2, 0x81, 0x5b, 0x16, 0xf8, 0x17,
+    0x98,
+];
+
+// The integer `x` modulo the curve order, for hash values
+fn reduce(mut x: [u8; 32]) -> [u8; 32] {
+    if Scalar::from_be_bytes(x).is_ok() {
+        return x;
+    }
+    // 2^256 < 2n, so a single subtraction is enough
+    let mut borrow = 0u16;
+    for i in (0..32).rev() {
+        let diff = 0x100 + u16::from(x[i]) - u16::from(CURVE_ORDER[i]) - borrow;
+        x[i] = diff as u8;
+        borrow = 1 - (diff >> 8);
+    }
+    x
+}
+
+// Arithmetic modulo the curve order on scalars that may be zero, which
+// secret keys cannot be
+fn scalar_add(a: [u8; 32], b: [u8; 32]) -> [u8; 32] {
+    match (SecretKey::from_slice(&a), Scalar::from_be_bytes(b)) {
+        (Err(_), _) => b,
+        (Ok(a), Ok(b)) => a.add_tweak(&b).map(|s| s.secret_bytes()).unwrap_or([0; 32]),
+        (Ok(_), Err(_)) => unreachable!("scalars are reduced"),
+    }
+}
+
+fn scalar_mul(a: [u8; 32], b: [u8; 32]) -> [u8; 32] {
+    match (SecretKey::from_slice(&a), Scalar::from_be_bytes(b)) {
+        (Ok(a), Ok(b)) => a.mul_tweak(&b).map(|s| s.secret_bytes()).unwrap_or([0; 32]),
+        _ => [0; 32],
+    }
+}
+
+fn scalar_negate(a: [u8; 32]) -> [u8; 32] {
+    SecretKey::from_slice(&a)
+        .map(|s| s.negate().secret_bytes())
+        .unwrap_or([0; 32])
+}
+
+/// Aggregate the keys of a `musig()` expression into the MuSig2 aggregate
+/// key. As required by BIP-390, the keys are sorted first, so their order
+/// does not matter.
+///
+/// Taproot only uses the x coordinate of the result, see
+/// [`bitcoin::PublicKey::to_x_only_pubkey`](crate::ToPublicKey::to_x_only_pubkey).
+///
+/// # Panics
+///
+/// If `keys` is empty.
+pub fn aggregate_keys<C: Verification>(
+    secp: &Secp256k1<C>,
+    keys: &[bitcoin::PublicKey],
+) -> bitcoin::PublicKey {
+    bitcoin::PublicKey::new(MuSigKeyAgg::new(secp, keys).aggregate_key())
+}
+
+/// The key aggregation context of BIP-327: the aggregate key of the signers
+/// with the tweaks applied to it so far, and the accumulated tweak that
+/// [`MuSigSession::aggregate`] adds to the signature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MuSigKeyAgg {
+    key: PublicKey,
+    tacc: [u8; 32],
+}
+
+impl MuSigKeyAgg {
+    /// Aggregate `keys` sorted as for `musig()` keys, see [`aggregate_keys`].
+    ///
+    /// # Panics
+    ///
+    /// If `keys` is empty.
+    pub fn new<C: Verification>(secp: &Secp256k1<C>, keys: &[bitcoin::PublicKey]) -> Self {
+        let mut keys: Vec<PublicKey> = keys.iter().map(|pk| pk.inner).collect();
+        keys.sort_by_key(|pk| pk.serialize());
+        MuSigKeyAgg::from_ordered_keys(secp, &keys)
+    }
+
+    /// Aggregate `keys` in the given order, as `KeyAgg` of BIP-327.
+    ///
+    /// # Panics
+    ///
+    /// If `keys` is empty.
+    pub fn from_ordered_keys<C: Verification>(secp: &Secp256k1<C>, keys: &[PublicKey]) -> Self {
+        let serialized: Vec<[u8; 33]> = keys.iter().map(PublicKey::serialize).collect();
+        let list: Vec<&[u8]> = serialized.iter().map(|pk| &pk[..]).collect();
+        let list_hash = tagged_hash("KeyAgg list", &list);
+        let second = serialized.iter().find(|pk| **pk != serialized[0]);
+
+        let mut points = Vec::with_capacity(keys.len());
+        for (pk, ser) in keys.iter().zip(&serialized) {
+            if Some(ser) == second {
+                points.push(*pk);
+            } else {
+                let coeff = reduce(tagged_hash("KeyAgg coefficient", &[&list_hash, ser]));
+                let coeff = Scalar::from_be_bytes(coeff).expect("reduced");
+                points.push(pk.mul_tweak(secp, &coeff).expect("coefficient is not zero"));
+            }
+        }
+        let points: Vec<&PublicKey> = points.iter().collect();
+        MuSigKeyAgg {
+            // The sum can only be infinity if the hashes are broken
+            key: PublicKey::combine_keys(&points).expect("aggregate key is not infinity"),
+            tacc: [0; 32],
+        }
+    }
+
+    /// The aggregate key with the tweaks applied
+    pub fn aggregate_key(&self) -> PublicKey {
+        self.key
+    }
+
+    /// Add `tweak` times the generator to the aggregate key, as `ApplyTweak`
+    /// of BIP-327. An x-only tweak is added to the key with even y, as in
+    /// taproot. Returns `None` if `tweak` is not a scalar or the result is
+    /// the point at infinity.
+    pub fn apply_tweak<C: Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        tweak: [u8; 32],
+        x_only: bool,
+    ) -> Option<Self> {
+        let scalar = Scalar::from_be_bytes(tweak).ok()?;
+        let odd = self.key.x_only_public_key().1 == Parity::Odd;
+        let (key, tacc) = if x_only && odd {
+            (self.key.negate(secp), scalar_negate(self.tacc))
+        } else {
+            (self.key, self.tacc)
+        };
+        Some(MuSigKeyAgg {
+            key: key.add_exp_tweak(secp, &scalar).ok()?,
+            tacc: scalar_add(tweak, tacc),
+        })
+    }
+
+    /// Apply the taproot tweak of the output key of a taproot output with
+    /// this internal key and `merkle_root`, for key spends.
+    pub fn tap_tweak<C: Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        merkle_root: Option<TapBranchHash>,
+    ) -> Self {
+        let internal_key = self.key.x_only_public_key().0;
+        let tweak = TapTweakHash::from_key_and_tweak(internal_key, merkle_root);
+        self.apply_tweak(secp, tweak.into_inner(), true)
+            .expect("taproot tweaks are valid")
+    }
+}
+
+/// A MuSig2 signing session of BIP-327, from the aggregate of the public
+/// nonces of the signers, the key aggregation context and the message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MuSigSession {
+    key_agg: MuSigKeyAgg,
+    nonce: XOnlyPublicKey,
+    msg: Message,
+}
+
+impl MuSigSession {
+    /// Process the aggregate nonce `aggnonce`, the concatenation of the two
+    /// compressed points of `NonceAgg` of BIP-327 where the point at
+    /// infinity is 33 zero bytes. Returns `None` if `aggnonce` does not
+    /// encode two points.
+    pub fn new<C: Verification>(
+        secp: &Secp256k1<C>,
+        key_agg: MuSigKeyAgg,
+        aggnonce: &[u8; 66],
+        msg: Message,
+    ) -> Option<Self> {
+        let point = |bytes: &[u8]| {
+            if bytes == [0; 33] {
+                Some(None)
+            } else {
+                PublicKey::from_slice(bytes).ok().map(Some)
+            }
+        };
+        let (r1, r2) = (point(&aggnonce[..33])?, point(&aggnonce[33..])?);
+        let key = key_agg.key.x_only_public_key().0.serialize();
+        let b = reduce(tagged_hash(
+            "MuSig/noncecoef",
+            &[aggnonce, &key, msg.as_ref()],
+        ));
+        let b = Scalar::from_be_bytes(b).expect("reduced");
+        let r2 = r2.and_then(|r2| r2.mul_tweak(secp, &b).ok());
+        let points: Vec<&PublicKey> = r1.iter().chain(r2.iter()).collect();
+        // The final nonce is the generator if the sum is infinity
+        let nonce = PublicKey::combine_keys(&points)
+            .unwrap_or_else(|_| PublicKey::from_slice(&GENERATOR).expect("valid point"));
+        Some(MuSigSession {
+            key_agg,
+            nonce: nonce.x_only_public_key().0,
+            msg,
+        })
+    }
+
+    /// The key aggregation context of the session
+    pub fn key_agg(&self) -> &MuSigKeyAgg {
+        &self.key_agg
+    }
+
+    /// The final nonce of the session, the `R` of the signature
+    pub fn final_nonce(&self) -> XOnlyPublicKey {
+        self.nonce
+    }
+
+    /// The message signed in the session
+    pub fn message(&self) -> Message {
+        self.msg
+    }
+
+    /// Aggregate the partial signatures of all signers into a BIP-340
+    /// signature, as `PartialSigAgg` of BIP-327: their sum plus the
+    /// challenge times the accumulated tweak. Returns `None` if a partial
+    /// signature is not a scalar, or if the signature does not verify
+    /// against the tweaked aggregate key.
+    pub fn aggregate<C: Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        partial_sigs: &[[u8; 32]],
+    ) -> Option<schnorr::Signature> {
+        let (key, parity) = self.key_agg.key.x_only_public_key();
+        let e = reduce(tagged_hash(
+            "BIP0340/challenge",
+            &[&self.nonce.serialize(), &key.serialize(), self.msg.as_ref()],
+        ));
+        let mut tacc = self.key_agg.tacc;
+        if parity == Parity::Odd {
+            tacc = scalar_negate(tacc);
+        }
+        let mut s = scalar_mul(e, tacc);
+        for psig in partial_sigs {
+            Scalar::from_be_bytes(*psig).ok()?;
+            s = scalar_add(*psig, s);
+        }
+        let mut bytes = [0; 64];
+        bytes[..32].copy_from_slice(&self.nonce.serialize());
+        bytes[32..].copy_from_slice(&s);
+        let sig = schnorr::Signature::from_slice(&bytes).ok()?;
+        secp.verify_schnorr(&sig, &self.msg, &key).ok()?;
+        Some(sig)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn key_agg_vectors() {
+        // From the key aggregation vectors of BIP-327
+        let secp = Secp256k1::verification_only();
+        let x = [
+            "02F9308A019258C31049344F85F89D5229B531C845836F99B08601F113BCE036F9",
+            "03DFF1D77F2A671C5F36183726DB2341BE58FEAE1DA2DECED843240F7B502BA659",
+            "023590A94E768F8E1815C2F24B4D80A8E3149316C3518CE7B7AD338368D038CA66",
+        ];
+        let x: Vec<_> = x
+            .iter()
+            .map(|pk| PublicKey::from_str(pk).unwrap())
+            .collect();
+        let agg = |indices: &[usize]| {
+            let keys: Vec<_> = indices.iter().map(|&i| x[i]).collect();
+            MuSigKeyAgg::from_ordered_keys(&secp, &keys)
+                .aggregate_key()
+                .x_only_public_key()
+                .0
+                .to_string()
+        };
+        assert_eq!(
+            agg(&[0, 1, 2]),
+            "90539eede565f5d054f32cc0c220126889ed1e5d193baf15aef344fe59d4610c"
+        );
+        assert_eq!(
+            agg(&[2, 1, 0]),
+            "6204de8b083426dc6eaf9502d27024d53fc826bf7d2012148a0575435df54b2b"
+        );
+        assert_eq!(
+            agg(&[0, 0, 0]),
+            "b436e3bad62b8cd409969a224731c193d051162d8c5ae8b109306127da3aa935"
+        );
+        assert_eq!(
+            agg(&[0, 0, 1, 1]),
+            "69bc22bfa5d106306e48a20679de1d7389386124d07571d0d872686028c26a3e"
+        );
+
+        // The taproot tweak gives the output key of elements taproot outputs
+        let key_agg = MuSigKeyAgg::from_ordered_keys(&secp, &x);
+        let internal_key = key_agg.aggregate_key().x_only_public_key().0;
+        let merkle_root = TapBranchHash::from_inner([1; 32]);
+        for &root in &[None, Some(merkle_root)] {
+            let output_key = elements::schnorr::TapTweak::tap_tweak(internal_key, &secp, root).0;
+            assert_eq!(
+                key_agg
+                    .tap_tweak(&secp, root)
+                    .aggregate_key()
+                    .x_only_public_key()
+                    .0,
+                output_key.into_inner()
+            );
+        }
+
+        // Sorting makes the order irrelevant
+        let keys: Vec<_> = x.iter().map(|pk| bitcoin::PublicKey::new(*pk)).collect();
+        let reversed: Vec<_> = keys.iter().rev().cloned().collect();
+        assert_eq!(
+            aggregate_keys(&secp, &keys),
+            aggregate_keys(&secp, &reversed)
+        );
+    }
+
+    #[test]
+    fn sig_agg_vectors() {
+        // From the signature aggregation vectors of BIP-327
+        use elements::hashes::hex::FromHex;
+
+        let secp = Secp256k1::verification_only();
+        let hex = |s: &str| Vec::<u8>::from_hex(s).unwrap();
+        let pubkeys: Vec<_> = [
+            "03935F972DA013F80AE011890FA89B67A27B7BE6CCB24D3274D18B2D4067F261A9",
+            "02D2DC6F5DF7C56ACF38C7FA0AE7A759AE30E19B37359DFDE015872324C7EF6E05",
+            "03C7FB101D97FF930ACD0C6760852EF64E69083DE0B06AC6335724754BB4B0522C",
+            "02352433B21E7E05D3B452B81CAE566E06D2E003ECE16D1074AABA4289E0E3D581",
+        ]
+        .iter()
+        .map(|pk| PublicKey::from_str(pk).unwrap())
+        .collect();
+        let tweaks = [
+            "B511DA492182A91B0FFB9A98020D55F260AE86D7ECBD0399C7383D59A5F2AF7C",
+            "A815FE049EE3C5AAB66310477FBC8BCCCAC2F3395F59F921C364ACD78A2F48DC",
+            "75448A87274B056468B977BE06EB1E9F657577B7320B0A3376EA51FD420D18A8",
+        ];
+        let psigs = [
+            "B15D2CD3C3D22B04DAE438CE653F6B4ECF042F42CFDED7C41B64AAF9B4AF53FB",
+            "6193D6AC61B354E9105BBDC8937A3454A6D705B6D57322A5A472A02CE99FCB64",
+            "9A87D3B79EC67228CB97878B76049B15DBD05B8158D17B5B9114D3C226887505",
+            "66F82EA90923689B855D36C6B7E032FB9970301481B99E01CDB4D6AC7C347A15",
+            "4F5AEE41510848A6447DCD1BBC78457EF69024944C87F40250D3EF2C25D33EFE",
+            "DDEF427BBB847CC027BEFF4EDB01038148917832253EBC355FC33F4A8E2FCCE4",
+            "97B890A26C981DA8102D3BC294159D171D72810FDF7C6A691DEF02F0F7AF3FDC",
+            "53FA9E08BA5243CBCB0D797C5EE83BC6728E539EB76C2D0BF0F971EE4E909971",
+            "FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFEBAAEDCE6AF48A03BBFD25E8CD0364141",
+        ];
+        let msg = Message::from_slice(&hex(
+            "599C67EA410D005B9DA90817CF03ED3B1C868E4DA4EDF00A5880B0082C237869",
+        ))
+        .unwrap();
+
+        let aggregate =
+            |keys: &[usize], tweaked: &[(usize, bool)], aggnonce: &str, sigs: &[usize]| {
+                let keys: Vec<_> = keys.iter().map(|&i| pubkeys[i]).collect();
+                let mut key_agg = MuSigKeyAgg::from_ordered_keys(&secp, &keys);
+                for &(i, x_only) in tweaked {
+                    let mut tweak = [0; 32];
+                    tweak.copy_from_slice(&hex(tweaks[i]));
+                    key_agg = key_agg.apply_tweak(&secp, tweak, x_only).unwrap();
+                }
+                let mut nonce = [0; 66];
+                nonce.copy_from_slice(&hex(aggnonce));
+                let session = MuSigSession::new(&secp, key_agg, &nonce, msg).unwrap();
+                let sigs: Vec<[u8; 32]> = sigs
+                    .iter()
+                    .map(|&i| {
+                        let mut sig = [0; 32];
+                        sig.copy_from_slice(&hex(psigs[i]));
+                        sig
+                    })
+                    .collect();
+                session
+                    .aggregate(&secp, &sigs)
+                    .map(|sig| elements::hashes::hex::ToHex::to_hex(&sig[..]).to_uppercase())
+            };
+
+        assert_eq!(
+            aggregate(
+                &[0, 1],
+                &[],
+                "0341432722C5CD0268D829C702CF0D1CBCE57033EED201FD335191385227C3210C03D377F2D258B64AADC0E16F26462323D701D286046A2EA93365656AFD9875982B",
+                &[0, 1]
+            )
+            .unwrap(),
+            "041DA22223CE65C92C9A0D6C2CAC828AAF1EEE56304FEC371DDF91EBB2B9EF0912F1038025857FEDEB3FF696F8B99FA4BB2C5812F6095A2E0004EC99CE18DE1E"
+        );
+        assert_eq!(
+            aggregate(
+                &[0, 2],
+                &[],
+                "0224AFD36C902084058B51B5D36676BBA4DC97C775873768E58822F87FE437D792028CB15929099EEE2F5DAE404CD39357591BA32E9AF4E162B8D3E7CB5EFE31CB20",
+                &[2, 3]
+            )
+            .unwrap(),
+            "1069B67EC3D2F3C7C08291ACCB17A9C9B8F2819A52EB5DF8726E17E7D6B52E9F01800260A7E9DAC450F4BE522DE4CE12BA91AEAF2B4279219EF74BE1D286ADD9"
+        );
+        assert_eq!(
+            aggregate(
+                &[0, 2],
+                &[(0, false)],
+                "0208C5C438C710F4F96A61E9FF3C37758814B8C3AE12BFEA0ED2C87FF6954FF186020B1816EA104B4FCA2D304D733E0E19CEAD51303FF6420BFD222335CAA402916D",
+                &[4, 5]
+            )
+            .unwrap(),
+            "5C558E1DCADE86DA0B2F02626A512E30A22CF5255CAEA7EE32C38E9A71A0E9148BA6C0E6EC7683B64220F0298696F1B878CD47B107B81F7188812D593971E0CC"
+        );
+        let tweaked = [(0, true), (1, false), (2, true)];
+        let aggnonce = "02B5AD07AFCD99B6D92CB433FBD2A28FDEB98EAE2EB09B6014EF0F8197CD58403302E8616910F9293CF692C49F351DB86B25E352901F0E237BAFDA11F1C1CEF29FFD";
+        assert_eq!(
+            aggregate(&[0, 3], &tweaked, aggnonce, &[6, 7]).unwrap(),
+            "839B08820B681DBA8DAF4CC7B104E8F2638F9388F8D7A555DC17B6E6971D7426CE07BF6AB01F1DB50E4E33719295F4094572B79868E440FB3DEFD3FAC1DB589E"
+        );
+        // The second partial signature exceeds the group size
+        assert_eq!(aggregate(&[0, 3], &tweaked, aggnonce, &[7, 8]), None);
+        // Without the tweak the signature does not verify
+        assert_eq!(aggregate(&[0, 3], &tweaked[..2], aggnonce, &[6, 7]), None);
+    }
+}
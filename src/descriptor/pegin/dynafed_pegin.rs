@@ -22,18 +22,18 @@
 
 use std::fmt;
 
-use bitcoin::blockdata::script;
-use bitcoin::hashes::Hash;
+use bitcoin::hashes::{hash160, ripemd160};
 use bitcoin::{self, hashes, Script as BtcScript};
+use bitcoin_miniscript::TranslatePk as BtcTranslatePk;
 use elements::secp256k1_zkp;
 
 use crate::descriptor::checksum::{desc_checksum, verify_checksum};
-use crate::expression::{self, FromTree};
+use crate::expression;
 use crate::extensions::{CovExtArgs, CovenantExt};
 use crate::policy::{semantic, Liftable};
 use crate::{
-    BtcDescriptor, BtcError, BtcFromTree, BtcLiftable, BtcPolicy, BtcSatisfier, BtcTree,
-    Descriptor, Error, MiniscriptKey, ToPublicKey,
+    hash256, tweak_key, BtcDescriptor, BtcError, BtcFromTree, BtcLiftable, BtcPolicy, BtcSatisfier,
+    BtcTree, Descriptor, Error, ForEachKey, MiniscriptKey, Satisfier, ToPublicKey,
 };
 
 /// New Pegin Descriptor with Miniscript support
@@ -69,7 +69,7 @@ impl<Pk: MiniscriptKey> fmt::Debug for Pegin<Pk> {
 
 impl<Pk: MiniscriptKey> fmt::Display for Pegin<Pk> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let desc = format!("pegin({},{})", self.fed_desc, self.elem_desc);
+        let desc = format!("pegin({:#},{:#})", self.fed_desc, self.elem_desc);
         let checksum = desc_checksum(&desc).map_err(|_| fmt::Error)?;
         write!(f, "{}#{}", &desc, &checksum)
     }
@@ -88,13 +88,24 @@ impl<Pk: MiniscriptKey> BtcLiftable<Pk> for Pegin<Pk> {
     }
 }
 
+impl<Pk: MiniscriptKey> ForEachKey<Pk> for Pegin<Pk> {
+    /// Run a predicate on the keys of the federation descriptor, then on
+    /// the keys of the redeem descriptor
+    fn for_each_key<'a, F: FnMut(&'a Pk) -> bool>(&'a self, mut pred: F) -> bool
+    where
+        Pk: 'a,
+    {
+        self.fed_desc.for_each_key(&mut pred) && self.elem_desc.for_each_key(pred)
+    }
+}
+
 impl_from_tree!(
     Pegin<Pk>,
     fn from_tree(top: &expression::Tree<'_>) -> Result<Self, Error> {
         if top.name == "pegin" && top.args.len() == 2 {
             // a roundtrip hack to use FromTree from bitcoin::Miniscript from
             // expression::Tree in elements.
-            let ms_str = top.args[0].to_string();
+            let ms_str = super::tree_to_string(&top.args[0]);
             let ms_expr = BtcTree::from_str(&ms_str)?;
             //
             // TODO: Confirm with Andrew about the descriptor type for dynafed
@@ -117,8 +128,10 @@ impl_from_str!(
     type Err = Error;,
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let desc_str = verify_checksum(s)?;
-        let top = expression::Tree::from_str(desc_str)?;
-        Self::from_tree(&top)
+        let (fed_str, elem_str) = super::split_pegin_args(desc_str, "pegin")?;
+        let fed_desc = BtcDescriptor::<Pk>::from_str(fed_str)?;
+        let elem_desc = Descriptor::<Pk, CovenantExt<CovExtArgs>>::from_str(elem_str)?;
+        Ok(Pegin::new(fed_desc, elem_desc))
     }
 );
 
@@ -134,6 +147,63 @@ impl<Pk: MiniscriptKey> Pegin<Pk> {
         Ok(())
     }
 
+    /// The script pubkey of the elements output claiming the pegin, which
+    /// the keys of the federation descriptor are tweaked with
+    pub fn claim_script(&self) -> elements::Script
+    where
+        Pk: ToPublicKey,
+    {
+        self.elem_desc.script_pubkey()
+    }
+
+    // The federation descriptor with every key tweaked by the claim script
+    fn tweaked_fed_desc<C: secp256k1_zkp::Verification>(
+        &self,
+        secp: &secp256k1_zkp::Secp256k1<C>,
+    ) -> BtcDescriptor<bitcoin::PublicKey>
+    where
+        Pk: ToPublicKey,
+    {
+        struct TranslateTweak<'a, C: secp256k1_zkp::Verification> {
+            secp: &'a secp256k1_zkp::Secp256k1<C>,
+            contract: Vec<u8>,
+        }
+
+        impl<'a, Pk, C> bitcoin_miniscript::Translator<Pk, bitcoin::PublicKey, ()> for TranslateTweak<'a, C>
+        where
+            Pk: MiniscriptKey + ToPublicKey,
+            C: secp256k1_zkp::Verification,
+        {
+            fn pk(&mut self, pk: &Pk) -> Result<bitcoin::PublicKey, ()> {
+                Ok(tweak_key(pk, self.secp, &self.contract))
+            }
+
+            fn sha256(&mut self, sha256: &Pk::Sha256) -> Result<hashes::sha256::Hash, ()> {
+                Ok(Pk::to_sha256(sha256))
+            }
+
+            fn hash256(&mut self, hash256: &Pk::Hash256) -> Result<hash256::Hash, ()> {
+                Ok(Pk::to_hash256(hash256))
+            }
+
+            fn ripemd160(&mut self, ripemd160: &Pk::Ripemd160) -> Result<ripemd160::Hash, ()> {
+                Ok(Pk::to_ripemd160(ripemd160))
+            }
+
+            fn hash160(&mut self, hash160: &Pk::Hash160) -> Result<hash160::Hash, ()> {
+                Ok(Pk::to_hash160(hash160))
+            }
+        }
+
+        let mut t = TranslateTweak {
+            secp,
+            contract: self.claim_script().into_bytes(),
+        };
+        self.fed_desc
+            .translate_pk(&mut t)
+            .expect("Tweaking must succeed")
+    }
+
     /// Computes the Bitcoin address of the pegin descriptor, if one exists.
     /// Requires the secp context to compute the tweak
     pub fn bitcoin_address<C: secp256k1_zkp::Verification>(
@@ -144,13 +214,7 @@ impl<Pk: MiniscriptKey> Pegin<Pk> {
     where
         Pk: ToPublicKey,
     {
-        // TODO
-        Ok(bitcoin::Address::p2shwsh(
-            &self
-                .bitcoin_witness_script(secp)
-                .expect("DO this cleanly after TR. Pay to taproot pegins unspecified till now"),
-            network,
-        ))
+        Ok(self.tweaked_fed_desc(secp).address(network)?)
     }
 
     /// Computes the bitcoin scriptpubkey of the descriptor.
@@ -162,9 +226,7 @@ impl<Pk: MiniscriptKey> Pegin<Pk> {
     where
         Pk: ToPublicKey,
     {
-        self.bitcoin_address(bitcoin::Network::Bitcoin, secp)
-            .expect("Address cannot fail for pegin")
-            .script_pubkey()
+        self.tweaked_fed_desc(secp).script_pubkey()
     }
 
     /// Computes the scriptSig that will be in place for an unsigned
@@ -183,12 +245,7 @@ impl<Pk: MiniscriptKey> Pegin<Pk> {
     where
         Pk: ToPublicKey,
     {
-        let witness_script = self
-            .bitcoin_witness_script(secp)
-            .expect("TODO after taproot");
-        script::Builder::new()
-            .push_slice(&witness_script.to_v0_p2wsh()[..])
-            .into_script()
+        self.tweaked_fed_desc(secp).unsigned_script_sig()
     }
 
     /// Computes the bitcoin "witness script" of the descriptor, i.e. the underlying
@@ -197,73 +254,42 @@ impl<Pk: MiniscriptKey> Pegin<Pk> {
     /// for the others it is the witness script.
     pub fn bitcoin_witness_script<C: secp256k1_zkp::Verification>(
         &self,
-        _secp: &secp256k1_zkp::Secp256k1<C>,
+        secp: &secp256k1_zkp::Secp256k1<C>,
     ) -> Result<BtcScript, Error>
     where
         Pk: ToPublicKey,
     {
-        let tweak_vec = self
-            .elem_desc
-            .explicit_script()
-            .expect("Tr pegins unknown yet")
-            .into_bytes();
-        let _tweak = hashes::sha256::Hash::hash(&tweak_vec);
-
-        unreachable!("TODO: After upstream Refactor for Translator trait")
-        // let derived = self.fed_desc.derive
-
-        // struct TranslateTweak<'a, C: secp256k1_zkp::Verification>(
-        //     hashes::sha256::Hash,
-        //     &'a secp256k1_zkp::Secp256k1<C>,
-        // );
-
-        // impl<'a, Pk, C> PkTranslator<Pk, bitcoin::PublicKey, ()> for TranslateTweak<'a, C>
-        // where
-        //     Pk: MiniscriptKey,
-        //     C: secp256k1_zkp::Verification,
-        // {
-        //     fn pk(&mut self, pk: &Pk) -> Result<bitcoin::PublicKey, ()> {
-        //         tweak_key(pk, self.1, self.0.as_inner())
-        //     }
-
-        //     fn pkh(
-        //         &mut self,
-        //         pkh: &<Pk as MiniscriptKey>::Hash,
-        //     ) -> Result<<bitcoin::PublicKey as MiniscriptKey>::Hash, ()> {
-        //         unreachable!("No keyhashes in elements descriptors")
-        //     }
-        // }
-        // let mut t = TranslateTweak(tweak, secp);
-
-        // let tweaked_desc = <bitcoin_miniscript::TranslatePk>::translate_pk(&self.fed_desc, t).expect("Tweaking must succeed"),
-        // Ok(tweaked_desc.explicit_script()?)
+        Ok(self.tweaked_fed_desc(secp).explicit_script()?)
     }
 
     /// Returns satisfying witness and scriptSig to spend an
     /// output controlled by the given descriptor if it possible to
-    /// construct one using the satisfier S.
+    /// construct one using the satisfier S. The satisfier is queried
+    /// for signatures of the tweaked federation keys.
     pub fn get_bitcoin_satisfaction<S, C: secp256k1_zkp::Verification>(
         &self,
-        _secp: &secp256k1_zkp::Secp256k1<C>,
-        _satisfier: S,
+        secp: &secp256k1_zkp::Secp256k1<C>,
+        satisfier: S,
     ) -> Result<(Vec<Vec<u8>>, BtcScript), Error>
     where
         S: BtcSatisfier<bitcoin::PublicKey>,
         Pk: ToPublicKey,
     {
-        let tweak_vec = self
-            .elem_desc
-            .explicit_script()
-            .expect("Tr pegins unknown yet")
-            .into_bytes();
-        let _tweak = hashes::sha256::Hash::hash(&tweak_vec);
-        unreachable!("TODO: After upstream refactor");
-        // let tweaked_desc = self.fed_desc.translate_pk_infallible(
-        //     |pk| tweak_key(pk, secp, tweak.as_inner()),
-        //     |_| unreachable!("No keyhashes in elements descriptors"),
-        // );
-        // let res = tweaked_desc.get_satisfaction(satisfier)?;
-        // Ok(res)
+        Ok(self.tweaked_fed_desc(secp).get_satisfaction(satisfier)?)
+    }
+
+    /// Returns satisfying witness and scriptSig to spend the claim script
+    /// on the elements side, i.e. to claim the pegged-in coins, if it is
+    /// possible to construct one using the satisfier S.
+    pub fn get_claim_satisfaction<S>(
+        &self,
+        satisfier: S,
+    ) -> Result<(Vec<Vec<u8>>, elements::Script), Error>
+    where
+        S: Satisfier<Pk>,
+        Pk: ToPublicKey,
+    {
+        self.elem_desc.get_satisfaction(satisfier)
     }
 
     /// Computes an upper bound on the weight of a satisfying witness to the
@@ -302,3 +328,116 @@ impl<Pk: MiniscriptKey> Pegin<Pk> {
         self.elem_desc
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::str::FromStr;
+
+    use elements::secp256k1_zkp::{Message, Secp256k1, SecretKey};
+
+    use super::*;
+
+    #[test]
+    fn dynafed_pegin() {
+        let secp = Secp256k1::new();
+        let sks: Vec<_> = (1..5u8)
+            .map(|i| SecretKey::from_slice(&[i; 32]).unwrap())
+            .collect();
+        let pks: Vec<_> = sks
+            .iter()
+            .map(|sk| bitcoin::PublicKey::new(secp256k1_zkp::PublicKey::from_secret_key(&secp, sk)))
+            .collect();
+        let pegin = Pegin::<bitcoin::PublicKey>::from_str(&format!(
+            "pegin(sh(wsh(multi(2,{},{},{}))),elwpkh({}))",
+            pks[0], pks[1], pks[2], pks[3]
+        ))
+        .unwrap();
+
+        assert_eq!(
+            Pegin::<bitcoin::PublicKey>::from_str(&pegin.to_string()).unwrap(),
+            pegin
+        );
+
+        let claim_script = pegin.claim_script();
+        assert_eq!(claim_script, pegin.elem_desc.script_pubkey());
+
+        // The federation keys are tweaked with the claim script
+        let tweaked: Vec<_> = pks[..3]
+            .iter()
+            .map(|pk| tweak_key(pk, &secp, claim_script.as_bytes()))
+            .collect();
+        let expected = BtcDescriptor::<bitcoin::PublicKey>::from_str(&format!(
+            "sh(wsh(multi(2,{},{},{})))",
+            tweaked[0], tweaked[1], tweaked[2]
+        ))
+        .unwrap();
+        assert_eq!(
+            pegin.bitcoin_witness_script(&secp).unwrap(),
+            expected.explicit_script().unwrap()
+        );
+        assert_eq!(
+            pegin
+                .bitcoin_address(bitcoin::Network::Bitcoin, &secp)
+                .unwrap(),
+            expected.address(bitcoin::Network::Bitcoin).unwrap()
+        );
+        assert_eq!(pegin.bitcoin_script_pubkey(&secp), expected.script_pubkey());
+        assert_eq!(
+            pegin.bitcoin_unsigned_script_sig(&secp),
+            expected.unsigned_script_sig()
+        );
+
+        // The bitcoin side is signed by the tweaked federation keys
+        let msg = Message::from_slice(&[0xab; 32]).unwrap();
+        let btc_sig = |sk: &SecretKey| bitcoin::EcdsaSig {
+            sig: secp.sign_ecdsa(&msg, sk),
+            hash_ty: bitcoin::EcdsaSighashType::All,
+        };
+        let mut sigs = HashMap::new();
+        sigs.insert(pks[0], btc_sig(&sks[0]));
+        sigs.insert(pks[1], btc_sig(&sks[1]));
+        assert!(pegin.get_bitcoin_satisfaction(&secp, &sigs).is_err());
+        let mut sigs = HashMap::new();
+        sigs.insert(tweaked[0], btc_sig(&sks[0]));
+        sigs.insert(tweaked[1], btc_sig(&sks[1]));
+        let (witness, script_sig) = pegin.get_bitcoin_satisfaction(&secp, &sigs).unwrap();
+        assert_eq!(witness.len(), 4);
+        assert_eq!(
+            witness[3],
+            pegin.bitcoin_witness_script(&secp).unwrap().to_bytes()
+        );
+        assert_eq!(script_sig, pegin.bitcoin_unsigned_script_sig(&secp));
+
+        // The claim is signed by the user key
+        let mut sigs = HashMap::new();
+        sigs.insert(
+            pks[3],
+            (
+                secp.sign_ecdsa(&msg, &sks[3]),
+                elements::EcdsaSigHashType::All,
+            ),
+        );
+        let (witness, script_sig) = pegin.get_claim_satisfaction(&sigs).unwrap();
+        assert_eq!(witness.len(), 2);
+        assert!(script_sig.is_empty());
+
+        let mut keys = vec![];
+        assert!(pegin.for_each_key(|pk| {
+            keys.push(*pk);
+            true
+        }));
+        assert_eq!(keys, pks);
+
+        // Taproot script trees on either side survive a round trip
+        let pegin = Pegin::<bitcoin::PublicKey>::from_str(&format!(
+            "pegin(tr({},{{pk({}),pk({})}}),eltr({},{{pk({}),pk({})}}))",
+            pks[0], pks[1], pks[2], pks[3], pks[0], pks[1]
+        ))
+        .unwrap();
+        assert_eq!(
+            Pegin::<bitcoin::PublicKey>::from_str(&pegin.to_string()).unwrap(),
+            pegin
+        );
+    }
+}
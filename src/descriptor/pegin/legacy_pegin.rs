@@ -33,13 +33,15 @@ use bitcoin_miniscript::TranslatePk as BtcTranslatePk;
 use elements::secp256k1_zkp;
 
 use crate::descriptor::checksum::{desc_checksum, verify_checksum};
-use crate::expression::{self, FromTree};
+use crate::descriptor::federation::WatchmanParams;
+use crate::expression;
 use crate::extensions::{CovExtArgs, CovenantExt};
 use crate::policy::{semantic, Liftable};
 use crate::util::varint_len;
 use crate::{
-    hash256, tweak_key, BtcError, BtcFromTree, BtcLiftable, BtcMiniscript, BtcPolicy, BtcSatisfier,
-    BtcSegwitv0, BtcTerminal, BtcTree, Descriptor, Error, MiniscriptKey, ToPublicKey,
+    hash256, script_num_size, tweak_key, BtcError, BtcFromTree, BtcLiftable, BtcMiniscript,
+    BtcPolicy, BtcSatisfier, BtcSegwitv0, BtcTerminal, BtcTree, Descriptor, Error, ForEachKey,
+    MiniscriptKey, ToPublicKey,
 };
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -127,6 +129,19 @@ pub struct LegacyPegin<Pk: MiniscriptKey> {
     ms: BtcMiniscript<LegacyPeginKey, BtcSegwitv0>,
 }
 
+/// A satisfaction of the bitcoin side of a [`LegacyPegin`], along with the
+/// nSequence that the chosen spend path requires.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct LegacyPeginSatisfaction {
+    /// The witness stack of the spending input
+    pub witness: Vec<Vec<u8>>,
+    /// The scriptSig of the spending input
+    pub script_sig: BtcScript,
+    /// The minimum nSequence of the spending input, if the emergency path
+    /// is used
+    pub required_sequence: Option<bitcoin::Sequence>,
+}
+
 impl<Pk: MiniscriptKey> LegacyPegin<Pk> {
     /// Create a new LegacyPegin descriptor
     pub fn new(
@@ -137,19 +152,28 @@ impl<Pk: MiniscriptKey> LegacyPegin<Pk> {
         timelock: bitcoin::Sequence,
         desc: Descriptor<Pk, CovenantExt<CovExtArgs>>,
     ) -> Self {
-        let fed_ms = BtcMiniscript::from_ast(BtcTerminal::Multi(fed_k, fed_pks.clone()))
-            .expect("Multi type check can't fail");
-        let csv = BtcMiniscript::from_ast(BtcTerminal::Verify(Arc::new(
-            BtcMiniscript::from_ast(BtcTerminal::Older(timelock)).unwrap(),
-        )))
-        .unwrap();
-        let emer_ms = BtcMiniscript::from_ast(BtcTerminal::Multi(emer_k, emer_pks.clone()))
-            .expect("Multi type check can't fail");
-        let emer_ms =
-            BtcMiniscript::from_ast(BtcTerminal::AndV(Arc::new(csv), Arc::new(emer_ms))).unwrap();
-        let ms = BtcMiniscript::from_ast(BtcTerminal::OrD(Arc::new(fed_ms), Arc::new(emer_ms)))
-            .expect("Type check");
-        Self {
+        Self::try_new(fed_pks, fed_k, emer_pks, emer_k, timelock, desc)
+            .expect("Multi type check can't fail")
+    }
+
+    // Internal function building the federation miniscript, failing if
+    // the thresholds or the number of keys are invalid
+    fn try_new(
+        fed_pks: Vec<LegacyPeginKey>,
+        fed_k: usize,
+        emer_pks: Vec<LegacyPeginKey>,
+        emer_k: usize,
+        timelock: bitcoin::Sequence,
+        desc: Descriptor<Pk, CovenantExt<CovExtArgs>>,
+    ) -> Result<Self, Error> {
+        let fed_ms = BtcMiniscript::from_ast(BtcTerminal::Multi(fed_k, fed_pks.clone()))?;
+        let csv = BtcMiniscript::from_ast(BtcTerminal::Verify(Arc::new(BtcMiniscript::from_ast(
+            BtcTerminal::Older(timelock),
+        )?)))?;
+        let emer_ms = BtcMiniscript::from_ast(BtcTerminal::Multi(emer_k, emer_pks.clone()))?;
+        let emer_ms = BtcMiniscript::from_ast(BtcTerminal::AndV(Arc::new(csv), Arc::new(emer_ms)))?;
+        let ms = BtcMiniscript::from_ast(BtcTerminal::OrD(Arc::new(fed_ms), Arc::new(emer_ms)))?;
+        Ok(Self {
             fed_pks,
             fed_k,
             emer_pks,
@@ -157,7 +181,7 @@ impl<Pk: MiniscriptKey> LegacyPegin<Pk> {
             timelock,
             desc,
             ms,
-        }
+        })
     }
 
     // Internal function to set the fields of Self according to
@@ -267,49 +291,67 @@ impl<Pk: MiniscriptKey> LegacyPegin<Pk> {
     }
 
     /// Create a new descriptor with hard coded values for the
-    /// legacy federation and emergency keys
+    /// legacy federation and emergency keys, as [`LegacyPegin::from_watchman`]
+    /// does for the parameters of the Liquid federation
     pub fn new_legacy_fed(user_desc: Descriptor<Pk, CovenantExt<CovExtArgs>>) -> Self {
-        // Taken from functionary codebase
-        // TODO: Verify the keys are correct
-        let pks = "
-                    020e0338c96a8870479f2396c373cc7696ba124e8635d41b0ea581112b67817261,
-                    02675333a4e4b8fb51d9d4e22fa5a8eaced3fdac8a8cbf9be8c030f75712e6af99,
-                    02896807d54bc55c24981f24a453c60ad3e8993d693732288068a23df3d9f50d48,
-                    029e51a5ef5db3137051de8323b001749932f2ff0d34c82e96a2c2461de96ae56c,
-                    02a4e1a9638d46923272c266631d94d36bdb03a64ee0e14c7518e49d2f29bc4010,
-                    02f8a00b269f8c5e59c67d36db3cdc11b11b21f64b4bffb2815e9100d9aa8daf07,
-                    03079e252e85abffd3c401a69b087e590a9b86f33f574f08129ccbd3521ecf516b,
-                    03111cf405b627e22135b3b3733a4a34aa5723fb0f58379a16d32861bf576b0ec2,
-                    0318f331b3e5d38156da6633b31929c5b220349859cc9ca3d33fb4e68aa0840174,
-                    03230dae6b4ac93480aeab26d000841298e3b8f6157028e47b0897c1e025165de1,
-                    035abff4281ff00660f99ab27bb53e6b33689c2cd8dcd364bc3c90ca5aea0d71a6,
-                    03bd45cddfacf2083b14310ae4a84e25de61e451637346325222747b157446614c,
-                    03cc297026b06c71cbfa52089149157b5ff23de027ac5ab781800a578192d17546,
-                    03d3bde5d63bdb3a6379b461be64dad45eabff42f758543a9645afd42f6d424828,
-                    03ed1e8d5109c9ed66f7941bc53cc71137baa76d50d274bda8d5e8ffbd6e61fe9a";
-        let fed_pks: Vec<LegacyPeginKey> = pks
-            .split(',')
-            .map(|pk| LegacyPeginKey::Functionary(bitcoin::PublicKey::from_str(pk).unwrap()))
-            .collect();
-
-        let emer_pks = "
-                    03aab896d53a8e7d6433137bbba940f9c521e085dd07e60994579b64a6d992cf79,
-                    0291b7d0b1b692f8f524516ed950872e5da10fb1b808b5a526dedc6fed1cf29807,
-                    0386aa9372fbab374593466bc5451dc59954e90787f08060964d95c87ef34ca5bb";
-        let emer_pks: Vec<LegacyPeginKey> = emer_pks
-            .split(',')
-            .map(|pk| LegacyPeginKey::Functionary(bitcoin::PublicKey::from_str(pk).unwrap()))
-            .collect();
+        Self::from_watchman(&WatchmanParams::liquid(), user_desc)
+            .expect("valid Liquid federation script")
+    }
 
-        Self::new(
-            fed_pks,
-            11,
-            emer_pks,
-            2,
-            bitcoin::Sequence::from_consensus(4032),
+    /// Create a new descriptor for the federation with parameters `params`,
+    /// the emergency keys being untweakable
+    ///
+    /// # Errors
+    /// If the federation script cannot be built from `params`.
+    pub fn from_watchman(
+        params: &WatchmanParams,
+        user_desc: Descriptor<Pk, CovenantExt<CovExtArgs>>,
+    ) -> Result<Self, Error> {
+        Self::try_new(
+            params
+                .functionaries()
+                .iter()
+                .map(|pk| LegacyPeginKey::Functionary(*pk))
+                .collect(),
+            params.threshold(),
+            params
+                .emergency_keys()
+                .iter()
+                .map(|pk| LegacyPeginKey::NonFunctionary(*pk))
+                .collect(),
+            params.emergency_threshold(),
+            params.csv(),
             user_desc,
         )
     }
+
+    // Size of the witness script, which does not depend on the tweak
+    fn script_size(&self) -> usize {
+        let right = if let BtcTerminal::OrD(_l, right) = &self.ms.node {
+            right
+        } else {
+            unreachable!("Only valid pegin descriptors should be created inside LegacyPegin")
+        };
+        // OP_DEPTH <k + 1> OP_EQUAL OP_IF <k> <fed keys> <n> OP_ELSE <right>
+        // OP_ENDIF, where `right` ends with the shared OP_CMS
+        3 + script_num_size(self.fed_k + 1)
+            + script_num_size(self.fed_k)
+            + 34 * self.fed_pks.len()
+            + script_num_size(self.fed_pks.len())
+            + 1
+            + right.script_size()
+            + 1
+    }
+}
+
+impl<Pk: MiniscriptKey> ForEachKey<LegacyPeginKey> for LegacyPegin<Pk> {
+    /// Run a predicate on the federation and emergency keys
+    fn for_each_key<'a, F: FnMut(&'a LegacyPeginKey) -> bool>(&'a self, pred: F) -> bool
+    where
+        LegacyPeginKey: 'a,
+    {
+        self.fed_pks.iter().chain(&self.emer_pks).all(pred)
+    }
 }
 
 impl<Pk: MiniscriptKey> fmt::Debug for LegacyPegin<Pk> {
@@ -320,7 +362,7 @@ impl<Pk: MiniscriptKey> fmt::Debug for LegacyPegin<Pk> {
 
 impl<Pk: MiniscriptKey> fmt::Display for LegacyPegin<Pk> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let desc = format!("legacy_pegin({},{})", self.ms, self.desc);
+        let desc = format!("legacy_pegin({},{:#})", self.ms, self.desc);
         let checksum = desc_checksum(&desc).map_err(|_| fmt::Error)?;
         write!(f, "{}#{}", &desc, &checksum)
     }
@@ -345,7 +387,7 @@ impl_from_tree!(
         if top.name == "legacy_pegin" && top.args.len() == 2 {
             // a roundtrip hack to use FromTree from bitcoin::Miniscript from
             // expression::Tree in elements.
-            let ms_str = top.args[0].to_string();
+            let ms_str = super::tree_to_string(&top.args[0]);
             let ms_expr = BtcTree::from_str(&ms_str)?;
             //
             let ms = BtcMiniscript::<LegacyPeginKey, BtcSegwitv0>::from_tree(&ms_expr);
@@ -366,8 +408,11 @@ impl_from_str!(
     type Err = Error;,
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let desc_str = verify_checksum(s)?;
-        let top = expression::Tree::from_str(desc_str)?;
-        Self::from_tree(&top)
+        let (ms_str, desc_str) = super::split_pegin_args(desc_str, "legacy_pegin")?;
+        let ms_expr = BtcTree::from_str(ms_str)?;
+        let ms = BtcMiniscript::<LegacyPeginKey, BtcSegwitv0>::from_tree(&ms_expr)?;
+        let desc = Descriptor::<Pk, CovenantExt<CovExtArgs>>::from_str(desc_str)?;
+        Ok(LegacyPegin::from_ms_and_desc(desc, ms))
     }
 );
 
@@ -453,11 +498,33 @@ impl<Pk: MiniscriptKey> LegacyPegin<Pk> {
     /// Returns satisfying witness and scriptSig to spend an
     /// output controlled by the given descriptor if it possible to
     /// construct one using the satisfier S.
+    ///
+    /// The emergency path is only used if the satisfier accepts its csv
+    /// timelock, see [`LegacyPegin::get_full_bitcoin_satisfaction`] for
+    /// the nSequence this requires.
     pub fn get_bitcoin_satisfaction<S, C: secp256k1_zkp::Verification>(
         &self,
         secp: &secp256k1_zkp::Secp256k1<C>,
         satisfier: S,
     ) -> Result<(Vec<Vec<u8>>, BtcScript), Error>
+    where
+        S: BtcSatisfier<bitcoin::PublicKey>,
+        Pk: ToPublicKey,
+    {
+        let sat = self.get_full_bitcoin_satisfaction(secp, satisfier)?;
+        Ok((sat.witness, sat.script_sig))
+    }
+
+    /// Returns satisfying witness and scriptSig to spend an
+    /// output controlled by the given descriptor, along with the nSequence
+    /// the spending input requires, if it possible to construct one using
+    /// the satisfier S. Spends by the federation require no nSequence,
+    /// emergency spends require the csv timelock of the descriptor.
+    pub fn get_full_bitcoin_satisfaction<S, C: secp256k1_zkp::Verification>(
+        &self,
+        secp: &secp256k1_zkp::Secp256k1<C>,
+        satisfier: S,
+    ) -> Result<LegacyPeginSatisfaction, Error>
     where
         S: BtcSatisfier<bitcoin::PublicKey>,
         Pk: ToPublicKey,
@@ -478,8 +545,12 @@ impl<Pk: MiniscriptKey> LegacyPegin<Pk> {
             // Prefer using federation keys over emergency paths
             let mut sigs: Vec<Vec<u8>> = sigs.into_iter().take(self.fed_k).collect();
             sigs.push(vec![0]); // CMS extra value
-            Ok((sigs, unsigned_script_sig))
-        } else {
+            Ok(LegacyPeginSatisfaction {
+                witness: sigs,
+                script_sig: unsigned_script_sig,
+                required_sequence: None,
+            })
+        } else if satisfier.check_older(self.timelock) {
             let mut emer_sigs = vec![];
             for emer_key in &self.emer_pks {
                 match satisfier.lookup_ecdsa_sig(emer_key.as_untweaked()) {
@@ -491,10 +562,16 @@ impl<Pk: MiniscriptKey> LegacyPegin<Pk> {
             if emer_sigs.len() >= self.emer_k {
                 let mut sigs: Vec<Vec<u8>> = emer_sigs.into_iter().take(self.emer_k).collect();
                 sigs.push(vec![0]); // CMS extra value
-                Ok((sigs, unsigned_script_sig))
+                Ok(LegacyPeginSatisfaction {
+                    witness: sigs,
+                    script_sig: unsigned_script_sig,
+                    required_sequence: Some(self.timelock),
+                })
             } else {
                 Err(Error::CouldNotSatisfy)
             }
+        } else {
+            Err(Error::CouldNotSatisfy)
         }
     }
 
@@ -503,7 +580,7 @@ impl<Pk: MiniscriptKey> LegacyPegin<Pk> {
     /// and sighash suffix. Includes the weight of the VarInts encoding the
     /// scriptSig and witness stack length.
     pub fn max_satisfaction_weight(&self) -> Result<usize, Error> {
-        let script_size = 628;
+        let script_size = self.script_size();
         Ok(4 * 36
             + varint_len(script_size)
             + script_size
@@ -533,3 +610,127 @@ impl<Pk: MiniscriptKey> LegacyPegin<Pk> {
         self.desc
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use elements::secp256k1_zkp::Secp256k1;
+
+    use super::*;
+    use crate::descriptor::federation::{LIQUID_EMERGENCY_KEYS, LIQUID_FUNCTIONARY_KEYS};
+    use crate::DescriptorPublicKey;
+
+    #[test]
+    fn legacy_pegin_watchman() {
+        let secp = Secp256k1::verification_only();
+        let user_desc = Descriptor::<DescriptorPublicKey, _>::from_str(
+            "elwpkh(02d7924d4f7d43ea965a465ae3095ff41131e5946f3c85f79e44adbcf8e27e080e)",
+        )
+        .unwrap();
+        let user_desc = user_desc.at_derivation_index(0).unwrap();
+        let liquid =
+            LegacyPegin::from_watchman(&WatchmanParams::liquid(), user_desc.clone()).unwrap();
+        let legacy = LegacyPegin::new_legacy_fed(user_desc.clone());
+        assert_eq!(liquid, legacy);
+        let script = liquid.bitcoin_witness_script(&secp).unwrap();
+        assert_eq!(script, legacy.bitcoin_witness_script(&secp).unwrap());
+        assert_eq!(
+            liquid.bitcoin_script_pubkey(&secp),
+            legacy.bitcoin_script_pubkey(&secp)
+        );
+        // Only the functionary keys are tweaked, the emergency keys after the
+        // OP_ELSE are pushed as they are
+        let pushed = |pk: &str| {
+            let pk = bitcoin::PublicKey::from_str(pk).unwrap().to_bytes();
+            script.as_bytes().windows(pk.len()).any(|w| w == &pk[..])
+        };
+        assert!(LIQUID_EMERGENCY_KEYS.iter().all(|pk| pushed(pk)));
+        assert!(!LIQUID_FUNCTIONARY_KEYS.iter().any(|pk| pushed(pk)));
+        assert_eq!(liquid.script_size(), 628);
+        assert_eq!(
+            liquid.max_satisfaction_weight().unwrap(),
+            legacy.max_satisfaction_weight().unwrap()
+        );
+
+        let mut n = 0;
+        assert!(liquid.for_each_key(|pk| {
+            n += 1;
+            pk.as_untweaked().compressed
+        }));
+        assert_eq!(n, 18);
+        assert!(!liquid.for_any_key(|pk| match *pk {
+            LegacyPeginKey::Functionary(ref pk) =>
+                LIQUID_EMERGENCY_KEYS.contains(&&*pk.to_string()),
+            LegacyPeginKey::NonFunctionary(ref pk) => {
+                LIQUID_FUNCTIONARY_KEYS.contains(&&*pk.to_string())
+            }
+        }));
+
+        // The script size follows the parameters of the federation
+        let keys = |pks: &[&str]| -> Vec<bitcoin::PublicKey> {
+            pks.iter()
+                .map(|pk| bitcoin::PublicKey::from_str(pk).unwrap())
+                .collect()
+        };
+        let params = WatchmanParams::new(
+            keys(&LIQUID_FUNCTIONARY_KEYS[..3]),
+            2,
+            keys(&LIQUID_EMERGENCY_KEYS[..1]),
+            1,
+            144,
+        )
+        .unwrap();
+        let small = LegacyPegin::from_watchman(&params, user_desc).unwrap();
+        assert_eq!(
+            small.script_size(),
+            small.bitcoin_witness_script(&secp).unwrap().len()
+        );
+        assert!(
+            small.max_satisfaction_weight().unwrap() < liquid.max_satisfaction_weight().unwrap()
+        );
+    }
+
+    #[test]
+    fn legacy_pegin_emergency_sequence() {
+        use std::collections::HashMap;
+
+        use elements::secp256k1_zkp::{Message, SecretKey};
+
+        let secp = Secp256k1::new();
+        let sks: Vec<_> = (1..4)
+            .map(|i| SecretKey::from_slice(&[i; 32]).unwrap())
+            .collect();
+        let pks: Vec<_> = sks
+            .iter()
+            .map(|sk| bitcoin::PublicKey::new(sk.public_key(&secp)))
+            .collect();
+        let params = WatchmanParams::new(pks[..2].to_vec(), 2, pks[2..].to_vec(), 1, 144).unwrap();
+        let user_desc = Descriptor::<DescriptorPublicKey, _>::from_str(
+            "elwpkh(02d7924d4f7d43ea965a465ae3095ff41131e5946f3c85f79e44adbcf8e27e080e)",
+        )
+        .unwrap()
+        .at_derivation_index(0)
+        .unwrap();
+        let pegin = LegacyPegin::from_watchman(&params, user_desc).unwrap();
+
+        let msg = Message::from_slice(&[0xab; 32]).unwrap();
+        let mut sigs = HashMap::new();
+        sigs.insert(
+            pks[2],
+            bitcoin::EcdsaSig {
+                sig: secp.sign_ecdsa(&msg, &sks[2]),
+                hash_ty: bitcoin::EcdsaSighashType::All,
+            },
+        );
+        // The emergency path cannot be used before the timelock
+        assert!(pegin.get_bitcoin_satisfaction(&secp, &sigs).is_err());
+        let sequence = bitcoin::Sequence::from_height(144);
+        let sat = pegin
+            .get_full_bitcoin_satisfaction(&secp, (&sigs, sequence))
+            .unwrap();
+        assert_eq!(sat.witness.len(), 2);
+        assert_eq!(sat.required_sequence, Some(sequence));
+        assert!(pegin
+            .get_bitcoin_satisfaction(&secp, (&sigs, bitcoin::Sequence::from_height(100)))
+            .is_err());
+    }
+}
@@ -22,6 +22,127 @@
 //! Thus, as a simple solution we implement these as a separate
 //! struct with it's own API.
 
+use crate::{expression, Error};
+
 pub mod dynafed_pegin;
 pub mod legacy_pegin;
-pub use self::legacy_pegin::{LegacyPegin, LegacyPeginKey};
+pub use self::dynafed_pegin::Pegin;
+pub use self::legacy_pegin::{LegacyPegin, LegacyPeginKey, LegacyPeginSatisfaction};
+
+// Splits the string `name(fed,elem)` of a pegin descriptor into the strings
+// of its bitcoin and elements sides, so that each side is parsed by its own
+// descriptor parser, which handles taproot script trees within `{}`
+fn split_pegin_args<'a>(s: &'a str, name: &str) -> Result<(&'a str, &'a str), Error> {
+    let args = if s.len() > name.len() + 1
+        && s.starts_with(name)
+        && s[name.len()..].starts_with('(')
+        && s.ends_with(')')
+    {
+        &s[name.len() + 1..s.len() - 1]
+    } else {
+        return Err(Error::Unexpected(format!(
+            "{} while parsing {} descriptor",
+            s, name
+        )));
+    };
+    let mut depth = 0;
+    for (n, ch) in args.char_indices() {
+        match ch {
+            '(' | '{' => depth += 1,
+            ')' | '}' => depth -= 1,
+            ',' if depth == 0 => return Ok((&args[..n], &args[n + 1..])),
+            _ => {}
+        }
+    }
+    Err(Error::Unexpected(format!(
+        "{}(1 args) while parsing {} descriptor",
+        name, name
+    )))
+}
+
+// Writes `tree` back in descriptor syntax, to parse the bitcoin side of the
+// pegin with the bitcoin miniscript library. Taproot branches are nodes
+// without a name and are written within `{}`. Nodes are kept on an explicit
+// stack, as in the parser, so that the stack usage does not depend on the
+// tree.
+fn tree_to_string(tree: &expression::Tree<'_>) -> String {
+    enum Item<'a, 'b> {
+        Node(&'b expression::Tree<'a>),
+        Str(&'static str),
+    }
+
+    let mut s = String::new();
+    let mut stack = vec![Item::Node(tree)];
+    while let Some(item) = stack.pop() {
+        let node = match item {
+            Item::Node(node) => node,
+            Item::Str(sep) => {
+                s.push_str(sep);
+                continue;
+            }
+        };
+        s.push_str(node.name);
+        if node.args.is_empty() {
+            continue;
+        }
+        let (open, close) = if node.name.is_empty() {
+            ("{", "}")
+        } else {
+            ("(", ")")
+        };
+        s.push_str(open);
+        stack.push(Item::Str(close));
+        for (i, arg) in node.args.iter().enumerate().rev() {
+            stack.push(Item::Node(arg));
+            if i > 0 {
+                stack.push(Item::Str(","));
+            }
+        }
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pegin_args() {
+        let s = "pegin(tr(A,{pk(B),{pk(C),pk(D)}}),eltr(E,{pk(F),pk(G)}))";
+        assert_eq!(
+            split_pegin_args(s, "pegin").unwrap(),
+            ("tr(A,{pk(B),{pk(C),pk(D)}})", "eltr(E,{pk(F),pk(G)})")
+        );
+        assert!(split_pegin_args(s, "legacy_pegin").is_err());
+        assert!(split_pegin_args("pegin(tr(A,{pk(B),pk(C)}))", "pegin").is_err());
+
+        let tree = expression::Tree::from_str("pegin(sh(wsh(multi(2,A,B))),elwpkh(C))").unwrap();
+        assert_eq!(
+            tree_to_string(&tree),
+            "pegin(sh(wsh(multi(2,A,B))),elwpkh(C))"
+        );
+        let tree = expression::Tree {
+            name: "tr",
+            args: vec![
+                expression::Tree {
+                    name: "A",
+                    args: vec![],
+                },
+                expression::Tree {
+                    name: "",
+                    args: vec![
+                        expression::Tree {
+                            name: "pk(B)",
+                            args: vec![],
+                        },
+                        expression::Tree {
+                            name: "pk(C)",
+                            args: vec![],
+                        },
+                    ],
+                },
+            ],
+        };
+        assert_eq!(tree_to_string(&tree), "tr(A,{pk(B),pk(C)})");
+    }
+}
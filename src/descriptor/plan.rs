@@ -0,0 +1,417 @@
+// Miniscript
+// Written in 2023 by
+//     The Elements Miniscript developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Spend Planning
+//!
+//! Choosing how to spend a descriptor from the keys, preimages and
+//! timelocks available, before any signature is made. Plans give the exact
+//! weight of the satisfaction, for coin selection and fee estimation.
+//!
+
+use std::collections::BTreeSet;
+
+use elements::hashes::hash160;
+use elements::script::Instruction;
+use elements::secp256k1_zkp::{ecdsa, schnorr};
+use elements::taproot::{LeafVersion, TapLeafHash};
+use elements::{LockTime, Script, Sequence};
+
+use super::{Descriptor, SpendHash};
+use crate::extensions::ParseableExt;
+use crate::miniscript::satisfy::{ElementsSig, Preimage32};
+use crate::util::varint_len;
+use crate::{Error, Extension, MiniscriptKey, Satisfier, ToPublicKey};
+
+/// What is available to spend a descriptor, see [`Descriptor::plan`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Assets<Pk: MiniscriptKey> {
+    /// Keys which can sign, in any context
+    pub keys: BTreeSet<Pk>,
+    /// Hashes whose preimages are known
+    pub hashes: BTreeSet<SpendHash<Pk>>,
+    /// The lock time of the spending transaction, if absolute timelocks
+    /// may be used
+    pub after: Option<LockTime>,
+    /// The sequence of the spending input, if relative timelocks may be
+    /// used
+    pub older: Option<Sequence>,
+}
+
+impl<Pk: MiniscriptKey> Assets<Pk> {
+    /// No assets at all
+    pub fn new() -> Self {
+        Assets {
+            keys: BTreeSet::new(),
+            hashes: BTreeSet::new(),
+            after: None,
+            older: None,
+        }
+    }
+
+    /// Add a key which can sign
+    pub fn add_key(mut self, pk: Pk) -> Self {
+        self.keys.insert(pk);
+        self
+    }
+
+    /// Add a hash whose preimage is known
+    pub fn add_hash(mut self, hash: SpendHash<Pk>) -> Self {
+        self.hashes.insert(hash);
+        self
+    }
+
+    /// Set the lock time of the spending transaction
+    pub fn after(mut self, lock_time: LockTime) -> Self {
+        self.after = Some(lock_time);
+        self
+    }
+
+    /// Set the sequence of the spending input
+    pub fn older(mut self, sequence: Sequence) -> Self {
+        self.older = Some(sequence);
+        self
+    }
+}
+
+impl<Pk: MiniscriptKey> Default for Assets<Pk> {
+    fn default() -> Self {
+        Assets::new()
+    }
+}
+
+/// How to spend a descriptor, see [`Descriptor::plan`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Plan<Pk: MiniscriptKey> {
+    /// Keys which must sign, in the order their signatures appear in the
+    /// satisfaction
+    pub keys: Vec<Pk>,
+    /// Hashes whose preimages must be revealed
+    pub hashes: Vec<SpendHash<Pk>>,
+    /// The lock time the spending transaction must have, if the plan uses
+    /// an absolute timelock
+    pub after: Option<LockTime>,
+    /// The sequence the spending input must have, if the plan uses a
+    /// relative timelock
+    pub older: Option<Sequence>,
+    /// Hash of the spent tap leaf, for script path spends of taproot
+    /// descriptors
+    pub leaf: Option<TapLeafHash>,
+    /// Weight of the satisfaction, counted like
+    /// [`Descriptor::max_satisfaction_weight`]
+    pub satisfaction_weight: usize,
+}
+
+// Answers a satisfier's questions from the assets, with placeholder
+// signatures and preimages which are distinct for each key and hash.
+struct PlanSatisfier<'a, Pk: MiniscriptKey> {
+    assets: &'a Assets<Pk>,
+    internal_key: Option<&'a Pk>,
+    after: Option<LockTime>,
+    older: Option<Sequence>,
+}
+
+fn position<T: Ord>(set: &BTreeSet<T>, item: &T) -> Option<u32> {
+    set.iter().position(|x| x == item).map(|i| i as u32)
+}
+
+// A high R, low S signature: the largest standard ECDSA signature, 72 bytes
+// with the sighash type
+pub(super) fn dummy_ecdsa_sig(index: u32) -> ElementsSig {
+    let mut compact = [0u8; 64];
+    compact[0] = 0x80;
+    compact[28..32].copy_from_slice(&index.to_be_bytes());
+    compact[32] = 0x7f;
+    compact[63] = 0x01;
+    let sig = ecdsa::Signature::from_compact(&compact).expect("valid r and s");
+    (sig, elements::EcdsaSigHashType::All)
+}
+
+pub(super) fn dummy_schnorr_sig(index: u32) -> elements::SchnorrSig {
+    let mut bytes = [0x01u8; 64];
+    bytes[..4].copy_from_slice(&index.to_be_bytes());
+    elements::SchnorrSig {
+        sig: schnorr::Signature::from_slice(&bytes).expect("64 bytes"),
+        hash_ty: elements::SchnorrSigHashType::Default,
+    }
+}
+
+pub(super) fn dummy_preimage(index: u32) -> Preimage32 {
+    let mut preimage = [0xaa; 32];
+    preimage[..4].copy_from_slice(&index.to_be_bytes());
+    preimage
+}
+
+impl<'a, Pk: MiniscriptKey> PlanSatisfier<'a, Pk> {
+    fn preimage(&self, hash: SpendHash<Pk>) -> Option<Preimage32> {
+        position(&self.assets.hashes, &hash).map(dummy_preimage)
+    }
+}
+
+impl<'a, Pk: MiniscriptKey + ToPublicKey> Satisfier<Pk> for PlanSatisfier<'a, Pk> {
+    fn lookup_ecdsa_sig(&self, pk: &Pk) -> Option<ElementsSig> {
+        position(&self.assets.keys, pk).map(dummy_ecdsa_sig)
+    }
+
+    fn lookup_tap_key_spend_sig(&self) -> Option<elements::SchnorrSig> {
+        self.internal_key
+            .and_then(|pk| position(&self.assets.keys, pk))
+            .map(dummy_schnorr_sig)
+    }
+
+    fn lookup_tap_leaf_script_sig(&self, pk: &Pk, _: &TapLeafHash) -> Option<elements::SchnorrSig> {
+        position(&self.assets.keys, pk).map(dummy_schnorr_sig)
+    }
+
+    fn lookup_raw_pkh_pk(&self, hash: &hash160::Hash) -> Option<Pk> {
+        self.assets
+            .keys
+            .iter()
+            .find(|pk| pk.to_pubkeyhash(crate::SigType::Ecdsa) == *hash)
+            .cloned()
+    }
+
+    fn lookup_sha256(&self, h: &Pk::Sha256) -> Option<Preimage32> {
+        self.preimage(SpendHash::Sha256(h.clone()))
+    }
+
+    fn lookup_hash256(&self, h: &Pk::Hash256) -> Option<Preimage32> {
+        self.preimage(SpendHash::Hash256(h.clone()))
+    }
+
+    fn lookup_ripemd160(&self, h: &Pk::Ripemd160) -> Option<Preimage32> {
+        self.preimage(SpendHash::Ripemd160(h.clone()))
+    }
+
+    fn lookup_hash160(&self, h: &Pk::Hash160) -> Option<Preimage32> {
+        self.preimage(SpendHash::Hash160(h.clone()))
+    }
+
+    fn check_older(&self, n: Sequence) -> bool {
+        self.older
+            .map_or(false, |seq| Satisfier::<Pk>::check_older(&seq, n))
+    }
+
+    fn check_after(&self, n: LockTime) -> bool {
+        self.after.map_or(false, |lock_time| {
+            Satisfier::<Pk>::check_after(&lock_time, n)
+        })
+    }
+}
+
+// The pushes of the script sig, with small numbers as script integers
+pub(super) fn script_sig_elements(script_sig: &Script) -> Vec<Vec<u8>> {
+    script_sig
+        .instructions()
+        .filter_map(|ins| match ins {
+            Ok(Instruction::PushBytes(bytes)) => Some(bytes.to_vec()),
+            Ok(Instruction::Op(op)) => match op.into_u8() {
+                0x4f => Some(vec![0x81]),
+                n @ 0x51..=0x60 => Some(vec![n - 0x50]),
+                _ => None,
+            },
+            Err(_) => None,
+        })
+        .collect()
+}
+
+// The pushes of the script sig followed by the witness
+fn satisfaction_elements(witness: &[Vec<u8>], script_sig: &Script) -> Vec<Vec<u8>> {
+    let mut elements = script_sig_elements(script_sig);
+    elements.extend(witness.iter().cloned());
+    elements
+}
+
+fn satisfaction_weight(witness: &[Vec<u8>], script_sig: &Script) -> usize {
+    let script_sig_weight = 4 * (varint_len(script_sig.len()) + script_sig.len());
+    let witness_weight = if witness.is_empty() {
+        0
+    } else {
+        varint_len(witness.len())
+            + witness
+                .iter()
+                .map(|elem| varint_len(elem.len()) + elem.len())
+                .sum::<usize>()
+    };
+    script_sig_weight + witness_weight
+}
+
+impl<Pk: MiniscriptKey + ToPublicKey, Ext: Extension + ParseableExt> Descriptor<Pk, Ext> {
+    pub(super) fn plan_satisfaction(
+        &self,
+        assets: &Assets<Pk>,
+        after: Option<LockTime>,
+        older: Option<Sequence>,
+    ) -> Result<(Vec<Vec<u8>>, Script), Error> {
+        let internal_key = match *self {
+            Descriptor::Tr(ref tr) => Some(tr.internal_key()),
+            Descriptor::TrExt(ref tr) => Some(tr.internal_key()),
+            _ => None,
+        };
+        self.get_satisfaction(PlanSatisfier {
+            assets,
+            internal_key,
+            after,
+            older,
+        })
+    }
+
+    /// Plan the spend of this descriptor with the given assets, without
+    /// making any signature.
+    ///
+    /// The plan is the one [`Descriptor::get_satisfaction`] would choose
+    /// if it had the signatures and preimages of the assets: the smallest
+    /// non-malleable satisfaction, or the key spend for taproot
+    /// descriptors whose internal key is an asset. Its weight assumes
+    /// ECDSA signatures of 72 bytes and Schnorr signatures of 64 bytes,
+    /// both including the sighash type, so signing with other sighash
+    /// types than `ALL` and `SIGHASH_DEFAULT` respectively adds a byte.
+    /// Timelocks are only part of the plan when the satisfaction needs
+    /// them.
+    ///
+    /// Extensions which need the spending transaction cannot be planned.
+    ///
+    /// # Errors
+    /// When the descriptor cannot be satisfied with the assets.
+    pub fn plan(&self, assets: &Assets<Pk>) -> Result<Plan<Pk>, Error> {
+        let (witness, script_sig) = self.plan_satisfaction(assets, assets.after, assets.older)?;
+
+        // Drop the timelocks which do not change the satisfaction
+        let needs = |after, older| {
+            self.plan_satisfaction(assets, after, older).ok()
+                != Some((witness.clone(), script_sig.clone()))
+        };
+        let after = assets.after.filter(|_| needs(None, assets.older));
+        let older = assets.older.filter(|_| needs(assets.after, None));
+
+        let elements = satisfaction_elements(&witness, &script_sig);
+        let mut keys = vec![];
+        for elem in &elements {
+            for (i, pk) in assets.keys.iter().enumerate() {
+                let (sig, hash_ty) = dummy_ecdsa_sig(i as u32);
+                let mut ecdsa = sig.serialize_der().to_vec();
+                ecdsa.push(hash_ty as u8);
+                if *elem == ecdsa || *elem == dummy_schnorr_sig(i as u32).to_vec() {
+                    keys.push(pk.clone());
+                }
+            }
+        }
+        let hashes = assets
+            .hashes
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| {
+                elements
+                    .iter()
+                    .any(|e| e[..] == dummy_preimage(i as u32)[..])
+            })
+            .map(|(_, h)| h.clone())
+            .collect();
+
+        // Script path spends end with the leaf script and control block
+        let leaf = match *self {
+            Descriptor::Tr(..) | Descriptor::TrExt(..) if witness.len() > 1 => {
+                let script = Script::from(witness[witness.len() - 2].clone());
+                Some(TapLeafHash::from_script(&script, LeafVersion::default()))
+            }
+            _ => None,
+        };
+
+        Ok(Plan {
+            keys,
+            hashes,
+            after,
+            older,
+            leaf,
+            satisfaction_weight: satisfaction_weight(&witness, &script_sig),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use elements::hashes::{sha256, Hash};
+
+    use super::*;
+    use crate::NoExt;
+
+    const A: &str = "028c28a97bf8298bc0d23d8c749452a32e694b65e30a9472a3954ab30fe5324caa";
+    const B: &str = "03daed4f2be3a8bf278e70132fb0beb7522f570e144bf615c07e996d443dee8729";
+
+    fn pk(s: &str) -> bitcoin::PublicKey {
+        bitcoin::PublicKey::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn plan_wsh() {
+        let hash = sha256::Hash::hash(&[1; 32]);
+        let desc = Descriptor::<bitcoin::PublicKey, NoExt>::from_str(&format!(
+            "elwsh(or_d(pk({}),or_i(and_v(v:pk({}),sha256({})),and_v(v:pk({}),older(144)))))",
+            A, B, hash, B
+        ))
+        .unwrap();
+
+        let plan = desc.plan(&Assets::new().add_key(pk(A))).unwrap();
+        assert_eq!(plan.keys, vec![pk(A)]);
+        assert_eq!(plan.older, None);
+        assert_eq!(plan.leaf, None);
+        // Signature of A and the witness script
+        assert_eq!(
+            plan.satisfaction_weight,
+            4 + 1 + 73 + 1 + desc.script_code().unwrap().len()
+        );
+
+        let assets = Assets::new()
+            .add_key(pk(B))
+            .add_hash(SpendHash::Sha256(hash))
+            .older(Sequence(100));
+        let plan = desc.plan(&assets).unwrap();
+        assert_eq!(plan.keys, vec![pk(B)]);
+        assert_eq!(plan.hashes, vec![SpendHash::Sha256(hash)]);
+        assert_eq!(plan.older, None);
+
+        let plan = desc
+            .plan(&Assets::new().add_key(pk(B)).older(Sequence(144)))
+            .unwrap();
+        assert_eq!(plan.hashes, vec![]);
+        assert_eq!(plan.older, Some(Sequence(144)));
+
+        assert!(desc.plan(&Assets::new().add_key(pk(B))).is_err());
+    }
+
+    #[test]
+    fn plan_tr() {
+        let desc = Descriptor::<bitcoin::PublicKey, NoExt>::from_str(&format!(
+            "eltr({},{{pk({}),and_v(v:pk({}),after(100))}})",
+            A, B, A
+        ))
+        .unwrap();
+
+        let plan = desc.plan(&Assets::new().add_key(pk(A))).unwrap();
+        assert_eq!(plan.keys, vec![pk(A)]);
+        assert_eq!(plan.leaf, None);
+        assert_eq!(plan.satisfaction_weight, 4 + 1 + 1 + 64);
+
+        let plan = desc.plan(&Assets::new().add_key(pk(B))).unwrap();
+        assert_eq!(plan.keys, vec![pk(B)]);
+        let script = Script::from_str(&format!("20{}ac", &B[2..])).unwrap();
+        assert_eq!(
+            plan.leaf,
+            Some(TapLeafHash::from_script(&script, LeafVersion::default()))
+        );
+        // Signature, leaf script and control block
+        assert_eq!(plan.satisfaction_weight, 4 + 1 + 65 + 35 + 66);
+    }
+}
@@ -0,0 +1,226 @@
+// Miniscript
+// Written in 2023 by
+//     The Elements Miniscript developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Privacy Report
+//!
+//! Estimates of what the spends of a descriptor reveal on chain, to compare
+//! encodings of the same policy, e.g. `elwsh` against `eltr`.
+//!
+
+use elements::taproot::{LeafVersion, TapLeafHash};
+
+use super::{Descriptor, DescriptorType, ShInner, SpendPath, Tr};
+use crate::extensions::ParseableExt;
+use crate::{Error, Extension, ForEachKey, MiniscriptKey, ToPublicKey};
+
+/// What a spend along a [`SpendPath`] reveals, see
+/// [`Descriptor::privacy_report`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpendFootprint<Pk: MiniscriptKey> {
+    /// The spend path
+    pub path: SpendPath<Pk>,
+    /// Keys whose public keys appear in the witness or scriptSig, besides
+    /// the output key of taproot outputs. The internal key is revealed by
+    /// the control block of script path spends.
+    pub revealed_keys: Vec<Pk>,
+    /// Number of the other spend paths which stay hidden. Script path
+    /// spends of taproot descriptors only reveal the spent leaf, other
+    /// descriptors reveal their whole script.
+    pub hidden_paths: usize,
+    /// Whether the spend looks like a spend of a single key output of the
+    /// same script type, e.g. a taproot key spend
+    pub looks_like_single_key: bool,
+}
+
+/// An estimate of the on-chain footprint of the spends of a descriptor
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrivacyReport<Pk: MiniscriptKey> {
+    /// The script type revealed by the output
+    pub desc_type: DescriptorType,
+    /// The footprint of each of the
+    /// [spend paths][`Descriptor::spend_paths`], in the same order
+    pub spends: Vec<SpendFootprint<Pk>>,
+}
+
+impl<Pk: MiniscriptKey> PrivacyReport<Pk> {
+    /// The largest number of keys revealed by any spend
+    pub fn max_revealed_keys(&self) -> usize {
+        self.spends
+            .iter()
+            .map(|s| s.revealed_keys.len())
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Whether every spend reveals all spend paths
+    pub fn reveals_all_paths(&self) -> bool {
+        self.spends.iter().all(|s| s.hidden_paths == 0)
+    }
+}
+
+impl<Pk: MiniscriptKey + ToPublicKey, Ext: Extension + ParseableExt> Descriptor<Pk, Ext> {
+    /// Estimates what spending this descriptor reveals on chain along each
+    /// of its [spend paths][`Descriptor::spend_paths`]: the script type,
+    /// the keys exposed, and how many alternative paths stay hidden.
+    ///
+    /// This does not account for the timelocks, hashes or signature counts
+    /// of paths, which are in the spend paths of the report, nor for
+    /// correlations between transactions.
+    ///
+    /// # Errors
+    /// When the descriptor cannot be lifted, see [`crate::policy::Liftable::lift`].
+    pub fn privacy_report(&self) -> Result<PrivacyReport<Pk>, Error> {
+        let paths = self.spend_paths()?;
+        let spends = match *self {
+            Descriptor::Tr(ref tr) => tr_footprints(tr, paths),
+            Descriptor::TrExt(ref tr) => tr_footprints(tr, paths),
+            _ => {
+                let mut keys = vec![];
+                self.for_each_key(|pk| {
+                    push_new(&mut keys, pk);
+                    true
+                });
+                let single_key = match *self {
+                    Descriptor::Pkh(..) | Descriptor::Wpkh(..) => true,
+                    Descriptor::Sh(ref sh) => matches_wpkh(sh.as_inner()),
+                    _ => false,
+                };
+                paths
+                    .into_iter()
+                    .map(|path| SpendFootprint {
+                        path,
+                        revealed_keys: keys.clone(),
+                        hidden_paths: 0,
+                        looks_like_single_key: single_key,
+                    })
+                    .collect()
+            }
+        };
+        Ok(PrivacyReport {
+            desc_type: self.desc_type(),
+            spends,
+        })
+    }
+}
+
+fn matches_wpkh<Pk: MiniscriptKey>(inner: &ShInner<Pk>) -> bool {
+    match *inner {
+        ShInner::Wpkh(..) => true,
+        ShInner::Wsh(..) | ShInner::SortedMulti(..) | ShInner::Ms(..) => false,
+    }
+}
+
+fn push_new<Pk: MiniscriptKey>(keys: &mut Vec<Pk>, pk: &Pk) {
+    if !keys.contains(pk) {
+        keys.push(pk.clone());
+    }
+}
+
+fn tr_footprints<Pk, Ext>(tr: &Tr<Pk, Ext>, paths: Vec<SpendPath<Pk>>) -> Vec<SpendFootprint<Pk>>
+where
+    Pk: MiniscriptKey + ToPublicKey,
+    Ext: Extension + ParseableExt,
+{
+    let leaf_keys = |leaf: TapLeafHash| {
+        let mut keys = vec![tr.internal_key().clone()];
+        for (_, ms) in tr.iter_scripts() {
+            if TapLeafHash::from_script(&ms.encode(), LeafVersion::default()) == leaf {
+                for pk in ms.iter_pk() {
+                    push_new(&mut keys, &pk);
+                }
+            }
+        }
+        keys
+    };
+    let n_paths = paths.len();
+    let leaves: Vec<_> = paths.iter().map(|p| p.leaf).collect();
+    paths
+        .into_iter()
+        .map(|path| match path.leaf {
+            // The output key is always on chain, and tweaked
+            None => SpendFootprint {
+                path,
+                revealed_keys: vec![],
+                hidden_paths: n_paths - 1,
+                looks_like_single_key: true,
+            },
+            Some(leaf) => SpendFootprint {
+                revealed_keys: leaf_keys(leaf),
+                hidden_paths: leaves
+                    .iter()
+                    .filter(|l| l.map_or(false, |l| l != leaf))
+                    .count(),
+                looks_like_single_key: false,
+                path,
+            },
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::NoExt;
+
+    type Desc = Descriptor<bitcoin::PublicKey, NoExt>;
+
+    fn key(i: u8) -> String {
+        let secp = elements::secp256k1_zkp::Secp256k1::signing_only();
+        let sk = elements::secp256k1_zkp::SecretKey::from_slice(&[i + 1; 32]).unwrap();
+        elements::secp256k1_zkp::PublicKey::from_secret_key(&secp, &sk).to_string()
+    }
+
+    #[test]
+    fn wsh_against_tr() {
+        let (a, b, c) = (key(0), key(1), key(2));
+
+        let wsh = Desc::from_str(&format!(
+            "elwsh(or_d(pk({}),and_v(v:pk({}),older(144))))",
+            a, b
+        ))
+        .unwrap();
+        let report = wsh.privacy_report().unwrap();
+        assert_eq!(report.desc_type, DescriptorType::Wsh);
+        assert_eq!(report.spends.len(), 2);
+        assert_eq!(report.max_revealed_keys(), 2);
+        assert!(report.reveals_all_paths());
+        assert!(report.spends.iter().all(|s| !s.looks_like_single_key));
+
+        let tr = Desc::from_str(&format!(
+            "eltr({},{{pk({}),and_v(v:pk({}),older(144))}})",
+            a, b, c
+        ))
+        .unwrap();
+        let report = tr.privacy_report().unwrap();
+        assert_eq!(report.desc_type, DescriptorType::Tr);
+        assert_eq!(report.spends.len(), 3);
+        assert!(!report.reveals_all_paths());
+        let key_spend = &report.spends[0];
+        assert!(key_spend.looks_like_single_key);
+        assert!(key_spend.revealed_keys.is_empty());
+        assert_eq!(key_spend.hidden_paths, 2);
+        for spend in &report.spends[1..] {
+            assert!(!spend.looks_like_single_key);
+            assert_eq!(spend.revealed_keys.len(), 2);
+            assert_eq!(spend.hidden_paths, 1);
+        }
+
+        let wpkh = Desc::from_str(&format!("elsh(wpkh({}))", a)).unwrap();
+        let report = wpkh.privacy_report().unwrap();
+        assert!(report.spends[0].looks_like_single_key);
+        assert_eq!(report.max_revealed_keys(), 1);
+    }
+}
@@ -0,0 +1,219 @@
+// Miniscript
+// Written in 2023 by
+//     The Elements Miniscript developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Descriptor Records
+//!
+//! The metadata wallets store next to their descriptors to know which part
+//! of the chain and which derivation indices to scan.
+//!
+
+use std::ops::{Range, RangeInclusive};
+
+use super::{Descriptor, DescriptorPublicKey};
+use crate::extensions::ParseableExt;
+use crate::{Extension, MiniscriptKey, NoExt};
+
+/// The gap limit used by [`DescriptorRecord::new`], following BIP-44
+pub const DEFAULT_GAP_LIMIT: u32 = 20;
+
+/// A descriptor with its birthday and scanning metadata.
+///
+/// With the `serde` feature, records serialize to a map with the same field
+/// names, in which the descriptor is serialized as a string. The label may
+/// be omitted when deserializing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DescriptorRecord<Pk: MiniscriptKey, Ext: Extension = NoExt> {
+    /// The descriptor
+    pub descriptor: Descriptor<Pk, Ext>,
+    /// Height of the first block which may contain outputs of the descriptor
+    pub birthday_height: u32,
+    /// Number of consecutive unused derivation indices after which scanning
+    /// stops
+    pub gap_limit: u32,
+    /// A name for the descriptor chosen by the user
+    pub label: Option<String>,
+}
+
+impl<Pk: MiniscriptKey, Ext: Extension> DescriptorRecord<Pk, Ext> {
+    /// Create a record of `descriptor`, born at `birthday_height`, with the
+    /// [`DEFAULT_GAP_LIMIT`] and no label
+    pub fn new(descriptor: Descriptor<Pk, Ext>, birthday_height: u32) -> Self {
+        DescriptorRecord {
+            descriptor,
+            birthday_height,
+            gap_limit: DEFAULT_GAP_LIMIT,
+            label: None,
+        }
+    }
+
+    /// The heights of the blocks to rescan for a chain with tip at
+    /// `tip_height`, or `None` if the descriptor is younger than the tip
+    pub fn rescan_heights(&self, tip_height: u32) -> Option<RangeInclusive<u32>> {
+        if self.birthday_height > tip_height {
+            None
+        } else {
+            Some(self.birthday_height..=tip_height)
+        }
+    }
+}
+
+impl<Ext: Extension + ParseableExt> DescriptorRecord<DescriptorPublicKey, Ext> {
+    /// The derivation indices to scan, given the highest index `last_used`
+    /// known to have been used, if any.
+    ///
+    /// Scanning covers `gap_limit` indices after the last used one. The
+    /// range must be extended whenever an index in it turns out to be used.
+    /// Descriptors without wildcards only have the index 0.
+    pub fn rescan_indices(&self, last_used: Option<u32>) -> Range<u32> {
+        if !self.descriptor.has_wildcard() {
+            return 0..1;
+        }
+        let start = last_used.map_or(0, |i| i.saturating_add(1));
+        0..start.saturating_add(self.gap_limit)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<Pk, Ext> crate::serde::Serialize for DescriptorRecord<Pk, Ext>
+where
+    Pk: MiniscriptKey,
+    Ext: Extension,
+{
+    fn serialize<S: crate::serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use crate::serde::ser::SerializeStruct;
+
+        let mut s = serializer.serialize_struct("DescriptorRecord", 4)?;
+        s.serialize_field("descriptor", &self.descriptor)?;
+        s.serialize_field("birthday_height", &self.birthday_height)?;
+        s.serialize_field("gap_limit", &self.gap_limit)?;
+        s.serialize_field("label", &self.label)?;
+        s.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, Pk, Ext> crate::serde::Deserialize<'de> for DescriptorRecord<Pk, Ext>
+where
+    Pk: MiniscriptKey,
+    Ext: Extension,
+    Descriptor<Pk, Ext>: crate::serde::Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: crate::serde::de::Deserializer<'de>,
+    {
+        use std::fmt;
+        use std::marker::PhantomData;
+
+        use crate::serde::de::{self, IgnoredAny, MapAccess};
+
+        const FIELDS: &[&str] = &["descriptor", "birthday_height", "gap_limit", "label"];
+
+        struct Visitor<Pk, Ext>(PhantomData<(Pk, Ext)>);
+
+        impl<'de, Pk, Ext> de::Visitor<'de> for Visitor<Pk, Ext>
+        where
+            Pk: MiniscriptKey,
+            Ext: Extension,
+            Descriptor<Pk, Ext>: crate::serde::Deserialize<'de>,
+        {
+            type Value = DescriptorRecord<Pk, Ext>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+                formatter.write_str("a descriptor record")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut descriptor = None;
+                let mut birthday_height = None;
+                let mut gap_limit = None;
+                let mut label = None;
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "descriptor" => descriptor = Some(map.next_value()?),
+                        "birthday_height" => birthday_height = Some(map.next_value()?),
+                        "gap_limit" => gap_limit = Some(map.next_value()?),
+                        "label" => label = map.next_value()?,
+                        _ => {
+                            map.next_value::<IgnoredAny>()?;
+                        }
+                    }
+                }
+                Ok(DescriptorRecord {
+                    descriptor: descriptor.ok_or_else(|| de::Error::missing_field("descriptor"))?,
+                    birthday_height: birthday_height
+                        .ok_or_else(|| de::Error::missing_field("birthday_height"))?,
+                    gap_limit: gap_limit.ok_or_else(|| de::Error::missing_field("gap_limit"))?,
+                    label,
+                })
+            }
+        }
+
+        deserializer.deserialize_struct("DescriptorRecord", FIELDS, Visitor(PhantomData))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    type Record = DescriptorRecord<DescriptorPublicKey>;
+
+    const XPUB: &str = "xpub6BgBgsespWvERF3LHQu6CnqdvfEvtMcQjYrcRzx53QJjSxarj2afYWcLteoGVky7D3UKDP9QyrLprQ3VCECoY49yfdDEHGCtMMj92pReUsQ";
+
+    #[test]
+    fn rescan_ranges() {
+        let desc = Descriptor::from_str(&format!("elwpkh({}/0/*)", XPUB)).unwrap();
+        let record = Record::new(desc, 1_000);
+        assert_eq!(record.rescan_heights(999), None);
+        assert_eq!(record.rescan_heights(1_500), Some(1_000..=1_500));
+        assert_eq!(record.rescan_indices(None), 0..20);
+        assert_eq!(record.rescan_indices(Some(4)), 0..25);
+        assert_eq!(
+            record.rescan_indices(Some(u32::max_value())),
+            0..u32::max_value()
+        );
+
+        let desc = Descriptor::from_str(&format!("elwpkh({}/0/0)", XPUB)).unwrap();
+        assert_eq!(Record::new(desc, 0).rescan_indices(Some(7)), 0..1);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn record_serde() {
+        let desc = Descriptor::from_str(&format!("elwpkh({}/0/*)", XPUB)).unwrap();
+        let mut record = Record::new(desc, 1_000);
+        record.label = Some("savings".to_owned());
+        let json = serde_json::to_value(&record).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "descriptor": record.descriptor.to_string(),
+                "birthday_height": 1_000,
+                "gap_limit": 20,
+                "label": "savings",
+            })
+        );
+        assert_eq!(serde_json::from_value::<Record>(json).unwrap(), record);
+
+        let json = serde_json::json!({
+            "descriptor": record.descriptor.to_string(),
+            "birthday_height": 1_000,
+            "gap_limit": 20,
+        });
+        assert_eq!(serde_json::from_value::<Record>(json).unwrap().label, None);
+    }
+}
@@ -0,0 +1,289 @@
+// Miniscript
+// Written in 2023 by
+//     The Elements Miniscript developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Sealed Descriptors
+//!
+//! Passphrase encryption of descriptors for backups, enabled by the
+//! `encrypt` feature. A sealed descriptor is a byte blob of the form
+//!
+//! ```text
+//! magic "ELDS" | version (1) | iterations (4, BE) | salt (16) | nonce (12) | ciphertext | tag (16) | checksum (4)
+//! ```
+//!
+//! The key is derived from the passphrase with PBKDF2-HMAC-SHA256 over the
+//! salt, with at most [`MAX_ITERATIONS`] iterations. The descriptor string
+//! is encrypted with ChaCha20-Poly1305 under that key and the nonce, with
+//! the header up to the nonce as associated data so that it cannot be
+//! altered either. The checksum consists of the first four bytes of the
+//! double SHA256 of everything preceding it, which tells corrupted backups
+//! apart from wrong passphrases.
+//!
+//! Keys, plaintexts and the descriptor strings with secret keys are
+//! zeroized when dropped.
+//!
+
+use std::{error, fmt, str};
+
+use bitcoin::secp256k1::rand::{thread_rng, RngCore};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use elements::hashes::{sha256d, Hash};
+use elements::secp256k1_zkp;
+use zeroize::Zeroizing;
+
+use super::{Descriptor, DescriptorPublicKey, KeyMap};
+use crate::extensions::ParseableExt;
+use crate::{Error, Extension, MiniscriptKey};
+
+const MAGIC: &[u8; 4] = b"ELDS";
+const VERSION: u8 = 1;
+/// PBKDF2 iterations used when sealing
+const ITERATIONS: u32 = 100_000;
+/// The maximum number of PBKDF2 iterations of a blob that is unsealed, so
+/// that crafted blobs cannot make unsealing run for hours
+pub const MAX_ITERATIONS: u32 = 10_000_000;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const HEADER_LEN: usize = 4 + 1 + 4 + SALT_LEN + NONCE_LEN;
+const TAG_LEN: usize = 16;
+const CHECKSUM_LEN: usize = 4;
+
+/// Error unsealing a descriptor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnsealError {
+    /// The blob is truncated or fails its checksum
+    Corrupted,
+    /// The blob was sealed with a format version that is not supported
+    UnknownVersion(u8),
+    /// The blob asks for zero or more than [`MAX_ITERATIONS`] iterations
+    BadIterations(u32),
+    /// The passphrase does not match
+    WrongPassphrase,
+}
+
+impl fmt::Display for UnsealError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            UnsealError::Corrupted => f.write_str("sealed descriptor is corrupted"),
+            UnsealError::UnknownVersion(v) => write!(f, "unknown sealed descriptor version {}", v),
+            UnsealError::BadIterations(n) => write!(
+                f,
+                "sealed descriptor with {} iterations, expected 1 to {}",
+                n, MAX_ITERATIONS
+            ),
+            UnsealError::WrongPassphrase => f.write_str("wrong passphrase for sealed descriptor"),
+        }
+    }
+}
+
+impl error::Error for UnsealError {}
+
+impl<Pk: MiniscriptKey, Ext: Extension> Descriptor<Pk, Ext> {
+    /// Encrypt the descriptor with `passphrase` for backups.
+    ///
+    /// Use [`Descriptor::seal_with_secret`] to include secret keys.
+    pub fn seal(&self, passphrase: &str) -> Vec<u8> {
+        seal(&self.to_string(), passphrase.as_bytes(), ITERATIONS)
+    }
+
+    /// Decrypt a descriptor encrypted by [`Descriptor::seal`].
+    pub fn unseal(blob: &[u8], passphrase: &str) -> Result<Self, Error>
+    where
+        Self: str::FromStr<Err = Error>,
+    {
+        unseal(blob, passphrase.as_bytes())?.parse()
+    }
+}
+
+impl<Ext: Extension + ParseableExt> Descriptor<DescriptorPublicKey, Ext> {
+    /// Encrypt the descriptor along with the secret keys in `key_map` with
+    /// `passphrase` for backups.
+    pub fn seal_with_secret(&self, key_map: &KeyMap, passphrase: &str) -> Vec<u8> {
+        let plaintext = Zeroizing::new(self.to_string_with_secret(key_map));
+        seal(&plaintext, passphrase.as_bytes(), ITERATIONS)
+    }
+
+    /// Decrypt a descriptor encrypted by [`Descriptor::seal_with_secret`],
+    /// see [`Descriptor::parse_descriptor`] for the returned key map.
+    pub fn unseal_with_secret<C: secp256k1_zkp::Signing>(
+        secp: &secp256k1_zkp::Secp256k1<C>,
+        blob: &[u8],
+        passphrase: &str,
+    ) -> Result<(Self, KeyMap), Error> {
+        Self::parse_descriptor(secp, &unseal(blob, passphrase.as_bytes())?)
+    }
+}
+
+fn seal(plaintext: &str, passphrase: &[u8], iterations: u32) -> Vec<u8> {
+    let mut salt = [0u8; SALT_LEN];
+    let mut nonce = [0u8; NONCE_LEN];
+    thread_rng().fill_bytes(&mut salt);
+    thread_rng().fill_bytes(&mut nonce);
+
+    let mut blob = Vec::with_capacity(HEADER_LEN + plaintext.len() + TAG_LEN + CHECKSUM_LEN);
+    blob.extend(MAGIC);
+    blob.push(VERSION);
+    blob.extend(&iterations.to_be_bytes());
+    blob.extend(&salt);
+    blob.extend(&nonce);
+
+    let key = derive_key(passphrase, &salt, iterations);
+    let ciphertext = ChaCha20Poly1305::new(Key::from_slice(&key[..]))
+        .encrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: plaintext.as_bytes(),
+                aad: &blob,
+            },
+        )
+        .expect("descriptors are shorter than the ChaCha20 limit");
+    blob.extend(ciphertext);
+
+    let checksum = sha256d::Hash::hash(&blob);
+    blob.extend(&checksum[..CHECKSUM_LEN]);
+    blob
+}
+
+fn unseal(blob: &[u8], passphrase: &[u8]) -> Result<Zeroizing<String>, UnsealError> {
+    if blob.len() < HEADER_LEN + TAG_LEN + CHECKSUM_LEN || &blob[..4] != MAGIC {
+        return Err(UnsealError::Corrupted);
+    }
+    let (blob, checksum) = blob.split_at(blob.len() - CHECKSUM_LEN);
+    if sha256d::Hash::hash(blob)[..CHECKSUM_LEN] != *checksum {
+        return Err(UnsealError::Corrupted);
+    }
+    if blob[4] != VERSION {
+        return Err(UnsealError::UnknownVersion(blob[4]));
+    }
+    let mut iterations = [0u8; 4];
+    iterations.copy_from_slice(&blob[5..9]);
+    let iterations = u32::from_be_bytes(iterations);
+    if iterations == 0 || iterations > MAX_ITERATIONS {
+        return Err(UnsealError::BadIterations(iterations));
+    }
+    let (header, ciphertext) = blob.split_at(HEADER_LEN);
+    let salt = &header[9..9 + SALT_LEN];
+    let nonce = &header[9 + SALT_LEN..];
+
+    let key = derive_key(passphrase, salt, iterations);
+    let plaintext = Zeroizing::new(
+        ChaCha20Poly1305::new(Key::from_slice(&key[..]))
+            .decrypt(
+                Nonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: header,
+                },
+            )
+            .map_err(|_| UnsealError::WrongPassphrase)?,
+    );
+    // Authenticated plaintexts were produced from strings
+    str::from_utf8(&plaintext)
+        .map(|s| Zeroizing::new(s.to_owned()))
+        .map_err(|_| UnsealError::Corrupted)
+}
+
+fn derive_key(passphrase: &[u8], salt: &[u8], iterations: u32) -> Zeroizing<[u8; 32]> {
+    let mut key = Zeroizing::new([0u8; 32]);
+    pbkdf2::pbkdf2_hmac::<sha2::Sha256>(passphrase, salt, iterations, &mut key[..]);
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::NoExt;
+
+    #[test]
+    fn pbkdf2_vector() {
+        // RFC 7914, section 11
+        let key = derive_key(b"passwd", b"salt", 1);
+        let expected = "55ac046e56e3089fec1691c22544b605f94185216dde0465e68b9d57c20dacbc";
+        assert_eq!(elements::hashes::hex::ToHex::to_hex(&key[..]), expected);
+    }
+
+    #[test]
+    fn seal_roundtrip() {
+        let secp = secp256k1_zkp::Secp256k1::signing_only();
+        let s = "elwsh(multi(1,xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi/1/2/*,xpub69H7F5d8KSRgmmdJg2KhpAK8SR3DjMwAdkxj3ZuxV27CprR9LgpeyGmXUbC6wb7ERfvrnKZjXoUmmDznezpbZb7ap6r1D3tgFxHmwMkQTPH/3/4/*))";
+        let (desc, key_map) =
+            Descriptor::<DescriptorPublicKey, NoExt>::parse_descriptor(&secp, s).unwrap();
+
+        // Few iterations to keep the test fast, the count is part of the blob
+        let blob = seal(&desc.to_string_with_secret(&key_map), b"hunter2", 10);
+        let (unsealed, unsealed_keys) =
+            Descriptor::<DescriptorPublicKey, NoExt>::unseal_with_secret(&secp, &blob, "hunter2")
+                .unwrap();
+        assert_eq!(unsealed, desc);
+        assert_eq!(unsealed_keys, key_map);
+        // Salts are random
+        assert_ne!(
+            blob,
+            seal(&desc.to_string_with_secret(&key_map), b"hunter2", 10)
+        );
+
+        let public = Descriptor::<DescriptorPublicKey, NoExt>::from_str(&desc.to_string()).unwrap();
+        let blob = seal(&public.to_string(), b"hunter2", 10);
+        assert_eq!(Descriptor::unseal(&blob, "hunter2").unwrap(), public);
+        assert!(Descriptor::<DescriptorPublicKey, NoExt>::unseal(&blob, "hunter3").is_err());
+    }
+
+    // Replace the checksum of `blob` after editing it
+    fn rechecksum(blob: &mut Vec<u8>) {
+        blob.truncate(blob.len() - CHECKSUM_LEN);
+        let checksum = sha256d::Hash::hash(blob);
+        blob.extend(&checksum[..CHECKSUM_LEN]);
+    }
+
+    #[test]
+    fn unseal_errors() {
+        let blob = seal("elpk(A)", b"pass", 10);
+        assert_eq!(*unseal(&blob, b"pass").unwrap(), "elpk(A)");
+        assert_eq!(unseal(&blob, b"word"), Err(UnsealError::WrongPassphrase));
+        assert_eq!(unseal(&blob[..20], b"pass"), Err(UnsealError::Corrupted));
+
+        let mut flipped = blob.clone();
+        flipped[HEADER_LEN] ^= 1;
+        assert_eq!(unseal(&flipped, b"pass"), Err(UnsealError::Corrupted));
+        // The header is authenticated, a valid checksum does not help
+        rechecksum(&mut flipped);
+        assert_eq!(unseal(&flipped, b"pass"), Err(UnsealError::WrongPassphrase));
+        let mut nonce = blob.clone();
+        nonce[HEADER_LEN - 1] ^= 1;
+        rechecksum(&mut nonce);
+        assert_eq!(unseal(&nonce, b"pass"), Err(UnsealError::WrongPassphrase));
+
+        // Unknown versions are only reported for intact blobs
+        let mut future = blob.clone();
+        future[4] = 2;
+        rechecksum(&mut future);
+        assert_eq!(
+            unseal(&future, b"pass"),
+            Err(UnsealError::UnknownVersion(2))
+        );
+
+        // Iteration counts are bounded before deriving the key
+        for &iterations in &[0, MAX_ITERATIONS + 1, u32::max_value()] {
+            let mut costly = blob.clone();
+            costly[5..9].copy_from_slice(&iterations.to_be_bytes());
+            rechecksum(&mut costly);
+            assert_eq!(
+                unseal(&costly, b"pass"),
+                Err(UnsealError::BadIterations(iterations))
+            );
+        }
+    }
+}
@@ -27,6 +27,7 @@ use super::{SortedMultiVec, ELMTS_STR};
 use crate::descriptor::checksum;
 use crate::expression::{self, FromTree};
 use crate::miniscript::context::{ScriptContext, ScriptContextError};
+use crate::miniscript::satisfy::{checked_ecdsa_sig, Timelocks};
 use crate::policy::{semantic, Liftable};
 use crate::util::varint_len;
 use crate::{
@@ -184,13 +185,7 @@ impl<Pk: MiniscriptKey + ToPublicKey> Wsh<Pk> {
     where
         S: Satisfier<Pk>,
     {
-        let mut witness = match self.inner {
-            WshInner::SortedMulti(ref smv) => smv.satisfy(satisfier)?,
-            WshInner::Ms(ref ms) => ms.satisfy(satisfier)?,
-        };
-        let witness_script = self.inner_script();
-        witness.push(witness_script.into_bytes());
-        let script_sig = Script::new();
+        let (witness, script_sig, _) = self.satisfy_timelocked(satisfier, false)?;
         Ok((witness, script_sig))
     }
 
@@ -201,13 +196,27 @@ impl<Pk: MiniscriptKey + ToPublicKey> Wsh<Pk> {
     where
         S: Satisfier<Pk>,
     {
-        let mut witness = match self.inner {
-            WshInner::SortedMulti(ref smv) => smv.satisfy(satisfier)?,
-            WshInner::Ms(ref ms) => ms.satisfy_malleable(satisfier)?,
+        let (witness, script_sig, _) = self.satisfy_timelocked(satisfier, true)?;
+        Ok((witness, script_sig))
+    }
+
+    // Helper function for the satisfaction methods, also returning the
+    // timelocks of the chosen spend path
+    pub(super) fn satisfy_timelocked<S>(
+        &self,
+        satisfier: S,
+        allow_mall: bool,
+    ) -> Result<(Vec<Vec<u8>>, Script, Timelocks), Error>
+    where
+        S: Satisfier<Pk>,
+    {
+        let (mut witness, timelocks) = match self.inner {
+            WshInner::SortedMulti(ref smv) => (smv.satisfy(satisfier)?, Timelocks::default()),
+            WshInner::Ms(ref ms) => ms.satisfy_timelocked(satisfier, allow_mall)?,
         };
         witness.push(self.inner_script().into_bytes());
         let script_sig = Script::new();
-        Ok((witness, script_sig))
+        Ok((witness, script_sig, timelocks))
     }
 }
 
@@ -329,6 +338,7 @@ impl<Pk: MiniscriptKey> Wpkh<Pk> {
                 pk.to_string(),
             )))
         } else {
+            Segwitv0::check_pk(&pk).map_err(Error::ContextError)?;
             Ok(Self { pk })
         }
     }
@@ -433,7 +443,7 @@ impl<Pk: MiniscriptKey + ToPublicKey> Wpkh<Pk> {
     where
         S: Satisfier<Pk>,
     {
-        if let Some(sig) = satisfier.lookup_ecdsa_sig(&self.pk) {
+        if let Some(sig) = checked_ecdsa_sig(&satisfier, &self.pk)? {
             let sig_vec = elementssig_to_rawsig(&sig);
             let script_sig = Script::new();
             let witness = vec![sig_vec, self.pk.to_public_key().to_bytes()];
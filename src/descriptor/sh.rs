@@ -27,6 +27,8 @@ use super::{SortedMultiVec, Wpkh, Wsh, ELMTS_STR};
 use crate::descriptor::checksum;
 use crate::expression::{self, FromTree};
 use crate::miniscript::context::ScriptContext;
+use crate::miniscript::limits::WITNESS_SCALE_FACTOR;
+use crate::miniscript::satisfy::Timelocks;
 use crate::policy::{semantic, Liftable};
 use crate::util::{varint_len, witness_to_scriptsig};
 use crate::{
@@ -226,20 +228,20 @@ impl<Pk: MiniscriptKey> Sh<Pk> {
     pub fn max_satisfaction_weight(&self) -> Result<usize, Error> {
         Ok(match self.inner {
             // add weighted script sig, len byte stays the same
-            ShInner::Wsh(ref wsh) => 4 * 35 + wsh.max_satisfaction_weight()?,
+            ShInner::Wsh(ref wsh) => WITNESS_SCALE_FACTOR * 35 + wsh.max_satisfaction_weight()?,
             ShInner::SortedMulti(ref smv) => {
                 let ss = smv.script_size();
                 let ps = push_opcode_size(ss);
                 let scriptsig_len = ps + ss + smv.max_satisfaction_size();
-                4 * (varint_len(scriptsig_len) + scriptsig_len)
+                WITNESS_SCALE_FACTOR * (varint_len(scriptsig_len) + scriptsig_len)
             }
             // add weighted script sig, len byte stays the same
-            ShInner::Wpkh(ref wpkh) => 4 * 23 + wpkh.max_satisfaction_weight(),
+            ShInner::Wpkh(ref wpkh) => WITNESS_SCALE_FACTOR * 23 + wpkh.max_satisfaction_weight(),
             ShInner::Ms(ref ms) => {
                 let ss = ms.script_size();
                 let ps = push_opcode_size(ss);
                 let scriptsig_len = ps + ss + ms.max_satisfaction_size()?;
-                4 * (varint_len(scriptsig_len) + scriptsig_len)
+                WITNESS_SCALE_FACTOR * (varint_len(scriptsig_len) + scriptsig_len)
             }
         })
     }
@@ -330,54 +332,56 @@ impl<Pk: MiniscriptKey + ToPublicKey> Sh<Pk> {
     where
         S: Satisfier<Pk>,
     {
-        let script_sig = self.unsigned_script_sig();
-        match self.inner {
-            ShInner::Wsh(ref wsh) => {
-                let (witness, _) = wsh.get_satisfaction(satisfier)?;
-                Ok((witness, script_sig))
-            }
-            ShInner::Wpkh(ref wpkh) => {
-                let (witness, _) = wpkh.get_satisfaction(satisfier)?;
-                Ok((witness, script_sig))
-            }
-            ShInner::SortedMulti(ref smv) => {
-                let mut script_witness = smv.satisfy(satisfier)?;
-                script_witness.push(smv.encode().into_bytes());
-                let script_sig = witness_to_scriptsig(&script_witness);
-                let witness = vec![];
-                Ok((witness, script_sig))
-            }
-            ShInner::Ms(ref ms) => {
-                let mut script_witness = ms.satisfy(satisfier)?;
-                script_witness.push(ms.encode().into_bytes());
-                let script_sig = witness_to_scriptsig(&script_witness);
-                let witness = vec![];
-                Ok((witness, script_sig))
-            }
-        }
+        let (witness, script_sig, _) = self.satisfy_timelocked(satisfier, false)?;
+        Ok((witness, script_sig))
     }
 
     /// Returns satisfying, possibly malleable, witness and scriptSig with
     /// minimum weight to spend an output controlled by the given descriptor if
     /// it is possible to construct one using the `satisfier`.
     pub fn get_satisfaction_mall<S>(&self, satisfier: S) -> Result<(Vec<Vec<u8>>, Script), Error>
+    where
+        S: Satisfier<Pk>,
+    {
+        let (witness, script_sig, _) = self.satisfy_timelocked(satisfier, true)?;
+        Ok((witness, script_sig))
+    }
+
+    // Helper function for the satisfaction methods, also returning the
+    // timelocks of the chosen spend path
+    pub(super) fn satisfy_timelocked<S>(
+        &self,
+        satisfier: S,
+        allow_mall: bool,
+    ) -> Result<(Vec<Vec<u8>>, Script, Timelocks), Error>
     where
         S: Satisfier<Pk>,
     {
         let script_sig = self.unsigned_script_sig();
         match self.inner {
             ShInner::Wsh(ref wsh) => {
-                let (witness, _) = wsh.get_satisfaction_mall(satisfier)?;
-                Ok((witness, script_sig))
+                let (witness, _, timelocks) = wsh.satisfy_timelocked(satisfier, allow_mall)?;
+                Ok((witness, script_sig, timelocks))
+            }
+            ShInner::Wpkh(ref wpkh) => {
+                let (witness, _) = wpkh.get_satisfaction(satisfier)?;
+                Ok((witness, script_sig, Timelocks::default()))
+            }
+            ShInner::SortedMulti(ref smv) => {
+                let mut script_witness = smv.satisfy(satisfier)?;
+                script_witness.push(smv.encode().into_bytes());
+                let script_sig = witness_to_scriptsig(&script_witness);
+                let witness = vec![];
+                Ok((witness, script_sig, Timelocks::default()))
             }
             ShInner::Ms(ref ms) => {
-                let mut script_witness = ms.satisfy_malleable(satisfier)?;
+                let (mut script_witness, timelocks) =
+                    ms.satisfy_timelocked(satisfier, allow_mall)?;
                 script_witness.push(ms.encode().into_bytes());
                 let script_sig = witness_to_scriptsig(&script_witness);
                 let witness = vec![];
-                Ok((witness, script_sig))
+                Ok((witness, script_sig, timelocks))
             }
-            _ => self.get_satisfaction(satisfier),
         }
     }
 }
@@ -0,0 +1,157 @@
+// Miniscript
+// Written in 2023 by
+//     The Elements Miniscript developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Descriptor Shapes
+//!
+//! The structure of a descriptor with its keys erased, which allows the
+//! participants of a multisig setup to agree on the fragments, thresholds
+//! and timelocks before exchanging any keys.
+//!
+
+use std::convert::Infallible;
+use std::{fmt, str};
+
+use super::Descriptor;
+use crate::{Error, Extension, MiniscriptKey, NoExt, TranslatePk, Translator};
+
+/// Placeholder for an erased key, displayed and parsed as `_`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ErasedKey;
+
+impl fmt::Display for ErasedKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("_")
+    }
+}
+
+impl str::FromStr for ErasedKey {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "_" {
+            Ok(ErasedKey)
+        } else {
+            Err(Error::Unexpected(format!("{} is not an erased key", s)))
+        }
+    }
+}
+
+impl MiniscriptKey for ErasedKey {
+    type Sha256 = String;
+    type Hash256 = String;
+    type Ripemd160 = String;
+    type Hash160 = String;
+}
+
+/// A descriptor with all keys replaced by [`ErasedKey`].
+///
+/// Hashes are kept, as are any keys that are arguments of extensions.
+pub type DescriptorShape<Ext = NoExt> = Descriptor<ErasedKey, Ext>;
+
+impl<Pk: MiniscriptKey, Ext: Extension> Descriptor<Pk, Ext> {
+    /// The shape of this descriptor, with all keys erased
+    pub fn shape(&self) -> DescriptorShape<Ext> {
+        match self.translate_pk(&mut EraseKeys) {
+            Ok(shape) => shape,
+            Err(e) => match e {},
+        }
+    }
+
+    /// Whether `other` has the same shape as this descriptor, i.e. whether
+    /// they are equal up to their keys
+    pub fn shape_eq<Q: MiniscriptKey>(&self, other: &Descriptor<Q, Ext>) -> bool {
+        self.shape() == other.shape()
+    }
+}
+
+pub(crate) struct EraseKeys;
+
+impl<Pk: MiniscriptKey> Translator<Pk, ErasedKey, Infallible> for EraseKeys {
+    fn pk(&mut self, _: &Pk) -> Result<ErasedKey, Infallible> {
+        Ok(ErasedKey)
+    }
+
+    fn sha256(&mut self, sha256: &Pk::Sha256) -> Result<String, Infallible> {
+        Ok(sha256.to_string())
+    }
+
+    fn hash256(&mut self, hash256: &Pk::Hash256) -> Result<String, Infallible> {
+        Ok(hash256.to_string())
+    }
+
+    fn ripemd160(&mut self, ripemd160: &Pk::Ripemd160) -> Result<String, Infallible> {
+        Ok(ripemd160.to_string())
+    }
+
+    fn hash160(&mut self, hash160: &Pk::Hash160) -> Result<String, Infallible> {
+        Ok(hash160.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::descriptor::DescriptorPublicKey;
+
+    #[test]
+    fn shapes() {
+        let xpub = |i: usize| {
+            [
+                "xpub661MyMwAqRbcFW31YEwpkMuc5THy2PSt5bDMsktWQcFF8syAmRUapSCGu8ED9W6oDMSgv6Zz8idoc4a6mr8BDzTJY47LJhkJ8UB7WEGuduB",
+                "xpub69H7F5d8KSRgmmdJg2KhpAK8SR3DjMwAdkxj3ZuxV27CprR9LgpeyGmXUbC6wb7ERfvrnKZjXoUmmDznezpbZb7ap6r1D3tgFxHmwMkQTPH",
+            ][i]
+            .to_owned()
+                + "/0/*"
+        };
+        let desc = |s: &str| Descriptor::<DescriptorPublicKey, NoExt>::from_str(s).unwrap();
+
+        let a = desc(&format!(
+            "elwsh(or_d(multi(2,{},{}),older(144)))",
+            xpub(0),
+            xpub(1)
+        ));
+        let b = desc(&format!(
+            "elwsh(or_d(multi(2,{},{}),older(144)))",
+            xpub(1),
+            xpub(0)
+        ));
+        let c = desc(&format!(
+            "elwsh(or_d(multi(1,{},{}),older(144)))",
+            xpub(0),
+            xpub(1)
+        ));
+        assert!(a.shape_eq(&b));
+        assert!(!a.shape_eq(&c));
+        assert_eq!(
+            format!("{:#}", a.shape()),
+            "elwsh(or_d(multi(2,_,_),older(144)))"
+        );
+
+        // Shapes can be exchanged as strings and compared to descriptors
+        let shape = DescriptorShape::<NoExt>::from_str(&a.shape().to_string()).unwrap();
+        assert_eq!(shape, a.shape());
+        let shape = DescriptorShape::<NoExt>::from_str("eltr(_,{pk(_),pk(_)})").unwrap();
+        let tr = desc(&format!(
+            "eltr({},{{pk({}),pk({})}})",
+            xpub(0),
+            xpub(1),
+            xpub(0)
+        ));
+        assert!(tr.shape_eq(&shape));
+        assert!(!tr.shape_eq(&a));
+        assert!(DescriptorShape::<NoExt>::from_str("elpk(A)").is_err());
+    }
+}
@@ -0,0 +1,307 @@
+// Miniscript
+// Written in 2023 by
+//     The Elements Miniscript developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Sighash Analysis
+//!
+//! Detect taproot leaves whose covenant checks only protect the coins when
+//! the transaction signatures commit to the rest of the transaction, so that
+//! signing them with `SIGHASH_NONE`, `SIGHASH_SINGLE` or
+//! `SIGHASH_ANYONECANPAY` is unsafe.
+//!
+
+use elements::SchnorrSigHashType;
+
+use super::Descriptor;
+use crate::extensions::{
+    Arith, AssetExpr, CovOps, Expr, ExprInner, ExtParam, IdxExpr, SpkExpr, ValueExpr,
+};
+use crate::{CovenantExt, Extension, Miniscript, MiniscriptKey, SighashPolicy, Tap, Terminal};
+
+/// A way in which the security of a taproot leaf depends on the sighash
+/// types of its signatures
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SighashHazard {
+    /// The leaf checks an oracle signature over a fixed message with `csfs`.
+    /// The attestation does not commit to the spending transaction, so once
+    /// it is published, it can be reused with any outputs which the
+    /// transaction signatures do not commit to.
+    ReplayableAttestation,
+    /// The leaf introspects outputs at fixed indices. When several coins
+    /// locked by the leaf are spent together, all their checks can be
+    /// satisfied by the same outputs, unless the transaction signatures
+    /// commit to all the inputs.
+    SharedOutputs,
+}
+
+/// A hazard of a taproot leaf, as reported by [`Descriptor::sighash_lint`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SighashWarning {
+    /// The hazard
+    pub hazard: SighashHazard,
+    /// Index of the leaf in the depth first order of
+    /// [`Tr::iter_scripts`](super::Tr::iter_scripts)
+    pub leaf: usize,
+    /// Whether every satisfaction of the leaf contains a signature. If not,
+    /// the leaf is exposed whatever the sighash types of its signatures.
+    pub signature_required: bool,
+    /// The allowed sighash types which expose the leaf
+    pub sighash_types: Vec<SchnorrSigHashType>,
+}
+
+// Sighash types which do not commit to all outputs
+fn skips_outputs(ty: SchnorrSigHashType) -> bool {
+    match ty {
+        SchnorrSigHashType::None
+        | SchnorrSigHashType::Single
+        | SchnorrSigHashType::NonePlusAnyoneCanPay
+        | SchnorrSigHashType::SinglePlusAnyoneCanPay => true,
+        _ => false,
+    }
+}
+
+// Sighash types which do not commit to all inputs
+fn skips_inputs(ty: SchnorrSigHashType) -> bool {
+    match ty {
+        SchnorrSigHashType::AllPlusAnyoneCanPay
+        | SchnorrSigHashType::NonePlusAnyoneCanPay
+        | SchnorrSigHashType::SinglePlusAnyoneCanPay => true,
+        _ => false,
+    }
+}
+
+// Whether every satisfaction of the fragment contains a signature for the
+// transaction. Fragments which cannot be satisfied are reported as not
+// requiring one, so that the analysis errs on the side of warning.
+fn signature_required<Pk: MiniscriptKey, Ext: Extension>(ms: &Miniscript<Pk, Tap, Ext>) -> bool {
+    match ms.node {
+        Terminal::PkK(..) | Terminal::PkH(..) | Terminal::RawPkH(..) => true,
+        Terminal::Multi(k, _) | Terminal::MultiA(k, _) => k > 0,
+        Terminal::Alt(ref sub)
+        | Terminal::Swap(ref sub)
+        | Terminal::Check(ref sub)
+        | Terminal::DupIf(ref sub)
+        | Terminal::Verify(ref sub)
+        | Terminal::NonZero(ref sub)
+        | Terminal::ZeroNotEqual(ref sub) => signature_required(sub),
+        Terminal::AndV(ref left, ref right) | Terminal::AndB(ref left, ref right) => {
+            signature_required(left) || signature_required(right)
+        }
+        Terminal::AndOr(ref a, ref b, ref c) => {
+            (signature_required(a) || signature_required(b)) && signature_required(c)
+        }
+        Terminal::OrB(ref left, ref right)
+        | Terminal::OrD(ref left, ref right)
+        | Terminal::OrC(ref left, ref right)
+        | Terminal::OrI(ref left, ref right) => {
+            signature_required(left) && signature_required(right)
+        }
+        Terminal::Thresh(k, ref subs) => {
+            subs.iter().filter(|sub| !signature_required(sub)).count() < k
+        }
+        _ => false,
+    }
+}
+
+fn fixed_index(idx: &IdxExpr) -> bool {
+    match *idx {
+        IdxExpr::Const(_) => true,
+        IdxExpr::CurrIdx => false,
+        IdxExpr::Add(ref x, ref y)
+        | IdxExpr::Sub(ref x, ref y)
+        | IdxExpr::Mul(ref x, ref y)
+        | IdxExpr::Div(ref x, ref y) => fixed_index(x) && fixed_index(y),
+    }
+}
+
+fn asset_fixed_output<T: ExtParam>(e: &AssetExpr<T>) -> bool {
+    match *e {
+        AssetExpr::Output(ref idx) => fixed_index(idx),
+        _ => false,
+    }
+}
+
+fn value_fixed_output<T: ExtParam>(e: &ValueExpr<T>) -> bool {
+    match *e {
+        ValueExpr::Output(ref idx) => fixed_index(idx),
+        _ => false,
+    }
+}
+
+fn spk_fixed_output<T: ExtParam>(e: &SpkExpr<T>) -> bool {
+    match *e {
+        SpkExpr::Output(ref idx) => fixed_index(idx),
+        _ => false,
+    }
+}
+
+fn expr_fixed_output(e: &Expr) -> bool {
+    match *e.as_inner() {
+        ExprInner::Output(ref idx) => fixed_index(idx),
+        ExprInner::Const(_)
+        | ExprInner::CurrInputIdx
+        | ExprInner::Input(_)
+        | ExprInner::InputIssue(_)
+        | ExprInner::InputReIssue(_) => false,
+        ExprInner::Add(ref x, ref y)
+        | ExprInner::Sub(ref x, ref y)
+        | ExprInner::Mul(ref x, ref y)
+        | ExprInner::Div(ref x, ref y)
+        | ExprInner::Mod(ref x, ref y)
+        | ExprInner::BitAnd(ref x, ref y)
+        | ExprInner::BitOr(ref x, ref y)
+        | ExprInner::Xor(ref x, ref y) => expr_fixed_output(x) || expr_fixed_output(y),
+        ExprInner::Invert(ref x) | ExprInner::Negate(ref x) => expr_fixed_output(x),
+    }
+}
+
+// Whether the extension introspects an output at an index which does not
+// depend on the index of the input being spent
+fn ext_fixed_output<T: ExtParam>(ext: &CovenantExt<T>) -> bool {
+    match *ext {
+        CovenantExt::Introspect(ref op) => match *op {
+            CovOps::IsExpAsset(ref x) => asset_fixed_output(x),
+            CovOps::IsExpValue(ref x) => value_fixed_output(x),
+            CovOps::AssetEq(ref x, ref y) => asset_fixed_output(x) || asset_fixed_output(y),
+            CovOps::ValueEq(ref x, ref y) => value_fixed_output(x) || value_fixed_output(y),
+            CovOps::SpkEq(ref x, ref y) => spk_fixed_output(x) || spk_fixed_output(y),
+            CovOps::CurrIndEq(_) | CovOps::IdxEq(..) => false,
+        },
+        CovenantExt::Arith(ref arith) => match *arith {
+            Arith::Eq(ref x, ref y)
+            | Arith::Lt(ref x, ref y)
+            | Arith::Leq(ref x, ref y)
+            | Arith::Gt(ref x, ref y)
+            | Arith::Geq(ref x, ref y) => expr_fixed_output(x) || expr_fixed_output(y),
+        },
+        CovenantExt::LegacyVerEq(..)
+        | CovenantExt::LegacyOutputsPref(..)
+        | CovenantExt::Csfs(..) => false,
+    }
+}
+
+impl<Pk: MiniscriptKey, T: ExtParam> Descriptor<Pk, CovenantExt<T>> {
+    /// Report the taproot leaves whose covenant checks can be bypassed when
+    /// their signatures use one of the sighash types allowed by `policy`,
+    /// or which need no signature at all. Pass [`SighashPolicy::any`] to
+    /// find every leaf whose security depends on the sighash types.
+    ///
+    /// Leaves are analyzed as a whole and the analysis is conservative: a
+    /// leaf may be reported even though its other checks, e.g. on all the
+    /// outputs of the transaction, make it safe. Only taproot descriptors
+    /// can use the extensions involved, so other descriptors have no
+    /// warnings.
+    pub fn sighash_lint(&self, policy: &SighashPolicy) -> Vec<SighashWarning> {
+        let tr = match *self {
+            Descriptor::TrExt(ref tr) => tr,
+            _ => return vec![],
+        };
+        let mut warnings = vec![];
+        for (leaf, (_, ms)) in tr.iter_scripts().enumerate() {
+            let signature_required = signature_required(ms);
+            let mut csfs = false;
+            let mut fixed_output = false;
+            for node in ms.iter() {
+                if let Terminal::Ext(ref ext) = node.node {
+                    csfs |= match *ext {
+                        CovenantExt::Csfs(..) => true,
+                        _ => false,
+                    };
+                    fixed_output |= ext_fixed_output(ext);
+                }
+            }
+
+            let mut warn = |hazard, exposes: fn(SchnorrSigHashType) -> bool| {
+                let sighash_types: Vec<_> = policy
+                    .schnorr
+                    .iter()
+                    .cloned()
+                    .filter(|ty| exposes(*ty))
+                    .collect();
+                if !signature_required || !sighash_types.is_empty() {
+                    warnings.push(SighashWarning {
+                        hazard,
+                        leaf,
+                        signature_required,
+                        sighash_types,
+                    });
+                }
+            };
+            if csfs {
+                warn(SighashHazard::ReplayableAttestation, skips_outputs);
+            }
+            if fixed_output {
+                warn(SighashHazard::SharedOutputs, skips_inputs);
+            }
+        }
+        warnings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::extensions::CovExtArgs;
+
+    type CovDescriptor = Descriptor<String, CovenantExt<CovExtArgs>>;
+
+    const MSG: &str = "a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2";
+    const ORACLE: &str = "cc8a4bc64d897bddc5fbc2f670f7a8ba0b386779106cf1223c6fc5d7cd6fc115";
+
+    #[test]
+    fn sighash_lint() {
+        let desc = CovDescriptor::from_str(&format!(
+            "eltr(X,{{and_v(v:pk(A),csfs({},{})),pk(B)}})",
+            ORACLE, MSG
+        ))
+        .unwrap();
+        assert!(desc.sighash_lint(&SighashPolicy::all_only()).is_empty());
+        let warnings = desc.sighash_lint(&SighashPolicy::any());
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].hazard, SighashHazard::ReplayableAttestation);
+        assert_eq!(warnings[0].leaf, 0);
+        assert!(warnings[0].signature_required);
+        assert_eq!(warnings[0].sighash_types.len(), 4);
+
+        // Without a signature the attestation can always be replayed
+        let desc =
+            CovDescriptor::from_str(&format!("eltr(X,or_b(pk(A),a:csfs({},{})))", ORACLE, MSG))
+                .unwrap();
+        let warnings = desc.sighash_lint(&SighashPolicy::all_only());
+        assert_eq!(warnings.len(), 1);
+        assert!(!warnings[0].signature_required);
+
+        // Introspection of outputs relative to the current input is fine
+        let desc = CovDescriptor::from_str(
+            "eltr(X,{and_v(v:pk(A),spk_eq(out_spk(curr_idx),curr_inp_spk)),and_v(v:pk(B),spk_eq(out_spk(1),curr_inp_spk))})",
+        )
+        .unwrap();
+        let warnings = desc.sighash_lint(&SighashPolicy::any());
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].hazard, SighashHazard::SharedOutputs);
+        assert_eq!(warnings[0].leaf, 1);
+        assert_eq!(
+            warnings[0].sighash_types,
+            vec![
+                SchnorrSigHashType::AllPlusAnyoneCanPay,
+                SchnorrSigHashType::NonePlusAnyoneCanPay,
+                SchnorrSigHashType::SinglePlusAnyoneCanPay,
+            ]
+        );
+
+        let desc = CovDescriptor::from_str("elwsh(pk(A))").unwrap();
+        assert!(desc.sighash_lint(&SighashPolicy::any()).is_empty());
+    }
+}
@@ -270,4 +270,47 @@ mod tests {
             other => panic!("unexpected error: {:?}", other),
         }
     }
+
+    #[test]
+    fn too_many_p2sh_pubkeys() {
+        use crate::descriptor::Descriptor;
+        use crate::miniscript::context::ScriptContextError;
+        use crate::miniscript::limits::MAX_PUBKEYS_PER_P2SH_MULTISIG;
+        use crate::Segwitv0;
+
+        let keys: Vec<String> = (1..=16u8)
+            .map(|i| {
+                let secp = elements::secp256k1_zkp::Secp256k1::signing_only();
+                let sk = elements::secp256k1_zkp::SecretKey::from_slice(&[i; 32]).unwrap();
+                elements::secp256k1_zkp::PublicKey::from_secret_key(&secp, &sk).to_string()
+            })
+            .collect();
+        let pks: Vec<PublicKey> = keys
+            .iter()
+            .map(|k| PublicKey::from_str(k).unwrap())
+            .collect();
+
+        // 15 keys fit in a redeem script, 16 do not
+        let max = MAX_PUBKEYS_PER_P2SH_MULTISIG;
+        assert!(SortedMultiVec::<PublicKey, Legacy>::new(1, pks[..max].to_vec()).is_ok());
+        match SortedMultiVec::<PublicKey, Legacy>::new(1, pks.clone()) {
+            Err(Error::ContextError(ScriptContextError::P2shMultiSigLimitExceeded(16))) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert!(SortedMultiVec::<PublicKey, Segwitv0>::new(1, pks).is_ok());
+
+        for frag in &["multi", "sortedmulti"] {
+            let desc = format!("elsh({}(1,{}))", frag, keys.join(","));
+            let err = Descriptor::<PublicKey>::from_str(&desc).unwrap_err();
+            assert!(
+                err.to_string().contains("up to 15 pubkeys, found 16"),
+                "{}",
+                err
+            );
+            let desc = format!("elsh({}(1,{}))", frag, keys[..max].join(","));
+            assert!(Descriptor::<PublicKey>::from_str(&desc).is_ok());
+            let desc = format!("elsh(wsh({}(1,{})))", frag, keys.join(","));
+            assert!(Descriptor::<PublicKey>::from_str(&desc).is_ok());
+        }
+    }
 }
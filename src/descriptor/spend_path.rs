@@ -0,0 +1,872 @@
+// Miniscript
+// Written in 2023 by
+//     The Elements Miniscript developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Spend Paths
+//!
+//! Machine readable description of the ways a descriptor can be spent,
+//! for spend planning and user interfaces.
+//!
+
+use std::cmp;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::str::FromStr;
+
+use elements::taproot::{LeafVersion, TapLeafHash};
+use elements::{PackedLockTime, Sequence};
+
+use super::tr::{leaf_satisfaction_weight, KEY_SPEND_WEIGHT};
+use super::{Descriptor, Tr};
+use crate::extensions::ParseableExt;
+use crate::policy::{semantic, Liftable};
+use crate::{expression, Error, Extension, MiniscriptKey, ToPublicKey};
+
+/// The maximum number of spend paths listed by [`Descriptor::spend_paths`].
+///
+/// The number of paths grows exponentially with nested thresholds, so
+/// listing them errors with [`Error::TooManySpendPaths`] past this.
+pub const MAX_SPEND_PATHS: usize = 10_000;
+
+/// A hash whose preimage must be revealed on a spend path
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SpendHash<Pk: MiniscriptKey> {
+    /// SHA256 hash
+    Sha256(Pk::Sha256),
+    /// Double SHA256 hash
+    Hash256(Pk::Hash256),
+    /// RIPEMD160 hash
+    Ripemd160(Pk::Ripemd160),
+    /// HASH160 hash
+    Hash160(Pk::Hash160),
+}
+
+impl<Pk: MiniscriptKey> fmt::Display for SpendHash<Pk> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            SpendHash::Sha256(ref h) => write!(f, "sha256({})", h),
+            SpendHash::Hash256(ref h) => write!(f, "hash256({})", h),
+            SpendHash::Ripemd160(ref h) => write!(f, "ripemd160({})", h),
+            SpendHash::Hash160(ref h) => write!(f, "hash160({})", h),
+        }
+    }
+}
+
+impl_from_str!(
+    SpendHash<Pk>,
+    type Err = Error;,
+    fn from_str(s: &str) -> Result<SpendHash<Pk>, Error> {
+        let top = expression::Tree::from_str(s)?;
+        match (top.name, top.args.len()) {
+            ("sha256", 1) => expression::terminal(&top.args[0], |x| {
+                Pk::Sha256::from_str(x).map(SpendHash::Sha256)
+            }),
+            ("hash256", 1) => expression::terminal(&top.args[0], |x| {
+                Pk::Hash256::from_str(x).map(SpendHash::Hash256)
+            }),
+            ("ripemd160", 1) => expression::terminal(&top.args[0], |x| {
+                Pk::Ripemd160::from_str(x).map(SpendHash::Ripemd160)
+            }),
+            ("hash160", 1) => expression::terminal(&top.args[0], |x| {
+                Pk::Hash160::from_str(x).map(SpendHash::Hash160)
+            }),
+            _ => Err(Error::Unexpected(s.to_owned())),
+        }
+    }
+);
+
+/// A timelock which must be satisfied on a spend path
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SpendTimelock {
+    /// An absolute timelock
+    After(PackedLockTime),
+    /// A relative timelock
+    Older(Sequence),
+}
+
+impl fmt::Display for SpendTimelock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            SpendTimelock::After(t) => write!(f, "after({})", t.0),
+            SpendTimelock::Older(t) => write!(f, "older({})", t.to_consensus_u32()),
+        }
+    }
+}
+
+impl FromStr for SpendTimelock {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let top = expression::Tree::from_str(s)?;
+        match (top.name, top.args.len()) {
+            ("after", 1) => expression::terminal(&top.args[0], |x| {
+                expression::parse_num(x).map(|x| SpendTimelock::After(PackedLockTime(x)))
+            }),
+            ("older", 1) => expression::terminal(&top.args[0], |x| {
+                expression::parse_num(x).map(|x| SpendTimelock::Older(Sequence(x)))
+            }),
+            _ => Err(Error::Unexpected(s.to_owned())),
+        }
+    }
+}
+
+/// A way of spending a descriptor, see [`Descriptor::spend_paths`].
+///
+/// With the `serde` feature, spend paths serialize to a map with the same
+/// field names, in which keys are serialized as strings, hashes and
+/// timelocks as their descriptor fragments (e.g. `older(144)`) and the leaf
+/// as hex string or null.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpendPath<Pk: MiniscriptKey> {
+    /// Keys of which `threshold` must sign
+    pub keys: Vec<Pk>,
+    /// Number of signatures required from `keys`
+    pub threshold: usize,
+    /// Hashes whose preimages must be revealed
+    pub hashes: Vec<SpendHash<Pk>>,
+    /// Timelocks which must be satisfied
+    pub timelocks: Vec<SpendTimelock>,
+    /// Hash of the spent tap leaf, for script path spends of taproot
+    /// descriptors
+    pub leaf: Option<TapLeafHash>,
+    /// Maximum satisfaction weight of the script this path is part of,
+    /// estimated like [`Descriptor::max_satisfaction_weight`]
+    pub est_weight: usize,
+}
+
+impl<Pk: MiniscriptKey> SpendPath<Pk> {
+    fn trivial() -> Self {
+        SpendPath {
+            keys: vec![],
+            threshold: 0,
+            hashes: vec![],
+            timelocks: vec![],
+            leaf: None,
+            est_weight: 0,
+        }
+    }
+
+    fn with_keys(keys: Vec<Pk>, threshold: usize) -> Self {
+        SpendPath {
+            keys,
+            threshold,
+            ..SpendPath::trivial()
+        }
+    }
+
+    /// The sets of keys, all of which must sign, that satisfy the threshold
+    fn key_sets(&self) -> Result<Vec<Vec<Pk>>, Error> {
+        Ok(combinations(self.keys.len(), self.threshold)?
+            .into_iter()
+            .map(|c| c.into_iter().map(|i| self.keys[i].clone()).collect())
+            .collect())
+    }
+
+    /// The paths requiring everything both `self` and `other` require
+    fn and(&self, other: &Self) -> Result<Vec<Self>, Error> {
+        let mut hashes = self.hashes.clone();
+        hashes.extend(
+            other
+                .hashes
+                .iter()
+                .filter(|h| !self.hashes.contains(h))
+                .cloned(),
+        );
+        let mut timelocks = self.timelocks.clone();
+        timelocks.extend(
+            other
+                .timelocks
+                .iter()
+                .filter(|t| !self.timelocks.contains(t)),
+        );
+
+        let key_sets = if other.keys.is_empty() {
+            vec![(self.keys.clone(), self.threshold)]
+        } else if self.keys.is_empty() {
+            vec![(other.keys.clone(), other.threshold)]
+        } else {
+            // Thresholds cannot be combined, so fall back to all-of sets
+            let (self_sets, other_sets) = (self.key_sets()?, other.key_sets()?);
+            check_count(self_sets.len().saturating_mul(other_sets.len()))?;
+            let mut key_sets = vec![];
+            for a in &self_sets {
+                for b in &other_sets {
+                    let mut keys = a.clone();
+                    keys.extend(b.iter().filter(|pk| !a.contains(pk)).cloned());
+                    let threshold = keys.len();
+                    key_sets.push((keys, threshold));
+                }
+            }
+            key_sets
+        };
+        Ok(key_sets
+            .into_iter()
+            .map(|(keys, threshold)| SpendPath {
+                keys,
+                threshold,
+                hashes: hashes.clone(),
+                timelocks: timelocks.clone(),
+                ..SpendPath::trivial()
+            })
+            .collect())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<Pk: MiniscriptKey> crate::serde::Serialize for SpendPath<Pk> {
+    fn serialize<S: crate::serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use crate::serde::ser::SerializeStruct;
+
+        fn strings<T: fmt::Display>(v: &[T]) -> Vec<String> {
+            v.iter().map(|x| x.to_string()).collect()
+        }
+
+        let mut s = serializer.serialize_struct("SpendPath", 6)?;
+        s.serialize_field("keys", &strings(&self.keys))?;
+        s.serialize_field("threshold", &self.threshold)?;
+        s.serialize_field("hashes", &strings(&self.hashes))?;
+        s.serialize_field("timelocks", &strings(&self.timelocks))?;
+        s.serialize_field("leaf", &self.leaf.map(|l| l.to_string()))?;
+        s.serialize_field("est_weight", &self.est_weight)?;
+        s.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, Pk> crate::serde::Deserialize<'de> for SpendPath<Pk>
+where
+    Pk: MiniscriptKey + FromStr,
+    Pk::Sha256: FromStr,
+    Pk::Hash256: FromStr,
+    Pk::Ripemd160: FromStr,
+    Pk::Hash160: FromStr,
+    <Pk as FromStr>::Err: ToString,
+    <<Pk as MiniscriptKey>::Sha256 as FromStr>::Err: ToString,
+    <<Pk as MiniscriptKey>::Hash256 as FromStr>::Err: ToString,
+    <<Pk as MiniscriptKey>::Ripemd160 as FromStr>::Err: ToString,
+    <<Pk as MiniscriptKey>::Hash160 as FromStr>::Err: ToString,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: crate::serde::de::Deserializer<'de>,
+    {
+        use std::marker::PhantomData;
+
+        use crate::serde::de::{self, MapAccess};
+
+        const FIELDS: &[&str] = &[
+            "keys",
+            "threshold",
+            "hashes",
+            "timelocks",
+            "leaf",
+            "est_weight",
+        ];
+
+        fn parse<T: FromStr, E: de::Error>(v: Vec<String>) -> Result<Vec<T>, E>
+        where
+            T::Err: ToString,
+        {
+            v.iter()
+                .map(|s| T::from_str(s).map_err(|e| E::custom(e.to_string())))
+                .collect()
+        }
+
+        fn set<T, E: de::Error>(field: &mut Option<T>, name: &'static str, v: T) -> Result<(), E> {
+            if field.is_some() {
+                return Err(E::duplicate_field(name));
+            }
+            *field = Some(v);
+            Ok(())
+        }
+
+        struct Visitor<Pk>(PhantomData<Pk>);
+
+        impl<'de, Pk> de::Visitor<'de> for Visitor<Pk>
+        where
+            Pk: MiniscriptKey + FromStr,
+            Pk::Sha256: FromStr,
+            Pk::Hash256: FromStr,
+            Pk::Ripemd160: FromStr,
+            Pk::Hash160: FromStr,
+            <Pk as FromStr>::Err: ToString,
+            <<Pk as MiniscriptKey>::Sha256 as FromStr>::Err: ToString,
+            <<Pk as MiniscriptKey>::Hash256 as FromStr>::Err: ToString,
+            <<Pk as MiniscriptKey>::Ripemd160 as FromStr>::Err: ToString,
+            <<Pk as MiniscriptKey>::Hash160 as FromStr>::Err: ToString,
+        {
+            type Value = SpendPath<Pk>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+                formatter.write_str("a spend path")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut keys = None;
+                let mut threshold = None;
+                let mut hashes = None;
+                let mut timelocks = None;
+                let mut leaf = None;
+                let mut est_weight = None;
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "keys" => set(&mut keys, "keys", parse(map.next_value()?)?)?,
+                        "threshold" => set(&mut threshold, "threshold", map.next_value()?)?,
+                        "hashes" => set(&mut hashes, "hashes", parse(map.next_value()?)?)?,
+                        "timelocks" => set(&mut timelocks, "timelocks", parse(map.next_value()?)?)?,
+                        "leaf" => {
+                            let v = match map.next_value::<Option<String>>()? {
+                                Some(ref s) => {
+                                    Some(TapLeafHash::from_str(s).map_err(de::Error::custom)?)
+                                }
+                                None => None,
+                            };
+                            set(&mut leaf, "leaf", v)?
+                        }
+                        "est_weight" => set(&mut est_weight, "est_weight", map.next_value()?)?,
+                        _ => return Err(de::Error::unknown_field(&key, FIELDS)),
+                    }
+                }
+                Ok(SpendPath {
+                    keys: keys.ok_or_else(|| de::Error::missing_field("keys"))?,
+                    threshold: threshold.ok_or_else(|| de::Error::missing_field("threshold"))?,
+                    hashes: hashes.ok_or_else(|| de::Error::missing_field("hashes"))?,
+                    timelocks: timelocks.ok_or_else(|| de::Error::missing_field("timelocks"))?,
+                    leaf: leaf.ok_or_else(|| de::Error::missing_field("leaf"))?,
+                    est_weight: est_weight.ok_or_else(|| de::Error::missing_field("est_weight"))?,
+                })
+            }
+        }
+
+        deserializer.deserialize_struct("SpendPath", FIELDS, Visitor(PhantomData))
+    }
+}
+
+/// A single-key, time-locked spend path, see [`Descriptor::refund_path`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RefundPath<Pk: MiniscriptKey> {
+    /// Key which must sign the refund
+    pub key: Pk,
+    /// Timelock which must expire before the refund can be broadcast
+    pub timelock: SpendTimelock,
+    /// Hash of the spent tap leaf, for taproot descriptors
+    pub leaf: Option<TapLeafHash>,
+}
+
+impl<Pk: MiniscriptKey> RefundPath<Pk> {
+    fn from_spend_path(path: SpendPath<Pk>) -> Option<Self> {
+        if path.keys.len() != 1
+            || path.threshold != 1
+            || !path.hashes.is_empty()
+            || path.timelocks.len() != 1
+        {
+            return None;
+        }
+        Some(RefundPath {
+            key: path.keys.into_iter().next()?,
+            timelock: path.timelocks[0],
+            leaf: path.leaf,
+        })
+    }
+}
+
+/// Everything a satisfier may be asked for when satisfying a descriptor,
+/// see [`Descriptor::requirements`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Requirements<Pk: MiniscriptKey> {
+    /// Keys whose ECDSA signatures may be needed
+    pub ecdsa_keys: BTreeSet<Pk>,
+    /// The internal key of a taproot descriptor, whose key spend signature
+    /// may be needed
+    pub tap_key_spend: Option<Pk>,
+    /// Keys and leaves of the tapscript signatures which may be needed
+    pub tap_leaf_keys: BTreeSet<(Pk, TapLeafHash)>,
+    /// Hashes whose preimages may be needed
+    pub hashes: BTreeSet<SpendHash<Pk>>,
+}
+
+impl<Pk: MiniscriptKey> Requirements<Pk> {
+    fn new() -> Self {
+        Requirements {
+            ecdsa_keys: BTreeSet::new(),
+            tap_key_spend: None,
+            tap_leaf_keys: BTreeSet::new(),
+            hashes: BTreeSet::new(),
+        }
+    }
+
+    fn add_policy(&mut self, policy: &semantic::Policy<Pk>, leaf: Option<TapLeafHash>) {
+        use semantic::Policy;
+
+        match *policy {
+            Policy::Key(ref pk) => match leaf {
+                Some(leaf) => {
+                    self.tap_leaf_keys.insert((pk.clone(), leaf));
+                }
+                None => {
+                    self.ecdsa_keys.insert(pk.clone());
+                }
+            },
+            Policy::Sha256(ref h) => {
+                self.hashes.insert(SpendHash::Sha256(h.clone()));
+            }
+            Policy::Hash256(ref h) => {
+                self.hashes.insert(SpendHash::Hash256(h.clone()));
+            }
+            Policy::Ripemd160(ref h) => {
+                self.hashes.insert(SpendHash::Ripemd160(h.clone()));
+            }
+            Policy::Hash160(ref h) => {
+                self.hashes.insert(SpendHash::Hash160(h.clone()));
+            }
+            Policy::Ext(ref e) => match *e {},
+            Policy::Threshold(_, ref subs) => {
+                for sub in subs {
+                    self.add_policy(sub, leaf);
+                }
+            }
+            Policy::Unsatisfiable | Policy::Trivial | Policy::After(..) | Policy::Older(..) => {}
+        }
+    }
+
+    fn add_tr<Ext>(&mut self, tr: &Tr<Pk, Ext>) -> Result<(), Error>
+    where
+        Pk: ToPublicKey,
+        Ext: Extension + ParseableExt,
+    {
+        self.tap_key_spend = Some(tr.internal_key().clone());
+        for (_, ms) in tr.iter_scripts() {
+            let leaf = TapLeafHash::from_script(&ms.encode(), LeafVersion::default());
+            self.add_policy(&ms.lift()?, Some(leaf));
+        }
+        Ok(())
+    }
+}
+
+impl<Pk: MiniscriptKey + ToPublicKey, Ext: Extension + ParseableExt> Descriptor<Pk, Ext> {
+    /// The refund path of this descriptor, if it has one.
+    ///
+    /// This is a heuristic for the refund branches of swaps and HTLCs: the
+    /// first of the [spend paths][`Descriptor::spend_paths`] which requires
+    /// a signature of a single key and a single timelock, and nothing else.
+    /// Paths without a timelock, such as the key spend of a taproot
+    /// descriptor, are never refund paths.
+    ///
+    /// # Errors
+    /// When the descriptor cannot be lifted, see [`Liftable::lift`].
+    pub fn refund_path(&self) -> Result<Option<RefundPath<Pk>>, Error> {
+        Ok(self
+            .spend_paths()?
+            .into_iter()
+            .filter_map(RefundPath::from_spend_path)
+            .next())
+    }
+
+    /// The signatures and preimages a satisfier may be asked for when
+    /// satisfying this descriptor, e.g. to fetch them from a remote signer
+    /// ahead of satisfaction. Timelocks are not included.
+    ///
+    /// Leaves below hidden nodes are not known and not considered.
+    ///
+    /// # Errors
+    /// When the descriptor cannot be lifted, see [`Liftable::lift`].
+    pub fn requirements(&self) -> Result<Requirements<Pk>, Error> {
+        let mut req = Requirements::new();
+        match *self {
+            Descriptor::Tr(ref tr) => req.add_tr(tr)?,
+            Descriptor::TrExt(ref tr) => req.add_tr(tr)?,
+            Descriptor::LegacyCSFSCov(ref cov) => {
+                req.ecdsa_keys.insert(cov.pk.clone());
+                req.add_policy(&cov.ms.lift()?, None);
+            }
+            _ => req.add_policy(&self.lift()?, None),
+        }
+        Ok(req)
+    }
+
+    /// All ways of spending this descriptor.
+    ///
+    /// Each path lists what is needed to satisfy the descriptor in one
+    /// way. Key thresholds are only kept for paths with no other keys, e.g.
+    /// for `multi`, otherwise a path is listed for each set of signers.
+    /// Taproot descriptors have a path for the key spend and paths for each
+    /// tap leaf, leaves below hidden nodes are not known and not listed.
+    ///
+    /// # Errors
+    /// When the descriptor cannot be lifted, see [`Liftable::lift`], or
+    /// when it has more than [`MAX_SPEND_PATHS`] paths.
+    pub fn spend_paths(&self) -> Result<Vec<SpendPath<Pk>>, Error> {
+        match *self {
+            Descriptor::Tr(ref tr) => tr_spend_paths(tr),
+            Descriptor::TrExt(ref tr) => tr_spend_paths(tr),
+            Descriptor::LegacyCSFSCov(ref cov) => {
+                let policy = semantic::Policy::Threshold(
+                    2,
+                    vec![semantic::Policy::Key(cov.pk.clone()), cov.ms.lift()?],
+                );
+                with_weight(policy_paths(&policy.normalized())?, None, || {
+                    self.max_satisfaction_weight().ok()
+                })
+            }
+            _ => with_weight(policy_paths(&self.lift()?.normalized())?, None, || {
+                self.max_satisfaction_weight().ok()
+            }),
+        }
+    }
+}
+
+fn tr_spend_paths<Pk, Ext>(tr: &Tr<Pk, Ext>) -> Result<Vec<SpendPath<Pk>>, Error>
+where
+    Pk: MiniscriptKey + ToPublicKey,
+    Ext: Extension + ParseableExt,
+{
+    let mut paths = vec![SpendPath {
+        est_weight: KEY_SPEND_WEIGHT,
+        ..SpendPath::with_keys(vec![tr.internal_key().clone()], 1)
+    }];
+    for (depth, ms) in tr.iter_scripts() {
+        let leaf = TapLeafHash::from_script(&ms.encode(), LeafVersion::default());
+        paths.extend(with_weight(
+            policy_paths(&ms.lift()?.normalized())?,
+            Some(leaf),
+            || leaf_satisfaction_weight(depth, ms),
+        )?);
+        check_count(paths.len())?;
+    }
+    Ok(paths)
+}
+
+/// Sets the leaf and weight of `paths`, computing the weight only if there
+/// are paths at all
+fn with_weight<Pk: MiniscriptKey, F: FnOnce() -> Option<usize>>(
+    mut paths: Vec<SpendPath<Pk>>,
+    leaf: Option<TapLeafHash>,
+    weight: F,
+) -> Result<Vec<SpendPath<Pk>>, Error> {
+    if paths.is_empty() {
+        return Ok(paths);
+    }
+    let weight = weight().ok_or(Error::ImpossibleSatisfaction)?;
+    for path in &mut paths {
+        path.leaf = leaf;
+        path.est_weight = weight;
+    }
+    Ok(paths)
+}
+
+/// Errors if `count` paths are more than [`MAX_SPEND_PATHS`]
+fn check_count(count: usize) -> Result<(), Error> {
+    if count > MAX_SPEND_PATHS {
+        Err(Error::TooManySpendPaths(MAX_SPEND_PATHS))
+    } else {
+        Ok(())
+    }
+}
+
+fn policy_paths<Pk: MiniscriptKey>(
+    policy: &semantic::Policy<Pk>,
+) -> Result<Vec<SpendPath<Pk>>, Error> {
+    use semantic::Policy;
+
+    let mut path = SpendPath::trivial();
+    match *policy {
+        Policy::Unsatisfiable => return Ok(vec![]),
+        Policy::Trivial => {}
+        Policy::Key(ref pk) => return Ok(vec![SpendPath::with_keys(vec![pk.clone()], 1)]),
+        Policy::After(t) => path.timelocks.push(SpendTimelock::After(t)),
+        Policy::Older(t) => path.timelocks.push(SpendTimelock::Older(t)),
+        Policy::Sha256(ref h) => path.hashes.push(SpendHash::Sha256(h.clone())),
+        Policy::Hash256(ref h) => path.hashes.push(SpendHash::Hash256(h.clone())),
+        Policy::Ripemd160(ref h) => path.hashes.push(SpendHash::Ripemd160(h.clone())),
+        Policy::Hash160(ref h) => path.hashes.push(SpendHash::Hash160(h.clone())),
+        Policy::Ext(ref e) => match *e {},
+        Policy::Threshold(k, ref subs) => {
+            let keys: Option<Vec<Pk>> = subs
+                .iter()
+                .map(|sub| match *sub {
+                    Policy::Key(ref pk) => Some(pk.clone()),
+                    _ => None,
+                })
+                .collect();
+            if let Some(keys) = keys {
+                return Ok(vec![SpendPath::with_keys(keys, k)]);
+            }
+
+            let sub_paths = subs
+                .iter()
+                .map(policy_paths)
+                .collect::<Result<Vec<_>, _>>()?;
+            let mut paths = vec![];
+            for combination in combinations(subs.len(), k)? {
+                let mut product = vec![SpendPath::trivial()];
+                for i in combination {
+                    let mut next = vec![];
+                    for a in &product {
+                        for b in &sub_paths[i] {
+                            next.extend(a.and(b)?);
+                            check_count(paths.len() + next.len())?;
+                        }
+                    }
+                    product = next;
+                }
+                paths.extend(product);
+            }
+            return Ok(paths);
+        }
+    }
+    Ok(vec![path])
+}
+
+/// The number of `k` element subsets of a set of `n` elements, saturating
+/// at `usize::MAX`
+fn binomial(n: usize, k: usize) -> usize {
+    let k = cmp::min(k, n - k);
+    let mut ret: u128 = 1;
+    for i in 0..k {
+        // Exact, since the product of `i + 1` consecutive integers is
+        // divisible by `(i + 1)!`
+        ret = ret * (n - i) as u128 / (i + 1) as u128;
+        if ret > std::usize::MAX as u128 {
+            return std::usize::MAX;
+        }
+    }
+    ret as usize
+}
+
+/// All `k` element subsets of `0..n`, in lexicographic order
+///
+/// Errors if there are more than [`MAX_SPEND_PATHS`] of them.
+fn combinations(n: usize, k: usize) -> Result<Vec<Vec<usize>>, Error> {
+    if k > n {
+        return Ok(vec![]);
+    }
+    check_count(binomial(n, k))?;
+    let mut ret = vec![];
+    let mut combination: Vec<usize> = (0..k).collect();
+    loop {
+        ret.push(combination.clone());
+        // Find the rightmost index which can be incremented
+        let mut i = k;
+        while i > 0 && combination[i - 1] == n - k + i - 1 {
+            i -= 1;
+        }
+        if i == 0 {
+            return Ok(ret);
+        }
+        combination[i - 1] += 1;
+        for j in i..k {
+            combination[j] = combination[j - 1] + 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::NoExt;
+
+    type Desc = Descriptor<bitcoin::PublicKey, NoExt>;
+
+    fn key(i: u8) -> String {
+        let secp = elements::secp256k1_zkp::Secp256k1::signing_only();
+        let sk = elements::secp256k1_zkp::SecretKey::from_slice(&[i + 1; 32]).unwrap();
+        elements::secp256k1_zkp::PublicKey::from_secret_key(&secp, &sk).to_string()
+    }
+
+    #[test]
+    fn combinations_of() {
+        assert_eq!(
+            combinations(4, 2).unwrap(),
+            vec![
+                vec![0, 1],
+                vec![0, 2],
+                vec![0, 3],
+                vec![1, 2],
+                vec![1, 3],
+                vec![2, 3]
+            ]
+        );
+        assert_eq!(combinations(2, 0).unwrap(), vec![Vec::<usize>::new()]);
+        assert!(combinations(1, 2).unwrap().is_empty());
+        assert_eq!(binomial(4, 2), 6);
+        assert_eq!(binomial(200, 100), std::usize::MAX);
+        assert_eq!(
+            combinations(30, 15),
+            Err(Error::TooManySpendPaths(MAX_SPEND_PATHS))
+        );
+    }
+
+    #[test]
+    fn too_many_spend_paths() {
+        // 2 ways to satisfy each of the 8 `or`s
+        let or = (0..8)
+            .map(|i| format!("or_b(pk({}),a:pk({}))", key(2 * i), key(2 * i + 1)))
+            .collect::<Vec<_>>();
+        let desc = Desc::from_str(&format!(
+            "elwsh(and_v(v:{},and_v(v:{},and_v(v:{},and_v(v:{},and_v(v:{},and_v(v:{},\
+             and_v(v:{},{}))))))))",
+            or[0], or[1], or[2], or[3], or[4], or[5], or[6], or[7]
+        ))
+        .unwrap();
+        assert_eq!(desc.spend_paths().unwrap().len(), 256);
+
+        // C(20, 10)^2 sets of signers, more than the maximum
+        let keys = (0..20).map(key).collect::<Vec<_>>().join(",");
+        let desc = Desc::from_str(&format!(
+            "elwsh(and_v(v:multi(10,{}),multi(10,{})))",
+            keys, keys
+        ))
+        .unwrap();
+        assert_eq!(
+            desc.spend_paths(),
+            Err(Error::TooManySpendPaths(MAX_SPEND_PATHS))
+        );
+    }
+
+    #[test]
+    fn spend_paths() {
+        let (a, b, c) = (key(0), key(1), key(2));
+        let pk = |s: &str| bitcoin::PublicKey::from_str(s).unwrap();
+
+        let desc = Desc::from_str(&format!(
+            "elwsh(or_d(multi(2,{},{}),and_v(v:pk({}),older(144))))",
+            a, b, c
+        ))
+        .unwrap();
+        let weight = desc.max_satisfaction_weight().unwrap();
+        let paths = desc.spend_paths().unwrap();
+        assert_eq!(paths.len(), 2);
+        assert_eq!(paths[0].keys, vec![pk(&a), pk(&b)]);
+        assert_eq!(paths[0].threshold, 2);
+        assert!(paths[0].timelocks.is_empty());
+        assert_eq!(paths[1].keys, vec![pk(&c)]);
+        assert_eq!(
+            paths[1].timelocks,
+            vec![SpendTimelock::Older(Sequence(144))]
+        );
+        assert!(paths
+            .iter()
+            .all(|p| p.est_weight == weight && p.leaf.is_none()));
+
+        // Thresholds combined with other keys are expanded
+        let desc =
+            Desc::from_str(&format!("elwsh(and_v(v:multi(1,{},{}),pk({})))", a, b, c)).unwrap();
+        let paths = desc.spend_paths().unwrap();
+        assert_eq!(paths.len(), 2);
+        assert_eq!(paths[0].keys, vec![pk(&a), pk(&c)]);
+        assert_eq!(paths[1].keys, vec![pk(&b), pk(&c)]);
+        assert!(paths.iter().all(|p| p.threshold == 2));
+
+        let hash = "1111111111111111111111111111111111111111111111111111111111111111";
+        let desc = Desc::from_str(&format!(
+            "eltr({},{{pk({}),and_v(v:pk({}),sha256({}))}})",
+            a, b, c, hash
+        ))
+        .unwrap();
+        let paths = desc.spend_paths().unwrap();
+        assert_eq!(paths.len(), 3);
+        assert_eq!(paths[0].keys, vec![pk(&a)]);
+        assert_eq!(paths[0].leaf, None);
+        assert_eq!(paths[0].est_weight, KEY_SPEND_WEIGHT);
+        assert!(paths[1].leaf.is_some());
+        assert_eq!(paths[2].keys, vec![pk(&c)]);
+        assert_eq!(paths[2].hashes[0].to_string(), format!("sha256({})", hash));
+        assert!(paths[2].est_weight > paths[1].est_weight);
+    }
+
+    #[test]
+    fn refund_path() {
+        let (a, b) = (key(0), key(1));
+        let pk = |s: &str| bitcoin::PublicKey::from_str(s).unwrap();
+        let hash = "1111111111111111111111111111111111111111111111111111111111111111";
+
+        // An HTLC, spent by `a` with the preimage or by `b` after a timeout
+        let desc = Desc::from_str(&format!(
+            "elwsh(andor(pk({}),sha256({}),and_v(v:pk({}),after(500000))))",
+            a, hash, b
+        ))
+        .unwrap();
+        assert_eq!(
+            desc.refund_path().unwrap(),
+            Some(RefundPath {
+                key: pk(&b),
+                timelock: SpendTimelock::After(PackedLockTime(500000)),
+                leaf: None,
+            })
+        );
+
+        let desc = Desc::from_str(&format!(
+            "eltr({},{{and_v(v:pk({}),sha256({})),and_v(v:pk({}),older(144))}})",
+            a, a, hash, b
+        ))
+        .unwrap();
+        let refund = desc.refund_path().unwrap().unwrap();
+        assert_eq!(refund.key, pk(&b));
+        assert_eq!(refund.timelock, SpendTimelock::Older(Sequence(144)));
+        assert!(refund.leaf.is_some());
+
+        // No timelocked path, or one needing more than a single key
+        let desc = Desc::from_str(&format!("elwsh(or_d(pk({}),pk({})))", a, b)).unwrap();
+        assert_eq!(desc.refund_path().unwrap(), None);
+        let desc =
+            Desc::from_str(&format!("elwsh(and_v(v:multi(2,{},{}),older(144)))", a, b)).unwrap();
+        assert_eq!(desc.refund_path().unwrap(), None);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn spend_path_serde() {
+        let desc = Desc::from_str(&format!("elwsh(and_v(v:pk({}),after(100)))", key(0))).unwrap();
+        let paths = desc.spend_paths().unwrap();
+        assert_eq!(
+            serde_json::to_value(&paths).unwrap(),
+            serde_json::json!([{
+                "keys": [key(0)],
+                "threshold": 1,
+                "hashes": [],
+                "timelocks": ["after(100)"],
+                "leaf": null,
+                "est_weight": desc.max_satisfaction_weight().unwrap(),
+            }])
+        );
+
+        let hash = "1111111111111111111111111111111111111111111111111111111111111111";
+        let desc = Desc::from_str(&format!(
+            "eltr({},{{pk({}),and_v(v:pk({}),and_v(v:sha256({}),older(144)))}})",
+            key(0),
+            key(1),
+            key(2),
+            hash
+        ))
+        .unwrap();
+        let paths = desc.spend_paths().unwrap();
+        let json = serde_json::to_value(&paths).unwrap();
+        assert_eq!(
+            serde_json::from_value::<Vec<SpendPath<bitcoin::PublicKey>>>(json.clone()).unwrap(),
+            paths
+        );
+
+        let mut path = json[2].clone();
+        path["extra"] = serde_json::json!(1);
+        assert!(serde_json::from_value::<SpendPath<bitcoin::PublicKey>>(path).is_err());
+        let mut path = json[2].clone();
+        path["timelocks"] = serde_json::json!(["older(x)"]);
+        assert!(serde_json::from_value::<SpendPath<bitcoin::PublicKey>>(path).is_err());
+        let path = serde_json::from_str::<SpendPath<bitcoin::PublicKey>>(
+            r#"{"keys":[],"keys":[],"threshold":0,"hashes":[],"timelocks":[],"leaf":null,"est_weight":0}"#,
+        );
+        assert!(path.unwrap_err().to_string().contains("duplicate field"));
+    }
+}
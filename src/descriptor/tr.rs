@@ -1,24 +1,29 @@
 // Tapscript
 
-use std::cmp::{self, max};
+use std::cmp::{self, max, Reverse};
+use std::collections::BinaryHeap;
 use std::str::FromStr;
 use std::sync::{Arc, Mutex};
-use std::{fmt, hash};
+use std::{fmt, hash, io};
 
+use bitcoin::consensus::Encodable;
+use elements::hashes::{sha256, Hash, HashEngine};
 use elements::taproot::{
-    LeafVersion, TaprootBuilder, TaprootSpendInfo, TAPROOT_CONTROL_BASE_SIZE,
-    TAPROOT_CONTROL_MAX_NODE_COUNT, TAPROOT_CONTROL_NODE_SIZE,
+    LeafVersion, TapBranchHash, TapLeafHash, TaprootBuilder, TaprootSpendInfo,
+    TAPROOT_CONTROL_BASE_SIZE, TAPROOT_CONTROL_MAX_NODE_COUNT, TAPROOT_CONTROL_NODE_SIZE,
 };
-use elements::{self, opcodes, secp256k1_zkp, Script};
+use elements::{self, opcodes, secp256k1_zkp, Script, VarInt};
 
 use super::checksum::verify_checksum;
 use super::ELMTS_STR;
 use crate::descriptor::checksum;
 use crate::expression::{self, FromTree};
 use crate::extensions::ParseableExt;
+use crate::miniscript::analyzable::ExtParams;
+use crate::miniscript::satisfy::Timelocks;
 use crate::miniscript::Miniscript;
 use crate::policy::semantic::Policy;
-use crate::policy::Liftable;
+use crate::policy::{LiftError, Liftable};
 use crate::util::{varint_len, witness_size};
 use crate::{
     errstr, Error, Extension, ForEachKey, MiniscriptKey, NoExt, Satisfier, Tap, ToPublicKey,
@@ -26,8 +31,8 @@ use crate::{
 };
 
 /// A Taproot Tree representation.
-// Hidden leaves are not yet supported in descriptor spec. Conceptually, it should
-// be simple to integrate those here, but it is best to wait on core for the exact syntax.
+// Hidden nodes are not part of the descriptor spec. They are written as
+// `hidden(<hash>)` here, which does not conflict with any Miniscript fragment.
 #[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
 pub enum TapTree<Pk: MiniscriptKey, Ext: Extension = NoExt> {
     /// A taproot tree structure
@@ -37,6 +42,153 @@ pub enum TapTree<Pk: MiniscriptKey, Ext: Extension = NoExt> {
     // in adding a LeafVersion with Leaf type here. All Miniscripts right now
     // are of Leafversion::default
     Leaf(Arc<Miniscript<Pk, Tap, Ext>>),
+    /// A node of which only the hash is known, e.g. a subtree belonging to
+    /// another participant. Its leaves cannot be used for spending.
+    Hidden(TapBranchHash),
+}
+
+/// Incremental builder for a [`TapTree`] from weighted leaves.
+///
+/// Leaves with a higher weight, e.g. the expected number of times they are
+/// used, end up closer to the root. Finalizing arranges the leaves into a
+/// Huffman tree, which minimizes the expected control block size. Leaves of
+/// equal weight are combined in the order in which they were added, so the
+/// resulting tree only depends on the sequence of added leaves.
+pub struct TapTreeBuilder<Pk: MiniscriptKey, Ext: Extension = NoExt> {
+    /// Pending subtrees by weight and insertion index, the latter ensuring
+    /// that subtrees are never compared themselves
+    nodes: BinaryHeap<WeightedTree<Pk, Ext>>,
+    /// Number of subtrees added to `nodes` so far
+    n_added: usize,
+}
+
+// Subtree along with its weight and insertion index
+type WeightedTree<Pk, Ext> = (Reverse<usize>, Reverse<usize>, TapTree<Pk, Ext>);
+
+impl<Pk: MiniscriptKey, Ext: Extension> TapTreeBuilder<Pk, Ext> {
+    /// Create a builder without any leaves
+    pub fn new() -> Self {
+        TapTreeBuilder {
+            nodes: BinaryHeap::new(),
+            n_added: 0,
+        }
+    }
+
+    /// Number of leaves added so far
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Whether no leaves were added so far
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Add a leaf with the given weight
+    pub fn add_leaf(&mut self, weight: usize, ms: Miniscript<Pk, Tap, Ext>) {
+        self.push(weight, TapTree::Leaf(Arc::new(ms)));
+    }
+
+    fn push(&mut self, weight: usize, tree: TapTree<Pk, Ext>) {
+        self.nodes
+            .push((Reverse(weight), Reverse(self.n_added), tree));
+        self.n_added += 1;
+    }
+
+    /// Arrange all leaves into a Huffman tree.
+    ///
+    /// # Errors
+    /// If no leaves were added.
+    pub fn finalize(mut self) -> Result<TapTree<Pk, Ext>, Error> {
+        while self.nodes.len() > 1 {
+            let (w1, _, t1) = self.nodes.pop().expect("len must atleast be two");
+            let (w2, _, t2) = self.nodes.pop().expect("len must atleast be two");
+            let w = (w1.0).saturating_add(w2.0);
+            self.push(w, TapTree::Tree(Arc::new(t1), Arc::new(t2)));
+        }
+        self.nodes
+            .pop()
+            .map(|(_, _, tree)| tree)
+            .ok_or_else(|| errstr("Empty TapTree"))
+    }
+
+    /// Finalize the tree into a [`Tr`] descriptor with `internal_key`.
+    ///
+    /// # Errors
+    /// If no leaves were added, or the tree is too deep for taproot.
+    pub fn finalize_tr(self, internal_key: Pk) -> Result<Tr<Pk, Ext>, Error> {
+        Tr::new(internal_key, Some(self.finalize()?))
+    }
+}
+
+// Leaf probability, ordered as an f64. Probabilities are checked to be
+// finite before they are ordered.
+#[derive(PartialEq)]
+struct LeafProb(f64);
+
+impl Eq for LeafProb {}
+
+impl PartialOrd for LeafProb {
+    fn partial_cmp(&self, other: &LeafProb) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for LeafProb {
+    fn cmp(&self, other: &LeafProb) -> cmp::Ordering {
+        self.0
+            .partial_cmp(&other.0)
+            .expect("probabilities are finite")
+    }
+}
+
+/// Arrange `leaves` into a Huffman tree by their probabilities, as the
+/// policy compiler does with the compilations of taproot leaves.
+pub(crate) fn huffman_tree<Pk: MiniscriptKey, Ext: Extension>(
+    leaves: Vec<(f64, Miniscript<Pk, Tap, Ext>)>,
+) -> Result<TapTree<Pk, Ext>, Error> {
+    let mut node_weights = BinaryHeap::<(Reverse<LeafProb>, TapTree<Pk, Ext>)>::new();
+    for (prob, script) in leaves {
+        if !prob.is_finite() || prob < 0.0 {
+            return Err(Error::BadDescriptor(format!(
+                "invalid leaf probability {}",
+                prob
+            )));
+        }
+        node_weights.push((Reverse(LeafProb(prob)), TapTree::Leaf(Arc::new(script))));
+    }
+    while node_weights.len() > 1 {
+        let (p1, s1) = node_weights.pop().expect("len must atleast be two");
+        let (p2, s2) = node_weights.pop().expect("len must atleast be two");
+
+        let p = (p1.0).0 + (p2.0).0;
+        node_weights.push((
+            Reverse(LeafProb(p)),
+            TapTree::Tree(Arc::from(s1), Arc::from(s2)),
+        ));
+    }
+    node_weights
+        .pop()
+        .map(|(_, tree)| tree)
+        .ok_or_else(|| errstr("Empty TapTree"))
+}
+
+impl<Ext: ParseableExt> TapTreeBuilder<bitcoin::XOnlyPublicKey, Ext> {
+    /// Add a leaf with the given weight, decoding it from `script`.
+    ///
+    /// # Errors
+    /// If `script` is not a sane tapscript Miniscript.
+    pub fn add_script(&mut self, weight: usize, script: &Script) -> Result<(), Error> {
+        let ms = Miniscript::<_, Tap, Ext>::parse(script)?;
+        self.add_leaf(weight, ms);
+        Ok(())
+    }
+}
+
+impl<Pk: MiniscriptKey, Ext: Extension> Default for TapTreeBuilder<Pk, Ext> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 /// A taproot descriptor
@@ -118,11 +270,11 @@ impl<Pk: MiniscriptKey, Ext: Extension> TapTree<Pk, Ext> {
             TapTree::Tree(ref left_tree, ref right_tree) => {
                 1 + max(left_tree.taptree_height(), right_tree.taptree_height())
             }
-            TapTree::Leaf(..) => 0,
+            TapTree::Leaf(..) | TapTree::Hidden(..) => 0,
         }
     }
 
-    /// Iterate over all miniscripts
+    /// Iterate over all miniscripts, skipping hidden nodes
     pub fn iter(&self) -> TapTreeIter<'_, Pk, Ext> {
         TapTreeIter {
             stack: vec![(0, self)],
@@ -142,6 +294,7 @@ impl<Pk: MiniscriptKey, Ext: Extension> TapTree<Pk, Ext> {
                 Arc::new(r.translate_helper(t)?),
             ),
             TapTree::Leaf(ms) => TapTree::Leaf(Arc::new(ms.translate_pk(t)?)),
+            TapTree::Hidden(hash) => TapTree::Hidden(*hash),
         };
         Ok(frag)
     }
@@ -159,9 +312,132 @@ impl<Pk: MiniscriptKey, Ext: Extension> TapTree<Pk, Ext> {
                 Arc::new(r.translate_ext_helper(t)?),
             ),
             TapTree::Leaf(ms) => TapTree::Leaf(Arc::new(ms.translate_ext(t)?)),
+            TapTree::Hidden(hash) => TapTree::Hidden(*hash),
         };
         Ok(frag)
     }
+
+    /// Whether any node of the tree is hidden
+    pub fn has_hidden_nodes(&self) -> bool {
+        match *self {
+            TapTree::Tree(ref l, ref r) => l.has_hidden_nodes() || r.has_hidden_nodes(),
+            TapTree::Leaf(..) => false,
+            TapTree::Hidden(..) => true,
+        }
+    }
+
+    // Helper function to add all nodes to a builder in depth first order
+    pub(crate) fn add_to_builder(&self, depth: usize, builder: TaprootBuilder) -> TaprootBuilder
+    where
+        Pk: ToPublicKey,
+        Ext: ParseableExt,
+    {
+        let res = match *self {
+            TapTree::Tree(ref l, ref r) => {
+                let builder = l.add_to_builder(depth + 1, builder);
+                return r.add_to_builder(depth + 1, builder);
+            }
+            TapTree::Leaf(ref ms) => builder.add_leaf(depth, ms.encode()),
+            TapTree::Hidden(hash) => {
+                builder.add_hidden(depth, sha256::Hash::from_inner(hash.into_inner()))
+            }
+        };
+        res.expect("Computing spend data on a valid Tree should always succeed")
+    }
+
+    /// The merkle root of the tree
+    ///
+    /// Unlike [`Tr::spend_info`], this only keeps a single encoded leaf
+    /// script in memory at a time, so it can be used for the output key of
+    /// very large trees with [`TaprootSpendInfo::new_key_spend`].
+    pub fn merkle_root(&self) -> TapBranchHash
+    where
+        Pk: ToPublicKey,
+        Ext: ParseableExt,
+    {
+        self.node_hash()
+    }
+
+    // Helper function to compute the hash of a node the way TaprootBuilder does
+    fn node_hash(&self) -> TapBranchHash
+    where
+        Pk: ToPublicKey,
+        Ext: ParseableExt,
+    {
+        match *self {
+            TapTree::Tree(ref l, ref r) => branch_hash(l.node_hash(), r.node_hash()),
+            TapTree::Leaf(ref ms) => {
+                let leaf_hash = TapLeafHash::from_script(&ms.encode(), LeafVersion::default());
+                TapBranchHash::from_inner(leaf_hash.into_inner())
+            }
+            TapTree::Hidden(hash) => hash,
+        }
+    }
+
+    /// The tree with every node that does not lead to the leaf `leaf_hash`
+    /// replaced by a hidden node, or `None` if there is no such leaf
+    ///
+    /// The reduced tree has the same merkle root and yields the same control
+    /// block for the leaf, so it can be handed to a party that should be
+    /// able to spend with this leaf without learning the other scripts.
+    pub fn leaf_only(&self, leaf_hash: &TapLeafHash) -> Option<Self>
+    where
+        Pk: ToPublicKey,
+        Ext: ParseableExt,
+    {
+        self.leaf_only_helper(leaf_hash).ok()
+    }
+
+    // Helper function for leaf_only: the reduced tree if it contains the
+    // leaf, otherwise the hash of the node
+    fn leaf_only_helper(&self, leaf_hash: &TapLeafHash) -> Result<Self, TapBranchHash>
+    where
+        Pk: ToPublicKey,
+        Ext: ParseableExt,
+    {
+        match *self {
+            TapTree::Tree(ref l, ref r) => {
+                match (l.leaf_only_helper(leaf_hash), r.leaf_only_helper(leaf_hash)) {
+                    (Err(l), Err(r)) => Err(branch_hash(l, r)),
+                    (l, r) => Ok(TapTree::Tree(
+                        Arc::new(l.unwrap_or_else(TapTree::Hidden)),
+                        Arc::new(r.unwrap_or_else(TapTree::Hidden)),
+                    )),
+                }
+            }
+            TapTree::Leaf(ref ms) => {
+                let hash = TapLeafHash::from_script(&ms.encode(), LeafVersion::default());
+                if hash == *leaf_hash {
+                    Ok(self.clone())
+                } else {
+                    Err(TapBranchHash::from_inner(hash.into_inner()))
+                }
+            }
+            TapTree::Hidden(hash) => Err(hash),
+        }
+    }
+
+    /// Write the known leaves to `w` in depth first order, encoding one leaf
+    /// at a time, and return the number of bytes written
+    ///
+    /// Every leaf is written as its depth byte followed by the preimage of
+    /// its [`TapLeafHash`]: the leaf version byte and the length prefixed
+    /// script. Hidden nodes are skipped.
+    pub fn write_leaf_scripts<W: io::Write>(&self, mut w: W) -> io::Result<usize>
+    where
+        Pk: ToPublicKey,
+        Ext: ParseableExt,
+    {
+        let mut len = 0;
+        for (depth, ms) in self.iter() {
+            let script = ms.encode();
+            w.write_all(&[depth as u8, LeafVersion::default().as_u8()])?;
+            len += 2 + VarInt(script.len() as u64).consensus_encode(&mut w)?;
+            w.write_all(script.as_bytes())?;
+            len += script.len();
+        }
+        Ok(len)
+    }
 }
 
 impl<Pk: MiniscriptKey, Ext: Extension> fmt::Display for TapTree<Pk, Ext> {
@@ -169,6 +445,7 @@ impl<Pk: MiniscriptKey, Ext: Extension> fmt::Display for TapTree<Pk, Ext> {
         match self {
             TapTree::Tree(ref left, ref right) => write!(f, "{{{},{}}}", *left, *right),
             TapTree::Leaf(ref script) => write!(f, "{}", *script),
+            TapTree::Hidden(ref hash) => write!(f, "hidden({})", hash),
         }
     }
 }
@@ -178,6 +455,7 @@ impl<Pk: MiniscriptKey, Ext: Extension> fmt::Debug for TapTree<Pk, Ext> {
         match self {
             TapTree::Tree(ref left, ref right) => write!(f, "{{{:?},{:?}}}", *left, *right),
             TapTree::Leaf(ref script) => write!(f, "{:?}", *script),
+            TapTree::Hidden(ref hash) => write!(f, "hidden({})", hash),
         }
     }
 }
@@ -239,27 +517,31 @@ impl<Pk: MiniscriptKey, Ext: Extension> Tr<Pk, Ext> {
         // This would be cheap operation after static context support from upstream
         let secp = secp256k1_zkp::Secp256k1::verification_only();
         // Key spend path with no merkle root
-        let data = if self.tree.is_none() {
-            TaprootSpendInfo::new_key_spend(&secp, self.internal_key.to_x_only_pubkey(), None)
-        } else {
-            let mut builder = TaprootBuilder::new();
-            for (depth, ms) in self.iter_scripts() {
-                let script = ms.encode();
-                builder = builder
-                    .add_leaf(depth, script)
-                    .expect("Computing spend data on a valid Tree should always succeed");
-            }
+        let data = if let Some(ref tree) = self.tree {
+            let builder = tree.add_to_builder(0, TaprootBuilder::new());
             // Assert builder cannot error here because we have a well formed descriptor
             match builder.finalize(&secp, self.internal_key.to_x_only_pubkey()) {
                 Ok(data) => data,
                 Err(_) => unreachable!("We know the builder can be finalized"),
             }
+        } else {
+            TaprootSpendInfo::new_key_spend(&secp, self.internal_key.to_x_only_pubkey(), None)
         };
         let spend_info = Arc::new(data);
         *self.spend_info.lock().expect("Lock poisoned") = Some(Arc::clone(&spend_info));
         spend_info
     }
 
+    /// The merkle root of the tree, without computing the
+    /// [`TaprootSpendInfo`], see [`TapTree::merkle_root`]
+    pub fn merkle_root(&self) -> Option<TapBranchHash>
+    where
+        Pk: ToPublicKey,
+        Ext: ParseableExt,
+    {
+        self.tree.as_ref().map(TapTree::merkle_root)
+    }
+
     /// Checks whether the descriptor is safe.
     pub fn sanity_check(&self) -> Result<(), Error> {
         for (_depth, ms) in self.iter_scripts() {
@@ -275,44 +557,82 @@ impl<Pk: MiniscriptKey, Ext: Extension> Tr<Pk, Ext> {
     /// sighash suffix. Includes the weight of the VarInts encoding the
     /// scriptSig and witness stack length.
     ///
+    /// Leaves below hidden nodes are not known and thus not considered.
+    ///
     /// # Errors
     /// When the descriptor is impossible to safisfy (ex: sh(OP_FALSE)).
     pub fn max_satisfaction_weight(&self) -> Result<usize, Error> {
         let tree = match self.taptree() {
-            // key spend path:
-            // scriptSigLen(4) + stackLen(1) + stack[Sig]Len(1) + stack[Sig](65)
-            None => return Ok(4 + 1 + 1 + 65),
+            None => return Ok(KEY_SPEND_WEIGHT),
+            // Only the key spend path is known
+            Some(tree) if tree.iter().next().is_none() => return Ok(KEY_SPEND_WEIGHT),
             // script path spend..
             Some(tree) => tree,
         };
 
         tree.iter()
-            .filter_map(|(depth, ms)| {
-                let script_size = ms.script_size();
-                let max_sat_elems = ms.max_satisfaction_witness_elements().ok()?;
-                let max_sat_size = ms.max_satisfaction_size().ok()?;
-                let control_block_size = control_block_len(depth);
-                Some(
-                    // scriptSig len byte
-                    4 +
-                    // witness field stack len (+2 for control block & script)
-                    varint_len(max_sat_elems + 2) +
-                    // size of elements to satisfy script
-                    max_sat_size +
-                    // second to last element: script
-                    varint_len(script_size) +
-                    script_size +
-                    // last element: control block
-                    varint_len(control_block_size) +
-                    control_block_size,
-                )
-            })
+            .filter_map(|(depth, ms)| leaf_satisfaction_weight(depth, ms))
             .max()
             .ok_or(Error::ImpossibleSatisfaction)
     }
 }
 
+// The hash of a branch with children hashes `l` and `r`, the way
+// TaprootBuilder computes it
+fn branch_hash(l: TapBranchHash, r: TapBranchHash) -> TapBranchHash {
+    let mut engine = TapBranchHash::engine();
+    if l < r {
+        engine.input(&l);
+        engine.input(&r);
+    } else {
+        engine.input(&r);
+        engine.input(&l);
+    }
+    TapBranchHash::from_engine(engine)
+}
+
+/// Maximum weight of a key path spend:
+/// scriptSigLen(4) + stackLen(1) + stack[Sig]Len(1) + stack[Sig](65)
+pub(crate) const KEY_SPEND_WEIGHT: usize = 4 + 1 + 1 + 65;
+
+/// Maximum weight of a script path spend of the leaf `ms` at `depth`, or
+/// `None` if the leaf cannot be satisfied
+pub(crate) fn leaf_satisfaction_weight<Pk: MiniscriptKey, Ext: Extension>(
+    depth: usize,
+    ms: &Miniscript<Pk, Tap, Ext>,
+) -> Option<usize> {
+    let script_size = ms.script_size();
+    let max_sat_elems = ms.max_satisfaction_witness_elements().ok()?;
+    let max_sat_size = ms.max_satisfaction_size().ok()?;
+    let control_block_size = control_block_len(depth);
+    Some(
+        // scriptSig len byte
+        4 +
+        // witness field stack len (+2 for control block & script)
+        varint_len(max_sat_elems + 2) +
+        // size of elements to satisfy script
+        max_sat_size +
+        // second to last element: script
+        varint_len(script_size) +
+        script_size +
+        // last element: control block
+        varint_len(control_block_size) +
+        control_block_size,
+    )
+}
+
 impl<Pk: MiniscriptKey + ToPublicKey, Ext: ParseableExt> Tr<Pk, Ext> {
+    /// The descriptor reduced to the leaf `leaf_hash`, with all other nodes
+    /// hidden, or `None` if there is no such leaf. See [`TapTree::leaf_only`].
+    pub fn leaf_only(&self, leaf_hash: &TapLeafHash) -> Option<Self> {
+        let tree = self.tree.as_ref()?.leaf_only(leaf_hash)?;
+        Some(Self {
+            internal_key: self.internal_key.clone(),
+            tree: Some(tree),
+            spend_info: Mutex::new(None),
+        })
+    }
+
     /// Obtains the corresponding script pubkey for this descriptor.
     pub fn script_pubkey(&self) -> Script {
         let output_key = self.spend_info().output_key();
@@ -340,7 +660,7 @@ impl<Pk: MiniscriptKey + ToPublicKey, Ext: ParseableExt> Tr<Pk, Ext> {
     where
         S: Satisfier<Pk>,
     {
-        best_tap_spend(self, satisfier, false /* allow_mall */)
+        best_tap_spend(self, satisfier, false /* allow_mall */).map(|(wit, ss, _)| (wit, ss))
     }
 
     /// Returns satisfying, possibly malleable, witness and scriptSig with
@@ -350,7 +670,7 @@ impl<Pk: MiniscriptKey + ToPublicKey, Ext: ParseableExt> Tr<Pk, Ext> {
     where
         S: Satisfier<Pk>,
     {
-        best_tap_spend(self, satisfier, true /* allow_mall */)
+        best_tap_spend(self, satisfier, true /* allow_mall */).map(|(wit, ss, _)| (wit, ss))
     }
 }
 
@@ -387,6 +707,7 @@ where
                     self.stack.push((depth + 1, l));
                 }
                 TapTree::Leaf(ref ms) => return Some((depth, ms)),
+                TapTree::Hidden(..) => {}
             }
         }
         None
@@ -397,16 +718,29 @@ where
 impl_block_str!(
     Tr<Pk, Ext>,
     => Ext; Extension,
-    // Helper function to parse taproot script path
-    fn parse_tr_script_spend(tree: &expression::Tree,) -> Result<TapTree<Pk, Ext>, Error> {
+    // Helper function to parse taproot script path. Leaves are only checked
+    // to be sane if `checked`.
+    fn parse_tr_script_spend(tree: &expression::Tree, checked: bool,) -> Result<TapTree<Pk, Ext>, Error> {
         match tree {
             expression::Tree { name, args } if !name.is_empty() && args.is_empty() => {
-                let script = Miniscript::<Pk, Tap, Ext>::from_str(name)?;
+                let leaf = expression::Tree::from_str(name)?;
+                if leaf.name == "hidden" && leaf.args.len() == 1 {
+                    let hash = expression::terminal(&leaf.args[0], TapBranchHash::from_str)?;
+                    return Ok(TapTree::Hidden(hash));
+                }
+                // The leaf is parsed only once, with the checks of `Miniscript::from_str`
+                let script = if checked {
+                    let script = Miniscript::<Pk, Tap, Ext>::from_tree_ext(&leaf, &ExtParams::sane())?;
+                    script.sanity_check()?;
+                    script
+                } else {
+                    Miniscript::<Pk, Tap, Ext>::from_tree_ext(&leaf, &ExtParams::insane())?
+                };
                 Ok(TapTree::Leaf(Arc::new(script)))
             }
             expression::Tree { name, args } if name.is_empty() && args.len() == 2 => {
-                let left = Self::parse_tr_script_spend(&args[0])?;
-                let right = Self::parse_tr_script_spend(&args[1])?;
+                let left = Self::parse_tr_script_spend(&args[0], checked)?;
+                let right = Self::parse_tr_script_spend(&args[1], checked)?;
                 Ok(TapTree::Tree(Arc::new(left), Arc::new(right)))
             }
             _ => Err(Error::Unexpected(
@@ -417,10 +751,13 @@ impl_block_str!(
     }
 );
 
-impl_from_tree!(
+#[rustfmt::skip]
+impl_block_str!(
     Tr<Pk, Ext>,
     => Ext; Extension,
-    fn from_tree(top: &expression::Tree) -> Result<Self, Error> {
+    // Parse a taproot descriptor, only checking that the leaves are sane if
+    // `checked`
+    pub(super) fn from_tree_checked(top: &expression::Tree, checked: bool,) -> Result<Self, Error> {
         if top.name == "eltr" {
             match top.args.len() {
                 1 => {
@@ -442,27 +779,35 @@ impl_from_tree!(
                         )));
                     }
                     let tree = &top.args[1];
-                    let ret = Self::parse_tr_script_spend(tree)?;
+                    let ret = Self::parse_tr_script_spend(tree, checked)?;
                     Tr::new(expression::terminal(key, Pk::from_str)?, Some(ret))
                 }
                 _ => {
-                    return Err(Error::Unexpected(format!(
+                    Err(Error::Unexpected(format!(
                         "{}[#{} args] while parsing taproot descriptor",
                         top.name,
                         top.args.len()
-                    )));
+                    )))
                 }
             }
         } else {
-            return Err(Error::Unexpected(format!(
+            Err(Error::Unexpected(format!(
                 "{}[#{} args] while parsing taproot descriptor",
                 top.name,
                 top.args.len()
-            )));
+            )))
         }
     }
 );
 
+impl_from_tree!(
+    Tr<Pk, Ext>,
+    => Ext; Extension,
+    fn from_tree(top: &expression::Tree) -> Result<Self, Error> {
+        Self::from_tree_checked(top, true)
+    }
+);
+
 impl_from_str!(
     Tr<Pk, Ext>,
     => Ext; Extension,
@@ -497,7 +842,7 @@ impl<Pk: MiniscriptKey, Ext: Extension> fmt::Display for Tr<Pk, Ext> {
 }
 
 // Helper function to parse string into miniscript tree form
-fn parse_tr_tree(s: &str) -> Result<expression::Tree<'_>, Error> {
+pub(super) fn parse_tr_tree(s: &str) -> Result<expression::Tree<'_>, Error> {
     for ch in s.bytes() {
         if !ch.is_ascii() {
             return Err(Error::Unprintable(ch));
@@ -549,11 +894,18 @@ fn split_once(inp: &str, delim: char) -> Option<(&str, &str)> {
     let ret = if inp.is_empty() {
         None
     } else {
+        // Skip delimiters inside parentheses, such as in `musig(A,B)` keys
         let mut found = inp.len();
+        let mut depth = 0;
         for (idx, ch) in inp.chars().enumerate() {
-            if ch == delim {
-                found = idx;
-                break;
+            match ch {
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                _ if ch == delim && depth == 0 => {
+                    found = idx;
+                    break;
+                }
+                _ => {}
             }
         }
         // No comma or trailing comma found
@@ -576,6 +928,7 @@ impl<Pk: MiniscriptKey, Ext: Extension> Liftable<Pk> for TapTree<Pk, Ext> {
                     Ok(Policy::Threshold(1, vec![lift_helper(l)?, lift_helper(r)?]))
                 }
                 TapTree::Leaf(ref leaf) => leaf.lift(),
+                TapTree::Hidden(..) => Err(Error::LiftError(LiftError::HiddenTapNode)),
             }
         }
 
@@ -596,6 +949,33 @@ impl<Pk: MiniscriptKey, Ext: Extension> Liftable<Pk> for Tr<Pk, Ext> {
     }
 }
 
+impl<Pk: MiniscriptKey, Ext: Extension> Tr<Pk, Ext> {
+    /// Lift the descriptor like [`Liftable::lift`], but keep the extension
+    /// fragments of the tap leaves as [`Policy::Ext`] rather than failing
+    pub fn lift_ext(&self) -> Result<Policy<Pk, Ext>, Error> {
+        fn lift_helper<Pk: MiniscriptKey, Ext: Extension>(
+            s: &TapTree<Pk, Ext>,
+        ) -> Result<Policy<Pk, Ext>, Error> {
+            match s {
+                TapTree::Tree(ref l, ref r) => {
+                    Ok(Policy::Threshold(1, vec![lift_helper(l)?, lift_helper(r)?]))
+                }
+                TapTree::Leaf(ref leaf) => leaf.lift_ext(),
+                TapTree::Hidden(..) => Err(Error::LiftError(LiftError::HiddenTapNode)),
+            }
+        }
+
+        let key = Policy::Key(self.internal_key.clone());
+        match &self.tree {
+            Some(root) => Ok(Policy::Threshold(
+                1,
+                vec![key, lift_helper(root)?.normalized()],
+            )),
+            None => Ok(key),
+        }
+    }
+}
+
 impl<Pk: MiniscriptKey, Ext: Extension> ForEachKey<Pk> for Tr<Pk, Ext> {
     fn for_each_key<'a, F: FnMut(&'a Pk) -> bool>(&'a self, mut pred: F) -> bool
     where
@@ -661,36 +1041,40 @@ fn control_block_len(depth: usize) -> usize {
     TAPROOT_CONTROL_BASE_SIZE + depth * TAPROOT_CONTROL_NODE_SIZE
 }
 
-// Helper function to get a script spend satisfaction
+// Helper function to get a script spend satisfaction, along with the
+// timelocks of the chosen leaf (none for key spends)
 // try script spend
-fn best_tap_spend<Pk, S, Ext>(
+pub(super) fn best_tap_spend<Pk, S, Ext>(
     desc: &Tr<Pk, Ext>,
     satisfier: S,
     allow_mall: bool,
-) -> Result<(Vec<Vec<u8>>, Script), Error>
+) -> Result<(Vec<Vec<u8>>, Script, Timelocks), Error>
 where
     Pk: ToPublicKey,
     S: Satisfier<Pk>,
     Ext: ParseableExt,
 {
+    trace_span!("best_tap_spend", allow_mall);
     let spend_info = desc.spend_info();
     // First try the key spend path
     if let Some(sig) = satisfier.lookup_tap_key_spend_sig() {
-        Ok((vec![sig.to_vec()], Script::new()))
+        trace_event!("chose key spend");
+        Ok((vec![sig.to_vec()], Script::new(), Timelocks::default()))
     } else {
         // Since we have the complete descriptor we can ignore the satisfier. We don't use the control block
         // map (lookup_control_block) from the satisfier here.
-        let (mut min_wit, mut min_wit_len) = (None, None);
+        let (mut min_wit, mut min_wit_len, mut min_timelocks) = (None, None, None);
+        let (mut blinded_inputs, mut blinded_outputs) = (vec![], vec![]);
         for (depth, ms) in desc.iter_scripts() {
-            let mut wit = if allow_mall {
-                match ms.satisfy_malleable(&satisfier) {
-                    Ok(wit) => wit,
-                    Err(..) => continue, // No witness for this script in tr descriptor, look for next one
-                }
-            } else {
-                match ms.satisfy(&satisfier) {
-                    Ok(wit) => wit,
-                    Err(..) => continue, // No witness for this script in tr descriptor, look for next one
+            let (mut wit, timelocks) = match ms.satisfy_timelocked(&satisfier, allow_mall) {
+                Ok(res) => res,
+                Err(e) => {
+                    trace_event!(depth, "leaf not satisfiable");
+                    if let Error::NeedsUnblinding { inputs, outputs } = e {
+                        blinded_inputs.extend(inputs);
+                        blinded_outputs.extend(outputs);
+                    }
+                    continue; // No witness for this script in tr descriptor, look for next one
                 }
             };
             // Compute the final witness size
@@ -700,6 +1084,7 @@ where
                 + control_block_len(depth)
                 + ms.script_size()
                 + varint_len(ms.script_size());
+            trace_event!(depth, wit_size, "leaf satisfied");
             if min_wit_len.is_some() && Some(wit_size) > min_wit_len {
                 continue;
             } else {
@@ -714,11 +1099,26 @@ where
                 // Finally, save the minimum
                 min_wit = Some(wit);
                 min_wit_len = Some(wit_size);
+                min_timelocks = Some(timelocks);
             }
         }
-        match min_wit {
-            Some(wit) => Ok((wit, Script::new())),
-            None => Err(Error::CouldNotSatisfy), // Could not satisfy all miniscripts inside Tr
+        trace_event!(?min_wit_len, "chose script spend");
+        match (min_wit, min_timelocks) {
+            (Some(wit), Some(timelocks)) => Ok((wit, Script::new(), timelocks)),
+            // Could not satisfy all miniscripts inside Tr
+            _ if blinded_inputs.is_empty() && blinded_outputs.is_empty() => {
+                Err(Error::CouldNotSatisfy)
+            }
+            _ => {
+                blinded_inputs.sort_unstable();
+                blinded_inputs.dedup();
+                blinded_outputs.sort_unstable();
+                blinded_outputs.dedup();
+                Err(Error::NeedsUnblinding {
+                    inputs: blinded_inputs,
+                    outputs: blinded_outputs,
+                })
+            }
         }
     }
 }
@@ -726,7 +1126,7 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{ForEachKey, NoExt};
+    use crate::{Descriptor, ForEachKey, NoExt};
 
     #[test]
     fn test_for_each() {
@@ -747,4 +1147,335 @@ mod tests {
         // Note the last ac12 only has ac and fails the predicate
         assert!(!tr.for_each_key(|k| k.starts_with("acc")));
     }
+
+    #[test]
+    fn taptree_builder() {
+        let leaf = |s: &str| Miniscript::<String, Tap, NoExt>::from_str(s).unwrap();
+
+        assert!(TapTreeBuilder::<String, NoExt>::new().finalize().is_err());
+
+        let mut builder = TapTreeBuilder::new();
+        builder.add_leaf(10, leaf("pk(A)"));
+        builder.add_leaf(1, leaf("pk(B)"));
+        builder.add_leaf(1, leaf("pk(C)"));
+        assert_eq!(builder.len(), 3);
+        let tr = builder.finalize_tr(String::from("K")).unwrap();
+        assert_eq!(tr.to_string(), "eltr(K,{{pk(B),pk(C)},pk(A)})#tgxt5r6a");
+
+        // Hundreds of leaves of equal weight form a balanced tree
+        let mut builder = TapTreeBuilder::new();
+        for i in 0..300 {
+            builder.add_leaf(1, leaf(&format!("pk(U{})", i)));
+        }
+        let tree = builder.finalize().unwrap();
+        assert_eq!(tree.taptree_height(), 9);
+        assert_eq!(tree.iter().count(), 300);
+        assert!(tree.iter().all(|(depth, _)| depth == 8 || depth == 9));
+
+        let ms = Miniscript::<bitcoin::XOnlyPublicKey, Tap, NoExt>::from_str(
+            "pk(26d137d15e2ae24f2d5158663d190d1269ad6b1a6ce330aa825ba502e7519d44)",
+        )
+        .unwrap();
+        let mut builder = TapTreeBuilder::new();
+        builder.add_script(1, &ms.encode()).unwrap();
+        assert!(builder.add_script(1, &Script::new()).is_err());
+        assert_eq!(builder.finalize().unwrap(), TapTree::Leaf(Arc::new(ms)));
+    }
+
+    #[test]
+    fn huffman_descriptor() {
+        let leaf = |s: &str| Miniscript::<String, Tap, NoExt>::from_str(s).unwrap();
+
+        let desc = Descriptor::<String, NoExt>::new_tr_with_huffman(
+            String::from("K"),
+            vec![
+                (0.7, leaf("pk(A)")),
+                (0.1, leaf("pk(B)")),
+                (0.15, leaf("pk(C)")),
+                (0.05, leaf("pk(D)")),
+            ],
+        )
+        .unwrap();
+        let tr = match desc {
+            Descriptor::Tr(ref tr) => tr,
+            _ => unreachable!(),
+        };
+        let depths: Vec<_> = tr
+            .iter_scripts()
+            .map(|(depth, ms)| (ms.to_string(), depth))
+            .collect();
+        assert!(depths.contains(&(String::from("pk(A)"), 1)));
+        assert!(depths.contains(&(String::from("pk(C)"), 2)));
+        assert!(depths.contains(&(String::from("pk(B)"), 3)));
+        assert!(depths.contains(&(String::from("pk(D)"), 3)));
+
+        assert!(
+            Descriptor::<String, NoExt>::new_tr_with_huffman(String::from("K"), vec![]).is_err()
+        );
+        for prob in &[-1.0, std::f64::NAN, std::f64::INFINITY] {
+            assert!(Descriptor::<String, NoExt>::new_tr_with_huffman(
+                String::from("K"),
+                vec![(*prob, leaf("pk(A)"))]
+            )
+            .is_err());
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "compiler")]
+    fn huffman_matches_compiler() {
+        use crate::policy::Concrete;
+
+        let sub = |s: &str| Concrete::<String>::from_str(s).unwrap();
+        let leaves = [
+            (0.9, "and(pk(A),older(5))"),
+            (0.075, "and(pk(B),older(10))"),
+            (0.025, "and(pk(C),after(100))"),
+        ];
+        let policy = sub(&format!(
+            "or(9@{},1@or(3@{},1@{}))",
+            leaves[0].1, leaves[1].1, leaves[2].1
+        ));
+        let compiled = policy.compile_tr(Some(String::from("K"))).unwrap();
+
+        let weighted_leaves = leaves
+            .iter()
+            .map(|&(prob, s)| (prob, sub(s).compile::<Tap>().unwrap()))
+            .collect();
+        let desc =
+            Descriptor::<String, NoExt>::new_tr_with_huffman(String::from("K"), weighted_leaves)
+                .unwrap();
+        assert_eq!(desc, compiled);
+    }
+
+    fn xonly_key(i: u8) -> bitcoin::XOnlyPublicKey {
+        let secp = secp256k1_zkp::Secp256k1::signing_only();
+        let sk = secp256k1_zkp::SecretKey::from_slice(&[i + 1; 32]).unwrap();
+        secp256k1_zkp::KeyPair::from_secret_key(&secp, &sk)
+            .x_only_public_key()
+            .0
+    }
+
+    struct TapSat;
+
+    impl Satisfier<bitcoin::XOnlyPublicKey> for TapSat {
+        fn lookup_tap_leaf_script_sig(
+            &self,
+            _: &bitcoin::XOnlyPublicKey,
+            _: &elements::taproot::TapLeafHash,
+        ) -> Option<elements::SchnorrSig> {
+            Some(elements::SchnorrSig {
+                sig: secp256k1_zkp::schnorr::Signature::from_slice(&[1; 64]).unwrap(),
+                hash_ty: elements::SchnorrSigHashType::Default,
+            })
+        }
+    }
+
+    #[test]
+    fn hidden_nodes() {
+        type TrX = Tr<bitcoin::XOnlyPublicKey, NoExt>;
+        let (k, a, b, c) = (xonly_key(0), xonly_key(1), xonly_key(2), xonly_key(3));
+        let full = TrX::from_str(&format!(
+            "eltr({},{{pk({}),{{pk({}),pk({})}}}})",
+            k, a, b, c
+        ))
+        .unwrap();
+
+        let leaf_hash = |pk: bitcoin::XOnlyPublicKey| {
+            let ms =
+                Miniscript::<bitcoin::XOnlyPublicKey, Tap, NoExt>::from_str(&format!("pk({})", pk))
+                    .unwrap();
+            TapLeafHash::from_script(&ms.encode(), LeafVersion::default())
+        };
+        let branch = {
+            let (mut l, mut r) = (leaf_hash(b).into_inner(), leaf_hash(c).into_inner());
+            if r < l {
+                std::mem::swap(&mut l, &mut r);
+            }
+            let mut engine = TapBranchHash::engine();
+            engine.input(&l);
+            engine.input(&r);
+            TapBranchHash::from_engine(engine)
+        };
+        let partial_strs = [
+            format!(
+                "eltr({},{{pk({}),hidden({})}})",
+                k,
+                a,
+                sha256::Hash::from_inner(branch.into_inner())
+            ),
+            format!(
+                "eltr({},{{pk({}),{{pk({}),hidden({})}}}})",
+                k,
+                a,
+                b,
+                sha256::Hash::from_inner(leaf_hash(c).into_inner())
+            ),
+        ];
+        for s in &partial_strs {
+            let partial = TrX::from_str(s).unwrap();
+            assert_eq!(format!("{:#}", partial), *s);
+            assert!(partial.taptree().as_ref().unwrap().has_hidden_nodes());
+            assert!(partial.lift().is_err());
+            // Same output, and spends of known leaves have the same merkle proof
+            assert_eq!(partial.script_pubkey(), full.script_pubkey());
+            assert_eq!(
+                partial.get_satisfaction(TapSat).unwrap(),
+                full.get_satisfaction(TapSat).unwrap()
+            );
+        }
+        assert!(!full.taptree().as_ref().unwrap().has_hidden_nodes());
+
+        // Only the key spend is known
+        let key_only =
+            TrX::from_str(&format!("eltr({},hidden({}))", k, sha256::Hash::hash(&[]))).unwrap();
+        assert_eq!(key_only.max_satisfaction_weight().unwrap(), 4 + 1 + 1 + 65);
+        assert!(key_only.get_satisfaction(TapSat).is_err());
+        assert!(TrX::from_str(&format!("eltr({},hidden(00))", k)).is_err());
+    }
+
+    #[test]
+    fn leaf_only() {
+        type TrX = Tr<bitcoin::XOnlyPublicKey, NoExt>;
+        let (k, a, b, c) = (xonly_key(0), xonly_key(1), xonly_key(2), xonly_key(3));
+        let full = TrX::from_str(&format!(
+            "eltr({},{{pk({}),{{pk({}),pk({})}}}})",
+            k, a, b, c
+        ))
+        .unwrap();
+        let leaf = |pk: bitcoin::XOnlyPublicKey| {
+            Miniscript::<bitcoin::XOnlyPublicKey, Tap, NoExt>::from_str(&format!("pk({})", pk))
+                .unwrap()
+                .encode()
+        };
+        let leaf_hash = |pk| TapLeafHash::from_script(&leaf(pk), LeafVersion::default());
+
+        let reduced = full.leaf_only(&leaf_hash(b)).unwrap();
+        assert_eq!(
+            format!("{:#}", reduced),
+            format!(
+                "eltr({},{{hidden({}),{{pk({}),hidden({})}}}})",
+                k,
+                sha256::Hash::from_inner(leaf_hash(a).into_inner()),
+                b,
+                sha256::Hash::from_inner(leaf_hash(c).into_inner())
+            )
+        );
+        assert_eq!(reduced.script_pubkey(), full.script_pubkey());
+        let script = (leaf(b), LeafVersion::default());
+        assert_eq!(
+            reduced.spend_info().control_block(&script),
+            full.spend_info().control_block(&script)
+        );
+        // The reduced descriptor can only spend with the given leaf
+        let (wit, _) = reduced.get_satisfaction(TapSat).unwrap();
+        assert_eq!(wit[1], leaf(b).into_bytes());
+        assert_eq!(reduced.leaf_only(&leaf_hash(b)).unwrap(), reduced);
+        assert!(reduced.leaf_only(&leaf_hash(a)).is_none());
+
+        // Subtrees without the leaf are collapsed to a single hidden node
+        let reduced = full.leaf_only(&leaf_hash(a)).unwrap();
+        assert_eq!(reduced.iter_scripts().count(), 1);
+        assert_eq!(reduced.merkle_root(), full.merkle_root());
+
+        let desc = Descriptor::<bitcoin::XOnlyPublicKey, NoExt>::Tr(full);
+        assert!(desc.leaf_only(&leaf_hash(k)).is_none());
+        let bare = Descriptor::<bitcoin::PublicKey, NoExt>::from_str(
+            "elpkh(028c28a97bf8298bc0d23d8c749452a32e694b65e30a9472a3954ab30fe5324caa)",
+        )
+        .unwrap();
+        assert!(bare.leaf_only(&leaf_hash(a)).is_none());
+    }
+
+    #[test]
+    fn max_depth_tree() {
+        type TrX = Tr<bitcoin::XOnlyPublicKey, NoExt>;
+        // A chain of `depth` hashlocks without known preimages ending in a key
+        let chain = |depth: u8| {
+            let mut tree = format!("pk({})", xonly_key(1));
+            for i in 0..depth {
+                let leaf = format!(
+                    "and_v(v:pk({}),sha256({}))",
+                    xonly_key(1),
+                    sha256::Hash::hash(&[i])
+                );
+                tree = format!("{{{},{}}}", leaf, tree);
+            }
+            format!("eltr({},{})", xonly_key(0), tree)
+        };
+
+        let s = chain(128);
+        let tr = TrX::from_str(&s).unwrap();
+        assert_eq!(format!("{:#}", tr), s);
+        assert_eq!(tr.iter_scripts().last().unwrap().0, 128);
+        let (witness, _) = tr.get_satisfaction(TapSat).unwrap();
+        assert_eq!(
+            witness.last().unwrap().len(),
+            TAPROOT_CONTROL_BASE_SIZE + 128 * 32
+        );
+
+        assert!(TrX::from_str(&chain(129)).is_err());
+    }
+
+    #[test]
+    fn streaming_leaves() {
+        type TrX = Tr<bitcoin::XOnlyPublicKey, NoExt>;
+        let mut tree = format!("pk({})", xonly_key(0));
+        for i in 1..40 {
+            let leaf = format!(
+                "and_v(v:multi_a(2,{},{},{}),older({}))",
+                xonly_key(i),
+                xonly_key(i + 1),
+                xonly_key(i + 2),
+                i
+            );
+            tree = if i % 3 == 0 {
+                format!("{{{},{}}}", tree, leaf)
+            } else {
+                format!("{{{},{}}}", leaf, tree)
+            };
+        }
+        let tr = TrX::from_str(&format!("eltr({},{})", xonly_key(100), tree)).unwrap();
+        assert_eq!(tr.merkle_root(), tr.spend_info().merkle_root());
+
+        // Hidden nodes are kept in the streamed merkle root
+        let first = tr.iter_scripts().next().unwrap().1.encode();
+        let hidden = sha256::Hash::from_inner(
+            TapLeafHash::from_script(&first, LeafVersion::default()).into_inner(),
+        );
+        let s = tr.to_string();
+        let s = s[..s.len() - 9].replacen(
+            &tr.iter_scripts().next().unwrap().1.to_string(),
+            &format!("hidden({})", hidden),
+            1,
+        );
+        let partial = TrX::from_str(&s).unwrap();
+        assert!(partial.taptree().as_ref().unwrap().has_hidden_nodes());
+        assert_eq!(partial.merkle_root(), tr.merkle_root());
+        assert_eq!(
+            TrX::from_str(&format!("eltr({})", xonly_key(100)))
+                .unwrap()
+                .merkle_root(),
+            None
+        );
+
+        let mut leaves = vec![];
+        let len = tr
+            .taptree()
+            .as_ref()
+            .unwrap()
+            .write_leaf_scripts(&mut leaves)
+            .unwrap();
+        assert_eq!(len, leaves.len());
+        let mut cursor = &leaves[..];
+        for (depth, ms) in tr.iter_scripts() {
+            let script = ms.encode();
+            assert_eq!(cursor[0] as usize, depth);
+            assert_eq!(cursor[1], LeafVersion::default().as_u8());
+            let encoded = elements::encode::serialize(&script);
+            assert_eq!(&cursor[2..2 + encoded.len()], &encoded[..]);
+            cursor = &cursor[2 + encoded.len()..];
+        }
+        assert!(cursor.is_empty());
+    }
 }
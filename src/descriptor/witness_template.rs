@@ -0,0 +1,342 @@
+// Miniscript
+// Written in 2023 by
+//     The Elements Miniscript developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Witness Templates
+//!
+//! The satisfaction of a planned spend with its signatures and preimages
+//! left as slots, for signing services which fill them in without knowing
+//! about Miniscript.
+//!
+
+use elements::taproot::{ControlBlock, LeafVersion, TapLeafHash};
+use elements::{EcdsaSigHashType, SchnorrSigHashType, Script};
+
+use super::plan::{dummy_ecdsa_sig, dummy_preimage, dummy_schnorr_sig, script_sig_elements};
+use super::{Assets, Descriptor, ShInner, SpendHash};
+use crate::extensions::ParseableExt;
+use crate::miniscript::satisfy::Preimage32;
+use crate::{util, Error, Extension, MiniscriptKey, Satisfier, ToPublicKey};
+
+/// The sighash type a [`Placeholder::Sig`] is made with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlaceholderSigHash {
+    /// An ECDSA signature, for legacy and segwit v0 spends
+    Ecdsa(EcdsaSigHashType),
+    /// A Schnorr signature, for taproot spends
+    Schnorr(SchnorrSigHashType),
+}
+
+/// An element of a [`WitnessTemplate`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Placeholder<Pk: MiniscriptKey> {
+    /// A signature of the key, serialized with its sighash type
+    Sig(Pk, PlaceholderSigHash),
+    /// The preimage of the hash
+    Preimage(SpendHash<Pk>),
+    /// A fixed push, e.g. a public key or the empty dissatisfaction
+    Push(Vec<u8>),
+    /// The script being satisfied: the redeem script, witness script or
+    /// tap leaf script
+    Script(Script),
+    /// The control block of a taproot script path spend
+    ControlBlock(ControlBlock),
+}
+
+/// The satisfaction of a spend with slots for signatures and preimages, see
+/// [`Descriptor::witness_template`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WitnessTemplate<Pk: MiniscriptKey> {
+    /// The pushes of the script sig, in order
+    pub script_sig: Vec<Placeholder<Pk>>,
+    /// The witness stack, bottom first
+    pub witness: Vec<Placeholder<Pk>>,
+    /// Hash of the spent tap leaf, which Schnorr signatures of script path
+    /// spends commit to. `None` for a key spend.
+    pub leaf: Option<TapLeafHash>,
+}
+
+// The placeholder of a satisfaction element made by the plan satisfier
+fn placeholder<Pk: MiniscriptKey>(assets: &Assets<Pk>, elem: &[u8]) -> Placeholder<Pk> {
+    for (i, pk) in assets.keys.iter().enumerate() {
+        let (sig, hash_ty) = dummy_ecdsa_sig(i as u32);
+        let mut ecdsa = sig.serialize_der().to_vec();
+        ecdsa.push(hash_ty as u8);
+        if elem == &ecdsa[..] {
+            return Placeholder::Sig(pk.clone(), PlaceholderSigHash::Ecdsa(hash_ty));
+        }
+        let schnorr = dummy_schnorr_sig(i as u32);
+        if elem == &schnorr.to_vec()[..] {
+            return Placeholder::Sig(pk.clone(), PlaceholderSigHash::Schnorr(schnorr.hash_ty));
+        }
+    }
+    for (i, hash) in assets.hashes.iter().enumerate() {
+        if elem == &dummy_preimage(i as u32)[..] {
+            return Placeholder::Preimage(hash.clone());
+        }
+    }
+    Placeholder::Push(elem.to_vec())
+}
+
+impl<Pk: MiniscriptKey + ToPublicKey, Ext: Extension + ParseableExt> Descriptor<Pk, Ext> {
+    /// The witness template of the spend [`Descriptor::plan`] chooses with
+    /// the given assets.
+    ///
+    /// Signatures are ECDSA with `SIGHASH_ALL` and Schnorr with
+    /// `SIGHASH_DEFAULT`, the ones the plan weight assumes. Fill the template
+    /// with [`WitnessTemplate::fill`] once the signatures, and the preimages
+    /// not known yet, are available.
+    ///
+    /// # Errors
+    /// When the descriptor cannot be satisfied with the assets.
+    pub fn witness_template(&self, assets: &Assets<Pk>) -> Result<WitnessTemplate<Pk>, Error> {
+        let (witness, script_sig) = self.plan_satisfaction(assets, assets.after, assets.older)?;
+        let mut script_sig: Vec<_> = script_sig_elements(&script_sig)
+            .iter()
+            .map(|elem| placeholder(assets, elem))
+            .collect();
+        let mut witness: Vec<_> = witness
+            .iter()
+            .map(|elem| placeholder(assets, elem))
+            .collect();
+
+        // The scripts and control blocks are recognized by their position
+        fn last_script<Pk: MiniscriptKey>(elems: &mut [Placeholder<Pk>], from_end: usize) {
+            let idx = elems.len() - from_end;
+            if let Placeholder::Push(ref bytes) = elems[idx] {
+                elems[idx] = Placeholder::Script(Script::from(bytes.clone()));
+            }
+        }
+        let mut leaf = None;
+        match *self {
+            Descriptor::Bare(..) | Descriptor::Pkh(..) | Descriptor::Wpkh(..) => {}
+            Descriptor::Sh(ref sh) => {
+                last_script(&mut script_sig, 1);
+                if let ShInner::Wsh(..) = *sh.as_inner() {
+                    last_script(&mut witness, 1);
+                }
+            }
+            Descriptor::Wsh(..) | Descriptor::LegacyCSFSCov(..) => last_script(&mut witness, 1),
+            Descriptor::Tr(..) | Descriptor::TrExt(..) => {
+                if witness.len() > 1 {
+                    let n = witness.len();
+                    if let Placeholder::Push(ref bytes) = witness[n - 1] {
+                        let control_block = ControlBlock::from_slice(bytes)
+                            .map_err(|e| Error::Unexpected(e.to_string()))?;
+                        witness[n - 1] = Placeholder::ControlBlock(control_block);
+                    }
+                    last_script(&mut witness, 2);
+                    if let Placeholder::Script(ref script) = witness[n - 2] {
+                        leaf = Some(TapLeafHash::from_script(script, LeafVersion::default()));
+                    }
+                }
+            }
+        }
+
+        Ok(WitnessTemplate {
+            script_sig,
+            witness,
+            leaf,
+        })
+    }
+}
+
+impl<Pk: MiniscriptKey + ToPublicKey> WitnessTemplate<Pk> {
+    /// Fill the slots of the template with the signatures and preimages of
+    /// `satisfier`, returning the witness and script sig.
+    ///
+    /// Signatures are used with the sighash type the satisfier returns them
+    /// with, which need not be the one of the template.
+    ///
+    /// # Errors
+    /// [`Error::CouldNotSatisfy`] when a signature or preimage is missing.
+    pub fn fill<S: Satisfier<Pk>>(&self, satisfier: &S) -> Result<(Vec<Vec<u8>>, Script), Error> {
+        let fill = |elems: &[Placeholder<Pk>]| -> Result<Vec<Vec<u8>>, Error> {
+            elems
+                .iter()
+                .map(|elem| self.fill_one(elem, satisfier).ok_or(Error::CouldNotSatisfy))
+                .collect()
+        };
+        let script_sig = util::witness_to_scriptsig(&fill(&self.script_sig)?);
+        Ok((fill(&self.witness)?, script_sig))
+    }
+
+    fn fill_one<S: Satisfier<Pk>>(&self, elem: &Placeholder<Pk>, satisfier: &S) -> Option<Vec<u8>> {
+        match *elem {
+            Placeholder::Sig(ref pk, PlaceholderSigHash::Ecdsa(..)) => {
+                let (sig, hash_ty) = satisfier.lookup_ecdsa_sig(pk)?;
+                let mut ser = sig.serialize_der().to_vec();
+                ser.push(hash_ty as u8);
+                Some(ser)
+            }
+            Placeholder::Sig(ref pk, PlaceholderSigHash::Schnorr(..)) => {
+                let sig = match self.leaf {
+                    Some(ref leaf) => satisfier.lookup_tap_leaf_script_sig(pk, leaf),
+                    None => satisfier.lookup_tap_key_spend_sig(),
+                };
+                sig.map(|sig| sig.to_vec())
+            }
+            Placeholder::Preimage(ref hash) => {
+                let preimage: Preimage32 = match *hash {
+                    SpendHash::Sha256(ref h) => satisfier.lookup_sha256(h),
+                    SpendHash::Hash256(ref h) => satisfier.lookup_hash256(h),
+                    SpendHash::Ripemd160(ref h) => satisfier.lookup_ripemd160(h),
+                    SpendHash::Hash160(ref h) => satisfier.lookup_hash160(h),
+                }?;
+                Some(preimage.to_vec())
+            }
+            Placeholder::Push(ref bytes) => Some(bytes.clone()),
+            Placeholder::Script(ref script) => Some(script.to_bytes()),
+            Placeholder::ControlBlock(ref control_block) => Some(control_block.serialize()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::str::FromStr;
+
+    use elements::hashes::{sha256, Hash};
+    use elements::secp256k1_zkp::{ecdsa, schnorr};
+
+    use super::*;
+    use crate::{ElementsSig, NoExt};
+
+    const A: &str = "028c28a97bf8298bc0d23d8c749452a32e694b65e30a9472a3954ab30fe5324caa";
+    const B: &str = "03daed4f2be3a8bf278e70132fb0beb7522f570e144bf615c07e996d443dee8729";
+
+    fn pk(s: &str) -> bitcoin::PublicKey {
+        bitcoin::PublicKey::from_str(s).unwrap()
+    }
+
+    // Signs with fixed signatures and knows one preimage
+    struct Signer {
+        ecdsa: HashMap<bitcoin::PublicKey, ElementsSig>,
+        schnorr: HashMap<bitcoin::PublicKey, elements::SchnorrSig>,
+        preimage: Preimage32,
+    }
+
+    impl Signer {
+        fn new(keys: &[&str]) -> Self {
+            let mut compact = [0x11; 64];
+            let mut ecdsa = HashMap::new();
+            let mut schnorr = HashMap::new();
+            for (i, key) in keys.iter().enumerate() {
+                compact[0] = i as u8 + 1;
+                let sig = ecdsa::Signature::from_compact(&compact).unwrap();
+                ecdsa.insert(pk(key), (sig, EcdsaSigHashType::All));
+                let sig = elements::SchnorrSig {
+                    sig: schnorr::Signature::from_slice(&compact).unwrap(),
+                    hash_ty: SchnorrSigHashType::Default,
+                };
+                schnorr.insert(pk(key), sig);
+            }
+            Signer {
+                ecdsa,
+                schnorr,
+                preimage: [1; 32],
+            }
+        }
+    }
+
+    impl Satisfier<bitcoin::PublicKey> for Signer {
+        fn lookup_ecdsa_sig(&self, pk: &bitcoin::PublicKey) -> Option<ElementsSig> {
+            self.ecdsa.get(pk).cloned()
+        }
+
+        fn lookup_tap_leaf_script_sig(
+            &self,
+            pk: &bitcoin::PublicKey,
+            _: &TapLeafHash,
+        ) -> Option<elements::SchnorrSig> {
+            self.schnorr.get(pk).cloned()
+        }
+
+        fn lookup_sha256(&self, h: &sha256::Hash) -> Option<Preimage32> {
+            if sha256::Hash::hash(&self.preimage) == *h {
+                Some(self.preimage)
+            } else {
+                None
+            }
+        }
+    }
+
+    #[test]
+    fn witness_template_sh_wsh() {
+        let hash = sha256::Hash::hash(&[1; 32]);
+        let desc = Descriptor::<bitcoin::PublicKey, NoExt>::from_str(&format!(
+            "elsh(wsh(or_d(pk({}),and_v(v:pk({}),sha256({})))))",
+            A, B, hash
+        ))
+        .unwrap();
+        let assets = Assets::new()
+            .add_key(pk(B))
+            .add_hash(SpendHash::Sha256(hash));
+        let template = desc.witness_template(&assets).unwrap();
+        let witness_script = desc.script_code().unwrap();
+        assert_eq!(
+            template.witness,
+            vec![
+                Placeholder::Preimage(SpendHash::Sha256(hash)),
+                Placeholder::Sig(pk(B), PlaceholderSigHash::Ecdsa(EcdsaSigHashType::All)),
+                Placeholder::Push(vec![]),
+                Placeholder::Script(witness_script),
+            ]
+        );
+        assert_eq!(template.script_sig.len(), 1);
+        assert_eq!(template.leaf, None);
+
+        let signer = Signer::new(&[B]);
+        assert_eq!(
+            template.fill(&signer).unwrap(),
+            desc.get_satisfaction(&signer).unwrap()
+        );
+        // No signature of B
+        assert!(template.fill(&()).is_err());
+    }
+
+    #[test]
+    fn witness_template_tr() {
+        let desc = Descriptor::<bitcoin::PublicKey, NoExt>::from_str(&format!(
+            "eltr({},{{pk({}),and_v(v:pk({}),after(100))}})",
+            A, B, A
+        ))
+        .unwrap();
+        let template = desc
+            .witness_template(&Assets::new().add_key(pk(B)))
+            .unwrap();
+        assert_eq!(template.witness.len(), 3);
+        assert_eq!(
+            template.witness[0],
+            Placeholder::Sig(
+                pk(B),
+                PlaceholderSigHash::Schnorr(SchnorrSigHashType::Default)
+            )
+        );
+        match (&template.witness[1], &template.witness[2]) {
+            (Placeholder::Script(script), Placeholder::ControlBlock(..)) => assert_eq!(
+                template.leaf,
+                Some(TapLeafHash::from_script(script, LeafVersion::default()))
+            ),
+            _ => panic!("expected a script path spend"),
+        }
+        assert!(template.script_sig.is_empty());
+
+        let signer = Signer::new(&[A, B]);
+        assert_eq!(
+            template.fill(&signer).unwrap(),
+            desc.get_satisfaction(&signer).unwrap()
+        );
+    }
+}
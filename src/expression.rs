@@ -15,8 +15,8 @@
 //! # Function-like Expression Language
 //!
 
-use std::fmt;
 use std::str::FromStr;
+use std::{cmp, fmt};
 
 use crate::{errstr, Error, MAX_RECURSION_DEPTH};
 
@@ -152,66 +152,99 @@ impl<'a> Tree<'a> {
     }
 
     pub(crate) fn from_slice_delim(
+        sl: &'a str,
+        depth: u32,
+        delim: char,
+    ) -> Result<(Tree<'a>, &'a str), Error> {
+        Self::from_slice_delim_max_depth(sl, depth, delim, MAX_RECURSION_DEPTH)
+    }
+
+    // Parse an expression starting at `depth`, failing if it nests to
+    // `max_depth` or beyond. The parser keeps the enclosing function calls on
+    // an explicit stack rather than recursing, so its stack usage does not
+    // depend on the input.
+    fn from_slice_delim_max_depth(
         mut sl: &'a str,
         depth: u32,
         delim: char,
+        max_depth: u32,
     ) -> Result<(Tree<'a>, &'a str), Error> {
-        if depth >= MAX_RECURSION_DEPTH {
-            return Err(Error::MaxRecursiveDepthExceeded);
-        }
+        let closing = closing_delim(delim);
+        // Function calls whose arguments are being parsed, innermost last
+        let mut stack: Vec<Tree<'a>> = vec![];
+        loop {
+            if depth as usize + stack.len() >= max_depth as usize {
+                return Err(Error::MaxRecursiveDepthExceeded);
+            }
 
-        match next_expr(sl, delim) {
-            // String-ending terminal
-            Found::Nothing => Ok((
-                Tree {
-                    name: sl,
-                    args: vec![],
-                },
-                "",
-            )),
-            // Terminal
-            Found::Comma(n) | Found::RBracket(n) => Ok((
-                Tree {
-                    name: &sl[..n],
-                    args: vec![],
-                },
-                &sl[n..],
-            )),
-            // Function call
-            Found::LBracket(n) => {
-                let mut ret = Tree {
-                    name: &sl[..n],
-                    args: vec![],
-                };
+            let mut node = match next_expr(sl, delim) {
+                // String-ending terminal
+                Found::Nothing => {
+                    let node = Tree {
+                        name: sl,
+                        args: vec![],
+                    };
+                    sl = "";
+                    node
+                }
+                // Terminal
+                Found::Comma(n) | Found::RBracket(n) => {
+                    let node = Tree {
+                        name: &sl[..n],
+                        args: vec![],
+                    };
+                    sl = &sl[n..];
+                    node
+                }
+                // Function call, parse its first argument next
+                Found::LBracket(n) => {
+                    stack.push(Tree {
+                        name: &sl[..n],
+                        args: vec![],
+                    });
+                    sl = &sl[n + 1..];
+                    continue;
+                }
+            };
 
-                sl = &sl[n + 1..];
-                loop {
-                    let (arg, new_sl) = Tree::from_slice_delim(sl, depth + 1, delim)?;
-                    ret.args.push(arg);
+            // Add the node to its parent, which is complete itself if the
+            // node is its last argument
+            loop {
+                let mut parent = match stack.pop() {
+                    Some(parent) => parent,
+                    None => return Ok((node, sl)),
+                };
+                parent.args.push(node);
 
-                    if new_sl.is_empty() {
-                        return Err(Error::ExpectedChar(closing_delim(delim)));
+                match sl.chars().next() {
+                    Some(',') => {
+                        sl = &sl[1..];
+                        stack.push(parent);
+                        break;
                     }
-
-                    sl = &new_sl[1..];
-                    match new_sl.as_bytes()[0] {
-                        b',' => {}
-                        last_byte => {
-                            if last_byte == closing_delim(delim) as u8 {
-                                break;
-                            } else {
-                                return Err(Error::ExpectedChar(closing_delim(delim)));
-                            }
-                        }
+                    Some(ch) if ch == closing => {
+                        sl = &sl[1..];
+                        node = parent;
                     }
+                    _ => return Err(Error::ExpectedChar(closing)),
                 }
-                Ok((ret, sl))
             }
         }
     }
 
     /// Parses a tree from a string
     pub fn from_str(s: &'a str) -> Result<Tree<'a>, Error> {
+        Self::from_str_with_max_depth(s, MAX_RECURSION_DEPTH)
+    }
+
+    /// Parses a tree from a string, failing if it nests `max_depth` levels
+    /// or more, e.g. to reject untrusted input early. The limit is at most
+    /// the one of [`Tree::from_str`], as converting trees into descriptors
+    /// and Miniscripts is recursive.
+    ///
+    /// Parsing does not allocate beyond the nodes of the tree, whose names
+    /// borrow from `s`.
+    pub fn from_str_with_max_depth(s: &'a str, max_depth: u32) -> Result<Tree<'a>, Error> {
         // Filter out non-ASCII because we byte-index strings all over the
         // place and Rust gets very upsbt when you splinch a string.
         for ch in s.bytes() {
@@ -220,7 +253,8 @@ impl<'a> Tree<'a> {
             }
         }
 
-        let (top, rem) = Tree::from_slice(s)?;
+        let max_depth = cmp::min(max_depth, MAX_RECURSION_DEPTH);
+        let (top, rem) = Tree::from_slice_delim_max_depth(s, 0, '(', max_depth)?;
         if rem.is_empty() {
             Ok(top)
         } else {
@@ -295,7 +329,8 @@ where
 #[cfg(test)]
 mod tests {
 
-    use super::parse_num;
+    use super::{parse_num, Tree};
+    use crate::Error;
 
     #[test]
     fn test_parse_num() {
@@ -306,4 +341,30 @@ mod tests {
         assert!(parse_num::<u32>("+6").is_err());
         assert!(parse_num::<u32>("-6").is_err());
     }
+    #[test]
+    fn test_parse_tree() {
+        let tree = Tree::from_str("or_b(pk(A),s:pk(slip77(B,C)))").unwrap();
+        assert_eq!(tree.to_string(), "(or_b,(pk,(A)),(s:pk,(slip77(B,C))))");
+        match Tree::from_str("pk(A").unwrap_err() {
+            Error::ExpectedChar(')') => {}
+            e => panic!("unexpected {:?}", e),
+        }
+        match Tree::from_str("pk(A,B)}").unwrap_err() {
+            Error::Unexpected(_) => {}
+            e => panic!("unexpected {:?}", e),
+        }
+
+        // Deep nesting fails without exhausting the stack
+        let deep = format!("{}0{}", "n(".repeat(100_000), ")".repeat(100_000));
+        match Tree::from_str(&deep).unwrap_err() {
+            Error::MaxRecursiveDepthExceeded => {}
+            e => panic!("unexpected {:?}", e),
+        }
+        let s = "or_b(pk(A),s:pk(B))";
+        assert!(Tree::from_str_with_max_depth(s, 3).is_ok());
+        match Tree::from_str_with_max_depth(s, 2).unwrap_err() {
+            Error::MaxRecursiveDepthExceeded => {}
+            e => panic!("unexpected {:?}", e),
+        }
+    }
 }
@@ -28,7 +28,8 @@ use crate::{
 ///     - Any of operations are on confidential amounts. The Null case is automatically
 ///       converted to explicit zero.
 ///     - Supplied index is out of bounds.
-///     - Any of the operations overflow. Refer to tapscript opcodes spec for overflow specification
+///     - Any of the operations overflow. Refer to tapscript opcodes spec for overflow specification.
+///       Expressions of only constants that always overflow are rejected when parsing.
 ///     - In extreme cases, when recursive operations exceed 400 depth
 #[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Clone)]
 pub enum ExprInner {
@@ -103,6 +104,8 @@ pub struct Expr {
     script_size: usize,
     /// depth of expression thunk/tree
     depth: usize,
+    /// The cached maximum number of stack elements while executing
+    stack_elem_count: usize,
 }
 
 impl Expr {
@@ -126,72 +129,172 @@ impl Expr {
         self.depth
     }
 
+    /// Obtains the maximum number of stack elements used while executing
+    /// this expression, including its result
+    pub fn stack_elem_count(&self) -> usize {
+        self.stack_elem_count
+    }
+
     /// Creates [`Expr`] from [`ExprInner`]
     pub fn from_inner(inner: ExprInner) -> Self {
-        let (script_size, depth) = match &inner {
-            ExprInner::Const(_c) => (8 + 1, 0),
-            ExprInner::CurrInputIdx => (4, 0), // INSPECTCURRENTINPUTINDEX INPSECTINPUTVALUE <1> EQUALVERIFY
+        // Peak number of stack elements while executing [X] [Y] <op>, where
+        // the operation itself needs `op` elements.
+        fn binary(x: &Expr, y: &Expr, op: usize) -> usize {
+            cmp::max(cmp::max(x.stack_elem_count, 1 + y.stack_elem_count), op)
+        }
+        let (script_size, depth, stack_elem_count) = match &inner {
+            ExprInner::Const(_c) => (8 + 1, 0, 1),
+            // INSPECTCURRENTINPUTINDEX INPSECTINPUTVALUE <1> EQUALVERIFY
+            ExprInner::CurrInputIdx => (4, 0, 3),
             ExprInner::Input(i) => (
                 i.script_size() + 3, // i INPSECTINPUTVALUE <1> EQUALVERIFY
                 0,
+                cmp::max(i.stack_elem_count(), 3),
             ),
             ExprInner::Output(i) => (
                 i.script_size() + 3, // i INPSECTOUTPUTVALUE <1> EQUALVERIFY
                 0,
+                cmp::max(i.stack_elem_count(), 3),
             ),
             ExprInner::InputIssue(i) => (
                 i.script_size() + 7, // i OP_INSPECTINPUTISSUANCE DROP DROP <1> EQUALVERIFY NIP NIP
                 0,
+                cmp::max(i.stack_elem_count(), 6), // issuance pushes 6 elements
             ),
             ExprInner::InputReIssue(i) => (
                 i.script_size() + 7, // i OP_INSPECTINPUTISSUANCE DROP DROP DROP DROP <1> EQUALVERIFY
                 0,
+                cmp::max(i.stack_elem_count(), 6), // issuance pushes 6 elements
             ),
             ExprInner::Add(x, y) => (
                 x.script_size + y.script_size + 3, // [X] [Y] ADD64 <1> EQUALVERIFY
-                cmp::max(x.depth, y.depth),
+                cmp::max(x.depth, y.depth) + 1,
+                binary(x, y, 3), // result, success flag, <1>
             ),
             ExprInner::Sub(x, y) => (
                 x.script_size + y.script_size + 3, // [X] [Y] SUB64 <1> EQUALVERIFY
-                cmp::max(x.depth, y.depth),
+                cmp::max(x.depth, y.depth) + 1,
+                binary(x, y, 3),
             ),
             ExprInner::Mul(x, y) => (
                 x.script_size + y.script_size + 3, // [X] [Y] MUL64 <1> EQUALVERIFY
-                cmp::max(x.depth, y.depth),
+                cmp::max(x.depth, y.depth) + 1,
+                binary(x, y, 3),
             ),
             ExprInner::Div(x, y) => (
                 x.script_size + y.script_size + 4, // [X] [Y] DIV64 <1> EQUALVERIFY NIP
-                cmp::max(x.depth, y.depth),
+                cmp::max(x.depth, y.depth) + 1,
+                binary(x, y, 4), // remainder, quotient, success flag, <1>
             ),
             ExprInner::Mod(x, y) => (
                 x.script_size + y.script_size + 4, // [X] [Y] DIV64 <1> EQUALVERIFY DROP
-                cmp::max(x.depth, y.depth),
+                cmp::max(x.depth, y.depth) + 1,
+                binary(x, y, 4),
             ),
             ExprInner::BitAnd(x, y) => (
                 x.script_size + y.script_size + 1, // [X] [Y] AND
-                cmp::max(x.depth, y.depth),
+                cmp::max(x.depth, y.depth) + 1,
+                binary(x, y, 0),
             ),
             ExprInner::BitOr(x, y) => (
                 x.script_size + y.script_size + 1, // [X] [Y] OR
-                cmp::max(x.depth, y.depth),
+                cmp::max(x.depth, y.depth) + 1,
+                binary(x, y, 0),
             ),
             ExprInner::Xor(x, y) => (
                 x.script_size + y.script_size + 1, // [X] [Y] XOR
-                cmp::max(x.depth, y.depth),
+                cmp::max(x.depth, y.depth) + 1,
+                binary(x, y, 0),
             ),
             ExprInner::Invert(x) => (
                 x.script_size + 1, // [X] INVERT
                 x.depth + 1,
+                x.stack_elem_count,
             ),
             ExprInner::Negate(x) => (
                 x.script_size + 3, // [X] NEG64 <1> EQUALVERIFY
                 x.depth + 1,
+                cmp::max(x.stack_elem_count, 3),
             ),
         };
         Self {
             inner,
             script_size,
             depth,
+            stack_elem_count,
+        }
+    }
+
+    /// Adds the indexes of the inputs and outputs whose values this
+    /// expression reads, but which are confidential in `env`
+    fn blinded_refs(&self, env: &TxEnv, inputs: &mut Vec<usize>, outputs: &mut Vec<usize>) {
+        // Out of bounds indexes are reported by `eval`
+        let idx = |i: &IdxExpr| i.eval(env).ok();
+        match &self.inner {
+            ExprInner::Const(_) => {}
+            ExprInner::CurrInputIdx => {
+                if let Some(utxo) = env.spent_utxos.get(env.idx) {
+                    if utxo.value.explicit().is_none() {
+                        inputs.push(env.idx);
+                    }
+                }
+            }
+            ExprInner::Input(i) => {
+                if let Some(i) = idx(i) {
+                    if env
+                        .spent_utxos
+                        .get(i)
+                        .map_or(false, |u| u.value.explicit().is_none())
+                    {
+                        inputs.push(i);
+                    }
+                }
+            }
+            ExprInner::Output(i) => {
+                if let Some(i) = idx(i) {
+                    if env
+                        .tx
+                        .output
+                        .get(i)
+                        .map_or(false, |o| o.value.explicit().is_none())
+                    {
+                        outputs.push(i);
+                    }
+                }
+            }
+            ExprInner::InputIssue(i) => {
+                if let Some(i) = idx(i) {
+                    if env
+                        .tx
+                        .input
+                        .get(i)
+                        .map_or(false, |inp| inp.asset_issuance.amount.explicit().is_none())
+                    {
+                        inputs.push(i);
+                    }
+                }
+            }
+            ExprInner::InputReIssue(i) => {
+                if let Some(i) = idx(i) {
+                    if env.tx.input.get(i).map_or(false, |inp| {
+                        inp.asset_issuance.inflation_keys.explicit().is_none()
+                    }) {
+                        inputs.push(i);
+                    }
+                }
+            }
+            ExprInner::Add(x, y)
+            | ExprInner::Sub(x, y)
+            | ExprInner::Mul(x, y)
+            | ExprInner::Div(x, y)
+            | ExprInner::Mod(x, y)
+            | ExprInner::BitAnd(x, y)
+            | ExprInner::BitOr(x, y)
+            | ExprInner::Xor(x, y) => {
+                x.blinded_refs(env, inputs, outputs);
+                y.blinded_refs(env, inputs, outputs);
+            }
+            ExprInner::Invert(x) | ExprInner::Negate(x) => x.blinded_refs(env, inputs, outputs),
         }
     }
 
@@ -258,54 +361,80 @@ impl Expr {
                     .map(|x| x as i64) // safe conversion bitcoin values from u64 to i64 because 21 mil
                     .ok_or(EvalError::NonExplicitInputReIssuance(i))
             }
-            ExprInner::Add(x, y) => {
-                let x = x.eval(env)?;
-                let y = y.eval(env)?;
-                x.checked_add(y).ok_or(EvalError::AddOverflow(x, y))
-            }
-            ExprInner::Sub(x, y) => {
+            ExprInner::Add(x, y)
+            | ExprInner::Sub(x, y)
+            | ExprInner::Mul(x, y)
+            | ExprInner::Div(x, y)
+            | ExprInner::Mod(x, y)
+            | ExprInner::BitAnd(x, y)
+            | ExprInner::BitOr(x, y)
+            | ExprInner::Xor(x, y) => {
                 let x = x.eval(env)?;
                 let y = y.eval(env)?;
-                x.checked_sub(y).ok_or(EvalError::SubOverflow(x, y))
+                self.apply(x, y)
             }
-            ExprInner::Mul(x, y) => {
+            ExprInner::Invert(x) | ExprInner::Negate(x) => {
                 let x = x.eval(env)?;
-                let y = y.eval(env)?;
-                x.checked_mul(y).ok_or(EvalError::MulOverflow(x, y))
+                self.apply(x, 0)
             }
-            ExprInner::Div(x, y) => {
-                let x = x.eval(env)?;
-                let y = y.eval(env)?;
-                x.checked_div_euclid(y).ok_or(EvalError::DivOverflow(x, y))
-            }
-            ExprInner::Mod(x, y) => {
-                let x = x.eval(env)?;
-                let y = y.eval(env)?;
-                x.checked_rem_euclid(y).ok_or(EvalError::ModOverflow(x, y))
-            }
-            ExprInner::BitAnd(x, y) => {
-                let x = x.eval(env)?;
-                let y = y.eval(env)?;
-                Ok(x & y)
-            }
-            ExprInner::BitOr(x, y) => {
-                let x = x.eval(env)?;
-                let y = y.eval(env)?;
-                Ok(x | y)
-            }
-            ExprInner::Xor(x, y) => {
-                let x = x.eval(env)?;
-                let y = y.eval(env)?;
-                Ok(x ^ y)
-            }
-            ExprInner::Invert(x) => {
-                let x = x.eval(env)?;
-                Ok(!x)
-            }
-            ExprInner::Negate(x) => {
-                let x = x.eval(env)?;
-                x.checked_neg().ok_or(EvalError::NegOverflow(x))
+        }
+    }
+
+    /// Applies the operation of this expression to the already evaluated
+    /// operands. `y` is ignored for unary operations.
+    fn apply(&self, x: i64, y: i64) -> Result<i64, EvalError> {
+        match &self.inner {
+            ExprInner::Add(..) => x.checked_add(y).ok_or(EvalError::AddOverflow(x, y)),
+            ExprInner::Sub(..) => x.checked_sub(y).ok_or(EvalError::SubOverflow(x, y)),
+            ExprInner::Mul(..) => x.checked_mul(y).ok_or(EvalError::MulOverflow(x, y)),
+            ExprInner::Div(..) => x.checked_div_euclid(y).ok_or(EvalError::DivOverflow(x, y)),
+            ExprInner::Mod(..) => x.checked_rem_euclid(y).ok_or(EvalError::ModOverflow(x, y)),
+            ExprInner::BitAnd(..) => Ok(x & y),
+            ExprInner::BitOr(..) => Ok(x | y),
+            ExprInner::Xor(..) => Ok(x ^ y),
+            ExprInner::Invert(..) => Ok(!x),
+            ExprInner::Negate(..) => x.checked_neg().ok_or(EvalError::NegOverflow(x)),
+            _ => unreachable!("apply is only called for operations"),
+        }
+    }
+
+    /// Evaluates this expression if it only consists of constants. Such
+    /// expressions evaluate to the same result in every transaction, so an
+    /// overflow here means that the script always aborts.
+    fn const_eval(&self) -> Option<Result<i64, EvalError>> {
+        let res = match &self.inner {
+            ExprInner::Const(c) => Ok(*c),
+            ExprInner::CurrInputIdx
+            | ExprInner::Input(..)
+            | ExprInner::Output(..)
+            | ExprInner::InputIssue(..)
+            | ExprInner::InputReIssue(..) => return None,
+            ExprInner::Add(x, y)
+            | ExprInner::Sub(x, y)
+            | ExprInner::Mul(x, y)
+            | ExprInner::Div(x, y)
+            | ExprInner::Mod(x, y)
+            | ExprInner::BitAnd(x, y)
+            | ExprInner::BitOr(x, y)
+            | ExprInner::Xor(x, y) => match (x.const_eval()?, y.const_eval()?) {
+                (Ok(x), Ok(y)) => self.apply(x, y),
+                (Err(e), _) | (_, Err(e)) => Err(e),
+            },
+            ExprInner::Invert(x) | ExprInner::Negate(x) => {
+                x.const_eval()?.and_then(|x| self.apply(x, 0))
             }
+        };
+        Some(res)
+    }
+
+    // Rejects constant expressions that always overflow
+    fn const_checked(self, top: &expression::Tree<'_>) -> Result<Self, Error> {
+        match self.const_eval() {
+            Some(Err(e)) => Err(Error::Unexpected(format!(
+                "{} always fails: {}",
+                top.name, e
+            ))),
+            _ => Ok(self),
         }
     }
 
@@ -525,6 +654,10 @@ impl Expr {
 /// Expr cannot be directly used a miniscript fragment because it pushes a 64 bit
 /// value on stack. Two expressions can be combined with Arith to something is
 /// of Base type B to be used in miniscript expressions
+///
+/// `outvalue(i,v)` is parsed as `num64_eq(out_v(i),v)`, which asserts that
+/// the output at index `i` has the explicit value `v`, e.g. to pin the
+/// amounts of vault outputs. It is displayed as the latter.
 #[derive(Eq, PartialEq, Ord, PartialOrd, Hash, Clone)]
 pub enum Arith {
     /// Eq
@@ -552,7 +685,19 @@ impl Arith {
             | Arith::Lt(x, y)
             | Arith::Leq(x, y)
             | Arith::Gt(x, y)
-            | Arith::Geq(x, y) => cmp::max(x.depth, y.depth),
+            | Arith::Geq(x, y) => cmp::max(x.depth, y.depth) + 1,
+        }
+    }
+
+    /// Obtains the maximum number of stack elements used while executing
+    /// this fragment
+    pub fn stack_elem_count(&self) -> usize {
+        match self {
+            Arith::Eq(x, y)
+            | Arith::Lt(x, y)
+            | Arith::Leq(x, y)
+            | Arith::Gt(x, y)
+            | Arith::Geq(x, y) => cmp::max(x.stack_elem_count, 1 + y.stack_elem_count),
         }
     }
 
@@ -699,7 +844,7 @@ impl FromTree for Expr {
             F: FnOnce(Box<Expr>) -> ExprInner,
         {
             let l: Expr = FromTree::from_tree(&top.args[0])?;
-            Ok(Expr::from_inner(frag(Box::new(l))))
+            Expr::from_inner(frag(Box::new(l))).const_checked(top)
         }
 
         fn binary<F>(top: &expression::Tree<'_>, frag: F) -> Result<Expr, Error>
@@ -708,7 +853,7 @@ impl FromTree for Expr {
         {
             let l: Expr = FromTree::from_tree(&top.args[0])?;
             let r: Expr = FromTree::from_tree(&top.args[1])?;
-            Ok(Expr::from_inner(frag(Box::new(l), Box::new(r))))
+            Expr::from_inner(frag(Box::new(l), Box::new(r))).const_checked(top)
         }
         match (top.name, top.args.len()) {
             ("inp_v", 1) => Ok(Expr::from_inner(expression::unary(top, ExprInner::Input)?)),
@@ -732,14 +877,9 @@ impl FromTree for Expr {
             ("bitxor", 2) => binary(top, ExprInner::Xor),
             ("bitinv", 1) => unary(top, ExprInner::Invert),
             ("neg", 1) => unary(top, ExprInner::Negate),
-            (_num, 0) => {
-                Ok(Expr {
-                    inner: expression::terminal(top, expression::parse_num::<i64>)
-                        .map(ExprInner::Const)?,
-                    script_size: 8 + 1, // 8 byte push
-                    depth: 0,
-                })
-            }
+            (_num, 0) => Ok(Expr::from_inner(
+                expression::terminal(top, expression::parse_num::<i64>).map(ExprInner::Const)?,
+            )),
             _ => Err(Error::Unexpected(format!(
                 "{}({} args) while parsing Extension",
                 top.name,
@@ -773,6 +913,11 @@ impl FromTree for Arith {
             ("num64_gt", 2) => expression::binary(top, Arith::Gt),
             ("num64_lt", 2) => expression::binary(top, Arith::Lt),
             ("num64_leq", 2) => expression::binary(top, Arith::Leq),
+            ("outvalue", 2) => {
+                let idx: IdxExpr = FromTree::from_tree(&top.args[0])?;
+                let value: Expr = FromTree::from_tree(&top.args[1])?;
+                Ok(Arith::Eq(Expr::from_inner(ExprInner::Output(idx)), value))
+            }
             _ => Err(Error::Unexpected(format!(
                 "{}({} args) while parsing Extension",
                 top.name,
@@ -834,8 +979,8 @@ impl Extension for Arith {
             max_sat_size: Some((0, 0)),
             max_dissat_size: Some((0, 0)),
             timelock_info: TimelockInfo::default(),
-            exec_stack_elem_count_sat: Some(self.depth()),
-            exec_stack_elem_count_dissat: Some(self.depth()),
+            exec_stack_elem_count_sat: Some(self.stack_elem_count()),
+            exec_stack_elem_count_dissat: Some(self.stack_elem_count()),
             ops: OpLimits {
                 // Opcodes are really not relevant in tapscript as BIP342 removes all rules on them
                 // So, don't make any effort in trying to compute and cache them.
@@ -928,6 +1073,19 @@ impl ParseableExt for Arith {
         self.push_to_builder(builder)
     }
 
+    fn blinded_refs(&self, env: &TxEnv, inputs: &mut Vec<usize>, outputs: &mut Vec<usize>) {
+        match self {
+            Arith::Eq(x, y)
+            | Arith::Lt(x, y)
+            | Arith::Leq(x, y)
+            | Arith::Gt(x, y)
+            | Arith::Geq(x, y) => {
+                x.blinded_refs(env, inputs, outputs);
+                y.blinded_refs(env, inputs, outputs);
+            }
+        }
+    }
+
     fn from_token_iter(tokens: &mut TokenIter<'_>) -> Result<Self, ()> {
         let len = tokens.len();
         match Self::from_tokens(&tokens.as_inner_mut()) {
@@ -957,7 +1115,19 @@ impl ParseableExt for Arith {
                 stack.push(interpreter::Element::Dissatisfied);
                 Ok(false)
             }
-            Err(e) => Err(interpreter::Error::ArithError(e)),
+            Err(e) => {
+                let (mut inputs, mut outputs) = (vec![], vec![]);
+                ParseableExt::blinded_refs(self, txenv, &mut inputs, &mut outputs);
+                if inputs.is_empty() && outputs.is_empty() {
+                    Err(interpreter::Error::ArithError(e))
+                } else {
+                    inputs.sort_unstable();
+                    inputs.dedup();
+                    outputs.sort_unstable();
+                    outputs.dedup();
+                    Err(interpreter::Error::NeedsUnblinding { inputs, outputs })
+                }
+            }
         }
     }
 }
@@ -1035,7 +1205,10 @@ impl fmt::Display for EvalError {
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
+
     use bitcoin::XOnlyPublicKey;
+    use elements::taproot::{LeafVersion, TapLeafHash};
 
     use super::*;
     use crate::test_utils::{StrExtTranslator, StrXOnlyKeyTranslator};
@@ -1095,6 +1268,200 @@ mod tests {
         );
     }
 
+    #[test]
+    fn needs_unblinding() {
+        use elements::confidential;
+        use elements::secp256k1_zkp::{Generator, PedersenCommitment, Secp256k1, Tag, Tweak};
+
+        let secp = Secp256k1::new();
+        let asset = Generator::new_blinded(
+            &secp,
+            Tag::from([1; 32]),
+            Tweak::from_inner([2; 32]).unwrap(),
+        );
+        let blinded =
+            PedersenCommitment::new(&secp, 1000, Tweak::from_inner([3; 32]).unwrap(), asset);
+        let txout = |value| elements::TxOut {
+            value,
+            ..Default::default()
+        };
+        let mut tx = Transaction {
+            version: 2,
+            lock_time: elements::PackedLockTime::ZERO,
+            input: vec![Default::default(), Default::default()],
+            output: vec![
+                txout(confidential::Value::Confidential(blinded)),
+                txout(confidential::Value::Explicit(1000)),
+            ],
+        };
+        let utxos = vec![
+            txout(confidential::Value::Explicit(1000)),
+            txout(confidential::Value::Confidential(blinded)),
+        ];
+
+        let ms = Miniscript::<XOnlyPublicKey, Tap, Arith>::from_str_insane(
+            "num64_eq(add(out_v(0),out_v(1)),add(curr_inp_v,inp_v(1)))",
+        )
+        .unwrap();
+        let env = TxEnv::new(&tx, &utxos, 0).unwrap();
+        assert_eq!(ms.blinded_refs(&env), (vec![1], vec![0]));
+        assert_eq!(
+            ms.satisfy(&env),
+            Err(Error::NeedsUnblinding {
+                inputs: vec![1],
+                outputs: vec![0],
+            })
+        );
+        let arith = match ms.node {
+            crate::Terminal::Ext(ref e) => e,
+            _ => unreachable!(),
+        };
+        let mut stack = interpreter::Stack::from(vec![]);
+        match arith.evaluate(&mut stack, Some(&env)) {
+            Err(interpreter::Error::NeedsUnblinding { inputs, outputs }) => {
+                assert_eq!((inputs, outputs), (vec![1], vec![0]))
+            }
+            res => panic!("unexpected evaluation {:?}", res),
+        }
+
+        // Satisfactions failing for other reasons are not reported
+        let key = XOnlyPublicKey::from_str(
+            "79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+        )
+        .unwrap();
+        let signed = Miniscript::<XOnlyPublicKey, Tap, Arith>::from_str_insane(&format!(
+            "and_v(v:pk({}),num64_eq(add(out_v(0),out_v(1)),add(curr_inp_v,inp_v(1))))",
+            key
+        ))
+        .unwrap();
+        assert_eq!(signed.satisfy(&env), Err(Error::CouldNotSatisfy));
+        let sig = elements::SchnorrSig {
+            sig: elements::secp256k1_zkp::schnorr::Signature::from_slice(&[1; 64]).unwrap(),
+            hash_ty: elements::SchnorrSigHashType::Default,
+        };
+        let mut sigs = HashMap::new();
+        sigs.insert(
+            (
+                key,
+                TapLeafHash::from_script(&signed.encode(), LeafVersion::default()),
+            ),
+            sig,
+        );
+        assert_eq!(
+            signed.satisfy((&env, &sigs)),
+            Err(Error::NeedsUnblinding {
+                inputs: vec![1],
+                outputs: vec![0],
+            })
+        );
+
+        // Once unblinded, the equality is checked
+        tx.output[0] = txout(confidential::Value::Explicit(500));
+        let utxos = vec![
+            txout(confidential::Value::Explicit(1000)),
+            txout(confidential::Value::Explicit(400)),
+        ];
+        let env = TxEnv::new(&tx, &utxos, 0).unwrap();
+        assert_eq!(ms.blinded_refs(&env), (vec![], vec![]));
+        assert_eq!(ms.satisfy(&env), Err(Error::CouldNotSatisfy));
+        tx.output[1] = txout(confidential::Value::Explicit(900));
+        let env = TxEnv::new(&tx, &utxos, 0).unwrap();
+        assert_eq!(ms.satisfy(&env), Ok(vec![]));
+    }
+
+    #[test]
+    fn outvalue() {
+        use elements::confidential;
+
+        type MsExt = Miniscript<XOnlyPublicKey, Tap, Arith>;
+        let ms = MsExt::from_str_insane("outvalue(1,5000)").unwrap();
+        let eq = MsExt::from_str_insane("num64_eq(out_v(1),5000)").unwrap();
+        assert_eq!(ms, eq);
+        assert_eq!(ms.to_string(), "num64_eq(out_v(1),5000)");
+        assert_eq!(ms.encode(), eq.encode());
+        assert_eq!(ms.ext.pk_cost, eq.ext.pk_cost);
+        assert!(MsExt::from_str_insane("outvalue(curr_idx,add(inp_v(0),1))").is_ok());
+        assert!(MsExt::from_str_insane("outvalue(1)").is_err());
+
+        let txout = |value| elements::TxOut {
+            value,
+            ..Default::default()
+        };
+        let mut tx = Transaction {
+            version: 2,
+            lock_time: elements::PackedLockTime::ZERO,
+            input: vec![Default::default()],
+            output: vec![
+                txout(confidential::Value::Explicit(100)),
+                txout(confidential::Value::Explicit(5000)),
+            ],
+        };
+        let utxos = vec![txout(confidential::Value::Explicit(5100))];
+        let env = TxEnv::new(&tx, &utxos, 0).unwrap();
+        assert_eq!(ms.satisfy(&env), Ok(vec![]));
+        tx.output[1] = txout(confidential::Value::Explicit(4999));
+        let env = TxEnv::new(&tx, &utxos, 0).unwrap();
+        assert_eq!(ms.satisfy(&env), Err(Error::CouldNotSatisfy));
+    }
+
+    #[test]
+    fn const_overflow() {
+        type MsExt = Miniscript<XOnlyPublicKey, Tap, Arith>;
+        let max = i64::max_value();
+        // Constant expressions are checked for overflows when parsing
+        assert!(MsExt::from_str_insane(&format!("num64_eq(add({},1),0)", max)).is_err());
+        assert!(MsExt::from_str_insane(&format!("num64_eq(mul({},2),0)", max)).is_err());
+        assert!(MsExt::from_str_insane("num64_eq(div(5,0),0)").is_err());
+        assert!(MsExt::from_str_insane(&format!("num64_eq(neg({}),0)", i64::min_value())).is_err());
+        // also when nested under expressions reading the transaction
+        assert!(
+            MsExt::from_str_insane(&format!("num64_eq(add(out_v(0),sub(-2,{})),0)", max)).is_err()
+        );
+        assert!(MsExt::from_str_insane(&format!("num64_eq(add({},0),0)", max)).is_ok());
+        // Overflows depending on the transaction can only be detected when evaluating
+        let ms = MsExt::from_str_insane(&format!("num64_eq(add(out_v(0),{}),0)", max)).unwrap();
+
+        let tx = Transaction {
+            version: 2,
+            lock_time: elements::PackedLockTime::ZERO,
+            input: vec![Default::default()],
+            output: vec![elements::TxOut {
+                value: elements::confidential::Value::Explicit(1),
+                ..Default::default()
+            }],
+        };
+        let utxos = vec![elements::TxOut::default()];
+        let env = TxEnv::new(&tx, &utxos, 0).unwrap();
+        let arith = match ms.node {
+            crate::Terminal::Ext(ref e) => e,
+            _ => unreachable!(),
+        };
+        assert_eq!(arith.eval(&env), Err(EvalError::AddOverflow(1, max)));
+        assert_eq!(ms.satisfy(&env), Err(Error::CouldNotSatisfy));
+    }
+
+    #[test]
+    fn stack_elem_count() {
+        type MsExt = Miniscript<XOnlyPublicKey, Tap, Arith>;
+        let count = |s: &str| {
+            MsExt::from_str_insane(s)
+                .unwrap()
+                .ext
+                .exec_stack_elem_count_sat
+                .unwrap()
+        };
+        assert_eq!(count("num64_eq(1,2)"), 2);
+        // <1> <2> ADD64 leaves the result and the success flag before <1>
+        assert_eq!(count("num64_eq(add(1,2),3)"), 3);
+        assert_eq!(count("num64_eq(3,add(1,2))"), 4);
+        assert_eq!(count("num64_eq(div(4,2),2)"), 4);
+        assert_eq!(count("num64_eq(1,add(2,add(3,4)))"), 5);
+        assert_eq!(count("num64_eq(inp_issue_v(0),0)"), 6);
+
+        let expr = Expr::from_str("add(1,mul(2,neg(3)))").unwrap();
+        assert_eq!(expr.depth(), 3);
+    }
+
     fn _arith_parse(s: &str) {
         type MsExtStr = Miniscript<String, Tap, Arith>;
         type MsExt = Miniscript<XOnlyPublicKey, Tap, Arith>;
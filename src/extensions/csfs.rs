@@ -13,7 +13,6 @@ use super::param::{ExtParamTranslator, TranslateExtParam};
 use super::{ArgFromStr, CovExtArgs, ExtParam, ParseableExt, TxEnv};
 use crate::miniscript::context::ScriptContextError;
 use crate::miniscript::lex::{Token as Tk, TokenIter};
-use crate::miniscript::limits::MAX_STANDARD_P2WSH_STACK_ITEM_SIZE;
 use crate::miniscript::satisfy::{Satisfaction, Witness};
 use crate::miniscript::types::extra_props::{OpLimits, TimelockInfo};
 use crate::miniscript::types::{Base, Correctness, Dissat, ExtData, Input, Malleability};
@@ -22,6 +21,9 @@ use crate::{
     TranslateExt,
 };
 
+/// Length of the messages signed for `csfs`
+const CSFS_MSG_LEN: usize = 32;
+
 /// CheckSigFromStack struct
 /// `<msg> <pk> CHECKSIGFROMSTACK`
 #[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Clone)]
@@ -89,7 +91,7 @@ impl<T: ExtParam> Extension for CheckSigFromStack<T> {
     }
 
     fn script_size(&self) -> usize {
-        1 + 32 + 1 // opcode + key+ push
+        1 + CSFS_MSG_LEN + 1 + 32 + 1 // msg push + key push + opcode
     }
 
     fn segwit_ctx_checks(&self) -> Result<(), miniscript::context::ScriptContextError> {
@@ -150,25 +152,21 @@ where
 pub struct CsfsMsg(Vec<u8>);
 
 impl CsfsMsg {
-    /// Creates a new Msg with witness len check
-    /// The current rust-secp API only supports verification of 32 byte signature
-    /// but this should work in elementsd
+    /// Creates a new Msg, returning `None` unless it is exactly 32 bytes.
+    /// The current rust-secp API only supports verification of 32 byte messages,
+    /// and the script parser only recognizes 32 byte pushes, so other sizes
+    /// could neither be interpreted nor round-tripped through script.
     pub fn new(msg: Vec<u8>) -> Option<Self> {
-        // Same rule about initial witness stack item size for tapscript
-        if msg.len() > MAX_STANDARD_P2WSH_STACK_ITEM_SIZE {
-            None
-        } else {
+        if msg.len() == CSFS_MSG_LEN {
             Some(Self(msg))
+        } else {
+            None
         }
     }
 
     /// Creates Self from slice
     pub fn from_slice(sl: &[u8]) -> Option<Self> {
-        if sl.len() > MAX_STANDARD_P2WSH_STACK_ITEM_SIZE {
-            None
-        } else {
-            Some(Self(sl.to_vec()))
-        }
+        Self::new(sl.to_vec())
     }
 
     /// Obtains the inner slice of this message
@@ -187,13 +185,13 @@ impl ArgFromStr for CsfsMsg {
     fn arg_from_str(s: &str, parent: &str, pos: usize) -> Result<Self, Error> {
         if parent != "csfs" || pos != 1 {
             return Err(Error::Unexpected(
-                "Msg must be the first arg of csfs".to_string(),
+                "Msg must be the second arg of csfs".to_string(),
             ));
         }
         let inner = Vec::<u8>::from_hex(s).map_err(|e| Error::Unexpected(e.to_string()))?;
         let inner_len = inner.len();
         let x = Self::new(inner)
-            .ok_or(hex::Error::InvalidLength(32, inner_len))
+            .ok_or(hex::Error::InvalidLength(CSFS_MSG_LEN, inner_len))
             .map_err(|e| Error::Unexpected(e.to_string()))?;
         Ok(x)
     }
@@ -413,4 +411,68 @@ mod tests {
 
         assert_eq!(ms_translated.to_string(), "and_v(v:csfs(26d137d15e2ae24f2d5158663d190d1269ad6b1a6ce330aa825ba502e7519d44,abababababababababababababababababababababababababababababababab),pk(9064b3ac01fb4cb648e8899723ee4d50433920ae558c572e96d945805e0bc3ec))");
     }
+
+    #[test]
+    fn csfs_msg_len() {
+        type MsExtCsfs = Miniscript<XOnlyPublicKey, Tap, CheckSigFromStack<CovExtArgs>>;
+        let pk = "26d137d15e2ae24f2d5158663d190d1269ad6b1a6ce330aa825ba502e7519d44";
+
+        // Only 32 byte messages can be verified and parsed back from script
+        for msg in &["", "ab", &"ab".repeat(31), &"ab".repeat(33)] {
+            assert!(MsExtCsfs::from_str_insane(&format!("csfs({},{})", pk, msg)).is_err());
+        }
+        assert!(CsfsMsg::from_slice(&[0xab; 33]).is_none());
+
+        let ms = MsExtCsfs::from_str_insane(&format!("csfs({},{})", pk, "ab".repeat(32))).unwrap();
+        assert_eq!(ms.script_size(), ms.encode().len());
+    }
+
+    #[test]
+    fn csfs_oracle_sig() {
+        type MsExtCsfs = Miniscript<XOnlyPublicKey, Tap, CheckSigFromStack<CovExtArgs>>;
+
+        // An oracle attesting to an outcome by signing its 32 byte hash
+        struct Oracle(secp256k1_zkp::KeyPair);
+
+        impl Satisfier<XOnlyPublicKey> for Oracle {
+            fn lookup_csfs_sig(
+                &self,
+                pk: &XOnlyPublicKey,
+                msg: &CsfsMsg,
+            ) -> Option<secp256k1_zkp::schnorr::Signature> {
+                if *pk != XOnlyPublicKey::from_keypair(&self.0).0 {
+                    return None;
+                }
+                let msg = secp256k1_zkp::Message::from_slice(msg.as_inner()).unwrap();
+                let secp = secp256k1_zkp::Secp256k1::signing_only();
+                Some(secp.sign_schnorr_no_aux_rand(&msg, &self.0))
+            }
+        }
+
+        let secp = secp256k1_zkp::Secp256k1::new();
+        let oracle = Oracle(secp256k1_zkp::KeyPair::from_seckey_slice(&secp, &[1; 32]).unwrap());
+        let oracle_pk = XOnlyPublicKey::from_keypair(&oracle.0).0;
+        let ms = MsExtCsfs::from_str_insane(&format!("csfs({},{})", oracle_pk, "ab".repeat(32)))
+            .unwrap();
+
+        let wit = ms.satisfy(&oracle).unwrap();
+        assert_eq!(wit.len(), 1);
+        let ext = match ms.node {
+            crate::Terminal::Ext(ref ext) => ext,
+            _ => unreachable!(),
+        };
+        let mut stack = interpreter::Stack::from(vec![interpreter::Element::from(&wit[0])]);
+        assert!(ext.evaluate(&mut stack, None).unwrap());
+
+        // A signature over a different outcome is rejected
+        let other = MsExtCsfs::from_str_insane(&format!("csfs({},{})", oracle_pk, "cd".repeat(32)))
+            .unwrap();
+        let other_wit = other.satisfy(&oracle).unwrap();
+        let mut stack = interpreter::Stack::from(vec![interpreter::Element::from(&other_wit[0])]);
+        assert!(ext.evaluate(&mut stack, None).is_err());
+
+        // Unknown oracles cannot be satisfied
+        let stranger = Oracle(secp256k1_zkp::KeyPair::from_seckey_slice(&secp, &[2; 32]).unwrap());
+        assert!(ms.satisfy(&stranger).is_err());
+    }
 }
@@ -1,7 +1,7 @@
 //! Miniscript Index expressions:
 //! Note that these fragment is only supported for Tapscript context
 //! Refer to the spec for additional details.
-use std::fmt;
+use std::{cmp, fmt};
 
 use elements::opcodes::{self};
 use elements::script;
@@ -47,6 +47,21 @@ impl IdxExpr {
             IdxExpr::Div(x, y) => x.script_size() + y.script_size() + 7,
         }
     }
+
+    /// Returns the maximum number of stack elements used while executing
+    /// this expression, including its result
+    pub fn stack_elem_count(&self) -> usize {
+        // [X] [Y] <op>, where the operation itself needs `op` elements
+        fn binary(x: &IdxExpr, y: &IdxExpr, op: usize) -> usize {
+            cmp::max(cmp::max(x.stack_elem_count(), 1 + y.stack_elem_count()), op)
+        }
+        match self {
+            IdxExpr::Const(_) | IdxExpr::CurrIdx => 1,
+            IdxExpr::Add(x, y) | IdxExpr::Sub(x, y) => binary(x, y, 0),
+            IdxExpr::Mul(x, y) => binary(x, y, 3), // result, success flag, <1>
+            IdxExpr::Div(x, y) => binary(x, y, 4), // remainder, quotient, success flag, <1>
+        }
+    }
 }
 
 impl fmt::Display for IdxExpr {
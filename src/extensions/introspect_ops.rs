@@ -96,6 +96,10 @@ pub enum SpkExpr<T: ExtParam> {
 /// Expr cannot be directly used a miniscript fragment because it pushes a 64 bit
 /// value on stack. Two expressions can be combined with Arith to something is
 /// of Base type B to be used in miniscript expressions
+///
+/// `outasset(i,a)` and `inasset(i,a)` are parsed as `asset_eq(out_asset(i),a)`
+/// and `asset_eq(inp_asset(i),a)`, which pin the asset of the output or input
+/// at index `i` to `a`. They are displayed as the latter.
 #[derive(Eq, PartialEq, Ord, PartialOrd, Hash, Clone)]
 pub enum CovOps<T: ExtParam> {
     /// Checks that asset is explicit
@@ -391,6 +395,17 @@ impl<T: ExtParam> FromTree for CovOps<T> {
                 let r = AssetExpr::from_tree_parent(&top.args[1], &top.name, 1)?;
                 Ok(CovOps::AssetEq(l, r))
             }
+            ("outasset", 2) | ("inasset", 2) => {
+                let idx = IdxExpr::from_tree(&top.args[0])?;
+                let l = if top.name == "outasset" {
+                    AssetExpr::Output(idx)
+                } else {
+                    AssetExpr::Input(idx)
+                };
+                // The asset is parsed as the constant of the equivalent asset_eq
+                let r = AssetExpr::from_tree_parent(&top.args[1], "asset_eq", 1)?;
+                Ok(CovOps::AssetEq(l, r))
+            }
             ("value_eq", 2) => {
                 let l = ValueExpr::from_tree_parent(&top.args[0], &top.name, 0)?;
                 let r = ValueExpr::from_tree_parent(&top.args[1], &top.name, 1)?;
@@ -1295,6 +1310,49 @@ mod tests {
         MsExt::from_str_insane("asset_eq(out_asset(0),79d51a47e4ac8e32306486dd0926a88678c392f2ed5f213e3ff2ad461c7c25e1)").unwrap_err();
     }
 
+    #[test]
+    fn asset_pinning() {
+        type MsExt = Miniscript<XOnlyPublicKey, Tap, CovOps<CovExtArgs>>;
+        let asset = "01c73ac1b7a518499b9642aed8cfa15d5401e5bd85ad760b937b69521c297722f0";
+
+        let ms = MsExt::from_str_insane(&format!("outasset(1,{})", asset)).unwrap();
+        let eq = MsExt::from_str_insane(&format!("asset_eq(out_asset(1),{})", asset)).unwrap();
+        assert_eq!(ms, eq);
+        assert_eq!(ms.to_string(), format!("asset_eq(out_asset(1),{})", asset));
+        assert_eq!(ms.encode(), eq.encode());
+        let ms_in = MsExt::from_str_insane(&format!("inasset(curr_idx,{})", asset)).unwrap();
+        assert_eq!(
+            ms_in.to_string(),
+            format!("asset_eq(inp_asset(curr_idx),{})", asset)
+        );
+        assert!(MsExt::from_str_insane("outasset(1,out_asset(0))").is_ok());
+        assert!(MsExt::from_str_insane("outasset(1)").is_err());
+        // Only allowed in tapscript, like asset_eq
+        type MsSegwitv0 = Miniscript<XOnlyPublicKey, Segwitv0, CovOps<CovExtArgs>>;
+        assert!(MsSegwitv0::from_str_insane(&format!("outasset(1,{})", asset)).is_err());
+
+        let pinned: confidential::Asset =
+            encode::deserialize(&Vec::<u8>::from_hex(asset).unwrap()).unwrap();
+        let txout = |asset| elements::TxOut {
+            asset,
+            value: confidential::Value::Explicit(1000),
+            ..Default::default()
+        };
+        let mut tx = elements::Transaction {
+            version: 2,
+            lock_time: elements::PackedLockTime::ZERO,
+            input: vec![Default::default()],
+            output: vec![txout(confidential::Asset::Null), txout(pinned)],
+        };
+        let utxos = vec![txout(pinned)];
+        let env = TxEnv::new(&tx, &utxos, 0).unwrap();
+        assert_eq!(ms.satisfy(&env), Ok(vec![]));
+        assert_eq!(ms_in.satisfy(&env), Ok(vec![]));
+        tx.output[1] = txout(confidential::Asset::Null);
+        let env = TxEnv::new(&tx, &utxos, 0).unwrap();
+        assert_eq!(ms.satisfy(&env), Err(Error::CouldNotSatisfy));
+    }
+
     #[rustfmt::skip]
     fn _test_parse(s: &str) {
         type MsExtStr = Miniscript<String, Tap, CovOps<String>>;
@@ -117,6 +117,12 @@ pub trait ParseableExt:
     where
         Pk: ToPublicKey,
         S: Satisfier<Pk>;
+
+    /// Adds the indexes of the inputs and outputs of `env` whose explicit
+    /// values this fragment reads, but which are confidential, to `inputs`
+    /// and `outputs`. These are reported as [`Error::NeedsUnblinding`] when
+    /// satisfaction fails.
+    fn blinded_refs(&self, _env: &TxEnv, _inputs: &mut Vec<usize>, _outputs: &mut Vec<usize>) {}
 }
 
 /// No Extensions for elements-miniscript
@@ -194,12 +200,14 @@ impl fmt::Display for NoExt {
     }
 }
 
+// Being uninhabited, `NoExt` translates into any extension, so that
+// Miniscripts without extensions can be embedded into ones with extensions
 impl<PExt, QExt> TranslateExt<PExt, QExt> for NoExt
 where
     PExt: Extension,
     QExt: Extension,
 {
-    type Output = NoExt;
+    type Output = QExt;
 
     fn translate_ext<T, E>(&self, _t: &mut T) -> Result<Self::Output, E>
     where
@@ -308,6 +316,10 @@ impl ParseableExt for CovenantExt<CovExtArgs> {
         all_arms_fn!(self, ParseableExt, push_to_builder, builder,)
     }
 
+    fn blinded_refs(&self, env: &TxEnv, inputs: &mut Vec<usize>, outputs: &mut Vec<usize>) {
+        all_arms_fn!(self, ParseableExt, blinded_refs, env, inputs, outputs,)
+    }
+
     fn from_token_iter(tokens: &mut TokenIter<'_>) -> Result<Self, ()> {
         try_from_arms!(ParseableExt, CovExtArgs, from_token_iter, tokens,)
     }
@@ -400,3 +412,77 @@ impl<'tx, 'ptx, Pk: ToPublicKey> Satisfier<Pk> for TxEnv<'tx, 'ptx> {
         Some(self.idx)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::mem::size_of;
+
+    use bitcoin::XOnlyPublicKey;
+
+    use super::*;
+    use crate::{Miniscript, Tap, Terminal};
+
+    const KEY: &str = "26d137d15e2ae24f2d5158663d190d1269ad6b1a6ce330aa825ba502e7519d44";
+
+    /// Checks that `s` survives a string and a script roundtrip with
+    /// extension `Ext`, returning whether it parsed at all
+    fn ext_roundtrip<Ext: ParseableExt>(s: &str) -> bool {
+        type Ms<Ext> = Miniscript<XOnlyPublicKey, Tap, Ext>;
+
+        let ms = match Ms::<Ext>::from_str_insane(s) {
+            Ok(ms) => ms,
+            Err(..) => return false,
+        };
+        assert_eq!(ms.to_string(), s);
+        assert_eq!(ms, Ms::<Ext>::parse_insane(&ms.encode()).unwrap());
+        true
+    }
+
+    #[test]
+    fn generic_roundtrip() {
+        let plain = format!("and_v(v:pk({}),older(10))", KEY);
+        assert!(ext_roundtrip::<NoExt>(&plain));
+        assert!(ext_roundtrip::<CovenantExt<CovExtArgs>>(&plain));
+
+        let with_ext = format!("and_v(v:pk({}),num64_eq(inp_v(0),out_v(0)))", KEY);
+        assert!(!ext_roundtrip::<NoExt>(&with_ext));
+        assert!(ext_roundtrip::<CovenantExt<CovExtArgs>>(&with_ext));
+    }
+
+    #[test]
+    fn noext_is_free() {
+        assert_eq!(size_of::<NoExt>(), 0);
+        assert!(
+            size_of::<Terminal<XOnlyPublicKey, Tap, NoExt>>()
+                <= size_of::<Terminal<XOnlyPublicKey, Tap, CovenantExt<CovExtArgs>>>()
+        );
+    }
+}
+
+#[cfg(all(test, feature = "unstable"))]
+mod benches {
+    use std::str::FromStr;
+
+    use test::{black_box, Bencher};
+
+    use super::{CovExtArgs, CovenantExt, NoExt};
+    use crate::{Miniscript, Tap};
+
+    const MS: &str = "or_d(multi_a(2,A,B,C),and_v(v:pk(D),and_v(v:pk(E),older(144))))";
+
+    #[bench]
+    pub fn parse_str_noext(bh: &mut Bencher) {
+        bh.iter(|| {
+            let ms = Miniscript::<String, Tap, NoExt>::from_str(MS);
+            black_box(ms).unwrap();
+        });
+    }
+
+    #[bench]
+    pub fn parse_str_covext(bh: &mut Bencher) {
+        bh.iter(|| {
+            let ms = Miniscript::<String, Tap, CovenantExt<CovExtArgs>>::from_str(MS);
+            black_box(ms).unwrap();
+        });
+    }
+}
@@ -134,6 +134,14 @@ pub enum Error {
     },
     /// Errors related to extensions.
     ArithError(EvalError),
+    /// Extensions need the explicit values of confidential spent utxos or
+    /// outputs, which must be unblinded first
+    NeedsUnblinding {
+        /// Indexes of the inputs whose spent utxo or issuance is confidential
+        inputs: Vec<usize>,
+        /// Indexes of the confidential outputs
+        outputs: Vec<usize>,
+    },
 }
 
 impl fmt::Display for Error {
@@ -221,6 +229,14 @@ impl fmt::Display for Error {
                 pos, expected, actual
             ),
             Error::ArithError(ref e) => write!(f, "{}", e),
+            Error::NeedsUnblinding {
+                ref inputs,
+                ref outputs,
+            } => write!(
+                f,
+                "needs unblinded inputs {:?} and outputs {:?}",
+                inputs, outputs
+            ),
         }
     }
 }
@@ -271,6 +287,7 @@ impl error::Error for Error {
             IncorrectCovenantWitness => None,
             CovWitnessSizeErr { .. } => None,
             ArithError(..) => None,
+            NeedsUnblinding { .. } => None,
         }
     }
 }
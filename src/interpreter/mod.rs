@@ -161,6 +161,25 @@ impl<'txin> Interpreter<'txin, CovenantExt<CovExtArgs>> {
     }
 }
 
+/// The output spent by a pegin input, `None` if `txin` is not a pegin or has
+/// an invalid pegin witness.
+///
+/// Pegin inputs spend an output of the mainchain, which is not in the UTXO set
+/// of the sidechain. Consensus treats the claim script of the pegin witness as
+/// the spent script pubkey, with the pegged-in value and asset explicit. Use it
+/// as the `spk` of [`Interpreter::from_txdata`] and as the spent utxo of the input
+/// in [`TxEnv`].
+pub fn pegin_utxo(txin: &elements::TxIn) -> Option<elements::TxOut> {
+    let pegin_data = txin.pegin_data()?;
+    Some(elements::TxOut {
+        asset: elements::confidential::Asset::Explicit(pegin_data.asset),
+        value: elements::confidential::Value::Explicit(pegin_data.value),
+        nonce: elements::confidential::Nonce::Null,
+        script_pubkey: elements::Script::from(pegin_data.claim_script.to_vec()),
+        witness: elements::TxOutWitness::default(),
+    })
+}
+
 impl<'txin, Ext> Interpreter<'txin, Ext>
 where
     Ext: ParseableExt,
@@ -171,6 +190,7 @@ where
     /// that ECSDA signatures are valid, this can be set to the constant true
     /// function; otherwise, it should be a closure containing a sighash and
     /// secp context, which can actually verify a given signature.
+    /// For pegin inputs, `spk` is the claim script, see [`pegin_utxo`].
     pub fn from_txdata_ext(
         spk: &elements::Script,
         script_sig: &'txin elements::Script,
@@ -191,6 +211,10 @@ where
     /// Same as [`Interpreter::iter`], but allows for a custom verification function.
     /// See [Self::iter_assume_sigs] for a simpler API without information about Prevouts
     /// but skips the signature verification
+    ///
+    /// Satisfied extension fragments are yielded as [`SatisfiedConstraint::Ext`].
+    /// Introspection fragments can only be evaluated with `txenv`; without it,
+    /// they error.
     pub fn iter_custom<'iter>(
         &'iter self,
         verify_sig: Box<dyn FnMut(&KeySigPair) -> bool + 'iter>,
@@ -404,6 +428,13 @@ where
         }
     }
 
+    /// The script executed by the spend: the scriptCode for non-Taproot
+    /// spends and the leaf script for Taproot script spends. `None` for
+    /// Taproot key spends.
+    pub fn script_code(&self) -> Option<&elements::Script> {
+        self.script_code.as_ref()
+    }
+
     /// Whether this is a pre-segwit spend
     pub fn is_legacy(&self) -> bool {
         match self.inner {
@@ -515,7 +546,7 @@ pub enum HashLockType {
     Ripemd160(ripemd160::Hash),
 }
 
-/// A satisfied Miniscript condition (Signature, Hashlock, Timelock)
+/// A satisfied Miniscript condition (Signature, Hashlock, Timelock, Extension)
 /// 'intp represents the lifetime of descriptor and `stack represents
 /// the lifetime of witness
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -571,6 +602,17 @@ pub enum SatisfiedConstraint<Ext: Extension> {
     },
 }
 
+impl<Ext: Extension> SatisfiedConstraint<Ext> {
+    /// The extension fragment, e.g. an introspection or CSFS check, if this is
+    /// a satisfied extension
+    pub fn as_ext(&self) -> Option<&Ext> {
+        match *self {
+            SatisfiedConstraint::Ext { ref ext } => Some(ext),
+            _ => None,
+        }
+    }
+}
+
 ///This is used by the interpreter to know which evaluation state a AstemElem is.
 ///This is required because whenever a same node(for eg. OrB) appears on the stack, we don't
 ///know if the left child has been evaluated or not. And based on the result on
@@ -1209,7 +1251,7 @@ mod tests {
     use super::*;
     use crate::miniscript::analyzable::ExtParams;
     use crate::miniscript::context::NoChecks;
-    use crate::{ElementsSig, Miniscript, NoExt, ToPublicKey};
+    use crate::{ElementsSig, Miniscript, NoExt, Satisfier, ToPublicKey};
 
     fn setup_keys_sigs(
         n: usize,
@@ -1775,6 +1817,162 @@ mod tests {
         assert!(multi_a_error.is_err());
     }
 
+    #[test]
+    fn confidential_and_pegin_spends() {
+        let secp = Secp256k1::new();
+        let sk = secp256k1_zkp::SecretKey::from_slice(&[1; 32]).unwrap();
+        let pk = bitcoin::PublicKey::new(secp256k1_zkp::PublicKey::from_secret_key(&secp, &sk));
+        let desc =
+            Descriptor::<bitcoin::PublicKey, NoExt>::from_str(&format!("elwpkh({})", pk)).unwrap();
+        let asset = elements::AssetId::from_slice(&[2; 32]).unwrap();
+        let value = elements::confidential::Value::new_confidential_from_assetid(
+            &secp,
+            1000,
+            asset,
+            elements::confidential::ValueBlindingFactor::from_slice(&[3; 32]).unwrap(),
+            elements::confidential::AssetBlindingFactor::from_slice(&[4; 32]).unwrap(),
+        );
+        let confidential_utxo = elements::TxOut {
+            value,
+            script_pubkey: desc.script_pubkey(),
+            ..Default::default()
+        };
+
+        let pegin_witness = vec![
+            bitcoin::consensus::serialize(&1000u64),
+            elements::encode::serialize(&asset),
+            vec![5; 32],
+            desc.script_pubkey().to_bytes(),
+            vec![],
+            vec![6; 80],
+        ];
+        let mut tx = elements::Transaction {
+            version: 2,
+            lock_time: elements::PackedLockTime::ZERO,
+            input: vec![
+                elements::TxIn::default(),
+                elements::TxIn {
+                    is_pegin: true,
+                    witness: elements::TxInWitness {
+                        pegin_witness,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+            ],
+            output: vec![elements::TxOut::default()],
+        };
+        assert_eq!(pegin_utxo(&tx.input[0]), None);
+        let pegin_utxo = pegin_utxo(&tx.input[1]).unwrap();
+        assert_eq!(pegin_utxo.script_pubkey, desc.script_pubkey());
+        assert_eq!(pegin_utxo.value.explicit(), Some(1000));
+        let utxos = [confidential_utxo, pegin_utxo];
+
+        let script_code = desc.script_code().unwrap();
+        for (idx, utxo) in utxos.iter().enumerate() {
+            let sighash = elements::sighash::SigHashCache::new(&tx).segwitv0_sighash(
+                idx,
+                &script_code,
+                utxo.value,
+                EcdsaSigHashType::All,
+            );
+            let msg = secp256k1_zkp::Message::from_slice(&sighash[..]).unwrap();
+            let mut sig = secp.sign_ecdsa(&msg, &sk).serialize_der().to_vec();
+            sig.push(EcdsaSigHashType::All as u8);
+            tx.input[idx].witness.script_witness = vec![sig, pk.to_bytes()];
+        }
+
+        for (idx, utxo) in utxos.iter().enumerate() {
+            let txin = &tx.input[idx];
+            let interpreter = Interpreter::from_txdata(
+                &utxo.script_pubkey,
+                &txin.script_sig,
+                &txin.witness.script_witness,
+                txin.sequence,
+                LockTime::from(tx.lock_time),
+            )
+            .unwrap();
+            let env = TxEnv::new(&tx, &utxos, idx).unwrap();
+            let constraints: Result<Vec<_>, _> = interpreter
+                .iter(&secp, &env, elements::BlockHash::all_zeros())
+                .collect();
+            assert_eq!(constraints.unwrap().len(), 1);
+
+            // The signature does not commit to another value
+            let mut wrong_utxos = utxos.clone();
+            wrong_utxos[idx].value = elements::confidential::Value::Explicit(999);
+            let env = TxEnv::new(&tx, &wrong_utxos, idx).unwrap();
+            assert!(interpreter
+                .iter(&secp, &env, elements::BlockHash::all_zeros())
+                .any(|res| res.is_err()));
+        }
+    }
+
+    #[test]
+    fn ext_constraints() {
+        struct DummySig;
+        impl Satisfier<bitcoin::PublicKey> for DummySig {
+            fn lookup_tap_leaf_script_sig(
+                &self,
+                _: &bitcoin::PublicKey,
+                _: &elements::taproot::TapLeafHash,
+            ) -> Option<elements::SchnorrSig> {
+                Some(elements::SchnorrSig {
+                    sig: secp256k1_zkp::schnorr::Signature::from_slice(&[1; 64]).unwrap(),
+                    hash_ty: elements::SchnorrSigHashType::Default,
+                })
+            }
+        }
+
+        let desc = Descriptor::<bitcoin::PublicKey>::from_str(
+            "eltr(028c28a97bf8298bc0d23d8c749452a32e694b65e30a9472a3954ab30fe5324caa,\
+             and_v(v:pk(03daed4f2be3a8bf278e70132fb0beb7522f570e144bf615c07e996d443dee8729),\
+             num64_eq(out_v(0),1000)))",
+        )
+        .unwrap();
+        let utxos = [elements::TxOut {
+            script_pubkey: desc.script_pubkey(),
+            value: elements::confidential::Value::Explicit(2000),
+            ..Default::default()
+        }];
+        let tx = elements::Transaction {
+            version: 2,
+            lock_time: elements::PackedLockTime::ZERO,
+            input: vec![elements::TxIn::default()],
+            output: vec![elements::TxOut {
+                value: elements::confidential::Value::Explicit(1000),
+                ..Default::default()
+            }],
+        };
+        let env = TxEnv::new(&tx, &utxos, 0).unwrap();
+        let (wit, ss) = desc.get_satisfaction((&env, DummySig)).unwrap();
+        let interpreter = Interpreter::from_txdata(
+            &utxos[0].script_pubkey,
+            &ss,
+            &wit,
+            Sequence::ZERO,
+            LockTime::ZERO,
+        )
+        .unwrap();
+
+        let constraints: Vec<_> = interpreter
+            .iter_custom(Box::new(|_| true), Some(&env))
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(constraints.len(), 2);
+        let exts: Vec<_> = constraints
+            .iter()
+            .filter_map(|c| c.as_ext())
+            .map(|ext| ext.to_string())
+            .collect();
+        assert_eq!(exts, vec!["num64_eq(out_v(0),1000)"]);
+
+        // Introspection needs the transaction
+        assert!(interpreter
+            .iter_custom(Box::new(|_| true), None)
+            .any(|res| res.is_err()));
+    }
+
     // By design there is no support for parse a miniscript with BitcoinKey
     // because it does not implement FromStr
     fn no_checks_ms(ms: &str) -> Miniscript<BitcoinKey, NoChecks> {
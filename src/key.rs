@@ -0,0 +1,213 @@
+// Miniscript
+// Written in 2023 by
+//     The Elements Miniscript developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Key Adapters
+//!
+//! A single key type covering the public keys of the bitcoin ecosystem,
+//! so that descriptors over `bitcoin::PublicKey`, `secp256k1_zkp::PublicKey`
+//! and `XOnlyPublicKey` can be combined without converting by hand.
+//!
+
+use std::convert::Infallible;
+use std::{fmt, str};
+
+use bitcoin::secp256k1::XOnlyPublicKey;
+use elements::hashes::{hash160, ripemd160, sha256};
+use elements::secp256k1_zkp;
+
+use crate::{
+    hash256, Descriptor, Error, Extension, MiniscriptKey, ToPublicKey, TranslatePk, Translator,
+};
+
+/// A full or x-only public key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum AnyKey {
+    /// A full public key, which may be uncompressed
+    Full(bitcoin::PublicKey),
+    /// An x-only public key, as used in taproot
+    XOnly(XOnlyPublicKey),
+}
+
+impl fmt::Display for AnyKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            AnyKey::Full(ref pk) => fmt::Display::fmt(pk, f),
+            AnyKey::XOnly(ref pk) => fmt::Display::fmt(pk, f),
+        }
+    }
+}
+
+impl str::FromStr for AnyKey {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() == 64 {
+            Ok(AnyKey::XOnly(XOnlyPublicKey::from_str(s)?))
+        } else {
+            Ok(AnyKey::Full(bitcoin::PublicKey::from_str(s)?))
+        }
+    }
+}
+
+impl From<bitcoin::PublicKey> for AnyKey {
+    fn from(pk: bitcoin::PublicKey) -> Self {
+        AnyKey::Full(pk)
+    }
+}
+
+impl From<secp256k1_zkp::PublicKey> for AnyKey {
+    fn from(pk: secp256k1_zkp::PublicKey) -> Self {
+        AnyKey::Full(bitcoin::PublicKey::new(pk))
+    }
+}
+
+impl From<XOnlyPublicKey> for AnyKey {
+    fn from(pk: XOnlyPublicKey) -> Self {
+        AnyKey::XOnly(pk)
+    }
+}
+
+impl MiniscriptKey for AnyKey {
+    type Sha256 = sha256::Hash;
+    type Hash256 = hash256::Hash;
+    type Ripemd160 = ripemd160::Hash;
+    type Hash160 = hash160::Hash;
+
+    fn is_uncompressed(&self) -> bool {
+        match *self {
+            AnyKey::Full(ref pk) => pk.is_uncompressed(),
+            AnyKey::XOnly(..) => false,
+        }
+    }
+
+    fn is_x_only_key(&self) -> bool {
+        match *self {
+            AnyKey::Full(..) => false,
+            AnyKey::XOnly(..) => true,
+        }
+    }
+}
+
+impl ToPublicKey for AnyKey {
+    fn to_public_key(&self) -> bitcoin::PublicKey {
+        match *self {
+            AnyKey::Full(pk) => pk,
+            AnyKey::XOnly(ref pk) => pk.to_public_key(),
+        }
+    }
+
+    fn to_x_only_pubkey(&self) -> XOnlyPublicKey {
+        match *self {
+            AnyKey::Full(ref pk) => pk.to_x_only_pubkey(),
+            AnyKey::XOnly(pk) => pk,
+        }
+    }
+
+    fn to_sha256(hash: &sha256::Hash) -> sha256::Hash {
+        *hash
+    }
+
+    fn to_hash256(hash: &hash256::Hash) -> hash256::Hash {
+        *hash
+    }
+
+    fn to_ripemd160(hash: &ripemd160::Hash) -> ripemd160::Hash {
+        *hash
+    }
+
+    fn to_hash160(hash: &hash160::Hash) -> hash160::Hash {
+        *hash
+    }
+}
+
+impl<Pk: ToPublicKey, Ext: Extension> Descriptor<Pk, Ext> {
+    /// Convert the keys of this descriptor to [`AnyKey`]s
+    ///
+    /// Keys are converted to x-only keys if they are x-only keys according to
+    /// [`MiniscriptKey::is_x_only_key`] and to full keys otherwise.
+    pub fn to_any_key(&self) -> Descriptor<AnyKey, Ext> {
+        match self.translate_pk(&mut AnyKeyTranslator) {
+            Ok(desc) => desc,
+            Err(e) => match e {},
+        }
+    }
+}
+
+struct AnyKeyTranslator;
+
+impl<Pk: ToPublicKey> Translator<Pk, AnyKey, Infallible> for AnyKeyTranslator {
+    fn pk(&mut self, pk: &Pk) -> Result<AnyKey, Infallible> {
+        if pk.is_x_only_key() {
+            Ok(AnyKey::XOnly(pk.to_x_only_pubkey()))
+        } else {
+            Ok(AnyKey::Full(pk.to_public_key()))
+        }
+    }
+
+    fn sha256(&mut self, sha256: &Pk::Sha256) -> Result<sha256::Hash, Infallible> {
+        Ok(Pk::to_sha256(sha256))
+    }
+
+    fn hash256(&mut self, hash256: &Pk::Hash256) -> Result<hash256::Hash, Infallible> {
+        Ok(Pk::to_hash256(hash256))
+    }
+
+    fn ripemd160(&mut self, ripemd160: &Pk::Ripemd160) -> Result<ripemd160::Hash, Infallible> {
+        Ok(Pk::to_ripemd160(ripemd160))
+    }
+
+    fn hash160(&mut self, hash160: &Pk::Hash160) -> Result<hash160::Hash, Infallible> {
+        Ok(Pk::to_hash160(hash160))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::NoExt;
+
+    #[test]
+    fn any_key() {
+        let full = "020202020202020202020202020202020202020202020202020202020202020202";
+        let xonly = "0202020202020202020202020202020202020202020202020202020202020202";
+        let pk = bitcoin::PublicKey::from_str(full).unwrap();
+        let xpk = XOnlyPublicKey::from_str(xonly).unwrap();
+
+        assert_eq!(AnyKey::from(pk), AnyKey::from_str(full).unwrap());
+        assert_eq!(AnyKey::from(pk.inner), AnyKey::Full(pk));
+        assert_eq!(AnyKey::from(xpk), AnyKey::from_str(xonly).unwrap());
+        assert_eq!(AnyKey::from(xpk).to_string(), xonly);
+        assert_eq!(AnyKey::from(pk).to_x_only_pubkey(), xpk);
+
+        // Descriptors over different key types convert to the same descriptor
+        let a = Descriptor::<bitcoin::PublicKey, NoExt>::from_str(&format!("elwpkh({})", full))
+            .unwrap();
+        let b =
+            Descriptor::<secp256k1_zkp::PublicKey, NoExt>::from_str(&format!("elwpkh({})", full))
+                .unwrap();
+        assert_eq!(a.to_any_key(), b.to_any_key());
+        assert_eq!(a.to_any_key().script_pubkey(), a.script_pubkey());
+
+        let tr = Descriptor::<XOnlyPublicKey, NoExt>::from_str(&format!(
+            "eltr({},pk({}))",
+            xonly, xonly
+        ))
+        .unwrap();
+        let any = Descriptor::<AnyKey, NoExt>::from_str(&tr.to_string()).unwrap();
+        assert_eq!(tr.to_any_key(), any);
+        assert_eq!(any.script_pubkey(), tr.script_pubkey());
+    }
+}
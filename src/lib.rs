@@ -141,13 +141,26 @@ mod pub_macros;
 
 pub use pub_macros::*;
 
+pub mod alias;
+#[cfg(feature = "bdd")]
+pub mod analysis;
+#[cfg(feature = "bip39-test-utils")]
+pub mod bip39;
+pub mod confidential;
 pub mod descriptor;
 pub mod expression;
 pub mod extensions;
 pub mod interpreter;
+pub mod key;
 pub mod miniscript;
 pub mod policy;
+pub mod proof_of_reserves;
 pub mod psbt;
+#[cfg(feature = "registry")]
+pub mod registry;
+pub mod scan;
+#[cfg(feature = "gen-vectors")]
+pub mod vectors;
 
 #[cfg(test)]
 mod test_utils;
@@ -159,14 +172,20 @@ use elements::hashes::sha256;
 use elements::secp256k1_zkp::Secp256k1;
 use elements::{opcodes, script, secp256k1_zkp};
 
+pub use crate::alias::Aliased;
 pub use crate::descriptor::{DefiniteDescriptorKey, Descriptor, DescriptorPublicKey};
 pub use crate::extensions::{CovenantExt, Extension, NoExt, TxEnv};
 pub use crate::interpreter::Interpreter;
-pub use crate::miniscript::analyzable::{AnalysisError, ExtParams};
+pub use crate::key::AnyKey;
+pub use crate::miniscript::analyzable::{AnalysisError, ExtParams, OpSet};
+pub use crate::miniscript::async_satisfy::{AsyncSatisfier, PrefetchedSatisfier};
 pub use crate::miniscript::context::{BareCtx, Legacy, ScriptContext, Segwitv0, Tap};
 pub use crate::miniscript::decode::Terminal;
+pub use crate::miniscript::limits;
 pub use crate::miniscript::satisfy::{
-    elementssig_from_rawsig, elementssig_to_rawsig, ElementsSig, Preimage32, Satisfier,
+    elementssig_from_rawsig, elementssig_to_rawsig, AdaptorCompleter, EcdsaAdaptorSig, ElementsSig,
+    MuSigAggregator, MuSigPartialSigs, Preimage32, Satisfier, SatisfierStack, SatisfyOptions,
+    SchnorrAdaptorSig, SighashFilter, SighashPolicy,
 };
 pub use crate::miniscript::Miniscript;
 // minimal implementation of contract hash module
@@ -303,9 +322,11 @@ pub enum Error {
     Script(script::Error),
     /// rust-bitcoin address error
     AddrError(bitcoin::util::address::Error),
+    /// rust-elements address error
+    ElementsAddrError(elements::AddressError),
     /// A `CHECKMULTISIG` opcode was preceded by a number > 20
     CmsTooManyKeys(u32),
-    /// A tapscript multi_a cannot support more than MAX_BLOCK_WEIGHT/32 keys
+    /// A tapscript multi_a cannot support more than MAX_PUBKEYS_IN_CHECKSIGADD keys
     MultiATooManyKeys(u32),
     /// Encountered unprintable character in descriptor
     Unprintable(u8),
@@ -335,12 +356,29 @@ pub enum Error {
     MissingHash(sha256::Hash),
     /// Could not satisfy a script (fragment) because of a missing signature
     MissingSig(bitcoin::PublicKey),
+    /// The satisfier provided a signature which failed
+    /// [`Satisfier::check_ecdsa_sig`], e.g. one with a high S value
+    NonCanonicalSig(bitcoin::PublicKey),
     /// Could not satisfy, relative locktime not met
     RelativeLocktimeNotMet(u32),
     /// Could not satisfy, absolute locktime not met
     AbsoluteLocktimeNotMet(u32),
     /// General failure to satisfy
     CouldNotSatisfy,
+    /// Satisfaction explored more than the given number of subexpressions,
+    /// see [`miniscript::satisfy::SatisfyOptions`]
+    SatisfactionEffortExceeded(usize),
+    /// A descriptor has more spend paths than the given maximum, see
+    /// [`descriptor::MAX_SPEND_PATHS`]
+    TooManySpendPaths(usize),
+    /// Could not satisfy because extensions need the explicit values of
+    /// confidential spent utxos or outputs, which must be unblinded first
+    NeedsUnblinding {
+        /// Indexes of the inputs whose spent utxo or issuance is confidential
+        inputs: Vec<usize>,
+        /// Indexes of the confidential outputs
+        outputs: Vec<usize>,
+    },
     /// Typechecking failed
     TypeCheck(String),
     /// General error in creating descriptor
@@ -350,6 +388,9 @@ pub enum Error {
     #[cfg(feature = "compiler")]
     /// Compiler related errors
     CompilerError(policy::compiler::CompilerError),
+    #[cfg(feature = "encrypt")]
+    /// Errors decrypting a sealed descriptor
+    UnsealError(descriptor::UnsealError),
     /// Errors related to policy
     PolicyError(policy::concrete::PolicyError),
     /// Errors related to lifting
@@ -369,6 +410,15 @@ pub enum Error {
     ImpossibleSatisfaction,
     /// Bare descriptors don't have any addresses
     BareDescriptorAddr,
+    /// Address does not pay to the descriptor, or is not blinded with the
+    /// expected blinding key
+    AddressMismatch(String),
+    /// Address is confidential where an unconfidential one was expected,
+    /// or vice versa
+    AddressBlinding {
+        /// Whether a confidential address was expected
+        expected_blinded: bool,
+    },
     /// Upstream Miniscript Errors
     BtcError(bitcoin_miniscript::Error),
     /// Covenant Error
@@ -450,14 +500,18 @@ impl From<bitcoin::util::address::Error> for Error {
     }
 }
 
+impl From<elements::AddressError> for Error {
+    fn from(e: elements::AddressError) -> Error {
+        Error::ElementsAddrError(e)
+    }
+}
+
 fn errstr(s: &str) -> Error {
     Error::Unexpected(s.to_owned())
 }
 
 // https://github.com/sipa/miniscript/pull/5 for discussion on this number
 const MAX_RECURSION_DEPTH: u32 = 402;
-// https://github.com/bitcoin/bips/blob/master/bip-0141.mediawiki
-const MAX_SCRIPT_SIZE: u32 = 10000;
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -467,6 +521,7 @@ impl fmt::Display for Error {
             Error::InvalidPush(ref push) => write!(f, "invalid push {:?}", push), // TODO hexify this
             Error::Script(ref e) => fmt::Display::fmt(e, f),
             Error::AddrError(ref e) => fmt::Display::fmt(e, f),
+            Error::ElementsAddrError(ref e) => fmt::Display::fmt(e, f),
             Error::CmsTooManyKeys(n) => write!(f, "checkmultisig with {} keys", n),
             Error::Unprintable(x) => write!(f, "unprintable character 0x{:02x}", x),
             Error::ExpectedChar(c) => write!(f, "expected {}", c),
@@ -481,6 +536,9 @@ impl fmt::Display for Error {
             Error::Trailing(ref s) => write!(f, "trailing tokens: {}", s),
             Error::MissingHash(ref h) => write!(f, "missing preimage of hash {}", h),
             Error::MissingSig(ref pk) => write!(f, "missing signature for key {:?}", pk),
+            Error::NonCanonicalSig(ref pk) => {
+                write!(f, "non-canonical signature for key {:?}", pk)
+            }
             Error::RelativeLocktimeNotMet(n) => {
                 write!(f, "required relative locktime CSV of {} blocks, not met", n)
             }
@@ -490,6 +548,18 @@ impl fmt::Display for Error {
                 n
             ),
             Error::CouldNotSatisfy => f.write_str("could not satisfy"),
+            Error::SatisfactionEffortExceeded(n) => {
+                write!(f, "satisfaction exceeded the budget of {} branches", n)
+            }
+            Error::TooManySpendPaths(n) => write!(f, "more than {} spend paths", n),
+            Error::NeedsUnblinding {
+                ref inputs,
+                ref outputs,
+            } => write!(
+                f,
+                "could not satisfy, needs unblinded inputs {:?} and outputs {:?}",
+                inputs, outputs
+            ),
             Error::BadPubkey(ref e) => fmt::Display::fmt(e, f),
             Error::TypeCheck(ref e) => write!(f, "typecheck: {}", e),
             Error::BadDescriptor(ref e) => write!(f, "Invalid descriptor: {}", e),
@@ -497,6 +567,8 @@ impl fmt::Display for Error {
             Error::ContextError(ref e) => fmt::Display::fmt(e, f),
             #[cfg(feature = "compiler")]
             Error::CompilerError(ref e) => fmt::Display::fmt(e, f),
+            #[cfg(feature = "encrypt")]
+            Error::UnsealError(ref e) => fmt::Display::fmt(e, f),
             Error::PolicyError(ref e) => fmt::Display::fmt(e, f),
             Error::LiftError(ref e) => fmt::Display::fmt(e, f),
             Error::MaxRecursiveDepthExceeded => write!(
@@ -507,7 +579,7 @@ impl fmt::Display for Error {
             Error::ScriptSizeTooLarge => write!(
                 f,
                 "Standardness rules imply bitcoin than {} bytes",
-                MAX_SCRIPT_SIZE
+                miniscript::limits::MAX_SCRIPT_SIZE
             ),
             Error::NonStandardBareScript => write!(
                 f,
@@ -518,6 +590,14 @@ impl fmt::Display for Error {
             Error::AnalysisError(ref e) => e.fmt(f),
             Error::ImpossibleSatisfaction => write!(f, "Impossible to satisfy Miniscript"),
             Error::BareDescriptorAddr => write!(f, "Bare descriptors don't have address"),
+            Error::AddressMismatch(ref s) => write!(f, "Address {} does not match descriptor", s),
+            Error::AddressBlinding { expected_blinded } => {
+                if expected_blinded {
+                    f.write_str("Expected a confidential address")
+                } else {
+                    f.write_str("Expected an unconfidential address")
+                }
+            }
             Error::BtcError(ref e) => write!(f, " Bitcoin Miniscript Error {}", e),
             Error::CovError(ref e) => write!(f, "Covenant Error: {}", e),
             Error::PubKeyCtxError(ref pk, ref ctx) => {
@@ -554,9 +634,13 @@ impl error::Error for Error {
             | Trailing(_)
             | MissingHash(_)
             | MissingSig(_)
+            | NonCanonicalSig(_)
             | RelativeLocktimeNotMet(_)
             | AbsoluteLocktimeNotMet(_)
             | CouldNotSatisfy
+            | SatisfactionEffortExceeded(_)
+            | TooManySpendPaths(_)
+            | NeedsUnblinding { .. }
             | TypeCheck(_)
             | BadDescriptor(_)
             | MaxRecursiveDepthExceeded
@@ -564,6 +648,8 @@ impl error::Error for Error {
             | NonStandardBareScript
             | ImpossibleSatisfaction
             | BareDescriptorAddr
+            | AddressMismatch(_)
+            | AddressBlinding { .. }
             | TaprootSpendInfoUnavialable
             | TrNoScriptCode
             | TrNoExplicitScript => None,
@@ -571,10 +657,13 @@ impl error::Error for Error {
             CovError(e) => Some(e),
             Script(_e) => None, // should be Some(e), but requires changes upstream
             AddrError(e) => Some(e),
+            ElementsAddrError(e) => Some(e),
             BadPubkey(e) => Some(e),
             Secp(e) => Some(e),
             #[cfg(feature = "compiler")]
             CompilerError(e) => Some(e),
+            #[cfg(feature = "encrypt")]
+            UnsealError(e) => Some(e),
             PolicyError(e) => Some(e),
             LiftError(e) => Some(e),
             ContextError(e) => Some(e),
@@ -592,6 +681,14 @@ impl From<policy::compiler::CompilerError> for Error {
     }
 }
 
+#[doc(hidden)]
+#[cfg(feature = "encrypt")]
+impl From<descriptor::UnsealError> for Error {
+    fn from(e: descriptor::UnsealError) -> Error {
+        Error::UnsealError(e)
+    }
+}
+
 #[doc(hidden)]
 impl From<policy::concrete::PolicyError> for Error {
     fn from(e: policy::concrete::PolicyError) -> Error {
@@ -16,6 +16,27 @@ macro_rules! policy_str {
     ($($arg:tt)*) => ($crate::policy::Concrete::from_str(&format!($($arg)*)).unwrap())
 }
 
+/// Emits a trace level event with the arguments of `tracing::trace!`,
+/// compiled out without the `tracing` feature
+macro_rules! trace_event {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        {
+            tracing::trace!($($arg)*);
+        }
+    };
+}
+
+/// Enters a trace level span with the arguments of `tracing::trace_span!`
+/// until the end of the enclosing scope, compiled out without the `tracing`
+/// feature
+macro_rules! trace_span {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::trace_span!($($arg)*).entered();
+    };
+}
+
 /// Macro for implementing FromTree trait. This avoids copying all the Pk::Associated type bounds
 /// throughout the codebase.
 macro_rules! impl_from_tree {
@@ -91,7 +112,7 @@ macro_rules! impl_block_str {
     ($(;$gen:ident; $gen_con:ident, )* $name: ty,
     $(=> $ext:ident; $trt:ident, )?
         $(#[$meta:meta])*
-        $v:vis fn $fn:ident ( $($arg:ident : $type:ty, )* ) -> $ret:ty
+        $v:vis fn $fn:ident ( $($arg:ident : $type:ty),* $(,)? ) -> $ret:ty
         $body:block
     ) => {
         impl<Pk $(, $gen)* $(, $ext)?> $name
@@ -20,7 +20,11 @@
 use std::collections::HashSet;
 use std::fmt;
 
-use crate::{error, Extension, Miniscript, MiniscriptKey, ScriptContext, Terminal};
+use elements::opcodes;
+use elements::script::Instruction;
+
+use crate::extensions::ParseableExt;
+use crate::{error, Extension, Miniscript, MiniscriptKey, ScriptContext, Terminal, ToPublicKey};
 
 /// Params for parsing miniscripts that either non-sane or non-specified(experimental) in the spec.
 /// Used as a parameter [`Miniscript::from_str_ext`] and [`Miniscript::parse_with_ext`].
@@ -154,6 +158,8 @@ pub enum AnalysisError {
     Malleable,
     /// Contains partial descriptor raw pkh
     ContainsRawPkh,
+    /// The script contains an opcode outside of the allowed [`OpSet`]s
+    DisallowedOpcode(opcodes::All),
 }
 
 impl fmt::Display for AnalysisError {
@@ -173,6 +179,9 @@ impl fmt::Display for AnalysisError {
             }
             AnalysisError::Malleable => f.write_str("Miniscript is malleable"),
             AnalysisError::ContainsRawPkh => f.write_str("Miniscript contains raw pkh"),
+            AnalysisError::DisallowedOpcode(op) => {
+                write!(f, "Miniscript contains disallowed opcode {}", op)
+            }
         }
     }
 }
@@ -187,7 +196,77 @@ impl error::Error for AnalysisError {
             | BranchExceedResouceLimits
             | HeightTimelockCombination
             | Malleable
-            | ContainsRawPkh => None,
+            | ContainsRawPkh
+            | DisallowedOpcode(_) => None,
+        }
+    }
+}
+
+/// A class of opcodes, see [`Miniscript::assert_opcode_whitelist`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum OpSet {
+    /// Data pushes and small number pushes
+    Push,
+    /// Stack manipulation, including `OP_SIZE`
+    Stack,
+    /// `OP_IF`, `OP_NOTIF`, `OP_ELSE`, `OP_ENDIF` and `OP_VERIFY`
+    Control,
+    /// Script number arithmetic, comparisons and `OP_EQUAL(VERIFY)`
+    Arithmetic,
+    /// `OP_RIPEMD160`, `OP_SHA1`, `OP_SHA256`, `OP_HASH160` and `OP_HASH256`
+    Hash,
+    /// Transaction signature checks, including multisig and `OP_CHECKSIGADD`
+    Signature,
+    /// `OP_CHECKLOCKTIMEVERIFY` and `OP_CHECKSEQUENCEVERIFY`
+    Timelock,
+    /// Byte string and bitwise operations re-enabled in elements, such as
+    /// `OP_CAT` and `OP_SUBSTR`
+    Splice,
+    /// `OP_CHECKSIGFROMSTACK(VERIFY)`
+    Csfs,
+    /// Streaming `OP_SHA256INITIALIZE`, `OP_SHA256UPDATE` and
+    /// `OP_SHA256FINALIZE`
+    StreamingHash,
+    /// Transaction introspection, such as `OP_INSPECTOUTPUTVALUE` and
+    /// `OP_TXWEIGHT`
+    Introspection,
+    /// 64-bit arithmetic and conversions, such as `OP_ADD64`
+    Arithmetic64,
+    /// `OP_ECMULSCALARVERIFY` and `OP_TWEAKVERIFY`
+    EcOps,
+}
+
+impl OpSet {
+    /// The opcode sets used by miniscript without extensions
+    pub const MINISCRIPT: &'static [OpSet] = &[
+        OpSet::Push,
+        OpSet::Stack,
+        OpSet::Control,
+        OpSet::Arithmetic,
+        OpSet::Hash,
+        OpSet::Signature,
+        OpSet::Timelock,
+    ];
+
+    /// The set `op` belongs to, or `None` for opcodes in no set, such as
+    /// `OP_RETURN`, `OP_CODESEPARATOR` and the `OP_NOP`s, which are never
+    /// allowed
+    pub fn of(op: opcodes::All) -> Option<OpSet> {
+        match op.into_u8() {
+            0x00..=0x4f | 0x51..=0x60 => Some(OpSet::Push),
+            0x6b..=0x7d | 0x82 => Some(OpSet::Stack),
+            0x63 | 0x64 | 0x67..=0x69 => Some(OpSet::Control),
+            0x87 | 0x88 | 0x8b | 0x8c | 0x8f..=0x94 | 0x9a..=0xa5 => Some(OpSet::Arithmetic),
+            0xa6..=0xaa => Some(OpSet::Hash),
+            0xac..=0xaf | 0xba => Some(OpSet::Signature),
+            0xb1 | 0xb2 => Some(OpSet::Timelock),
+            0x7e..=0x81 | 0x83..=0x86 | 0x98 | 0x99 | 0xc3 => Some(OpSet::Splice),
+            0xc1 | 0xc2 => Some(OpSet::Csfs),
+            0xc4..=0xc6 => Some(OpSet::StreamingHash),
+            0xc7..=0xd6 => Some(OpSet::Introspection),
+            0xd7..=0xe2 => Some(OpSet::Arithmetic64),
+            0xe3 | 0xe4 => Some(OpSet::EcOps),
+            _ => None,
         }
     }
 }
@@ -278,3 +357,31 @@ impl<Pk: MiniscriptKey, Ctx: ScriptContext, Ext: Extension> Miniscript<Pk, Ctx,
         }
     }
 }
+
+impl<Pk: ToPublicKey, Ctx: ScriptContext, Ext: ParseableExt> Miniscript<Pk, Ctx, Ext> {
+    /// Check that the encoded script only contains opcodes of the `allowed`
+    /// sets, for example [`OpSet::MINISCRIPT`] to reject all extensions.
+    ///
+    /// This checks the script itself rather than the fragments, so that
+    /// opcodes introduced by the compiler or by extensions cannot slip
+    /// through. Opcodes without an [`OpSet`] are always rejected.
+    pub fn assert_opcode_whitelist(&self, allowed: &[OpSet]) -> Result<(), AnalysisError> {
+        for ins in self.encode().instructions() {
+            let op = match ins {
+                Ok(Instruction::PushBytes(_)) => opcodes::all::OP_PUSHBYTES_0,
+                Ok(Instruction::Op(op)) => op,
+                // Fail closed, although encoded miniscripts always parse
+                Err(_) => {
+                    return Err(AnalysisError::DisallowedOpcode(
+                        opcodes::all::OP_INVALIDOPCODE,
+                    ))
+                }
+            };
+            match OpSet::of(op) {
+                Some(set) if allowed.contains(&set) => {}
+                _ => return Err(AnalysisError::DisallowedOpcode(op)),
+            }
+        }
+        Ok(())
+    }
+}
@@ -317,37 +317,7 @@ where
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.write_str("[")?;
         if let Ok(type_map) = types::Type::type_check(self, |_| None) {
-            f.write_str(match type_map.corr.base {
-                types::Base::B => "B",
-                types::Base::K => "K",
-                types::Base::V => "V",
-                types::Base::W => "W",
-            })?;
-            fmt::Write::write_char(f, '/')?;
-            f.write_str(match type_map.corr.input {
-                types::Input::Zero => "z",
-                types::Input::One => "o",
-                types::Input::OneNonZero => "on",
-                types::Input::Any => "",
-                types::Input::AnyNonZero => "n",
-            })?;
-            if type_map.corr.dissatisfiable {
-                fmt::Write::write_char(f, 'd')?;
-            }
-            if type_map.corr.unit {
-                fmt::Write::write_char(f, 'u')?;
-            }
-            f.write_str(match type_map.mall.dissat {
-                types::Dissat::None => "f",
-                types::Dissat::Unique => "e",
-                types::Dissat::Unknown => "",
-            })?;
-            if type_map.mall.safe {
-                fmt::Write::write_char(f, 's')?;
-            }
-            if type_map.mall.non_malleable {
-                fmt::Write::write_char(f, 'm')?;
-            }
+            write!(f, "{}", type_map)?;
         } else {
             f.write_str("TYPECHECK FAILED")?;
         }
@@ -556,7 +526,11 @@ impl_from_tree!(
                 if wrap.is_empty() {
                     return Err(Error::Unexpected(top.name.to_owned()));
                 }
-                if name == "pk" {
+                if name == "pk" && wrap.ends_with('c') {
+                    // legacy: c:pk(K) = c:pk_k(K), from when pk was not an alias
+                    frag_name = "pk_k";
+                    frag_wrap = wrap;
+                } else if name == "pk" {
                     frag_name = "pk_k";
                     aliased_wrap = wrap.to_owned();
                     aliased_wrap.push('c');
@@ -638,7 +612,8 @@ impl_from_tree!(
 
                 Ok(Terminal::Thresh(k, subs?))
             }
-            ("multi", n) | ("multi_a", n) => {
+            // legacy: thresh_m was renamed multi
+            ("multi", n) | ("thresh_m", n) | ("multi_a", n) => {
                 if n == 0 {
                     return Err(errstr("no arguments given"));
                 }
@@ -652,7 +627,7 @@ impl_from_tree!(
                     .map(|sub| expression::terminal(sub, Pk::from_str))
                     .collect();
 
-                if frag_name == "multi" {
+                if frag_name == "multi" || frag_name == "thresh_m" {
                     pks.map(|pks| Terminal::Multi(k, pks))
                 } else {
                     // must be multi_a
@@ -0,0 +1,343 @@
+// Miniscript
+// Written in 2023 by
+//     The Elements Miniscript developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Asynchronous Satisfiers
+//!
+//! Satisfaction itself is synchronous, so signers which have to wait on a
+//! remote service, such as a HSM, cannot be used as a [`Satisfier`]
+//! directly. [`AsyncSatisfier`]s are instead asked for everything listed by
+//! [`Descriptor::requirements`] ahead of time, and the answers are collected
+//! in a [`PrefetchedSatisfier`] which is then used for satisfaction.
+//!
+
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::pin::Pin;
+
+use elements::taproot::TapLeafHash;
+
+use super::satisfy::{ElementsSig, Preimage32, Satisfier};
+use crate::descriptor::{Requirements, SpendHash};
+use crate::extensions::ParseableExt;
+use crate::{Descriptor, Error, Extension, MiniscriptKey, ToPublicKey};
+
+/// A boxed future, as returned by the methods of [`AsyncSatisfier`]
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Asynchronous counterpart of [`Satisfier`]
+///
+/// All methods default to not providing anything.
+pub trait AsyncSatisfier<Pk: MiniscriptKey + ToPublicKey> {
+    /// Fetch the ECDSA signature of `pk`
+    fn ecdsa_sig<'a>(&'a self, _pk: &'a Pk) -> BoxFuture<'a, Option<ElementsSig>> {
+        Box::pin(async { None })
+    }
+
+    /// Fetch the taproot key spend signature of `internal_key`
+    fn tap_key_spend_sig<'a>(
+        &'a self,
+        _internal_key: &'a Pk,
+    ) -> BoxFuture<'a, Option<elements::SchnorrSig>> {
+        Box::pin(async { None })
+    }
+
+    /// Fetch the signature of `pk` for the script path spend of `leaf`
+    fn tap_leaf_script_sig<'a>(
+        &'a self,
+        _pk: &'a Pk,
+        _leaf: &'a TapLeafHash,
+    ) -> BoxFuture<'a, Option<elements::SchnorrSig>> {
+        Box::pin(async { None })
+    }
+
+    /// Fetch the preimage of a SHA256 hash
+    fn sha256_preimage<'a>(&'a self, _h: &'a Pk::Sha256) -> BoxFuture<'a, Option<Preimage32>> {
+        Box::pin(async { None })
+    }
+
+    /// Fetch the preimage of a double SHA256 hash
+    fn hash256_preimage<'a>(&'a self, _h: &'a Pk::Hash256) -> BoxFuture<'a, Option<Preimage32>> {
+        Box::pin(async { None })
+    }
+
+    /// Fetch the preimage of a RIPEMD160 hash
+    fn ripemd160_preimage<'a>(
+        &'a self,
+        _h: &'a Pk::Ripemd160,
+    ) -> BoxFuture<'a, Option<Preimage32>> {
+        Box::pin(async { None })
+    }
+
+    /// Fetch the preimage of a HASH160 hash
+    fn hash160_preimage<'a>(&'a self, _h: &'a Pk::Hash160) -> BoxFuture<'a, Option<Preimage32>> {
+        Box::pin(async { None })
+    }
+}
+
+/// A satisfier holding the items fetched from an [`AsyncSatisfier`]
+///
+/// Items which were not fetched are not available. Timelocks and covenant
+/// data can be added by combining it with other satisfiers in a tuple.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrefetchedSatisfier<Pk: MiniscriptKey> {
+    ecdsa_sigs: BTreeMap<Pk, ElementsSig>,
+    tap_key_spend_sig: Option<elements::SchnorrSig>,
+    tap_leaf_sigs: BTreeMap<(Pk, TapLeafHash), elements::SchnorrSig>,
+    sha256: BTreeMap<Pk::Sha256, Preimage32>,
+    hash256: BTreeMap<Pk::Hash256, Preimage32>,
+    ripemd160: BTreeMap<Pk::Ripemd160, Preimage32>,
+    hash160: BTreeMap<Pk::Hash160, Preimage32>,
+}
+
+impl<Pk: MiniscriptKey + ToPublicKey> PrefetchedSatisfier<Pk> {
+    /// Fetch everything listed in `requirements` from `satisfier`
+    ///
+    /// Items are requested one after the other.
+    pub async fn fetch<S: AsyncSatisfier<Pk>>(
+        satisfier: &S,
+        requirements: &Requirements<Pk>,
+    ) -> PrefetchedSatisfier<Pk> {
+        let mut ret = PrefetchedSatisfier {
+            ecdsa_sigs: BTreeMap::new(),
+            tap_key_spend_sig: None,
+            tap_leaf_sigs: BTreeMap::new(),
+            sha256: BTreeMap::new(),
+            hash256: BTreeMap::new(),
+            ripemd160: BTreeMap::new(),
+            hash160: BTreeMap::new(),
+        };
+        for pk in &requirements.ecdsa_keys {
+            if let Some(sig) = satisfier.ecdsa_sig(pk).await {
+                ret.ecdsa_sigs.insert(pk.clone(), sig);
+            }
+        }
+        if let Some(ref internal_key) = requirements.tap_key_spend {
+            ret.tap_key_spend_sig = satisfier.tap_key_spend_sig(internal_key).await;
+        }
+        for (pk, leaf) in &requirements.tap_leaf_keys {
+            if let Some(sig) = satisfier.tap_leaf_script_sig(pk, leaf).await {
+                ret.tap_leaf_sigs.insert((pk.clone(), *leaf), sig);
+            }
+        }
+        for hash in &requirements.hashes {
+            match *hash {
+                SpendHash::Sha256(ref h) => {
+                    if let Some(preimage) = satisfier.sha256_preimage(h).await {
+                        ret.sha256.insert(h.clone(), preimage);
+                    }
+                }
+                SpendHash::Hash256(ref h) => {
+                    if let Some(preimage) = satisfier.hash256_preimage(h).await {
+                        ret.hash256.insert(h.clone(), preimage);
+                    }
+                }
+                SpendHash::Ripemd160(ref h) => {
+                    if let Some(preimage) = satisfier.ripemd160_preimage(h).await {
+                        ret.ripemd160.insert(h.clone(), preimage);
+                    }
+                }
+                SpendHash::Hash160(ref h) => {
+                    if let Some(preimage) = satisfier.hash160_preimage(h).await {
+                        ret.hash160.insert(h.clone(), preimage);
+                    }
+                }
+            }
+        }
+        ret
+    }
+}
+
+impl<Pk: MiniscriptKey + ToPublicKey> Satisfier<Pk> for PrefetchedSatisfier<Pk> {
+    fn lookup_ecdsa_sig(&self, pk: &Pk) -> Option<ElementsSig> {
+        self.ecdsa_sigs.get(pk).copied()
+    }
+
+    fn lookup_tap_key_spend_sig(&self) -> Option<elements::SchnorrSig> {
+        self.tap_key_spend_sig
+    }
+
+    fn lookup_tap_leaf_script_sig(
+        &self,
+        pk: &Pk,
+        leaf: &TapLeafHash,
+    ) -> Option<elements::SchnorrSig> {
+        self.tap_leaf_sigs.get(&(pk.clone(), *leaf)).copied()
+    }
+
+    fn lookup_sha256(&self, h: &Pk::Sha256) -> Option<Preimage32> {
+        self.sha256.get(h).copied()
+    }
+
+    fn lookup_hash256(&self, h: &Pk::Hash256) -> Option<Preimage32> {
+        self.hash256.get(h).copied()
+    }
+
+    fn lookup_ripemd160(&self, h: &Pk::Ripemd160) -> Option<Preimage32> {
+        self.ripemd160.get(h).copied()
+    }
+
+    fn lookup_hash160(&self, h: &Pk::Hash160) -> Option<Preimage32> {
+        self.hash160.get(h).copied()
+    }
+}
+
+impl<Pk: MiniscriptKey + ToPublicKey, Ext: Extension + ParseableExt> Descriptor<Pk, Ext> {
+    /// Fetch everything this descriptor may need from `satisfier`, see
+    /// [`Descriptor::requirements`] and [`PrefetchedSatisfier::fetch`]
+    ///
+    /// # Errors
+    /// When the descriptor cannot be lifted.
+    pub async fn prefetch_satisfier<S: AsyncSatisfier<Pk>>(
+        &self,
+        satisfier: &S,
+    ) -> Result<PrefetchedSatisfier<Pk>, Error> {
+        let requirements = self.requirements()?;
+        Ok(PrefetchedSatisfier::fetch(satisfier, &requirements).await)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+    use std::str::FromStr;
+    use std::sync::Mutex;
+    use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+    use bitcoin::XOnlyPublicKey;
+    use elements::hashes::{sha256, Hash};
+    use elements::secp256k1_zkp;
+
+    use super::*;
+    use crate::NoExt;
+
+    // Polls a future to completion, the futures of the tests never wait.
+    // Building a no-op waker needs unsafe code without `std::task::Wake`,
+    // which is not available with the MSRV
+    #[allow(unsafe_code)]
+    fn block_on<F: Future>(fut: F) -> F::Output {
+        fn raw_waker() -> RawWaker {
+            fn clone(_: *const ()) -> RawWaker {
+                raw_waker()
+            }
+            fn noop(_: *const ()) {}
+            static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+            RawWaker::new(std::ptr::null(), &VTABLE)
+        }
+        let waker = unsafe { Waker::from_raw(raw_waker()) };
+        let mut cx = Context::from_waker(&waker);
+        let mut fut = Box::pin(fut);
+        loop {
+            if let Poll::Ready(ret) = fut.as_mut().poll(&mut cx) {
+                return ret;
+            }
+        }
+    }
+
+    fn xonly_key(i: u8) -> XOnlyPublicKey {
+        let secp = secp256k1_zkp::Secp256k1::signing_only();
+        let sk = secp256k1_zkp::SecretKey::from_slice(&[i + 1; 32]).unwrap();
+        secp256k1_zkp::KeyPair::from_secret_key(&secp, &sk)
+            .x_only_public_key()
+            .0
+    }
+
+    /// Signs for a single key and logs all requests
+    struct RemoteSigner {
+        key: XOnlyPublicKey,
+        preimage: Preimage32,
+        requests: Mutex<Vec<String>>,
+    }
+
+    fn schnorr_sig() -> elements::SchnorrSig {
+        elements::SchnorrSig {
+            sig: secp256k1_zkp::schnorr::Signature::from_slice(&[1; 64]).unwrap(),
+            hash_ty: elements::SchnorrSigHashType::Default,
+        }
+    }
+
+    impl AsyncSatisfier<XOnlyPublicKey> for RemoteSigner {
+        fn tap_key_spend_sig<'a>(
+            &'a self,
+            internal_key: &'a XOnlyPublicKey,
+        ) -> BoxFuture<'a, Option<elements::SchnorrSig>> {
+            Box::pin(async move {
+                self.requests
+                    .lock()
+                    .unwrap()
+                    .push(format!("key spend {}", internal_key));
+                None
+            })
+        }
+
+        fn tap_leaf_script_sig<'a>(
+            &'a self,
+            pk: &'a XOnlyPublicKey,
+            _leaf: &'a TapLeafHash,
+        ) -> BoxFuture<'a, Option<elements::SchnorrSig>> {
+            Box::pin(async move {
+                self.requests.lock().unwrap().push(format!("leaf {}", pk));
+                if *pk == self.key {
+                    Some(schnorr_sig())
+                } else {
+                    None
+                }
+            })
+        }
+
+        fn sha256_preimage<'a>(&'a self, h: &'a sha256::Hash) -> BoxFuture<'a, Option<Preimage32>> {
+            Box::pin(async move {
+                self.requests.lock().unwrap().push(format!("sha256 {}", h));
+                Some(self.preimage)
+            })
+        }
+    }
+
+    #[test]
+    fn prefetch_satisfier() {
+        let (internal, a, b) = (xonly_key(0), xonly_key(1), xonly_key(2));
+        let preimage = [3; 32];
+        let hash = sha256::Hash::hash(&preimage);
+        let desc = Descriptor::<XOnlyPublicKey, NoExt>::from_str(&format!(
+            "eltr({},{{and_v(v:pk({}),sha256({})),multi_a(2,{},{})}})",
+            internal, a, hash, a, b
+        ))
+        .unwrap();
+
+        let req = desc.requirements().unwrap();
+        assert!(req.ecdsa_keys.is_empty());
+        assert_eq!(req.tap_key_spend, Some(internal));
+        assert_eq!(req.tap_leaf_keys.len(), 3);
+        assert_eq!(
+            req.hashes,
+            vec![SpendHash::Sha256(hash)]
+                .into_iter()
+                .collect::<BTreeSet<_>>()
+        );
+
+        let signer = RemoteSigner {
+            key: a,
+            preimage,
+            requests: Mutex::new(vec![]),
+        };
+        let prefetched = block_on(desc.prefetch_satisfier(&signer)).unwrap();
+        assert_eq!(signer.requests.lock().unwrap().len(), 5);
+        assert_eq!(prefetched.lookup_tap_key_spend_sig(), None);
+        assert_eq!(prefetched.lookup_sha256(&hash), Some(preimage));
+
+        // Only the hashlock leaf can be satisfied with the fetched items
+        let (witness, _) = desc.get_satisfaction(&prefetched).unwrap();
+        assert_eq!(witness[0], preimage.to_vec());
+        assert_eq!(witness.len(), 4);
+    }
+}
@@ -15,14 +15,13 @@
 use std::{error, fmt, hash};
 
 use bitcoin;
-use bitcoin::blockdata::constants::MAX_BLOCK_WEIGHT;
 use bitcoin::hashes::{hash160, ripemd160, sha256};
 
 use super::decode::ParseableKey;
 use crate::miniscript::limits::{
-    MAX_OPS_PER_SCRIPT, MAX_PUBKEYS_PER_MULTISIG, MAX_SCRIPTSIG_SIZE, MAX_SCRIPT_ELEMENT_SIZE,
-    MAX_SCRIPT_SIZE, MAX_STACK_SIZE, MAX_STANDARD_P2WSH_SCRIPT_SIZE,
-    MAX_STANDARD_P2WSH_STACK_ITEMS,
+    MAX_BLOCK_WEIGHT, MAX_OPS_PER_SCRIPT, MAX_PUBKEYS_PER_MULTISIG, MAX_PUBKEYS_PER_P2SH_MULTISIG,
+    MAX_SCRIPTSIG_SIZE, MAX_SCRIPT_ELEMENT_SIZE, MAX_SCRIPT_SIZE, MAX_STACK_SIZE,
+    MAX_STANDARD_P2WSH_SCRIPT_SIZE, MAX_STANDARD_P2WSH_STACK_ITEMS,
 };
 use crate::miniscript::types;
 use crate::util::witness_to_scriptsig;
@@ -75,6 +74,9 @@ pub enum ScriptContextError {
     StackSizeLimitExceeded { actual: usize, limit: usize },
     /// More than 20 keys in a Multi fragment
     CheckMultiSigLimitExceeded,
+    /// More than `MAX_PUBKEYS_PER_P2SH_MULTISIG` (15) keys in a Multi fragment
+    /// of a P2SH redeem script. The number of keys is included.
+    P2shMultiSigLimitExceeded(usize),
     /// MultiA is only allowed in post tapscript
     MultiANotAllowed,
     /// Extension Error for Downstream implementations, includes a string
@@ -101,6 +103,7 @@ impl error::Error for ScriptContextError {
             | TaprootMultiDisabled
             | StackSizeLimitExceeded { .. }
             | CheckMultiSigLimitExceeded
+            | P2shMultiSigLimitExceeded(_)
             | MultiANotAllowed
             | CovElementSizeExceeded
             | ExtensionError(_) => None,
@@ -186,6 +189,13 @@ impl fmt::Display for ScriptContextError {
                     "CHECkMULTISIG ('multi()' descriptor) only supports up to 20 pubkeys"
                 )
             }
+            ScriptContextError::P2shMultiSigLimitExceeded(n) => write!(
+                f,
+                "CHECKMULTISIG ('multi()' descriptor) under P2SH supports up to {} pubkeys, \
+                 found {}: the redeem script is pushed by the scriptSig, so it cannot be \
+                 larger than MAX_SCRIPT_ELEMENT_SIZE ({}) bytes",
+                MAX_PUBKEYS_PER_P2SH_MULTISIG, n, MAX_SCRIPT_ELEMENT_SIZE
+            ),
             ScriptContextError::MultiANotAllowed => {
                 write!(f, "Multi a(CHECKSIGADD) only allowed post tapscript")
             }
@@ -363,8 +373,25 @@ where
     /// Note that this includes the serialization prefix. Returns
     /// 34/66 for Bare/Legacy based on key compressedness
     /// 34 for Segwitv0, 33 for Tap
+    ///
+    /// The length is computed from the context alone and does not check
+    /// whether the key is allowed in it, e.g. x-only keys are reported as
+    /// 34 bytes in Legacy context. Use [`ScriptContext::checked_pk_len`] for
+    /// keys that have not been validated already.
     fn pk_len<Pk: MiniscriptKey>(pk: &Pk) -> usize;
 
+    /// Check whether the key can be used in this context: x-only keys are
+    /// only allowed in Tap and uncompressed keys only in Bare/Legacy.
+    fn check_pk<Pk: MiniscriptKey>(pk: &Pk) -> Result<(), ScriptContextError>;
+
+    /// Get the len of public key when serialized based on context, see
+    /// [`ScriptContext::pk_len`], erroring if the key cannot be used in this
+    /// context, see [`ScriptContext::check_pk`].
+    fn checked_pk_len<Pk: MiniscriptKey>(pk: &Pk) -> Result<usize, ScriptContextError> {
+        Self::check_pk(pk)?;
+        Ok(Self::pk_len(pk))
+    }
+
     /// Local helper function to display error messages with context
     fn name_str() -> &'static str;
 }
@@ -405,28 +432,24 @@ impl ScriptContext for Legacy {
     fn check_global_consensus_validity<Pk: MiniscriptKey, Ext: Extension>(
         ms: &Miniscript<Pk, Self, Ext>,
     ) -> Result<(), ScriptContextError> {
+        // Checked first to report the key limit rather than the script size
+        if let Terminal::Multi(_, ref pks) = ms.node {
+            if pks.len() > MAX_PUBKEYS_PER_P2SH_MULTISIG {
+                return Err(ScriptContextError::P2shMultiSigLimitExceeded(pks.len()));
+            }
+        }
         if ms.ext.pk_cost > MAX_SCRIPT_ELEMENT_SIZE {
             return Err(ScriptContextError::MaxRedeemScriptSizeExceeded);
         }
 
         match ms.node {
-            Terminal::PkK(ref key) if key.is_x_only_key() => {
-                return Err(ScriptContextError::XOnlyKeysNotAllowed(
-                    key.to_string(),
-                    Self::name_str(),
-                ))
-            }
+            Terminal::PkK(ref key) => Self::check_pk(key)?,
             Terminal::Multi(_k, ref pks) => {
                 if pks.len() > MAX_PUBKEYS_PER_MULTISIG {
                     return Err(ScriptContextError::CheckMultiSigLimitExceeded);
                 }
                 for pk in pks.iter() {
-                    if pk.is_x_only_key() {
-                        return Err(ScriptContextError::XOnlyKeysNotAllowed(
-                            pk.to_string(),
-                            Self::name_str(),
-                        ));
-                    }
+                    Self::check_pk(pk)?;
                 }
             }
             Terminal::MultiA(..) => {
@@ -484,6 +507,17 @@ impl ScriptContext for Legacy {
         }
     }
 
+    fn check_pk<Pk: MiniscriptKey>(pk: &Pk) -> Result<(), ScriptContextError> {
+        if pk.is_x_only_key() {
+            Err(ScriptContextError::XOnlyKeysNotAllowed(
+                pk.to_string(),
+                Self::name_str(),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
     fn name_str() -> &'static str {
         "Legacy/p2sh"
     }
@@ -526,30 +560,13 @@ impl ScriptContext for Segwitv0 {
         }
 
         match ms.node {
-            Terminal::PkK(ref pk) => {
-                if pk.is_uncompressed() {
-                    return Err(ScriptContextError::CompressedOnly(pk.to_string()));
-                } else if pk.is_x_only_key() {
-                    return Err(ScriptContextError::XOnlyKeysNotAllowed(
-                        pk.to_string(),
-                        Self::name_str(),
-                    ));
-                }
-                Ok(())
-            }
+            Terminal::PkK(ref pk) => Self::check_pk(pk),
             Terminal::Multi(_k, ref pks) => {
                 if pks.len() > MAX_PUBKEYS_PER_MULTISIG {
                     return Err(ScriptContextError::CheckMultiSigLimitExceeded);
                 }
                 for pk in pks.iter() {
-                    if pk.is_uncompressed() {
-                        return Err(ScriptContextError::CompressedOnly(pk.to_string()));
-                    } else if pk.is_x_only_key() {
-                        return Err(ScriptContextError::XOnlyKeysNotAllowed(
-                            pk.to_string(),
-                            Self::name_str(),
-                        ));
-                    }
+                    Self::check_pk(pk)?;
                 }
                 Ok(())
             }
@@ -613,6 +630,19 @@ impl ScriptContext for Segwitv0 {
         34
     }
 
+    fn check_pk<Pk: MiniscriptKey>(pk: &Pk) -> Result<(), ScriptContextError> {
+        if pk.is_uncompressed() {
+            Err(ScriptContextError::CompressedOnly(pk.to_string()))
+        } else if pk.is_x_only_key() {
+            Err(ScriptContextError::XOnlyKeysNotAllowed(
+                pk.to_string(),
+                Self::name_str(),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
     fn name_str() -> &'static str {
         "Segwitv0"
     }
@@ -657,17 +687,12 @@ impl ScriptContext for Tap {
         // When the transaction sizes get close to block limits,
         // some guarantees are not easy to satisfy because of knapsack
         // constraints
-        if ms.ext.pk_cost > MAX_BLOCK_WEIGHT as usize {
+        if ms.ext.pk_cost > MAX_BLOCK_WEIGHT {
             return Err(ScriptContextError::MaxWitnessScriptSizeExceeded);
         }
 
         match ms.node {
-            Terminal::PkK(ref pk) => {
-                if pk.is_uncompressed() {
-                    return Err(ScriptContextError::UncompressedKeysNotAllowed);
-                }
-                Ok(())
-            }
+            Terminal::PkK(ref pk) => Self::check_pk(pk),
             Terminal::Multi(..) => Err(ScriptContextError::TaprootMultiDisabled),
             Terminal::Ext(ref e) => {
                 e.tap_ctx_checks()?;
@@ -687,15 +712,13 @@ impl ScriptContext for Tap {
         // own unique signature. That is, there is no way to re-use signatures from one CHECKSIG
         // to another checksig. In other words, for each successfully executed checksig
         // will have it's corresponding 64 bytes signature.
-        // sigops budget = witness_script.len() + witness.size() + 50
-        // Each signature will cover it's own cost(64 > 50) and thus will will never exceed the budget
-        if let (Some(s), Some(h)) = (
-            ms.ext.exec_stack_elem_count_sat,
-            ms.ext.stack_elem_count_sat,
-        ) {
-            if s + h > MAX_STACK_SIZE {
+        // sigops budget = witness_script.len() + witness.size() + VALIDATION_WEIGHT_OFFSET
+        // Each signature will cover it's own cost(64 > VALIDATION_WEIGHT_PER_SIGOP_PASSED) and
+        // thus will will never exceed the budget
+        if let Some(size) = ms.max_stack_size_during_execution() {
+            if size > MAX_STACK_SIZE {
                 return Err(ScriptContextError::StackSizeLimitExceeded {
-                    actual: s + h,
+                    actual: size,
                     limit: MAX_STACK_SIZE,
                 });
             }
@@ -731,6 +754,14 @@ impl ScriptContext for Tap {
         33
     }
 
+    fn check_pk<Pk: MiniscriptKey>(pk: &Pk) -> Result<(), ScriptContextError> {
+        if pk.is_uncompressed() {
+            Err(ScriptContextError::UncompressedKeysNotAllowed)
+        } else {
+            Ok(())
+        }
+    }
+
     fn name_str() -> &'static str {
         "TapscriptCtx"
     }
@@ -768,23 +799,13 @@ impl ScriptContext for BareCtx {
             )));
         }
         match ms.node {
-            Terminal::PkK(ref key) if key.is_x_only_key() => {
-                return Err(ScriptContextError::XOnlyKeysNotAllowed(
-                    key.to_string(),
-                    Self::name_str(),
-                ))
-            }
+            Terminal::PkK(ref key) => Self::check_pk(key),
             Terminal::Multi(_k, ref pks) => {
                 if pks.len() > MAX_PUBKEYS_PER_MULTISIG {
                     return Err(ScriptContextError::CheckMultiSigLimitExceeded);
                 }
                 for pk in pks.iter() {
-                    if pk.is_x_only_key() {
-                        return Err(ScriptContextError::XOnlyKeysNotAllowed(
-                            pk.to_string(),
-                            Self::name_str(),
-                        ));
-                    }
+                    Self::check_pk(pk)?;
                 }
                 Ok(())
             }
@@ -834,6 +855,17 @@ impl ScriptContext for BareCtx {
         }
     }
 
+    fn check_pk<Pk: MiniscriptKey>(pk: &Pk) -> Result<(), ScriptContextError> {
+        if pk.is_x_only_key() {
+            Err(ScriptContextError::XOnlyKeysNotAllowed(
+                pk.to_string(),
+                Self::name_str(),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
     fn name_str() -> &'static str {
         "BareCtx"
     }
@@ -892,6 +924,10 @@ impl ScriptContext for NoChecks {
         panic!("Tried to compute a pk len bound on a no-checks ecdsa miniscript")
     }
 
+    fn check_pk<Pk: MiniscriptKey>(_pk: &Pk) -> Result<(), ScriptContextError> {
+        Ok(())
+    }
+
     fn name_str() -> &'static str {
         // Internally used code
         "NochecksEcdsa"
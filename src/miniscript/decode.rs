@@ -26,7 +26,7 @@ use elements::hashes::{hash160, ripemd160, sha256, Hash};
 use crate::elements::{LockTime, PackedLockTime, Sequence};
 use crate::extensions::ParseableExt;
 use crate::miniscript::lex::{Token as Tk, TokenIter};
-use crate::miniscript::limits::{MAX_BLOCK_WEIGHT, MAX_PUBKEYS_PER_MULTISIG};
+use crate::miniscript::limits::{MAX_PUBKEYS_IN_CHECKSIGADD, MAX_PUBKEYS_PER_MULTISIG};
 use crate::miniscript::types::extra_props::ExtData;
 use crate::miniscript::types::{Property, Type};
 use crate::miniscript::ScriptContext;
@@ -500,8 +500,8 @@ pub fn parse<Ctx: ScriptContext, Ext: ParseableExt>(
                     // MultiA
                     Tk::NumEqual, Tk::Num(k) => {
                         // Check size before allocating keys
-                        if k > (MAX_BLOCK_WEIGHT/32) as u32 {
-                            return Err(Error::MultiATooManyKeys((MAX_BLOCK_WEIGHT/32) as u32))
+                        if k > MAX_PUBKEYS_IN_CHECKSIGADD as u32 {
+                            return Err(Error::MultiATooManyKeys(MAX_PUBKEYS_IN_CHECKSIGADD as u32))
                         }
                         let mut keys = Vec::with_capacity(k as usize); // atleast k capacity
                         while tokens.peek() == Some(&Tk::CheckSigAdd) {
@@ -0,0 +1,708 @@
+// Miniscript
+// Written in 2023 by
+//     The Elements Miniscript developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Execution Stack Size
+//!
+//! Exact maximum of the stack and altstack sizes reached while executing
+//! the satisfactions of a Miniscript, overall or restricted to the
+//! satisfactions a satisfier can make.
+//!
+
+use std::cmp;
+
+use elements::taproot::{LeafVersion, TapLeafHash};
+
+use crate::extensions::ParseableExt;
+use crate::miniscript::decode::Terminal;
+use crate::miniscript::satisfy::{Satisfier, TimelockedSatisfaction, Witness};
+use crate::{Extension, Miniscript, MiniscriptKey, ScriptContext, ToPublicKey};
+
+// Stack usage of a fragment: the number of witness elements it consumes
+// and the peak number of elements on the stack and altstack during its
+// execution, both counted from the stack height below its witness. For
+// `W` fragments the peak includes the element they take from the top.
+// Both are maxima over the possible (dis)satisfactions: the values of
+// different fragments are independent, so combining them is exact.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Usage {
+    witness: usize,
+    peak: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct StackUsage {
+    sat: Option<Usage>,
+    dissat: Option<Usage>,
+}
+
+fn usage(witness: usize, peak: usize) -> Option<Usage> {
+    Some(Usage { witness, peak })
+}
+
+fn max(a: Option<Usage>, b: Option<Usage>) -> Option<Usage> {
+    match (a, b) {
+        (Some(a), Some(b)) => usage(cmp::max(a.witness, b.witness), cmp::max(a.peak, b.peak)),
+        (a, None) => a,
+        (None, b) => b,
+    }
+}
+
+// `first` is executed on top of the witness of `second`, leaving at most
+// one element which `second` consumes if it is a `W` fragment.
+fn seq(first: Option<Usage>, second: Option<Usage>) -> Option<Usage> {
+    let (first, second) = (first?, second?);
+    usage(
+        first.witness + second.witness,
+        cmp::max(second.witness + first.peak, second.peak),
+    )
+}
+
+// The branch of an `IF` selected by an additional witness element
+fn selected(branch: Option<Usage>) -> Option<Usage> {
+    let branch = branch?;
+    usage(
+        branch.witness + 1,
+        cmp::max(branch.witness + 1, branch.peak),
+    )
+}
+
+// With `X1 X2 ADD .. Xn ADD <k> EQUAL` each sub is executed on top of the
+// witnesses of the following ones. Returns the usage of the executions with
+// exactly `sats` satisfied subs.
+fn thresh(subs: &[StackUsage], sats: usize) -> Option<Usage> {
+    let n = subs.len();
+    // suffix[i][s]: max witness size of the subs from i with s satisfied
+    let mut suffix = vec![vec![None; n + 2]; n + 1];
+    suffix[n][0] = Some(0);
+    for i in (0..n).rev() {
+        for s in 0..=n - i {
+            let dissat = subs[i]
+                .dissat
+                .and_then(|u| suffix[i + 1][s].map(|w: usize| w + u.witness));
+            let sat = if s == 0 {
+                None
+            } else {
+                subs[i]
+                    .sat
+                    .and_then(|u| suffix[i + 1][s - 1].map(|w: usize| w + u.witness))
+            };
+            suffix[i][s] = cmp::max(dissat, sat);
+        }
+    }
+    // prefix[i][s]: whether the subs before i can have s satisfied
+    let mut prefix = vec![vec![false; n + 2]; n + 1];
+    prefix[0][0] = true;
+    for i in 0..n {
+        for s in 0..=i {
+            if prefix[i][s] {
+                prefix[i + 1][s] |= subs[i].dissat.is_some();
+                prefix[i + 1][s + 1] |= subs[i].sat.is_some();
+            }
+        }
+    }
+
+    let witness = suffix[0].get(sats).cloned().flatten()?;
+    let mut peak = 2; // <k> on top of the sum
+    for (i, sub) in subs.iter().enumerate() {
+        for &(u, is_sat) in &[(sub.sat, 1), (sub.dissat, 0)] {
+            let u = match u {
+                Some(u) => u,
+                None => continue,
+            };
+            // Witness sizes of the following subs, by their satisfactions
+            for (after, w) in suffix[i + 1].iter().enumerate().take(n - i) {
+                // The subs before i must have the remaining satisfactions
+                let feasible = sats
+                    .checked_sub(after + is_sat)
+                    .map_or(false, |before| before <= i && prefix[i][before]);
+                if let (true, Some(w)) = (feasible, *w) {
+                    peak = cmp::max(peak, w + u.peak);
+                }
+            }
+        }
+    }
+    usage(witness, peak)
+}
+
+// Whether the (dis)satisfactions of leaf fragments can be made, so that
+// only they are taken into account
+pub(super) type Available<'a, Pk, Ctx, Ext> = &'a dyn Fn(&Terminal<Pk, Ctx, Ext>) -> bool;
+
+impl<Pk: MiniscriptKey, Ctx: ScriptContext, Ext: Extension> Miniscript<Pk, Ctx, Ext> {
+    fn stack_usage(&self, available: Available<'_, Pk, Ctx, Ext>) -> StackUsage {
+        let (sat, dissat) = match self.node {
+            Terminal::True => (usage(0, 1), None),
+            Terminal::False => (None, usage(0, 1)),
+            // <sig> <pk>
+            Terminal::PkK(..) => (usage(1, 2), usage(1, 2)),
+            // <sig> <pk> DUP HASH160 <hash> EQUALVERIFY
+            Terminal::PkH(..) | Terminal::RawPkH(..) => (usage(2, 4), usage(2, 4)),
+            Terminal::After(..) | Terminal::Older(..) => (usage(0, 1), None),
+            // <preimage> SIZE <32> EQUALVERIFY
+            Terminal::Sha256(..)
+            | Terminal::Hash256(..)
+            | Terminal::Ripemd160(..)
+            | Terminal::Hash160(..) => (usage(1, 3), usage(1, 3)),
+            // 0 <sig>.. <k> <pk>.. <n> CHECKMULTISIG
+            Terminal::Multi(k, ref pks) => {
+                let u = usage(k + 1, k + 1 + pks.len() + 2);
+                (u, u)
+            }
+            // <sig>.. <pk> CHECKSIG <pk> CHECKSIGADD ..
+            Terminal::MultiA(_, ref pks) => {
+                let u = usage(pks.len(), pks.len() + 1);
+                (u, u)
+            }
+            Terminal::Alt(ref sub) | Terminal::Swap(ref sub) => {
+                let sub = sub.stack_usage(available);
+                let cast = |u: Option<Usage>| {
+                    u.map(|u| Usage {
+                        peak: u.peak + 1,
+                        ..u
+                    })
+                };
+                (cast(sub.sat), cast(sub.dissat))
+            }
+            Terminal::Check(ref sub) | Terminal::ZeroNotEqual(ref sub) => {
+                let sub = sub.stack_usage(available);
+                (sub.sat, sub.dissat)
+            }
+            Terminal::Verify(ref sub) => (sub.stack_usage(available).sat, None),
+            // DUP IF [X] ENDIF
+            Terminal::DupIf(ref sub) => (
+                sub.stack_usage(available)
+                    .sat
+                    .and_then(|u| usage(u.witness + 1, cmp::max(u.witness + 2, u.peak + 1))),
+                usage(1, 2),
+            ),
+            // SIZE 0NOTEQUAL IF [X] ENDIF
+            Terminal::NonZero(ref sub) => (
+                sub.stack_usage(available)
+                    .sat
+                    .and_then(|u| usage(u.witness, cmp::max(u.witness + 1, u.peak))),
+                usage(1, 2),
+            ),
+            Terminal::AndV(ref l, ref r) => {
+                let (l, r) = (l.stack_usage(available), r.stack_usage(available));
+                (seq(l.sat, r.sat), None)
+            }
+            Terminal::AndB(ref l, ref r) => {
+                let (l, r) = (l.stack_usage(available), r.stack_usage(available));
+                (seq(l.sat, r.sat), seq(l.dissat, r.dissat))
+            }
+            Terminal::AndOr(ref a, ref b, ref c) => {
+                let (a, b, c) = (
+                    a.stack_usage(available),
+                    b.stack_usage(available),
+                    c.stack_usage(available),
+                );
+                (
+                    max(seq(a.sat, b.sat), seq(a.dissat, c.sat)),
+                    seq(a.dissat, c.dissat),
+                )
+            }
+            Terminal::OrB(ref l, ref r) => {
+                let (l, r) = (l.stack_usage(available), r.stack_usage(available));
+                (
+                    max(seq(l.sat, r.dissat), seq(l.dissat, r.sat)),
+                    seq(l.dissat, r.dissat),
+                )
+            }
+            // [X] IFDUP NOTIF [Z] ENDIF
+            Terminal::OrD(ref l, ref r) => {
+                let (l, r) = (l.stack_usage(available), r.stack_usage(available));
+                let dup = l.sat.map(|u| Usage {
+                    peak: cmp::max(u.peak, 2),
+                    ..u
+                });
+                (max(dup, seq(l.dissat, r.sat)), seq(l.dissat, r.dissat))
+            }
+            Terminal::OrC(ref l, ref r) => {
+                let (l, r) = (l.stack_usage(available), r.stack_usage(available));
+                (max(l.sat, seq(l.dissat, r.sat)), None)
+            }
+            Terminal::OrI(ref l, ref r) => {
+                let (l, r) = (l.stack_usage(available), r.stack_usage(available));
+                (
+                    max(selected(l.sat), selected(r.sat)),
+                    max(selected(l.dissat), selected(r.dissat)),
+                )
+            }
+            Terminal::Thresh(k, ref subs) => {
+                let subs: Vec<_> = subs.iter().map(|sub| sub.stack_usage(available)).collect();
+                (thresh(&subs, k), thresh(&subs, 0))
+            }
+            // Extensions only tell the elements they push on top of their
+            // witness
+            Terminal::Ext(ref e) => {
+                let ext = e.extra_prop();
+                (
+                    ext.stack_elem_count_sat
+                        .and_then(|w| usage(w, w + ext.exec_stack_elem_count_sat?)),
+                    ext.stack_elem_count_dissat
+                        .and_then(|w| usage(w, w + ext.exec_stack_elem_count_dissat?)),
+                )
+            }
+        };
+        // Leaves whose satisfaction cannot be made are only dissatisfied
+        let sat = match self.node {
+            Terminal::PkK(..)
+            | Terminal::PkH(..)
+            | Terminal::RawPkH(..)
+            | Terminal::After(..)
+            | Terminal::Older(..)
+            | Terminal::Sha256(..)
+            | Terminal::Hash256(..)
+            | Terminal::Ripemd160(..)
+            | Terminal::Hash160(..)
+            | Terminal::Multi(..)
+            | Terminal::MultiA(..)
+            | Terminal::Ext(..) => sat.filter(|_| available(&self.node)),
+            _ => sat,
+        };
+        StackUsage { sat, dissat }
+    }
+
+    /// Maximum number of elements on the stack and altstack together while
+    /// executing a satisfaction of this Miniscript, including the witness
+    /// elements, or `None` if it cannot be satisfied.
+    ///
+    /// This is the largest size reached by any satisfaction, accounting for
+    /// the witness elements consumed before the largest size is reached.
+    /// The execution of extensions is estimated from their
+    /// [`ExtData`][crate::miniscript::types::extra_props::ExtData].
+    pub fn max_stack_size_during_execution(&self) -> Option<usize> {
+        self.stack_usage(&|_| true).sat.map(|u| u.peak)
+    }
+}
+
+impl<Pk, Ctx, Ext> Miniscript<Pk, Ctx, Ext>
+where
+    Pk: MiniscriptKey + ToPublicKey,
+    Ctx: ScriptContext,
+    Ext: ParseableExt,
+{
+    /// Maximum number of elements on the stack and altstack together while
+    /// executing the satisfactions of this Miniscript which only use the
+    /// signatures, preimages, timelocks and extension satisfactions
+    /// available from `satisfier`, or `None` if there is none.
+    ///
+    /// This is the size for the spend paths open to the satisfier, e.g.
+    /// with the signatures of some of the keys only, which may be well
+    /// below [`Miniscript::max_stack_size_during_execution`].
+    pub fn max_stack_size_during_satisfaction<S: Satisfier<Pk>>(
+        &self,
+        satisfier: S,
+    ) -> Option<usize> {
+        self.satisfaction_peak(satisfier, &|_| false)
+    }
+
+    // Like `max_stack_size_during_satisfaction`, also taking the leaves
+    // `assumed` as available
+    pub(super) fn satisfaction_peak<S: Satisfier<Pk>>(
+        &self,
+        satisfier: S,
+        assumed: Available<'_, Pk, Ctx, Ext>,
+    ) -> Option<usize> {
+        let leaf_hash = TapLeafHash::from_script(&self.encode(), LeafVersion::default());
+        let available = |term: &Terminal<Pk, Ctx, Ext>| {
+            assumed(term)
+                || match TimelockedSatisfaction::satisfy(term, &satisfier, false, &leaf_hash) {
+                    Ok(TimelockedSatisfaction {
+                        stack: Witness::Stack(..),
+                        ..
+                    }) => true,
+                    _ => false,
+                }
+        };
+        self.stack_usage(&available).sat.map(|u| u.peak)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp;
+    use std::str::FromStr;
+
+    use elements::hashes::{hash160, ripemd160, sha256, sha256d, Hash};
+    use elements::opcodes::all::*;
+    use elements::script::Instruction;
+    use elements::secp256k1_zkp::{self, schnorr};
+    use elements::taproot::TapLeafHash;
+    use elements::{LockTime, Script, Sequence};
+
+    use crate::interpreter::Interpreter;
+    use crate::miniscript::satisfy::{ElementsSig, Preimage32};
+    use crate::miniscript::{Segwitv0, Tap};
+    use crate::{Descriptor, Miniscript, NoExt, Satisfier, ToPublicKey};
+
+    fn tap(s: &str) -> Option<usize> {
+        Miniscript::<String, Tap, NoExt>::from_str(s)
+            .unwrap()
+            .max_stack_size_during_execution()
+    }
+
+    fn wsh(s: &str) -> Option<usize> {
+        Miniscript::<String, Segwitv0, NoExt>::from_str(s)
+            .unwrap()
+            .max_stack_size_during_execution()
+    }
+
+    #[test]
+    fn exec_stack_size() {
+        // <sigB> <sigA> <A> CHECKSIGVERIFY <B> CHECKSIG
+        assert_eq!(tap("and_v(v:pk(A),pk(B))"), Some(3));
+        // <sigC> <sigB> <sigA> <A> CHECKSIG <B> CHECKSIGADD ..
+        assert_eq!(tap("multi_a(2,A,B,C)"), Some(4));
+        // 0 <sigA> <sigB> 2 <A> <B> <C> 3 CHECKMULTISIG
+        assert_eq!(wsh("multi(2,A,B,C)"), Some(8));
+        // <sigB> 0 <A> CHECKSIG IFDUP NOTIF <B> CHECKSIG ENDIF
+        assert_eq!(wsh("or_d(pk(A),pk(B))"), Some(3));
+        // <sigC> <sigB> <sigA> <A> CHECKSIG SWAP <B> CHECKSIG ADD ..
+        assert_eq!(tap("thresh(2,pk(A),s:pk(B),s:pk(C))"), Some(4));
+        // The selector is consumed before the preimage is checked
+        assert_eq!(
+            wsh("or_i(and_v(v:pk(A),sha256(0000000000000000000000000000000000000000000000000000000000000000)),pk(B))"),
+            Some(3)
+        );
+    }
+
+    #[test]
+    fn exec_stack_size_limit() {
+        // The witness elements are consumed as the execution goes, so that
+        // 999 keys fit in the 1000 elements limit
+        let keys: Vec<_> = (0..999).map(|i| format!("K{}", i)).collect();
+        let ms = format!("multi_a(1,{})", keys.join(","));
+        assert_eq!(tap(&ms), Some(1000));
+        let keys: Vec<_> = (0..999).map(|i| format!("K{}", i)).collect();
+        let ms = format!("and_v(v:pk(X),multi_a(1,{}))", keys.join(","));
+        assert!(Miniscript::<String, Tap, NoExt>::from_str(&ms).is_err());
+    }
+
+    // Signs with some keys and knows the preimage of `sha256(PREIMAGE)`; all
+    // timelocks are met
+    struct Signers {
+        keys: Vec<bitcoin::PublicKey>,
+        sig: ElementsSig,
+    }
+
+    const PREIMAGE: [u8; 32] = [1; 32];
+
+    impl Satisfier<bitcoin::PublicKey> for Signers {
+        fn lookup_ecdsa_sig(&self, pk: &bitcoin::PublicKey) -> Option<ElementsSig> {
+            Some(self.sig).filter(|_| self.keys.contains(pk))
+        }
+
+        fn lookup_tap_leaf_script_sig(
+            &self,
+            pk: &bitcoin::PublicKey,
+            _: &TapLeafHash,
+        ) -> Option<elements::SchnorrSig> {
+            Some(elements::SchnorrSig {
+                sig: schnorr::Signature::from_slice(&[1; 64]).unwrap(),
+                hash_ty: elements::SchnorrSigHashType::Default,
+            })
+            .filter(|_| self.keys.contains(pk))
+        }
+
+        fn lookup_raw_pkh_pk(&self, h: &hash160::Hash) -> Option<bitcoin::PublicKey> {
+            keys()
+                .into_iter()
+                .find(|pk| pk.to_pubkeyhash(crate::SigType::Ecdsa) == *h)
+        }
+
+        fn lookup_raw_pkh_ecdsa_sig(
+            &self,
+            h: &hash160::Hash,
+        ) -> Option<(bitcoin::PublicKey, ElementsSig)> {
+            let pk = self.lookup_raw_pkh_pk(h)?;
+            Some((pk, self.lookup_ecdsa_sig(&pk)?))
+        }
+
+        fn lookup_sha256(&self, h: &sha256::Hash) -> Option<Preimage32> {
+            Some(PREIMAGE).filter(|_| *h == sha256::Hash::hash(&PREIMAGE))
+        }
+
+        fn check_older(&self, _: Sequence) -> bool {
+            true
+        }
+
+        fn check_after(&self, _: LockTime) -> bool {
+            true
+        }
+    }
+
+    fn read_num(v: &[u8]) -> i64 {
+        let mut ret = 0i64;
+        for (i, byte) in v.iter().enumerate() {
+            ret |= i64::from(*byte) << (8 * i);
+        }
+        match v.last() {
+            Some(last) if last & 0x80 != 0 => -(ret & !(0x80i64 << (8 * (v.len() - 1)))),
+            _ => ret,
+        }
+    }
+
+    fn write_num(n: i64) -> Vec<u8> {
+        assert!(n >= 0);
+        let mut ret = vec![];
+        let mut n = n;
+        while n > 0 {
+            ret.push((n & 0xff) as u8);
+            n >>= 8;
+        }
+        if ret.last().map_or(false, |last| last & 0x80 != 0) {
+            ret.push(0);
+        }
+        ret
+    }
+
+    fn truthy(v: &[u8]) -> bool {
+        read_num(v) != 0
+    }
+
+    // Executes `script` on `witness`, taking non-empty signatures to be
+    // valid, and returns the largest size of the stack and altstack
+    // together. Panics unless the witness satisfies the script.
+    fn execute(script: &Script, witness: &[Vec<u8>]) -> usize {
+        let mut stack = witness.to_vec();
+        let mut alt = vec![];
+        // The conditions of the enclosing IFs, `None` in skipped branches
+        let mut conds: Vec<Option<bool>> = vec![];
+        let mut peak = stack.len();
+        for ins in script.instructions() {
+            let executing = conds.iter().all(|c| *c == Some(true));
+            match ins.unwrap() {
+                Instruction::Op(op) if op == OP_IF || op == OP_NOTIF => {
+                    let cond = if executing {
+                        Some(truthy(&stack.pop().unwrap()) == (op == OP_IF))
+                    } else {
+                        None
+                    };
+                    conds.push(cond);
+                }
+                Instruction::Op(OP_ELSE) => {
+                    let cond = conds.last_mut().unwrap();
+                    *cond = cond.map(|c| !c);
+                }
+                Instruction::Op(OP_ENDIF) => {
+                    conds.pop().unwrap();
+                }
+                _ if !executing => {}
+                Instruction::PushBytes(bytes) => stack.push(bytes.to_vec()),
+                Instruction::Op(op) => {
+                    let code = op.into_u8();
+                    let mut pop = || stack.pop().unwrap();
+                    let push = match op {
+                        _ if code >= OP_PUSHNUM_1.into_u8() && code <= OP_PUSHNUM_16.into_u8() => {
+                            Some(write_num(i64::from(code - OP_PUSHNUM_1.into_u8() + 1)))
+                        }
+                        OP_CHECKSIG | OP_CHECKSIGVERIFY => {
+                            let (_pk, sig) = (pop(), pop());
+                            Some(write_num((!sig.is_empty()) as i64))
+                        }
+                        OP_CHECKSIGADD => {
+                            let (_pk, n, sig) = (pop(), pop(), pop());
+                            Some(write_num(read_num(&n) + (!sig.is_empty()) as i64))
+                        }
+                        OP_CHECKMULTISIG | OP_CHECKMULTISIGVERIFY => {
+                            let n = read_num(&pop());
+                            (0..n).for_each(|_| drop(pop()));
+                            let k = read_num(&pop());
+                            let sigs: Vec<_> = (0..k).map(|_| pop()).collect();
+                            assert!(pop().is_empty());
+                            Some(write_num(sigs.iter().all(|s| !s.is_empty()) as i64))
+                        }
+                        OP_DUP => {
+                            let top = pop();
+                            stack.push(top.clone());
+                            Some(top)
+                        }
+                        OP_IFDUP => {
+                            let top = pop();
+                            let dup = Some(top.clone()).filter(|t| truthy(t));
+                            stack.push(top);
+                            dup
+                        }
+                        OP_SWAP => {
+                            let (a, b) = (pop(), pop());
+                            stack.push(a);
+                            Some(b)
+                        }
+                        OP_TOALTSTACK => {
+                            alt.push(pop());
+                            None
+                        }
+                        OP_FROMALTSTACK => alt.pop(),
+                        OP_SIZE => {
+                            let top = pop();
+                            let size = write_num(top.len() as i64);
+                            stack.push(top);
+                            Some(size)
+                        }
+                        OP_EQUAL | OP_EQUALVERIFY => {
+                            let (a, b) = (pop(), pop());
+                            Some(write_num((a == b) as i64))
+                        }
+                        OP_NUMEQUAL | OP_NUMEQUALVERIFY => {
+                            let (a, b) = (pop(), pop());
+                            Some(write_num((read_num(&a) == read_num(&b)) as i64))
+                        }
+                        OP_VERIFY => Some(pop()),
+                        OP_0NOTEQUAL => Some(write_num(truthy(&pop()) as i64)),
+                        OP_ADD => Some(write_num(read_num(&pop()) + read_num(&pop()))),
+                        OP_BOOLAND => Some(write_num((truthy(&pop()) & truthy(&pop())) as i64)),
+                        OP_BOOLOR => Some(write_num((truthy(&pop()) | truthy(&pop())) as i64)),
+                        OP_SHA256 => Some(sha256::Hash::hash(&pop()).to_vec()),
+                        OP_HASH256 => Some(sha256d::Hash::hash(&pop()).to_vec()),
+                        OP_RIPEMD160 => Some(ripemd160::Hash::hash(&pop()).to_vec()),
+                        OP_HASH160 => Some(hash160::Hash::hash(&pop()).to_vec()),
+                        OP_CLTV | OP_CSV => None,
+                        _ => panic!("unexpected opcode {}", op),
+                    };
+                    stack.extend(push);
+                    peak = cmp::max(peak, stack.len() + alt.len());
+                    // The VERIFY versions check their result
+                    let verify = [
+                        OP_CHECKSIGVERIFY,
+                        OP_CHECKMULTISIGVERIFY,
+                        OP_EQUALVERIFY,
+                        OP_NUMEQUALVERIFY,
+                        OP_VERIFY,
+                    ];
+                    if verify.contains(&op) {
+                        assert!(truthy(&stack.pop().unwrap()), "{} failed", op);
+                    }
+                }
+            }
+            peak = cmp::max(peak, stack.len() + alt.len());
+        }
+        assert_eq!(stack.len(), 1);
+        assert!(truthy(&stack[0]));
+        peak
+    }
+
+    fn keys() -> Vec<bitcoin::PublicKey> {
+        let secp = secp256k1_zkp::Secp256k1::signing_only();
+        (1..=4)
+            .map(|i| {
+                let sk = secp256k1_zkp::SecretKey::from_slice(&[i; 32]).unwrap();
+                bitcoin::PublicKey::new(secp256k1_zkp::PublicKey::from_secret_key(&secp, &sk))
+            })
+            .collect()
+    }
+
+    fn signers(keys: Vec<bitcoin::PublicKey>) -> Signers {
+        let secp = secp256k1_zkp::Secp256k1::signing_only();
+        let sk = secp256k1_zkp::SecretKey::from_slice(&[1; 32]).unwrap();
+        let msg = secp256k1_zkp::Message::from_slice(&[2; 32]).unwrap();
+        Signers {
+            keys,
+            sig: (secp.sign_ecdsa(&msg, &sk), elements::EcdsaSigHashType::All),
+        }
+    }
+
+    // For each listed set of signers, whose satisfaction must be unique,
+    // checks that executing the satisfaction peaks at the size computed for
+    // the signers, and that the interpreter accepts it
+    fn check_wsh(ms: &str, signer_sets: &[&[usize]]) {
+        let k = keys();
+        let hash = sha256::Hash::hash(&PREIMAGE);
+        let ms = ms
+            .replace("H", &hash.to_string())
+            .replace("A", &k[0].to_string())
+            .replace("B", &k[1].to_string())
+            .replace("C", &k[2].to_string())
+            .replace("D", &k[3].to_string());
+        let ms = Miniscript::<bitcoin::PublicKey, Segwitv0, NoExt>::from_str(&ms).unwrap();
+        let max = ms.max_stack_size_during_execution().unwrap();
+        let spk = Descriptor::<_, NoExt>::new_wsh(ms.clone())
+            .unwrap()
+            .script_pubkey();
+        for set in signer_sets {
+            let signers = signers(set.iter().map(|&i| k[i]).collect());
+            let witness = ms.satisfy(&signers).unwrap();
+            let peak = execute(&ms.encode(), &witness);
+            assert_eq!(
+                ms.max_stack_size_during_satisfaction(&signers),
+                Some(peak),
+                "{} with signers {:?}",
+                ms,
+                set
+            );
+            assert!(peak <= max);
+
+            let mut full = witness.clone();
+            full.push(ms.encode().into_bytes());
+            let script_sig = Script::new();
+            let interpreter = Interpreter::from_txdata(
+                &spk,
+                &script_sig,
+                &full,
+                Sequence::from_height(1000),
+                LockTime::from_consensus(500_000),
+            )
+            .unwrap();
+            assert!(interpreter.iter_assume_sigs().all(|res| res.is_ok()));
+        }
+    }
+
+    #[test]
+    fn exec_stack_size_per_spend_path() {
+        check_wsh("or_d(pk(A),pk(B))", &[&[0], &[1]]);
+        check_wsh("or_b(pk(A),a:pk(B))", &[&[0], &[1]]);
+        check_wsh("multi(2,A,B,C)", &[&[0, 1], &[1, 2]]);
+        check_wsh(
+            "thresh(2,pk(A),s:pk(B),s:pk(C))",
+            &[&[0, 1], &[0, 2], &[1, 2]],
+        );
+        check_wsh("andor(pk(A),older(144),multi(2,B,C,D))", &[&[0], &[1, 3]]);
+        check_wsh(
+            "or_i(and_v(v:pk(A),sha256(H)),and_v(v:pk(B),after(100)))",
+            &[&[0], &[1]],
+        );
+        check_wsh(
+            "thresh(2,pk(A),a:pkh(B),s:pk(C))",
+            &[&[0, 1], &[0, 2], &[1, 2]],
+        );
+
+        // The cheaper spend paths are well below the maximum
+        let k = keys();
+        let ms = Miniscript::<bitcoin::PublicKey, Tap, NoExt>::from_str(&format!(
+            "or_d(pk({}),multi_a(3,{},{},{}))",
+            k[0], k[1], k[2], k[3]
+        ))
+        .unwrap();
+        let signers = signers(vec![k[0]]);
+        let witness = ms.satisfy(&signers).unwrap();
+        let peak = execute(&ms.encode(), &witness);
+        assert_eq!(peak, 2);
+        assert_eq!(ms.max_stack_size_during_satisfaction(&signers), Some(peak));
+        assert_eq!(ms.max_stack_size_during_execution(), Some(5));
+        let signers = Signers {
+            keys: k[1..].to_vec(),
+            ..signers
+        };
+        let witness = ms.satisfy(&signers).unwrap();
+        assert_eq!(execute(&ms.encode(), &witness), 5);
+        assert_eq!(ms.max_stack_size_during_satisfaction(&signers), Some(5));
+        assert_eq!(
+            ms.max_stack_size_during_satisfaction(Signers {
+                keys: vec![],
+                ..signers
+            }),
+            None
+        );
+    }
+}
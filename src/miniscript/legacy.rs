@@ -0,0 +1,120 @@
+//! Legacy fragment spellings
+//!
+//! The parser accepts some fragment names from earlier versions of miniscript,
+//! which are still found in old wallet exports, e.g. from Liquid wallets:
+//!
+//! - `thresh_m(k,K1,..,Kn)` for `multi(k,K1,..,Kn)`
+//! - `c:pk(K)` for `pk(K)`, i.e. `c:pk_k(K)`, from when `pk` was the name of
+//!   `pk_k`. This also applies with other wrappers, e.g. `vc:pk(K)`.
+//!
+//! Parsed miniscripts are always displayed with the current spellings. Use
+//! [`legacy_aliases`] to find which deprecated spellings a string uses.
+
+use std::fmt;
+
+/// A deprecated fragment spelling found in a miniscript or descriptor string
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LegacyAlias {
+    /// The fragment name, with its wrappers, as found in the string
+    pub found: String,
+    /// The current spelling of the fragment
+    pub replacement: String,
+}
+
+impl fmt::Display for LegacyAlias {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "`{}` is deprecated, use `{}`",
+            self.found, self.replacement
+        )
+    }
+}
+
+/// The current spelling of the fragment `name`, if it is a legacy one
+fn replacement(name: &str) -> Option<String> {
+    let (wrap, frag) = match name.find(':') {
+        Some(i) => (&name[..i], &name[i + 1..]),
+        None => ("", name),
+    };
+    match frag {
+        "thresh_m" if wrap.is_empty() => Some(String::from("multi")),
+        "thresh_m" => Some(format!("{}:multi", wrap)),
+        "pk" if wrap == "c" => Some(String::from("pk")),
+        "pk" if wrap.ends_with('c') => Some(format!("{}:pk", &wrap[..wrap.len() - 1])),
+        _ => None,
+    }
+}
+
+/// The deprecated fragment spellings used in the miniscript or descriptor
+/// string `s`, in the order they appear.
+///
+/// This only looks at the fragment names, so it does not check that `s`
+/// parses.
+pub fn legacy_aliases(s: &str) -> Vec<LegacyAlias> {
+    let mut aliases = vec![];
+    let mut start = 0;
+    for (i, ch) in s.char_indices() {
+        match ch {
+            '(' => {
+                let name = &s[start..i];
+                if let Some(replacement) = replacement(name) {
+                    aliases.push(LegacyAlias {
+                        found: name.to_owned(),
+                        replacement,
+                    });
+                }
+                start = i + 1;
+            }
+            ',' | ')' | '{' | '}' => start = i + 1,
+            _ => {}
+        }
+    }
+    aliases
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::{Descriptor, Miniscript, Segwitv0};
+
+    type Segwitv0Script = Miniscript<bitcoin::PublicKey, Segwitv0>;
+
+    const K1: &str = "028c28a97bf8298bc0d23d8c749452a32e694b65e30a9472a3954ab30fe5324caa";
+    const K2: &str = "03daed4f2be3a8bf278e70132fb0beb7522f570e144bf615c07e996d443dee8729";
+
+    #[test]
+    fn legacy_fragments() {
+        let legacy = format!("and_v(vc:pk({}),thresh_m(1,{},{}))", K1, K1, K2);
+        let ms = Segwitv0Script::from_str_insane(&legacy).unwrap();
+        let current = format!("and_v(v:pk({}),multi(1,{},{}))", K1, K1, K2);
+        assert_eq!(ms.to_string(), current);
+        assert_eq!(ms, Segwitv0Script::from_str_insane(&current).unwrap());
+        assert_eq!(
+            legacy_aliases(&legacy),
+            vec![
+                LegacyAlias {
+                    found: String::from("vc:pk"),
+                    replacement: String::from("v:pk"),
+                },
+                LegacyAlias {
+                    found: String::from("thresh_m"),
+                    replacement: String::from("multi"),
+                },
+            ]
+        );
+        assert!(legacy_aliases(&current).is_empty());
+
+        let desc = format!("elwsh(or_d(c:pk({}),thresh_m(1,{})))", K1, K2);
+        let parsed = Descriptor::<bitcoin::PublicKey>::from_str(&desc).unwrap();
+        assert_eq!(
+            parsed.to_string().split('#').next().unwrap(),
+            format!("elwsh(or_d(pk({}),multi(1,{})))", K1, K2)
+        );
+        let aliases = legacy_aliases(&desc);
+        assert_eq!(aliases.len(), 2);
+        assert_eq!(aliases[0].to_string(), "`c:pk` is deprecated, use `pk`");
+    }
+}
@@ -1,6 +1,9 @@
-//! Miscellaneous constraints imposed by Bitcoin.
-//! These constraints can be either Consensus or Policy (standardness) rules, for either Segwitv0
-//! or Legacy scripts.
+//! Miscellaneous constraints imposed by Bitcoin and inherited by Elements.
+//! These constraints can be either Consensus or Policy (standardness) rules, for either Segwitv0,
+//! Legacy or Tapscript scripts.
+//!
+//! The values are the same for Elements as for Bitcoin unless noted otherwise; Elements only
+//! increases the size of transaction outputs, see [`crate::confidential`].
 
 /// Maximum operations per script
 // https://github.com/bitcoin/bitcoin/blob/875e1ccc9fe01e026e564dfd39a64d9a4b332a89/src/script/script.h#L26
@@ -30,7 +33,23 @@ pub const MAX_STANDARD_P2WSH_STACK_ITEM_SIZE: usize = 80;
 pub const MAX_STACK_SIZE: usize = 1000;
 /** The maximum allowed weight for a block, see BIP 141 (network rule) */
 pub const MAX_BLOCK_WEIGHT: usize = 4000000;
+/// Weight units per byte of non-witness data, see BIP 141
+// https://github.com/bitcoin/bitcoin/blob/3af495d6972379b07530a5fcc2665aa626d01621/src/consensus/consensus.h#L21
+pub const WITNESS_SCALE_FACTOR: usize = 4;
+
+/// Sigops budget every tapscript spend starts with, in addition to the size of its witness
+// https://github.com/bitcoin/bips/blob/master/bip-0342.mediawiki#resource-limits
+pub const VALIDATION_WEIGHT_OFFSET: usize = 50;
+/// Sigops budget consumed by every executed signature check with a non-empty signature
+// https://github.com/bitcoin/bips/blob/master/bip-0342.mediawiki#resource-limits
+pub const VALIDATION_WEIGHT_PER_SIGOP_PASSED: usize = 50;
+/// Maximum keys in a tapscript `multi_a`: every key costs at least 32 bytes of script
+pub const MAX_PUBKEYS_IN_CHECKSIGADD: usize = MAX_BLOCK_WEIGHT / 32;
 
 /// Maximum pubkeys as arguments to CHECKMULTISIG
 // https://github.com/bitcoin/bitcoin/blob/6acda4b00b3fc1bfac02f5de590e1a5386cbc779/src/script/script.h#L30
 pub const MAX_PUBKEYS_PER_MULTISIG: usize = 20;
+/// Maximum pubkeys of a CHECKMULTISIG in a P2SH redeem script: the redeem
+/// script is pushed by the scriptSig, so it cannot exceed
+/// `MAX_SCRIPT_ELEMENT_SIZE` bytes, and 16 compressed keys already take 544
+pub const MAX_PUBKEYS_PER_P2SH_MULTISIG: usize = 15;
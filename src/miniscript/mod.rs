@@ -35,9 +35,12 @@ pub use self::context::{BareCtx, Legacy, Segwitv0, Tap};
 
 pub mod analyzable;
 pub mod astelem;
+pub mod async_satisfy;
 pub(crate) mod context;
 pub mod decode;
+mod exec_stack;
 pub mod iter;
+pub mod legacy;
 pub mod lex;
 pub mod limits;
 pub mod satisfy;
@@ -48,11 +51,12 @@ use std::sync::Arc;
 
 use self::lex::{lex, TokenIter};
 use self::types::Property;
-use crate::extensions::ParseableExt;
+use crate::extensions::{ParseableExt, TxEnv};
 pub use crate::miniscript::context::ScriptContext;
 use crate::miniscript::decode::Terminal;
 use crate::miniscript::types::extra_props::ExtData;
 use crate::miniscript::types::Type;
+use crate::policy::{Liftable, Semantic};
 use crate::{
     expression, Error, ExtTranslator, Extension, ForEachKey, MiniscriptKey, NoExt, ToPublicKey,
     TranslateExt, TranslatePk, Translator,
@@ -134,6 +138,27 @@ impl<Pk: MiniscriptKey, Ctx: ScriptContext, Ext: Extension> Miniscript<Pk, Ctx,
             phantom: PhantomData,
         })
     }
+
+    /// Create a Miniscript from its type information and extra data,
+    /// without type checking `node`, e.g. from components stored by an
+    /// earlier [`Miniscript::from_ast`].
+    ///
+    /// The components are trusted: if `ty` or `ext` are not those of `node`,
+    /// the analysis of the Miniscript is wrong, and satisfying or encoding it
+    /// may produce invalid scripts and witnesses. Only use this for
+    /// components from a trusted source.
+    pub fn from_components_unchecked(
+        node: Terminal<Pk, Ctx, Ext>,
+        ty: Type,
+        ext: ExtData,
+    ) -> Miniscript<Pk, Ctx, Ext> {
+        Miniscript {
+            node,
+            ty,
+            ext,
+            phantom: PhantomData,
+        }
+    }
 }
 
 impl<Pk: MiniscriptKey, Ctx: ScriptContext, Ext: Extension> fmt::Display
@@ -154,6 +179,69 @@ impl<Pk: MiniscriptKey, Ctx: ScriptContext, Ext: Extension> Miniscript<Pk, Ctx,
     pub fn as_inner(&self) -> &Terminal<Pk, Ctx, Ext> {
         &self.node
     }
+
+    /// Replaces the fragment at `path` with `replacement`.
+    ///
+    /// The path lists child indices from the root, as used by
+    /// [`Miniscript::get_nth_child`]; an empty path replaces the miniscript
+    /// itself. Every fragment along the path is type checked again, and the
+    /// result must be valid in the script context.
+    pub fn substitute(
+        &self,
+        path: &[usize],
+        replacement: Miniscript<Pk, Ctx, Ext>,
+    ) -> Result<Miniscript<Pk, Ctx, Ext>, Error> {
+        let ms = self.substitute_inner(path, replacement)?;
+        Ctx::check_global_validity(&ms)?;
+        Ok(ms)
+    }
+
+    // Recursion for `substitute`, without the context checks
+    fn substitute_inner(
+        &self,
+        path: &[usize],
+        replacement: Miniscript<Pk, Ctx, Ext>,
+    ) -> Result<Miniscript<Pk, Ctx, Ext>, Error> {
+        let (&n, rest) = match path.split_first() {
+            Some(split) => split,
+            None => return Ok(replacement),
+        };
+        let no_child = || Error::Unexpected(format!("{} has no child {}", self, n));
+        let child = self.get_nth_child(n).ok_or_else(no_child)?;
+        let child = Arc::new(child.substitute_inner(rest, replacement)?);
+
+        let node = match (n, &self.node) {
+            (0, Terminal::Alt(..)) => Terminal::Alt(child),
+            (0, Terminal::Swap(..)) => Terminal::Swap(child),
+            (0, Terminal::Check(..)) => Terminal::Check(child),
+            (0, Terminal::DupIf(..)) => Terminal::DupIf(child),
+            (0, Terminal::Verify(..)) => Terminal::Verify(child),
+            (0, Terminal::NonZero(..)) => Terminal::NonZero(child),
+            (0, Terminal::ZeroNotEqual(..)) => Terminal::ZeroNotEqual(child),
+            (0, Terminal::AndV(_, r)) => Terminal::AndV(child, Arc::clone(r)),
+            (0, Terminal::AndB(_, r)) => Terminal::AndB(child, Arc::clone(r)),
+            (0, Terminal::OrB(_, r)) => Terminal::OrB(child, Arc::clone(r)),
+            (0, Terminal::OrD(_, r)) => Terminal::OrD(child, Arc::clone(r)),
+            (0, Terminal::OrC(_, r)) => Terminal::OrC(child, Arc::clone(r)),
+            (0, Terminal::OrI(_, r)) => Terminal::OrI(child, Arc::clone(r)),
+            (1, Terminal::AndV(l, _)) => Terminal::AndV(Arc::clone(l), child),
+            (1, Terminal::AndB(l, _)) => Terminal::AndB(Arc::clone(l), child),
+            (1, Terminal::OrB(l, _)) => Terminal::OrB(Arc::clone(l), child),
+            (1, Terminal::OrD(l, _)) => Terminal::OrD(Arc::clone(l), child),
+            (1, Terminal::OrC(l, _)) => Terminal::OrC(Arc::clone(l), child),
+            (1, Terminal::OrI(l, _)) => Terminal::OrI(Arc::clone(l), child),
+            (0, Terminal::AndOr(_, b, c)) => Terminal::AndOr(child, Arc::clone(b), Arc::clone(c)),
+            (1, Terminal::AndOr(a, _, c)) => Terminal::AndOr(Arc::clone(a), child, Arc::clone(c)),
+            (2, Terminal::AndOr(a, b, _)) => Terminal::AndOr(Arc::clone(a), Arc::clone(b), child),
+            (n, Terminal::Thresh(k, subs)) => {
+                let mut subs = subs.clone();
+                subs[n] = child;
+                Terminal::Thresh(*k, subs)
+            }
+            _ => return Err(no_child()),
+        };
+        Miniscript::from_ast(node)
+    }
 }
 
 impl<Ctx, Ext> Miniscript<Ctx::Key, Ctx, Ext>
@@ -298,6 +386,25 @@ impl<Pk: MiniscriptKey, Ctx: ScriptContext, Ext: Extension> Miniscript<Pk, Ctx,
     pub fn max_satisfaction_size(&self) -> Result<usize, Error> {
         Ctx::max_satisfaction_size::<Pk, Ext>(self).ok_or(Error::ImpossibleSatisfaction)
     }
+
+    /// Whether this miniscript and `other` have the same spending conditions,
+    /// i.e. whether their [lifted][`Liftable::lift`] policies are equal after
+    /// normalization and sorting.
+    ///
+    /// The miniscripts may be for different script contexts, so that e.g. a
+    /// `multi` in a wsh descriptor can be checked against a `multi_a` of the
+    /// same keys in a tap leaf when migrating.
+    ///
+    /// # Errors
+    /// When either miniscript cannot be lifted.
+    pub fn semantic_eq<Ctx2, Ext2>(&self, other: &Miniscript<Pk, Ctx2, Ext2>) -> Result<bool, Error>
+    where
+        Ctx2: ScriptContext,
+        Ext2: Extension,
+    {
+        let semantic = |ms: Semantic<Pk>| ms.normalized().sorted();
+        Ok(semantic(self.lift()?) == semantic(other.lift()?))
+    }
 }
 
 impl<Pk: MiniscriptKey, Ctx: ScriptContext, Ext: Extension> ForEachKey<Pk>
@@ -430,7 +537,18 @@ impl_block_str!(
     {
         // This checks for invalid ASCII chars
         let top = expression::Tree::from_str(s)?;
-        let ms: Miniscript<Pk, Ctx, Ext> = expression::FromTree::from_tree(&top)?;
+        Miniscript::from_tree_ext(&top, ext)
+    }
+);
+
+impl_block_str!(
+    ;Ctx; ScriptContext,
+    Miniscript<Pk, Ctx, Ext>,
+    => Ext ; Extension,
+    // Like `from_str_ext`, for an already parsed expression tree
+    pub(crate) fn from_tree_ext(top: &expression::Tree<'_>, ext: &ExtParams,) -> Result<Miniscript<Pk, Ctx, Ext>, Error>
+    {
+        let ms: Miniscript<Pk, Ctx, Ext> = expression::FromTree::from_tree(top)?;
         ms.ext_check(ext)?;
 
         if ms.ty.corr.base != types::Base::B {
@@ -442,48 +560,140 @@ impl_block_str!(
 );
 
 impl<Pk: ToPublicKey, Ctx: ScriptContext, Ext: ParseableExt> Miniscript<Pk, Ctx, Ext> {
-    /// Attempt to produce non-malleable satisfying witness for the
-    /// witness script represented by the parse tree
-    pub fn satisfy<S: satisfy::Satisfier<Pk>>(&self, satisfier: S) -> Result<Vec<Vec<u8>>, Error> {
-        // Only satisfactions for default versions (0xc0) are allowed.
-        let leaf_hash = TapLeafHash::from_script(&self.encode(), LeafVersion::default());
-        match satisfy::Satisfaction::satisfy(&self.node, &satisfier, self.ty.mall.safe, &leaf_hash)
-            .stack
-        {
-            satisfy::Witness::Stack(stack) => {
-                Ctx::check_witness::<Pk, Ext>(&stack)?;
-                Ok(stack)
+    /// The indexes of the inputs and outputs of `env` whose explicit values
+    /// the extensions of this miniscript read, but which are confidential,
+    /// sorted and without duplicates
+    pub fn blinded_refs(&self, env: &TxEnv) -> (Vec<usize>, Vec<usize>) {
+        let (mut inputs, mut outputs) = (vec![], vec![]);
+        for ms in self.iter() {
+            if let Terminal::Ext(ref e) = ms.node {
+                e.blinded_refs(env, &mut inputs, &mut outputs);
             }
-            satisfy::Witness::Unavailable | satisfy::Witness::Impossible => {
-                Err(Error::CouldNotSatisfy)
+        }
+        inputs.sort_unstable();
+        inputs.dedup();
+        outputs.sort_unstable();
+        outputs.dedup();
+        (inputs, outputs)
+    }
+
+    /// The error for a failed satisfaction: [`Error::NeedsUnblinding`] if
+    /// the satisfier has a transaction whose confidential values are read
+    /// by extensions, and the satisfaction only fails for want of these
+    /// values, i.e. it would be possible if the extensions reading them
+    /// were satisfied. [`Error::CouldNotSatisfy`] otherwise.
+    fn satisfaction_error<S: satisfy::Satisfier<Pk>>(&self, satisfier: &S) -> Error {
+        let env = match (
+            satisfier.lookup_tx(),
+            satisfier.lookup_spent_utxos(),
+            satisfier.lookup_curr_inp(),
+        ) {
+            (Some(tx), Some(utxos), Some(idx)) => TxEnv::new(tx, utxos, idx),
+            _ => None,
+        };
+        let env = match env {
+            Some(env) => env,
+            None => return Error::CouldNotSatisfy,
+        };
+        let (inputs, outputs) = self.blinded_refs(&env);
+        if inputs.is_empty() && outputs.is_empty() {
+            return Error::CouldNotSatisfy;
+        }
+        let blinded = |term: &Terminal<Pk, Ctx, Ext>| match *term {
+            Terminal::Ext(ref e) => {
+                let (mut inputs, mut outputs) = (vec![], vec![]);
+                e.blinded_refs(&env, &mut inputs, &mut outputs);
+                !inputs.is_empty() || !outputs.is_empty()
             }
+            _ => false,
+        };
+        match self.satisfaction_peak(satisfier, &blinded) {
+            Some(..) => Error::NeedsUnblinding { inputs, outputs },
+            None => Error::CouldNotSatisfy,
         }
     }
 
+    /// Attempt to produce non-malleable satisfying witness for the
+    /// witness script represented by the parse tree
+    ///
+    /// The witness only depends on the answers of the satisfier, see
+    /// [`satisfy::Satisfier`] for how ties between satisfactions are broken.
+    pub fn satisfy<S: satisfy::Satisfier<Pk>>(&self, satisfier: S) -> Result<Vec<Vec<u8>>, Error> {
+        trace_span!("satisfy", ctx = Ctx::name_str());
+        self.satisfy_timelocked(satisfier, false /* allow_mall */)
+            .map(|(stack, _)| stack)
+    }
+
     /// Attempt to produce a malleable satisfying witness for the
     /// witness script represented by the parse tree
     pub fn satisfy_malleable<S: satisfy::Satisfier<Pk>>(
         &self,
         satisfier: S,
     ) -> Result<Vec<Vec<u8>>, Error> {
+        trace_span!("satisfy_malleable", ctx = Ctx::name_str());
+        self.satisfy_timelocked(satisfier, true /* allow_mall */)
+            .map(|(stack, _)| stack)
+    }
+
+    /// Like [`Miniscript::satisfy`], or [`Miniscript::satisfy_malleable`] if
+    /// `allow_mall` is set, also returning the timelocks of the chosen
+    /// spend path
+    pub(crate) fn satisfy_timelocked<S: satisfy::Satisfier<Pk>>(
+        &self,
+        satisfier: S,
+        allow_mall: bool,
+    ) -> Result<(Vec<Vec<u8>>, satisfy::Timelocks), Error> {
+        let checked = satisfy::CheckedSigs::new(&satisfier);
+        // Only satisfactions for default versions (0xc0) are allowed.
         let leaf_hash = TapLeafHash::from_script(&self.encode(), LeafVersion::default());
-        match satisfy::Satisfaction::satisfy_mall(
-            &self.node,
-            &satisfier,
-            self.ty.mall.safe,
-            &leaf_hash,
-        )
-        .stack
-        {
+        let sat = if allow_mall {
+            satisfy::TimelockedSatisfaction::satisfy_mall(
+                &self.node,
+                &checked,
+                self.ty.mall.safe,
+                &leaf_hash,
+            )?
+        } else {
+            satisfy::TimelockedSatisfaction::satisfy(
+                &self.node,
+                &checked,
+                self.ty.mall.safe,
+                &leaf_hash,
+            )?
+        };
+        match sat.stack {
             satisfy::Witness::Stack(stack) => {
+                trace_event!(elements = stack.len(), "satisfied");
+                checked.check_witness(&stack)?;
                 Ctx::check_witness::<Pk, Ext>(&stack)?;
-                Ok(stack)
+                Ok((stack, sat.timelocks))
             }
             satisfy::Witness::Unavailable | satisfy::Witness::Impossible => {
-                Err(Error::CouldNotSatisfy)
+                trace_event!("could not satisfy");
+                Err(self.satisfaction_error(&satisfier))
             }
         }
     }
+
+    /// Enumerate every distinct witness, of at most `bound` bytes, that
+    /// satisfies the script under a satisfier which has all signatures and
+    /// hash preimages, and for which all timelocks are met. This includes
+    /// malleable witnesses and is intended for differential testing against
+    /// script execution and for reviewing small (covenant) scripts.
+    ///
+    /// Signatures are represented by [`satisfy::DUMMY_ECDSA_SIG`] or
+    /// [`satisfy::DUMMY_SCHNORR_SIG`] and preimages by [`satisfy::DUMMY_PREIMAGE`].
+    /// `raw_pkh` fragments cannot be satisfied as the key is unknown, and
+    /// extensions contribute only the (dis)satisfaction they produce themselves.
+    /// The number of witnesses grows exponentially with the number of
+    /// disjunctions and thresholds, so only use this on small scripts.
+    pub fn all_satisfactions(&self, bound: usize) -> Vec<Vec<Vec<u8>>> {
+        satisfy::AllWitnesses::enumerate(&self.node, bound)
+            .sat
+            .into_iter()
+            .filter(|stack| Ctx::check_witness::<Pk, Ext>(stack).is_ok())
+            .collect()
+    }
 }
 
 impl_from_tree!(
@@ -540,7 +750,7 @@ mod tests {
     use bitcoin::{self, XOnlyPublicKey};
     use elements::hashes::{hash160, sha256, Hash};
     use elements::taproot::TapLeafHash;
-    use elements::{self, secp256k1_zkp, Sequence};
+    use elements::{self, opcodes, secp256k1_zkp, Sequence};
 
     use super::{Miniscript, ScriptContext, Segwitv0, Tap};
     use crate::extensions::CovExtArgs;
@@ -1225,4 +1435,749 @@ mod tests {
         SegwitMs::parse_insane(&script).unwrap_err();
         SegwitMs::parse_with_ext(&script, &ExtParams::allow_all()).unwrap();
     }
+
+    #[test]
+    fn all_satisfactions() {
+        use crate::miniscript::satisfy::{DUMMY_ECDSA_SIG, DUMMY_PREIMAGE};
+
+        type SegwitMs = Miniscript<bitcoin::PublicKey, Segwitv0>;
+        let keys = pubkeys(3);
+        let sig = DUMMY_ECDSA_SIG.to_vec();
+
+        // or_b is satisfied by either side or by both
+        let ms = SegwitMs::from_str(&format!("or_b(pk({}),s:pk({}))", keys[0], keys[1])).unwrap();
+        let sats = ms.all_satisfactions(1000);
+        assert_eq!(
+            sats,
+            vec![
+                vec![vec![], sig.clone()],
+                vec![sig.clone(), vec![]],
+                vec![sig.clone(), sig.clone()],
+            ]
+        );
+        // The bound excludes the witness with two signatures
+        assert_eq!(ms.all_satisfactions(2 * sig.len()).len(), 2);
+
+        // Placeholder signatures do not reveal which keys signed a multi
+        let ms =
+            SegwitMs::from_str(&format!("multi(2,{},{},{})", keys[0], keys[1], keys[2])).unwrap();
+        assert_eq!(
+            ms.all_satisfactions(1000),
+            vec![vec![vec![], sig.clone(), sig]]
+        );
+
+        // but their position does in multi_a
+        let xkeys: Vec<XOnlyPublicKey> = keys.iter().map(|k| k.to_x_only_pubkey()).collect();
+        let ms = Tapscript::from_str(&format!(
+            "multi_a(2,{},{},{})",
+            xkeys[0], xkeys[1], xkeys[2]
+        ))
+        .unwrap();
+        let sats = ms.all_satisfactions(1000);
+        assert_eq!(sats.len(), 3);
+        assert!(sats
+            .iter()
+            .all(|s| s.len() == 3 && s.iter().filter(|e| e.is_empty()).count() == 1));
+
+        // Only one way to satisfy a hashlock together with a timelock
+        let h = sha256::Hash::hash(&DUMMY_PREIMAGE);
+        let ms = SegwitMs::from_str_insane(&format!("and_v(v:sha256({}),older(10))", h)).unwrap();
+        assert_eq!(
+            ms.all_satisfactions(1000),
+            vec![vec![DUMMY_PREIMAGE.to_vec()]]
+        );
+        assert!(ms.all_satisfactions(10).is_empty());
+    }
+
+    #[test]
+    fn all_satisfactions_interpret() {
+        use elements::{LockTime, Script};
+
+        use crate::interpreter::Interpreter;
+        use crate::miniscript::satisfy::{DUMMY_ECDSA_SIG, DUMMY_PREIMAGE};
+        use crate::Descriptor;
+
+        // Signatures are not checked, but must parse, so replace the
+        // placeholders by a well-formed SIGHASH_ALL signature
+        let secp = secp256k1_zkp::Secp256k1::signing_only();
+        let sk = secp256k1_zkp::SecretKey::from_slice(&[1; 32]).unwrap();
+        let msg = secp256k1_zkp::Message::from_slice(&[2; 32]).unwrap();
+        let mut sig = secp.sign_ecdsa(&msg, &sk).serialize_der().to_vec();
+        sig.push(1);
+
+        let keys = pubkeys(4);
+        let hash = sha256::Hash::hash(&DUMMY_PREIMAGE);
+        for ms in &[
+            "or_b(pk(A),s:pk(B))",
+            "or_d(pk(A),and_v(v:pk(B),older(10)))",
+            "or_i(pk(A),and_v(v:sha256(H),pk(B)))",
+            "andor(pk(A),older(10),multi(2,B,C,D))",
+            "thresh(2,pk(A),s:pk(B),sln:older(10))",
+            "multi(2,A,B,C)",
+        ] {
+            let ms = ms
+                .replace("H", &hash.to_string())
+                .replace("A", &keys[0].to_string())
+                .replace("B", &keys[1].to_string())
+                .replace("C", &keys[2].to_string())
+                .replace("D", &keys[3].to_string());
+            let ms = Miniscript::<bitcoin::PublicKey, Segwitv0, NoExt>::from_str(&ms).unwrap();
+            let spk = Descriptor::<_, NoExt>::new_wsh(ms.clone())
+                .unwrap()
+                .script_pubkey();
+            let sats = ms.all_satisfactions(1000);
+            assert!(!sats.is_empty(), "{}", ms);
+            // Every enumerated witness is accepted by script execution
+            for sat in sats {
+                let mut witness: Vec<Vec<u8>> = sat
+                    .into_iter()
+                    .map(|elem| {
+                        if elem[..] == DUMMY_ECDSA_SIG[..] {
+                            sig.clone()
+                        } else {
+                            elem
+                        }
+                    })
+                    .collect();
+                witness.push(ms.encode().into_bytes());
+                let script_sig = Script::new();
+                let interpreter = Interpreter::from_txdata(
+                    &spk,
+                    &script_sig,
+                    &witness,
+                    Sequence::from_height(10),
+                    LockTime::from_consensus(500_000),
+                )
+                .unwrap();
+                assert!(
+                    interpreter.iter_assume_sigs().all(|res| res.is_ok()),
+                    "{} with {:?}",
+                    ms,
+                    witness
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn substitute() {
+        let keys = pubkeys(3);
+        let ms = Segwitv0Script::from_str(&format!(
+            "or_d(pk({}),and_v(v:pk({}),older(10)))",
+            keys[0], keys[1]
+        ))
+        .unwrap();
+
+        let older = Segwitv0Script::from_str_insane("older(20)").unwrap();
+        let expected = Segwitv0Script::from_str(&format!(
+            "or_d(pk({}),and_v(v:pk({}),older(20)))",
+            keys[0], keys[1]
+        ))
+        .unwrap();
+        assert_eq!(ms.substitute(&[1, 1], older.clone()).unwrap(), expected);
+        assert_eq!(ms.substitute(&[], older.clone()).unwrap(), older);
+
+        // The left child of and_v must be a V expression
+        let pk = Segwitv0Script::from_str(&format!("pk({})", keys[2])).unwrap();
+        assert!(ms.substitute(&[1, 0], pk.clone()).is_err());
+        let vpk = Segwitv0Script::from_ast(Terminal::Verify(Arc::new(pk.clone()))).unwrap();
+        assert_eq!(
+            ms.substitute(&[1, 0], vpk).unwrap(),
+            Segwitv0Script::from_str(&format!(
+                "or_d(pk({}),and_v(v:pk({}),older(10)))",
+                keys[0], keys[2]
+            ))
+            .unwrap()
+        );
+
+        // Paths must lead to an existing fragment
+        assert!(ms.substitute(&[2], pk.clone()).is_err());
+        assert!(ms.substitute(&[0, 0, 0], pk).is_err());
+    }
+
+    #[test]
+    fn checked_pk_len() {
+        use crate::{BareCtx, Legacy};
+
+        let compressed = pubkeys(1)[0];
+        let uncompressed = bitcoin::PublicKey::new_uncompressed(compressed.inner);
+        let xonly = compressed.to_x_only_pubkey();
+
+        assert_eq!(Legacy::checked_pk_len(&compressed), Ok(34));
+        assert_eq!(Legacy::checked_pk_len(&uncompressed), Ok(66));
+        assert!(Legacy::checked_pk_len(&xonly).is_err());
+        assert_eq!(BareCtx::checked_pk_len(&uncompressed), Ok(66));
+        assert_eq!(Segwitv0::checked_pk_len(&compressed), Ok(34));
+        assert!(Segwitv0::checked_pk_len(&uncompressed).is_err());
+        assert!(Segwitv0::checked_pk_len(&xonly).is_err());
+        assert_eq!(Tap::checked_pk_len(&xonly), Ok(33));
+        assert_eq!(Tap::checked_pk_len(&compressed), Ok(33));
+        assert!(Tap::checked_pk_len(&uncompressed).is_err());
+
+        // The unchecked lengths match the script sizes
+        let ms = Segwitv0Script::from_str(&format!("pk({})", compressed)).unwrap();
+        assert_eq!(ms.script_size(), Segwitv0::pk_len(&compressed) + 1);
+        let ms = Tapscript::from_str(&format!("pk({})", xonly)).unwrap();
+        assert_eq!(ms.script_size(), Tap::pk_len(&xonly) + 1);
+    }
+
+    #[test]
+    fn deterministic_satisfaction() {
+        use std::collections::HashMap;
+
+        use elements::EcdsaSigHashType;
+
+        use crate::{elementssig_to_rawsig, ElementsSig};
+
+        type SegwitMs = Miniscript<bitcoin::PublicKey, Segwitv0>;
+        let keys = pubkeys(3);
+        // Equally sized signatures, told apart by their sighash type
+        let sig = secp256k1_zkp::ecdsa::Signature::from_compact(&[1; 64]).unwrap();
+        let sigs: Vec<ElementsSig> = [
+            EcdsaSigHashType::All,
+            EcdsaSigHashType::None,
+            EcdsaSigHashType::Single,
+        ]
+        .iter()
+        .map(|&ty| (sig, ty))
+        .collect();
+        let raw = |i: usize| elementssig_to_rawsig(&sigs[i]);
+
+        // Satisfiers filled in different orders
+        let satisfier = |order: &[usize]| {
+            let mut map = HashMap::new();
+            for &i in order {
+                map.insert(keys[i], sigs[i]);
+            }
+            map
+        };
+        let orders: [&[usize]; 3] = [&[0, 1, 2], &[2, 1, 0], &[1, 2, 0]];
+
+        // Ties between keys of a multi keep the earliest keys
+        let multi =
+            SegwitMs::from_str(&format!("multi(2,{},{},{})", keys[0], keys[1], keys[2])).unwrap();
+        // Ties between subexpressions of a thresh satisfy the earliest ones
+        let thresh = SegwitMs::from_str(&format!(
+            "thresh(2,pk({}),s:pk({}),s:pk({}))",
+            keys[0], keys[1], keys[2]
+        ))
+        .unwrap();
+        for _ in 0..10 {
+            for order in orders.iter() {
+                assert_eq!(
+                    multi.satisfy(satisfier(order)).unwrap(),
+                    vec![vec![], raw(0), raw(1)]
+                );
+                assert_eq!(
+                    multi.satisfy_malleable(satisfier(order)).unwrap(),
+                    vec![vec![], raw(0), raw(1)]
+                );
+                assert_eq!(
+                    thresh.satisfy(satisfier(order)).unwrap(),
+                    vec![vec![], raw(1), raw(0)]
+                );
+                assert_eq!(
+                    thresh.satisfy_malleable(satisfier(order)).unwrap(),
+                    vec![vec![], raw(1), raw(0)]
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn satisfaction_budget() {
+        use std::collections::HashMap;
+
+        use elements::EcdsaSigHashType;
+
+        use crate::{elementssig_to_rawsig, Error, SatisfyOptions};
+
+        let keys = pubkeys(2);
+        let sig = (
+            secp256k1_zkp::ecdsa::Signature::from_compact(&[1; 64]).unwrap(),
+            EcdsaSigHashType::All,
+        );
+        let mut sigs = HashMap::new();
+        sigs.insert(keys[0], sig);
+        sigs.insert(keys[1], sig);
+        let ms =
+            Segwitv0Script::from_str(&format!("or_d(pk({}),pk({}))", keys[0], keys[1])).unwrap();
+        let options = |max_branches, prefer_first| SatisfyOptions {
+            max_branches: Some(max_branches),
+            prefer_first,
+        };
+
+        let expected = vec![elementssig_to_rawsig(&sig)];
+        assert_eq!(ms.satisfy(&sigs).unwrap(), expected);
+        assert_eq!(ms.satisfy((options(1000, false), &sigs)).unwrap(), expected);
+        // or_d, c: and pk_k of the first branch only
+        assert_eq!(
+            ms.satisfy((options(3, false), &sigs)),
+            Err(Error::SatisfactionEffortExceeded(3))
+        );
+        assert_eq!(
+            ms.satisfy_malleable((options(3, false), &sigs)),
+            Err(Error::SatisfactionEffortExceeded(3))
+        );
+        assert_eq!(ms.satisfy((options(3, true), &sigs)).unwrap(), expected);
+        assert_eq!(
+            ms.satisfy_malleable((options(3, true), &sigs)).unwrap(),
+            expected
+        );
+        // The stricter of combined limits applies
+        assert_eq!(
+            ms.satisfy((options(1000, false), (options(3, false), &sigs))),
+            Err(Error::SatisfactionEffortExceeded(3))
+        );
+
+        // Nothing satisfiable was explored
+        let mut second_only = HashMap::new();
+        second_only.insert(keys[1], sig);
+        assert!(ms.satisfy(&second_only).is_ok());
+        assert_eq!(
+            ms.satisfy((options(3, true), &second_only)),
+            Err(Error::SatisfactionEffortExceeded(3))
+        );
+    }
+
+    #[test]
+    fn satisfier_stack() {
+        use std::collections::HashMap;
+
+        use elements::EcdsaSigHashType;
+
+        use crate::{elementssig_to_rawsig, Preimage32, SatisfierStack, SatisfyOptions};
+
+        let keys = pubkeys(2);
+        let sig = |b| {
+            (
+                secp256k1_zkp::ecdsa::Signature::from_compact(&[b; 64]).unwrap(),
+                EcdsaSigHashType::All,
+            )
+        };
+        let preimage = [0xab; 32];
+        let hash = sha256::Hash::hash(&preimage);
+        let mut preimages: HashMap<sha256::Hash, Preimage32> = HashMap::new();
+        preimages.insert(hash, preimage);
+        let mut sigs = HashMap::new();
+        sigs.insert(keys[0], sig(1));
+        let mut other_sigs = HashMap::new();
+        other_sigs.insert(keys[0], sig(2));
+        other_sigs.insert(keys[1], sig(2));
+
+        // An HTLC: the preimage and a signature, or a signature after a timeout
+        let ms = Segwitv0Script::from_str(&format!(
+            "or_i(and_v(v:pk({}),sha256({})),and_v(v:pk({}),older(144)))",
+            keys[0], hash, keys[1]
+        ))
+        .unwrap();
+        assert!(ms.satisfy(&sigs).is_err());
+        let expected = vec![vec![0xab; 32], elementssig_to_rawsig(&sig(1)), vec![1]];
+        assert_eq!(ms.satisfy((&sigs, &preimages)).unwrap(), expected);
+
+        // Earlier satisfiers take priority
+        let stack = SatisfierStack::new().push(&sigs).push(&other_sigs);
+        assert!(ms.satisfy(&stack).is_err());
+        let stack = stack.push(&preimages);
+        assert_eq!(ms.satisfy(&stack).unwrap(), expected);
+        let stack = SatisfierStack::new()
+            .push(&other_sigs)
+            .push(&sigs)
+            .push(&preimages);
+        assert_eq!(
+            ms.satisfy(&stack).unwrap()[1],
+            elementssig_to_rawsig(&sig(2))
+        );
+
+        // Any satisfier can satisfy a timelock
+        let older = Sequence::from_height(144);
+        let stack = SatisfierStack::new().push(&other_sigs).push(&older);
+        assert_eq!(
+            ms.satisfy(&stack).unwrap(),
+            vec![elementssig_to_rawsig(&sig(2)), vec![]]
+        );
+
+        // The strictest options apply
+        let options = SatisfyOptions {
+            max_branches: Some(2),
+            prefer_first: false,
+        };
+        let stack = stack.push(&options);
+        assert!(ms.satisfy(&stack).is_err());
+        assert!(SatisfierStack::<bitcoin::PublicKey>::new().is_empty());
+    }
+
+    #[test]
+    fn sighash_filter() {
+        use std::collections::HashMap;
+
+        use elements::EcdsaSigHashType;
+
+        use crate::{elementssig_to_rawsig, Descriptor, SighashFilter, SighashPolicy};
+
+        type SegwitMs = Miniscript<bitcoin::PublicKey, Segwitv0>;
+        let keys = pubkeys(2);
+        let sig = secp256k1_zkp::ecdsa::Signature::from_compact(&[1; 64]).unwrap();
+        let ms = SegwitMs::from_str(&format!("multi(1,{},{})", keys[0], keys[1])).unwrap();
+
+        let mut satisfier = HashMap::new();
+        satisfier.insert(keys[0], (sig, EcdsaSigHashType::None));
+        satisfier.insert(keys[1], (sig, EcdsaSigHashType::All));
+        assert_eq!(
+            ms.satisfy(SighashFilter::new(&satisfier, SighashPolicy::all_only()))
+                .unwrap(),
+            vec![vec![], elementssig_to_rawsig(&(sig, EcdsaSigHashType::All))]
+        );
+        assert_eq!(
+            ms.satisfy(SighashFilter::new(&satisfier, SighashPolicy::any()))
+                .unwrap(),
+            vec![
+                vec![],
+                elementssig_to_rawsig(&(sig, EcdsaSigHashType::None))
+            ]
+        );
+
+        satisfier.remove(&keys[1]);
+        assert!(ms.satisfy(&satisfier).is_ok());
+        assert!(ms
+            .satisfy(SighashFilter::new(&satisfier, SighashPolicy::all_only()))
+            .is_err());
+
+        // Covenants require SIGHASH_ALL
+        let desc = Descriptor::<String, CovenantExt<CovExtArgs>>::from_str("elwsh(pk(A))").unwrap();
+        assert_eq!(desc.sighash_policy(), SighashPolicy::any());
+        let desc = Descriptor::<String>::from_str("elcovwsh(A,pk(B))").unwrap();
+        assert!(desc.sighash_policy().allows_ecdsa(EcdsaSigHashType::All));
+        assert!(!desc
+            .sighash_policy()
+            .allows_ecdsa(EcdsaSigHashType::AllPlusAnyoneCanPay));
+    }
+
+    #[test]
+    fn adaptor_sigs() {
+        use elements::secp256k1_zkp::{EcdsaAdaptorSignature, Message, Scalar, SecretKey};
+        use elements::taproot::TapLeafHash;
+        use elements::{EcdsaSigHashType, SchnorrSig, SchnorrSigHashType};
+
+        use crate::{AdaptorCompleter, EcdsaAdaptorSig, SchnorrAdaptorSig, SigType};
+
+        struct AdaptorSat<Pk>(Pk, Option<EcdsaAdaptorSig>, Option<SchnorrAdaptorSig>);
+        impl<Pk: ToPublicKey> Satisfier<Pk> for AdaptorSat<Pk> {
+            fn lookup_raw_pkh_pk(&self, _: &hash160::Hash) -> Option<Pk> {
+                Some(self.0.clone())
+            }
+
+            fn lookup_ecdsa_adaptor_sig(&self, _: &Pk) -> Option<EcdsaAdaptorSig> {
+                self.1
+            }
+
+            fn lookup_schnorr_adaptor_sig(
+                &self,
+                _: &Pk,
+                _: &TapLeafHash,
+            ) -> Option<SchnorrAdaptorSig> {
+                self.2
+            }
+
+            fn lookup_tap_key_spend_adaptor_sig(
+                &self,
+            ) -> Option<(XOnlyPublicKey, SchnorrAdaptorSig)> {
+                Some((self.0.to_x_only_pubkey(), self.2?))
+            }
+        }
+
+        let secp = secp256k1_zkp::Secp256k1::new();
+        let sk = SecretKey::from_slice(&[1; 32]).unwrap();
+        let secret = SecretKey::from_slice(&[2; 32]).unwrap();
+        let point = secp256k1_zkp::PublicKey::from_secret_key(&secp, &secret);
+        let msg = Message::from_slice(&[3; 32]).unwrap();
+
+        // ECDSA
+        let pk = bitcoin::PublicKey::new(secp256k1_zkp::PublicKey::from_secret_key(&secp, &sk));
+        let ms =
+            Miniscript::<bitcoin::PublicKey, Segwitv0>::from_str(&format!("pk({})", pk)).unwrap();
+        let adaptor = EcdsaAdaptorSig {
+            sig: EcdsaAdaptorSignature::encrypt_no_aux_rand(&secp, &msg, &sk, &point),
+            encryption_key: point,
+            msg,
+            hash_ty: EcdsaSigHashType::All,
+        };
+        let mut completer = AdaptorCompleter::new(AdaptorSat(pk, Some(adaptor), None));
+        assert!(ms.satisfy(&completer).is_err());
+        assert_eq!(completer.add_secret(&secp, secret), point);
+        let witness = ms.satisfy(&completer).unwrap();
+        let (sig, hash_ty) = crate::elementssig_from_rawsig(&witness[0]).unwrap();
+        assert_eq!(hash_ty, EcdsaSigHashType::All);
+        secp.verify_ecdsa(&msg, &sig, &pk.inner).unwrap();
+
+        // pkh fragments find their key by hash
+        let pkh =
+            Miniscript::<bitcoin::PublicKey, Segwitv0>::from_str(&format!("pkh({})", pk)).unwrap();
+        let witness = pkh.satisfy(&completer).unwrap();
+        assert_eq!(witness[1], pk.to_bytes());
+        secp.verify_ecdsa(
+            &msg,
+            &crate::elementssig_from_rawsig(&witness[0]).unwrap().0,
+            &pk.inner,
+        )
+        .unwrap();
+
+        // Adaptor signatures of another message or key are not completed
+        let other_msg = Message::from_slice(&[4; 32]).unwrap();
+        let wrong_msg = EcdsaAdaptorSig {
+            msg: other_msg,
+            ..adaptor
+        };
+        let mut completer = AdaptorCompleter::new(AdaptorSat(pk, Some(wrong_msg), None));
+        completer.add_secret(&secp, secret);
+        assert!(ms.satisfy(&completer).is_err());
+        assert!(pkh.satisfy(&completer).is_err());
+        let other_pk = bitcoin::PublicKey::new(point);
+        let mut completer = AdaptorCompleter::new(AdaptorSat(other_pk, Some(adaptor), None));
+        completer.add_secret(&secp, secret);
+        let ms = Miniscript::<bitcoin::PublicKey, Segwitv0>::from_str(&format!("pk({})", other_pk))
+            .unwrap();
+        assert!(ms.satisfy(&completer).is_err());
+
+        // Schnorr, with a pre-signature of a known signature
+        let keypair = secp256k1_zkp::KeyPair::from_secret_key(&secp, &sk);
+        let xpk = keypair.x_only_public_key().0;
+        let sig = secp.sign_schnorr_no_aux_rand(&msg, &keypair);
+        let s = SecretKey::from_slice(&sig[32..]).unwrap();
+        let s_hat = s.add_tweak(&Scalar::from(secret.negate())).unwrap();
+        let adaptor = SchnorrAdaptorSig {
+            nonce: XOnlyPublicKey::from_slice(&sig[..32]).unwrap(),
+            s_hat: s_hat.secret_bytes(),
+            nonce_parity: false,
+            encryption_key: point,
+            msg,
+            hash_ty: SchnorrSigHashType::Default,
+        };
+        let ms = Miniscript::<XOnlyPublicKey, Tap>::from_str(&format!("pk({})", xpk)).unwrap();
+        let mut completer = AdaptorCompleter::new(AdaptorSat(xpk, None, Some(adaptor)));
+        assert!(ms.satisfy(&completer).is_err());
+        assert!(Satisfier::<XOnlyPublicKey>::lookup_tap_key_spend_sig(&completer).is_none());
+        completer.add_secret(&secp, secret);
+        let witness = ms.satisfy(&completer).unwrap();
+        let completed = SchnorrSig::from_slice(&witness[0]).unwrap();
+        assert_eq!(completed.sig, sig);
+        secp.verify_schnorr(&completed.sig, &msg, &xpk).unwrap();
+
+        // Keys found by hash and the key spend
+        let leaf = (
+            xpk.to_pubkeyhash(SigType::Schnorr),
+            TapLeafHash::all_zeros(),
+        );
+        let (raw_pk, raw_sig) =
+            Satisfier::<XOnlyPublicKey>::lookup_raw_pkh_tap_leaf_script_sig(&completer, &leaf)
+                .unwrap();
+        assert_eq!((raw_pk, raw_sig.sig), (xpk, sig));
+        let key_spend = Satisfier::<XOnlyPublicKey>::lookup_tap_key_spend_sig(&completer).unwrap();
+        assert_eq!(key_spend.sig, sig);
+
+        // Completed signatures of another message are not used
+        let wrong_msg = SchnorrAdaptorSig {
+            msg: other_msg,
+            ..adaptor
+        };
+        let mut completer = AdaptorCompleter::new(AdaptorSat(xpk, None, Some(wrong_msg)));
+        completer.add_secret(&secp, secret);
+        assert!(ms.satisfy(&completer).is_err());
+        assert!(
+            Satisfier::<XOnlyPublicKey>::lookup_raw_pkh_tap_leaf_script_sig(&completer, &leaf)
+                .is_none()
+        );
+        assert!(Satisfier::<XOnlyPublicKey>::lookup_tap_key_spend_sig(&completer).is_none());
+
+        // The negated secret completes pre-signatures with the nonce parity
+        let adaptor = SchnorrAdaptorSig {
+            s_hat: s.add_tweak(&Scalar::from(secret)).unwrap().secret_bytes(),
+            nonce_parity: true,
+            ..adaptor
+        };
+        assert_eq!(adaptor.complete(&secret).unwrap().sig, sig);
+    }
+
+    #[test]
+    fn musig_partial_sigs() {
+        use elements::hashes::hex::FromHex;
+        use elements::secp256k1_zkp::{Message, PublicKey};
+        use elements::taproot::TapLeafHash;
+        use elements::{SchnorrSig, SchnorrSigHashType};
+
+        use crate::descriptor::{MuSigKeyAgg, MuSigSession};
+        use crate::{MuSigAggregator, MuSigPartialSigs};
+
+        struct PartialSat(MuSigPartialSigs);
+        impl<Pk: ToPublicKey> Satisfier<Pk> for PartialSat {
+            fn lookup_musig_partial_sigs(
+                &self,
+                _: &Pk,
+                _: &TapLeafHash,
+            ) -> Option<MuSigPartialSigs> {
+                Some(self.0.clone())
+            }
+        }
+
+        // The first signature aggregation vector of BIP-327
+        let secp = secp256k1_zkp::Secp256k1::verification_only();
+        let hex = |s: &str| Vec::<u8>::from_hex(s).unwrap();
+        let keys = [
+            PublicKey::from_str(
+                "03935F972DA013F80AE011890FA89B67A27B7BE6CCB24D3274D18B2D4067F261A9",
+            )
+            .unwrap(),
+            PublicKey::from_str(
+                "02D2DC6F5DF7C56ACF38C7FA0AE7A759AE30E19B37359DFDE015872324C7EF6E05",
+            )
+            .unwrap(),
+        ];
+        let key_agg = MuSigKeyAgg::from_ordered_keys(&secp, &keys);
+        let mut aggnonce = [0; 66];
+        aggnonce.copy_from_slice(&hex(
+            "0341432722C5CD0268D829C702CF0D1CBCE57033EED201FD335191385227C3210C\
+             03D377F2D258B64AADC0E16F26462323D701D286046A2EA93365656AFD9875982B",
+        ));
+        let msg = Message::from_slice(&hex(
+            "599C67EA410D005B9DA90817CF03ED3B1C868E4DA4EDF00A5880B0082C237869",
+        ))
+        .unwrap();
+        let mut partial_sigs = [[0; 32]; 2];
+        partial_sigs[0].copy_from_slice(&hex(
+            "B15D2CD3C3D22B04DAE438CE653F6B4ECF042F42CFDED7C41B64AAF9B4AF53FB",
+        ));
+        partial_sigs[1].copy_from_slice(&hex(
+            "6193D6AC61B354E9105BBDC8937A3454A6D705B6D57322A5A472A02CE99FCB64",
+        ));
+        let partial = MuSigPartialSigs {
+            session: MuSigSession::new(&secp, key_agg, &aggnonce, msg).unwrap(),
+            partial_sigs: partial_sigs.to_vec(),
+            hash_ty: SchnorrSigHashType::Default,
+        };
+        let sig = partial.aggregate(&secp).unwrap();
+        assert_eq!(
+            sig.sig[..],
+            hex(
+                "041DA22223CE65C92C9A0D6C2CAC828AAF1EEE56304FEC371DDF91EBB2B9EF09\
+                 12F1038025857FEDEB3FF696F8B99FA4BB2C5812F6095A2E0004EC99CE18DE1E"
+            )[..]
+        );
+
+        let xpk = key_agg.aggregate_key().x_only_public_key().0;
+        let ms = Miniscript::<XOnlyPublicKey, Tap>::from_str(&format!("pk({})", xpk)).unwrap();
+        assert!(ms.satisfy(PartialSat(partial.clone())).is_err());
+        let witness = ms
+            .satisfy(MuSigAggregator::new(PartialSat(partial.clone())))
+            .unwrap();
+        assert_eq!(SchnorrSig::from_slice(&witness[0]).unwrap(), sig);
+
+        // Sessions of other keys are not used for the key
+        let other = keys[0].x_only_public_key().0;
+        let ms = Miniscript::<XOnlyPublicKey, Tap>::from_str(&format!("pk({})", other)).unwrap();
+        assert!(ms
+            .satisfy(MuSigAggregator::new(PartialSat(partial.clone())))
+            .is_err());
+
+        // Partial signatures that do not add up are rejected
+        let mut invalid = partial.clone();
+        invalid.partial_sigs.pop();
+        assert_eq!(invalid.aggregate(&secp), None);
+        invalid.partial_sigs.push([0xff; 32]);
+        assert_eq!(invalid.aggregate(&secp), None);
+    }
+
+    #[test]
+    fn semantic_eq() {
+        type SegwitMs = Miniscript<String, Segwitv0>;
+        type TapMs = Miniscript<String, Tap>;
+
+        let multi = SegwitMs::from_str("multi(2,A,B,C)").unwrap();
+        let multi_a = TapMs::from_str("multi_a(2,C,B,A)").unwrap();
+        let thresh = TapMs::from_str("thresh(2,pk(A),s:pk(B),s:pk(C))").unwrap();
+        assert!(multi.semantic_eq(&multi_a).unwrap());
+        assert!(multi_a.semantic_eq(&thresh).unwrap());
+        assert!(!multi
+            .semantic_eq(&TapMs::from_str("multi_a(1,A,B,C)").unwrap())
+            .unwrap());
+        assert!(!multi
+            .semantic_eq(&TapMs::from_str("multi_a(2,A,B,D)").unwrap())
+            .unwrap());
+
+        let or = SegwitMs::from_str("or_d(pk(A),and_v(v:pk(B),older(144)))").unwrap();
+        let or_swapped = TapMs::from_str("or_i(and_v(v:pk(B),older(144)),pk(A))").unwrap();
+        assert!(or.semantic_eq(&or_swapped).unwrap());
+        assert!(!or
+            .semantic_eq(&TapMs::from_str("or_i(and_v(v:pk(B),older(145)),pk(A))").unwrap())
+            .unwrap());
+    }
+
+    #[test]
+    fn opcode_whitelist() {
+        use crate::{AnalysisError, OpSet};
+
+        let pk = "020202020202020202020202020202020202020202020202020202020202020202";
+        let ms = Segwitv0Script::from_str(&format!("and_v(v:pk({}),older(144))", pk)).unwrap();
+        assert_eq!(ms.assert_opcode_whitelist(OpSet::MINISCRIPT), Ok(()));
+        assert_eq!(
+            ms.assert_opcode_whitelist(&[OpSet::Push, OpSet::Signature, OpSet::Control]),
+            Err(AnalysisError::DisallowedOpcode(opcodes::all::OP_CSV))
+        );
+
+        let xpk = "0202020202020202020202020202020202020202020202020202020202020202";
+        let ms = Miniscript::<XOnlyPublicKey, Tap, CovenantExt<CovExtArgs>>::from_str_insane(
+            &format!(
+                "and_v(v:pk({}),asset_eq(out_asset(0),0179d51a47e4ac8e32306486dd0926a88678c392f2ed5f213e3ff2ad461c7c25e1))",
+                xpk
+            ),
+        )
+        .unwrap();
+        assert_eq!(
+            ms.assert_opcode_whitelist(OpSet::MINISCRIPT),
+            Err(AnalysisError::DisallowedOpcode(
+                opcodes::all::OP_INSPECTOUTPUTASSET
+            ))
+        );
+        let mut allowed = OpSet::MINISCRIPT.to_vec();
+        allowed.push(OpSet::Introspection);
+        assert_eq!(ms.assert_opcode_whitelist(&allowed), Ok(()));
+    }
+
+    #[test]
+    #[cfg(feature = "tracing")]
+    fn satisfy_tracing() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        use tracing::span::{Attributes, Id, Record};
+        use tracing::{Event, Metadata};
+
+        #[derive(Default)]
+        struct Counter {
+            spans: AtomicUsize,
+            events: AtomicUsize,
+        }
+
+        impl tracing::Subscriber for Counter {
+            fn enabled(&self, _: &Metadata<'_>) -> bool {
+                true
+            }
+            fn new_span(&self, _: &Attributes<'_>) -> Id {
+                Id::from_u64(self.spans.fetch_add(1, Ordering::SeqCst) as u64 + 1)
+            }
+            fn record(&self, _: &Id, _: &Record<'_>) {}
+            fn record_follows_from(&self, _: &Id, _: &Id) {}
+            fn event(&self, _: &Event<'_>) {
+                self.events.fetch_add(1, Ordering::SeqCst);
+            }
+            fn enter(&self, _: &Id) {}
+            fn exit(&self, _: &Id) {}
+        }
+
+        let ms = Segwitv0Script::from_str(&format!("pk({})", pubkeys(1)[0])).unwrap();
+        let counter = std::sync::Arc::new(Counter::default());
+        tracing::subscriber::with_default(counter.clone(), || {
+            assert!(ms.satisfy(()).is_err());
+        });
+        assert_eq!(counter.spans.load(Ordering::SeqCst), 1);
+        assert_eq!(counter.events.load(Ordering::SeqCst), 1);
+    }
 }
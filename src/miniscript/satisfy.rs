@@ -18,21 +18,26 @@
 //! scriptpubkeys.
 //!
 
-use std::collections::{BTreeMap, HashMap};
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::sync::Arc;
 use std::{cmp, i64, mem};
 
-use bitcoin::hashes::hash160;
+use bitcoin::hashes::{hash160, ripemd160, sha256};
 use bitcoin::secp256k1::XOnlyPublicKey;
 use elements::hashes::sha256d;
 use elements::secp256k1_zkp::schnorr;
 use elements::taproot::{ControlBlock, LeafVersion, TapLeafHash};
-use elements::{self, confidential, secp256k1_zkp, LockTime, OutPoint, Script, Sequence};
+use elements::{
+    self, confidential, secp256k1_zkp, LockTime, OutPoint, PackedLockTime, Script, Sequence,
+};
 
 use super::context::SigType;
-use crate::extensions::{CsfsMsg, ParseableExt};
+use super::types::{Malleability, Property};
+use crate::descriptor::MuSigSession;
+use crate::extensions::{CsfsMsg, Extension, ParseableExt};
 use crate::util::witness_size;
-use crate::{Miniscript, MiniscriptKey, ScriptContext, Terminal, ToPublicKey};
+use crate::{hash256, Error, Miniscript, MiniscriptKey, ScriptContext, Terminal, ToPublicKey};
 
 /// Type alias for a signature/hashtype pair
 pub type ElementsSig = (secp256k1_zkp::ecdsa::Signature, elements::EcdsaSigHashType);
@@ -56,16 +61,53 @@ pub fn elementssig_from_rawsig(rawsig: &[u8]) -> Result<ElementsSig, crate::inte
     let sig = secp256k1_zkp::ecdsa::Signature::from_der(sig)?;
     Ok((sig, flag))
 }
+
+/// Look up the ECDSA signature for `pk`, checking it with
+/// [`Satisfier::check_ecdsa_sig`]
+pub(crate) fn checked_ecdsa_sig<Pk, S>(stfr: &S, pk: &Pk) -> Result<Option<ElementsSig>, Error>
+where
+    Pk: MiniscriptKey + ToPublicKey,
+    S: Satisfier<Pk>,
+{
+    match stfr.lookup_ecdsa_sig(pk) {
+        Some(sig) if !stfr.check_ecdsa_sig(&pk.to_public_key(), &sig) => {
+            Err(Error::NonCanonicalSig(pk.to_public_key()))
+        }
+        sig => Ok(sig),
+    }
+}
+
 /// Trait describing a lookup table for signatures, hash preimages, etc.
 /// Every method has a default implementation that simply returns `None`
 /// on every query. Users are expected to override the methods that they
 /// have data for.
+///
+/// Satisfaction is deterministic: given a satisfier that answers every
+/// query the same way, the same witness is produced on every run and on
+/// every platform. Whenever several satisfactions have the same cost, the
+/// one using the earliest subexpressions (or keys) in script order is
+/// chosen. In particular, the iteration order of a `HashMap` satisfier has
+/// no effect on the result, since satisfiers are only used for lookups.
 pub trait Satisfier<Pk: MiniscriptKey + ToPublicKey> {
     /// Given a public key, look up an ECDSA signature with that key
     fn lookup_ecdsa_sig(&self, _: &Pk) -> Option<ElementsSig> {
         None
     }
 
+    /// Check an ECDSA signature returned by [`Satisfier::lookup_ecdsa_sig`]
+    /// or [`Satisfier::lookup_raw_pkh_ecdsa_sig`]. Satisfying fails with
+    /// [`crate::Error::NonCanonicalSig`] if the chosen satisfaction uses a
+    /// signature failing the check.
+    ///
+    /// By default, signatures must have a low S value, as required by the
+    /// standardness rules. Their DER encoding is always canonical since they
+    /// are serialized by this library.
+    fn check_ecdsa_sig(&self, _: &bitcoin::PublicKey, sig: &ElementsSig) -> bool {
+        let mut normalized = sig.0;
+        normalized.normalize_s();
+        normalized == sig.0
+    }
+
     /// Lookup the tap key spend sig
     fn lookup_tap_key_spend_sig(&self) -> Option<elements::SchnorrSig> {
         None
@@ -216,6 +258,45 @@ pub trait Satisfier<Pk: MiniscriptKey + ToPublicKey> {
     fn lookup_csfs_sig(&self, _pk: &XOnlyPublicKey, _msg: &CsfsMsg) -> Option<schnorr::Signature> {
         None
     }
+
+    /// Given a public key, look up an ECDSA adaptor signature with that key.
+    /// It is only used once completed by an [`AdaptorCompleter`].
+    fn lookup_ecdsa_adaptor_sig(&self, _: &Pk) -> Option<EcdsaAdaptorSig> {
+        None
+    }
+
+    /// Given a public key and a associated leaf hash, look up a Schnorr
+    /// adaptor signature with that key. It is only used once completed by an
+    /// [`AdaptorCompleter`].
+    fn lookup_schnorr_adaptor_sig(&self, _: &Pk, _: &TapLeafHash) -> Option<SchnorrAdaptorSig> {
+        None
+    }
+
+    /// Look up a Schnorr adaptor signature for the taproot key spend, along
+    /// with the output key it signs for. It is only used once completed by
+    /// an [`AdaptorCompleter`].
+    fn lookup_tap_key_spend_adaptor_sig(&self) -> Option<(XOnlyPublicKey, SchnorrAdaptorSig)> {
+        None
+    }
+
+    /// Given a MuSig2 aggregate key and a associated leaf hash, look up the
+    /// partial signatures of its signers. They are only used once aggregated
+    /// by a [`MuSigAggregator`].
+    fn lookup_musig_partial_sigs(&self, _: &Pk, _: &TapLeafHash) -> Option<MuSigPartialSigs> {
+        None
+    }
+
+    /// Look up the MuSig2 partial signatures for a taproot key spend with an
+    /// aggregate internal key. They are only used once aggregated by a
+    /// [`MuSigAggregator`].
+    fn lookup_tap_key_spend_musig_partial_sigs(&self) -> Option<MuSigPartialSigs> {
+        None
+    }
+
+    /// Limits on the search for a satisfaction, unlimited by default
+    fn satisfy_options(&self) -> SatisfyOptions {
+        SatisfyOptions::default()
+    }
 }
 
 // Allow use of `()` as a "no conditions available" satisfier
@@ -257,6 +338,53 @@ impl<Pk: MiniscriptKey + ToPublicKey> Satisfier<Pk> for LockTime {
     }
 }
 
+/// Limits on the search for a satisfaction.
+///
+/// Satisfying a fragment computes both the satisfaction and the
+/// dissatisfaction of many of its subexpressions, and nested disjunctions
+/// and thresholds multiply that work. For descriptors from untrusted
+/// sources, `max_branches` bounds the number of (dis)satisfactions of
+/// subexpressions computed per script. Subexpressions are explored in script
+/// order and the ones left over once the budget runs out are treated as
+/// unavailable, so the result is deterministic. The whole satisfaction then
+/// fails with [`Error::SatisfactionEffortExceeded`], unless `prefer_first`
+/// is set, in which case the best satisfaction among the explored
+/// subexpressions is used. That still is a valid (and, unless malleable
+/// satisfactions are requested, non-malleable) witness, but it favours
+/// earlier branches over cheaper later ones.
+///
+/// The options are taken from [`Satisfier::satisfy_options`]. They can be
+/// combined with any satisfier as a tuple, e.g. `(options, &sigs)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct SatisfyOptions {
+    /// Maximum number of (dis)satisfactions of subexpressions computed for
+    /// a script, unlimited if `None`
+    pub max_branches: Option<usize>,
+    /// Whether to use the best satisfaction found so far instead of failing
+    /// when `max_branches` is exceeded
+    pub prefer_first: bool,
+}
+
+impl SatisfyOptions {
+    // The stricter limit of both, used when combining satisfiers
+    fn combine(self, other: Self) -> Self {
+        let max_branches = match (self.max_branches, other.max_branches) {
+            (Some(a), Some(b)) => Some(cmp::min(a, b)),
+            (a, b) => a.or(b),
+        };
+        SatisfyOptions {
+            max_branches,
+            prefer_first: self.prefer_first || other.prefer_first,
+        }
+    }
+}
+
+impl<Pk: MiniscriptKey + ToPublicKey> Satisfier<Pk> for SatisfyOptions {
+    fn satisfy_options(&self) -> SatisfyOptions {
+        *self
+    }
+}
+
 impl<Pk: MiniscriptKey + ToPublicKey> Satisfier<Pk> for HashMap<Pk, ElementsSig> {
     fn lookup_ecdsa_sig(&self, key: &Pk) -> Option<ElementsSig> {
         self.get(key).copied()
@@ -323,11 +451,39 @@ where
     }
 }
 
+impl<Pk: MiniscriptKey + ToPublicKey> Satisfier<Pk> for HashMap<sha256::Hash, Preimage32> {
+    fn lookup_sha256(&self, h: &Pk::Sha256) -> Option<Preimage32> {
+        self.get(&Pk::to_sha256(h)).copied()
+    }
+}
+
+impl<Pk: MiniscriptKey + ToPublicKey> Satisfier<Pk> for HashMap<hash256::Hash, Preimage32> {
+    fn lookup_hash256(&self, h: &Pk::Hash256) -> Option<Preimage32> {
+        self.get(&Pk::to_hash256(h)).copied()
+    }
+}
+
+impl<Pk: MiniscriptKey + ToPublicKey> Satisfier<Pk> for HashMap<ripemd160::Hash, Preimage32> {
+    fn lookup_ripemd160(&self, h: &Pk::Ripemd160) -> Option<Preimage32> {
+        self.get(&Pk::to_ripemd160(h)).copied()
+    }
+}
+
+impl<Pk: MiniscriptKey + ToPublicKey> Satisfier<Pk> for HashMap<hash160::Hash, Preimage32> {
+    fn lookup_hash160(&self, h: &Pk::Hash160) -> Option<Preimage32> {
+        self.get(&Pk::to_hash160(h)).copied()
+    }
+}
+
 impl<'a, Pk: MiniscriptKey + ToPublicKey, S: Satisfier<Pk>> Satisfier<Pk> for &'a S {
     fn lookup_ecdsa_sig(&self, p: &Pk) -> Option<ElementsSig> {
         (**self).lookup_ecdsa_sig(p)
     }
 
+    fn check_ecdsa_sig(&self, pk: &bitcoin::PublicKey, sig: &ElementsSig) -> bool {
+        (**self).check_ecdsa_sig(pk, sig)
+    }
+
     fn lookup_tap_leaf_script_sig(&self, p: &Pk, h: &TapLeafHash) -> Option<elements::SchnorrSig> {
         (**self).lookup_tap_leaf_script_sig(p, h)
     }
@@ -443,6 +599,30 @@ impl<'a, Pk: MiniscriptKey + ToPublicKey, S: Satisfier<Pk>> Satisfier<Pk> for &'
     fn lookup_csfs_sig(&self, pk: &XOnlyPublicKey, msg: &CsfsMsg) -> Option<schnorr::Signature> {
         (**self).lookup_csfs_sig(pk, msg)
     }
+
+    fn lookup_ecdsa_adaptor_sig(&self, p: &Pk) -> Option<EcdsaAdaptorSig> {
+        (**self).lookup_ecdsa_adaptor_sig(p)
+    }
+
+    fn lookup_schnorr_adaptor_sig(&self, p: &Pk, h: &TapLeafHash) -> Option<SchnorrAdaptorSig> {
+        (**self).lookup_schnorr_adaptor_sig(p, h)
+    }
+
+    fn lookup_tap_key_spend_adaptor_sig(&self) -> Option<(XOnlyPublicKey, SchnorrAdaptorSig)> {
+        (**self).lookup_tap_key_spend_adaptor_sig()
+    }
+
+    fn lookup_musig_partial_sigs(&self, p: &Pk, h: &TapLeafHash) -> Option<MuSigPartialSigs> {
+        (**self).lookup_musig_partial_sigs(p, h)
+    }
+
+    fn lookup_tap_key_spend_musig_partial_sigs(&self) -> Option<MuSigPartialSigs> {
+        (**self).lookup_tap_key_spend_musig_partial_sigs()
+    }
+
+    fn satisfy_options(&self) -> SatisfyOptions {
+        (**self).satisfy_options()
+    }
 }
 
 impl<'a, Pk: MiniscriptKey + ToPublicKey, S: Satisfier<Pk>> Satisfier<Pk> for &'a mut S {
@@ -450,6 +630,10 @@ impl<'a, Pk: MiniscriptKey + ToPublicKey, S: Satisfier<Pk>> Satisfier<Pk> for &'
         (**self).lookup_ecdsa_sig(p)
     }
 
+    fn check_ecdsa_sig(&self, pk: &bitcoin::PublicKey, sig: &ElementsSig) -> bool {
+        (**self).check_ecdsa_sig(pk, sig)
+    }
+
     fn lookup_tap_leaf_script_sig(&self, p: &Pk, h: &TapLeafHash) -> Option<elements::SchnorrSig> {
         (**self).lookup_tap_leaf_script_sig(p, h)
     }
@@ -565,189 +749,1214 @@ impl<'a, Pk: MiniscriptKey + ToPublicKey, S: Satisfier<Pk>> Satisfier<Pk> for &'
     fn lookup_csfs_sig(&self, pk: &XOnlyPublicKey, msg: &CsfsMsg) -> Option<schnorr::Signature> {
         (**self).lookup_csfs_sig(pk, msg)
     }
-}
 
-macro_rules! impl_tuple_satisfier {
-    ($($ty:ident),*) => {
-        #[allow(non_snake_case)]
-        impl<$($ty,)* Pk> Satisfier<Pk> for ($($ty,)*)
-        where
-            Pk: MiniscriptKey + ToPublicKey,
-            $($ty: Satisfier< Pk>,)*
-        {
-            fn lookup_ecdsa_sig(&self, key: &Pk) -> Option<ElementsSig> {
-                let &($(ref $ty,)*) = self;
-                $(
-                    if let Some(result) = $ty.lookup_ecdsa_sig(key) {
-                        return Some(result);
-                    }
-                )*
-                None
-            }
+    fn lookup_ecdsa_adaptor_sig(&self, p: &Pk) -> Option<EcdsaAdaptorSig> {
+        (**self).lookup_ecdsa_adaptor_sig(p)
+    }
 
-            fn lookup_tap_key_spend_sig(&self) -> Option<elements::SchnorrSig> {
-                let &($(ref $ty,)*) = self;
-                $(
-                    if let Some(result) = $ty.lookup_tap_key_spend_sig() {
-                        return Some(result);
-                    }
-                )*
-                None
-            }
+    fn lookup_schnorr_adaptor_sig(&self, p: &Pk, h: &TapLeafHash) -> Option<SchnorrAdaptorSig> {
+        (**self).lookup_schnorr_adaptor_sig(p, h)
+    }
 
-            fn lookup_tap_leaf_script_sig(&self, key: &Pk, h: &TapLeafHash) -> Option<elements::SchnorrSig> {
-                let &($(ref $ty,)*) = self;
-                $(
-                    if let Some(result) = $ty.lookup_tap_leaf_script_sig(key, h) {
-                        return Some(result);
-                    }
-                )*
-                None
-            }
+    fn lookup_tap_key_spend_adaptor_sig(&self) -> Option<(XOnlyPublicKey, SchnorrAdaptorSig)> {
+        (**self).lookup_tap_key_spend_adaptor_sig()
+    }
 
-            fn lookup_raw_pkh_ecdsa_sig(
-                &self,
-                key_hash: &hash160::Hash,
-            ) -> Option<(bitcoin::PublicKey, ElementsSig)> {
-                let &($(ref $ty,)*) = self;
-                $(
-                    if let Some(result) = $ty.lookup_raw_pkh_ecdsa_sig(key_hash) {
-                        return Some(result);
-                    }
-                )*
-                None
-            }
+    fn lookup_musig_partial_sigs(&self, p: &Pk, h: &TapLeafHash) -> Option<MuSigPartialSigs> {
+        (**self).lookup_musig_partial_sigs(p, h)
+    }
 
-            fn lookup_raw_pkh_tap_leaf_script_sig(
-                &self,
-                key_hash: &(hash160::Hash, TapLeafHash),
-            ) -> Option<(XOnlyPublicKey, elements::SchnorrSig)> {
-                let &($(ref $ty,)*) = self;
-                $(
-                    if let Some(result) = $ty.lookup_raw_pkh_tap_leaf_script_sig(key_hash) {
-                        return Some(result);
-                    }
-                )*
-                None
-            }
+    fn lookup_tap_key_spend_musig_partial_sigs(&self) -> Option<MuSigPartialSigs> {
+        (**self).lookup_tap_key_spend_musig_partial_sigs()
+    }
 
-            fn lookup_raw_pkh_pk(
-                &self,
-                key_hash: &hash160::Hash,
-            ) -> Option<Pk> {
-                let &($(ref $ty,)*) = self;
-                $(
-                    if let Some(result) = $ty.lookup_raw_pkh_pk(key_hash) {
-                        return Some(result);
-                    }
-                )*
-                None
-            }
+    fn satisfy_options(&self) -> SatisfyOptions {
+        (**self).satisfy_options()
+    }
+}
 
-            fn lookup_tap_control_block_map(
-                &self,
-            ) -> Option<&BTreeMap<ControlBlock, (elements::Script, LeafVersion)>> {
-                let &($(ref $ty,)*) = self;
-                $(
-                    if let Some(result) = $ty.lookup_tap_control_block_map() {
-                        return Some(result);
-                    }
-                )*
-                None
-            }
+/// The sighash types that signatures may use
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SighashPolicy {
+    /// Allowed sighash types of ECDSA signatures
+    pub ecdsa: Vec<elements::EcdsaSigHashType>,
+    /// Allowed sighash types of Schnorr signatures
+    pub schnorr: Vec<elements::SchnorrSigHashType>,
+}
 
-            fn lookup_sha256(&self, h: &Pk::Sha256) -> Option<Preimage32> {
-                let &($(ref $ty,)*) = self;
-                $(
-                    if let Some(result) = $ty.lookup_sha256(h) {
-                        return Some(result);
-                    }
-                )*
-                None
-            }
+impl SighashPolicy {
+    /// Allow all sighash types
+    pub fn any() -> Self {
+        use elements::{EcdsaSigHashType as E, SchnorrSigHashType as S};
+
+        SighashPolicy {
+            ecdsa: vec![
+                E::All,
+                E::None,
+                E::Single,
+                E::AllPlusAnyoneCanPay,
+                E::NonePlusAnyoneCanPay,
+                E::SinglePlusAnyoneCanPay,
+            ],
+            schnorr: vec![
+                S::Default,
+                S::All,
+                S::None,
+                S::Single,
+                S::AllPlusAnyoneCanPay,
+                S::NonePlusAnyoneCanPay,
+                S::SinglePlusAnyoneCanPay,
+            ],
+        }
+    }
 
-            fn lookup_hash256(&self, h: &Pk::Hash256) -> Option<Preimage32> {
-                let &($(ref $ty,)*) = self;
-                $(
-                    if let Some(result) = $ty.lookup_hash256(h) {
-                        return Some(result);
-                    }
-                )*
-                None
-            }
+    /// Only allow signatures committing to the whole transaction, i.e.
+    /// `SIGHASH_ALL` and, for Schnorr signatures, `SIGHASH_DEFAULT`
+    pub fn all_only() -> Self {
+        SighashPolicy {
+            ecdsa: vec![elements::EcdsaSigHashType::All],
+            schnorr: vec![
+                elements::SchnorrSigHashType::Default,
+                elements::SchnorrSigHashType::All,
+            ],
+        }
+    }
 
-            fn lookup_ripemd160(&self, h: &Pk::Ripemd160) -> Option<Preimage32> {
-                let &($(ref $ty,)*) = self;
-                $(
-                    if let Some(result) = $ty.lookup_ripemd160(h) {
-                        return Some(result);
-                    }
-                )*
-                None
-            }
+    /// Whether ECDSA signatures with sighash type `ty` are allowed
+    pub fn allows_ecdsa(&self, ty: elements::EcdsaSigHashType) -> bool {
+        self.ecdsa.contains(&ty)
+    }
 
-            fn lookup_hash160(&self, h: &Pk::Hash160) -> Option<Preimage32> {
-                let &($(ref $ty,)*) = self;
-                $(
-                    if let Some(result) = $ty.lookup_hash160(h) {
-                        return Some(result);
-                    }
-                )*
-                None
-            }
+    /// Whether Schnorr signatures with sighash type `ty` are allowed
+    pub fn allows_schnorr(&self, ty: elements::SchnorrSigHashType) -> bool {
+        self.schnorr.contains(&ty)
+    }
+}
 
-            fn check_older(&self, n: Sequence) -> bool {
-                let &($(ref $ty,)*) = self;
-                $(
-                    if $ty.check_older(n) {
-                        return true;
-                    }
-                )*
-                false
-            }
+/// A satisfier which hides the signatures of another satisfier whose
+/// sighash types are not allowed by a [`SighashPolicy`].
+///
+/// Satisfying with this satisfier fails if there is no satisfaction
+/// using only allowed signatures.
+#[derive(Debug, Clone)]
+pub struct SighashFilter<S> {
+    satisfier: S,
+    policy: SighashPolicy,
+}
 
-            fn check_after(&self, n: LockTime) -> bool {
-                let &($(ref $ty,)*) = self;
-                $(
-                    if $ty.check_after(n) {
-                        return true;
-                    }
-                )*
-                false
-            }
+impl<S> SighashFilter<S> {
+    /// Restrict the signatures of `satisfier` to those allowed by `policy`
+    pub fn new(satisfier: S, policy: SighashPolicy) -> Self {
+        SighashFilter { satisfier, policy }
+    }
 
-            fn lookup_nversion(&self) -> Option<u32> {
-                let &($(ref $ty,)*) = self;
-                $(
-                    if let Some(result) = $ty.lookup_nversion() {
-                        return Some(result);
-                    }
-                )*
-                None
-            }
+    /// The policy signatures are checked against
+    pub fn policy(&self) -> &SighashPolicy {
+        &self.policy
+    }
 
-            fn lookup_hashprevouts(&self) -> Option<sha256d::Hash> {
-                let &($(ref $ty,)*) = self;
-                $(
-                    if let Some(result) = $ty.lookup_hashprevouts() {
-                        return Some(result);
-                    }
-                )*
-                None
-            }
+    /// The wrapped satisfier
+    pub fn into_inner(self) -> S {
+        self.satisfier
+    }
+}
 
-            fn lookup_hashsequence(&self) -> Option<sha256d::Hash> {
-                let &($(ref $ty,)*) = self;
-                $(
-                    if let Some(result) = $ty.lookup_hashsequence() {
-                        return Some(result);
-                    }
-                )*
-                None
-            }
+impl<Pk: MiniscriptKey + ToPublicKey, S: Satisfier<Pk>> Satisfier<Pk> for SighashFilter<S> {
+    fn lookup_ecdsa_sig(&self, p: &Pk) -> Option<ElementsSig> {
+        self.satisfier
+            .lookup_ecdsa_sig(p)
+            .filter(|sig| self.policy.allows_ecdsa(sig.1))
+    }
 
-            fn lookup_hashissuances(&self) -> Option<sha256d::Hash> {
-                let &($(ref $ty,)*) = self;
+    fn check_ecdsa_sig(&self, pk: &bitcoin::PublicKey, sig: &ElementsSig) -> bool {
+        self.satisfier.check_ecdsa_sig(pk, sig)
+    }
+
+    fn lookup_tap_leaf_script_sig(&self, p: &Pk, h: &TapLeafHash) -> Option<elements::SchnorrSig> {
+        self.satisfier
+            .lookup_tap_leaf_script_sig(p, h)
+            .filter(|sig| self.policy.allows_schnorr(sig.hash_ty))
+    }
+
+    fn lookup_raw_pkh_pk(&self, pkh: &hash160::Hash) -> Option<Pk> {
+        self.satisfier.lookup_raw_pkh_pk(pkh)
+    }
+
+    fn lookup_raw_pkh_ecdsa_sig(
+        &self,
+        pkh: &hash160::Hash,
+    ) -> Option<(bitcoin::PublicKey, ElementsSig)> {
+        self.satisfier
+            .lookup_raw_pkh_ecdsa_sig(pkh)
+            .filter(|&(_, sig)| self.policy.allows_ecdsa(sig.1))
+    }
+
+    fn lookup_tap_key_spend_sig(&self) -> Option<elements::SchnorrSig> {
+        self.satisfier
+            .lookup_tap_key_spend_sig()
+            .filter(|sig| self.policy.allows_schnorr(sig.hash_ty))
+    }
+
+    fn lookup_raw_pkh_tap_leaf_script_sig(
+        &self,
+        pkh: &(hash160::Hash, TapLeafHash),
+    ) -> Option<(XOnlyPublicKey, elements::SchnorrSig)> {
+        self.satisfier
+            .lookup_raw_pkh_tap_leaf_script_sig(pkh)
+            .filter(|&(_, sig)| self.policy.allows_schnorr(sig.hash_ty))
+    }
+
+    fn lookup_tap_control_block_map(
+        &self,
+    ) -> Option<&BTreeMap<ControlBlock, (elements::Script, LeafVersion)>> {
+        self.satisfier.lookup_tap_control_block_map()
+    }
+
+    fn lookup_sha256(&self, h: &Pk::Sha256) -> Option<Preimage32> {
+        self.satisfier.lookup_sha256(h)
+    }
+
+    fn lookup_hash256(&self, h: &Pk::Hash256) -> Option<Preimage32> {
+        self.satisfier.lookup_hash256(h)
+    }
+
+    fn lookup_ripemd160(&self, h: &Pk::Ripemd160) -> Option<Preimage32> {
+        self.satisfier.lookup_ripemd160(h)
+    }
+
+    fn lookup_hash160(&self, h: &Pk::Hash160) -> Option<Preimage32> {
+        self.satisfier.lookup_hash160(h)
+    }
+
+    fn check_older(&self, t: Sequence) -> bool {
+        self.satisfier.check_older(t)
+    }
+
+    fn check_after(&self, n: LockTime) -> bool {
+        self.satisfier.check_after(n)
+    }
+
+    fn lookup_nversion(&self) -> Option<u32> {
+        self.satisfier.lookup_nversion()
+    }
+
+    fn lookup_hashprevouts(&self) -> Option<sha256d::Hash> {
+        self.satisfier.lookup_hashprevouts()
+    }
+
+    fn lookup_hashsequence(&self) -> Option<sha256d::Hash> {
+        self.satisfier.lookup_hashsequence()
+    }
+
+    fn lookup_hashissuances(&self) -> Option<sha256d::Hash> {
+        self.satisfier.lookup_hashissuances()
+    }
+
+    fn lookup_outpoint(&self) -> Option<OutPoint> {
+        self.satisfier.lookup_outpoint()
+    }
+
+    fn lookup_scriptcode(&self) -> Option<&Script> {
+        self.satisfier.lookup_scriptcode()
+    }
+
+    fn lookup_value(&self) -> Option<confidential::Value> {
+        self.satisfier.lookup_value()
+    }
+
+    fn lookup_nsequence(&self) -> Option<u32> {
+        self.satisfier.lookup_nsequence()
+    }
+
+    fn lookup_outputs(&self) -> Option<&[elements::TxOut]> {
+        self.satisfier.lookup_outputs()
+    }
+
+    fn lookup_nlocktime(&self) -> Option<u32> {
+        self.satisfier.lookup_nlocktime()
+    }
+
+    fn lookup_sighashu32(&self) -> Option<u32> {
+        self.satisfier.lookup_sighashu32()
+    }
+
+    fn lookup_spent_utxos(&self) -> Option<&[elements::TxOut]> {
+        self.satisfier.lookup_spent_utxos()
+    }
+
+    fn lookup_tx(&self) -> Option<&elements::Transaction> {
+        self.satisfier.lookup_tx()
+    }
+
+    fn lookup_curr_inp(&self) -> Option<usize> {
+        self.satisfier.lookup_curr_inp()
+    }
+
+    fn lookup_csfs_sig(&self, pk: &XOnlyPublicKey, msg: &CsfsMsg) -> Option<schnorr::Signature> {
+        self.satisfier.lookup_csfs_sig(pk, msg)
+    }
+
+    fn lookup_ecdsa_adaptor_sig(&self, p: &Pk) -> Option<EcdsaAdaptorSig> {
+        self.satisfier
+            .lookup_ecdsa_adaptor_sig(p)
+            .filter(|sig| self.policy.allows_ecdsa(sig.hash_ty))
+    }
+
+    fn lookup_schnorr_adaptor_sig(&self, p: &Pk, h: &TapLeafHash) -> Option<SchnorrAdaptorSig> {
+        self.satisfier
+            .lookup_schnorr_adaptor_sig(p, h)
+            .filter(|sig| self.policy.allows_schnorr(sig.hash_ty))
+    }
+
+    fn lookup_tap_key_spend_adaptor_sig(&self) -> Option<(XOnlyPublicKey, SchnorrAdaptorSig)> {
+        self.satisfier
+            .lookup_tap_key_spend_adaptor_sig()
+            .filter(|(_, sig)| self.policy.allows_schnorr(sig.hash_ty))
+    }
+
+    fn lookup_musig_partial_sigs(&self, p: &Pk, h: &TapLeafHash) -> Option<MuSigPartialSigs> {
+        self.satisfier
+            .lookup_musig_partial_sigs(p, h)
+            .filter(|sigs| self.policy.allows_schnorr(sigs.hash_ty))
+    }
+
+    fn lookup_tap_key_spend_musig_partial_sigs(&self) -> Option<MuSigPartialSigs> {
+        self.satisfier
+            .lookup_tap_key_spend_musig_partial_sigs()
+            .filter(|sigs| self.policy.allows_schnorr(sigs.hash_ty))
+    }
+
+    fn satisfy_options(&self) -> SatisfyOptions {
+        self.satisfier.satisfy_options()
+    }
+}
+
+/// An ECDSA adaptor signature, which becomes a signature once decrypted with
+/// the secret key of its encryption key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EcdsaAdaptorSig {
+    /// The adaptor signature
+    pub sig: secp256k1_zkp::EcdsaAdaptorSignature,
+    /// The adaptor point the signature is encrypted to
+    pub encryption_key: secp256k1_zkp::PublicKey,
+    /// The message signed, i.e. the sighash of the spending transaction
+    pub msg: secp256k1_zkp::Message,
+    /// The sighash type of the completed signature
+    pub hash_ty: elements::EcdsaSigHashType,
+}
+
+/// A Schnorr adaptor signature, or pre-signature, of BIP 340 signatures.
+///
+/// The completed signature has the nonce `nonce` and the scalar `s_hat + t`,
+/// where `t` is the secret key of `encryption_key`, negated if
+/// `nonce_parity` is set. This is the convention of the pre-signatures in
+/// the MuSig2 module of libsecp256k1-zkp.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SchnorrAdaptorSig {
+    /// The nonce of the completed signature
+    pub nonce: XOnlyPublicKey,
+    /// The scalar of the pre-signature
+    pub s_hat: [u8; 32],
+    /// Whether the adaptor secret is negated in the completed signature
+    pub nonce_parity: bool,
+    /// The adaptor point the signature is encrypted to
+    pub encryption_key: secp256k1_zkp::PublicKey,
+    /// The message signed, i.e. the sighash of the spending transaction
+    pub msg: secp256k1_zkp::Message,
+    /// The sighash type of the completed signature
+    pub hash_ty: elements::SchnorrSigHashType,
+}
+
+/// The MuSig2 partial signatures of the signers of an aggregate key in a
+/// signing session.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MuSigPartialSigs {
+    /// The signing session. For key spends, its key aggregation context
+    /// must include the taproot tweak, see [`MuSigKeyAgg::tap_tweak`].
+    ///
+    /// [`MuSigKeyAgg::tap_tweak`]: crate::descriptor::MuSigKeyAgg::tap_tweak
+    pub session: MuSigSession,
+    /// The partial signatures of the signers
+    pub partial_sigs: Vec<[u8; 32]>,
+    /// The sighash type of the aggregated signature
+    pub hash_ty: elements::SchnorrSigHashType,
+}
+
+impl EcdsaAdaptorSig {
+    /// Decrypt the adaptor signature with the secret key `t` of its
+    /// encryption key. The result is normalized to a low S value.
+    pub fn complete(&self, t: &secp256k1_zkp::SecretKey) -> Option<ElementsSig> {
+        let mut sig = self.sig.decrypt(t).ok()?;
+        sig.normalize_s();
+        Some((sig, self.hash_ty))
+    }
+}
+
+impl SchnorrAdaptorSig {
+    /// Complete the pre-signature with the secret key `t` of its
+    /// encryption key.
+    pub fn complete(&self, t: &secp256k1_zkp::SecretKey) -> Option<elements::SchnorrSig> {
+        let t = if self.nonce_parity { t.negate() } else { *t };
+        let s = match secp256k1_zkp::SecretKey::from_slice(&self.s_hat) {
+            Ok(s_hat) => s_hat
+                .add_tweak(&secp256k1_zkp::Scalar::from(t))
+                .ok()?
+                .secret_bytes(),
+            // s_hat is zero or not a scalar
+            Err(_) if self.s_hat == [0; 32] => t.secret_bytes(),
+            Err(_) => return None,
+        };
+        let mut bytes = [0; 64];
+        bytes[..32].copy_from_slice(&self.nonce.serialize());
+        bytes[32..].copy_from_slice(&s);
+        Some(elements::SchnorrSig {
+            sig: schnorr::Signature::from_slice(&bytes).ok()?,
+            hash_ty: self.hash_ty,
+        })
+    }
+}
+
+impl MuSigPartialSigs {
+    /// Aggregate the partial signatures into a BIP 340 signature, as
+    /// [`MuSigSession::aggregate`]. Returns `None` if a partial signature
+    /// is not a scalar or if the signature does not verify against the
+    /// aggregate key of the session.
+    pub fn aggregate<C: secp256k1_zkp::Verification>(
+        &self,
+        secp: &secp256k1_zkp::Secp256k1<C>,
+    ) -> Option<elements::SchnorrSig> {
+        Some(elements::SchnorrSig {
+            sig: self.session.aggregate(secp, &self.partial_sigs)?,
+            hash_ty: self.hash_ty,
+        })
+    }
+}
+
+/// A satisfier which completes the adaptor signatures of another satisfier
+/// whose adaptor secrets are known, for protocols like atomic swaps where a
+/// counterparty reveals the secret.
+///
+/// Signatures of the wrapped satisfier are preferred to completed adaptor
+/// signatures. Adaptor signatures without a known secret are not used, and
+/// neither are adaptor signatures or completed signatures that do not verify
+/// against their key and message. Keys of `pkh` fragments are found with
+/// [`Satisfier::lookup_raw_pkh_pk`], and the key spend uses
+/// [`Satisfier::lookup_tap_key_spend_adaptor_sig`].
+#[derive(Debug, Clone)]
+pub struct AdaptorCompleter<S> {
+    satisfier: S,
+    secrets: BTreeMap<secp256k1_zkp::PublicKey, secp256k1_zkp::SecretKey>,
+    secp: secp256k1_zkp::Secp256k1<secp256k1_zkp::VerifyOnly>,
+}
+
+impl<S> AdaptorCompleter<S> {
+    /// Complete the adaptor signatures of `satisfier`, without any secret yet
+    pub fn new(satisfier: S) -> Self {
+        AdaptorCompleter {
+            satisfier,
+            secrets: BTreeMap::new(),
+            secp: secp256k1_zkp::Secp256k1::verification_only(),
+        }
+    }
+
+    /// Add the secret key of an adaptor point, returning the point
+    pub fn add_secret<C: secp256k1_zkp::Signing>(
+        &mut self,
+        secp: &secp256k1_zkp::Secp256k1<C>,
+        secret: secp256k1_zkp::SecretKey,
+    ) -> secp256k1_zkp::PublicKey {
+        let point = secp256k1_zkp::PublicKey::from_secret_key(secp, &secret);
+        self.secrets.insert(point, secret);
+        point
+    }
+
+    /// The wrapped satisfier
+    pub fn into_inner(self) -> S {
+        self.satisfier
+    }
+
+    // Complete an adaptor signature of `pk`, checking both the adaptor
+    // signature and the completed signature
+    fn complete_ecdsa(
+        &self,
+        pk: &bitcoin::PublicKey,
+        adaptor: &EcdsaAdaptorSig,
+    ) -> Option<ElementsSig> {
+        let secret = self.secrets.get(&adaptor.encryption_key)?;
+        adaptor
+            .sig
+            .verify(&self.secp, &adaptor.msg, &pk.inner, &adaptor.encryption_key)
+            .ok()?;
+        let sig = adaptor.complete(secret)?;
+        self.secp
+            .verify_ecdsa(&adaptor.msg, &sig.0, &pk.inner)
+            .ok()?;
+        Some(sig)
+    }
+
+    // Complete a pre-signature of `pk`, checking the completed signature
+    fn complete_schnorr(
+        &self,
+        pk: &XOnlyPublicKey,
+        adaptor: &SchnorrAdaptorSig,
+    ) -> Option<elements::SchnorrSig> {
+        let sig = adaptor.complete(self.secrets.get(&adaptor.encryption_key)?)?;
+        self.secp.verify_schnorr(&sig.sig, &adaptor.msg, pk).ok()?;
+        Some(sig)
+    }
+}
+
+impl<Pk: MiniscriptKey + ToPublicKey, S: Satisfier<Pk>> Satisfier<Pk> for AdaptorCompleter<S> {
+    fn lookup_ecdsa_sig(&self, p: &Pk) -> Option<ElementsSig> {
+        self.satisfier.lookup_ecdsa_sig(p).or_else(|| {
+            let adaptor = self.satisfier.lookup_ecdsa_adaptor_sig(p)?;
+            self.complete_ecdsa(&p.to_public_key(), &adaptor)
+        })
+    }
+
+    fn check_ecdsa_sig(&self, pk: &bitcoin::PublicKey, sig: &ElementsSig) -> bool {
+        self.satisfier.check_ecdsa_sig(pk, sig)
+    }
+
+    fn lookup_tap_leaf_script_sig(&self, p: &Pk, h: &TapLeafHash) -> Option<elements::SchnorrSig> {
+        self.satisfier.lookup_tap_leaf_script_sig(p, h).or_else(|| {
+            let adaptor = self.satisfier.lookup_schnorr_adaptor_sig(p, h)?;
+            self.complete_schnorr(&p.to_x_only_pubkey(), &adaptor)
+        })
+    }
+
+    fn lookup_raw_pkh_pk(&self, pkh: &hash160::Hash) -> Option<Pk> {
+        self.satisfier.lookup_raw_pkh_pk(pkh)
+    }
+
+    fn lookup_raw_pkh_ecdsa_sig(
+        &self,
+        pkh: &hash160::Hash,
+    ) -> Option<(bitcoin::PublicKey, ElementsSig)> {
+        self.satisfier.lookup_raw_pkh_ecdsa_sig(pkh).or_else(|| {
+            let pk = self.satisfier.lookup_raw_pkh_pk(pkh)?;
+            let adaptor = self.satisfier.lookup_ecdsa_adaptor_sig(&pk)?;
+            let pk = pk.to_public_key();
+            Some((pk, self.complete_ecdsa(&pk, &adaptor)?))
+        })
+    }
+
+    fn lookup_tap_key_spend_sig(&self) -> Option<elements::SchnorrSig> {
+        self.satisfier.lookup_tap_key_spend_sig().or_else(|| {
+            let (pk, adaptor) = self.satisfier.lookup_tap_key_spend_adaptor_sig()?;
+            self.complete_schnorr(&pk, &adaptor)
+        })
+    }
+
+    fn lookup_raw_pkh_tap_leaf_script_sig(
+        &self,
+        pkh: &(hash160::Hash, TapLeafHash),
+    ) -> Option<(XOnlyPublicKey, elements::SchnorrSig)> {
+        self.satisfier
+            .lookup_raw_pkh_tap_leaf_script_sig(pkh)
+            .or_else(|| {
+                let pk = self.satisfier.lookup_raw_pkh_pk(&pkh.0)?;
+                let adaptor = self.satisfier.lookup_schnorr_adaptor_sig(&pk, &pkh.1)?;
+                let pk = pk.to_x_only_pubkey();
+                Some((pk, self.complete_schnorr(&pk, &adaptor)?))
+            })
+    }
+
+    fn lookup_tap_control_block_map(
+        &self,
+    ) -> Option<&BTreeMap<ControlBlock, (elements::Script, LeafVersion)>> {
+        self.satisfier.lookup_tap_control_block_map()
+    }
+
+    fn lookup_sha256(&self, h: &Pk::Sha256) -> Option<Preimage32> {
+        self.satisfier.lookup_sha256(h)
+    }
+
+    fn lookup_hash256(&self, h: &Pk::Hash256) -> Option<Preimage32> {
+        self.satisfier.lookup_hash256(h)
+    }
+
+    fn lookup_ripemd160(&self, h: &Pk::Ripemd160) -> Option<Preimage32> {
+        self.satisfier.lookup_ripemd160(h)
+    }
+
+    fn lookup_hash160(&self, h: &Pk::Hash160) -> Option<Preimage32> {
+        self.satisfier.lookup_hash160(h)
+    }
+
+    fn check_older(&self, t: Sequence) -> bool {
+        self.satisfier.check_older(t)
+    }
+
+    fn check_after(&self, n: LockTime) -> bool {
+        self.satisfier.check_after(n)
+    }
+
+    fn lookup_nversion(&self) -> Option<u32> {
+        self.satisfier.lookup_nversion()
+    }
+
+    fn lookup_hashprevouts(&self) -> Option<sha256d::Hash> {
+        self.satisfier.lookup_hashprevouts()
+    }
+
+    fn lookup_hashsequence(&self) -> Option<sha256d::Hash> {
+        self.satisfier.lookup_hashsequence()
+    }
+
+    fn lookup_hashissuances(&self) -> Option<sha256d::Hash> {
+        self.satisfier.lookup_hashissuances()
+    }
+
+    fn lookup_outpoint(&self) -> Option<OutPoint> {
+        self.satisfier.lookup_outpoint()
+    }
+
+    fn lookup_scriptcode(&self) -> Option<&Script> {
+        self.satisfier.lookup_scriptcode()
+    }
+
+    fn lookup_value(&self) -> Option<confidential::Value> {
+        self.satisfier.lookup_value()
+    }
+
+    fn lookup_nsequence(&self) -> Option<u32> {
+        self.satisfier.lookup_nsequence()
+    }
+
+    fn lookup_outputs(&self) -> Option<&[elements::TxOut]> {
+        self.satisfier.lookup_outputs()
+    }
+
+    fn lookup_nlocktime(&self) -> Option<u32> {
+        self.satisfier.lookup_nlocktime()
+    }
+
+    fn lookup_sighashu32(&self) -> Option<u32> {
+        self.satisfier.lookup_sighashu32()
+    }
+
+    fn lookup_spent_utxos(&self) -> Option<&[elements::TxOut]> {
+        self.satisfier.lookup_spent_utxos()
+    }
+
+    fn lookup_tx(&self) -> Option<&elements::Transaction> {
+        self.satisfier.lookup_tx()
+    }
+
+    fn lookup_curr_inp(&self) -> Option<usize> {
+        self.satisfier.lookup_curr_inp()
+    }
+
+    fn lookup_csfs_sig(&self, pk: &XOnlyPublicKey, msg: &CsfsMsg) -> Option<schnorr::Signature> {
+        self.satisfier.lookup_csfs_sig(pk, msg)
+    }
+
+    fn lookup_ecdsa_adaptor_sig(&self, p: &Pk) -> Option<EcdsaAdaptorSig> {
+        self.satisfier.lookup_ecdsa_adaptor_sig(p)
+    }
+
+    fn lookup_schnorr_adaptor_sig(&self, p: &Pk, h: &TapLeafHash) -> Option<SchnorrAdaptorSig> {
+        self.satisfier.lookup_schnorr_adaptor_sig(p, h)
+    }
+
+    fn lookup_tap_key_spend_adaptor_sig(&self) -> Option<(XOnlyPublicKey, SchnorrAdaptorSig)> {
+        self.satisfier.lookup_tap_key_spend_adaptor_sig()
+    }
+
+    fn lookup_musig_partial_sigs(&self, p: &Pk, h: &TapLeafHash) -> Option<MuSigPartialSigs> {
+        self.satisfier.lookup_musig_partial_sigs(p, h)
+    }
+
+    fn lookup_tap_key_spend_musig_partial_sigs(&self) -> Option<MuSigPartialSigs> {
+        self.satisfier.lookup_tap_key_spend_musig_partial_sigs()
+    }
+
+    fn satisfy_options(&self) -> SatisfyOptions {
+        self.satisfier.satisfy_options()
+    }
+}
+
+/// A satisfier which aggregates the MuSig2 partial signatures of another
+/// satisfier into signatures of the aggregate keys, for `musig()` keys whose
+/// signers each provide a partial signature.
+///
+/// Signatures of the wrapped satisfier are preferred to aggregated partial
+/// signatures. Aggregated signatures are only used if they verify against
+/// the aggregate key of their session, which for script spends must be the
+/// key of the script.
+#[derive(Debug, Clone)]
+pub struct MuSigAggregator<S> {
+    satisfier: S,
+    secp: secp256k1_zkp::Secp256k1<secp256k1_zkp::VerifyOnly>,
+}
+
+impl<S> MuSigAggregator<S> {
+    /// Aggregate the partial signatures of `satisfier`
+    pub fn new(satisfier: S) -> Self {
+        MuSigAggregator {
+            satisfier,
+            secp: secp256k1_zkp::Secp256k1::verification_only(),
+        }
+    }
+
+    /// The wrapped satisfier
+    pub fn into_inner(self) -> S {
+        self.satisfier
+    }
+}
+
+impl<Pk: MiniscriptKey + ToPublicKey, S: Satisfier<Pk>> Satisfier<Pk> for MuSigAggregator<S> {
+    fn lookup_ecdsa_sig(&self, p: &Pk) -> Option<ElementsSig> {
+        self.satisfier.lookup_ecdsa_sig(p)
+    }
+
+    fn check_ecdsa_sig(&self, pk: &bitcoin::PublicKey, sig: &ElementsSig) -> bool {
+        self.satisfier.check_ecdsa_sig(pk, sig)
+    }
+
+    fn lookup_tap_leaf_script_sig(&self, p: &Pk, h: &TapLeafHash) -> Option<elements::SchnorrSig> {
+        self.satisfier.lookup_tap_leaf_script_sig(p, h).or_else(|| {
+            let partial = self.satisfier.lookup_musig_partial_sigs(p, h)?;
+            let key = partial.session.key_agg().aggregate_key();
+            if key.x_only_public_key().0 != p.to_x_only_pubkey() {
+                return None;
+            }
+            partial.aggregate(&self.secp)
+        })
+    }
+
+    fn lookup_raw_pkh_pk(&self, pkh: &hash160::Hash) -> Option<Pk> {
+        self.satisfier.lookup_raw_pkh_pk(pkh)
+    }
+
+    fn lookup_raw_pkh_ecdsa_sig(
+        &self,
+        pkh: &hash160::Hash,
+    ) -> Option<(bitcoin::PublicKey, ElementsSig)> {
+        self.satisfier.lookup_raw_pkh_ecdsa_sig(pkh)
+    }
+
+    fn lookup_tap_key_spend_sig(&self) -> Option<elements::SchnorrSig> {
+        self.satisfier.lookup_tap_key_spend_sig().or_else(|| {
+            self.satisfier
+                .lookup_tap_key_spend_musig_partial_sigs()?
+                .aggregate(&self.secp)
+        })
+    }
+
+    fn lookup_raw_pkh_tap_leaf_script_sig(
+        &self,
+        pkh: &(hash160::Hash, TapLeafHash),
+    ) -> Option<(XOnlyPublicKey, elements::SchnorrSig)> {
+        self.satisfier.lookup_raw_pkh_tap_leaf_script_sig(pkh)
+    }
+
+    fn lookup_tap_control_block_map(
+        &self,
+    ) -> Option<&BTreeMap<ControlBlock, (elements::Script, LeafVersion)>> {
+        self.satisfier.lookup_tap_control_block_map()
+    }
+
+    fn lookup_sha256(&self, h: &Pk::Sha256) -> Option<Preimage32> {
+        self.satisfier.lookup_sha256(h)
+    }
+
+    fn lookup_hash256(&self, h: &Pk::Hash256) -> Option<Preimage32> {
+        self.satisfier.lookup_hash256(h)
+    }
+
+    fn lookup_ripemd160(&self, h: &Pk::Ripemd160) -> Option<Preimage32> {
+        self.satisfier.lookup_ripemd160(h)
+    }
+
+    fn lookup_hash160(&self, h: &Pk::Hash160) -> Option<Preimage32> {
+        self.satisfier.lookup_hash160(h)
+    }
+
+    fn check_older(&self, t: Sequence) -> bool {
+        self.satisfier.check_older(t)
+    }
+
+    fn check_after(&self, n: LockTime) -> bool {
+        self.satisfier.check_after(n)
+    }
+
+    fn lookup_nversion(&self) -> Option<u32> {
+        self.satisfier.lookup_nversion()
+    }
+
+    fn lookup_hashprevouts(&self) -> Option<sha256d::Hash> {
+        self.satisfier.lookup_hashprevouts()
+    }
+
+    fn lookup_hashsequence(&self) -> Option<sha256d::Hash> {
+        self.satisfier.lookup_hashsequence()
+    }
+
+    fn lookup_hashissuances(&self) -> Option<sha256d::Hash> {
+        self.satisfier.lookup_hashissuances()
+    }
+
+    fn lookup_outpoint(&self) -> Option<OutPoint> {
+        self.satisfier.lookup_outpoint()
+    }
+
+    fn lookup_scriptcode(&self) -> Option<&Script> {
+        self.satisfier.lookup_scriptcode()
+    }
+
+    fn lookup_value(&self) -> Option<confidential::Value> {
+        self.satisfier.lookup_value()
+    }
+
+    fn lookup_nsequence(&self) -> Option<u32> {
+        self.satisfier.lookup_nsequence()
+    }
+
+    fn lookup_outputs(&self) -> Option<&[elements::TxOut]> {
+        self.satisfier.lookup_outputs()
+    }
+
+    fn lookup_nlocktime(&self) -> Option<u32> {
+        self.satisfier.lookup_nlocktime()
+    }
+
+    fn lookup_sighashu32(&self) -> Option<u32> {
+        self.satisfier.lookup_sighashu32()
+    }
+
+    fn lookup_spent_utxos(&self) -> Option<&[elements::TxOut]> {
+        self.satisfier.lookup_spent_utxos()
+    }
+
+    fn lookup_tx(&self) -> Option<&elements::Transaction> {
+        self.satisfier.lookup_tx()
+    }
+
+    fn lookup_curr_inp(&self) -> Option<usize> {
+        self.satisfier.lookup_curr_inp()
+    }
+
+    fn lookup_csfs_sig(&self, pk: &XOnlyPublicKey, msg: &CsfsMsg) -> Option<schnorr::Signature> {
+        self.satisfier.lookup_csfs_sig(pk, msg)
+    }
+
+    fn lookup_ecdsa_adaptor_sig(&self, p: &Pk) -> Option<EcdsaAdaptorSig> {
+        self.satisfier.lookup_ecdsa_adaptor_sig(p)
+    }
+
+    fn lookup_schnorr_adaptor_sig(&self, p: &Pk, h: &TapLeafHash) -> Option<SchnorrAdaptorSig> {
+        self.satisfier.lookup_schnorr_adaptor_sig(p, h)
+    }
+
+    fn lookup_tap_key_spend_adaptor_sig(&self) -> Option<(XOnlyPublicKey, SchnorrAdaptorSig)> {
+        self.satisfier.lookup_tap_key_spend_adaptor_sig()
+    }
+
+    fn lookup_musig_partial_sigs(&self, p: &Pk, h: &TapLeafHash) -> Option<MuSigPartialSigs> {
+        self.satisfier.lookup_musig_partial_sigs(p, h)
+    }
+
+    fn lookup_tap_key_spend_musig_partial_sigs(&self) -> Option<MuSigPartialSigs> {
+        self.satisfier.lookup_tap_key_spend_musig_partial_sigs()
+    }
+
+    fn satisfy_options(&self) -> SatisfyOptions {
+        self.satisfier.satisfy_options()
+    }
+}
+
+/// Checks the ECDSA signatures returned by a satisfier with
+/// [`Satisfier::check_ecdsa_sig`] as the satisfaction looks them up,
+/// remembering those which fail so that [`CheckedSigs::check_witness`] only
+/// rejects them if the chosen satisfaction uses them
+pub(crate) struct CheckedSigs<'s, S> {
+    satisfier: &'s S,
+    rejected: RefCell<Vec<(bitcoin::PublicKey, Vec<u8>)>>,
+}
+
+impl<'s, S> CheckedSigs<'s, S> {
+    /// Check the signatures of `satisfier`
+    pub(crate) fn new(satisfier: &'s S) -> Self {
+        CheckedSigs {
+            satisfier,
+            rejected: RefCell::new(vec![]),
+        }
+    }
+
+    /// Fail with [`Error::NonCanonicalSig`] if `witness` contains a
+    /// signature that failed the check
+    pub(crate) fn check_witness(&self, witness: &[Vec<u8>]) -> Result<(), Error> {
+        match self
+            .rejected
+            .borrow()
+            .iter()
+            .find(|(_, sig)| witness.contains(sig))
+        {
+            Some((pk, _)) => Err(Error::NonCanonicalSig(*pk)),
+            None => Ok(()),
+        }
+    }
+
+    fn check<Pk>(&self, pk: bitcoin::PublicKey, sig: &ElementsSig)
+    where
+        Pk: MiniscriptKey + ToPublicKey,
+        S: Satisfier<Pk>,
+    {
+        if !self.satisfier.check_ecdsa_sig(&pk, sig) {
+            self.rejected
+                .borrow_mut()
+                .push((pk, elementssig_to_rawsig(sig)));
+        }
+    }
+}
+
+impl<'s, Pk: MiniscriptKey + ToPublicKey, S: Satisfier<Pk>> Satisfier<Pk> for CheckedSigs<'s, S> {
+    fn lookup_ecdsa_sig(&self, p: &Pk) -> Option<ElementsSig> {
+        let sig = self.satisfier.lookup_ecdsa_sig(p)?;
+        self.check::<Pk>(p.to_public_key(), &sig);
+        Some(sig)
+    }
+
+    fn check_ecdsa_sig(&self, pk: &bitcoin::PublicKey, sig: &ElementsSig) -> bool {
+        self.satisfier.check_ecdsa_sig(pk, sig)
+    }
+
+    fn lookup_tap_leaf_script_sig(&self, p: &Pk, h: &TapLeafHash) -> Option<elements::SchnorrSig> {
+        self.satisfier.lookup_tap_leaf_script_sig(p, h)
+    }
+
+    fn lookup_raw_pkh_pk(&self, pkh: &hash160::Hash) -> Option<Pk> {
+        self.satisfier.lookup_raw_pkh_pk(pkh)
+    }
+
+    fn lookup_raw_pkh_ecdsa_sig(
+        &self,
+        pkh: &hash160::Hash,
+    ) -> Option<(bitcoin::PublicKey, ElementsSig)> {
+        let (pk, sig) = self.satisfier.lookup_raw_pkh_ecdsa_sig(pkh)?;
+        self.check::<Pk>(pk, &sig);
+        Some((pk, sig))
+    }
+
+    fn lookup_tap_key_spend_sig(&self) -> Option<elements::SchnorrSig> {
+        self.satisfier.lookup_tap_key_spend_sig()
+    }
+
+    fn lookup_raw_pkh_tap_leaf_script_sig(
+        &self,
+        pkh: &(hash160::Hash, TapLeafHash),
+    ) -> Option<(XOnlyPublicKey, elements::SchnorrSig)> {
+        self.satisfier.lookup_raw_pkh_tap_leaf_script_sig(pkh)
+    }
+
+    fn lookup_tap_control_block_map(
+        &self,
+    ) -> Option<&BTreeMap<ControlBlock, (elements::Script, LeafVersion)>> {
+        self.satisfier.lookup_tap_control_block_map()
+    }
+
+    fn lookup_sha256(&self, h: &Pk::Sha256) -> Option<Preimage32> {
+        self.satisfier.lookup_sha256(h)
+    }
+
+    fn lookup_hash256(&self, h: &Pk::Hash256) -> Option<Preimage32> {
+        self.satisfier.lookup_hash256(h)
+    }
+
+    fn lookup_ripemd160(&self, h: &Pk::Ripemd160) -> Option<Preimage32> {
+        self.satisfier.lookup_ripemd160(h)
+    }
+
+    fn lookup_hash160(&self, h: &Pk::Hash160) -> Option<Preimage32> {
+        self.satisfier.lookup_hash160(h)
+    }
+
+    fn check_older(&self, t: Sequence) -> bool {
+        self.satisfier.check_older(t)
+    }
+
+    fn check_after(&self, n: LockTime) -> bool {
+        self.satisfier.check_after(n)
+    }
+
+    fn lookup_nversion(&self) -> Option<u32> {
+        self.satisfier.lookup_nversion()
+    }
+
+    fn lookup_hashprevouts(&self) -> Option<sha256d::Hash> {
+        self.satisfier.lookup_hashprevouts()
+    }
+
+    fn lookup_hashsequence(&self) -> Option<sha256d::Hash> {
+        self.satisfier.lookup_hashsequence()
+    }
+
+    fn lookup_hashissuances(&self) -> Option<sha256d::Hash> {
+        self.satisfier.lookup_hashissuances()
+    }
+
+    fn lookup_outpoint(&self) -> Option<OutPoint> {
+        self.satisfier.lookup_outpoint()
+    }
+
+    fn lookup_scriptcode(&self) -> Option<&Script> {
+        self.satisfier.lookup_scriptcode()
+    }
+
+    fn lookup_value(&self) -> Option<confidential::Value> {
+        self.satisfier.lookup_value()
+    }
+
+    fn lookup_nsequence(&self) -> Option<u32> {
+        self.satisfier.lookup_nsequence()
+    }
+
+    fn lookup_outputs(&self) -> Option<&[elements::TxOut]> {
+        self.satisfier.lookup_outputs()
+    }
+
+    fn lookup_nlocktime(&self) -> Option<u32> {
+        self.satisfier.lookup_nlocktime()
+    }
+
+    fn lookup_sighashu32(&self) -> Option<u32> {
+        self.satisfier.lookup_sighashu32()
+    }
+
+    fn lookup_spent_utxos(&self) -> Option<&[elements::TxOut]> {
+        self.satisfier.lookup_spent_utxos()
+    }
+
+    fn lookup_tx(&self) -> Option<&elements::Transaction> {
+        self.satisfier.lookup_tx()
+    }
+
+    fn lookup_curr_inp(&self) -> Option<usize> {
+        self.satisfier.lookup_curr_inp()
+    }
+
+    fn lookup_csfs_sig(&self, pk: &XOnlyPublicKey, msg: &CsfsMsg) -> Option<schnorr::Signature> {
+        self.satisfier.lookup_csfs_sig(pk, msg)
+    }
+
+    fn lookup_ecdsa_adaptor_sig(&self, p: &Pk) -> Option<EcdsaAdaptorSig> {
+        self.satisfier.lookup_ecdsa_adaptor_sig(p)
+    }
+
+    fn lookup_schnorr_adaptor_sig(&self, p: &Pk, h: &TapLeafHash) -> Option<SchnorrAdaptorSig> {
+        self.satisfier.lookup_schnorr_adaptor_sig(p, h)
+    }
+
+    fn lookup_tap_key_spend_adaptor_sig(&self) -> Option<(XOnlyPublicKey, SchnorrAdaptorSig)> {
+        self.satisfier.lookup_tap_key_spend_adaptor_sig()
+    }
+
+    fn lookup_musig_partial_sigs(&self, p: &Pk, h: &TapLeafHash) -> Option<MuSigPartialSigs> {
+        self.satisfier.lookup_musig_partial_sigs(p, h)
+    }
+
+    fn lookup_tap_key_spend_musig_partial_sigs(&self) -> Option<MuSigPartialSigs> {
+        self.satisfier.lookup_tap_key_spend_musig_partial_sigs()
+    }
+
+    fn satisfy_options(&self) -> SatisfyOptions {
+        self.satisfier.satisfy_options()
+    }
+}
+
+macro_rules! impl_tuple_satisfier {
+    ($($ty:ident),*) => {
+        #[allow(non_snake_case)]
+        impl<$($ty,)* Pk> Satisfier<Pk> for ($($ty,)*)
+        where
+            Pk: MiniscriptKey + ToPublicKey,
+            $($ty: Satisfier< Pk>,)*
+        {
+            fn lookup_ecdsa_sig(&self, key: &Pk) -> Option<ElementsSig> {
+                let &($(ref $ty,)*) = self;
+                $(
+                    if let Some(result) = $ty.lookup_ecdsa_sig(key) {
+                        return Some(result);
+                    }
+                )*
+                None
+            }
+
+            fn check_ecdsa_sig(&self, pk: &bitcoin::PublicKey, sig: &ElementsSig) -> bool {
+                let &($(ref $ty,)*) = self;
+                $(
+                    if !$ty.check_ecdsa_sig(pk, sig) {
+                        return false;
+                    }
+                )*
+                true
+            }
+
+            fn lookup_tap_key_spend_sig(&self) -> Option<elements::SchnorrSig> {
+                let &($(ref $ty,)*) = self;
+                $(
+                    if let Some(result) = $ty.lookup_tap_key_spend_sig() {
+                        return Some(result);
+                    }
+                )*
+                None
+            }
+
+            fn lookup_tap_leaf_script_sig(&self, key: &Pk, h: &TapLeafHash) -> Option<elements::SchnorrSig> {
+                let &($(ref $ty,)*) = self;
+                $(
+                    if let Some(result) = $ty.lookup_tap_leaf_script_sig(key, h) {
+                        return Some(result);
+                    }
+                )*
+                None
+            }
+
+            fn lookup_raw_pkh_ecdsa_sig(
+                &self,
+                key_hash: &hash160::Hash,
+            ) -> Option<(bitcoin::PublicKey, ElementsSig)> {
+                let &($(ref $ty,)*) = self;
+                $(
+                    if let Some(result) = $ty.lookup_raw_pkh_ecdsa_sig(key_hash) {
+                        return Some(result);
+                    }
+                )*
+                None
+            }
+
+            fn lookup_raw_pkh_tap_leaf_script_sig(
+                &self,
+                key_hash: &(hash160::Hash, TapLeafHash),
+            ) -> Option<(XOnlyPublicKey, elements::SchnorrSig)> {
+                let &($(ref $ty,)*) = self;
+                $(
+                    if let Some(result) = $ty.lookup_raw_pkh_tap_leaf_script_sig(key_hash) {
+                        return Some(result);
+                    }
+                )*
+                None
+            }
+
+            fn lookup_raw_pkh_pk(
+                &self,
+                key_hash: &hash160::Hash,
+            ) -> Option<Pk> {
+                let &($(ref $ty,)*) = self;
+                $(
+                    if let Some(result) = $ty.lookup_raw_pkh_pk(key_hash) {
+                        return Some(result);
+                    }
+                )*
+                None
+            }
+
+            fn lookup_tap_control_block_map(
+                &self,
+            ) -> Option<&BTreeMap<ControlBlock, (elements::Script, LeafVersion)>> {
+                let &($(ref $ty,)*) = self;
+                $(
+                    if let Some(result) = $ty.lookup_tap_control_block_map() {
+                        return Some(result);
+                    }
+                )*
+                None
+            }
+
+            fn lookup_sha256(&self, h: &Pk::Sha256) -> Option<Preimage32> {
+                let &($(ref $ty,)*) = self;
+                $(
+                    if let Some(result) = $ty.lookup_sha256(h) {
+                        return Some(result);
+                    }
+                )*
+                None
+            }
+
+            fn lookup_hash256(&self, h: &Pk::Hash256) -> Option<Preimage32> {
+                let &($(ref $ty,)*) = self;
+                $(
+                    if let Some(result) = $ty.lookup_hash256(h) {
+                        return Some(result);
+                    }
+                )*
+                None
+            }
+
+            fn lookup_ripemd160(&self, h: &Pk::Ripemd160) -> Option<Preimage32> {
+                let &($(ref $ty,)*) = self;
+                $(
+                    if let Some(result) = $ty.lookup_ripemd160(h) {
+                        return Some(result);
+                    }
+                )*
+                None
+            }
+
+            fn lookup_hash160(&self, h: &Pk::Hash160) -> Option<Preimage32> {
+                let &($(ref $ty,)*) = self;
+                $(
+                    if let Some(result) = $ty.lookup_hash160(h) {
+                        return Some(result);
+                    }
+                )*
+                None
+            }
+
+            fn check_older(&self, n: Sequence) -> bool {
+                let &($(ref $ty,)*) = self;
+                $(
+                    if $ty.check_older(n) {
+                        return true;
+                    }
+                )*
+                false
+            }
+
+            fn check_after(&self, n: LockTime) -> bool {
+                let &($(ref $ty,)*) = self;
+                $(
+                    if $ty.check_after(n) {
+                        return true;
+                    }
+                )*
+                false
+            }
+
+            fn lookup_nversion(&self) -> Option<u32> {
+                let &($(ref $ty,)*) = self;
+                $(
+                    if let Some(result) = $ty.lookup_nversion() {
+                        return Some(result);
+                    }
+                )*
+                None
+            }
+
+            fn lookup_hashprevouts(&self) -> Option<sha256d::Hash> {
+                let &($(ref $ty,)*) = self;
+                $(
+                    if let Some(result) = $ty.lookup_hashprevouts() {
+                        return Some(result);
+                    }
+                )*
+                None
+            }
+
+            fn lookup_hashsequence(&self) -> Option<sha256d::Hash> {
+                let &($(ref $ty,)*) = self;
+                $(
+                    if let Some(result) = $ty.lookup_hashsequence() {
+                        return Some(result);
+                    }
+                )*
+                None
+            }
+
+            fn lookup_hashissuances(&self) -> Option<sha256d::Hash> {
+                let &($(ref $ty,)*) = self;
                 $(
                     if let Some(result) = $ty.lookup_hashissuances() {
                         return Some(result);
@@ -865,19 +2074,313 @@ macro_rules! impl_tuple_satisfier {
                 )*
                 None
             }
+
+            fn lookup_ecdsa_adaptor_sig(&self, key: &Pk) -> Option<EcdsaAdaptorSig> {
+                let &($(ref $ty,)*) = self;
+                $(
+                    if let Some(result) = $ty.lookup_ecdsa_adaptor_sig(key) {
+                        return Some(result);
+                    }
+                )*
+                None
+            }
+
+            fn lookup_schnorr_adaptor_sig(&self, key: &Pk, h: &TapLeafHash) -> Option<SchnorrAdaptorSig> {
+                let &($(ref $ty,)*) = self;
+                $(
+                    if let Some(result) = $ty.lookup_schnorr_adaptor_sig(key, h) {
+                        return Some(result);
+                    }
+                )*
+                None
+            }
+
+            fn lookup_tap_key_spend_adaptor_sig(&self) -> Option<(XOnlyPublicKey, SchnorrAdaptorSig)> {
+                let &($(ref $ty,)*) = self;
+                $(
+                    if let Some(result) = $ty.lookup_tap_key_spend_adaptor_sig() {
+                        return Some(result);
+                    }
+                )*
+                None
+            }
+
+            fn lookup_musig_partial_sigs(&self, key: &Pk, h: &TapLeafHash) -> Option<MuSigPartialSigs> {
+                let &($(ref $ty,)*) = self;
+                $(
+                    if let Some(result) = $ty.lookup_musig_partial_sigs(key, h) {
+                        return Some(result);
+                    }
+                )*
+                None
+            }
+
+            fn lookup_tap_key_spend_musig_partial_sigs(&self) -> Option<MuSigPartialSigs> {
+                let &($(ref $ty,)*) = self;
+                $(
+                    if let Some(result) = $ty.lookup_tap_key_spend_musig_partial_sigs() {
+                        return Some(result);
+                    }
+                )*
+                None
+            }
+
+            fn satisfy_options(&self) -> SatisfyOptions {
+                let &($(ref $ty,)*) = self;
+                let options = SatisfyOptions::default();
+                $(
+                    let options = options.combine($ty.satisfy_options());
+                )*
+                options
+            }
         }
     }
 }
 
-impl_tuple_satisfier!(A);
-impl_tuple_satisfier!(A, B);
-impl_tuple_satisfier!(A, B, C);
-impl_tuple_satisfier!(A, B, C, D);
-impl_tuple_satisfier!(A, B, C, D, E);
-impl_tuple_satisfier!(A, B, C, D, E, F);
-impl_tuple_satisfier!(A, B, C, D, E, F, G);
-impl_tuple_satisfier!(A, B, C, D, E, F, G, H);
-
+impl_tuple_satisfier!(A);
+impl_tuple_satisfier!(A, B);
+impl_tuple_satisfier!(A, B, C);
+impl_tuple_satisfier!(A, B, C, D);
+impl_tuple_satisfier!(A, B, C, D, E);
+impl_tuple_satisfier!(A, B, C, D, E, F);
+impl_tuple_satisfier!(A, B, C, D, E, F, G);
+impl_tuple_satisfier!(A, B, C, D, E, F, G, H);
+
+/// A satisfier chaining any number of satisfiers, for when the number of
+/// satisfiers is only known at runtime or exceeds what tuples support.
+///
+/// Lookups are answered by the first satisfier, in the order they were
+/// pushed, that has an answer, so earlier satisfiers take priority. Like for
+/// tuples, a timelock is satisfied if any satisfier accepts it, a signature
+/// is only accepted if every satisfier accepts it, and the strictest
+/// [`SatisfyOptions`] of all satisfiers apply.
+///
+/// ```
+/// use std::collections::HashMap;
+///
+/// use elements::hashes::sha256;
+/// use elements_miniscript::{Preimage32, SatisfierStack};
+///
+/// let preimages: HashMap<sha256::Hash, Preimage32> = HashMap::new();
+/// let older = elements::Sequence::from_height(144);
+/// let stack = SatisfierStack::<bitcoin::PublicKey>::new()
+///     .push(&preimages)
+///     .push(&older);
+/// assert_eq!(stack.len(), 2);
+/// ```
+pub struct SatisfierStack<'a, Pk: MiniscriptKey + ToPublicKey> {
+    satisfiers: Vec<&'a dyn Satisfier<Pk>>,
+}
+
+impl<'a, Pk: MiniscriptKey + ToPublicKey> SatisfierStack<'a, Pk> {
+    /// Create an empty stack, which satisfies nothing
+    pub fn new() -> Self {
+        SatisfierStack { satisfiers: vec![] }
+    }
+
+    /// Add a satisfier, with a lower priority than the ones already added
+    pub fn push(mut self, satisfier: &'a dyn Satisfier<Pk>) -> Self {
+        self.satisfiers.push(satisfier);
+        self
+    }
+
+    /// The number of satisfiers
+    pub fn len(&self) -> usize {
+        self.satisfiers.len()
+    }
+
+    /// Whether there are no satisfiers
+    pub fn is_empty(&self) -> bool {
+        self.satisfiers.is_empty()
+    }
+}
+
+impl<'a, Pk: MiniscriptKey + ToPublicKey> Default for SatisfierStack<'a, Pk> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, Pk: MiniscriptKey + ToPublicKey> Satisfier<Pk> for SatisfierStack<'a, Pk> {
+    fn lookup_ecdsa_sig(&self, p: &Pk) -> Option<ElementsSig> {
+        self.satisfiers.iter().find_map(|s| s.lookup_ecdsa_sig(p))
+    }
+
+    fn check_ecdsa_sig(&self, pk: &bitcoin::PublicKey, sig: &ElementsSig) -> bool {
+        self.satisfiers.iter().all(|s| s.check_ecdsa_sig(pk, sig))
+    }
+
+    fn lookup_tap_key_spend_sig(&self) -> Option<elements::SchnorrSig> {
+        self.satisfiers
+            .iter()
+            .find_map(|s| s.lookup_tap_key_spend_sig())
+    }
+
+    fn lookup_tap_leaf_script_sig(&self, p: &Pk, h: &TapLeafHash) -> Option<elements::SchnorrSig> {
+        self.satisfiers
+            .iter()
+            .find_map(|s| s.lookup_tap_leaf_script_sig(p, h))
+    }
+
+    fn lookup_tap_control_block_map(
+        &self,
+    ) -> Option<&BTreeMap<ControlBlock, (elements::Script, LeafVersion)>> {
+        self.satisfiers
+            .iter()
+            .find_map(|s| s.lookup_tap_control_block_map())
+    }
+
+    fn lookup_raw_pkh_pk(&self, pkh: &hash160::Hash) -> Option<Pk> {
+        self.satisfiers
+            .iter()
+            .find_map(|s| s.lookup_raw_pkh_pk(pkh))
+    }
+
+    fn lookup_raw_pkh_ecdsa_sig(
+        &self,
+        pkh: &hash160::Hash,
+    ) -> Option<(bitcoin::PublicKey, ElementsSig)> {
+        self.satisfiers
+            .iter()
+            .find_map(|s| s.lookup_raw_pkh_ecdsa_sig(pkh))
+    }
+
+    fn lookup_raw_pkh_tap_leaf_script_sig(
+        &self,
+        pkh: &(hash160::Hash, TapLeafHash),
+    ) -> Option<(XOnlyPublicKey, elements::SchnorrSig)> {
+        self.satisfiers
+            .iter()
+            .find_map(|s| s.lookup_raw_pkh_tap_leaf_script_sig(pkh))
+    }
+
+    fn lookup_sha256(&self, h: &Pk::Sha256) -> Option<Preimage32> {
+        self.satisfiers.iter().find_map(|s| s.lookup_sha256(h))
+    }
+
+    fn lookup_hash256(&self, h: &Pk::Hash256) -> Option<Preimage32> {
+        self.satisfiers.iter().find_map(|s| s.lookup_hash256(h))
+    }
+
+    fn lookup_ripemd160(&self, h: &Pk::Ripemd160) -> Option<Preimage32> {
+        self.satisfiers.iter().find_map(|s| s.lookup_ripemd160(h))
+    }
+
+    fn lookup_hash160(&self, h: &Pk::Hash160) -> Option<Preimage32> {
+        self.satisfiers.iter().find_map(|s| s.lookup_hash160(h))
+    }
+
+    fn check_older(&self, n: Sequence) -> bool {
+        self.satisfiers.iter().any(|s| s.check_older(n))
+    }
+
+    fn check_after(&self, n: LockTime) -> bool {
+        self.satisfiers.iter().any(|s| s.check_after(n))
+    }
+
+    fn lookup_nversion(&self) -> Option<u32> {
+        self.satisfiers.iter().find_map(|s| s.lookup_nversion())
+    }
+
+    fn lookup_hashprevouts(&self) -> Option<sha256d::Hash> {
+        self.satisfiers.iter().find_map(|s| s.lookup_hashprevouts())
+    }
+
+    fn lookup_hashsequence(&self) -> Option<sha256d::Hash> {
+        self.satisfiers.iter().find_map(|s| s.lookup_hashsequence())
+    }
+
+    fn lookup_hashissuances(&self) -> Option<sha256d::Hash> {
+        self.satisfiers
+            .iter()
+            .find_map(|s| s.lookup_hashissuances())
+    }
+
+    fn lookup_outpoint(&self) -> Option<OutPoint> {
+        self.satisfiers.iter().find_map(|s| s.lookup_outpoint())
+    }
+
+    fn lookup_scriptcode(&self) -> Option<&Script> {
+        self.satisfiers.iter().find_map(|s| s.lookup_scriptcode())
+    }
+
+    fn lookup_value(&self) -> Option<confidential::Value> {
+        self.satisfiers.iter().find_map(|s| s.lookup_value())
+    }
+
+    fn lookup_nsequence(&self) -> Option<u32> {
+        self.satisfiers.iter().find_map(|s| s.lookup_nsequence())
+    }
+
+    fn lookup_outputs(&self) -> Option<&[elements::TxOut]> {
+        self.satisfiers.iter().find_map(|s| s.lookup_outputs())
+    }
+
+    fn lookup_nlocktime(&self) -> Option<u32> {
+        self.satisfiers.iter().find_map(|s| s.lookup_nlocktime())
+    }
+
+    fn lookup_sighashu32(&self) -> Option<u32> {
+        self.satisfiers.iter().find_map(|s| s.lookup_sighashu32())
+    }
+
+    fn lookup_tx(&self) -> Option<&elements::Transaction> {
+        self.satisfiers.iter().find_map(|s| s.lookup_tx())
+    }
+
+    fn lookup_spent_utxos(&self) -> Option<&[elements::TxOut]> {
+        self.satisfiers.iter().find_map(|s| s.lookup_spent_utxos())
+    }
+
+    fn lookup_curr_inp(&self) -> Option<usize> {
+        self.satisfiers.iter().find_map(|s| s.lookup_curr_inp())
+    }
+
+    fn lookup_csfs_sig(&self, pk: &XOnlyPublicKey, msg: &CsfsMsg) -> Option<schnorr::Signature> {
+        self.satisfiers
+            .iter()
+            .find_map(|s| s.lookup_csfs_sig(pk, msg))
+    }
+
+    fn lookup_ecdsa_adaptor_sig(&self, p: &Pk) -> Option<EcdsaAdaptorSig> {
+        self.satisfiers
+            .iter()
+            .find_map(|s| s.lookup_ecdsa_adaptor_sig(p))
+    }
+
+    fn lookup_schnorr_adaptor_sig(&self, p: &Pk, h: &TapLeafHash) -> Option<SchnorrAdaptorSig> {
+        self.satisfiers
+            .iter()
+            .find_map(|s| s.lookup_schnorr_adaptor_sig(p, h))
+    }
+
+    fn lookup_tap_key_spend_adaptor_sig(&self) -> Option<(XOnlyPublicKey, SchnorrAdaptorSig)> {
+        self.satisfiers
+            .iter()
+            .find_map(|s| s.lookup_tap_key_spend_adaptor_sig())
+    }
+
+    fn lookup_musig_partial_sigs(&self, p: &Pk, h: &TapLeafHash) -> Option<MuSigPartialSigs> {
+        self.satisfiers
+            .iter()
+            .find_map(|s| s.lookup_musig_partial_sigs(p, h))
+    }
+
+    fn lookup_tap_key_spend_musig_partial_sigs(&self) -> Option<MuSigPartialSigs> {
+        self.satisfiers
+            .iter()
+            .find_map(|s| s.lookup_tap_key_spend_musig_partial_sigs())
+    }
+
+    fn satisfy_options(&self) -> SatisfyOptions {
+        self.satisfiers
+            .iter()
+            .fold(SatisfyOptions::default(), |options, s| {
+                options.combine(s.satisfy_options())
+            })
+    }
+}
+
 /// A witness, if available, for a Miniscript fragment
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub enum Witness {
@@ -1051,6 +2554,118 @@ impl Satisfaction {
             has_sig: false,
         }
     }
+}
+
+/// The largest timelocks used by a (dis)satisfaction. Block and time based
+/// locks are kept apart, as only locks of the same unit can be compared.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub(crate) struct Timelocks {
+    older_blocks: Option<Sequence>,
+    older_time: Option<Sequence>,
+    after_height: Option<PackedLockTime>,
+    after_time: Option<PackedLockTime>,
+}
+
+impl Timelocks {
+    fn older(t: Sequence) -> Self {
+        if t.is_time_locked() {
+            Timelocks {
+                older_time: Some(t),
+                ..Default::default()
+            }
+        } else {
+            Timelocks {
+                older_blocks: Some(t),
+                ..Default::default()
+            }
+        }
+    }
+
+    fn after(t: PackedLockTime) -> Self {
+        if LockTime::from(t).is_block_height() {
+            Timelocks {
+                after_height: Some(t),
+                ..Default::default()
+            }
+        } else {
+            Timelocks {
+                after_time: Some(t),
+                ..Default::default()
+            }
+        }
+    }
+
+    fn combine(self, other: Self) -> Self {
+        Timelocks {
+            older_blocks: cmp::max(self.older_blocks, other.older_blocks),
+            older_time: cmp::max(self.older_time, other.older_time),
+            after_height: cmp::max(self.after_height, other.after_height),
+            after_time: cmp::max(self.after_time, other.after_time),
+        }
+    }
+
+    /// The nLockTime and nSequence that a spend using these timelocks
+    /// requires
+    ///
+    /// # Errors
+    /// When both a block and a time based lock of the same kind are used, as
+    /// no transaction satisfies both.
+    pub(crate) fn required(&self) -> Result<(Option<PackedLockTime>, Option<Sequence>), Error> {
+        match (
+            self.after_height,
+            self.after_time,
+            self.older_blocks,
+            self.older_time,
+        ) {
+            (Some(_), Some(_), _, _) | (_, _, Some(_), Some(_)) => {
+                Err(Error::ImpossibleSatisfaction)
+            }
+            (height, time, blocks, secs) => Ok((height.or(time), blocks.or(secs))),
+        }
+    }
+}
+
+/// A (dis)satisfaction along with the timelocks it uses, which extensions
+/// do not have
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub(crate) struct TimelockedSatisfaction {
+    /// The actual witness stack
+    pub(crate) stack: Witness,
+    /// Whether or not this (dis)satisfaction has a signature somewhere
+    /// in it
+    pub(crate) has_sig: bool,
+    /// The timelocks that the spending transaction must satisfy
+    pub(crate) timelocks: Timelocks,
+}
+
+impl From<Satisfaction> for TimelockedSatisfaction {
+    fn from(sat: Satisfaction) -> Self {
+        TimelockedSatisfaction {
+            stack: sat.stack,
+            has_sig: sat.has_sig,
+            timelocks: Timelocks::default(),
+        }
+    }
+}
+
+impl TimelockedSatisfaction {
+    // The (dis)satisfaction of a fragment which was not explored because
+    // the search budget ran out. It is unavailable, and only has a signature
+    // if every satisfaction of the fragment needs one, so that choosing
+    // around it cannot introduce malleability.
+    fn unexplored<Pk, Ctx, Ext>(term: &Terminal<Pk, Ctx, Ext>, sat: bool) -> Self
+    where
+        Pk: MiniscriptKey,
+        Ctx: ScriptContext,
+        Ext: Extension,
+    {
+        let has_sig = sat && Malleability::type_check(term, |_| None).map_or(false, |m| m.safe);
+        TimelockedSatisfaction {
+            stack: Witness::Unavailable,
+            has_sig,
+            timelocks: Timelocks::default(),
+        }
+    }
 
     // produce a non-malleable satisafaction for thesh frag
     fn thresh<Pk, Ctx, Sat, Ext, F>(
@@ -1059,6 +2674,7 @@ impl Satisfaction {
         stfr: &Sat,
         root_has_sig: bool,
         leaf_hash: &TapLeafHash,
+        budget: &Budget,
         min_fn: &mut F,
     ) -> Self
     where
@@ -1066,7 +2682,7 @@ impl Satisfaction {
         Ctx: ScriptContext,
         Sat: Satisfier<Pk>,
         Ext: ParseableExt,
-        F: FnMut(Satisfaction, Satisfaction) -> Satisfaction,
+        F: FnMut(TimelockedSatisfaction, TimelockedSatisfaction) -> TimelockedSatisfaction,
     {
         let mut sats = subs
             .iter()
@@ -1076,6 +2692,7 @@ impl Satisfaction {
                     stfr,
                     root_has_sig,
                     leaf_hash,
+                    budget,
                     min_fn,
                     &mut Self::thresh,
                 )
@@ -1090,6 +2707,7 @@ impl Satisfaction {
                     stfr,
                     root_has_sig,
                     leaf_hash,
+                    budget,
                     min_fn,
                     &mut Self::thresh,
                 )
@@ -1114,8 +2732,9 @@ impl Satisfaction {
             // First consider the candidates that are not impossible to satisfy
             // by any party. Among those first consider the ones that have no sig
             // because third party can malleate them if they are not chosen.
-            // Lastly, choose by weight.
-            (is_impossible, sats[i].has_sig, stack_weight)
+            // Lastly, choose by weight, breaking ties by position so that
+            // the earliest subexpressions are satisfied.
+            (is_impossible, sats[i].has_sig, stack_weight, i)
         });
 
         for i in 0..k {
@@ -1129,11 +2748,12 @@ impl Satisfaction {
         // is has an impossible witness
         assert!(k > 0);
         if sats[sat_indices[k - 1]].stack == Witness::Impossible {
-            Satisfaction {
+            TimelockedSatisfaction {
                 stack: Witness::Impossible,
                 // If the witness is impossible, we don't care about the
                 // has_sig flag
                 has_sig: false,
+                timelocks: Timelocks::default(),
             }
         }
         // We are now guaranteed that all elements in `k` satisfactions
@@ -1156,14 +2776,18 @@ impl Satisfaction {
             for sat in &ret_stack {
                 assert!(!sat.has_sig);
             }
-            Satisfaction {
+            TimelockedSatisfaction {
                 stack: Witness::Unavailable,
                 has_sig: false,
+                timelocks: Timelocks::default(),
             }
         } else {
             // Otherwise flatten everything out
-            Satisfaction {
+            TimelockedSatisfaction {
                 has_sig: ret_stack.iter().any(|sat| sat.has_sig),
+                timelocks: ret_stack
+                    .iter()
+                    .fold(Timelocks::default(), |acc, sat| acc.combine(sat.timelocks)),
                 stack: ret_stack.into_iter().fold(Witness::empty(), |acc, next| {
                     Witness::combine(next.stack, acc)
                 }),
@@ -1178,6 +2802,7 @@ impl Satisfaction {
         stfr: &Sat,
         root_has_sig: bool,
         leaf_hash: &TapLeafHash,
+        budget: &Budget,
         min_fn: &mut F,
     ) -> Self
     where
@@ -1185,7 +2810,7 @@ impl Satisfaction {
         Ctx: ScriptContext,
         Sat: Satisfier<Pk>,
         Ext: ParseableExt,
-        F: FnMut(Satisfaction, Satisfaction) -> Satisfaction,
+        F: FnMut(TimelockedSatisfaction, TimelockedSatisfaction) -> TimelockedSatisfaction,
     {
         let mut sats = subs
             .iter()
@@ -1195,6 +2820,7 @@ impl Satisfaction {
                     stfr,
                     root_has_sig,
                     leaf_hash,
+                    budget,
                     min_fn,
                     &mut Self::thresh_mall,
                 )
@@ -1209,6 +2835,7 @@ impl Satisfaction {
                     stfr,
                     root_has_sig,
                     leaf_hash,
+                    budget,
                     min_fn,
                     &mut Self::thresh_mall,
                 )
@@ -1220,15 +2847,17 @@ impl Satisfaction {
         // signatures
         let mut sat_indices = (0..subs.len()).collect::<Vec<_>>();
         sat_indices.sort_by_key(|&i| {
-            // For malleable satifactions, directly choose smallest weights
-            match (&sats[i].stack, &ret_stack[i].stack) {
+            // For malleable satifactions, directly choose smallest weights,
+            // breaking ties by position
+            let stack_weight = match (&sats[i].stack, &ret_stack[i].stack) {
                 (&Witness::Unavailable, _) | (&Witness::Impossible, _) => i64::MAX,
                 // This is only possible when one of the branches has PkH
                 (_, &Witness::Unavailable) | (_, &Witness::Impossible) => i64::MIN,
                 (&Witness::Stack(ref s), &Witness::Stack(ref d)) => {
                     witness_size(s) as i64 - witness_size(d) as i64
                 }
-            }
+            };
+            (stack_weight, i)
         });
 
         // swap the satisfactions
@@ -1238,8 +2867,11 @@ impl Satisfaction {
 
         // combine the witness
         // no non-malleability checks needed
-        Satisfaction {
+        TimelockedSatisfaction {
             has_sig: ret_stack.iter().any(|sat| sat.has_sig),
+            timelocks: ret_stack
+                .iter()
+                .fold(Timelocks::default(), |acc, sat| acc.combine(sat.timelocks)),
             stack: ret_stack.into_iter().fold(Witness::empty(), |acc, next| {
                 Witness::combine(next.stack, acc)
             }),
@@ -1258,28 +2890,37 @@ impl Satisfaction {
         match (sat1.has_sig, sat2.has_sig) {
             // If neither option has a signature, this is a malleability
             // vector, so choose neither one.
-            (false, false) => Satisfaction {
-                stack: Witness::Unavailable,
-                has_sig: false,
-            },
+            (false, false) => {
+                trace_event!("no branch chosen, neither has a signature");
+                TimelockedSatisfaction {
+                    stack: Witness::Unavailable,
+                    has_sig: false,
+                    timelocks: Timelocks::default(),
+                }
+            }
             // If only one has a signature, take the one that doesn't; a
             // third party could malleate by removing the signature, but
             // can't malleate if he'd have to add it
-            (false, true) => Satisfaction {
-                stack: sat1.stack,
-                has_sig: false,
-            },
-            (true, false) => Satisfaction {
-                stack: sat2.stack,
-                has_sig: false,
-            },
+            (false, true) => {
+                trace_event!("chose first branch, the second has a signature");
+                sat1
+            }
+            (true, false) => {
+                trace_event!("chose second branch, the first has a signature");
+                sat2
+            }
             // If both have a signature associated with them, choose the
             // cheaper one (where "cheaper" is defined such that available
             // things are cheaper than unavailable ones)
-            (true, true) => Satisfaction {
-                stack: cmp::min(sat1.stack, sat2.stack),
-                has_sig: true,
-            },
+            (true, true) => {
+                if sat1.stack <= sat2.stack {
+                    trace_event!("chose cheaper first branch");
+                    sat1
+                } else {
+                    trace_event!("chose cheaper second branch");
+                    sat2
+                }
+            }
         }
     }
 
@@ -1292,12 +2933,13 @@ impl Satisfaction {
             (_, &Witness::Impossible) | (_, &Witness::Unavailable) => return sat1,
             _ => {}
         }
-        Satisfaction {
-            stack: cmp::min(sat1.stack, sat2.stack),
-            // The fragment is has_sig only if both of the
-            // fragments are has_sig
-            has_sig: sat1.has_sig && sat2.has_sig,
-        }
+        // The fragment is has_sig only if both of the
+        // fragments are has_sig
+        let has_sig = sat1.has_sig && sat2.has_sig;
+        trace_event!(first = sat1.stack <= sat2.stack, "chose cheaper branch");
+        let mut sat = if sat1.stack <= sat2.stack { sat1 } else { sat2 };
+        sat.has_sig = has_sig;
+        sat
     }
 
     // produce a non-malleable satisfaction
@@ -1306,6 +2948,7 @@ impl Satisfaction {
         stfr: &Sat,
         root_has_sig: bool,
         leaf_hash: &TapLeafHash,
+        budget: &Budget,
         min_fn: &mut F,
         thresh_fn: &mut G,
     ) -> Self
@@ -1314,30 +2957,37 @@ impl Satisfaction {
         Ctx: ScriptContext,
         Sat: Satisfier<Pk>,
         Ext: ParseableExt,
-        F: FnMut(Satisfaction, Satisfaction) -> Satisfaction,
+        F: FnMut(TimelockedSatisfaction, TimelockedSatisfaction) -> TimelockedSatisfaction,
         G: FnMut(
             usize,
             &[Arc<Miniscript<Pk, Ctx, Ext>>],
             &Sat,
             bool,
             &TapLeafHash,
+            &Budget,
             &mut F,
-        ) -> Satisfaction,
+        ) -> Self,
     {
+        if !budget.spend() {
+            return Self::unexplored(term, true);
+        }
         match *term {
-            Terminal::PkK(ref pk) => Satisfaction {
+            Terminal::PkK(ref pk) => TimelockedSatisfaction {
                 stack: Witness::signature::<_, _, Ctx>(stfr, pk, leaf_hash),
                 has_sig: true,
+                timelocks: Timelocks::default(),
             },
-            Terminal::PkH(ref pk) => Satisfaction {
+            Terminal::PkH(ref pk) => TimelockedSatisfaction {
                 stack: Witness::pkh_signature(stfr, &pk.to_pubkeyhash(Ctx::sig_type())),
                 has_sig: true,
+                timelocks: Timelocks::default(),
             },
-            Terminal::RawPkH(ref pkh) => Satisfaction {
+            Terminal::RawPkH(ref pkh) => TimelockedSatisfaction {
                 stack: Witness::pkh_signature(stfr, pkh),
                 has_sig: true,
+                timelocks: Timelocks::default(),
             },
-            Terminal::After(t) => Satisfaction {
+            Terminal::After(t) => TimelockedSatisfaction {
                 stack: if stfr.check_after(t.into()) {
                     Witness::empty()
                 } else if root_has_sig {
@@ -1351,8 +3001,9 @@ impl Satisfaction {
                     Witness::Unavailable
                 },
                 has_sig: false,
+                timelocks: Timelocks::after(t),
             },
-            Terminal::Older(t) => Satisfaction {
+            Terminal::Older(t) => TimelockedSatisfaction {
                 stack: if stfr.check_older(t) {
                     Witness::empty()
                 } else if root_has_sig {
@@ -1367,100 +3018,169 @@ impl Satisfaction {
                 },
 
                 has_sig: false,
+                timelocks: Timelocks::older(t),
             },
-            Terminal::Ripemd160(ref h) => Satisfaction {
+            Terminal::Ripemd160(ref h) => TimelockedSatisfaction {
                 stack: Witness::ripemd160_preimage(stfr, h),
                 has_sig: false,
+                timelocks: Timelocks::default(),
             },
-            Terminal::Hash160(ref h) => Satisfaction {
+            Terminal::Hash160(ref h) => TimelockedSatisfaction {
                 stack: Witness::hash160_preimage(stfr, h),
                 has_sig: false,
+                timelocks: Timelocks::default(),
             },
-            Terminal::Sha256(ref h) => Satisfaction {
+            Terminal::Sha256(ref h) => TimelockedSatisfaction {
                 stack: Witness::sha256_preimage(stfr, h),
                 has_sig: false,
+                timelocks: Timelocks::default(),
             },
-            Terminal::Hash256(ref h) => Satisfaction {
+            Terminal::Hash256(ref h) => TimelockedSatisfaction {
                 stack: Witness::hash256_preimage(stfr, h),
                 has_sig: false,
+                timelocks: Timelocks::default(),
             },
-            Terminal::True => Satisfaction {
+            Terminal::True => TimelockedSatisfaction {
                 stack: Witness::empty(),
                 has_sig: false,
+                timelocks: Timelocks::default(),
             },
-            Terminal::False => Satisfaction {
+            Terminal::False => TimelockedSatisfaction {
                 stack: Witness::Impossible,
                 has_sig: false,
+                timelocks: Timelocks::default(),
             },
             Terminal::Alt(ref sub)
             | Terminal::Swap(ref sub)
             | Terminal::Check(ref sub)
             | Terminal::Verify(ref sub)
             | Terminal::NonZero(ref sub)
-            | Terminal::ZeroNotEqual(ref sub) => {
-                Self::satisfy_helper(&sub.node, stfr, root_has_sig, leaf_hash, min_fn, thresh_fn)
-            }
+            | Terminal::ZeroNotEqual(ref sub) => Self::satisfy_helper(
+                &sub.node,
+                stfr,
+                root_has_sig,
+                leaf_hash,
+                budget,
+                min_fn,
+                thresh_fn,
+            ),
             Terminal::DupIf(ref sub) => {
                 let sat = Self::satisfy_helper(
                     &sub.node,
                     stfr,
                     root_has_sig,
                     leaf_hash,
+                    budget,
                     min_fn,
                     thresh_fn,
                 );
-                Satisfaction {
+                TimelockedSatisfaction {
                     stack: Witness::combine(sat.stack, Witness::push_1()),
                     has_sig: sat.has_sig,
+                    timelocks: sat.timelocks,
                 }
             }
             Terminal::AndV(ref l, ref r) | Terminal::AndB(ref l, ref r) => {
-                let l_sat =
-                    Self::satisfy_helper(&l.node, stfr, root_has_sig, leaf_hash, min_fn, thresh_fn);
-                let r_sat =
-                    Self::satisfy_helper(&r.node, stfr, root_has_sig, leaf_hash, min_fn, thresh_fn);
-                Satisfaction {
+                let l_sat = Self::satisfy_helper(
+                    &l.node,
+                    stfr,
+                    root_has_sig,
+                    leaf_hash,
+                    budget,
+                    min_fn,
+                    thresh_fn,
+                );
+                let r_sat = Self::satisfy_helper(
+                    &r.node,
+                    stfr,
+                    root_has_sig,
+                    leaf_hash,
+                    budget,
+                    min_fn,
+                    thresh_fn,
+                );
+                TimelockedSatisfaction {
                     stack: Witness::combine(r_sat.stack, l_sat.stack),
                     has_sig: l_sat.has_sig || r_sat.has_sig,
+                    timelocks: l_sat.timelocks.combine(r_sat.timelocks),
                 }
             }
             Terminal::AndOr(ref a, ref b, ref c) => {
-                let a_sat =
-                    Self::satisfy_helper(&a.node, stfr, root_has_sig, leaf_hash, min_fn, thresh_fn);
+                let a_sat = Self::satisfy_helper(
+                    &a.node,
+                    stfr,
+                    root_has_sig,
+                    leaf_hash,
+                    budget,
+                    min_fn,
+                    thresh_fn,
+                );
                 let a_nsat = Self::dissatisfy_helper(
                     &a.node,
                     stfr,
                     root_has_sig,
                     leaf_hash,
+                    budget,
+                    min_fn,
+                    thresh_fn,
+                );
+                let b_sat = Self::satisfy_helper(
+                    &b.node,
+                    stfr,
+                    root_has_sig,
+                    leaf_hash,
+                    budget,
+                    min_fn,
+                    thresh_fn,
+                );
+                let c_sat = Self::satisfy_helper(
+                    &c.node,
+                    stfr,
+                    root_has_sig,
+                    leaf_hash,
+                    budget,
                     min_fn,
                     thresh_fn,
                 );
-                let b_sat =
-                    Self::satisfy_helper(&b.node, stfr, root_has_sig, leaf_hash, min_fn, thresh_fn);
-                let c_sat =
-                    Self::satisfy_helper(&c.node, stfr, root_has_sig, leaf_hash, min_fn, thresh_fn);
 
                 min_fn(
-                    Satisfaction {
+                    TimelockedSatisfaction {
                         stack: Witness::combine(b_sat.stack, a_sat.stack),
                         has_sig: a_sat.has_sig || b_sat.has_sig,
+                        timelocks: a_sat.timelocks.combine(b_sat.timelocks),
                     },
-                    Satisfaction {
+                    TimelockedSatisfaction {
                         stack: Witness::combine(c_sat.stack, a_nsat.stack),
                         has_sig: a_nsat.has_sig || c_sat.has_sig,
+                        timelocks: a_nsat.timelocks.combine(c_sat.timelocks),
                     },
                 )
             }
             Terminal::OrB(ref l, ref r) => {
-                let l_sat =
-                    Self::satisfy_helper(&l.node, stfr, root_has_sig, leaf_hash, min_fn, thresh_fn);
-                let r_sat =
-                    Self::satisfy_helper(&r.node, stfr, root_has_sig, leaf_hash, min_fn, thresh_fn);
+                let l_sat = Self::satisfy_helper(
+                    &l.node,
+                    stfr,
+                    root_has_sig,
+                    leaf_hash,
+                    budget,
+                    min_fn,
+                    thresh_fn,
+                );
+                let r_sat = Self::satisfy_helper(
+                    &r.node,
+                    stfr,
+                    root_has_sig,
+                    leaf_hash,
+                    budget,
+                    min_fn,
+                    thresh_fn,
+                );
                 let l_nsat = Self::dissatisfy_helper(
                     &l.node,
                     stfr,
                     root_has_sig,
                     leaf_hash,
+                    budget,
                     min_fn,
                     thresh_fn,
                 );
@@ -1469,6 +3189,7 @@ impl Satisfaction {
                     stfr,
                     root_has_sig,
                     leaf_hash,
+                    budget,
                     min_fn,
                     thresh_fn,
                 );
@@ -1477,26 +3198,43 @@ impl Satisfaction {
                 assert!(!r_nsat.has_sig);
 
                 min_fn(
-                    Satisfaction {
+                    TimelockedSatisfaction {
                         stack: Witness::combine(r_sat.stack, l_nsat.stack),
                         has_sig: r_sat.has_sig,
+                        timelocks: l_nsat.timelocks.combine(r_sat.timelocks),
                     },
-                    Satisfaction {
+                    TimelockedSatisfaction {
                         stack: Witness::combine(r_nsat.stack, l_sat.stack),
                         has_sig: l_sat.has_sig,
+                        timelocks: l_sat.timelocks.combine(r_nsat.timelocks),
                     },
                 )
             }
             Terminal::OrD(ref l, ref r) | Terminal::OrC(ref l, ref r) => {
-                let l_sat =
-                    Self::satisfy_helper(&l.node, stfr, root_has_sig, leaf_hash, min_fn, thresh_fn);
-                let r_sat =
-                    Self::satisfy_helper(&r.node, stfr, root_has_sig, leaf_hash, min_fn, thresh_fn);
+                let l_sat = Self::satisfy_helper(
+                    &l.node,
+                    stfr,
+                    root_has_sig,
+                    leaf_hash,
+                    budget,
+                    min_fn,
+                    thresh_fn,
+                );
+                let r_sat = Self::satisfy_helper(
+                    &r.node,
+                    stfr,
+                    root_has_sig,
+                    leaf_hash,
+                    budget,
+                    min_fn,
+                    thresh_fn,
+                );
                 let l_nsat = Self::dissatisfy_helper(
                     &l.node,
                     stfr,
                     root_has_sig,
                     leaf_hash,
+                    budget,
                     min_fn,
                     thresh_fn,
                 );
@@ -1505,30 +3243,47 @@ impl Satisfaction {
 
                 min_fn(
                     l_sat,
-                    Satisfaction {
+                    TimelockedSatisfaction {
                         stack: Witness::combine(r_sat.stack, l_nsat.stack),
                         has_sig: r_sat.has_sig,
+                        timelocks: l_nsat.timelocks.combine(r_sat.timelocks),
                     },
                 )
             }
             Terminal::OrI(ref l, ref r) => {
-                let l_sat =
-                    Self::satisfy_helper(&l.node, stfr, root_has_sig, leaf_hash, min_fn, thresh_fn);
-                let r_sat =
-                    Self::satisfy_helper(&r.node, stfr, root_has_sig, leaf_hash, min_fn, thresh_fn);
+                let l_sat = Self::satisfy_helper(
+                    &l.node,
+                    stfr,
+                    root_has_sig,
+                    leaf_hash,
+                    budget,
+                    min_fn,
+                    thresh_fn,
+                );
+                let r_sat = Self::satisfy_helper(
+                    &r.node,
+                    stfr,
+                    root_has_sig,
+                    leaf_hash,
+                    budget,
+                    min_fn,
+                    thresh_fn,
+                );
                 min_fn(
-                    Satisfaction {
+                    TimelockedSatisfaction {
                         stack: Witness::combine(l_sat.stack, Witness::push_1()),
                         has_sig: l_sat.has_sig,
+                        timelocks: l_sat.timelocks,
                     },
-                    Satisfaction {
+                    TimelockedSatisfaction {
                         stack: Witness::combine(r_sat.stack, Witness::push_0()),
                         has_sig: r_sat.has_sig,
+                        timelocks: r_sat.timelocks,
                     },
                 )
             }
             Terminal::Thresh(k, ref subs) => {
-                thresh_fn(k, subs, stfr, root_has_sig, leaf_hash, min_fn)
+                thresh_fn(k, subs, stfr, root_has_sig, leaf_hash, budget, min_fn)
             }
             Terminal::Multi(k, ref keys) => {
                 // Collect all available signatures
@@ -1548,12 +3303,15 @@ impl Satisfaction {
                 }
 
                 if sig_count < k {
-                    Satisfaction {
+                    TimelockedSatisfaction {
                         stack: Witness::Impossible,
                         has_sig: false,
+                        timelocks: Timelocks::default(),
                     }
                 } else {
-                    // Throw away the most expensive ones
+                    // Throw away the most expensive ones. `max_by_key` picks
+                    // the last of several equally expensive signatures, so
+                    // ties keep the signatures of the earliest keys.
                     for _ in 0..sig_count - k {
                         let max_idx = sigs
                             .iter()
@@ -1564,11 +3322,12 @@ impl Satisfaction {
                         sigs[max_idx] = vec![];
                     }
 
-                    Satisfaction {
+                    TimelockedSatisfaction {
                         stack: sigs.into_iter().fold(Witness::push_0(), |acc, sig| {
                             Witness::combine(acc, Witness::Stack(sig))
                         }),
                         has_sig: true,
+                        timelocks: Timelocks::default(),
                     }
                 }
             }
@@ -1597,20 +3356,22 @@ impl Satisfaction {
                 }
 
                 if sig_count < k {
-                    Satisfaction {
+                    TimelockedSatisfaction {
                         stack: Witness::Impossible,
                         has_sig: false,
+                        timelocks: Timelocks::default(),
                     }
                 } else {
-                    Satisfaction {
+                    TimelockedSatisfaction {
                         stack: sigs.into_iter().fold(Witness::empty(), |acc, sig| {
                             Witness::combine(acc, Witness::Stack(sig))
                         }),
                         has_sig: true,
+                        timelocks: Timelocks::default(),
                     }
                 }
             }
-            Terminal::Ext(ref e) => e.satisfy(stfr),
+            Terminal::Ext(ref e) => e.satisfy(stfr).into(),
         }
     }
 
@@ -1620,6 +3381,7 @@ impl Satisfaction {
         stfr: &Sat,
         root_has_sig: bool,
         leaf_hash: &TapLeafHash,
+        budget: &Budget,
         min_fn: &mut F,
         thresh_fn: &mut G,
     ) -> Self
@@ -1628,83 +3390,112 @@ impl Satisfaction {
         Ctx: ScriptContext,
         Sat: Satisfier<Pk>,
         Ext: ParseableExt,
-        F: FnMut(Satisfaction, Satisfaction) -> Satisfaction,
+        F: FnMut(TimelockedSatisfaction, TimelockedSatisfaction) -> TimelockedSatisfaction,
         G: FnMut(
             usize,
             &[Arc<Miniscript<Pk, Ctx, Ext>>],
             &Sat,
             bool,
             &TapLeafHash,
+            &Budget,
             &mut F,
-        ) -> Satisfaction,
+        ) -> Self,
     {
+        if !budget.spend() {
+            return Self::unexplored(term, false);
+        }
         match *term {
-            Terminal::PkK(..) => Satisfaction {
+            Terminal::PkK(..) => TimelockedSatisfaction {
                 stack: Witness::push_0(),
                 has_sig: false,
+                timelocks: Timelocks::default(),
             },
-            Terminal::PkH(ref pk) => Satisfaction {
+            Terminal::PkH(ref pk) => TimelockedSatisfaction {
                 stack: Witness::combine(
                     Witness::push_0(),
                     Witness::pkh_public_key(stfr, &pk.to_pubkeyhash(Ctx::sig_type())),
                 ),
                 has_sig: false,
+                timelocks: Timelocks::default(),
             },
-            Terminal::RawPkH(ref pkh) => Satisfaction {
+            Terminal::RawPkH(ref pkh) => TimelockedSatisfaction {
                 stack: Witness::combine(Witness::push_0(), Witness::pkh_public_key(stfr, pkh)),
                 has_sig: false,
+                timelocks: Timelocks::default(),
             },
-            Terminal::False => Satisfaction {
+            Terminal::False => TimelockedSatisfaction {
                 stack: Witness::empty(),
                 has_sig: false,
+                timelocks: Timelocks::default(),
             },
-            Terminal::True => Satisfaction {
+            Terminal::True => TimelockedSatisfaction {
                 stack: Witness::Impossible,
                 has_sig: false,
+                timelocks: Timelocks::default(),
             },
-            Terminal::Older(_) => Satisfaction {
+            Terminal::Older(_) => TimelockedSatisfaction {
                 stack: Witness::Impossible,
                 has_sig: false,
+                timelocks: Timelocks::default(),
             },
-            Terminal::After(_) => Satisfaction {
+            Terminal::After(_) => TimelockedSatisfaction {
                 stack: Witness::Impossible,
                 has_sig: false,
+                timelocks: Timelocks::default(),
             },
             Terminal::Sha256(_)
             | Terminal::Hash256(_)
             | Terminal::Ripemd160(_)
-            | Terminal::Hash160(_) => Satisfaction {
+            | Terminal::Hash160(_) => TimelockedSatisfaction {
                 stack: Witness::hash_dissatisfaction(),
                 has_sig: false,
+                timelocks: Timelocks::default(),
             },
             Terminal::Alt(ref sub)
             | Terminal::Swap(ref sub)
             | Terminal::Check(ref sub)
-            | Terminal::ZeroNotEqual(ref sub) => {
-                Self::dissatisfy_helper(&sub.node, stfr, root_has_sig, leaf_hash, min_fn, thresh_fn)
-            }
-            Terminal::DupIf(_) | Terminal::NonZero(_) => Satisfaction {
+            | Terminal::ZeroNotEqual(ref sub) => Self::dissatisfy_helper(
+                &sub.node,
+                stfr,
+                root_has_sig,
+                leaf_hash,
+                budget,
+                min_fn,
+                thresh_fn,
+            ),
+            Terminal::DupIf(_) | Terminal::NonZero(_) => TimelockedSatisfaction {
                 stack: Witness::push_0(),
                 has_sig: false,
+                timelocks: Timelocks::default(),
             },
-            Terminal::Verify(_) => Satisfaction {
+            Terminal::Verify(_) => TimelockedSatisfaction {
                 stack: Witness::Impossible,
                 has_sig: false,
+                timelocks: Timelocks::default(),
             },
             Terminal::AndV(ref v, ref other) => {
-                let vsat =
-                    Self::satisfy_helper(&v.node, stfr, root_has_sig, leaf_hash, min_fn, thresh_fn);
+                let vsat = Self::satisfy_helper(
+                    &v.node,
+                    stfr,
+                    root_has_sig,
+                    leaf_hash,
+                    budget,
+                    min_fn,
+                    thresh_fn,
+                );
                 let odissat = Self::dissatisfy_helper(
                     &other.node,
                     stfr,
                     root_has_sig,
                     leaf_hash,
+                    budget,
                     min_fn,
                     thresh_fn,
                 );
-                Satisfaction {
+                TimelockedSatisfaction {
                     stack: Witness::combine(odissat.stack, vsat.stack),
                     has_sig: vsat.has_sig || odissat.has_sig,
+                    timelocks: vsat.timelocks.combine(odissat.timelocks),
                 }
             }
             Terminal::AndB(ref l, ref r)
@@ -1716,6 +3507,7 @@ impl Satisfaction {
                     stfr,
                     root_has_sig,
                     leaf_hash,
+                    budget,
                     min_fn,
                     thresh_fn,
                 );
@@ -1724,17 +3516,20 @@ impl Satisfaction {
                     stfr,
                     root_has_sig,
                     leaf_hash,
+                    budget,
                     min_fn,
                     thresh_fn,
                 );
-                Satisfaction {
+                TimelockedSatisfaction {
                     stack: Witness::combine(rnsat.stack, lnsat.stack),
                     has_sig: rnsat.has_sig || lnsat.has_sig,
+                    timelocks: lnsat.timelocks.combine(rnsat.timelocks),
                 }
             }
-            Terminal::OrC(..) => Satisfaction {
+            Terminal::OrC(..) => TimelockedSatisfaction {
                 stack: Witness::Impossible,
                 has_sig: false,
+                timelocks: Timelocks::default(),
             },
             Terminal::OrI(ref l, ref r) => {
                 let lnsat = Self::dissatisfy_helper(
@@ -1742,12 +3537,14 @@ impl Satisfaction {
                     stfr,
                     root_has_sig,
                     leaf_hash,
+                    budget,
                     min_fn,
                     thresh_fn,
                 );
-                let dissat_1 = Satisfaction {
+                let dissat_1 = TimelockedSatisfaction {
                     stack: Witness::combine(lnsat.stack, Witness::push_1()),
                     has_sig: lnsat.has_sig,
+                    timelocks: lnsat.timelocks,
                 };
 
                 let rnsat = Self::dissatisfy_helper(
@@ -1755,41 +3552,54 @@ impl Satisfaction {
                     stfr,
                     root_has_sig,
                     leaf_hash,
+                    budget,
                     min_fn,
                     thresh_fn,
                 );
-                let dissat_2 = Satisfaction {
+                let dissat_2 = TimelockedSatisfaction {
                     stack: Witness::combine(rnsat.stack, Witness::push_0()),
                     has_sig: rnsat.has_sig,
+                    timelocks: rnsat.timelocks,
                 };
 
                 // Dissatisfactions don't need to non-malleable. Use minimum_mall always
-                Satisfaction::minimum_mall(dissat_1, dissat_2)
+                Self::minimum_mall(dissat_1, dissat_2)
             }
-            Terminal::Thresh(_, ref subs) => Satisfaction {
-                stack: subs.iter().fold(Witness::empty(), |acc, sub| {
+            Terminal::Thresh(_, ref subs) => subs.iter().fold(
+                TimelockedSatisfaction {
+                    stack: Witness::empty(),
+                    has_sig: false,
+                    timelocks: Timelocks::default(),
+                },
+                |acc, sub| {
                     let nsat = Self::dissatisfy_helper(
                         &sub.node,
                         stfr,
                         root_has_sig,
                         leaf_hash,
+                        budget,
                         min_fn,
                         thresh_fn,
                     );
                     assert!(!nsat.has_sig);
-                    Witness::combine(nsat.stack, acc)
-                }),
-                has_sig: false,
-            },
-            Terminal::Multi(k, _) => Satisfaction {
+                    TimelockedSatisfaction {
+                        stack: Witness::combine(nsat.stack, acc.stack),
+                        has_sig: false,
+                        timelocks: nsat.timelocks.combine(acc.timelocks),
+                    }
+                },
+            ),
+            Terminal::Multi(k, _) => TimelockedSatisfaction {
                 stack: Witness::Stack(vec![vec![]; k + 1]),
                 has_sig: false,
+                timelocks: Timelocks::default(),
             },
-            Terminal::MultiA(_, ref pks) => Satisfaction {
+            Terminal::MultiA(_, ref pks) => TimelockedSatisfaction {
                 stack: Witness::Stack(vec![vec![]; pks.len()]),
                 has_sig: false,
+                timelocks: Timelocks::default(),
             },
-            Terminal::Ext(ref e) => e.dissatisfy(stfr),
+            Terminal::Ext(ref e) => e.dissatisfy(stfr).into(),
         }
     }
 
@@ -1799,21 +3609,25 @@ impl Satisfaction {
         stfr: &Sat,
         root_has_sig: bool,
         leaf_hash: &TapLeafHash,
-    ) -> Self
+    ) -> Result<Self, Error>
     where
         Pk: MiniscriptKey + ToPublicKey,
         Ctx: ScriptContext,
         Sat: Satisfier<Pk>,
         Ext: ParseableExt,
     {
-        Self::satisfy_helper(
+        let options = stfr.satisfy_options();
+        let budget = Budget::new(&options);
+        let sat = Self::satisfy_helper(
             term,
             stfr,
             root_has_sig,
             leaf_hash,
-            &mut Satisfaction::minimum,
-            &mut Satisfaction::thresh,
-        )
+            &budget,
+            &mut Self::minimum,
+            &mut Self::thresh,
+        );
+        budget.check(&options, sat)
     }
 
     /// Produce a satisfaction(possibly malleable)
@@ -1827,14 +3641,340 @@ impl Satisfaction {
         stfr: &Sat,
         root_has_sig: bool,
         leaf_hash: &TapLeafHash,
-    ) -> Self {
-        Self::satisfy_helper(
+    ) -> Result<Self, Error> {
+        let options = stfr.satisfy_options();
+        let budget = Budget::new(&options);
+        let sat = Self::satisfy_helper(
             term,
             stfr,
             root_has_sig,
             leaf_hash,
-            &mut Satisfaction::minimum_mall,
-            &mut Satisfaction::thresh_mall,
+            &budget,
+            &mut Self::minimum_mall,
+            &mut Self::thresh_mall,
+        );
+        budget.check(&options, sat)
+    }
+}
+
+// The number of sub-(dis)satisfactions a single satisfaction may still
+// compute, see [`SatisfyOptions::max_branches`]
+struct Budget {
+    remaining: Cell<Option<usize>>,
+    exhausted: Cell<bool>,
+}
+
+impl Budget {
+    fn new(options: &SatisfyOptions) -> Self {
+        Budget {
+            remaining: Cell::new(options.max_branches),
+            exhausted: Cell::new(false),
+        }
+    }
+
+    // Spends one unit, returning false once the budget ran out
+    fn spend(&self) -> bool {
+        match self.remaining.get() {
+            None => true,
+            Some(0) => {
+                self.exhausted.set(true);
+                false
+            }
+            Some(n) => {
+                self.remaining.set(Some(n - 1));
+                true
+            }
+        }
+    }
+
+    // Applies the fallback strategy of `options` if the budget ran out
+    fn check(
+        &self,
+        options: &SatisfyOptions,
+        sat: TimelockedSatisfaction,
+    ) -> Result<TimelockedSatisfaction, Error> {
+        if !self.exhausted.get() {
+            return Ok(sat);
+        }
+        match (options.prefer_first, &sat.stack) {
+            (true, &Witness::Stack(_)) => Ok(sat),
+            _ => Err(Error::SatisfactionEffortExceeded(
+                options.max_branches.unwrap_or(0),
+            )),
+        }
+    }
+}
+
+/// Placeholder used in place of an ECDSA signature by [`Miniscript::all_satisfactions`]
+pub const DUMMY_ECDSA_SIG: [u8; 72] = [1; 72];
+/// Placeholder used in place of a Schnorr signature by [`Miniscript::all_satisfactions`]
+pub const DUMMY_SCHNORR_SIG: [u8; 64] = [1; 64];
+/// Placeholder used in place of a hash preimage by [`Miniscript::all_satisfactions`]
+pub const DUMMY_PREIMAGE: Preimage32 = [1; 32];
+
+type WitnessSet = BTreeSet<Vec<Vec<u8>>>;
+
+/// All satisfactions and dissatisfactions of a fragment under a satisfier
+/// that knows every signature and preimage, and for which all timelocks
+/// are met. Only witnesses of size at most `bound` are retained.
+pub(super) struct AllWitnesses {
+    /// Every distinct witness satisfying the fragment
+    pub(super) sat: WitnessSet,
+    /// Every distinct witness dissatisfying the fragment
+    pub(super) dissat: WitnessSet,
+}
+
+impl AllWitnesses {
+    fn new(sat: WitnessSet, dissat: WitnessSet) -> Self {
+        AllWitnesses { sat, dissat }
+    }
+
+    fn single(wit: Vec<Vec<u8>>) -> WitnessSet {
+        let mut ret = BTreeSet::new();
+        ret.insert(wit);
+        ret
+    }
+
+    // All concatenations of one witness from `bottom` followed by one from `top`
+    fn cat(bottom: &WitnessSet, top: &WitnessSet, bound: usize) -> WitnessSet {
+        let mut ret = BTreeSet::new();
+        for b in bottom {
+            for t in top {
+                let mut wit = b.clone();
+                wit.extend(t.iter().cloned());
+                if witness_size(&wit) <= bound {
+                    ret.insert(wit);
+                }
+            }
+        }
+        ret
+    }
+
+    fn union(mut a: WitnessSet, b: WitnessSet) -> WitnessSet {
+        a.extend(b);
+        a
+    }
+
+    // Witnesses with exactly `k` satisfied children satisfy, all others dissatisfy
+    fn split_at_count(by_count: Vec<WitnessSet>, k: usize) -> Self {
+        let mut sat = BTreeSet::new();
+        let mut dissat = BTreeSet::new();
+        for (n, wits) in by_count.into_iter().enumerate() {
+            if n == k {
+                sat = wits;
+            } else {
+                dissat.extend(wits);
+            }
+        }
+        Self::new(sat, dissat)
+    }
+
+    /// Enumerate the (dis)satisfactions of `term`
+    pub(super) fn enumerate<Pk, Ctx, Ext>(term: &Terminal<Pk, Ctx, Ext>, bound: usize) -> Self
+    where
+        Pk: MiniscriptKey + ToPublicKey,
+        Ctx: ScriptContext,
+        Ext: ParseableExt,
+    {
+        let sig = match Ctx::sig_type() {
+            SigType::Ecdsa => DUMMY_ECDSA_SIG.to_vec(),
+            SigType::Schnorr => DUMMY_SCHNORR_SIG.to_vec(),
+        };
+        let empty = || Self::single(vec![]);
+        let push_0 = || Self::single(vec![vec![]]);
+        let push_1 = || Self::single(vec![vec![1]]);
+
+        let ret = match *term {
+            Terminal::PkK(_) => Self::new(Self::single(vec![sig]), push_0()),
+            Terminal::PkH(ref pk) => {
+                let pk = pk.to_public_key().to_bytes();
+                Self::new(
+                    Self::single(vec![sig, pk.clone()]),
+                    Self::single(vec![vec![], pk]),
+                )
+            }
+            // The key behind a raw hash is unknown, so nothing can be produced
+            Terminal::RawPkH(_) => Self::new(BTreeSet::new(), BTreeSet::new()),
+            Terminal::After(_) | Terminal::Older(_) | Terminal::True => {
+                Self::new(empty(), BTreeSet::new())
+            }
+            Terminal::False => Self::new(BTreeSet::new(), empty()),
+            Terminal::Sha256(_)
+            | Terminal::Hash256(_)
+            | Terminal::Ripemd160(_)
+            | Terminal::Hash160(_) => Self::new(
+                Self::single(vec![DUMMY_PREIMAGE.to_vec()]),
+                Self::single(vec![vec![0; 32]]),
+            ),
+            Terminal::Alt(ref sub)
+            | Terminal::Swap(ref sub)
+            | Terminal::Check(ref sub)
+            | Terminal::ZeroNotEqual(ref sub) => Self::enumerate(&sub.node, bound),
+            Terminal::Verify(ref sub) => {
+                Self::new(Self::enumerate(&sub.node, bound).sat, BTreeSet::new())
+            }
+            Terminal::NonZero(ref sub) => {
+                Self::new(Self::enumerate(&sub.node, bound).sat, push_0())
+            }
+            Terminal::DupIf(ref sub) => {
+                let sub = Self::enumerate(&sub.node, bound);
+                Self::new(Self::cat(&sub.sat, &push_1(), bound), push_0())
+            }
+            Terminal::AndV(ref l, ref r) => {
+                let (l, r) = (
+                    Self::enumerate(&l.node, bound),
+                    Self::enumerate(&r.node, bound),
+                );
+                Self::new(
+                    Self::cat(&r.sat, &l.sat, bound),
+                    Self::cat(&r.dissat, &l.sat, bound),
+                )
+            }
+            Terminal::AndB(ref l, ref r) => {
+                let (l, r) = (
+                    Self::enumerate(&l.node, bound),
+                    Self::enumerate(&r.node, bound),
+                );
+                let dissat = Self::union(
+                    Self::cat(&r.dissat, &l.dissat, bound),
+                    Self::union(
+                        Self::cat(&r.sat, &l.dissat, bound),
+                        Self::cat(&r.dissat, &l.sat, bound),
+                    ),
+                );
+                Self::new(Self::cat(&r.sat, &l.sat, bound), dissat)
+            }
+            Terminal::AndOr(ref a, ref b, ref c) => {
+                let a = Self::enumerate(&a.node, bound);
+                let b = Self::enumerate(&b.node, bound);
+                let c = Self::enumerate(&c.node, bound);
+                Self::new(
+                    Self::union(
+                        Self::cat(&b.sat, &a.sat, bound),
+                        Self::cat(&c.sat, &a.dissat, bound),
+                    ),
+                    Self::union(
+                        Self::cat(&b.dissat, &a.sat, bound),
+                        Self::cat(&c.dissat, &a.dissat, bound),
+                    ),
+                )
+            }
+            Terminal::OrB(ref l, ref r) => {
+                let (l, r) = (
+                    Self::enumerate(&l.node, bound),
+                    Self::enumerate(&r.node, bound),
+                );
+                let sat = Self::union(
+                    Self::cat(&r.sat, &l.sat, bound),
+                    Self::union(
+                        Self::cat(&r.sat, &l.dissat, bound),
+                        Self::cat(&r.dissat, &l.sat, bound),
+                    ),
+                );
+                Self::new(sat, Self::cat(&r.dissat, &l.dissat, bound))
+            }
+            Terminal::OrD(ref l, ref r) | Terminal::OrC(ref l, ref r) => {
+                let (l, r) = (
+                    Self::enumerate(&l.node, bound),
+                    Self::enumerate(&r.node, bound),
+                );
+                let dissat = match *term {
+                    Terminal::OrD(..) => Self::cat(&r.dissat, &l.dissat, bound),
+                    _ => BTreeSet::new(),
+                };
+                let sat = Self::union(Self::cat(&r.sat, &l.dissat, bound), l.sat);
+                Self::new(sat, dissat)
+            }
+            Terminal::OrI(ref l, ref r) => {
+                let (l, r) = (
+                    Self::enumerate(&l.node, bound),
+                    Self::enumerate(&r.node, bound),
+                );
+                Self::new(
+                    Self::union(
+                        Self::cat(&l.sat, &push_1(), bound),
+                        Self::cat(&r.sat, &push_0(), bound),
+                    ),
+                    Self::union(
+                        Self::cat(&l.dissat, &push_1(), bound),
+                        Self::cat(&r.dissat, &push_0(), bound),
+                    ),
+                )
+            }
+            Terminal::Thresh(k, ref subs) => {
+                // Indexed by the number of satisfied sub-fragments so far. The
+                // first sub-fragment's witness ends up on top of the stack.
+                let mut by_count: Vec<WitnessSet> = vec![empty()];
+                for sub in subs {
+                    let sub = Self::enumerate(&sub.node, bound);
+                    let mut next = vec![BTreeSet::new(); by_count.len() + 1];
+                    for (n, wits) in by_count.iter().enumerate() {
+                        let d = Self::cat(&sub.dissat, wits, bound);
+                        let s = Self::cat(&sub.sat, wits, bound);
+                        next[n].extend(d);
+                        next[n + 1].extend(s);
+                    }
+                    by_count = next;
+                }
+                Self::split_at_count(by_count, k)
+            }
+            Terminal::Multi(k, ref keys) => {
+                // Signatures are in key order after the dummy element
+                let mut by_count: Vec<WitnessSet> = vec![push_0()];
+                for _ in keys {
+                    let mut next = vec![BTreeSet::new(); by_count.len() + 1];
+                    for (n, wits) in by_count.iter().enumerate() {
+                        next[n].extend(wits.iter().cloned());
+                        next[n + 1].extend(Self::cat(
+                            wits,
+                            &Self::single(vec![sig.clone()]),
+                            bound,
+                        ));
+                    }
+                    by_count = next;
+                }
+                let sat = by_count.into_iter().nth(k).unwrap_or_default();
+                let dissat = Self::single(vec![vec![]; k + 1]);
+                Self::new(sat, dissat)
+            }
+            Terminal::MultiA(k, ref keys) => {
+                // One element per key, the last key at the bottom of the stack
+                let mut by_count: Vec<WitnessSet> = vec![empty()];
+                for _ in keys {
+                    let mut next = vec![BTreeSet::new(); by_count.len() + 1];
+                    for (n, wits) in by_count.iter().enumerate() {
+                        next[n].extend(Self::cat(wits, &push_0(), bound));
+                        next[n + 1].extend(Self::cat(
+                            wits,
+                            &Self::single(vec![sig.clone()]),
+                            bound,
+                        ));
+                    }
+                    by_count = next;
+                }
+                Self::split_at_count(by_count, k)
+            }
+            // Extensions only expose a single (dis)satisfaction
+            Terminal::Ext(ref e) => {
+                let to_set = |sat: Satisfaction| match sat.stack {
+                    Witness::Stack(stack) => Self::single(stack),
+                    Witness::Unavailable | Witness::Impossible => BTreeSet::new(),
+                };
+                Self::new(
+                    to_set(e.satisfy::<Pk, _>(&())),
+                    to_set(e.dissatisfy::<Pk, _>(&())),
+                )
+            }
+        };
+        Self::new(
+            ret.sat
+                .into_iter()
+                .filter(|w| witness_size(w) <= bound)
+                .collect(),
+            ret.dissat
+                .into_iter()
+                .filter(|w| witness_size(w) <= bound)
+                .collect(),
         )
     }
 }
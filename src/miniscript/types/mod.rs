@@ -235,6 +235,44 @@ pub struct Type {
     pub mall: Malleability,
 }
 
+/// Displays the type as its base type and properties, e.g. `B/onduesm`
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self.corr.base {
+            Base::B => "B",
+            Base::K => "K",
+            Base::V => "V",
+            Base::W => "W",
+        })?;
+        fmt::Write::write_char(f, '/')?;
+        f.write_str(match self.corr.input {
+            Input::Zero => "z",
+            Input::One => "o",
+            Input::OneNonZero => "on",
+            Input::Any => "",
+            Input::AnyNonZero => "n",
+        })?;
+        if self.corr.dissatisfiable {
+            fmt::Write::write_char(f, 'd')?;
+        }
+        if self.corr.unit {
+            fmt::Write::write_char(f, 'u')?;
+        }
+        f.write_str(match self.mall.dissat {
+            Dissat::None => "f",
+            Dissat::Unique => "e",
+            Dissat::Unknown => "",
+        })?;
+        if self.mall.safe {
+            fmt::Write::write_char(f, 's')?;
+        }
+        if self.mall.non_malleable {
+            fmt::Write::write_char(f, 'm')?;
+        }
+        Ok(())
+    }
+}
+
 impl Type {
     /// Check whether the `self` is a subtype of `other` argument .
     /// This checks whether the argument `other` has attributes which are present
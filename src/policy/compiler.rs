@@ -143,19 +143,43 @@ impl CompilationKey {
     }
 }
 
+/// Cost model used by the compiler to rank candidate fragments.
+///
+/// This is exposed so that external tools can implement their own search
+/// strategies while reusing the compiler's costs. Since it implements
+/// [`Property`], the data for an existing fragment can be computed with
+/// [`Property::type_check`]; disjunctions additionally need the
+/// `branch_prob` of their children to be filled in.
 #[derive(Copy, Clone, Debug)]
-struct CompilerExtData {
+pub struct CompilerExtData {
     /// If this node is the direct child of a disjunction, this field must
     /// have the probability of its branch being taken. Otherwise it is ignored.
     /// All functions initialize it to `None`.
-    branch_prob: Option<f64>,
+    pub branch_prob: Option<f64>,
     /// The number of bytes needed to satisfy the fragment in segwit format
     /// (total length of all witness pushes, plus their own length prefixes)
-    sat_cost: f64,
+    pub sat_cost: f64,
     /// The number of bytes needed to dissatisfy the fragment in segwit format
     /// (total length of all witness pushes, plus their own length prefixes)
     /// for fragments that can be dissatisfied without failing the script.
-    dissat_cost: Option<f64>,
+    pub dissat_cost: Option<f64>,
+}
+
+impl CompilerExtData {
+    /// Compute a 1-dimensional cost for a fragment whose script is `pk_cost`
+    /// bytes long, given a probability of satisfaction and a probability of
+    /// dissatisfaction; if `dissat_prob` is `None` then it is assumed that
+    /// dissatisfaction never occurs
+    pub fn cost_1d(&self, pk_cost: usize, sat_prob: f64, dissat_prob: Option<f64>) -> f64 {
+        pk_cost as f64
+            + self.sat_cost * sat_prob
+            + match (dissat_prob, self.dissat_cost) {
+                (Some(prob), Some(cost)) => prob * cost,
+                (Some(_), None) => f64::INFINITY,
+                (None, Some(_)) => 0.0,
+                (None, None) => 0.0,
+            }
+    }
 }
 
 impl Property for CompilerExtData {
@@ -476,14 +500,8 @@ impl<Pk: MiniscriptKey, Ctx: ScriptContext> AstElemExt<Pk, Ctx> {
     /// and a probability of dissatisfaction; if `dissat_prob` is `None`
     /// then it is assumed that dissatisfaction never occurs
     fn cost_1d(&self, sat_prob: f64, dissat_prob: Option<f64>) -> f64 {
-        self.ms.ext.pk_cost as f64
-            + self.comp_ext_data.sat_cost * sat_prob
-            + match (dissat_prob, self.comp_ext_data.dissat_cost) {
-                (Some(prob), Some(cost)) => prob * cost,
-                (Some(_), None) => f64::INFINITY,
-                (None, Some(_)) => 0.0,
-                (None, None) => 0.0,
-            }
+        self.comp_ext_data
+            .cost_1d(self.ms.ext.pk_cost, sat_prob, dissat_prob)
     }
 }
 
@@ -796,6 +814,7 @@ where
     if let Some(ret) = policy_cache.get(&(policy.clone(), ord_sat_prob, ord_dissat_prob)) {
         return Ok(ret.clone());
     }
+    trace_span!("best_compilations", %policy, sat_prob, ?dissat_prob);
 
     let mut ret = BTreeMap::new();
 
@@ -1140,12 +1159,79 @@ fn compile_tern<Pk: MiniscriptKey, Ctx: ScriptContext>(
     Ok(())
 }
 
+/// The compilations of sub-policies computed by the compiler, which can be
+/// kept between compilations of similar policies with
+/// [`best_compilation_cached`]. Recompiling a policy after a small change,
+/// e.g. adding a key to one of its branches, then only compiles the
+/// changed sub-policies and the policies containing them.
+///
+/// Compilations are keyed by sub-policy and satisfaction probabilities, so
+/// a sub-policy is only reused if it has the same probabilities in the new
+/// policy. The cache grows with every compiled policy; see
+/// [`CompilationCache::clear`].
+pub struct CompilationCache<Pk: MiniscriptKey, Ctx: ScriptContext> {
+    cache: PolicyCache<Pk, Ctx>,
+}
+
+impl<Pk: MiniscriptKey, Ctx: ScriptContext> CompilationCache<Pk, Ctx> {
+    /// Create an empty cache
+    pub fn new() -> Self {
+        CompilationCache {
+            cache: PolicyCache::new(),
+        }
+    }
+
+    /// The number of cached sub-policy compilations
+    pub fn len(&self) -> usize {
+        self.cache.len()
+    }
+
+    /// Whether the cache is empty
+    pub fn is_empty(&self) -> bool {
+        self.cache.is_empty()
+    }
+
+    /// Drop all cached compilations
+    pub fn clear(&mut self) {
+        self.cache.clear()
+    }
+}
+
+impl<Pk: MiniscriptKey, Ctx: ScriptContext> Default for CompilationCache<Pk, Ctx> {
+    fn default() -> Self {
+        CompilationCache::new()
+    }
+}
+
+impl<Pk: MiniscriptKey, Ctx: ScriptContext> fmt::Debug for CompilationCache<Pk, Ctx> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CompilationCache")
+            .field("len", &self.cache.len())
+            .finish()
+    }
+}
+
 /// Obtain the best compilation of for p=1.0 and q=0
 pub fn best_compilation<Pk: MiniscriptKey, Ctx: ScriptContext>(
     policy: &Concrete<Pk>,
 ) -> Result<Miniscript<Pk, Ctx>, CompilerError> {
-    let mut policy_cache = PolicyCache::<Pk, Ctx>::new();
-    let x = &*best_t(&mut policy_cache, policy, 1.0, None)?.ms;
+    best_compilation_cached(policy, &mut CompilationCache::new())
+}
+
+/// Obtain the best compilation of for p=1.0 and q=0, reusing and extending
+/// the compilations of sub-policies in `cache`
+pub fn best_compilation_cached<Pk: MiniscriptKey, Ctx: ScriptContext>(
+    policy: &Concrete<Pk>,
+    cache: &mut CompilationCache<Pk, Ctx>,
+) -> Result<Miniscript<Pk, Ctx>, CompilerError> {
+    trace_span!("best_compilation", ctx = Ctx::name_str());
+    let policy_cache = &mut cache.cache;
+    let x = &*best_t(policy_cache, policy, 1.0, None)?.ms;
+    trace_event!(
+        cached_policies = policy_cache.len(),
+        script_size = x.script_size(),
+        "compiled"
+    );
     if !x.ty.mall.safe {
         Err(CompilerError::TopLevelNonSafe)
     } else if !x.ty.mall.non_malleable {
@@ -1331,6 +1417,35 @@ mod tests {
         );
     }
 
+    #[test]
+    fn compile_cached() {
+        let policy = SPolicy::from_str(
+            "or(and(pk(A),sha256(H)),or(and(pk(B),older(144)),and(pk(C),after(1000))))",
+        )
+        .unwrap();
+        let modified = SPolicy::from_str(
+            "or(and(pk(A),sha256(H)),or(and(pk(B),older(144)),and(thresh(1,pk(C),pk(D)),after(1000))))",
+        )
+        .unwrap();
+
+        let mut cache = CompilationCache::<String, Segwitv0>::new();
+        let ms = best_compilation_cached(&policy, &mut cache).unwrap();
+        assert_eq!(ms, best_compilation(&policy).unwrap());
+        let len = cache.len();
+        best_compilation_cached(&policy, &mut cache).unwrap();
+        assert_eq!(cache.len(), len);
+
+        let ms = best_compilation_cached(&modified, &mut cache).unwrap();
+        assert_eq!(ms, best_compilation(&modified).unwrap());
+        // Only the changed branch and its parents are compiled again
+        let mut fresh = CompilationCache::<String, Segwitv0>::new();
+        best_compilation_cached(&modified, &mut fresh).unwrap();
+        assert!(cache.len() - len < fresh.len());
+
+        cache.clear();
+        assert!(cache.is_empty());
+    }
+
     #[test]
     fn compile_misc() {
         let (keys, sig) = pubkeys_and_a_sig(10);
@@ -1619,6 +1734,21 @@ mod tests {
             assert_eq!(small_thresh_ms, small_thresh_ms_expected);
         }
     }
+
+    #[test]
+    fn compiler_ext_data_costs() {
+        let ms: Miniscript<String, Segwitv0> = ms_str!("and_b(pk(A),s:pk(B))");
+        let data = CompilerExtData::type_check(&ms.node, |_| None).unwrap();
+        // two sigs to satisfy, two empty pushes to dissatisfy
+        assert_eq!(data.sat_cost, 146.0);
+        assert_eq!(data.dissat_cost, Some(2.0));
+        let pk_cost = ms.ext.pk_cost;
+        assert_eq!(data.cost_1d(pk_cost, 1.0, None), pk_cost as f64 + 146.0);
+        assert_eq!(
+            data.cost_1d(pk_cost, 0.5, Some(0.5)),
+            pk_cost as f64 + 73.0 + 1.0
+        );
+    }
 }
 
 #[cfg(all(test, feature = "unstable"))]
@@ -21,6 +21,7 @@ use std::{error, fmt, str};
 use elements::{LockTime, PackedLockTime, Sequence};
 #[cfg(feature = "compiler")]
 use {
+    crate::descriptor::huffman_tree,
     crate::descriptor::TapTree,
     crate::miniscript::ScriptContext,
     crate::policy::compiler::CompilerError,
@@ -30,20 +31,29 @@ use {
     crate::Miniscript,
     crate::NoExt,
     crate::Tap,
+    crate::{ExtTranslator, Extension, TranslateExt},
     std::cmp::Reverse,
-    std::collections::{BTreeSet, BinaryHeap, HashMap},
+    std::collections::{BTreeMap, BTreeSet},
     std::sync::Arc,
 };
 
 use super::ENTAILMENT_MAX_TERMINALS;
 use crate::expression::{self, FromTree};
 use crate::miniscript::types::extra_props::TimelockInfo;
-use crate::{errstr, Error, ForEachKey, MiniscriptKey, Translator};
+use crate::{errstr, Error, ForEachKey, MiniscriptKey, TranslatePk, Translator};
 
 /// Maximum TapLeafs allowed in a compiled TapTree
 #[cfg(feature = "compiler")]
 const MAX_COMPILATION_LEAVES: usize = 1024;
 
+/// Maximum nesting of `and`, `or` and `thresh` in an arbitrary policy
+#[cfg(feature = "arbitrary")]
+const ARBITRARY_MAX_DEPTH: usize = 4;
+
+/// Maximum number of sub-policies of a `thresh` in an arbitrary policy
+#[cfg(feature = "arbitrary")]
+const ARBITRARY_MAX_THRESH: usize = 4;
+
 /// Concrete policy which corresponds directly to a Miniscript structure,
 /// and whose disjunctions are annotated with satisfaction probabilities
 /// to assist the compiler
@@ -339,7 +349,7 @@ impl<Pk: MiniscriptKey> Policy<Pk> {
             let mut prob = 0.;
             let semantic_policy = self.lift()?;
             let concrete_keys = self.keys();
-            let key_prob_map: HashMap<_, _> = self
+            let key_prob_map: BTreeMap<_, _> = self
                 .to_tapleaf_prob_vec(1.0)
                 .into_iter()
                 .filter(|(_, ref pol)| match *pol {
@@ -362,7 +372,7 @@ impl<Pk: MiniscriptKey> Policy<Pk> {
                                 internal_key = Some(key.clone());
                             }
                         }
-                        None => return Err(errstr("Key should have existed in the HashMap!")),
+                        None => return Err(errstr("Key should have existed in the BTreeMap!")),
                     }
                 }
             }
@@ -427,6 +437,73 @@ impl<Pk: MiniscriptKey> Policy<Pk> {
         }
     }
 
+    /// Compile the [`Policy`] into a [`TrExt`][`Descriptor::TrExt`] descriptor like
+    /// [`Policy::compile_tr`], adding tap leaves with extensions, e.g. covenants,
+    /// which cannot be expressed as policies.
+    ///
+    /// The leaves are arranged as if the policy and the extension leaves were
+    /// the branches of a top level [`Or`][`Policy::Or`], with the policy having
+    /// `policy_odds` and each extension leaf the odds it is given with.
+    /// Extension leaves are taken as they are: they are neither compiled nor
+    /// costed, and their depth in the tree only depends on their odds.
+    #[cfg(feature = "compiler")]
+    pub fn compile_tr_ext<Ext: Extension>(
+        &self,
+        unspendable_key: Option<Pk>,
+        policy_odds: usize,
+        ext_leaves: Vec<(usize, Miniscript<Pk, Tap, Ext>)>,
+    ) -> Result<Descriptor<Pk, Ext>, Error> {
+        struct IntoExt;
+        impl<Ext: Extension> ExtTranslator<NoExt, Ext, ()> for IntoExt {
+            fn ext(&mut self, e: &NoExt) -> Result<Ext, ()> {
+                match *e {}
+            }
+        }
+
+        self.is_valid()?; // Check for validity
+        match self.is_safe_nonmalleable() {
+            (false, _) => return Err(Error::from(CompilerError::TopLevelNonSafe)),
+            (_, false) => {
+                return Err(Error::from(
+                    CompilerError::ImpossibleNonMalleableCompilation,
+                ))
+            }
+            _ => {}
+        }
+        let total_odds = policy_odds + ext_leaves.iter().map(|&(odds, _)| odds).sum::<usize>();
+        if total_odds == 0 {
+            return Err(errstr("All leaves have zero odds"));
+        }
+        let policy_prob = policy_odds as f64 / total_odds as f64;
+
+        let (internal_key, policy) = self.clone().extract_key(unspendable_key)?;
+        policy.check_num_tapleaves()?;
+        let mut leaves = vec![];
+        if policy != Policy::Trivial {
+            for (prob, pol) in policy.to_tapleaf_prob_vec(policy_prob) {
+                if pol == Policy::Unsatisfiable {
+                    continue;
+                }
+                let compilation = compiler::best_compilation::<Pk, Tap>(&pol)?;
+                compilation.sanity_check()?;
+                let compilation = compilation
+                    .translate_ext(&mut IntoExt)
+                    .expect("no extensions to translate");
+                leaves.push((prob, compilation));
+            }
+        }
+        for (odds, ms) in ext_leaves {
+            ms.sanity_check()?;
+            leaves.push((odds as f64 / total_odds as f64, ms));
+        }
+        let tree = if leaves.is_empty() {
+            None
+        } else {
+            Some(huffman_tree(leaves)?)
+        };
+        Descriptor::new_tr_ext(internal_key, tree)
+    }
+
     /// Compile the [`Policy`] into a [`Tr`][`Descriptor::Tr`] Descriptor, with policy-enumeration
     /// by [`Policy::enumerate_policy_tree`].
     ///
@@ -517,6 +594,22 @@ impl<Pk: MiniscriptKey> Policy<Pk> {
             _ => compiler::best_compilation(self),
         }
     }
+
+    /// Compile the descriptor into an optimized `Miniscript` representation
+    /// like [`Policy::compile`], reusing the compilations of unchanged
+    /// sub-policies from earlier compilations with the same `cache`
+    #[cfg(feature = "compiler")]
+    pub fn compile_cached<Ctx: ScriptContext>(
+        &self,
+        cache: &mut compiler::CompilationCache<Pk, Ctx>,
+    ) -> Result<Miniscript<Pk, Ctx>, CompilerError> {
+        self.is_valid()?;
+        match self.is_safe_nonmalleable() {
+            (false, _) => Err(CompilerError::TopLevelNonSafe),
+            (_, false) => Err(CompilerError::ImpossibleNonMalleableCompilation),
+            _ => compiler::best_compilation_cached(self, cache),
+        }
+    }
 }
 
 #[cfg(feature = "compiler")]
@@ -560,7 +653,7 @@ impl<Pk: MiniscriptKey> PolicyArc<Pk> {
         // owing to the current [policy element enumeration algorithm][`Policy::enumerate_pol`],
         // two passes of the algorithm might result in same sub-policy showing up. Currently, we
         // merge the nodes by adding up the corresponding probabilities for the same policy.
-        let mut pol_prob_map = HashMap::<Arc<Self>, OrdF64>::new();
+        let mut pol_prob_map = BTreeMap::<Arc<Self>, OrdF64>::new();
 
         let arc_self = Arc::new(self);
         tapleaf_prob_vec.insert((Reverse(OrdF64(prob)), Arc::clone(&arc_self)));
@@ -673,6 +766,17 @@ impl<Pk: MiniscriptKey> ForEachKey<Pk> for Policy<Pk> {
     }
 }
 
+impl<P: MiniscriptKey, Q: MiniscriptKey> TranslatePk<P, Q> for Policy<P> {
+    type Output = Policy<Q>;
+
+    fn translate_pk<T, E>(&self, t: &mut T) -> Result<Self::Output, E>
+    where
+        T: Translator<P, Q, E>,
+    {
+        self._translate_pk(t)
+    }
+}
+
 impl<Pk: MiniscriptKey> Policy<Pk> {
     /// Convert a policy using one kind of public key to another
     /// type of public key
@@ -1219,35 +1323,75 @@ impl_from_tree!(
     }
 );
 
+/// Generates policies of at most [`ARBITRARY_MAX_DEPTH`] nested fragments,
+/// with binary `and` and `or` and small thresholds
+#[cfg(feature = "arbitrary")]
+impl<'a, Pk> arbitrary::Arbitrary<'a> for Policy<Pk>
+where
+    Pk: MiniscriptKey + arbitrary::Arbitrary<'a>,
+    Pk::Sha256: arbitrary::Arbitrary<'a>,
+    Pk::Hash256: arbitrary::Arbitrary<'a>,
+    Pk::Ripemd160: arbitrary::Arbitrary<'a>,
+    Pk::Hash160: arbitrary::Arbitrary<'a>,
+{
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        arbitrary_policy(u, ARBITRARY_MAX_DEPTH)
+    }
+}
+
+/// Generate an arbitrary policy, only terminals once `depth` is exhausted
+#[cfg(feature = "arbitrary")]
+fn arbitrary_policy<'a, Pk>(
+    u: &mut arbitrary::Unstructured<'a>,
+    depth: usize,
+) -> arbitrary::Result<Policy<Pk>>
+where
+    Pk: MiniscriptKey + arbitrary::Arbitrary<'a>,
+    Pk::Sha256: arbitrary::Arbitrary<'a>,
+    Pk::Hash256: arbitrary::Arbitrary<'a>,
+    Pk::Ripemd160: arbitrary::Arbitrary<'a>,
+    Pk::Hash160: arbitrary::Arbitrary<'a>,
+{
+    let max_frag: u8 = if depth == 0 { 8 } else { 11 };
+    let policy = match u.int_in_range(0..=max_frag)? {
+        0 => Policy::Unsatisfiable,
+        1 => Policy::Trivial,
+        2 => Policy::Key(u.arbitrary()?),
+        3 => Policy::after(u.arbitrary()?),
+        4 => Policy::older(u.arbitrary()?),
+        5 => Policy::Sha256(u.arbitrary()?),
+        6 => Policy::Hash256(u.arbitrary()?),
+        7 => Policy::Ripemd160(u.arbitrary()?),
+        8 => Policy::Hash160(u.arbitrary()?),
+        9 => Policy::And(vec![
+            arbitrary_policy(u, depth - 1)?,
+            arbitrary_policy(u, depth - 1)?,
+        ]),
+        10 => Policy::Or(vec![
+            (u.int_in_range(1..=10)?, arbitrary_policy(u, depth - 1)?),
+            (u.int_in_range(1..=10)?, arbitrary_policy(u, depth - 1)?),
+        ]),
+        _ => {
+            let n = u.int_in_range(1..=ARBITRARY_MAX_THRESH)?;
+            let k = u.int_in_range(1..=n)?;
+            let subs = (0..n)
+                .map(|_| arbitrary_policy(u, depth - 1))
+                .collect::<arbitrary::Result<Vec<_>>>()?;
+            Policy::Threshold(k, subs)
+        }
+    };
+    Ok(policy)
+}
+
 /// Create a Huffman Tree from compiled [Miniscript] nodes
 #[cfg(feature = "compiler")]
 fn with_huffman_tree<Pk: MiniscriptKey>(
     ms: Vec<(OrdF64, Miniscript<Pk, Tap>)>,
 ) -> Result<TapTree<Pk, NoExt>, Error> {
-    let mut node_weights = BinaryHeap::<(Reverse<OrdF64>, TapTree<Pk>)>::new();
-    for (prob, script) in ms {
-        node_weights.push((Reverse(prob), TapTree::Leaf(Arc::new(script))));
-    }
-    if node_weights.is_empty() {
+    if ms.is_empty() {
         return Err(errstr("Empty Miniscript compilation"));
     }
-    while node_weights.len() > 1 {
-        let (p1, s1) = node_weights.pop().expect("len must atleast be two");
-        let (p2, s2) = node_weights.pop().expect("len must atleast be two");
-
-        let p = (p1.0).0 + (p2.0).0;
-        node_weights.push((
-            Reverse(OrdF64(p)),
-            TapTree::Tree(Arc::from(s1), Arc::from(s2)),
-        ));
-    }
-
-    debug_assert!(node_weights.len() == 1);
-    let node = node_weights
-        .pop()
-        .expect("huffman tree algorithm is broken")
-        .1;
-    Ok(node)
+    huffman_tree(ms.into_iter().map(|(prob, ms)| (prob.0, ms)).collect())
 }
 
 /// Enumerate a [Thresh][`Policy::Threshold`](k, ..n..) into `n` different thresh.
@@ -29,11 +29,13 @@ use crate::{error, fmt};
 pub mod compiler;
 pub mod concrete;
 pub mod semantic;
+pub mod template;
 
 pub use self::concrete::Policy as Concrete;
 /// Semantic policies are "abstract" policies elsewhere; but we
 /// avoid this word because it is a reserved keyword in Rust
 pub use self::semantic::Policy as Semantic;
+pub use self::template::{PolicyDiff, TemplateMismatch, MAX_BINDING_STEPS};
 use crate::descriptor::{CovError, Descriptor};
 use crate::miniscript::{Miniscript, ScriptContext};
 use crate::{BtcPolicy, Error, Extension, MiniscriptKey, Terminal};
@@ -68,6 +70,8 @@ pub enum LiftError {
     BranchExceedResourceLimits,
     /// Cannot lift raw descriptors
     RawDescriptorLift,
+    /// Cannot lift taproot trees with hidden nodes
+    HiddenTapNode,
 }
 
 impl fmt::Display for LiftError {
@@ -80,6 +84,7 @@ impl fmt::Display for LiftError {
                 "Cannot lift policies containing one branch that exceeds resource limits",
             ),
             LiftError::RawDescriptorLift => f.write_str("Cannot lift raw descriptors"),
+            LiftError::HiddenTapNode => f.write_str("Cannot lift taproot trees with hidden nodes"),
         }
     }
 }
@@ -89,7 +94,10 @@ impl error::Error for LiftError {
         use self::LiftError::*;
 
         match self {
-            HeightTimelockCombination | BranchExceedResourceLimits | RawDescriptorLift => None,
+            HeightTimelockCombination
+            | BranchExceedResourceLimits
+            | RawDescriptorLift
+            | HiddenTapNode => None,
         }
     }
 }
@@ -125,6 +133,16 @@ impl<Pk: MiniscriptKey, Ctx: ScriptContext, Ext: Extension> Liftable<Pk>
     }
 }
 
+impl<Pk: MiniscriptKey, Ctx: ScriptContext, Ext: Extension> Miniscript<Pk, Ctx, Ext> {
+    /// Lift the miniscript like [`Liftable::lift`], but keep extension
+    /// fragments as [`Semantic::Ext`] rather than failing, so that timelocks
+    /// and keys of miniscripts with covenants can still be analyzed.
+    pub fn lift_ext(&self) -> Result<Semantic<Pk, Ext>, Error> {
+        self.lift_check()?;
+        lift_terminal(self.as_inner(), &|e| Ok(Semantic::Ext(e.clone())))
+    }
+}
+
 impl<Pk, Ctx, Ext> Liftable<Pk> for Terminal<Pk, Ctx, Ext>
 where
     Pk: MiniscriptKey,
@@ -132,54 +150,81 @@ where
     Ext: Extension,
 {
     fn lift(&self) -> Result<Semantic<Pk>, Error> {
-        let ret = match *self {
-            Terminal::PkK(ref pk) | Terminal::PkH(ref pk) => Semantic::Key(pk.clone()),
-            Terminal::RawPkH(ref _pkh) => {
-                return Err(Error::LiftError(LiftError::RawDescriptorLift))
-            }
-            Terminal::After(t) => Semantic::After(t),
-            Terminal::Older(t) => Semantic::Older(t),
-            Terminal::Sha256(ref h) => Semantic::Sha256(h.clone()),
-            Terminal::Hash256(ref h) => Semantic::Hash256(h.clone()),
-            Terminal::Ripemd160(ref h) => Semantic::Ripemd160(h.clone()),
-            Terminal::Hash160(ref h) => Semantic::Hash160(h.clone()),
-            Terminal::False => Semantic::Unsatisfiable,
-            Terminal::True => Semantic::Trivial,
-            Terminal::Alt(ref sub)
-            | Terminal::Swap(ref sub)
-            | Terminal::Check(ref sub)
-            | Terminal::DupIf(ref sub)
-            | Terminal::Verify(ref sub)
-            | Terminal::NonZero(ref sub)
-            | Terminal::ZeroNotEqual(ref sub) => sub.node.lift()?,
-            Terminal::AndV(ref left, ref right) | Terminal::AndB(ref left, ref right) => {
-                Semantic::Threshold(2, vec![left.node.lift()?, right.node.lift()?])
-            }
-            Terminal::AndOr(ref a, ref b, ref c) => Semantic::Threshold(
-                1,
+        lift_terminal(self, &|_| Err(Error::CovError(CovError::CovenantLift)))
+    }
+}
+
+// Lifts `term`, with `ext` lifting its extension fragments
+fn lift_terminal<Pk, Ctx, Ext, QExt, F>(
+    term: &Terminal<Pk, Ctx, Ext>,
+    ext: &F,
+) -> Result<Semantic<Pk, QExt>, Error>
+where
+    Pk: MiniscriptKey,
+    Ctx: ScriptContext,
+    Ext: Extension,
+    QExt: Extension,
+    F: Fn(&Ext) -> Result<Semantic<Pk, QExt>, Error>,
+{
+    let ret = match *term {
+        Terminal::PkK(ref pk) | Terminal::PkH(ref pk) => Semantic::Key(pk.clone()),
+        Terminal::RawPkH(ref _pkh) => return Err(Error::LiftError(LiftError::RawDescriptorLift)),
+        Terminal::After(t) => Semantic::After(t),
+        Terminal::Older(t) => Semantic::Older(t),
+        Terminal::Sha256(ref h) => Semantic::Sha256(h.clone()),
+        Terminal::Hash256(ref h) => Semantic::Hash256(h.clone()),
+        Terminal::Ripemd160(ref h) => Semantic::Ripemd160(h.clone()),
+        Terminal::Hash160(ref h) => Semantic::Hash160(h.clone()),
+        Terminal::False => Semantic::Unsatisfiable,
+        Terminal::True => Semantic::Trivial,
+        Terminal::Alt(ref sub)
+        | Terminal::Swap(ref sub)
+        | Terminal::Check(ref sub)
+        | Terminal::DupIf(ref sub)
+        | Terminal::Verify(ref sub)
+        | Terminal::NonZero(ref sub)
+        | Terminal::ZeroNotEqual(ref sub) => lift_terminal(&sub.node, ext)?,
+        Terminal::AndV(ref left, ref right) | Terminal::AndB(ref left, ref right) => {
+            Semantic::Threshold(
+                2,
                 vec![
-                    Semantic::Threshold(2, vec![a.node.lift()?, b.node.lift()?]),
-                    c.node.lift()?,
+                    lift_terminal(&left.node, ext)?,
+                    lift_terminal(&right.node, ext)?,
                 ],
-            ),
-            Terminal::OrB(ref left, ref right)
-            | Terminal::OrD(ref left, ref right)
-            | Terminal::OrC(ref left, ref right)
-            | Terminal::OrI(ref left, ref right) => {
-                Semantic::Threshold(1, vec![left.node.lift()?, right.node.lift()?])
-            }
-            Terminal::Thresh(k, ref subs) => {
-                let semantic_subs: Result<_, Error> = subs.iter().map(|s| s.node.lift()).collect();
-                Semantic::Threshold(k, semantic_subs?)
-            }
-            Terminal::Multi(k, ref keys) | Terminal::MultiA(k, ref keys) => {
-                Semantic::Threshold(k, keys.iter().map(|k| Semantic::Key(k.clone())).collect())
-            }
-            Terminal::Ext(ref _e) => Err(Error::CovError(CovError::CovenantLift))?,
+            )
         }
-        .normalized();
-        Ok(ret)
+        Terminal::AndOr(ref a, ref b, ref c) => Semantic::Threshold(
+            1,
+            vec![
+                Semantic::Threshold(
+                    2,
+                    vec![lift_terminal(&a.node, ext)?, lift_terminal(&b.node, ext)?],
+                ),
+                lift_terminal(&c.node, ext)?,
+            ],
+        ),
+        Terminal::OrB(ref left, ref right)
+        | Terminal::OrD(ref left, ref right)
+        | Terminal::OrC(ref left, ref right)
+        | Terminal::OrI(ref left, ref right) => Semantic::Threshold(
+            1,
+            vec![
+                lift_terminal(&left.node, ext)?,
+                lift_terminal(&right.node, ext)?,
+            ],
+        ),
+        Terminal::Thresh(k, ref subs) => {
+            let semantic_subs: Result<_, Error> =
+                subs.iter().map(|s| lift_terminal(&s.node, ext)).collect();
+            Semantic::Threshold(k, semantic_subs?)
+        }
+        Terminal::Multi(k, ref keys) | Terminal::MultiA(k, ref keys) => {
+            Semantic::Threshold(k, keys.iter().map(|k| Semantic::Key(k.clone())).collect())
+        }
+        Terminal::Ext(ref e) => ext(e)?,
     }
+    .normalized();
+    Ok(ret)
 }
 
 impl<Pk: MiniscriptKey, T: Extension> Liftable<Pk> for Descriptor<Pk, T> {
@@ -197,6 +242,25 @@ impl<Pk: MiniscriptKey, T: Extension> Liftable<Pk> for Descriptor<Pk, T> {
     }
 }
 
+impl<Pk: MiniscriptKey, T: Extension> Descriptor<Pk, T> {
+    /// Lift the descriptor like [`Liftable::lift`], but keep extension
+    /// fragments as [`Semantic::Ext`] rather than failing.
+    ///
+    /// A covenant descriptor additionally requires a signature of its
+    /// covenant key.
+    pub fn lift_ext(&self) -> Result<Semantic<Pk, T>, Error> {
+        match *self {
+            Descriptor::LegacyCSFSCov(ref cov) => Ok(Semantic::Threshold(
+                2,
+                vec![Semantic::Key(cov.pk().clone()), cov.to_ms().lift_ext()?],
+            )
+            .normalized()),
+            Descriptor::TrExt(ref tr) => tr.lift_ext(),
+            _ => self.lift().map(Semantic::into_ext),
+        }
+    }
+}
+
 impl<Pk: MiniscriptKey> Liftable<Pk> for Semantic<Pk> {
     fn lift(&self) -> Result<Semantic<Pk>, Error> {
         Ok(self.clone())
@@ -273,8 +337,9 @@ mod tests {
     use super::{Concrete, Liftable, Semantic};
     #[cfg(feature = "compiler")]
     use crate::descriptor::Tr;
+    use crate::Descriptor;
     #[cfg(feature = "compiler")]
-    use crate::{descriptor::TapTree, Descriptor, Tap};
+    use crate::{descriptor::TapTree, Tap};
 
     type ConcretePol = Concrete<String>;
     type SemanticPol = Semantic<String>;
@@ -405,6 +470,68 @@ mod tests {
         );
     }
 
+    #[test]
+    fn lift_multi_a() {
+        let ms: Miniscript<String, crate::Tap> = "multi_a(2,A,B,C)".parse().unwrap();
+        let lifted = ms.lift().unwrap();
+        assert_eq!(
+            lifted,
+            Semantic::Threshold(
+                2,
+                vec![
+                    Semantic::Key("A".to_owned()),
+                    Semantic::Key("B".to_owned()),
+                    Semantic::Key("C".to_owned()),
+                ]
+            )
+        );
+        assert_eq!(lifted.n_keys(), 3);
+        assert_eq!(lifted.minimum_n_keys(), Some(2));
+        assert!(lifted.is_key_only());
+
+        let ms: Miniscript<String, crate::Tap> =
+            "and_v(v:multi_a(1,A,B),older(42))".parse().unwrap();
+        let lifted = ms.lift().unwrap();
+        assert_eq!(lifted.n_keys(), 2);
+        assert_eq!(lifted.minimum_n_keys(), Some(1));
+        assert!(!lifted.is_key_only());
+    }
+
+    #[test]
+    fn lift_ext() {
+        use crate::extensions::{CovExtArgs, CovenantExt};
+        type CovSemantic = Semantic<String, CovenantExt<CovExtArgs>>;
+
+        let desc = Descriptor::<String, CovenantExt<CovExtArgs>>::from_str(
+            "eltr(A,{pk(B),and_v(v:pk(C),and_v(v:older(144),num64_eq(out_v(0),1000)))})",
+        )
+        .unwrap();
+        assert!(desc.lift().is_err());
+        let lifted = desc.lift_ext().unwrap();
+        assert_eq!(
+            lifted,
+            CovSemantic::from_str(
+                "or(pk(A),or(pk(B),and(pk(C),older(144),num64_eq(out_v(0),1000))))"
+            )
+            .unwrap()
+        );
+        assert_eq!(lifted.relative_timelocks(), vec![144]);
+        assert_eq!(lifted.n_keys(), 3);
+        assert_eq!(lifted.minimum_n_keys(), Some(1));
+        assert_eq!(
+            lifted.clone().at_age(Sequence::from_height(100)),
+            CovSemantic::from_str("or(pk(A),pk(B))").unwrap()
+        );
+        assert_eq!(CovSemantic::from_str(&lifted.to_string()).unwrap(), lifted);
+
+        // Descriptors without extensions lift as before
+        let desc = Descriptor::<String, CovenantExt<CovExtArgs>>::from_str(
+            "elwsh(and_v(v:pk(A),after(100)))",
+        )
+        .unwrap();
+        assert_eq!(desc.lift_ext().unwrap(), desc.lift().unwrap().into_ext());
+    }
+
     #[test]
     #[cfg(feature = "compiler")]
     fn taproot_compile() {
@@ -522,6 +649,36 @@ mod tests {
         }
     }
 
+    #[test]
+    #[cfg(feature = "compiler")]
+    fn taproot_compile_ext() {
+        use crate::extensions::{CovExtArgs, CovenantExt};
+
+        let pol = policy_str!("or(pk(A),and(pk(B),older(10)))");
+        let ext_leaf = Miniscript::<String, Tap, CovenantExt<CovExtArgs>>::from_str(
+            "and_v(v:pk(C),num64_eq(out_v(0),1000))",
+        )
+        .unwrap();
+        let desc = pol
+            .compile_tr_ext(
+                Some("UNSPENDABLE".to_string()),
+                1,
+                vec![(1, ext_leaf.clone())],
+            )
+            .unwrap();
+        let tr = match desc {
+            Descriptor::TrExt(ref tr) => tr,
+            _ => unreachable!(),
+        };
+        assert_eq!(tr.internal_key(), "A");
+        let leaves: Vec<String> = tr.iter_scripts().map(|(_, ms)| ms.to_string()).collect();
+        assert_eq!(leaves.len(), 2);
+        assert!(leaves.contains(&"and_v(v:pk(B),older(10))".to_string()));
+        assert!(leaves.contains(&ext_leaf.to_string()));
+
+        assert!(pol.compile_tr_ext(None, 0, vec![(0, ext_leaf)]).is_err());
+    }
+
     #[test]
     #[cfg(feature = "compiler")]
     fn experimental_taproot_compile() {
@@ -581,6 +738,28 @@ mod tests {
             assert_eq!(desc, expected_desc);
         }
     }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn policy_serde() {
+        let s = "or(99@pk(A),1@and(pk(B),older(144)))";
+        let concrete = ConcretePol::from_str(s).unwrap();
+        let json = serde_json::to_value(&concrete).unwrap();
+        assert_eq!(json, serde_json::json!(s));
+        assert_eq!(
+            serde_json::from_value::<ConcretePol>(json).unwrap(),
+            concrete
+        );
+        assert!(serde_json::from_value::<ConcretePol>(serde_json::json!("or(pk(A))")).is_err());
+
+        let semantic = concrete.lift().unwrap();
+        let json = serde_json::to_value(&semantic).unwrap();
+        assert_eq!(json, serde_json::json!(semantic.to_string()));
+        assert_eq!(
+            serde_json::from_value::<SemanticPol>(json).unwrap(),
+            semantic
+        );
+    }
 }
 
 #[cfg(all(test, feature = "compiler", feature = "unstable"))]
@@ -14,6 +14,7 @@
 
 //! Abstract Policies
 
+use std::collections::{BTreeMap, BTreeSet};
 use std::str::FromStr;
 use std::{fmt, str};
 
@@ -21,7 +22,9 @@ use elements::{LockTime, PackedLockTime, Sequence};
 
 use super::concrete::PolicyError;
 use super::ENTAILMENT_MAX_TERMINALS;
-use crate::{errstr, expression, Error, ForEachKey, MiniscriptKey, Translator};
+use crate::{
+    errstr, expression, Error, Extension, ForEachKey, MiniscriptKey, NoExt, TranslatePk, Translator,
+};
 
 /// Abstract policy which corresponds to the semantics of a Miniscript
 /// and which allows complex forms of analysis, e.g. filtering and
@@ -29,8 +32,11 @@ use crate::{errstr, expression, Error, ForEachKey, MiniscriptKey, Translator};
 /// Semantic policies store only hashes of keys to ensure that objects
 /// representing the same policy are lifted to the same `Semantic`,
 /// regardless of their choice of `pk` or `pk_h` nodes.
+///
+/// Extension fragments, e.g. covenants, are kept as opaque constraints on
+/// the spending transaction, see [`Policy::Ext`].
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
-pub enum Policy<Pk: MiniscriptKey> {
+pub enum Policy<Pk: MiniscriptKey, Ext: Extension = NoExt> {
     /// Unsatisfiable
     Unsatisfiable,
     /// Trivially satisfiable
@@ -50,27 +56,53 @@ pub enum Policy<Pk: MiniscriptKey> {
     /// A HASH160 whose preimage must be provided to satisfy the descriptor
     Hash160(Pk::Hash160),
     /// A set of descriptors, satisfactions must be provided for `k` of them
-    Threshold(usize, Vec<Policy<Pk>>),
+    Threshold(usize, Vec<Policy<Pk, Ext>>),
+    /// An extension fragment, which is satisfied by the spending transaction
+    /// rather than by keys, hashes or timelocks
+    Ext(Ext),
 }
 
-impl<Pk> Policy<Pk>
+impl<Pk, Ext> Policy<Pk, Ext>
 where
     Pk: MiniscriptKey,
+    Ext: Extension,
 {
     /// Construct a `Policy::After` from `n`. Helper function equivalent to
     /// `Policy::After(PackedLockTime::from(LockTime::from_consensus(n)))`.
-    pub fn after(n: u32) -> Policy<Pk> {
+    pub fn after(n: u32) -> Policy<Pk, Ext> {
         Policy::After(PackedLockTime::from(LockTime::from_consensus(n)))
     }
 
     /// Construct a `Policy::Older` from `n`. Helper function equivalent to
     /// `Policy::Older(Sequence::from_consensus(n))`.
-    pub fn older(n: u32) -> Policy<Pk> {
+    pub fn older(n: u32) -> Policy<Pk, Ext> {
         Policy::Older(Sequence::from_consensus(n))
     }
 }
 
-impl<Pk: MiniscriptKey> ForEachKey<Pk> for Policy<Pk> {
+impl<Pk: MiniscriptKey> Policy<Pk> {
+    /// Convert a policy without extensions into a policy which may have
+    /// extensions of type `Ext`, e.g. to combine it with lifted covenants
+    pub fn into_ext<Ext: Extension>(self) -> Policy<Pk, Ext> {
+        match self {
+            Policy::Unsatisfiable => Policy::Unsatisfiable,
+            Policy::Trivial => Policy::Trivial,
+            Policy::Key(pk) => Policy::Key(pk),
+            Policy::After(t) => Policy::After(t),
+            Policy::Older(t) => Policy::Older(t),
+            Policy::Sha256(h) => Policy::Sha256(h),
+            Policy::Hash256(h) => Policy::Hash256(h),
+            Policy::Ripemd160(h) => Policy::Ripemd160(h),
+            Policy::Hash160(h) => Policy::Hash160(h),
+            Policy::Threshold(k, subs) => {
+                Policy::Threshold(k, subs.into_iter().map(Policy::into_ext).collect())
+            }
+            Policy::Ext(e) => match e {},
+        }
+    }
+}
+
+impl<Pk: MiniscriptKey, Ext: Extension> ForEachKey<Pk> for Policy<Pk, Ext> {
     fn for_each_key<'a, F: FnMut(&'a Pk) -> bool>(&'a self, mut pred: F) -> bool
     where
         Pk: 'a,
@@ -83,13 +115,25 @@ impl<Pk: MiniscriptKey> ForEachKey<Pk> for Policy<Pk> {
             | Policy::Ripemd160(..)
             | Policy::Hash160(..)
             | Policy::After(..)
-            | Policy::Older(..) => true,
+            | Policy::Older(..)
+            | Policy::Ext(..) => true,
             Policy::Threshold(_, ref subs) => subs.iter().all(|sub| sub.for_each_key(&mut pred)),
         }
     }
 }
 
-impl<Pk: MiniscriptKey> Policy<Pk> {
+impl<P: MiniscriptKey, Q: MiniscriptKey, Ext: Extension> TranslatePk<P, Q> for Policy<P, Ext> {
+    type Output = Policy<Q, Ext>;
+
+    fn translate_pk<T, E>(&self, t: &mut T) -> Result<Self::Output, E>
+    where
+        T: Translator<P, Q, E>,
+    {
+        self._translate_pk(t)
+    }
+}
+
+impl<Pk: MiniscriptKey, Ext: Extension> Policy<Pk, Ext> {
     /// Convert a policy using one kind of public key to another
     /// type of public key
     ///
@@ -132,7 +176,7 @@ impl<Pk: MiniscriptKey> Policy<Pk> {
     /// let expected_policy = Policy::from_str(&format!("and(pk({}),pk({}))", alice_pk, bob_pk)).unwrap();
     /// assert_eq!(real_policy, expected_policy);
     /// ```
-    pub fn translate_pk<Q, E, T>(&self, t: &mut T) -> Result<Policy<Q>, E>
+    pub fn translate_pk<Q, E, T>(&self, t: &mut T) -> Result<Policy<Q, Ext>, E>
     where
         T: Translator<Pk, Q, E>,
         Q: MiniscriptKey,
@@ -140,7 +184,7 @@ impl<Pk: MiniscriptKey> Policy<Pk> {
         self._translate_pk(t)
     }
 
-    fn _translate_pk<Q, E, T>(&self, t: &mut T) -> Result<Policy<Q>, E>
+    fn _translate_pk<Q, E, T>(&self, t: &mut T) -> Result<Policy<Q, Ext>, E>
     where
         T: Translator<Pk, Q, E>,
         Q: MiniscriptKey,
@@ -156,10 +200,11 @@ impl<Pk: MiniscriptKey> Policy<Pk> {
             Policy::After(n) => Ok(Policy::After(n)),
             Policy::Older(n) => Ok(Policy::Older(n)),
             Policy::Threshold(k, ref subs) => {
-                let new_subs: Result<Vec<Policy<Q>>, _> =
+                let new_subs: Result<Vec<Policy<Q, Ext>>, _> =
                     subs.iter().map(|sub| sub._translate_pk(t)).collect();
                 new_subs.map(|ok| Policy::Threshold(k, ok))
             }
+            Policy::Ext(ref e) => Ok(Policy::Ext(e.clone())),
         }
     }
 
@@ -170,7 +215,7 @@ impl<Pk: MiniscriptKey> Policy<Pk> {
 
     // This algorithm has a naive implementation. It is possible to optimize this
     // by memoizing and maintaining a hashmap.
-    pub fn entails(self, other: Policy<Pk>) -> Result<bool, PolicyError> {
+    pub fn entails(self, other: Policy<Pk, Ext>) -> Result<bool, PolicyError> {
         if self.n_terminals() > ENTAILMENT_MAX_TERMINALS {
             return Err(PolicyError::EntailmentMaxTerminals);
         }
@@ -207,7 +252,7 @@ impl<Pk: MiniscriptKey> Policy<Pk> {
     // Helper function to get the first constraint in the policy.
     // Returns the first leaf policy. Used in policy entailment.
     // Assumes that the current policy is normalized.
-    fn first_constraint(&self) -> Policy<Pk> {
+    fn first_constraint(&self) -> Policy<Pk, Ext> {
         debug_assert!(self.clone().normalized() == self.clone());
         match self {
             &Policy::Threshold(_k, ref subs) => subs[0].first_constraint(),
@@ -220,7 +265,11 @@ impl<Pk: MiniscriptKey> Policy<Pk> {
     // policy.
     // Witness is currently encoded as policy. Only accepts leaf fragment and
     // a normalized policy
-    pub(crate) fn satisfy_constraint(self, witness: &Policy<Pk>, available: bool) -> Policy<Pk> {
+    pub(crate) fn satisfy_constraint(
+        self,
+        witness: &Policy<Pk, Ext>,
+        available: bool,
+    ) -> Policy<Pk, Ext> {
         debug_assert!(self.clone().normalized() == self);
         // only for internal purposes, safe to use unreachable!
         if let Policy::Threshold(..) = *witness {
@@ -247,7 +296,7 @@ impl<Pk: MiniscriptKey> Policy<Pk> {
     }
 }
 
-impl<Pk: MiniscriptKey> fmt::Debug for Policy<Pk> {
+impl<Pk: MiniscriptKey, Ext: Extension> fmt::Debug for Policy<Pk, Ext> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match *self {
             Policy::Unsatisfiable => f.write_str("UNSATISFIABLE()"),
@@ -259,6 +308,7 @@ impl<Pk: MiniscriptKey> fmt::Debug for Policy<Pk> {
             Policy::Hash256(ref h) => write!(f, "hash256({})", h),
             Policy::Ripemd160(ref h) => write!(f, "ripemd160({})", h),
             Policy::Hash160(ref h) => write!(f, "hash160({})", h),
+            Policy::Ext(ref e) => write!(f, "{}", e),
             Policy::Threshold(k, ref subs) => {
                 if k == subs.len() {
                     write!(f, "and(")?;
@@ -280,7 +330,7 @@ impl<Pk: MiniscriptKey> fmt::Debug for Policy<Pk> {
     }
 }
 
-impl<Pk: MiniscriptKey> fmt::Display for Policy<Pk> {
+impl<Pk: MiniscriptKey, Ext: Extension> fmt::Display for Policy<Pk, Ext> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match *self {
             Policy::Unsatisfiable => f.write_str("UNSATISFIABLE"),
@@ -292,6 +342,7 @@ impl<Pk: MiniscriptKey> fmt::Display for Policy<Pk> {
             Policy::Hash256(ref h) => write!(f, "hash256({})", h),
             Policy::Ripemd160(ref h) => write!(f, "ripemd160({})", h),
             Policy::Hash160(ref h) => write!(f, "hash160({})", h),
+            Policy::Ext(ref e) => write!(f, "{}", e),
             Policy::Threshold(k, ref subs) => {
                 if k == subs.len() {
                     write!(f, "and(")?;
@@ -314,9 +365,10 @@ impl<Pk: MiniscriptKey> fmt::Display for Policy<Pk> {
 }
 
 impl_from_str!(
-    Policy<Pk>,
+    Policy<Pk, Ext>,
+    => Ext; Extension,
     type Err = Error;,
-    fn from_str(s: &str) -> Result<Policy<Pk>, Error> {
+    fn from_str(s: &str) -> Result<Policy<Pk, Ext>, Error> {
         for ch in s.as_bytes() {
             if *ch < 20 || *ch > 127 {
                 return Err(Error::Unprintable(*ch));
@@ -328,11 +380,12 @@ impl_from_str!(
     }
 );
 
-serde_string_impl_pk!(Policy, "a miniscript semantic policy");
+serde_string_impl_pk!(Policy, "a miniscript semantic policy" => Ext ; Extension);
 
 impl_from_tree!(
-    Policy<Pk>,
-    fn from_tree(top: &expression::Tree) -> Result<Policy<Pk>, Error> {
+    Policy<Pk, Ext>,
+    => Ext; Extension,
+    fn from_tree(top: &expression::Tree) -> Result<Policy<Pk, Ext>, Error> {
         match (top.name, top.args.len()) {
             ("UNSATISFIABLE", 0) => Ok(Policy::Unsatisfiable),
             ("TRIVIAL", 0) => Ok(Policy::Trivial),
@@ -402,15 +455,17 @@ impl_from_tree!(
                 }
                 Ok(Policy::Threshold(thresh as usize, subs))
             }
-            _ => Err(errstr(top.name)),
+            _ => Ext::from_name_tree(top.name, &top.args)
+                .map(Policy::Ext)
+                .map_err(|_| errstr(top.name)),
         }
     }
 );
 
-impl<Pk: MiniscriptKey> Policy<Pk> {
+impl<Pk: MiniscriptKey, Ext: Extension> Policy<Pk, Ext> {
     /// Flatten out trees of `And`s and `Or`s; eliminate `Trivial` and
     /// `Unsatisfiable`s. Does not reorder any branches; use `.sort`.
-    pub fn normalized(self) -> Policy<Pk> {
+    pub fn normalized(self) -> Policy<Pk, Ext> {
         match self {
             Policy::Threshold(k, subs) => {
                 let mut ret_subs = Vec::with_capacity(subs.len());
@@ -485,6 +540,18 @@ impl<Pk: MiniscriptKey> Policy<Pk> {
         }
     }
 
+    /// Helper function to detect a policy that can only be satisfied with
+    /// signatures, i.e. one made of keys and thresholds of keys alone.
+    /// Such policies are good candidates for the taproot key path.
+    /// Like [`Policy::is_trivial`], this does not normalize the policy.
+    pub fn is_key_only(&self) -> bool {
+        match *self {
+            Policy::Key(..) => true,
+            Policy::Threshold(_, ref subs) => subs.iter().all(Policy::is_key_only),
+            _ => false,
+        }
+    }
+
     /// Helper function to do the recursion in `timelocks`.
     fn real_relative_timelocks(&self) -> Vec<u32> {
         match *self {
@@ -494,7 +561,8 @@ impl<Pk: MiniscriptKey> Policy<Pk> {
             | Policy::Sha256(..)
             | Policy::Hash256(..)
             | Policy::Ripemd160(..)
-            | Policy::Hash160(..) => vec![],
+            | Policy::Hash160(..)
+            | Policy::Ext(..) => vec![],
             Policy::After(..) => vec![],
             Policy::Older(t) => vec![t.to_consensus_u32()],
             Policy::Threshold(_, ref subs) => subs.iter().fold(vec![], |mut acc, x| {
@@ -522,7 +590,8 @@ impl<Pk: MiniscriptKey> Policy<Pk> {
             | Policy::Sha256(..)
             | Policy::Hash256(..)
             | Policy::Ripemd160(..)
-            | Policy::Hash160(..) => vec![],
+            | Policy::Hash160(..)
+            | Policy::Ext(..) => vec![],
             Policy::Older(..) => vec![],
             Policy::After(t) => vec![t.0],
             Policy::Threshold(_, ref subs) => subs.iter().fold(vec![], |mut acc, x| {
@@ -543,7 +612,7 @@ impl<Pk: MiniscriptKey> Policy<Pk> {
 
     /// Filter a policy by eliminating relative timelock constraints
     /// that are not satisfied at the given `age`.
-    pub fn at_age(mut self, age: Sequence) -> Policy<Pk> {
+    pub fn at_age(mut self, age: Sequence) -> Policy<Pk, Ext> {
         self = match self {
             Policy::Older(t) => {
                 if t.is_height_locked() && age.is_time_locked()
@@ -566,7 +635,7 @@ impl<Pk: MiniscriptKey> Policy<Pk> {
 
     /// Filter a policy by eliminating absolute timelock constraints
     /// that are not satisfied at the given `n` (`n OP_CHECKLOCKTIMEVERIFY`).
-    pub fn at_lock_time(mut self, n: LockTime) -> Policy<Pk> {
+    pub fn at_lock_time(mut self, n: LockTime) -> Policy<Pk, Ext> {
         use LockTime::*;
 
         self = match self {
@@ -602,7 +671,8 @@ impl<Pk: MiniscriptKey> Policy<Pk> {
             | Policy::Sha256(..)
             | Policy::Hash256(..)
             | Policy::Ripemd160(..)
-            | Policy::Hash160(..) => 0,
+            | Policy::Hash160(..)
+            | Policy::Ext(..) => 0,
             Policy::Threshold(_, ref subs) => subs.iter().map(|sub| sub.n_keys()).sum::<usize>(),
         }
     }
@@ -620,7 +690,8 @@ impl<Pk: MiniscriptKey> Policy<Pk> {
             | Policy::Sha256(..)
             | Policy::Hash256(..)
             | Policy::Ripemd160(..)
-            | Policy::Hash160(..) => Some(0),
+            | Policy::Hash160(..)
+            | Policy::Ext(..) => Some(0),
             Policy::Threshold(k, ref subs) => {
                 let mut sublens: Vec<usize> =
                     subs.iter().filter_map(Policy::minimum_n_keys).collect();
@@ -636,12 +707,12 @@ impl<Pk: MiniscriptKey> Policy<Pk> {
     }
 }
 
-impl<Pk: MiniscriptKey> Policy<Pk> {
+impl<Pk: MiniscriptKey, Ext: Extension> Policy<Pk, Ext> {
     /// "Sort" a policy to bring it into a canonical form to allow comparisons.
     /// Does **not** allow policies to be compared for functional equivalence;
     /// in general this appears to require Gröbner basis techniques that are not
     /// implemented.
-    pub fn sorted(self) -> Policy<Pk> {
+    pub fn sorted(self) -> Policy<Pk, Ext> {
         match self {
             Policy::Threshold(k, subs) => {
                 let mut new_subs: Vec<_> = subs.into_iter().map(Policy::sorted).collect();
@@ -653,6 +724,256 @@ impl<Pk: MiniscriptKey> Policy<Pk> {
     }
 }
 
+/// A likely mistake in a branch of a policy, as reported by [`Policy::lint`]
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Lint<Pk: MiniscriptKey, Ext: Extension = NoExt> {
+    /// The threshold requires more subpolicies than it has, so it can never
+    /// be satisfied
+    ImpossibleThreshold(Policy<Pk, Ext>),
+    /// Every way of satisfying the branch combines a height-based and a
+    /// time-based timelock of the same kind, so it can never be satisfied
+    ConflictingTimelocks(Policy<Pk, Ext>),
+    /// The conjunction requires the same key in more than one of its
+    /// subpolicies
+    DuplicateKey(Policy<Pk, Ext>, Pk),
+}
+
+// Timelock kinds used by a satisfaction, see `Policy::timelock_combinations`
+const ABS_HEIGHT: u8 = 1;
+const ABS_TIME: u8 = 2;
+const REL_HEIGHT: u8 = 4;
+const REL_TIME: u8 = 8;
+
+fn timelocks_conflict(kinds: u8) -> bool {
+    kinds & (ABS_HEIGHT | ABS_TIME) == ABS_HEIGHT | ABS_TIME
+        || kinds & (REL_HEIGHT | REL_TIME) == REL_HEIGHT | REL_TIME
+}
+
+impl<Pk: MiniscriptKey, Ext: Extension> Policy<Pk, Ext> {
+    /// Detect branches of the policy which can never be satisfied, because
+    /// of contradictory timelocks or impossible thresholds, and conjunctions
+    /// which require the same key more than once.
+    ///
+    /// Every offending branch is reported where the problem originates, so
+    /// a dead branch does not cause its parents to be reported as well.
+    /// Like [`Policy::is_trivial`], this does not normalize the policy.
+    pub fn lint(&self) -> Vec<Lint<Pk, Ext>> {
+        let mut lints = vec![];
+        self.real_lint(&mut lints);
+        lints
+    }
+
+    /// Helper function to do the recursion in `lint`.
+    fn real_lint(&self, lints: &mut Vec<Lint<Pk, Ext>>) {
+        let (k, subs) = match *self {
+            Policy::Threshold(k, ref subs) => (k, subs),
+            _ => return,
+        };
+        for sub in subs {
+            sub.real_lint(lints);
+        }
+
+        if k > subs.len() {
+            lints.push(Lint::ImpossibleThreshold(self.clone()));
+        } else if self.timelock_combinations().is_empty()
+            && subs
+                .iter()
+                .all(|sub| !sub.timelock_combinations().is_empty())
+        {
+            lints.push(Lint::ConflictingTimelocks(self.clone()));
+        }
+
+        if k == subs.len() {
+            let mut seen = BTreeSet::new();
+            let mut duplicates = BTreeSet::new();
+            for sub in subs {
+                let mut keys = BTreeSet::new();
+                sub.collect_keys(&mut keys);
+                for pk in keys {
+                    if !seen.insert(pk) {
+                        duplicates.insert(pk);
+                    }
+                }
+            }
+            for pk in duplicates {
+                lints.push(Lint::DuplicateKey(self.clone(), pk.clone()));
+            }
+        }
+    }
+
+    /// Helper function to collect all keys of the policy.
+    fn collect_keys<'a>(&'a self, keys: &mut BTreeSet<&'a Pk>) {
+        match *self {
+            Policy::Key(ref pk) => {
+                keys.insert(pk);
+            }
+            Policy::Threshold(_, ref subs) => {
+                for sub in subs {
+                    sub.collect_keys(keys);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Returns the kinds of timelocks used by each way of satisfying the
+    /// policy which does not mix heights and times. Empty if the policy is
+    /// unsatisfiable.
+    fn timelock_combinations(&self) -> Vec<u8> {
+        match *self {
+            Policy::Unsatisfiable => vec![],
+            Policy::Trivial
+            | Policy::Key(..)
+            | Policy::Sha256(..)
+            | Policy::Hash256(..)
+            | Policy::Ripemd160(..)
+            | Policy::Hash160(..)
+            | Policy::Ext(..) => vec![0],
+            Policy::After(t) => {
+                if LockTime::from(t).is_block_height() {
+                    vec![ABS_HEIGHT]
+                } else {
+                    vec![ABS_TIME]
+                }
+            }
+            Policy::Older(t) => {
+                if t.is_time_locked() {
+                    vec![REL_TIME]
+                } else {
+                    vec![REL_HEIGHT]
+                }
+            }
+            Policy::Threshold(k, ref subs) => {
+                if k > subs.len() {
+                    return vec![];
+                }
+                // combinations[j] holds the combinations of satisfying j of
+                // the subpolicies seen so far
+                let mut combinations = vec![vec![]; k + 1];
+                combinations[0].push(0);
+                for sub in subs {
+                    let sub_combinations = sub.timelock_combinations();
+                    for j in (0..k).rev() {
+                        for i in 0..combinations[j].len() {
+                            for kinds in &sub_combinations {
+                                let kinds = combinations[j][i] | kinds;
+                                if !timelocks_conflict(kinds)
+                                    && !combinations[j + 1].contains(&kinds)
+                                {
+                                    combinations[j + 1].push(kinds);
+                                }
+                            }
+                        }
+                    }
+                }
+                combinations.swap_remove(k)
+            }
+        }
+    }
+}
+
+/// How a key is used by a policy, as reported by [`Policy::key_weights`]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct KeyWeight {
+    /// The number of spend paths which require a signature of the key.
+    /// Saturates at `usize::MAX`.
+    pub spend_paths: usize,
+    /// The fewest keys, including this one, that must sign together on a
+    /// spend path which requires the key
+    pub min_quorum: usize,
+}
+
+impl<Pk: MiniscriptKey, Ext: Extension> Policy<Pk, Ext> {
+    /// Returns, for every key, how many spend paths use it and the size of
+    /// the smallest quorum it takes part in. Keys which appear on no
+    /// satisfiable spend path are omitted.
+    ///
+    /// A spend path is a choice of subpolicies to satisfy at every threshold.
+    /// Like [`Policy::is_trivial`], this does not normalize the policy.
+    pub fn key_weights(&self) -> BTreeMap<Pk, KeyWeight> {
+        let mut keys = BTreeSet::new();
+        self.collect_keys(&mut keys);
+        keys.into_iter()
+            .filter_map(|pk| {
+                let (all, without) = self.path_counts(pk);
+                // Once saturated, the counts no longer tell how many paths
+                // use the key
+                let spend_paths = if all == usize::max_value() {
+                    usize::max_value()
+                } else {
+                    all - without
+                };
+                let weight = KeyWeight {
+                    spend_paths,
+                    min_quorum: self.min_quorum_with(pk)?,
+                };
+                Some((pk.clone(), weight))
+            })
+            .collect()
+    }
+
+    /// Helper function for `key_weights`, returning the number of spend
+    /// paths and the number of those which do not use `pk`.
+    fn path_counts(&self, pk: &Pk) -> (usize, usize) {
+        match *self {
+            Policy::Unsatisfiable => (0, 0),
+            Policy::Key(ref key) if key == pk => (1, 0),
+            Policy::Trivial
+            | Policy::Key(..)
+            | Policy::After(..)
+            | Policy::Older(..)
+            | Policy::Sha256(..)
+            | Policy::Hash256(..)
+            | Policy::Ripemd160(..)
+            | Policy::Hash160(..)
+            | Policy::Ext(..) => (1, 1),
+            Policy::Threshold(k, ref subs) => {
+                let (all, without): (Vec<_>, Vec<_>) =
+                    subs.iter().map(|sub| sub.path_counts(pk)).unzip();
+                (combinations(&all, k), combinations(&without, k))
+            }
+        }
+    }
+
+    /// Helper function for `key_weights`, returning the fewest keys on a
+    /// spend path which uses `pk`.
+    fn min_quorum_with(&self, pk: &Pk) -> Option<usize> {
+        match *self {
+            Policy::Key(ref key) if key == pk => Some(1),
+            Policy::Threshold(k, ref subs) if k > 0 => (0..subs.len())
+                .filter_map(|i| {
+                    let with = subs[i].min_quorum_with(pk)?;
+                    let mut others: Vec<usize> = subs
+                        .iter()
+                        .enumerate()
+                        .filter(|&(j, _)| j != i)
+                        .filter_map(|(_, sub)| sub.minimum_n_keys())
+                        .collect();
+                    if others.len() < k - 1 {
+                        return None;
+                    }
+                    others.sort_unstable();
+                    Some(with + others[..k - 1].iter().sum::<usize>())
+                })
+                .min(),
+            _ => None,
+        }
+    }
+}
+
+// The number of ways to pick `k` of the given choices, where choice `i`
+// can itself be made in `counts[i]` ways
+fn combinations(counts: &[usize], k: usize) -> usize {
+    let mut ways = vec![0usize; k + 1];
+    ways[0] = 1;
+    for &count in counts {
+        for j in (1..=k).rev() {
+            ways[j] = ways[j].saturating_add(ways[j - 1].saturating_mul(count));
+        }
+    }
+    ways[k]
+}
+
 #[cfg(test)]
 mod tests {
     use std::str::FromStr;
@@ -992,4 +1313,107 @@ mod tests {
         assert!(auth_alice.entails(htlc_pol.clone()).unwrap());
         assert!(htlc_pol.entails(control_alice).unwrap());
     }
+
+    #[test]
+    fn lint_dead_branches() {
+        let policy = StringPolicy::from_str("or(pk(A),and(after(100),after(500000001)))").unwrap();
+        assert_eq!(
+            policy.lint(),
+            vec![Lint::ConflictingTimelocks(
+                StringPolicy::from_str("and(after(100),after(500000001))").unwrap()
+            )]
+        );
+
+        // Different kinds of timelocks can be combined, as can heights in one
+        // branch and times in another
+        let policy =
+            StringPolicy::from_str("or(and(after(100),older(4194305)),after(500000001))").unwrap();
+        assert!(policy.lint().is_empty());
+
+        // Only branches which cannot be satisfied at all are reported, and
+        // only where the conflict originates
+        let policy =
+            StringPolicy::from_str("or(pk(A),thresh(2,pk(B),older(10),older(4194305)),after(100))")
+                .unwrap();
+        assert!(policy.lint().is_empty());
+        let policy =
+            StringPolicy::from_str("or(pk(A),and(older(10),or(older(4194305),older(4194306))))")
+                .unwrap();
+        assert_eq!(
+            policy.lint(),
+            vec![Lint::ConflictingTimelocks(
+                StringPolicy::from_str("and(older(10),or(older(4194305),older(4194306)))").unwrap()
+            )]
+        );
+        let policy = StringPolicy::from_str("and(pk(A),and(after(100),after(500000001)))").unwrap();
+        assert_eq!(
+            policy.lint(),
+            vec![Lint::ConflictingTimelocks(
+                StringPolicy::from_str("and(after(100),after(500000001))").unwrap()
+            )]
+        );
+
+        let policy = StringPolicy::Threshold(2, vec![Policy::Key("A".to_owned())]);
+        assert_eq!(
+            policy.lint(),
+            vec![Lint::ImpossibleThreshold(policy.clone())]
+        );
+
+        let policy = StringPolicy::from_str("and(pk(A),or(pk(A),pk(B)))").unwrap();
+        assert_eq!(
+            policy.lint(),
+            vec![Lint::DuplicateKey(policy.clone(), "A".to_owned())]
+        );
+        let policy = StringPolicy::from_str("or(pk(A),and(pk(A),pk(B)))").unwrap();
+        assert!(policy.lint().is_empty());
+    }
+
+    #[test]
+    fn key_weights() {
+        let policy =
+            StringPolicy::from_str("or(and(pk(A),pk(B)),thresh(2,pk(A),pk(C),pk(D)))").unwrap();
+        let weights = policy.key_weights();
+        let weight = |pk: &str| weights[pk];
+        assert_eq!(
+            weight("A"),
+            KeyWeight {
+                spend_paths: 3,
+                min_quorum: 2
+            }
+        );
+        assert_eq!(
+            weight("C"),
+            KeyWeight {
+                spend_paths: 2,
+                min_quorum: 2
+            }
+        );
+        assert_eq!(weights.len(), 4);
+
+        // Keys only on unsatisfiable paths are omitted
+        let policy = StringPolicy::Threshold(
+            1,
+            vec![
+                Policy::Key("A".to_owned()),
+                Policy::Threshold(2, vec![Policy::Key("B".to_owned()), Policy::Unsatisfiable]),
+            ],
+        );
+        let weights = policy.key_weights();
+        assert_eq!(weights.keys().collect::<Vec<_>>(), vec!["A"]);
+
+        // 2^65 spend paths, with 2^64 not using A
+        let or = |a: String, b: String| Policy::Threshold(1, vec![Policy::Key(a), Policy::Key(b)]);
+        let mut subs = vec![or("A".to_owned(), "B".to_owned())];
+        subs.extend((0..64).map(|i| or(format!("K{}", i), format!("L{}", i))));
+        let policy = StringPolicy::Threshold(65, subs);
+        let weights = policy.key_weights();
+        assert_eq!(weights.len(), 130);
+        assert_eq!(
+            weights["A"],
+            KeyWeight {
+                spend_paths: usize::max_value(),
+                min_quorum: 65
+            }
+        );
+    }
 }
@@ -0,0 +1,355 @@
+// Miniscript
+// Written in 2023 by
+//     The Elements Miniscript developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Policy Templates
+//!
+//! Check that a descriptor, possibly compiled by another tool, implements a
+//! concrete policy. Both are lifted to semantic policies with their keys
+//! erased and compared after normalization, then the keys of the policy are
+//! bound to those of the descriptor.
+//!
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::{error, fmt};
+
+use super::{Concrete, Liftable, Semantic};
+use crate::descriptor::{DescriptorShape, EraseKeys, ErasedKey};
+use crate::{Descriptor, Error, Extension, MiniscriptKey};
+
+/// The maximum number of subpolicy pairs which [`Concrete::template_match`]
+/// tries to match while binding keys, after which it gives up
+pub const MAX_BINDING_STEPS: usize = 100_000;
+
+/// A place where the semantics of a descriptor differ from a policy
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PolicyDiff {
+    /// Indices of the thresholds leading to the difference, starting from
+    /// the root of the normalized and sorted policy
+    pub path: Vec<usize>,
+    /// The lifted policy at `path`
+    pub expected: Semantic<ErasedKey>,
+    /// The lifted descriptor at `path`
+    pub found: Semantic<ErasedKey>,
+}
+
+/// Error of [`Concrete::shape_match`] and [`Concrete::template_match`]
+#[derive(Debug)]
+pub enum TemplateMismatch {
+    /// The policy or the descriptor cannot be lifted
+    Lift(Error),
+    /// The semantics differ at each of the places
+    Semantics(Vec<PolicyDiff>),
+    /// The semantics are the same up to the keys, but the keys of the policy
+    /// cannot be bound one-to-one to those of the descriptor, or no binding
+    /// was found within [`MAX_BINDING_STEPS`]
+    Keys,
+}
+
+impl fmt::Display for TemplateMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            TemplateMismatch::Lift(ref e) => write!(f, "cannot lift: {}", e),
+            TemplateMismatch::Semantics(ref diffs) => {
+                f.write_str("descriptor does not implement the policy")?;
+                for diff in diffs {
+                    write!(
+                        f,
+                        "; at {:?} expected {} but found {}",
+                        diff.path, diff.expected, diff.found
+                    )?;
+                }
+                Ok(())
+            }
+            TemplateMismatch::Keys => {
+                f.write_str("policy keys cannot be bound to the descriptor keys")
+            }
+        }
+    }
+}
+
+impl error::Error for TemplateMismatch {
+    fn cause(&self) -> Option<&dyn error::Error> {
+        match *self {
+            TemplateMismatch::Lift(ref e) => Some(e),
+            TemplateMismatch::Semantics(_) | TemplateMismatch::Keys => None,
+        }
+    }
+}
+
+impl<Pk: MiniscriptKey> Concrete<Pk> {
+    /// Check that the descriptor of shape `shape` implements this policy,
+    /// i.e. that both have the same normalized semantics up to their keys.
+    /// The shape of a descriptor is obtained with
+    /// [`Descriptor::shape`](crate::Descriptor::shape).
+    ///
+    /// Probabilities are ignored, and since keys are erased, the check does
+    /// not tell keys apart: use [`Concrete::template_match`] to check a
+    /// descriptor with its keys. Policies which are equivalent in a way that
+    /// normalization does not detect are reported as mismatches.
+    pub fn shape_match<Ext: Extension>(
+        &self,
+        shape: &DescriptorShape<Ext>,
+    ) -> Result<(), TemplateMismatch> {
+        let expected = self
+            .translate_pk(&mut EraseKeys)
+            .unwrap_or_else(|e| match e {});
+        let expected = canonical(&expected)?;
+        let found = canonical(shape)?;
+
+        let mut diffs = vec![];
+        diff(&expected, &found, &mut vec![], &mut diffs);
+        if diffs.is_empty() {
+            Ok(())
+        } else {
+            Err(TemplateMismatch::Semantics(diffs))
+        }
+    }
+
+    /// Check that the descriptor `desc` implements this policy, as
+    /// [`Concrete::shape_match`] does for its shape, and return the key of
+    /// the descriptor bound to each key of the policy.
+    ///
+    /// Different keys of the policy are bound to different keys of the
+    /// descriptor. When several bindings are possible, e.g. for the keys of
+    /// a `multi`, one of them is returned.
+    pub fn template_match<Q: MiniscriptKey, Ext: Extension>(
+        &self,
+        desc: &Descriptor<Q, Ext>,
+    ) -> Result<BTreeMap<Pk, Q>, TemplateMismatch> {
+        self.shape_match(&desc.shape())?;
+
+        let policy = self.lift().map_err(TemplateMismatch::Lift)?.normalized();
+        let lifted = desc.lift().map_err(TemplateMismatch::Lift)?.normalized();
+        let mut bindings = Bindings {
+            keys: BTreeMap::new(),
+            bound: BTreeSet::new(),
+            steps: 0,
+        };
+        if bindings.bind(&policy, &lifted) {
+            Ok(bindings.keys)
+        } else {
+            Err(TemplateMismatch::Keys)
+        }
+    }
+}
+
+// A one-to-one binding of policy keys to descriptor keys, found by
+// backtracking over the matchings of the subpolicies of thresholds
+struct Bindings<Pk: MiniscriptKey, Q: MiniscriptKey> {
+    keys: BTreeMap<Pk, Q>,
+    bound: BTreeSet<Q>,
+    steps: usize,
+}
+
+impl<Pk: MiniscriptKey, Q: MiniscriptKey> Bindings<Pk, Q> {
+    fn bind(&mut self, expected: &Semantic<Pk>, found: &Semantic<Q>) -> bool {
+        self.steps += 1;
+        if self.steps > MAX_BINDING_STEPS {
+            return false;
+        }
+        match (expected, found) {
+            (Semantic::Key(pk), Semantic::Key(q)) => match self.keys.get(pk) {
+                Some(bound) => bound == q,
+                None if self.bound.contains(q) => false,
+                None => {
+                    self.keys.insert(pk.clone(), q.clone());
+                    self.bound.insert(q.clone());
+                    true
+                }
+            },
+            (Semantic::Threshold(k, subs), Semantic::Threshold(l, others))
+                if k == l && subs.len() == others.len() =>
+            {
+                let mut others: Vec<_> = others.iter().map(Some).collect();
+                self.bind_all(subs, &mut others)
+            }
+            (Semantic::Unsatisfiable, Semantic::Unsatisfiable)
+            | (Semantic::Trivial, Semantic::Trivial) => true,
+            (Semantic::After(a), Semantic::After(b)) => a == b,
+            (Semantic::Older(a), Semantic::Older(b)) => a == b,
+            (Semantic::Sha256(a), Semantic::Sha256(b)) => a.to_string() == b.to_string(),
+            (Semantic::Hash256(a), Semantic::Hash256(b)) => a.to_string() == b.to_string(),
+            (Semantic::Ripemd160(a), Semantic::Ripemd160(b)) => a.to_string() == b.to_string(),
+            (Semantic::Hash160(a), Semantic::Hash160(b)) => a.to_string() == b.to_string(),
+            _ => false,
+        }
+    }
+
+    // Match each of `subs` with a distinct one of `others`
+    fn bind_all(&mut self, subs: &[Semantic<Pk>], others: &mut [Option<&Semantic<Q>>]) -> bool {
+        let (first, rest) = match subs.split_first() {
+            Some(split) => split,
+            None => return true,
+        };
+        for i in 0..others.len() {
+            let other = match others[i].take() {
+                Some(other) => other,
+                None => continue,
+            };
+            let (keys, bound) = (self.keys.clone(), self.bound.clone());
+            if self.bind(first, other) && self.bind_all(rest, others) {
+                return true;
+            }
+            self.keys = keys;
+            self.bound = bound;
+            others[i] = Some(other);
+            if self.steps > MAX_BINDING_STEPS {
+                return false;
+            }
+        }
+        false
+    }
+}
+
+fn canonical<L: Liftable<ErasedKey>>(item: &L) -> Result<Semantic<ErasedKey>, TemplateMismatch> {
+    item.lift()
+        .map(|policy| policy.normalized().sorted())
+        .map_err(TemplateMismatch::Lift)
+}
+
+// Subpolicies present on both sides are matched first, so that a single
+// difference is reported once rather than shifting every sibling after it
+fn diff(
+    expected: &Semantic<ErasedKey>,
+    found: &Semantic<ErasedKey>,
+    path: &mut Vec<usize>,
+    diffs: &mut Vec<PolicyDiff>,
+) {
+    if expected == found {
+        return;
+    }
+    if let (Semantic::Threshold(k, exp_subs), Semantic::Threshold(l, found_subs)) =
+        (expected, found)
+    {
+        if k == l && exp_subs.len() == found_subs.len() {
+            let mut unmatched: Vec<&Semantic<ErasedKey>> = found_subs.iter().collect();
+            let mut leftover = vec![];
+            for (i, sub) in exp_subs.iter().enumerate() {
+                match unmatched.iter().position(|other| *other == sub) {
+                    Some(pos) => {
+                        unmatched.remove(pos);
+                    }
+                    None => leftover.push(i),
+                }
+            }
+            for (i, other) in leftover.into_iter().zip(unmatched) {
+                path.push(i);
+                diff(&exp_subs[i], other, path, diffs);
+                path.pop();
+            }
+            return;
+        }
+    }
+    diffs.push(PolicyDiff {
+        path: path.clone(),
+        expected: expected.clone(),
+        found: found.clone(),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::descriptor::DescriptorPublicKey;
+    use crate::{Descriptor, NoExt};
+
+    #[test]
+    fn template_match() {
+        let pol = |s: &str| Concrete::<String>::from_str(s).unwrap();
+        let shape = |s: &str| {
+            Descriptor::<DescriptorPublicKey, NoExt>::from_str(s)
+                .unwrap()
+                .shape()
+        };
+        let a = "020202020202020202020202020202020202020202020202020202020202020202";
+        let b = "028c28a97bf8298bc0d23d8c749452a32e694b65e30a9472a3954ab30fe5324caa";
+        let c = "03daed4f2be3a8bf278e70132fb0beb7522f570e144bf615c07e996d443dee8729";
+
+        // Equivalent up to the order of subpolicies and the script fragments
+        let policy = pol("or(99@and(pk(A),pk(B)),1@and(pk(C),older(144)))");
+        let wsh = format!(
+            "elwsh(or_d(multi(2,{},{}),and_v(v:pk({}),older(144))))",
+            b, a, c
+        );
+        let desc = shape(&wsh);
+        policy.shape_match(&desc).unwrap();
+
+        // Keys of the policy are bound to those of the descriptor
+        let key = |s: &str| DescriptorPublicKey::from_str(s).unwrap();
+        let bindings = policy
+            .template_match(&Descriptor::<DescriptorPublicKey, NoExt>::from_str(&wsh).unwrap())
+            .unwrap();
+        assert_eq!(bindings.len(), 3);
+        assert_eq!(bindings["C"], key(c));
+        let mut multi = vec![bindings["A"].clone(), bindings["B"].clone()];
+        multi.sort();
+        let mut expected = vec![key(a), key(b)];
+        expected.sort();
+        assert_eq!(multi, expected);
+
+        // Distinct policy keys must be bound to distinct descriptor keys
+        let reused = Descriptor::<DescriptorPublicKey, NoExt>::from_str(&format!(
+            "elwsh(or_d(pk({}),and_v(v:pk({}),older(144))))",
+            a, a
+        ))
+        .unwrap();
+        let tr_policy = pol("or(pk(A),and(pk(B),older(144)))");
+        tr_policy.shape_match(&reused.shape()).unwrap();
+        match tr_policy.template_match(&reused) {
+            Err(TemplateMismatch::Keys) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+        let desc = shape(&format!(
+            "eltr({},{{and_v(v:pk({}),older(144)),pk({})}})",
+            a, c, b
+        ));
+        assert!(policy.shape_match(&desc).is_err());
+        let tr = Descriptor::<DescriptorPublicKey, NoExt>::from_str(&format!(
+            "eltr({},and_v(v:pk({}),older(144)))",
+            a, b
+        ))
+        .unwrap();
+        tr_policy.shape_match(&tr.shape()).unwrap();
+        let bindings = tr_policy.template_match(&tr).unwrap();
+        assert_eq!(bindings["A"], key(a));
+        assert_eq!(bindings["B"], key(b));
+
+        // A wrong timelock is reported at its place in the tree
+        let desc = shape(&format!(
+            "elwsh(or_d(multi(2,{},{}),and_v(v:pk({}),older(100))))",
+            a, b, c
+        ));
+        match policy.shape_match(&desc) {
+            Err(TemplateMismatch::Semantics(diffs)) => {
+                assert_eq!(diffs.len(), 1);
+                assert_eq!(diffs[0].expected, Semantic::older(144));
+                assert_eq!(diffs[0].found, Semantic::older(100));
+                assert_eq!(diffs[0].path.len(), 2);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+
+        // Policies mixing timelocks cannot be lifted
+        let mixed = Concrete::<String>::And(vec![
+            Concrete::After(elements::PackedLockTime(100)),
+            Concrete::After(elements::PackedLockTime(500_000_001)),
+        ]);
+        match mixed.shape_match(&desc) {
+            Err(TemplateMismatch::Lift(_)) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+}
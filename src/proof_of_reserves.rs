@@ -0,0 +1,535 @@
+// Miniscript
+// Written in 2023 by
+//     The Elements Miniscript developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Proof of Reserves
+//!
+//! Proofs of control over UTXOs in the style of BIP-127: a transaction
+//! spending the UTXOs together with a challenge input committing to a
+//! message. The challenge input spends an output which does not exist, so
+//! the proof cannot be broadcast, yet it is signed like a regular spend.
+//!
+//! On Elements the proof has one `OP_TRUE` output per asset, with the
+//! explicit total of the reserves in that asset. Confidential reserves are
+//! unblinded with their blinding keys, which the verifier must be given too.
+//!
+//! All signatures must commit to all inputs and outputs. A signature with
+//! e.g. `SIGHASH_ANYONECANPAY` does not commit to the challenge input, so it
+//! could be reused in a proof for any other message.
+//!
+
+use std::collections::BTreeMap;
+use std::{error, fmt};
+
+use elements::hashes::{sha256d, Hash};
+use elements::pset::{self, PartiallySignedTransaction as Psbt};
+use elements::secp256k1_zkp::{self, Secp256k1, SecretKey};
+use elements::{
+    confidential, AssetId, EcdsaSigHashType, LockTime, OutPoint, SchnorrSigHashType, Script,
+    Sequence, Transaction, TxIn, TxOut, TxOutSecrets, Txid,
+};
+
+use crate::descriptor::DefiniteDescriptorKey;
+use crate::extensions::{CovExtArgs, CovenantExt};
+use crate::interpreter::{KeySigPair, SatisfiedConstraint};
+use crate::psbt::{PsbtExt, UtxoUpdateError};
+use crate::{interpreter, Descriptor, Interpreter, TxEnv};
+
+/// The prefix of the message committed to by the challenge input
+pub const CHALLENGE_PREFIX: &str = "Proof-of-Reserves: ";
+
+/// A UTXO whose control is proven
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReserveUtxo {
+    /// The outpoint of the UTXO
+    pub outpoint: OutPoint,
+    /// The UTXO itself
+    pub txout: TxOut,
+    /// The blinding key of the UTXO, required if it is confidential
+    pub blinding_key: Option<SecretKey>,
+    /// The nSequence of the input spending the UTXO in the proof, e.g. to
+    /// satisfy the `older()` fragments of its script
+    pub sequence: Sequence,
+}
+
+impl ReserveUtxo {
+    /// The explicit or unblinded asset and value of the UTXO
+    pub fn secrets<C: secp256k1_zkp::Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+    ) -> Result<TxOutSecrets, ReserveProofError> {
+        match (self.txout.asset, self.txout.value) {
+            (confidential::Asset::Explicit(asset), confidential::Value::Explicit(value)) => {
+                Ok(TxOutSecrets::new(
+                    asset,
+                    confidential::AssetBlindingFactor::zero(),
+                    value,
+                    confidential::ValueBlindingFactor::zero(),
+                ))
+            }
+            _ => {
+                let key = self
+                    .blinding_key
+                    .ok_or(ReserveProofError::MissingBlindingKey(self.outpoint))?;
+                self.txout
+                    .unblind(secp, key)
+                    .map_err(|_| ReserveProofError::Unblind(self.outpoint))
+            }
+        }
+    }
+}
+
+/// Error of creating or verifying a proof of reserves
+#[derive(Debug)]
+pub enum ReserveProofError {
+    /// A confidential reserve has no blinding key
+    MissingBlindingKey(OutPoint),
+    /// A confidential reserve cannot be unblinded with its blinding key
+    Unblind(OutPoint),
+    /// The total of an asset does not fit into 64 bits
+    Overflow(AssetId),
+    /// No descriptor has the script pubkey of the reserve
+    UnknownScript(OutPoint),
+    /// The input of the reserve cannot be updated with its descriptor
+    Update(OutPoint, UtxoUpdateError),
+    /// The first input of the proof does not commit to the message
+    ChallengeMismatch,
+    /// The inputs of the proof do not spend the given reserves
+    InputMismatch,
+    /// The outputs of the proof are not the totals of the reserves
+    OutputMismatch,
+    /// The input spending the reserve does not satisfy its script
+    Interpreter(OutPoint, interpreter::Error),
+    /// A signature of the input spending the reserve does not commit to all
+    /// inputs and outputs of the proof
+    SigHashType(OutPoint),
+}
+
+impl fmt::Display for ReserveProofError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            ReserveProofError::MissingBlindingKey(ref op) => {
+                write!(f, "missing blinding key of confidential reserve {}", op)
+            }
+            ReserveProofError::Unblind(ref op) => write!(f, "cannot unblind reserve {}", op),
+            ReserveProofError::Overflow(ref asset) => {
+                write!(f, "total of asset {} overflows", asset)
+            }
+            ReserveProofError::UnknownScript(ref op) => {
+                write!(f, "no descriptor for the script pubkey of reserve {}", op)
+            }
+            ReserveProofError::Update(ref op, ref e) => {
+                write!(f, "cannot update input of reserve {}: {}", op, e)
+            }
+            ReserveProofError::ChallengeMismatch => {
+                f.write_str("first input does not commit to the message")
+            }
+            ReserveProofError::InputMismatch => f.write_str("inputs do not spend the reserves"),
+            ReserveProofError::OutputMismatch => {
+                f.write_str("outputs are not the totals of the reserves")
+            }
+            ReserveProofError::Interpreter(ref op, ref e) => {
+                write!(f, "spend of reserve {} is invalid: {}", op, e)
+            }
+            ReserveProofError::SigHashType(ref op) => write!(
+                f,
+                "signature of reserve {} does not commit to the whole proof",
+                op
+            ),
+        }
+    }
+}
+
+impl error::Error for ReserveProofError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            ReserveProofError::Interpreter(_, ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// The outpoint spent by the challenge input, the `SHA256d` of the message
+/// with [`CHALLENGE_PREFIX`]
+pub fn challenge_outpoint(message: &str) -> OutPoint {
+    let hash = sha256d::Hash::hash(format!("{}{}", CHALLENGE_PREFIX, message).as_bytes());
+    OutPoint::new(Txid::from_inner(hash.into_inner()), 0)
+}
+
+/// The output considered spent by the challenge input when signing, an empty
+/// `OP_TRUE` output
+pub fn challenge_utxo() -> TxOut {
+    TxOut {
+        asset: confidential::Asset::Explicit(AssetId::default()),
+        value: confidential::Value::Explicit(0),
+        nonce: confidential::Nonce::Null,
+        script_pubkey: op_true(),
+        witness: Default::default(),
+    }
+}
+
+fn op_true() -> Script {
+    Script::from(vec![elements::opcodes::all::OP_PUSHNUM_1.into_u8()])
+}
+
+// The totals of the reserves per asset, in order of the first reserve of
+// each asset
+fn totals<C: secp256k1_zkp::Verification>(
+    secp: &Secp256k1<C>,
+    reserves: &[ReserveUtxo],
+) -> Result<Vec<(AssetId, u64)>, ReserveProofError> {
+    let mut order = vec![];
+    let mut totals = BTreeMap::new();
+    for reserve in reserves {
+        let secrets = reserve.secrets(secp)?;
+        let total = totals.entry(secrets.asset).or_insert_with(|| {
+            order.push(secrets.asset);
+            0u64
+        });
+        *total = total
+            .checked_add(secrets.value)
+            .ok_or(ReserveProofError::Overflow(secrets.asset))?;
+    }
+    Ok(order
+        .into_iter()
+        .map(|asset| (asset, totals[&asset]))
+        .collect())
+}
+
+/// The unsigned proof that `reserves` are controlled, committing to `message`.
+///
+/// The first input is the challenge input, followed by an input for each
+/// reserve in order, with the [`ReserveUtxo::sequence`] of the reserve. The
+/// `lock_time` of the proof must satisfy the `after()` fragments of the spend
+/// paths used.
+pub fn proof_tx<C: secp256k1_zkp::Verification>(
+    secp: &Secp256k1<C>,
+    message: &str,
+    reserves: &[ReserveUtxo],
+    lock_time: LockTime,
+) -> Result<Transaction, ReserveProofError> {
+    let txin = |previous_output, sequence| TxIn {
+        previous_output,
+        sequence,
+        ..Default::default()
+    };
+    let mut input = vec![txin(challenge_outpoint(message), Sequence::MAX)];
+    input.extend(reserves.iter().map(|r| txin(r.outpoint, r.sequence)));
+    let output = totals(secp, reserves)?
+        .into_iter()
+        .map(|(asset, value)| TxOut {
+            asset: confidential::Asset::Explicit(asset),
+            value: confidential::Value::Explicit(value),
+            nonce: confidential::Nonce::Null,
+            script_pubkey: op_true(),
+            witness: Default::default(),
+        })
+        .collect();
+    Ok(Transaction {
+        version: 2,
+        lock_time: lock_time.into(),
+        input,
+        output,
+    })
+}
+
+/// The proof of [`proof_tx`] as a PSET to be signed, with the inputs of the
+/// reserves updated with the descriptor having their script pubkey.
+///
+/// The challenge input spends [`challenge_utxo`] and need not be signed.
+/// Once the inputs of the reserves are signed and finalized, e.g. with
+/// [`PsbtExt::finalize_inp_mut`], the proof is the extracted transaction.
+///
+/// # Errors
+/// Besides the errors of [`proof_tx`], if no descriptor has the script pubkey
+/// of a reserve, or the descriptor is pre-segwit (which would need the full
+/// transaction of the reserve).
+pub fn proof_pset<C: secp256k1_zkp::Verification>(
+    secp: &Secp256k1<C>,
+    message: &str,
+    reserves: &[ReserveUtxo],
+    lock_time: LockTime,
+    descriptors: &[Descriptor<DefiniteDescriptorKey, CovenantExt<CovExtArgs>>],
+) -> Result<Psbt, ReserveProofError> {
+    let tx = proof_tx(secp, message, reserves, lock_time)?;
+    let mut psbt = Psbt::new_v2();
+    psbt.global.tx_data.fallback_locktime = Some(lock_time.into());
+    for (txin, utxo) in tx.input.iter().zip(
+        Some(challenge_utxo())
+            .into_iter()
+            .chain(reserves.iter().map(|r| r.txout.clone())),
+    ) {
+        let mut input = pset::Input::from_prevout(txin.previous_output);
+        input.sequence = Some(txin.sequence);
+        input.witness_utxo = Some(utxo);
+        psbt.add_input(input);
+    }
+    for txout in tx.output {
+        psbt.add_output(pset::Output::from_txout(txout));
+    }
+    for (idx, reserve) in reserves.iter().enumerate() {
+        let desc = descriptors
+            .iter()
+            .find(|desc| desc.script_pubkey() == reserve.txout.script_pubkey)
+            .ok_or(ReserveProofError::UnknownScript(reserve.outpoint))?;
+        psbt.update_input_with_descriptor(idx + 1, desc)
+            .map_err(|e| ReserveProofError::Update(reserve.outpoint, e))?;
+    }
+    Ok(psbt)
+}
+
+/// Verifies that `tx` proves control over `reserves` committing to
+/// `message`, returning the proven totals per asset.
+///
+/// Every input of the reserves must satisfy the script of its reserve, with
+/// `SIGHASH_ALL` signatures over the proof spending the reserves and
+/// [`challenge_utxo`]. The lock time and sequences of the proof are taken as
+/// they are. The spent reserves must be checked to be unspent separately, the
+/// proof only shows that they could be spent.
+pub fn verify_proof<C: secp256k1_zkp::Verification>(
+    secp: &Secp256k1<C>,
+    tx: &Transaction,
+    message: &str,
+    reserves: &[ReserveUtxo],
+    genesis_hash: elements::BlockHash,
+) -> Result<Vec<(AssetId, u64)>, ReserveProofError> {
+    match tx.input.first() {
+        Some(txin) if txin.previous_output == challenge_outpoint(message) => {}
+        _ => return Err(ReserveProofError::ChallengeMismatch),
+    }
+    if tx.input.len() != reserves.len() + 1
+        || tx.input[1..]
+            .iter()
+            .zip(reserves)
+            .any(|(txin, r)| txin.previous_output != r.outpoint)
+    {
+        return Err(ReserveProofError::InputMismatch);
+    }
+    let totals = totals(secp, reserves)?;
+    let expected = proof_tx(secp, message, reserves, LockTime::from(tx.lock_time))?;
+    if tx.output != expected.output {
+        return Err(ReserveProofError::OutputMismatch);
+    }
+
+    let utxos: Vec<TxOut> = Some(challenge_utxo())
+        .into_iter()
+        .chain(reserves.iter().map(|r| r.txout.clone()))
+        .collect();
+    for (idx, reserve) in reserves.iter().enumerate() {
+        let txin = &tx.input[idx + 1];
+        let env = TxEnv::new(tx, &utxos, idx + 1).expect("an utxo for every input");
+        let interpreter = Interpreter::from_txdata(
+            &reserve.txout.script_pubkey,
+            &txin.script_sig,
+            &txin.witness.script_witness,
+            txin.sequence,
+            LockTime::from(tx.lock_time),
+        )
+        .map_err(|e| ReserveProofError::Interpreter(reserve.outpoint, e))?;
+        for constraint in interpreter.iter(secp, &env, genesis_hash) {
+            let key_sig = match constraint {
+                Ok(SatisfiedConstraint::PublicKey { key_sig })
+                | Ok(SatisfiedConstraint::PublicKeyHash { key_sig, .. }) => key_sig,
+                Ok(_) => continue,
+                Err(e) => return Err(ReserveProofError::Interpreter(reserve.outpoint, e)),
+            };
+            let commits_to_all = match key_sig {
+                KeySigPair::Ecdsa(_, (_, hash_ty)) => hash_ty == EcdsaSigHashType::All,
+                KeySigPair::Schnorr(_, sig) => match sig.hash_ty {
+                    SchnorrSigHashType::Default | SchnorrSigHashType::All => true,
+                    _ => false,
+                },
+            };
+            if !commits_to_all {
+                return Err(ReserveProofError::SigHashType(reserve.outpoint));
+            }
+        }
+    }
+    Ok(totals)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::slice;
+    use std::str::FromStr;
+
+    use elements::pset::PsbtSighashType;
+    use elements::secp256k1_zkp::PublicKey;
+    use elements::sighash::SigHashCache;
+    use elements::{Address, AddressParams};
+
+    use super::*;
+
+    // Sign the reserve inputs of `psbt` with `sk`, with
+    // `SIGHASH_ALL|SIGHASH_ANYONECANPAY` if `acp`, and extract the proof
+    fn sign(
+        secp: &Secp256k1<secp256k1_zkp::All>,
+        mut psbt: Psbt,
+        sk: &SecretKey,
+        acp: bool,
+    ) -> Transaction {
+        let genesis_hash = elements::BlockHash::all_zeros();
+        let pk = bitcoin::PublicKey::new(PublicKey::from_secret_key(secp, sk));
+        let tx = psbt.extract_tx().unwrap();
+        let mut cache = SigHashCache::new(&tx);
+        for idx in 1..psbt.inputs().len() {
+            let hash_ty = if acp {
+                EcdsaSigHashType::AllPlusAnyoneCanPay
+            } else {
+                EcdsaSigHashType::All
+            };
+            psbt.inputs_mut()[idx].sighash_type = Some(PsbtSighashType::from(hash_ty));
+            let msg = psbt
+                .sighash_msg(idx, &mut cache, None, genesis_hash)
+                .unwrap()
+                .to_secp_msg();
+            let mut sig = secp.sign_ecdsa(&msg, sk).serialize_der().to_vec();
+            sig.push(hash_ty as u8);
+            psbt.inputs_mut()[idx].partial_sigs.insert(pk, sig);
+            psbt.finalize_inp_mut(secp, idx, genesis_hash).unwrap();
+        }
+        psbt.extract_tx().unwrap()
+    }
+
+    fn reserve(vout: u32, txout: TxOut) -> ReserveUtxo {
+        ReserveUtxo {
+            outpoint: OutPoint::new(Txid::all_zeros(), vout),
+            txout,
+            blinding_key: None,
+            sequence: Sequence::MAX,
+        }
+    }
+
+    fn explicit_txout(asset: AssetId, value: u64, script_pubkey: Script) -> TxOut {
+        TxOut {
+            asset: confidential::Asset::Explicit(asset),
+            value: confidential::Value::Explicit(value),
+            script_pubkey,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn proof_of_reserves() {
+        let secp = Secp256k1::new();
+        let mut rng = actual_rand::thread_rng();
+        let sk = SecretKey::from_slice(&[1; 32]).unwrap();
+        let pk = bitcoin::PublicKey::new(PublicKey::from_secret_key(&secp, &sk));
+        let desc = Descriptor::<DefiniteDescriptorKey, CovenantExt<CovExtArgs>>::from_str(
+            &format!("elwpkh({})", pk),
+        )
+        .unwrap();
+        let blinding_key = SecretKey::from_slice(&[3; 32]).unwrap();
+        let address = Address::p2wpkh(
+            &pk,
+            Some(PublicKey::from_secret_key(&secp, &blinding_key)),
+            &AddressParams::LIQUID,
+        );
+        let asset = AssetId::from_slice(&[2; 32]).unwrap();
+        let spent = TxOutSecrets::new(
+            asset,
+            confidential::AssetBlindingFactor::new(&mut rng),
+            2000,
+            confidential::ValueBlindingFactor::new(&mut rng),
+        );
+        let (confidential_txout, ..) =
+            TxOut::new_not_last_confidential(&mut rng, &secp, 2000, address, asset, &[spent])
+                .unwrap();
+        let mut reserves = vec![
+            reserve(0, explicit_txout(asset, 1000, desc.script_pubkey())),
+            reserve(1, confidential_txout),
+        ];
+        let genesis_hash = elements::BlockHash::all_zeros();
+        let pset = |reserves: &[ReserveUtxo]| {
+            proof_pset(
+                &secp,
+                "exchange",
+                reserves,
+                LockTime::ZERO,
+                slice::from_ref(&desc),
+            )
+        };
+        match pset(&reserves) {
+            Err(ReserveProofError::MissingBlindingKey(..)) => {}
+            e => panic!("unexpected {:?}", e),
+        }
+        reserves[1].blinding_key = Some(blinding_key);
+
+        let proof = sign(&secp, pset(&reserves).unwrap(), &sk, false);
+        assert_eq!(
+            verify_proof(&secp, &proof, "exchange", &reserves, genesis_hash).unwrap(),
+            vec![(asset, 3000)]
+        );
+        match verify_proof(&secp, &proof, "other", &reserves, genesis_hash) {
+            Err(ReserveProofError::ChallengeMismatch) => {}
+            e => panic!("unexpected {:?}", e),
+        }
+        match verify_proof(&secp, &proof, "exchange", &reserves[..1], genesis_hash) {
+            Err(ReserveProofError::InputMismatch) => {}
+            e => panic!("unexpected {:?}", e),
+        }
+        let mut tampered = proof.clone();
+        tampered.input[1].witness.script_witness[0][10] ^= 1;
+        match verify_proof(&secp, &tampered, "exchange", &reserves, genesis_hash) {
+            Err(ReserveProofError::Interpreter(..)) => {}
+            e => panic!("unexpected {:?}", e),
+        }
+        let mut tampered = proof;
+        tampered.output[0].value = confidential::Value::Explicit(4000);
+        match verify_proof(&secp, &tampered, "exchange", &reserves, genesis_hash) {
+            Err(ReserveProofError::OutputMismatch) => {}
+            e => panic!("unexpected {:?}", e),
+        }
+
+        // A signature not committing to the challenge input could be replayed
+        // for another message by swapping the challenge input
+        let proof = sign(&secp, pset(&reserves).unwrap(), &sk, true);
+        match verify_proof(&secp, &proof, "exchange", &reserves, genesis_hash) {
+            Err(ReserveProofError::SigHashType(op)) if op == reserves[0].outpoint => {}
+            e => panic!("unexpected {:?}", e),
+        }
+        let mut replayed = proof;
+        replayed.input[0].previous_output = challenge_outpoint("other");
+        match verify_proof(&secp, &replayed, "other", &reserves, genesis_hash) {
+            Err(ReserveProofError::SigHashType(..)) => {}
+            e => panic!("unexpected {:?}", e),
+        }
+    }
+
+    #[test]
+    fn timelocked_reserves() {
+        let secp = Secp256k1::new();
+        let sk = SecretKey::from_slice(&[1; 32]).unwrap();
+        let pk = bitcoin::PublicKey::new(PublicKey::from_secret_key(&secp, &sk));
+        let desc = Descriptor::<DefiniteDescriptorKey, CovenantExt<CovExtArgs>>::from_str(
+            &format!("elwsh(and_v(v:pk({}),and_v(v:older(10),after(100))))", pk),
+        )
+        .unwrap();
+        let asset = AssetId::from_slice(&[2; 32]).unwrap();
+        let mut reserves = vec![reserve(
+            0,
+            explicit_txout(asset, 1000, desc.script_pubkey()),
+        )];
+        reserves[0].sequence = Sequence::from_height(10);
+        let lock_time = LockTime::from_height(100).unwrap();
+        let genesis_hash = elements::BlockHash::all_zeros();
+
+        let psbt = proof_pset(&secp, "exchange", &reserves, lock_time, &[desc]).unwrap();
+        let proof = sign(&secp, psbt, &sk, false);
+        assert_eq!(LockTime::from(proof.lock_time), lock_time);
+        assert_eq!(
+            verify_proof(&secp, &proof, "exchange", &reserves, genesis_hash).unwrap(),
+            vec![(asset, 1000)]
+        );
+    }
+}
@@ -0,0 +1,634 @@
+// Miniscript
+// Written in 2023 by
+//     The Elements Miniscript developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Bitcoin PSBT Compatibility
+//!
+//! Conversion between Bitcoin PSBTs and Elements PSETs for the fields with
+//! the same meaning on both chains, so that transactions created by Bitcoin
+//! tooling can be signed by Liquid tooling and the other way around.
+//!
+//! rust-bitcoin models PSBTs as version 0, with an unsigned transaction. The
+//! BIP-370 fields of PSETs (previous outpoints, sequences, the locktime,
+//! output amounts and scripts) are mapped to and from that transaction.
+//!
+//! Signatures, finalized scripts and taproot trees commit to sighashes and
+//! tagged hashes which differ between the chains, so they are never carried
+//! over. They are reported as [`Dropped`] fields, like the Elements-only
+//! fields (issuances, peg-ins, blinding data) and the explicit fee output.
+//!
+
+use std::collections::BTreeMap;
+use std::{error, fmt};
+
+use bitcoin::secp256k1::{self, XOnlyPublicKey};
+use bitcoin::util::bip32::KeySource;
+use bitcoin::util::psbt as btc_psbt;
+use elements::hashes::Hash;
+use elements::pset::{self as psbt, PartiallySignedTransaction as Psbt};
+use elements::{confidential, AssetId, PackedLockTime, Script, Sequence, TxOut, Txid};
+
+/// Where a [`Dropped`] field was found. Indices are those of the source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum FieldLocation {
+    /// The global map
+    Global,
+    /// The input at the index
+    Input(usize),
+    /// The output at the index
+    Output(usize),
+}
+
+impl fmt::Display for FieldLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            FieldLocation::Global => f.write_str("global map"),
+            FieldLocation::Input(i) => write!(f, "input {}", i),
+            FieldLocation::Output(i) => write!(f, "output {}", i),
+        }
+    }
+}
+
+/// A kind of field without counterpart in the converted PSBT or PSET
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DroppedField {
+    /// Signatures and finalized scripts, which are only valid on their chain
+    Signatures,
+    /// Control blocks, leaf hashes, merkle roots and trees, which use tagged
+    /// hashes specific to each chain
+    TaprootTree,
+    /// The previous transaction of an input
+    NonWitnessUtxo,
+    /// The issuance or reissuance of an input
+    Issuance,
+    /// The peg-in data of an input
+    Pegin,
+    /// Commitments, proofs, blinding keys and scalars
+    Blinding,
+    /// The explicit fee output of a PSET, Bitcoin fees are implicit
+    FeeOutput,
+    /// Proprietary and unknown key-value pairs
+    Proprietary,
+}
+
+impl fmt::Display for DroppedField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match *self {
+            DroppedField::Signatures => "signatures",
+            DroppedField::TaprootTree => "taproot tree",
+            DroppedField::NonWitnessUtxo => "non-witness utxo",
+            DroppedField::Issuance => "issuance",
+            DroppedField::Pegin => "peg-in",
+            DroppedField::Blinding => "blinding data",
+            DroppedField::FeeOutput => "fee output",
+            DroppedField::Proprietary => "proprietary fields",
+        })
+    }
+}
+
+/// A field which was not carried over by a conversion
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Dropped {
+    /// Where the field was found
+    pub location: FieldLocation,
+    /// The kind of field
+    pub field: DroppedField,
+}
+
+/// Error of the conversion between Bitcoin PSBTs and PSETs
+#[derive(Debug)]
+pub enum CompatError {
+    /// An amount or asset is blinded
+    Confidential(FieldLocation),
+    /// An amount is of another asset than the one of the conversion
+    ForeignAsset(FieldLocation),
+    /// The previous transaction of an input does not match its outpoint
+    UtxoMismatch(usize),
+    /// The PSET has no valid locktime
+    Pset(psbt::Error),
+    /// The unsigned transaction of the Bitcoin PSBT is invalid
+    Psbt(btc_psbt::Error),
+}
+
+impl fmt::Display for CompatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            CompatError::Confidential(loc) => write!(f, "confidential amount in {}", loc),
+            CompatError::ForeignAsset(loc) => write!(f, "foreign asset in {}", loc),
+            CompatError::UtxoMismatch(i) => {
+                write!(f, "previous transaction of input {} does not match", i)
+            }
+            CompatError::Pset(ref e) => write!(f, "pset: {}", e),
+            CompatError::Psbt(ref e) => write!(f, "psbt: {}", e),
+        }
+    }
+}
+
+impl error::Error for CompatError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            CompatError::Pset(ref e) => Some(e),
+            CompatError::Psbt(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Converts a Bitcoin PSBT to a PSET, with all amounts in `asset`.
+///
+/// If the amounts of all inputs are known, an explicit fee output for the
+/// difference to the outputs is added. Witness utxos are derived from
+/// non-witness utxos if needed. Returns the PSET and the fields which were
+/// not carried over.
+pub fn from_bitcoin_psbt(
+    psbt: &btc_psbt::PartiallySignedTransaction,
+    asset: AssetId,
+) -> Result<(Psbt, Vec<Dropped>), CompatError> {
+    let mut dropped = vec![];
+    let tx = &psbt.unsigned_tx;
+    let mut pset = Psbt::new_v2();
+    pset.global.tx_data.version = tx.version as u32;
+    pset.global.tx_data.fallback_locktime = Some(PackedLockTime(tx.lock_time.0));
+    pset.global.xpub = psbt.xpub.clone();
+    if !psbt.proprietary.is_empty() || !psbt.unknown.is_empty() {
+        dropped.push(Dropped {
+            location: FieldLocation::Global,
+            field: DroppedField::Proprietary,
+        });
+    }
+
+    let mut in_value = Some(0u64);
+    for (i, (txin, inp)) in tx.input.iter().zip(&psbt.inputs).enumerate() {
+        let mut drop = |field| {
+            dropped.push(Dropped {
+                location: FieldLocation::Input(i),
+                field,
+            })
+        };
+        let prevout = txin.previous_output;
+        let utxo = match (&inp.witness_utxo, &inp.non_witness_utxo) {
+            (Some(utxo), _) => Some(utxo),
+            (None, Some(prev_tx)) => {
+                if prev_tx.txid() != prevout.txid {
+                    return Err(CompatError::UtxoMismatch(i));
+                }
+                let utxo = prev_tx.output.get(prevout.vout as usize);
+                Some(utxo.ok_or(CompatError::UtxoMismatch(i))?)
+            }
+            (None, None) => None,
+        };
+        in_value = match (in_value, utxo) {
+            (Some(sum), Some(utxo)) => sum.checked_add(utxo.value),
+            _ => None,
+        };
+        if inp.non_witness_utxo.is_some() {
+            drop(DroppedField::NonWitnessUtxo);
+        }
+        let new = psbt::Input {
+            previous_txid: Txid::from_inner(prevout.txid.into_inner()),
+            previous_output_index: prevout.vout,
+            sequence: Some(Sequence(txin.sequence.0)),
+            witness_utxo: utxo.map(|utxo| TxOut {
+                asset: confidential::Asset::Explicit(asset),
+                value: confidential::Value::Explicit(utxo.value),
+                script_pubkey: script(&utxo.script_pubkey),
+                ..Default::default()
+            }),
+            sighash_type: inp
+                .sighash_type
+                .map(|ty| psbt::PsbtSighashType::from_u32(ty.to_u32())),
+            redeem_script: inp.redeem_script.as_ref().map(script),
+            witness_script: inp.witness_script.as_ref().map(script),
+            bip32_derivation: full_keys(&inp.bip32_derivation),
+            ripemd160_preimages: inp.ripemd160_preimages.clone(),
+            sha256_preimages: inp.sha256_preimages.clone(),
+            hash160_preimages: inp.hash160_preimages.clone(),
+            hash256_preimages: inp.hash256_preimages.clone(),
+            tap_internal_key: inp.tap_internal_key,
+            tap_key_origins: key_origins(&inp.tap_key_origins),
+            ..Default::default()
+        };
+
+        if !inp.partial_sigs.is_empty()
+            || inp.final_script_sig.is_some()
+            || inp.final_script_witness.is_some()
+            || inp.tap_key_sig.is_some()
+            || !inp.tap_script_sigs.is_empty()
+        {
+            drop(DroppedField::Signatures);
+        }
+        if !inp.tap_scripts.is_empty()
+            || inp.tap_merkle_root.is_some()
+            || inp
+                .tap_key_origins
+                .values()
+                .any(|(leaves, _)| !leaves.is_empty())
+        {
+            drop(DroppedField::TaprootTree);
+        }
+        if !inp.proprietary.is_empty() || !inp.unknown.is_empty() {
+            drop(DroppedField::Proprietary);
+        }
+        pset.add_input(new);
+    }
+
+    let mut out_value = 0u64;
+    for (i, (txout, out)) in tx.output.iter().zip(&psbt.outputs).enumerate() {
+        out_value = out_value.saturating_add(txout.value);
+        let new = psbt::Output {
+            redeem_script: out.redeem_script.as_ref().map(script),
+            witness_script: out.witness_script.as_ref().map(script),
+            bip32_derivation: full_keys(&out.bip32_derivation),
+            tap_internal_key: out.tap_internal_key,
+            tap_key_origins: key_origins(&out.tap_key_origins),
+            ..psbt::Output::new_explicit(script(&txout.script_pubkey), txout.value, asset, None)
+        };
+
+        let location = FieldLocation::Output(i);
+        if out.tap_tree.is_some()
+            || out
+                .tap_key_origins
+                .values()
+                .any(|(leaves, _)| !leaves.is_empty())
+        {
+            dropped.push(Dropped {
+                location,
+                field: DroppedField::TaprootTree,
+            });
+        }
+        if !out.proprietary.is_empty() || !out.unknown.is_empty() {
+            dropped.push(Dropped {
+                location,
+                field: DroppedField::Proprietary,
+            });
+        }
+        pset.add_output(new);
+    }
+
+    match in_value {
+        Some(in_value) if in_value > out_value => pset.add_output(psbt::Output::new_explicit(
+            Script::new(),
+            in_value - out_value,
+            asset,
+            None,
+        )),
+        _ => {}
+    }
+    Ok((pset, dropped))
+}
+
+/// Converts a PSET with explicit amounts of `asset` to a Bitcoin PSBT.
+///
+/// The explicit fee output is left out, so the fee becomes implicit and the
+/// indices of later outputs shift. Returns the PSBT and the fields which were
+/// not carried over.
+pub fn to_bitcoin_psbt(
+    pset: &Psbt,
+    asset: AssetId,
+) -> Result<(btc_psbt::PartiallySignedTransaction, Vec<Dropped>), CompatError> {
+    let mut dropped = vec![];
+    let lock_time = pset.locktime().map_err(CompatError::Pset)?;
+    let mut tx = bitcoin::Transaction {
+        version: pset.global.tx_data.version as i32,
+        lock_time: bitcoin::PackedLockTime(lock_time.to_consensus_u32()),
+        input: vec![],
+        output: vec![],
+    };
+    if !pset.global.scalars.is_empty() || pset.global.elements_tx_modifiable_flag.is_some() {
+        dropped.push(Dropped {
+            location: FieldLocation::Global,
+            field: DroppedField::Blinding,
+        });
+    }
+    if !pset.global.proprietary.is_empty() || !pset.global.unknown.is_empty() {
+        dropped.push(Dropped {
+            location: FieldLocation::Global,
+            field: DroppedField::Proprietary,
+        });
+    }
+
+    let mut inputs = vec![];
+    for (i, inp) in pset.inputs().iter().enumerate() {
+        let location = FieldLocation::Input(i);
+        let mut drop = |field| dropped.push(Dropped { location, field });
+        tx.input.push(bitcoin::TxIn {
+            previous_output: bitcoin::OutPoint {
+                txid: bitcoin::Txid::from_inner(inp.previous_txid.into_inner()),
+                vout: inp.previous_output_index,
+            },
+            script_sig: bitcoin::Script::new(),
+            sequence: bitcoin::Sequence(inp.sequence.unwrap_or(Sequence::MAX).0),
+            witness: bitcoin::Witness::default(),
+        });
+
+        let witness_utxo = match inp.witness_utxo {
+            Some(ref utxo) => Some(bitcoin::TxOut {
+                value: explicit_amount(utxo.value.explicit(), utxo.asset, asset, location)?,
+                script_pubkey: btc_script(&utxo.script_pubkey),
+            }),
+            None => None,
+        };
+        if inp.non_witness_utxo.is_some() {
+            drop(DroppedField::NonWitnessUtxo);
+        }
+        let new = btc_psbt::Input {
+            witness_utxo,
+            sighash_type: inp
+                .sighash_type
+                .map(|ty| btc_psbt::PsbtSighashType::from_u32(ty.to_u32())),
+            redeem_script: inp.redeem_script.as_ref().map(btc_script),
+            witness_script: inp.witness_script.as_ref().map(btc_script),
+            bip32_derivation: inner_keys(&inp.bip32_derivation),
+            ripemd160_preimages: inp.ripemd160_preimages.clone(),
+            sha256_preimages: inp.sha256_preimages.clone(),
+            hash160_preimages: inp.hash160_preimages.clone(),
+            hash256_preimages: inp.hash256_preimages.clone(),
+            tap_internal_key: inp.tap_internal_key,
+            tap_key_origins: key_origins(&inp.tap_key_origins),
+            ..Default::default()
+        };
+
+        if !inp.partial_sigs.is_empty()
+            || inp.final_script_sig.is_some()
+            || inp.final_script_witness.is_some()
+            || inp.tap_key_sig.is_some()
+            || !inp.tap_script_sigs.is_empty()
+        {
+            drop(DroppedField::Signatures);
+        }
+        if !inp.tap_scripts.is_empty()
+            || inp.tap_merkle_root.is_some()
+            || inp
+                .tap_key_origins
+                .values()
+                .any(|(leaves, _)| !leaves.is_empty())
+        {
+            drop(DroppedField::TaprootTree);
+        }
+        if inp.has_issuance() {
+            drop(DroppedField::Issuance);
+        }
+        if inp.is_pegin() {
+            drop(DroppedField::Pegin);
+        }
+        if inp.in_utxo_rangeproof.is_some()
+            || inp.issuance_value_comm.is_some()
+            || inp.issuance_inflation_keys_comm.is_some()
+            || inp.issuance_value_rangeproof.is_some()
+            || inp.issuance_keys_rangeproof.is_some()
+            || inp.in_issuance_blind_value_proof.is_some()
+            || inp.in_issuance_blind_inflation_keys_proof.is_some()
+        {
+            drop(DroppedField::Blinding);
+        }
+        if !inp.proprietary.is_empty() || !inp.unknown.is_empty() {
+            drop(DroppedField::Proprietary);
+        }
+        inputs.push(new);
+    }
+
+    let mut outputs = vec![];
+    for (i, out) in pset.outputs().iter().enumerate() {
+        let location = FieldLocation::Output(i);
+        let mut drop = |field| dropped.push(Dropped { location, field });
+        let asset_value = match (out.asset, out.asset_comm) {
+            (Some(id), _) => confidential::Asset::Explicit(id),
+            (None, Some(gen)) => confidential::Asset::Confidential(gen),
+            (None, None) => confidential::Asset::Null,
+        };
+        let value = explicit_amount(out.amount, asset_value, asset, location)?;
+        if out.script_pubkey.is_empty() {
+            drop(DroppedField::FeeOutput);
+            continue;
+        }
+        tx.output.push(bitcoin::TxOut {
+            value,
+            script_pubkey: btc_script(&out.script_pubkey),
+        });
+
+        let new = btc_psbt::Output {
+            redeem_script: out.redeem_script.as_ref().map(btc_script),
+            witness_script: out.witness_script.as_ref().map(btc_script),
+            bip32_derivation: inner_keys(&out.bip32_derivation),
+            tap_internal_key: out.tap_internal_key,
+            tap_key_origins: key_origins(&out.tap_key_origins),
+            ..Default::default()
+        };
+
+        if out.tap_tree.is_some()
+            || out
+                .tap_key_origins
+                .values()
+                .any(|(leaves, _)| !leaves.is_empty())
+        {
+            drop(DroppedField::TaprootTree);
+        }
+        if out.blinding_key.is_some()
+            || out.ecdh_pubkey.is_some()
+            || out.blinder_index.is_some()
+            || out.amount_comm.is_some()
+            || out.asset_comm.is_some()
+            || out.value_rangeproof.is_some()
+            || out.asset_surjection_proof.is_some()
+            || out.blind_value_proof.is_some()
+            || out.blind_asset_proof.is_some()
+        {
+            drop(DroppedField::Blinding);
+        }
+        if !out.proprietary.is_empty() || !out.unknown.is_empty() {
+            drop(DroppedField::Proprietary);
+        }
+        outputs.push(new);
+    }
+
+    let mut psbt =
+        btc_psbt::PartiallySignedTransaction::from_unsigned_tx(tx).map_err(CompatError::Psbt)?;
+    psbt.xpub = pset.global.xpub.clone();
+    psbt.inputs = inputs;
+    psbt.outputs = outputs;
+    Ok((psbt, dropped))
+}
+
+// The explicit amount of `value`, if it is of the explicit `expected` asset
+fn explicit_amount(
+    value: Option<u64>,
+    asset: confidential::Asset,
+    expected: AssetId,
+    location: FieldLocation,
+) -> Result<u64, CompatError> {
+    match (value, asset) {
+        (Some(value), confidential::Asset::Explicit(id)) if id == expected => Ok(value),
+        (Some(_), confidential::Asset::Explicit(_)) => Err(CompatError::ForeignAsset(location)),
+        _ => Err(CompatError::Confidential(location)),
+    }
+}
+
+fn full_keys(
+    derivation: &BTreeMap<secp256k1::PublicKey, KeySource>,
+) -> BTreeMap<bitcoin::PublicKey, KeySource> {
+    derivation
+        .iter()
+        .map(|(pk, source)| (bitcoin::PublicKey::new(*pk), source.clone()))
+        .collect()
+}
+
+fn inner_keys(
+    derivation: &BTreeMap<bitcoin::PublicKey, KeySource>,
+) -> BTreeMap<secp256k1::PublicKey, KeySource> {
+    derivation
+        .iter()
+        .map(|(pk, source)| (pk.inner, source.clone()))
+        .collect()
+}
+
+// The taproot key origins without their leaf hashes, which differ between
+// the chains
+fn key_origins<L, M>(
+    origins: &BTreeMap<XOnlyPublicKey, (Vec<L>, KeySource)>,
+) -> BTreeMap<XOnlyPublicKey, (Vec<M>, KeySource)> {
+    origins
+        .iter()
+        .map(|(pk, (_, source))| (*pk, (vec![], source.clone())))
+        .collect()
+}
+
+fn script(script: &bitcoin::Script) -> Script {
+    Script::from(script.to_bytes())
+}
+
+fn btc_script(script: &Script) -> bitcoin::Script {
+    bitcoin::Script::from(script.to_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use bitcoin::hashes::hex::FromHex;
+    use bitcoin::util::bip32;
+    use elements::hashes::sha256;
+
+    use super::*;
+
+    fn asset() -> AssetId {
+        AssetId::from_str("6f0279e9ed041c3d710a9f57d0c02928416460c4b722ae3457a11eec381c526d")
+            .unwrap()
+    }
+
+    fn btc_psbt() -> btc_psbt::PartiallySignedTransaction {
+        let spk = bitcoin::Script::from(
+            Vec::<u8>::from_hex("0014d85c2b71d0060b09c9886aeb815e50991dda124d").unwrap(),
+        );
+        let tx = bitcoin::Transaction {
+            version: 2,
+            lock_time: bitcoin::PackedLockTime(800_000),
+            input: vec![bitcoin::TxIn {
+                previous_output: bitcoin::OutPoint {
+                    txid: bitcoin::Txid::from_inner([7; 32]),
+                    vout: 1,
+                },
+                sequence: bitcoin::Sequence(0xffff_fffd),
+                ..Default::default()
+            }],
+            output: vec![bitcoin::TxOut {
+                value: 90_000,
+                script_pubkey: spk.clone(),
+            }],
+        };
+        let mut psbt = btc_psbt::PartiallySignedTransaction::from_unsigned_tx(tx).unwrap();
+        let pk = bitcoin::PublicKey::from_str(
+            "028c28a97bf8298bc0d23d8c749452a32e694b65e30a9472a3954ab30fe5324caa",
+        )
+        .unwrap();
+        let source = (
+            bip32::Fingerprint::from_str("d34db33f").unwrap(),
+            bip32::DerivationPath::from_str("m/84'/0'/0'/0/0").unwrap(),
+        );
+        psbt.inputs[0].witness_utxo = Some(bitcoin::TxOut {
+            value: 100_000,
+            script_pubkey: spk,
+        });
+        psbt.inputs[0]
+            .bip32_derivation
+            .insert(pk.inner, source.clone());
+        psbt.inputs[0]
+            .sha256_preimages
+            .insert(sha256::Hash::hash(&[1; 32]), vec![1; 32]);
+        psbt.outputs[0].bip32_derivation.insert(pk.inner, source);
+        psbt
+    }
+
+    #[test]
+    fn bitcoin_psbt_round_trip() {
+        let psbt = btc_psbt();
+        let (pset, dropped) = from_bitcoin_psbt(&psbt, asset()).unwrap();
+        assert!(dropped.is_empty());
+        assert_eq!(pset.inputs()[0].previous_output_index, 1);
+        assert_eq!(pset.inputs()[0].sequence, Some(Sequence(0xffff_fffd)));
+        assert_eq!(pset.locktime().unwrap().to_consensus_u32(), 800_000);
+        // The fee becomes an explicit output
+        assert_eq!(pset.outputs().len(), 2);
+        assert!(pset.outputs()[1].script_pubkey.is_empty());
+        assert_eq!(pset.outputs()[1].amount, Some(10_000));
+        assert_eq!(pset.outputs()[1].asset, Some(asset()));
+        pset.extract_tx().unwrap();
+
+        let (back, dropped) = to_bitcoin_psbt(&pset, asset()).unwrap();
+        assert_eq!(
+            dropped,
+            vec![Dropped {
+                location: FieldLocation::Output(1),
+                field: DroppedField::FeeOutput,
+            }]
+        );
+        assert_eq!(back, psbt);
+    }
+
+    #[test]
+    fn bitcoin_psbt_dropped_fields() {
+        let mut psbt = btc_psbt();
+        let pk = *psbt.inputs[0].bip32_derivation.keys().next().unwrap();
+        let secp = bitcoin::secp256k1::Secp256k1::signing_only();
+        let sk = bitcoin::secp256k1::SecretKey::from_slice(&[1; 32]).unwrap();
+        let msg = bitcoin::secp256k1::Message::from_slice(&[2; 32]).unwrap();
+        let sig = bitcoin::EcdsaSig::sighash_all(secp.sign_ecdsa(&msg, &sk));
+        psbt.inputs[0]
+            .partial_sigs
+            .insert(bitcoin::PublicKey::new(pk), sig);
+        let (mut pset, dropped) = from_bitcoin_psbt(&psbt, asset()).unwrap();
+        assert_eq!(
+            dropped,
+            vec![Dropped {
+                location: FieldLocation::Input(0),
+                field: DroppedField::Signatures,
+            }]
+        );
+        assert!(pset.inputs()[0].partial_sigs.is_empty());
+
+        // Blinded and foreign amounts cannot be converted
+        pset.outputs_mut()[0].blinding_key = Some(bitcoin::PublicKey::new(pk));
+        let (_, dropped) = to_bitcoin_psbt(&pset, asset()).unwrap();
+        assert_eq!(dropped[0].location, FieldLocation::Output(0));
+        assert_eq!(dropped[0].field, DroppedField::Blinding);
+        pset.outputs_mut()[0].asset = Some(AssetId::from_inner(sha256::Midstate([1; 32])));
+        match to_bitcoin_psbt(&pset, asset()) {
+            Err(CompatError::ForeignAsset(FieldLocation::Output(0))) => {}
+            res => panic!("unexpected {:?}", res.map(|_| ())),
+        }
+        pset.outputs_mut()[0].amount = None;
+        match to_bitcoin_psbt(&pset, asset()) {
+            Err(CompatError::Confidential(FieldLocation::Output(0))) => {}
+            res => panic!("unexpected {:?}", res.map(|_| ())),
+        }
+    }
+}
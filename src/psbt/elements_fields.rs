@@ -0,0 +1,362 @@
+// Miniscript
+// Written in 2023 by
+//     The Elements Miniscript developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Elements PSET Fields
+//!
+//! Typed access to the `PSET_ELEMENTS_*` proprietary key-value pairs of
+//! PSET inputs and outputs: value and asset commitments, rangeproofs,
+//! surjection proofs and their blinding proofs.
+//!
+//! The upstream parser stores the pairs it knows in typed fields of
+//! [`psbt::Input`] and [`psbt::Output`]. A pair that is inserted by hand in
+//! the `proprietary` map instead is serialized next to the typed field and
+//! makes the PSET unparsable, so the updater and the finalizer move such
+//! pairs to their typed field with [`ElementsFields::absorb_elements_pairs`].
+//!
+
+use std::collections::BTreeMap;
+use std::{error, fmt};
+
+use elements::encode;
+use elements::pset::serialize::{Deserialize, Serialize};
+use elements::pset::{self as psbt, raw};
+
+use super::PsbtFields;
+
+/// The proprietary key prefix of the `PSET_ELEMENTS_*` fields
+pub const PSET_KEY_PREFIX: &[u8] = b"pset";
+
+/// Error of the typed access to the `PSET_ELEMENTS_*` fields. Each variant
+/// holds the proprietary subtype of the field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ElementsFieldError {
+    /// A `proprietary` entry differs from the already set typed field
+    Conflict(u8),
+    /// A value could not be parsed as the type of the field
+    Malformed(u8),
+}
+
+impl fmt::Display for ElementsFieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            ElementsFieldError::Conflict(subtype) => write!(
+                f,
+                "proprietary pset field {:#04x} conflicts with its typed value",
+                subtype
+            ),
+            ElementsFieldError::Malformed(subtype) => {
+                write!(f, "malformed value of pset field {:#04x}", subtype)
+            }
+        }
+    }
+}
+
+impl error::Error for ElementsFieldError {}
+
+// Defines a field enum with its subtypes, and the `ElementsFields`
+// implementation accessing the typed field of each variant
+macro_rules! elements_fields {
+    (
+        $(#[$meta:meta])*
+        $field:ident for $map:ty {
+            $($(#[$var_meta:meta])* $variant:ident = $subtype:expr => $name:ident,)*
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        pub enum $field {
+            $($(#[$var_meta])* $variant,)*
+        }
+
+        impl $field {
+            /// All fields, ordered by subtype
+            pub const ALL: &'static [$field] = &[$($field::$variant,)*];
+
+            /// The proprietary subtype of the field
+            pub fn subtype(self) -> u8 {
+                match self {
+                    $($field::$variant => $subtype,)*
+                }
+            }
+
+            /// The field of a proprietary subtype
+            pub fn from_subtype(subtype: u8) -> Option<Self> {
+                match subtype {
+                    $($subtype => Some($field::$variant),)*
+                    _ => None,
+                }
+            }
+        }
+
+        impl ElementsFields for $map {
+            type Field = $field;
+
+            fn elements_field(&self, field: $field) -> Option<Vec<u8>> {
+                match field {
+                    $($field::$variant => self.$name.as_ref().map(Serialize::serialize),)*
+                }
+            }
+
+            fn set_elements_field(
+                &mut self,
+                field: $field,
+                value: &[u8],
+            ) -> Result<(), ElementsFieldError> {
+                match field {
+                    $($field::$variant => {
+                        self.$name = Some(
+                            Deserialize::deserialize(value)
+                                .map_err(|_: encode::Error| ElementsFieldError::Malformed($subtype))?,
+                        )
+                    })*
+                }
+                Ok(())
+            }
+
+            fn clear_elements_field(&mut self, field: $field) {
+                match field {
+                    $($field::$variant => self.$name = None,)*
+                }
+            }
+
+            fn elements_pairs(&self) -> BTreeMap<raw::ProprietaryKey, Vec<u8>> {
+                $field::ALL
+                    .iter()
+                    .filter_map(|&field| {
+                        self.elements_field(field)
+                            .map(|value| (pset_key(field.subtype()), value))
+                    })
+                    .collect()
+            }
+
+            fn absorb_elements_pairs(&mut self) -> Result<(), ElementsFieldError> {
+                absorb(self, $field::from_subtype)
+            }
+        }
+    };
+}
+
+/// Typed access to the `PSET_ELEMENTS_*` fields of a PSET map
+pub trait ElementsFields {
+    /// The fields of the map
+    type Field: Copy;
+
+    /// The serialized value of `field`, if set
+    fn elements_field(&self, field: Self::Field) -> Option<Vec<u8>>;
+
+    /// Parse the serialized `value` into `field`, replacing its value
+    fn set_elements_field(
+        &mut self,
+        field: Self::Field,
+        value: &[u8],
+    ) -> Result<(), ElementsFieldError>;
+
+    /// Unset `field`
+    fn clear_elements_field(&mut self, field: Self::Field);
+
+    /// The set fields, as the proprietary key-value pairs of their
+    /// serialization
+    fn elements_pairs(&self) -> BTreeMap<raw::ProprietaryKey, Vec<u8>>;
+
+    /// Move the entries of the `proprietary` map with a known
+    /// `PSET_ELEMENTS_*` key to their typed field. Entries equal to the
+    /// typed field are dropped.
+    ///
+    /// # Errors
+    /// When an entry cannot be parsed or differs from the already set
+    /// typed field. The map is unchanged then.
+    fn absorb_elements_pairs(&mut self) -> Result<(), ElementsFieldError>;
+}
+
+elements_fields! {
+    /// The `PSET_ELEMENTS_IN_*` fields of [`psbt::Input`]
+    InputField for psbt::Input {
+        /// `PSBT_ELEMENTS_IN_ISSUANCE_VALUE`
+        IssuanceValue = 0x00 => issuance_value_amount,
+        /// `PSBT_ELEMENTS_IN_ISSUANCE_VALUE_COMMITMENT`
+        IssuanceValueCommitment = 0x01 => issuance_value_comm,
+        /// `PSBT_ELEMENTS_IN_ISSUANCE_VALUE_RANGEPROOF`
+        IssuanceValueRangeproof = 0x02 => issuance_value_rangeproof,
+        /// `PSBT_ELEMENTS_IN_ISSUANCE_KEYS_RANGEPROOF`
+        IssuanceKeysRangeproof = 0x03 => issuance_keys_rangeproof,
+        /// `PSBT_ELEMENTS_IN_PEG_IN_TX`
+        PeginTx = 0x04 => pegin_tx,
+        /// `PSBT_ELEMENTS_IN_PEG_IN_TXOUT_PROOF`
+        PeginTxoutProof = 0x05 => pegin_txout_proof,
+        /// `PSBT_ELEMENTS_IN_PEG_IN_GENESIS`
+        PeginGenesis = 0x06 => pegin_genesis_hash,
+        /// `PSBT_ELEMENTS_IN_PEG_IN_CLAIM_SCRIPT`
+        PeginClaimScript = 0x07 => pegin_claim_script,
+        /// `PSBT_ELEMENTS_IN_PEG_IN_VALUE`
+        PeginValue = 0x08 => pegin_value,
+        /// `PSBT_ELEMENTS_IN_PEG_IN_WITNESS`
+        PeginWitness = 0x09 => pegin_witness,
+        /// `PSBT_ELEMENTS_IN_ISSUANCE_INFLATION_KEYS`
+        IssuanceInflationKeys = 0x0a => issuance_inflation_keys,
+        /// `PSBT_ELEMENTS_IN_ISSUANCE_INFLATION_KEYS_COMMITMENT`
+        IssuanceInflationKeysCommitment = 0x0b => issuance_inflation_keys_comm,
+        /// `PSBT_ELEMENTS_IN_ISSUANCE_BLINDING_NONCE`
+        IssuanceBlindingNonce = 0x0c => issuance_blinding_nonce,
+        /// `PSBT_ELEMENTS_IN_ISSUANCE_ASSET_ENTROPY`
+        IssuanceAssetEntropy = 0x0d => issuance_asset_entropy,
+        /// `PSBT_ELEMENTS_IN_UTXO_RANGEPROOF`
+        UtxoRangeproof = 0x0e => in_utxo_rangeproof,
+        /// `PSBT_ELEMENTS_IN_ISSUANCE_BLIND_VALUE_PROOF`
+        IssuanceBlindValueProof = 0x0f => in_issuance_blind_value_proof,
+        /// `PSBT_ELEMENTS_IN_ISSUANCE_BLIND_INFLATION_KEYS_PROOF`
+        IssuanceBlindInflationKeysProof = 0x10 => in_issuance_blind_inflation_keys_proof,
+    }
+}
+
+elements_fields! {
+    /// The `PSET_ELEMENTS_OUT_*` fields of [`psbt::Output`]
+    OutputField for psbt::Output {
+        /// `PSBT_ELEMENTS_OUT_VALUE_COMMITMENT`
+        ValueCommitment = 0x01 => amount_comm,
+        /// `PSBT_ELEMENTS_OUT_ASSET`
+        Asset = 0x02 => asset,
+        /// `PSBT_ELEMENTS_OUT_ASSET_COMMITMENT`
+        AssetCommitment = 0x03 => asset_comm,
+        /// `PSBT_ELEMENTS_OUT_VALUE_RANGEPROOF`
+        ValueRangeproof = 0x04 => value_rangeproof,
+        /// `PSBT_ELEMENTS_OUT_ASSET_SURJECTION_PROOF`
+        AssetSurjectionProof = 0x05 => asset_surjection_proof,
+        /// `PSBT_ELEMENTS_OUT_BLINDING_PUBKEY`
+        BlindingPubkey = 0x06 => blinding_key,
+        /// `PSBT_ELEMENTS_OUT_ECDH_PUBKEY`
+        EcdhPubkey = 0x07 => ecdh_pubkey,
+        /// `PSBT_ELEMENTS_OUT_BLINDER_INDEX`
+        BlinderIndex = 0x08 => blinder_index,
+        /// `PSBT_ELEMENTS_OUT_BLIND_VALUE_PROOF`
+        BlindValueProof = 0x09 => blind_value_proof,
+        /// `PSBT_ELEMENTS_OUT_BLIND_ASSET_PROOF`
+        BlindAssetProof = 0x0a => blind_asset_proof,
+    }
+}
+
+/// The unkeyed proprietary key of the `PSET_ELEMENTS_*` field `subtype`
+pub fn pset_key(subtype: u8) -> raw::ProprietaryKey {
+    raw::ProprietaryKey {
+        prefix: PSET_KEY_PREFIX.to_vec(),
+        subtype,
+        key: vec![],
+    }
+}
+
+fn absorb<M, F>(map: &mut M, from_subtype: fn(u8) -> Option<F>) -> Result<(), ElementsFieldError>
+where
+    M: ElementsFields<Field = F> + PsbtFields + Clone,
+    F: Copy,
+{
+    let known: Vec<(raw::ProprietaryKey, F)> = map
+        .proprietary()
+        .keys()
+        .filter(|key| key.prefix == PSET_KEY_PREFIX && key.key.is_empty())
+        .filter_map(|key| from_subtype(key.subtype).map(|field| (key.clone(), field)))
+        .collect();
+    if known.is_empty() {
+        return Ok(());
+    }
+
+    // Work on a copy so that the map is unchanged on errors
+    let mut staged = map.clone();
+    for (key, field) in known {
+        let value = staged.proprietary().remove(&key).expect("key from the map");
+        match staged.elements_field(field) {
+            Some(typed) if typed == value => {}
+            Some(_) => return Err(ElementsFieldError::Conflict(key.subtype)),
+            None => staged.set_elements_field(field, &value)?,
+        }
+    }
+    *map = staged;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use elements::secp256k1_zkp::{Generator, PedersenCommitment, Secp256k1, Tag, Tweak};
+
+    use super::*;
+
+    #[test]
+    fn elements_fields() {
+        let secp = Secp256k1::new();
+        let asset = Generator::new_blinded(
+            &secp,
+            Tag::from([1; 32]),
+            Tweak::from_inner([2; 32]).unwrap(),
+        );
+        let value =
+            PedersenCommitment::new(&secp, 1000, Tweak::from_inner([3; 32]).unwrap(), asset);
+
+        let mut output = psbt::Output {
+            amount_comm: Some(value),
+            ..Default::default()
+        };
+        let pairs = output.elements_pairs();
+        assert_eq!(pairs.len(), 1);
+        assert_eq!(pairs[&pset_key(0x01)], value.serialize().to_vec());
+        assert_eq!(
+            output.elements_field(OutputField::ValueCommitment),
+            Some(value.serialize().to_vec())
+        );
+        for field in OutputField::ALL {
+            assert_eq!(OutputField::from_subtype(field.subtype()), Some(*field));
+        }
+
+        // Hand-inserted pairs move to the typed fields, others stay put
+        let asset_key = pset_key(OutputField::AssetCommitment.subtype());
+        let other = raw::ProprietaryKey {
+            prefix: b"other".to_vec(),
+            subtype: 0x03,
+            key: vec![],
+        };
+        output
+            .proprietary
+            .insert(asset_key.clone(), asset.serialize().to_vec());
+        output
+            .proprietary
+            .insert(pset_key(0x01), value.serialize().to_vec());
+        output.proprietary.insert(other.clone(), vec![1, 2, 3]);
+        output.absorb_elements_pairs().unwrap();
+        assert_eq!(output.asset_comm, Some(asset));
+        assert_eq!(output.amount_comm, Some(value));
+        assert_eq!(output.proprietary.len(), 1);
+        assert!(output.proprietary.contains_key(&other));
+
+        // Conflicting or malformed pairs leave the map unchanged
+        let mut input = psbt::Input::default();
+        input.proprietary.insert(pset_key(0x00), vec![5; 8]);
+        input.proprietary.insert(pset_key(0x01), vec![0; 3]);
+        assert_eq!(
+            input.absorb_elements_pairs(),
+            Err(ElementsFieldError::Malformed(0x01))
+        );
+        assert_eq!(input.issuance_value_amount, None);
+        assert_eq!(input.proprietary.len(), 2);
+
+        input.proprietary.remove(&pset_key(0x01));
+        input.issuance_value_amount = Some(7);
+        assert_eq!(
+            input.absorb_elements_pairs(),
+            Err(ElementsFieldError::Conflict(0x00))
+        );
+        input
+            .set_elements_field(InputField::IssuanceValue, &[5; 8])
+            .unwrap();
+        input.absorb_elements_pairs().unwrap();
+        assert!(input.proprietary.is_empty());
+        assert_eq!(input.issuance_value_amount, Some(0x0505050505050505));
+    }
+}
@@ -19,17 +19,26 @@
 //! `https://github.com/bitcoin/bips/blob/master/bip-0174.mediawiki`
 //!
 
+use std::convert::Infallible;
+use std::sync::Arc;
+
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::util::bip32;
 use bitcoin::{self, PublicKey, XOnlyPublicKey};
 use elements::secp256k1_zkp::{self, Secp256k1};
-use elements::taproot::LeafVersion;
-use elements::{self, confidential, Script, Sequence, Transaction, TxOut};
+use elements::taproot::{LeafVersion, TapBranchHash};
+use elements::{self, confidential, pset, Script, Sequence, Transaction, TxOut};
 
+use super::elements_fields::ElementsFields;
 use super::{sanity_check, Error, InputError, Psbt, PsbtInputSatisfier};
-use crate::descriptor::{LegacyCSFSCov, LegacyCovSatisfier};
-use crate::extensions::{CovExtArgs, TxEnv};
+use crate::descriptor::{
+    DefiniteDescriptorKey, DescriptorPublicKey, LegacyCSFSCov, LegacyCovSatisfier, SinglePub,
+    SinglePubKey, TapTree,
+};
+use crate::extensions::{CovExtArgs, ParseableExt, TxEnv};
 use crate::{
-    interpreter, util, BareCtx, CovenantExt, Descriptor, ExtParams, Legacy, Miniscript, Satisfier,
-    Segwitv0, SigType, Tap, ToPublicKey,
+    interpreter, util, BareCtx, CovenantExt, Descriptor, ExtParams, Extension, Legacy, Miniscript,
+    NoExt, Satisfier, Segwitv0, SigType, Tap, ToPublicKey, TranslatePk, Translator,
 };
 
 // Get the amount being spent for the psbt input
@@ -117,7 +126,11 @@ pub(super) fn get_scriptpubkey(psbt: &Psbt, index: usize) -> Result<&Script, Inp
 
 // Get the spending utxo for this psbt input
 pub(super) fn get_utxo(psbt: &Psbt, index: usize) -> Result<&elements::TxOut, InputError> {
-    let inp = &psbt.inputs()[index];
+    input_utxo(&psbt.inputs()[index])
+}
+
+// Get the spending utxo of a psbt input on its own
+fn input_utxo(inp: &pset::Input) -> Result<&elements::TxOut, InputError> {
     let utxo = if let Some(ref witness_utxo) = inp.witness_utxo {
         witness_utxo
     } else if let Some(ref non_witness_utxo) = inp.non_witness_utxo {
@@ -153,7 +166,14 @@ pub(super) fn get_descriptor(
 ) -> Result<Descriptor<PublicKey, CovenantExt<CovExtArgs>>, InputError> {
     // Figure out Scriptpubkey
     let script_pubkey = get_scriptpubkey(psbt, index)?;
-    let inp = &psbt.inputs()[index];
+    infer_descriptor(&psbt.inputs()[index], script_pubkey)
+}
+
+// The non-taproot part of `get_descriptor`, for an input on its own
+fn infer_descriptor(
+    inp: &pset::Input,
+    script_pubkey: &Script,
+) -> Result<Descriptor<PublicKey, CovenantExt<CovExtArgs>>, InputError> {
     // 1. `PK`: creates a `Pk` descriptor(does not check if partial sig is given)
     if script_pubkey.is_p2pk() {
         let script_pubkey_len = script_pubkey.len();
@@ -282,6 +302,142 @@ pub(super) fn get_descriptor(
     }
 }
 
+// A taproot leaf with the merkle branch of its control block
+type TapLeafBranch<'a, Ext> = (Miniscript<XOnlyPublicKey, Tap, Ext>, &'a [sha256::Hash]);
+
+// Rebuild the taproot tree of an input from the merkle branches of its
+// control blocks. Subtrees without a known leaf become hidden nodes, the
+// hash of which is the sibling in the branches of the known leaves.
+fn infer_tap_tree<Ext: Extension>(
+    leaves: Vec<TapLeafBranch<Ext>>,
+) -> Result<TapTree<XOnlyPublicKey, Ext>, InputError> {
+    let mut leaves = leaves;
+    if leaves.len() == 1 && leaves[0].1.is_empty() {
+        let (ms, _) = leaves.pop().expect("one leaf");
+        return Ok(TapTree::Leaf(Arc::new(ms)));
+    }
+    let mut left = vec![];
+    let mut right = vec![];
+    let (mut left_sibling, mut right_sibling) = (None, None);
+    for (ms, branch) in leaves {
+        let (sibling, rest) = branch.split_last().ok_or(InputError::TapTreeMismatch)?;
+        if left_sibling.is_none() || left_sibling == Some(sibling) {
+            left_sibling = Some(sibling);
+            left.push((ms, rest));
+        } else if right_sibling.is_none() || right_sibling == Some(sibling) {
+            right_sibling = Some(sibling);
+            right.push((ms, rest));
+        } else {
+            return Err(InputError::TapTreeMismatch);
+        }
+    }
+    let left_sibling = *left_sibling.expect("at least one leaf");
+    let right_tree = if right.is_empty() {
+        TapTree::Hidden(TapBranchHash::from_inner(left_sibling.into_inner()))
+    } else {
+        infer_tap_tree(right)?
+    };
+    Ok(TapTree::Tree(
+        Arc::new(infer_tap_tree(left)?),
+        Arc::new(right_tree),
+    ))
+}
+
+// Parse the leaves of a taproot input and arrange them into a tree
+fn infer_tap_leaves<Ext: ParseableExt>(
+    inp: &pset::Input,
+) -> Result<Option<TapTree<XOnlyPublicKey, Ext>>, InputError> {
+    let mut leaves = vec![];
+    for (control_block, (script, ver)) in &inp.tap_scripts {
+        if *ver != LeafVersion::default() {
+            return Err(InputError::TapTreeMismatch);
+        }
+        let ms = Miniscript::<XOnlyPublicKey, Tap, Ext>::parse_with_ext(
+            script,
+            &ExtParams::allow_all(),
+        )?;
+        leaves.push((ms, control_block.merkle_branch.as_inner()));
+    }
+    if leaves.is_empty() {
+        Ok(inp.tap_merkle_root.map(TapTree::Hidden))
+    } else {
+        infer_tap_tree(leaves).map(Some)
+    }
+}
+
+// Infer a taproot descriptor, with leaves using extensions only if needed
+fn infer_tr_descriptor(
+    inp: &pset::Input,
+    script_pubkey: &Script,
+) -> Result<Descriptor<XOnlyPublicKey, CovenantExt<CovExtArgs>>, InputError> {
+    let internal_key = inp
+        .tap_internal_key
+        .ok_or(InputError::MissingTapInternalKey)?;
+    let desc = match infer_tap_leaves::<NoExt>(inp) {
+        Ok(tree) => Descriptor::new_tr(internal_key, tree)?,
+        Err(_) => Descriptor::new_tr_ext(internal_key, infer_tap_leaves(inp)?)?,
+    };
+    if desc.script_pubkey() == *script_pubkey {
+        Ok(desc)
+    } else {
+        Err(InputError::TapTreeMismatch)
+    }
+}
+
+// Attach the origins recorded in a psbt input to the keys of a descriptor
+struct InputKeyOrigins<'a>(&'a pset::Input);
+
+impl<'a> InputKeyOrigins<'a> {
+    fn key(key: SinglePubKey, source: Option<&bip32::KeySource>) -> DefiniteDescriptorKey {
+        DescriptorPublicKey::Single(SinglePub {
+            origin: source.cloned(),
+            key,
+        })
+        .at_derivation_index(0)
+        .expect("single keys are not multipath")
+    }
+}
+
+impl<'a> Translator<PublicKey, DefiniteDescriptorKey, Infallible> for InputKeyOrigins<'a> {
+    fn pk(&mut self, pk: &PublicKey) -> Result<DefiniteDescriptorKey, Infallible> {
+        let source = self.0.bip32_derivation.get(pk);
+        Ok(Self::key(SinglePubKey::FullKey(*pk), source))
+    }
+
+    translate_hash_clone!(PublicKey, DefiniteDescriptorKey, Infallible);
+}
+
+impl<'a> Translator<XOnlyPublicKey, DefiniteDescriptorKey, Infallible> for InputKeyOrigins<'a> {
+    fn pk(&mut self, pk: &XOnlyPublicKey) -> Result<DefiniteDescriptorKey, Infallible> {
+        let source = self.0.tap_key_origins.get(pk).map(|(_, source)| source);
+        Ok(Self::key(SinglePubKey::XOnly(*pk), source))
+    }
+
+    translate_hash_clone!(XOnlyPublicKey, DefiniteDescriptorKey, Infallible);
+}
+
+impl Descriptor<DefiniteDescriptorKey, CovenantExt<CovExtArgs>> {
+    /// Infer the descriptor spent by a PSET input from its utxo, its redeem
+    /// and witness scripts and its taproot fields, as the finalizer does.
+    /// Keys are single keys, with the origins recorded in the input.
+    ///
+    /// The keys of pkh and wpkh inputs are only known from their partial
+    /// signatures. Taproot leaves without a control block in the input
+    /// become hidden nodes of the tree, and parts of the tree which cannot
+    /// be told apart from the control blocks are reported as
+    /// [`InputError::TapTreeMismatch`].
+    pub fn infer_from_psbt_input(inp: &pset::Input) -> Result<Self, InputError> {
+        let script_pubkey = &input_utxo(inp)?.script_pubkey;
+        let mut origins = InputKeyOrigins(inp);
+        let desc = if util::is_v1_p2tr(script_pubkey) {
+            infer_tr_descriptor(inp, script_pubkey)?.translate_pk(&mut origins)
+        } else {
+            infer_descriptor(inp, script_pubkey)?.translate_pk(&mut origins)
+        };
+        Ok(desc.unwrap_or_else(|e| match e {}))
+    }
+}
+
 // Helper function to de-duplicate code
 pub fn _interpreter_inp_check<C: secp256k1_zkp::Verification>(
     psbt: &Psbt,
@@ -353,9 +509,18 @@ pub fn interpreter_check<C: secp256k1_zkp::Verification>(
     Ok(())
 }
 
+/// Whether the input already has its final scripts, for example because it
+/// was finalized by another tool
+pub(super) fn is_finalized(input: &elements::pset::Input) -> bool {
+    input.final_script_sig.is_some() || input.final_script_witness.is_some()
+}
+
 // Helper function for input sanity checks and code-dedup
 fn input_sanity_checks(psbt: &Psbt, index: usize) -> Result<(), super::Error> {
     let input = &psbt.inputs()[index];
+    if is_finalized(input) {
+        return Ok(());
+    }
     let target = input
         .ecdsa_hash_ty()
         .ok_or(Error::InputError(InputError::NonStandardSighashType, index))?;
@@ -399,20 +564,87 @@ fn input_sanity_checks(psbt: &Psbt, index: usize) -> Result<(), super::Error> {
     Ok(())
 }
 
-// Helper function to finalize a input
+fn satisfy_with<S: Satisfier<PublicKey>>(
+    desc: &Descriptor<PublicKey, CovenantExt<CovExtArgs>>,
+    sat: S,
+    allow_mall: bool,
+    index: usize,
+) -> Result<(Vec<Vec<u8>>, Script), super::Error> {
+    let sat = if !allow_mall {
+        desc.get_satisfaction(sat)
+    } else {
+        desc.get_satisfaction_mall(sat)
+    };
+    sat.map_err(|e| Error::InputError(InputError::MiniscriptError(e), index))
+}
+
+// Satisfy the input at `index`, which spends `desc`
+fn satisfy_descriptor(
+    psbt: &Psbt,
+    extracted_tx: &Transaction,
+    spent_utxos: &[TxOut],
+    index: usize,
+    desc: &Descriptor<PublicKey, CovenantExt<CovExtArgs>>,
+    allow_mall: bool,
+) -> Result<(Vec<Vec<u8>>, Script), super::Error> {
+    let psbt_sat = PsbtInputSatisfier::new(psbt, index);
+    // If the descriptor is covenant one, create a covenant satisfier. Otherwise
+    // use the regular satisfier
+    match *desc {
+        Descriptor::LegacyCSFSCov(ref cov) => {
+            // For covenant descriptors create satisfier
+            let utxo = psbt.inputs()[index]
+                .witness_utxo
+                .as_ref()
+                .ok_or(super::Error::InputError(InputError::MissingUtxo, index))?;
+            // Codesepartor calculation
+            let script_code = cov.cov_script_code();
+            let cov_sat = LegacyCovSatisfier::new_segwitv0(
+                extracted_tx,
+                index as u32,
+                utxo.value,
+                &script_code,
+                psbt.inputs()[index]
+                    .ecdsa_hash_ty()
+                    .ok_or(Error::InputError(InputError::NonStandardSighashType, index))?,
+            );
+            satisfy_with(desc, (psbt_sat, cov_sat), allow_mall, index)
+        }
+        Descriptor::Tr(..) | Descriptor::TrExt(..) => {
+            let cov_sat = TxEnv::new(extracted_tx, spent_utxos, index)
+                .ok_or(super::Error::InputError(InputError::MissingUtxo, index))?;
+            satisfy_with(desc, (psbt_sat, cov_sat), allow_mall, index)
+        }
+        _ => satisfy_with(desc, psbt_sat, allow_mall, index),
+    }
+}
+
+// Helper function to finalize a input, with the first of `descriptors` whose
+// script pubkey is spent by the input or an inferred descriptor
 fn _finalize_inp(
     psbt: &mut Psbt,
     extracted_tx: &Transaction,
     spent_utxos: &[TxOut],
     index: usize,
+    descriptors: &[Descriptor<PublicKey, CovenantExt<CovExtArgs>>],
     allow_mall: bool,
 ) -> Result<(), super::Error> {
+    trace_span!("finalize_input", index, allow_mall);
+    // Finalized inputs are left as is, which makes finalizing idempotent
+    if is_finalized(&psbt.inputs()[index]) {
+        return Ok(());
+    }
+    psbt.inputs_mut()[index]
+        .absorb_elements_pairs()
+        .map_err(|e| Error::InputError(InputError::ElementsField(e), index))?;
     // rust 1.29 burrowchecker
     let (witness, script_sig) = {
         let spk = get_scriptpubkey(psbt, index).map_err(|e| Error::InputError(e, index))?;
-        let psbt_sat = PsbtInputSatisfier::new(psbt, index);
 
-        if util::is_v1_p2tr(spk) {
+        if let Some(desc) = descriptors.iter().find(|d| d.script_pubkey() == *spk) {
+            satisfy_descriptor(psbt, extracted_tx, spent_utxos, index, desc, allow_mall)?
+        } else if util::is_v1_p2tr(spk) {
+            let psbt_sat = PsbtInputSatisfier::new(psbt, index);
             let cov_sat = TxEnv::new(&extracted_tx, spent_utxos, index)
                 .ok_or(super::Error::InputError(InputError::MissingUtxo, index))?;
             // Deal with tr case separately, unfortunately we cannot infer the full descriptor for Tr
@@ -422,43 +654,14 @@ fn _finalize_inp(
         } else {
             // Get a descriptor for this input
             let desc = get_descriptor(psbt, index).map_err(|e| Error::InputError(e, index))?;
-
-            // If the descriptor is covenant one, create a covenant satisfier. Otherwise
-            // use the regular satisfier
-            if let Descriptor::LegacyCSFSCov(cov) = &desc {
-                // For covenant descriptors create satisfier
-                let utxo = psbt.inputs()[index]
-                    .witness_utxo
-                    .as_ref()
-                    .ok_or(super::Error::InputError(InputError::MissingUtxo, index))?;
-                // Codesepartor calculation
-                let script_code = cov.cov_script_code();
-                let cov_sat = LegacyCovSatisfier::new_segwitv0(
-                    extracted_tx,
-                    index as u32,
-                    utxo.value,
-                    &script_code,
-                    psbt.inputs()[index]
-                        .ecdsa_hash_ty()
-                        .ok_or(Error::InputError(InputError::NonStandardSighashType, index))?,
-                );
-                let sat = if !allow_mall {
-                    desc.get_satisfaction((psbt_sat, cov_sat))
-                } else {
-                    desc.get_satisfaction_mall((psbt_sat, cov_sat))
-                };
-                sat.map_err(|e| Error::InputError(InputError::MiniscriptError(e), index))?
-            } else {
-                //generate the satisfaction witness and scriptsig
-                let sat = if !allow_mall {
-                    desc.get_satisfaction(psbt_sat)
-                } else {
-                    desc.get_satisfaction_mall(psbt_sat)
-                };
-                sat.map_err(|e| Error::InputError(InputError::MiniscriptError(e), index))?
-            }
+            satisfy_descriptor(psbt, extracted_tx, spent_utxos, index, &desc, allow_mall)?
         }
     };
+    trace_event!(
+        witness_elements = witness.len(),
+        script_sig_len = script_sig.len(),
+        "satisfied input"
+    );
     let input = &mut psbt.inputs_mut()[index];
     //Fill in the satisfactions
     input.final_script_sig = if script_sig.is_empty() {
@@ -481,8 +684,74 @@ fn _finalize_inp(
     Ok(())
 }
 
+/// Options of [`PsbtExt::finalize_with_mut`](super::PsbtExt::finalize_with_mut)
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Default)]
+pub struct FinalizeOptions {
+    /// Allow malleable satisfactions
+    pub allow_mall: bool,
+    /// The inputs to finalize, in this order. Other inputs are left as is.
+    /// All inputs in index order if `None`.
+    pub order: Option<Vec<usize>>,
+    /// Stop at the first input that cannot be finalized, instead of
+    /// finalizing as many inputs as possible
+    pub stop_at_first_error: bool,
+    /// Do not check already finalized inputs with the interpreter. Use this
+    /// when other tools finalized inputs that are not miniscripts.
+    pub trust_finalized: bool,
+}
+
+impl FinalizeOptions {
+    /// The default options: non-malleable satisfactions of all inputs, in
+    /// index order
+    pub fn new() -> FinalizeOptions {
+        FinalizeOptions::default()
+    }
+}
+
+/// Finalize the inputs of `psbt` according to `options`, and return an
+/// error for each input that could not be finalized
+pub(super) fn finalize_with<C: secp256k1_zkp::Verification>(
+    psbt: &mut Psbt,
+    secp: &Secp256k1<C>,
+    genesis_hash: elements::BlockHash,
+    options: &FinalizeOptions,
+) -> Result<(), Vec<super::Error>> {
+    let n_inputs = psbt.inputs().len();
+    let order = match options.order {
+        Some(ref order) => order.clone(),
+        None => (0..n_inputs).collect(),
+    };
+    let mut errors = vec![];
+    for index in order {
+        let res = if index >= n_inputs {
+            Err(Error::InputIdxOutofBounds {
+                psbt_inp: n_inputs,
+                index,
+            })
+        } else if options.trust_finalized && is_finalized(&psbt.inputs()[index]) {
+            Ok(())
+        } else {
+            finalize_input(psbt, secp, index, options.allow_mall, genesis_hash)
+        };
+        if let Err(e) = res {
+            errors.push(e);
+            if options.stop_at_first_error {
+                break;
+            }
+        }
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
 /// Finalize a single input. Look at the
 /// [finalize] API for finalizing all inputs
+///
+/// An input that is already finalized is left as is and only checked with
+/// the interpreter.
 pub fn finalize_input<C: secp256k1_zkp::Verification>(
     psbt: &mut Psbt,
     secp: &Secp256k1<C>,
@@ -494,7 +763,7 @@ pub fn finalize_input<C: secp256k1_zkp::Verification>(
 
     let extracted_tx = psbt.extract_tx()?;
     let spent_utxos = prevouts(psbt)?;
-    _finalize_inp(psbt, &extracted_tx, &spent_utxos, index, allow_mall)?;
+    _finalize_inp(psbt, &extracted_tx, &spent_utxos, index, &[], allow_mall)?;
 
     interpreter_inp_check(psbt, secp, index, genesis_hash)?;
     Ok(())
@@ -507,11 +776,31 @@ pub fn finalize_input<C: secp256k1_zkp::Verification>(
 /// For satisfaction of individual inputs, use the satisfy API.
 /// This function also performs a sanity interpreter check on the
 /// finalized psbt which involves checking the signatures/ preimages/timelocks.
+/// Inputs that are already finalized are left as is, so finalizing twice is
+/// a no-op.
 pub fn finalize<C: secp256k1_zkp::Verification>(
     psbt: &mut Psbt,
     secp: &Secp256k1<C>,
     genesis_hash: elements::BlockHash,
 ) -> Result<(), super::Error> {
+    finalize_with_descriptors(psbt, &[], secp, genesis_hash)
+}
+
+/// Finalize the psbt like [finalize], satisfying the inputs which spend
+/// one of `descriptors` with it. This allows finalizing taproot inputs
+/// whose leaves are not all in the psbt, and inputs without witness or
+/// redeem scripts. Inputs spending other script pubkeys are finalized with
+/// the descriptors inferred from the psbt.
+///
+/// The satisfier has the signatures, preimages and Elements fields of the
+/// psbt, and the transaction introspected by covenant descriptors.
+pub fn finalize_with_descriptors<C: secp256k1_zkp::Verification>(
+    psbt: &mut Psbt,
+    descriptors: &[Descriptor<PublicKey, CovenantExt<CovExtArgs>>],
+    secp: &Secp256k1<C>,
+    genesis_hash: elements::BlockHash,
+) -> Result<(), super::Error> {
+    trace_span!("finalize", inputs = psbt.inputs().len());
     sanity_check(psbt)?;
 
     // Check well-formedness of input data
@@ -528,6 +817,7 @@ pub fn finalize<C: secp256k1_zkp::Verification>(
             &extracted_tx,
             &spent_utxos,
             index,
+            descriptors,
             /*allow_mall*/ false,
         )?;
     }
@@ -535,6 +825,7 @@ pub fn finalize<C: secp256k1_zkp::Verification>(
     // This only checks whether the script will be executed
     // correctly by the bitcoin interpreter under the current
     // psbt context.
+    trace_event!("checking finalized inputs with the interpreter");
     interpreter_check(psbt, secp, genesis_hash)?;
     Ok(())
 }
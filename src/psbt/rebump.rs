@@ -0,0 +1,441 @@
+// Miniscript
+// Written in 2023 by
+//     The Elements Miniscript developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Fee Bumping
+//!
+//! Raise the fee of a PSET that was already (partially) signed, as needed
+//! to replace it (RBF) or to make a child pay for it (CPFP). The increase
+//! is taken from an explicit change output, signatures which committed to
+//! the modified outputs are dropped and the inputs which can still be
+//! satisfied are finalized again. The PSET must signal replaceability as in
+//! BIP-125, since the bumped transaction replaces the original one.
+//!
+
+use std::{error, fmt, mem};
+
+use bitcoin::XOnlyPublicKey;
+use elements::pset::{self as psbt, PartiallySignedTransaction as Psbt};
+use elements::secp256k1_zkp::{self as secp256k1, Secp256k1};
+use elements::{SchnorrSigHashType, Sequence};
+
+use super::finalizer::get_descriptor;
+use super::PsbtExt;
+use crate::confidential::{INPUT_WITNESS_OVERHEAD, OUTPUT_WITNESS_OVERHEAD};
+use crate::descriptor::{leaf_satisfaction_weight, KEY_SPEND_WEIGHT};
+use crate::extensions::{CovExtArgs, CovenantExt};
+use crate::miniscript::context::Tap;
+use crate::{util, ExtParams, Miniscript};
+
+/// Error type for [`resatisfy`]
+#[derive(Debug)]
+pub enum RebumpError {
+    /// The PSET has no explicit fee output
+    MissingFeeOutput,
+    /// The change output does not exist, is the fee output or does not
+    /// have the asset of the fee
+    InvalidChange(usize),
+    /// The change output is blinded, so its amount cannot be changed
+    /// without blinding the transaction again
+    ConfidentialChange(usize),
+    /// The input is already finalized and the sighash types of its
+    /// signatures are unknown
+    FinalizedInput(usize),
+    /// No input has an nSequence below `0xfffffffe`, so the transaction
+    /// cannot be replaced
+    NotReplaceable,
+    /// The fee at the new feerate overflows a `u64`
+    FeerateTooHigh(u64),
+    /// The new feerate does not increase the fee
+    FeeNotIncreased {
+        /// Current fee
+        old: u64,
+        /// Fee at the new feerate
+        new: u64,
+    },
+    /// The change output cannot pay for the increase of the fee
+    InsufficientChange {
+        /// Amount of the change output
+        available: u64,
+        /// Increase of the fee
+        needed: u64,
+    },
+    /// Error while estimating the satisfaction weight of the inputs
+    Psbt(super::Error),
+}
+
+impl fmt::Display for RebumpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            RebumpError::MissingFeeOutput => f.write_str("PSET has no explicit fee output"),
+            RebumpError::InvalidChange(index) => {
+                write!(f, "output {} cannot be used as change", index)
+            }
+            RebumpError::ConfidentialChange(index) => {
+                write!(f, "change output {} is confidential", index)
+            }
+            RebumpError::FinalizedInput(index) => write!(f, "input {} is finalized", index),
+            RebumpError::NotReplaceable => f.write_str("PSET does not signal replaceability"),
+            RebumpError::FeerateTooHigh(feerate) => write!(f, "feerate {} is too high", feerate),
+            RebumpError::FeeNotIncreased { old, new } => write!(
+                f,
+                "fee {} at the new feerate does not increase the current fee {}",
+                new, old
+            ),
+            RebumpError::InsufficientChange { available, needed } => write!(
+                f,
+                "change of {} cannot pay the fee increase of {}",
+                available, needed
+            ),
+            RebumpError::Psbt(ref e) => fmt::Display::fmt(e, f),
+        }
+    }
+}
+
+impl error::Error for RebumpError {
+    fn cause(&self) -> Option<&dyn error::Error> {
+        match self {
+            RebumpError::Psbt(e) => Some(e),
+            RebumpError::MissingFeeOutput
+            | RebumpError::InvalidChange(_)
+            | RebumpError::ConfidentialChange(_)
+            | RebumpError::FinalizedInput(_)
+            | RebumpError::NotReplaceable
+            | RebumpError::FeerateTooHigh(_)
+            | RebumpError::FeeNotIncreased { .. }
+            | RebumpError::InsufficientChange { .. } => None,
+        }
+    }
+}
+
+#[doc(hidden)]
+impl From<super::Error> for RebumpError {
+    fn from(e: super::Error) -> RebumpError {
+        RebumpError::Psbt(e)
+    }
+}
+
+/// The outcome of [`resatisfy`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resatisfaction {
+    /// The new fee
+    pub fee: u64,
+    /// Inputs which were finalized with the signatures left after the bump
+    pub finalized: Vec<usize>,
+    /// Inputs which could not be finalized and need fresh signatures
+    pub resign: Vec<usize>,
+}
+
+/// Raise the fee of `pset` to `new_feerate`, in satoshi per 1000 virtual
+/// bytes, taking the increase from the explicit output `change`.
+///
+/// The size of the transaction is estimated from the maximum satisfaction
+/// weight of every input, so each input must be described well enough to
+/// be finalized: signed or with its scripts filled in. Signatures whose
+/// sighash type commits to the fee or the change output are removed, while
+/// `SIGHASH_NONE` and unaffected `SIGHASH_SINGLE` signatures, typically from
+/// other participants, are kept. Every input is then finalized if possible,
+/// and the inputs which are not are returned as needing fresh signatures.
+///
+/// # Errors
+/// When the fee or change outputs are unusable, an input is already
+/// finalized, no input signals replaceability, the fee does not increase,
+/// or the change cannot pay for the increase. In that case `pset` is not
+/// modified.
+pub fn resatisfy<C: secp256k1::Verification>(
+    pset: &mut Psbt,
+    secp: &Secp256k1<C>,
+    change: usize,
+    new_feerate: u64,
+    genesis_hash: elements::BlockHash,
+) -> Result<Resatisfaction, RebumpError> {
+    if let Some(index) = pset
+        .inputs()
+        .iter()
+        .position(|inp| inp.final_script_sig.is_some() || inp.final_script_witness.is_some())
+    {
+        return Err(RebumpError::FinalizedInput(index));
+    }
+    if !pset
+        .inputs()
+        .iter()
+        .any(|inp| inp.sequence.unwrap_or(Sequence::MAX).is_rbf())
+    {
+        return Err(RebumpError::NotReplaceable);
+    }
+
+    let fee_index = pset
+        .outputs()
+        .iter()
+        .position(|out| out.script_pubkey.is_empty() && out.amount.is_some())
+        .ok_or(RebumpError::MissingFeeOutput)?;
+    let fee_out = &pset.outputs()[fee_index];
+    let old_fee = fee_out.amount.expect("fee output has an explicit amount");
+    let change_out = match pset.outputs().get(change) {
+        Some(out) if change != fee_index && out.asset == fee_out.asset => out,
+        _ => return Err(RebumpError::InvalidChange(change)),
+    };
+    let available = match change_out.amount {
+        Some(amount) if !change_out.is_partially_blinded() => amount,
+        _ => return Err(RebumpError::ConfidentialChange(change)),
+    };
+
+    let weight = weight_estimate(pset)?;
+    let vsize = (weight as u64 + 3) / 4;
+    let new_fee = vsize
+        .checked_mul(new_feerate)
+        .and_then(|fee| fee.checked_add(999))
+        .ok_or(RebumpError::FeerateTooHigh(new_feerate))?
+        / 1000;
+    if new_fee <= old_fee {
+        return Err(RebumpError::FeeNotIncreased {
+            old: old_fee,
+            new: new_fee,
+        });
+    }
+    let needed = new_fee - old_fee;
+    let change_amount = available
+        .checked_sub(needed)
+        .ok_or(RebumpError::InsufficientChange { available, needed })?;
+
+    pset.outputs_mut()[fee_index].amount = Some(new_fee);
+    pset.outputs_mut()[change].amount = Some(change_amount);
+
+    let changed = [fee_index, change];
+    for (index, inp) in pset.inputs_mut().iter_mut().enumerate() {
+        drop_stale_sigs(inp, index, &changed);
+    }
+
+    let mut finalized = vec![];
+    let mut resign = vec![];
+    for index in 0..pset.inputs().len() {
+        match pset.finalize_inp_mut(secp, index, genesis_hash) {
+            Ok(()) => finalized.push(index),
+            Err(_) => resign.push(index),
+        }
+    }
+    Ok(Resatisfaction {
+        fee: new_fee,
+        finalized,
+        resign,
+    })
+}
+
+/// Estimated weight of `pset` once all of its inputs are satisfied
+fn weight_estimate(pset: &Psbt) -> Result<usize, super::Error> {
+    let tx = pset.extract_tx()?;
+    let mut weight = tx.weight()
+        + INPUT_WITNESS_OVERHEAD * tx.input.len()
+        + OUTPUT_WITNESS_OVERHEAD * tx.output.len();
+    for (index, inp) in pset.inputs().iter().enumerate() {
+        let spk = &inp
+            .witness_utxo
+            .as_ref()
+            .ok_or(super::Error::InputError(
+                super::InputError::MissingUtxo,
+                index,
+            ))?
+            .script_pubkey;
+        weight += if util::is_v1_p2tr(spk) {
+            tr_satisfaction_weight(inp)
+        } else {
+            get_descriptor(pset, index)
+                .and_then(|desc| desc.max_satisfaction_weight().map_err(Into::into))
+                .map_err(|e| super::Error::InputError(e, index))?
+        };
+    }
+    Ok(weight)
+}
+
+/// Maximum satisfaction weight of a taproot input: a key spend once the key
+/// signature is known, otherwise the most expensive known spend
+fn tr_satisfaction_weight(inp: &psbt::Input) -> usize {
+    if inp.tap_key_sig.is_some() {
+        return KEY_SPEND_WEIGHT;
+    }
+    inp.tap_scripts
+        .iter()
+        .filter_map(|(control_block, (script, _))| {
+            let ms = Miniscript::<XOnlyPublicKey, Tap, CovenantExt<CovExtArgs>>::parse_with_ext(
+                script,
+                &ExtParams::allow_all(),
+            )
+            .ok()?;
+            leaf_satisfaction_weight(control_block.merkle_branch.as_inner().len(), &ms)
+        })
+        .fold(KEY_SPEND_WEIGHT, ::std::cmp::max)
+}
+
+/// Whether a signature of the input at `index` with the sighash type `flag`
+/// stays valid when the outputs in `changed` are modified
+fn sig_survives(flag: u8, index: usize, changed: &[usize]) -> bool {
+    // Ignore SIGHASH_ANYONECANPAY and SIGHASH_RANGEPROOF
+    match flag & 0x1f {
+        0x02 => true,
+        0x03 => !changed.contains(&index),
+        _ => false,
+    }
+}
+
+/// Remove the signatures of `inp` committing to the outputs in `changed`
+fn drop_stale_sigs(inp: &mut psbt::Input, index: usize, changed: &[usize]) {
+    // BTreeMap::retain is not available with the MSRV
+    inp.partial_sigs = mem::take(&mut inp.partial_sigs)
+        .into_iter()
+        .filter(|(_, sig)| match sig.last() {
+            Some(flag) => sig_survives(*flag, index, changed),
+            None => false,
+        })
+        .collect();
+    let survives = |hash_ty: SchnorrSigHashType| sig_survives(hash_ty as u8, index, changed);
+    if let Some(false) = inp.tap_key_sig.as_ref().map(|sig| survives(sig.hash_ty)) {
+        inp.tap_key_sig = None;
+    }
+    inp.tap_script_sigs = mem::take(&mut inp.tap_script_sigs)
+        .into_iter()
+        .filter(|(_, sig)| survives(sig.hash_ty))
+        .collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use bitcoin::PublicKey;
+    use elements::hashes::Hash;
+    use elements::pset::{Input, Output};
+    use elements::sighash::SigHashCache;
+    use elements::{confidential, AssetId, EcdsaSigHashType, OutPoint, Script, TxOut, Txid};
+
+    use super::*;
+    use crate::descriptor::Wpkh;
+    use crate::elementssig_to_rawsig;
+
+    const UTXO: u64 = 100_000;
+    const FEE: u64 = 100;
+
+    fn sign(
+        pset: &mut Psbt,
+        secp: &Secp256k1<secp256k1::All>,
+        index: usize,
+        sk: &secp256k1::SecretKey,
+        hash_ty: EcdsaSigHashType,
+    ) {
+        let pk = PublicKey::new(secp256k1::PublicKey::from_secret_key(secp, sk));
+        let script_code = Wpkh::new(pk).unwrap().ecdsa_sighash_script_code();
+        let tx = pset.extract_tx().unwrap();
+        let sighash = SigHashCache::new(&tx).segwitv0_sighash(
+            index,
+            &script_code,
+            confidential::Value::Explicit(UTXO),
+            hash_ty,
+        );
+        let msg = secp256k1::Message::from_slice(&sighash[..]).unwrap();
+        let sig = secp.sign_ecdsa(&msg, sk);
+        let inp = &mut pset.inputs_mut()[index];
+        inp.sighash_type = Some(hash_ty.into());
+        inp.partial_sigs
+            .insert(pk, elementssig_to_rawsig(&(sig, hash_ty)));
+    }
+
+    fn pset(secp: &Secp256k1<secp256k1::All>, sks: &[secp256k1::SecretKey]) -> Psbt {
+        let asset = AssetId::from_slice(&[7; 32]).unwrap();
+        let mut pset = Psbt::new_v2();
+        for (vout, sk) in sks.iter().enumerate() {
+            let pk = PublicKey::new(secp256k1::PublicKey::from_secret_key(secp, sk));
+            let mut inp = Input::from_prevout(OutPoint::new(Txid::all_zeros(), vout as u32));
+            inp.sequence = Some(Sequence::ENABLE_RBF_NO_LOCKTIME);
+            inp.witness_utxo = Some(TxOut {
+                asset: confidential::Asset::Explicit(asset),
+                value: confidential::Value::Explicit(UTXO),
+                nonce: confidential::Nonce::Null,
+                script_pubkey: Wpkh::new(pk).unwrap().script_pubkey(),
+                witness: Default::default(),
+            });
+            pset.add_input(inp);
+        }
+        let dest = Script::from_str("0014000102030405060708090a0b0c0d0e0f10111213").unwrap();
+        let total = UTXO * sks.len() as u64;
+        pset.add_output(Output::new_explicit(dest, total / 2, asset, None));
+        let change = Script::from_str("0014131211100f0e0d0c0b0a09080706050403020100").unwrap();
+        pset.add_output(Output::new_explicit(
+            change,
+            total - total / 2 - FEE,
+            asset,
+            None,
+        ));
+        pset.add_output(Output::new_explicit(Script::new(), FEE, asset, None));
+        pset
+    }
+
+    #[test]
+    fn resatisfy_wpkh() {
+        let secp = Secp256k1::new();
+        let sks = [
+            secp256k1::SecretKey::from_slice(&[1; 32]).unwrap(),
+            secp256k1::SecretKey::from_slice(&[2; 32]).unwrap(),
+        ];
+        let mut pset = pset(&secp, &sks);
+        sign(&mut pset, &secp, 0, &sks[0], EcdsaSigHashType::All);
+        sign(&mut pset, &secp, 1, &sks[1], EcdsaSigHashType::None);
+        let genesis = elements::BlockHash::all_zeros();
+
+        // Bad fee bumps leave the pset untouched
+        let orig = pset.clone();
+        match resatisfy(&mut pset, &secp, 2, 1000, genesis) {
+            Err(RebumpError::InvalidChange(2)) => {}
+            e => panic!("unexpected {:?}", e),
+        }
+        match resatisfy(&mut pset, &secp, 1, 100, genesis) {
+            Err(RebumpError::FeeNotIncreased { old: FEE, .. }) => {}
+            e => panic!("unexpected {:?}", e),
+        }
+        match resatisfy(&mut pset, &secp, 1, 1_000_000_000, genesis) {
+            Err(RebumpError::InsufficientChange { .. }) => {}
+            e => panic!("unexpected {:?}", e),
+        }
+        match resatisfy(&mut pset, &secp, 1, std::u64::MAX / 2, genesis) {
+            Err(RebumpError::FeerateTooHigh(_)) => {}
+            e => panic!("unexpected {:?}", e),
+        }
+        let mut final_seq = pset.clone();
+        for inp in final_seq.inputs_mut() {
+            inp.sequence = Some(Sequence::MAX);
+        }
+        match resatisfy(&mut final_seq, &secp, 1, 1000, genesis) {
+            Err(RebumpError::NotReplaceable) => {}
+            e => panic!("unexpected {:?}", e),
+        }
+        assert_eq!(pset, orig);
+
+        let res = resatisfy(&mut pset, &secp, 1, 1000, genesis).unwrap();
+        assert!(res.fee > FEE);
+        assert_eq!(res.finalized, vec![1]);
+        assert_eq!(res.resign, vec![0]);
+        assert_eq!(pset.outputs()[2].amount, Some(res.fee));
+        assert_eq!(pset.outputs()[1].amount, Some(UTXO - res.fee));
+        assert!(pset.inputs()[0].partial_sigs.is_empty());
+        assert!(pset.inputs()[1].final_script_witness.is_some());
+
+        // Sign again and check that the estimate was not too low
+        sign(&mut pset, &secp, 0, &sks[0], EcdsaSigHashType::All);
+        pset.finalize_inp_mut(&secp, 0, genesis).unwrap();
+        let tx = pset.extract_tx().unwrap();
+        assert!((tx.weight() as u64 + 3) / 4 <= res.fee);
+
+        match resatisfy(&mut pset, &secp, 1, 2000, genesis) {
+            Err(RebumpError::FinalizedInput(0)) => {}
+            e => panic!("unexpected {:?}", e),
+        }
+    }
+}
@@ -0,0 +1,313 @@
+// Miniscript
+// Written in 2023 by
+//     The Elements Miniscript developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Witness Element Sizes
+//!
+//! Check finalized inputs against the limits on the size of their initial
+//! stack elements before broadcasting. Segwit v0 inputs with elements
+//! larger than 80 bytes are valid but not relayed, and elements larger than
+//! 520 bytes are invalid for every kind of script, which covenant
+//! satisfactions pushing introspected data can easily hit.
+//!
+
+use std::fmt;
+
+use elements::script::Instruction;
+use elements::Script;
+
+use super::finalizer::{get_scriptpubkey, is_finalized};
+use super::{Error, Psbt};
+use crate::miniscript::limits::{
+    MAX_SCRIPT_ELEMENT_SIZE, MAX_STANDARD_P2WSH_SCRIPT_SIZE, MAX_STANDARD_P2WSH_STACK_ITEM_SIZE,
+};
+use crate::util;
+
+/// The first byte of a taproot annex
+const TAPROOT_ANNEX_PREFIX: u8 = 0x50;
+
+/// The kind of rule a limit comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SizeRule {
+    /// The transaction is invalid
+    Consensus,
+    /// The transaction is valid, but nodes do not relay it
+    Standardness,
+}
+
+/// The way an input is spent, as far as its witness tells
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum WitnessSpendPath {
+    /// The elements are the pushes of the script sig
+    Legacy,
+    /// A segwit v0 spend of the witness script, if any
+    SegwitV0(Option<Script>),
+    /// A taproot key spend
+    TapKey,
+    /// A taproot script spend of the leaf script
+    TapScript(Script),
+}
+
+/// An initial stack element over a size limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct OversizedElement {
+    /// Position of the element in the witness, or in the pushes of the
+    /// script sig for legacy spends
+    pub position: usize,
+    /// Size of the element
+    pub size: usize,
+    /// The limit the element exceeds
+    pub limit: usize,
+    /// Whether the limit is a consensus or a standardness rule
+    pub rule: SizeRule,
+}
+
+/// The oversized elements of an input, see [`check_witness_sizes`]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct OversizedWitness {
+    /// The spend path of the input
+    pub path: WitnessSpendPath,
+    /// The elements over a limit, with the consensus limit reported over
+    /// the standardness one
+    pub elements: Vec<OversizedElement>,
+}
+
+impl fmt::Display for OversizedWitness {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.path {
+            WitnessSpendPath::Legacy => f.write_str("legacy spend")?,
+            WitnessSpendPath::SegwitV0(Some(ref script)) => {
+                write!(f, "segwit v0 spend of {}", script)?
+            }
+            WitnessSpendPath::SegwitV0(None) => f.write_str("segwit v0 spend")?,
+            WitnessSpendPath::TapKey => f.write_str("taproot key spend")?,
+            WitnessSpendPath::TapScript(ref script) => write!(f, "taproot spend of {}", script)?,
+        }
+        for elem in &self.elements {
+            write!(
+                f,
+                "; element {} has {} bytes, over the {:?} limit of {}",
+                elem.position, elem.size, elem.rule, elem.limit
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn check_elements<'a, I>(elements: I, standard_limit: Option<usize>) -> Vec<OversizedElement>
+where
+    I: IntoIterator<Item = &'a [u8]>,
+{
+    elements
+        .into_iter()
+        .enumerate()
+        .filter_map(|(position, elem)| {
+            let size = elem.len();
+            let (limit, rule) = if size > MAX_SCRIPT_ELEMENT_SIZE {
+                (MAX_SCRIPT_ELEMENT_SIZE, SizeRule::Consensus)
+            } else {
+                match standard_limit {
+                    Some(limit) if size > limit => (limit, SizeRule::Standardness),
+                    _ => return None,
+                }
+            };
+            Some(OversizedElement {
+                position,
+                size,
+                limit,
+                rule,
+            })
+        })
+        .collect()
+}
+
+/// Check the initial stack elements of an input spending `script_pubkey`
+/// with `script_sig` and `witness`.
+///
+/// Segwit v0 witness items other than the witness script must not exceed
+/// 80 bytes to be standard, and the witness script 3600 bytes; all stack
+/// elements of any spend must not exceed 520 bytes. Witness scripts and
+/// taproot leaf scripts are not stack elements, so only the standardness
+/// limit of witness scripts applies to them.
+pub fn check_witness_sizes(
+    script_pubkey: &Script,
+    script_sig: &Script,
+    witness: &[Vec<u8>],
+) -> Result<(), OversizedWitness> {
+    let redeem_script;
+    let spk = if script_pubkey.is_p2sh() && !witness.is_empty() {
+        // The script sig of a nested segwit spend pushes the redeem script
+        match script_sig.instructions().next() {
+            Some(Ok(Instruction::PushBytes(bytes))) => {
+                redeem_script = Script::from(bytes.to_vec());
+                &redeem_script
+            }
+            _ => script_pubkey,
+        }
+    } else {
+        script_pubkey
+    };
+
+    let (path, elements) = if util::is_v1_p2tr(spk) {
+        let mut stack = witness;
+        if stack.len() >= 2 && stack[stack.len() - 1].first() == Some(&TAPROOT_ANNEX_PREFIX) {
+            stack = &stack[..stack.len() - 1];
+        }
+        if stack.len() <= 1 {
+            (
+                WitnessSpendPath::TapKey,
+                check_elements(stack.iter().map(Vec::as_slice), None),
+            )
+        } else {
+            let leaf = Script::from(stack[stack.len() - 2].clone());
+            let items = &stack[..stack.len() - 2];
+            (
+                WitnessSpendPath::TapScript(leaf),
+                check_elements(items.iter().map(Vec::as_slice), None),
+            )
+        }
+    } else if spk.is_v0_p2wsh() {
+        let (script, items) = match witness.split_last() {
+            Some((script, items)) => (Script::from(script.clone()), items),
+            None => (Script::new(), witness),
+        };
+        let mut elements = check_elements(
+            items.iter().map(Vec::as_slice),
+            Some(MAX_STANDARD_P2WSH_STACK_ITEM_SIZE),
+        );
+        if script.len() > MAX_STANDARD_P2WSH_SCRIPT_SIZE {
+            elements.push(OversizedElement {
+                position: items.len(),
+                size: script.len(),
+                limit: MAX_STANDARD_P2WSH_SCRIPT_SIZE,
+                rule: SizeRule::Standardness,
+            });
+        }
+        (WitnessSpendPath::SegwitV0(Some(script)), elements)
+    } else if spk.is_v0_p2wpkh() {
+        (
+            WitnessSpendPath::SegwitV0(None),
+            check_elements(
+                witness.iter().map(Vec::as_slice),
+                Some(MAX_STANDARD_P2WSH_STACK_ITEM_SIZE),
+            ),
+        )
+    } else {
+        let pushes = script_sig.instructions().filter_map(|ins| match ins {
+            Ok(Instruction::PushBytes(bytes)) => Some(bytes),
+            _ => None,
+        });
+        (WitnessSpendPath::Legacy, check_elements(pushes, None))
+    };
+
+    if elements.is_empty() {
+        Ok(())
+    } else {
+        Err(OversizedWitness { path, elements })
+    }
+}
+
+/// Check the finalized inputs of `psbt` with [`check_witness_sizes`], and
+/// return the index and oversized elements of every failing input. Inputs
+/// which are not finalized are skipped.
+///
+/// # Errors
+/// When a finalized input has no utxo.
+pub fn check_psbt_witness_sizes(psbt: &Psbt) -> Result<Vec<(usize, OversizedWitness)>, Error> {
+    let mut failures = vec![];
+    for (index, input) in psbt.inputs().iter().enumerate() {
+        if !is_finalized(input) {
+            continue;
+        }
+        let spk = get_scriptpubkey(psbt, index).map_err(|e| Error::InputError(e, index))?;
+        let script_sig = input.final_script_sig.clone().unwrap_or_default();
+        let witness = input.final_script_witness.as_deref().unwrap_or(&[]);
+        if let Err(oversized) = check_witness_sizes(spk, &script_sig, witness) {
+            failures.push((index, oversized));
+        }
+    }
+    Ok(failures)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Descriptor, DescriptorPublicKey};
+    use std::str::FromStr;
+
+    #[test]
+    fn witness_sizes() {
+        let desc = Descriptor::<DescriptorPublicKey>::from_str(
+            "elwsh(and_v(v:pk(020000000000000000000000000000000000000000000000000000000000000002),older(1)))",
+        )
+        .unwrap()
+        .at_derivation_index(0).unwrap();
+        let spk = desc.script_pubkey();
+        let script = desc.explicit_script().unwrap().into_bytes();
+
+        let small = vec![vec![1; 72], script.clone()];
+        assert_eq!(check_witness_sizes(&spk, &Script::new(), &small), Ok(()));
+
+        let big = vec![vec![1; 81], vec![2; 521], script.clone()];
+        let oversized = check_witness_sizes(&spk, &Script::new(), &big).unwrap_err();
+        assert_eq!(
+            oversized.path,
+            WitnessSpendPath::SegwitV0(Some(Script::from(script)))
+        );
+        assert_eq!(
+            oversized.elements,
+            vec![
+                OversizedElement {
+                    position: 0,
+                    size: 81,
+                    limit: 80,
+                    rule: SizeRule::Standardness,
+                },
+                OversizedElement {
+                    position: 1,
+                    size: 521,
+                    limit: 520,
+                    rule: SizeRule::Consensus,
+                },
+            ]
+        );
+
+        // Taproot script spends have no standardness limit on elements
+        let tr = Descriptor::<DescriptorPublicKey>::from_str(
+            "eltr(020000000000000000000000000000000000000000000000000000000000000002,pk(020000000000000000000000000000000000000000000000000000000000000002))",
+        )
+        .unwrap()
+        .at_derivation_index(0).unwrap();
+        let spk = tr.script_pubkey();
+        let leaf = vec![0x51];
+        let control = vec![0xc4; 33];
+        let wit = vec![vec![1; 200], leaf.clone(), control.clone()];
+        assert_eq!(check_witness_sizes(&spk, &Script::new(), &wit), Ok(()));
+        let wit = vec![
+            vec![1; 600],
+            leaf.clone(),
+            control,
+            vec![TAPROOT_ANNEX_PREFIX],
+        ];
+        let oversized = check_witness_sizes(&spk, &Script::new(), &wit).unwrap_err();
+        assert_eq!(
+            oversized.path,
+            WitnessSpendPath::TapScript(Script::from(leaf))
+        );
+        assert_eq!(oversized.elements[0].rule, SizeRule::Consensus);
+        assert_eq!(
+            check_witness_sizes(&spk, &Script::new(), &[vec![1; 64]]),
+            Ok(())
+        );
+    }
+}
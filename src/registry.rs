@@ -0,0 +1,394 @@
+// Miniscript
+// Written in 2023 by
+//     The Elements Miniscript developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Descriptor Registry
+//!
+//! A thread-safe registry of parsed descriptors indexed by their checksum,
+//! for servers handling requests about a fixed set of descriptors from many
+//! threads. Descriptors are parsed once, and the descriptors derived at
+//! the most recent indices are cached, up to a capacity.
+//!
+//! A registry is shared by wrapping it in an [`Arc`]. Only available with
+//! the `registry` feature.
+//!
+
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{Arc, RwLock};
+
+use elements::secp256k1_zkp::{Secp256k1, Verification};
+
+use crate::descriptor::ConversionError;
+use crate::extensions::ParseableExt;
+use crate::{Descriptor, DescriptorPublicKey, Error, Extension, NoExt};
+
+/// The default number of derived descriptors cached for each registered
+/// descriptor, see [`Registry::with_cache_capacity`]
+pub const DEFAULT_CACHE_CAPACITY: usize = 1024;
+
+/// Derived descriptors by index, evicting the oldest one when full
+#[derive(Debug)]
+struct DerivedCache<Ext: Extension> {
+    capacity: usize,
+    map: BTreeMap<u32, Arc<Descriptor<bitcoin::PublicKey, Ext>>>,
+    order: VecDeque<u32>,
+}
+
+impl<Ext: Extension> DerivedCache<Ext> {
+    fn new(capacity: usize) -> Self {
+        DerivedCache {
+            capacity,
+            map: BTreeMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    fn insert(
+        &mut self,
+        index: u32,
+        derived: Arc<Descriptor<bitcoin::PublicKey, Ext>>,
+    ) -> Arc<Descriptor<bitcoin::PublicKey, Ext>> {
+        // Another thread may have derived it meanwhile, keep the first one
+        if let Some(cached) = self.map.get(&index) {
+            return Arc::clone(cached);
+        }
+        if self.capacity == 0 {
+            return derived;
+        }
+        if self.map.len() == self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.map.remove(&oldest);
+            }
+        }
+        self.order.push_back(index);
+        self.map.insert(index, Arc::clone(&derived));
+        derived
+    }
+
+    fn clear(&mut self) {
+        self.map.clear();
+        self.order.clear();
+    }
+}
+
+/// A descriptor of a [`Registry`] with its derivation cache
+#[derive(Debug)]
+pub struct RegisteredDescriptor<Ext: Extension = NoExt> {
+    descriptor: Descriptor<DescriptorPublicKey, Ext>,
+    string: String,
+    derived: RwLock<DerivedCache<Ext>>,
+}
+
+impl<Ext: Extension + ParseableExt> RegisteredDescriptor<Ext> {
+    fn new(descriptor: Descriptor<DescriptorPublicKey, Ext>, cache_capacity: usize) -> Self {
+        RegisteredDescriptor {
+            string: descriptor.to_string(),
+            descriptor,
+            derived: RwLock::new(DerivedCache::new(cache_capacity)),
+        }
+    }
+
+    /// The parsed descriptor
+    pub fn descriptor(&self) -> &Descriptor<DescriptorPublicKey, Ext> {
+        &self.descriptor
+    }
+
+    /// The string representation of the descriptor, with its checksum
+    pub fn as_str(&self) -> &str {
+        &self.string
+    }
+
+    /// The checksum of the descriptor, which identifies it in the registry
+    pub fn checksum(&self) -> &str {
+        let pos = self
+            .string
+            .rfind('#')
+            .expect("descriptors display a checksum");
+        &self.string[pos + 1..]
+    }
+
+    /// The descriptor at the derivation `index`, from the cache if it was
+    /// derived recently
+    ///
+    /// # Errors
+    ///
+    /// As for [`Descriptor::derived_descriptor`]. Errors are not cached.
+    pub fn derive<C: Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        index: u32,
+    ) -> Result<Arc<Descriptor<bitcoin::PublicKey, Ext>>, ConversionError> {
+        if let Some(derived) = self
+            .derived
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .map
+            .get(&index)
+        {
+            return Ok(Arc::clone(derived));
+        }
+        let derived = Arc::new(self.descriptor.derived_descriptor(secp, index)?);
+        Ok(self
+            .derived
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(index, derived))
+    }
+
+    /// The number of cached derived descriptors
+    pub fn cached_derivations(&self) -> usize {
+        self.derived
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .map
+            .len()
+    }
+
+    /// Drop the cached derived descriptors
+    pub fn clear_cache(&self) {
+        self.derived
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
+    }
+}
+
+/// A thread-safe map from descriptor checksums to parsed descriptors
+#[derive(Debug)]
+pub struct Registry<Ext: Extension = NoExt> {
+    descriptors: RwLock<BTreeMap<String, Arc<RegisteredDescriptor<Ext>>>>,
+    cache_capacity: usize,
+}
+
+impl<Ext: Extension + ParseableExt> Default for Registry<Ext> {
+    fn default() -> Self {
+        Registry::with_cache_capacity(DEFAULT_CACHE_CAPACITY)
+    }
+}
+
+impl<Ext: Extension + ParseableExt> Registry<Ext> {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Registry::default()
+    }
+
+    /// Create an empty registry caching at most `capacity` derived
+    /// descriptors for each registered descriptor. Zero disables caching.
+    pub fn with_cache_capacity(capacity: usize) -> Self {
+        Registry {
+            descriptors: RwLock::new(BTreeMap::new()),
+            cache_capacity: capacity,
+        }
+    }
+
+    /// Add `descriptor` to the registry and return its entry. If the same
+    /// descriptor was already registered, its entry is returned instead,
+    /// keeping its cache.
+    ///
+    /// # Errors
+    ///
+    /// If a different descriptor with the same checksum is registered.
+    pub fn insert(
+        &self,
+        descriptor: Descriptor<DescriptorPublicKey, Ext>,
+    ) -> Result<Arc<RegisteredDescriptor<Ext>>, Error> {
+        let entry = RegisteredDescriptor::new(descriptor, self.cache_capacity);
+        let mut descriptors = self.descriptors.write().unwrap_or_else(|e| e.into_inner());
+        if let Some(registered) = descriptors.get(entry.checksum()) {
+            if registered.as_str() != entry.as_str() {
+                return Err(Error::BadDescriptor(format!(
+                    "checksum of {} collides with registered descriptor {}",
+                    entry.as_str(),
+                    registered.as_str()
+                )));
+            }
+            return Ok(Arc::clone(registered));
+        }
+        let entry = Arc::new(entry);
+        descriptors.insert(entry.checksum().to_owned(), Arc::clone(&entry));
+        Ok(entry)
+    }
+
+    /// Look up the descriptor `s`, and parse and register it if it is not
+    /// yet in the registry. Strings in the form displayed by descriptors,
+    /// e.g. as returned by [`RegisteredDescriptor::as_str`], are found
+    /// without parsing them.
+    ///
+    /// # Errors
+    ///
+    /// If `s` is not registered and not a valid descriptor, or if its
+    /// checksum collides with a registered descriptor.
+    pub fn get_or_parse(&self, s: &str) -> Result<Arc<RegisteredDescriptor<Ext>>, Error> {
+        if let Some(pos) = s.rfind('#') {
+            if let Some(entry) = self.get(&s[pos + 1..]) {
+                if entry.as_str() == s {
+                    return Ok(entry);
+                }
+            }
+        }
+        self.insert(s.parse()?)
+    }
+
+    /// The registered descriptor with checksum `checksum`
+    pub fn get(&self, checksum: &str) -> Option<Arc<RegisteredDescriptor<Ext>>> {
+        self.descriptors
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(checksum)
+            .cloned()
+    }
+
+    /// Remove the descriptor with checksum `checksum` and return it.
+    /// Entries already handed out remain usable.
+    pub fn remove(&self, checksum: &str) -> Option<Arc<RegisteredDescriptor<Ext>>> {
+        self.descriptors
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(checksum)
+    }
+
+    /// The checksums of all registered descriptors, in lexicographic order
+    pub fn checksums(&self) -> Vec<String> {
+        self.descriptors
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .keys()
+            .cloned()
+            .collect()
+    }
+
+    /// The number of registered descriptors
+    pub fn len(&self) -> usize {
+        self.descriptors
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .len()
+    }
+
+    /// Whether no descriptor is registered
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+    use std::thread;
+
+    use super::*;
+
+    const DESC: &str = "elwpkh([73c5da0a/84'/0'/0']xpub6BgBgsespWvERF3LHQu6CnqdvfEvtMcQjYrcRzx53QJjSxarj2afYWcLteoGVky7D3UKDP9QyrLprQ3VCECoY49yfdDEHGCtMMj92pReUsQ/0/*)";
+
+    #[test]
+    fn registry_lookup() {
+        let registry = Registry::<NoExt>::new();
+        assert!(registry.is_empty());
+        let entry = registry.get_or_parse(DESC).unwrap();
+        assert_eq!(registry.checksums(), vec![entry.checksum().to_owned()]);
+        assert!(Arc::ptr_eq(
+            &registry.get(entry.checksum()).unwrap(),
+            &entry
+        ));
+        assert!(Arc::ptr_eq(
+            &registry.get_or_parse(entry.as_str()).unwrap(),
+            &entry
+        ));
+        assert!(Arc::ptr_eq(
+            &registry.insert(entry.descriptor().clone()).unwrap(),
+            &entry
+        ));
+        assert_eq!(registry.len(), 1);
+        assert!(registry.get_or_parse("elwpkh(A)").is_err());
+
+        assert!(registry.remove(entry.checksum()).is_some());
+        assert!(registry.get(entry.checksum()).is_none());
+
+        // A different descriptor under the same checksum is rejected
+        let other = Descriptor::<DescriptorPublicKey, NoExt>::from_str(
+            "elwpkh(02d7924d4f7d43ea965a465ae3095ff41131e5946f3c85f79e44adbcf8e27e080e)",
+        )
+        .unwrap();
+        let checksum = entry.checksum().to_owned();
+        registry.descriptors.write().unwrap().insert(
+            checksum.clone(),
+            Arc::new(RegisteredDescriptor::new(other, DEFAULT_CACHE_CAPACITY)),
+        );
+        assert!(registry.insert(entry.descriptor().clone()).is_err());
+        assert!(registry.get_or_parse(DESC).is_err());
+        assert!(registry.get(&checksum).unwrap().as_str() != entry.as_str());
+    }
+
+    #[test]
+    fn registry_cache_capacity() {
+        let secp = Secp256k1::verification_only();
+        let registry = Registry::<NoExt>::with_cache_capacity(3);
+        let entry = registry.get_or_parse(DESC).unwrap();
+        let first = entry.derive(&secp, 0).unwrap();
+        for i in 0..10 {
+            entry.derive(&secp, i).unwrap();
+        }
+        assert_eq!(entry.cached_derivations(), 3);
+        // The oldest derivations were evicted
+        assert!(!Arc::ptr_eq(&entry.derive(&secp, 0).unwrap(), &first));
+        assert_eq!(*entry.derive(&secp, 0).unwrap(), *first);
+
+        let registry = Registry::<NoExt>::with_cache_capacity(0);
+        let entry = registry.get_or_parse(DESC).unwrap();
+        entry.derive(&secp, 0).unwrap();
+        assert_eq!(entry.cached_derivations(), 0);
+    }
+
+    #[test]
+    fn registry_derivation_cache() {
+        let registry = Arc::new(Registry::<NoExt>::new());
+        let checksum = registry.get_or_parse(DESC).unwrap().checksum().to_owned();
+
+        let threads = (0..4)
+            .map(|_| {
+                let registry = Arc::clone(&registry);
+                let checksum = checksum.clone();
+                thread::spawn(move || {
+                    let secp = Secp256k1::verification_only();
+                    let entry = registry.get(&checksum).unwrap();
+                    (0..10)
+                        .map(|i| entry.derive(&secp, i).unwrap().script_pubkey())
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect::<Vec<_>>();
+        let spks = threads
+            .into_iter()
+            .map(|t| t.join().unwrap())
+            .collect::<Vec<_>>();
+        assert!(spks.iter().all(|s| *s == spks[0]));
+
+        let secp = Secp256k1::verification_only();
+        let entry = registry.get(&checksum).unwrap();
+        assert_eq!(entry.cached_derivations(), 10);
+        assert!(Arc::ptr_eq(
+            &entry.derive(&secp, 3).unwrap(),
+            &entry.derive(&secp, 3).unwrap()
+        ));
+        assert_eq!(
+            entry.derive(&secp, 3).unwrap().script_pubkey(),
+            entry
+                .descriptor()
+                .derived_descriptor(&secp, 3)
+                .unwrap()
+                .script_pubkey()
+        );
+        entry.clear_cache();
+        assert_eq!(entry.cached_derivations(), 0);
+    }
+}
@@ -0,0 +1,122 @@
+// Miniscript
+// Written in 2023 by
+//     The Elements Miniscript developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Transaction Scanning
+//!
+//! Utilities to find spends of our descriptors in transactions, e.g. the
+//! transactions of a newly received block.
+//!
+
+use elements::Transaction;
+
+use crate::extensions::ParseableExt;
+use crate::interpreter::HashLockType;
+use crate::miniscript::satisfy::Preimage32;
+use crate::{Descriptor, ToPublicKey};
+
+/// Returns all hashlocks of `descriptors` satisfied by inputs of `txs`,
+/// along with the revealed preimages, in the order they appear.
+///
+/// Since the spent outputs are not known, each input is decoded against
+/// every descriptor with [`Descriptor::parse_witness`], and inputs spending
+/// none of them are skipped.
+pub fn extract_preimages<Pk, Ext>(
+    txs: &[Transaction],
+    descriptors: &[Descriptor<Pk, Ext>],
+) -> Vec<(HashLockType, Preimage32)>
+where
+    Pk: ToPublicKey,
+    Ext: ParseableExt,
+{
+    let mut preimages = vec![];
+    for txin in txs.iter().flat_map(|tx| tx.input.iter()) {
+        let parsed = descriptors
+            .iter()
+            .filter_map(|desc| {
+                desc.parse_witness(&txin.witness.script_witness, &txin.script_sig)
+                    .ok()
+            })
+            .next();
+        if let Some(parsed) = parsed {
+            preimages.extend(parsed.preimages);
+        }
+    }
+    preimages
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::str::FromStr;
+
+    use bitcoin::PublicKey;
+    use elements::hashes::{sha256, Hash};
+    use elements::secp256k1_zkp;
+
+    use super::*;
+    use crate::{NoExt, Satisfier};
+
+    struct PreimageSat([u8; 32]);
+
+    impl Satisfier<PublicKey> for PreimageSat {
+        fn lookup_sha256(&self, _: &sha256::Hash) -> Option<Preimage32> {
+            Some(self.0)
+        }
+    }
+
+    #[test]
+    fn extract_preimages_from_spends() {
+        let pk = PublicKey::from_str(
+            "020202020202020202020202020202020202020202020202020202020202020202",
+        )
+        .unwrap();
+        let sig = secp256k1_zkp::ecdsa::Signature::from_compact(&[1; 64]).unwrap();
+        let mut sigs = HashMap::new();
+        sigs.insert(pk, (sig, elements::EcdsaSigHashType::All));
+
+        let preimages = [[1; 32], [2; 32]];
+        let descs: Vec<Descriptor<PublicKey, NoExt>> = preimages
+            .iter()
+            .map(|p| {
+                let hash = sha256::Hash::hash(p);
+                Descriptor::from_str(&format!("elwsh(and_v(v:pk({}),sha256({})))", pk, hash))
+                    .unwrap()
+            })
+            .collect();
+
+        let mut tx = Transaction {
+            version: 2,
+            lock_time: elements::PackedLockTime::ZERO,
+            input: vec![],
+            output: vec![],
+        };
+        for (desc, preimage) in descs.iter().zip(preimages.iter()).rev() {
+            let mut txin = elements::TxIn::default();
+            desc.satisfy(&mut txin, (&sigs, PreimageSat(*preimage)))
+                .unwrap();
+            tx.input.push(txin);
+        }
+        // An input spending something else
+        tx.input.push(elements::TxIn::default());
+
+        assert_eq!(
+            extract_preimages(&[tx.clone()], &descs),
+            vec![
+                (HashLockType::Sha256(sha256::Hash::hash(&[2; 32])), [2; 32]),
+                (HashLockType::Sha256(sha256::Hash::hash(&[1; 32])), [1; 32]),
+            ]
+        );
+        assert!(extract_preimages(&[tx], &descs[..0]).is_empty());
+    }
+}
@@ -0,0 +1,285 @@
+// Miniscript
+// Written in 2023 by
+//     The Elements Miniscript developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Test Vectors
+//!
+//! Deterministic test vectors for all descriptor types, so that other
+//! implementations can be tested against this library. Only available with
+//! the `gen-vectors` feature, see the `gen_vectors` example for writing
+//! them to stdout.
+//!
+//! All keys are derived from fixed secret keys. Signatures in the sample
+//! satisfactions sign [`SIGNED_MSG`] instead of a transaction sighash, so
+//! that the vectors check witness construction independently of sighash
+//! computation. Timelocks are considered satisfied.
+//!
+
+use std::str::FromStr;
+
+use elements::hashes::hex::ToHex;
+use elements::hashes::{sha256, Hash};
+use elements::secp256k1_zkp::{self, All, KeyPair, Message, Secp256k1, SecretKey};
+use elements::taproot::TapLeafHash;
+use elements::{AddressParams, LockTime, Script, Sequence};
+use serde_json::{json, Value};
+
+use crate::extensions::{CovExtArgs, CovenantExt};
+use crate::miniscript::satisfy::{ElementsSig, Preimage32};
+use crate::{Descriptor, Error, Satisfier, ToPublicKey};
+
+/// Message signed by all signatures in the sample satisfactions
+pub const SIGNED_MSG: [u8; 32] = [0x42; 32];
+
+/// Preimage of all hashlocks in the descriptor templates
+pub const PREIMAGE: Preimage32 = [0x11; 32];
+
+/// Number of keys available to the descriptor templates
+pub const N_KEYS: usize = 4;
+
+/// Descriptor templates, in which `@i` stands for the `i`th key and `@H`
+/// for the sha256 hash of [`PREIMAGE`]
+pub const DESCRIPTORS: &[&str] = &[
+    "elpk(@0)",
+    "elpkh(@0)",
+    "elmulti(1,@0,@1)",
+    "elwpkh(@0)",
+    "elsh(wpkh(@0))",
+    "elsh(multi(2,@0,@1))",
+    "elsh(sortedmulti(2,@1,@0))",
+    "elwsh(multi(2,@0,@1,@2))",
+    "elwsh(sortedmulti(2,@2,@1,@0))",
+    "elwsh(and_v(v:pk(@0),sha256(@H)))",
+    "elwsh(or_d(pk(@0),and_v(v:pk(@1),older(144))))",
+    "elsh(wsh(and_v(v:pk(@0),after(500000))))",
+    "elsh(wsh(thresh(2,pk(@0),s:pk(@1),s:pk(@2))))",
+    "eltr(@0)",
+    "eltr(@0,pk(@1))",
+    "eltr(@0,{pk(@1),and_v(v:pk(@2),sha256(@H))})",
+    "eltr(@3,multi_a(2,@0,@1,@2))",
+    "elcovwsh(@0,pk(@1))",
+];
+
+/// Networks for which addresses are generated, by name
+const NETWORKS: &[(&str, &AddressParams)] = &[
+    ("liquid", &AddressParams::LIQUID),
+    ("liquidtestnet", &AddressParams::LIQUID_TESTNET),
+    ("elements", &AddressParams::ELEMENTS),
+];
+
+type VectorDescriptor = Descriptor<bitcoin::PublicKey, CovenantExt<CovExtArgs>>;
+
+/// The `i`th fixed secret key
+pub fn secret_key(i: usize) -> SecretKey {
+    SecretKey::from_slice(&[i as u8 + 1; 32]).expect("valid key")
+}
+
+/// The fixed secret blinding key
+pub fn blinding_key() -> SecretKey {
+    SecretKey::from_slice(&[0xbb; 32]).expect("valid key")
+}
+
+/// Instantiate a template from [`DESCRIPTORS`] with the fixed keys
+pub fn instantiate(template: &str) -> String {
+    let secp = Secp256k1::signing_only();
+    let mut s = template.replace("@H", &sha256::Hash::hash(&PREIMAGE).to_hex());
+    for i in 0..N_KEYS {
+        let pk = secp256k1_zkp::PublicKey::from_secret_key(&secp, &secret_key(i));
+        s = s.replace(&format!("@{}", i), &pk.to_string());
+    }
+    s
+}
+
+/// Generate the test vectors for all of [`DESCRIPTORS`]
+pub fn generate() -> Result<Value, Error> {
+    let secp = Secp256k1::new();
+    let keys: Vec<Value> = (0..N_KEYS)
+        .map(|i| key_json(&secp, &secret_key(i)))
+        .collect();
+    let vectors = DESCRIPTORS
+        .iter()
+        .map(|t| descriptor_vector(&secp, &instantiate(t)))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(json!({
+        "keys": keys,
+        "blinding_key": key_json(&secp, &blinding_key()),
+        "signed_msg": SIGNED_MSG.to_hex(),
+        "preimage": PREIMAGE.to_hex(),
+        "vectors": vectors,
+    }))
+}
+
+fn key_json(secp: &Secp256k1<All>, sk: &SecretKey) -> Value {
+    json!({
+        "secret": sk.secret_bytes().to_hex(),
+        "public": secp256k1_zkp::PublicKey::from_secret_key(secp, sk).to_string(),
+    })
+}
+
+fn script_json(script: Result<Script, Error>) -> Value {
+    script.map_or(Value::Null, |s| Value::from(s.to_hex()))
+}
+
+fn descriptor_vector(secp: &Secp256k1<All>, s: &str) -> Result<Value, Error> {
+    let desc = VectorDescriptor::from_str(s)?;
+    let blinder = secp256k1_zkp::PublicKey::from_secret_key(secp, &blinding_key());
+
+    let mut addresses = serde_json::Map::new();
+    for (name, params) in NETWORKS {
+        if let Ok(unconf) = desc.address(params) {
+            addresses.insert(
+                name.to_string(),
+                json!({
+                    "unconfidential": unconf.to_string(),
+                    "confidential": desc.blinded_address(blinder, params)?.to_string(),
+                }),
+            );
+        }
+    }
+    let addresses = if addresses.is_empty() {
+        Value::Null
+    } else {
+        Value::Object(addresses)
+    };
+
+    let satisfier = VectorSatisfier::new(secp, &desc);
+    let satisfaction =
+        desc.get_satisfaction(&satisfier)
+            .map_or(Value::Null, |(witness, script_sig)| {
+                let witness: Vec<String> = witness.iter().map(|w| w.to_hex()).collect();
+                json!({
+                    "witness": witness,
+                    "script_sig": script_sig.to_hex(),
+                })
+            });
+
+    Ok(json!({
+        "descriptor": desc.to_string(),
+        "script_pubkey": desc.script_pubkey().to_hex(),
+        "explicit_script": script_json(desc.explicit_script()),
+        "script_code": script_json(desc.script_code()),
+        "addresses": addresses,
+        "max_satisfaction_weight": desc.max_satisfaction_weight().ok(),
+        "satisfaction": satisfaction,
+    }))
+}
+
+/// Satisfier signing [`SIGNED_MSG`] with the fixed keys, knowing
+/// [`PREIMAGE`] and treating all timelocks as satisfied
+struct VectorSatisfier<'a> {
+    secp: &'a Secp256k1<All>,
+    keys: Vec<KeyPair>,
+    tap_key_spend_sig: Option<elements::SchnorrSig>,
+}
+
+impl<'a> VectorSatisfier<'a> {
+    fn new(secp: &'a Secp256k1<All>, desc: &VectorDescriptor) -> Self {
+        let keys: Vec<KeyPair> = (0..N_KEYS)
+            .map(|i| KeyPair::from_secret_key(secp, &secret_key(i)))
+            .collect();
+        let mut sat = VectorSatisfier {
+            secp,
+            keys,
+            tap_key_spend_sig: None,
+        };
+        if let Descriptor::Tr(ref tr) = *desc {
+            let internal = tr.internal_key().to_x_only_pubkey();
+            if let Some(keypair) = sat.keypair(&internal) {
+                let tweak =
+                    secp256k1_zkp::Scalar::from_be_bytes(tr.spend_info().tap_tweak().into_inner())
+                        .expect("valid tweak");
+                let tweaked = keypair.add_xonly_tweak(secp, &tweak).expect("valid tweak");
+                sat.tap_key_spend_sig = Some(sat.schnorr_sig(&tweaked));
+            }
+        }
+        sat
+    }
+
+    fn keypair(&self, pk: &bitcoin::XOnlyPublicKey) -> Option<KeyPair> {
+        self.keys
+            .iter()
+            .find(|k| k.x_only_public_key().0 == *pk)
+            .copied()
+    }
+
+    fn schnorr_sig(&self, keypair: &KeyPair) -> elements::SchnorrSig {
+        let msg = Message::from_slice(&SIGNED_MSG).expect("32 bytes");
+        elements::SchnorrSig {
+            sig: self.secp.sign_schnorr_no_aux_rand(&msg, keypair),
+            hash_ty: elements::SchnorrSigHashType::Default,
+        }
+    }
+}
+
+impl<'a> Satisfier<bitcoin::PublicKey> for VectorSatisfier<'a> {
+    fn lookup_ecdsa_sig(&self, pk: &bitcoin::PublicKey) -> Option<ElementsSig> {
+        let keypair = self.keys.iter().find(|k| k.public_key() == pk.inner)?;
+        let msg = Message::from_slice(&SIGNED_MSG).expect("32 bytes");
+        let sig = self.secp.sign_ecdsa(&msg, &keypair.secret_key());
+        Some((sig, elements::EcdsaSigHashType::All))
+    }
+
+    fn lookup_tap_key_spend_sig(&self) -> Option<elements::SchnorrSig> {
+        self.tap_key_spend_sig
+    }
+
+    fn lookup_tap_leaf_script_sig(
+        &self,
+        pk: &bitcoin::PublicKey,
+        _: &TapLeafHash,
+    ) -> Option<elements::SchnorrSig> {
+        let keypair = self.keypair(&pk.to_x_only_pubkey())?;
+        Some(self.schnorr_sig(&keypair))
+    }
+
+    fn check_older(&self, _: Sequence) -> bool {
+        true
+    }
+
+    fn check_after(&self, _: LockTime) -> bool {
+        true
+    }
+
+    fn lookup_sha256(&self, hash: &sha256::Hash) -> Option<Preimage32> {
+        if *hash == sha256::Hash::hash(&PREIMAGE) {
+            Some(PREIMAGE)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vectors_deterministic() {
+        let vectors = generate().unwrap();
+        assert_eq!(vectors, generate().unwrap());
+        for (template, vector) in DESCRIPTORS
+            .iter()
+            .zip(vectors["vectors"].as_array().unwrap())
+        {
+            let desc = VectorDescriptor::from_str(&instantiate(template)).unwrap();
+            assert_eq!(vector["descriptor"], desc.to_string());
+            // Only the covenant descriptor needs a transaction to be satisfied
+            assert_eq!(
+                vector["satisfaction"].is_null(),
+                template.starts_with("elcovwsh"),
+                "{}",
+                template
+            );
+        }
+    }
+}
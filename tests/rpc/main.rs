@@ -0,0 +1,225 @@
+//! # Differential tests against Elements Core
+//!
+//! Derives addresses and spends from descriptors with this library and
+//! cross-checks the results against an `elementsd` regtest node. These are
+//! slower than the other integration tests and are only built with the
+//! `integration-test` feature:
+//!
+//! `cargo test --features integration-test --test rpc`
+//!
+
+use ::secp256k1::Scalar;
+use elements::hashes::Hash;
+use elements::pset::PartiallySignedTransaction as Psbt;
+use elements::sighash::SigHashCache;
+use elements::taproot::{LeafVersion, TapLeafHash};
+use elements::{
+    confidential, pset as psbt, secp256k1_zkp as secp256k1, sighash, OutPoint, SchnorrSig, Script,
+    Sequence, TxIn, TxOut, Txid,
+};
+use elementsd::ElementsD;
+use miniscript::psbt::{PsbtExt, PsbtInputExt};
+use miniscript::{Descriptor, DescriptorPublicKey, ToPublicKey};
+use rand::RngCore;
+use {actual_rand as rand, elements_miniscript as miniscript};
+
+#[path = "../setup/mod.rs"]
+mod setup;
+use setup::test_util::{self, TestData, PARAMS};
+use setup::Call;
+
+// Descriptors which Elements Core 0.21 can also derive addresses for
+const CORE_DESCS: &[&str] = &[
+    "pkh(K)",
+    "wpkh(K)",
+    "sh(wpkh(K))",
+    "sh(multi(2,K1,K2,K3))",
+    "wsh(multi(2,K1,K2,K3))",
+    "sh(wsh(sortedmulti(1,K1,K2)))",
+    "wsh(and_v(v:pk(K1),pk(K2)))",
+];
+
+fn parse_desc(testdata: &TestData, desc: &str) -> Descriptor<DescriptorPublicKey> {
+    test_util::parse_test_desc(&format!("el{}", desc), &testdata.pubdata)
+        .expect("valid test descriptor")
+}
+
+// Ask the node for the address of a descriptor, after stripping our `el` prefix
+fn core_address(cl: &ElementsD, desc: &Descriptor<DescriptorPublicKey>) -> String {
+    let desc_str = format!("{:#}", desc);
+    assert!(desc_str.starts_with("el"), "elements descriptor");
+    let core_desc = &desc_str[2..];
+    let info = cl.call("getdescriptorinfo", &[core_desc.into()]);
+    let checksummed = info["descriptor"].as_str().unwrap();
+    let addrs = cl.call("deriveaddresses", &[checksummed.into()]);
+    addrs[0].as_str().unwrap().to_string()
+}
+
+fn test_addresses(cl: &ElementsD, testdata: &TestData) {
+    let secp = secp256k1::Secp256k1::new();
+    let blinder = testdata.pubdata.pks[0].inner;
+    for desc in CORE_DESCS {
+        let desc = parse_desc(testdata, desc);
+        let derived = desc.derived_descriptor(&secp, 0).unwrap();
+
+        let addr = derived.address(&PARAMS).unwrap();
+        assert_eq!(addr.to_string(), core_address(cl, &desc), "{}", desc);
+
+        let blinded = derived.blinded_address(blinder, &PARAMS).unwrap();
+        let core_blinded = cl.call(
+            "createblindedaddress",
+            &[addr.to_string().into(), blinder.to_string().into()],
+        );
+        assert_eq!(
+            blinded.to_string(),
+            core_blinded.as_str().unwrap(),
+            "{}",
+            desc
+        );
+    }
+}
+
+// Send coins to a blinded address and check that we can unblind the output
+fn test_blinded_receive(cl: &ElementsD, testdata: &TestData) {
+    let secp = secp256k1::Secp256k1::new();
+    let blinding_sk = testdata.secretdata.sks[0];
+    let blinder = testdata.pubdata.pks[0].inner;
+    for desc in CORE_DESCS {
+        let desc = parse_desc(testdata, desc)
+            .derived_descriptor(&secp, 0)
+            .unwrap();
+        let addr = desc.blinded_address(blinder, &PARAMS).unwrap();
+        let txid = cl.send_to_address(&addr, "1");
+        cl.generate(1);
+
+        let tx = cl.get_transaction(&txid);
+        let txout = tx
+            .output
+            .iter()
+            .find(|out| out.script_pubkey == desc.script_pubkey())
+            .expect("output paying to the descriptor");
+        let secrets = txout.unblind(&secp, blinding_sk).unwrap();
+        assert_eq!(secrets.value, 100_000_000);
+    }
+}
+
+fn get_vout(cl: &ElementsD, txid: Txid, spk: &Script) -> (OutPoint, TxOut) {
+    let tx = cl.get_transaction(&txid);
+    for (i, txout) in tx.output.into_iter().enumerate() {
+        if txout.value == confidential::Value::Explicit(100_000_000) && txout.script_pubkey == *spk
+        {
+            return (OutPoint::new(txid, i as u32), txout);
+        }
+    }
+    unreachable!("funding transaction must pay to the descriptor");
+}
+
+// Fund a taproot descriptor, then spend it with the finalizer and check
+// that the node accepts the spend. Returns the final witness.
+fn test_tr_spend(cl: &ElementsD, testdata: &TestData, desc: &str) -> Vec<Vec<u8>> {
+    let secp = secp256k1::Secp256k1::new();
+    let desc = parse_desc(testdata, desc);
+    let derived = desc.derived_descriptor(&secp, 0).unwrap();
+    let tr = match derived {
+        Descriptor::Tr(ref tr) => tr,
+        _ => unreachable!("only taproot descriptors are spent here"),
+    };
+    let x_only_pks = &testdata.pubdata.x_only_pks;
+    let keypairs = &testdata.secretdata.x_only_keypairs;
+    let genesis_hash = testdata.pubdata.genesis_hash;
+
+    let txid = cl.send_to_address(&derived.address(&PARAMS).unwrap(), "1");
+    cl.generate(2);
+    let (outpoint, utxo) = get_vout(cl, txid, &derived.script_pubkey());
+
+    let mut psbt = Psbt::new_v2();
+    psbt.add_input(psbt::Input::from_txin(TxIn {
+        previous_output: outpoint,
+        is_pegin: false,
+        script_sig: Script::new(),
+        sequence: Sequence::MAX,
+        asset_issuance: Default::default(),
+        witness: Default::default(),
+    }));
+    let asset = utxo.asset.explicit().unwrap();
+    let out = TxOut {
+        value: confidential::Value::Explicit(99_997_000),
+        script_pubkey: cl.get_new_address().script_pubkey(),
+        asset: utxo.asset,
+        nonce: confidential::Nonce::Null,
+        witness: Default::default(),
+    };
+    psbt.add_output(psbt::Output::from_txout(out));
+    psbt.add_output(psbt::Output::from_txout(TxOut::new_fee(3_000, asset)));
+    psbt.inputs_mut()[0]
+        .update_with_descriptor_unchecked(&desc.at_derivation_index(0).unwrap())
+        .unwrap();
+    psbt.inputs_mut()[0].witness_utxo = Some(utxo.clone());
+
+    let unsigned_tx = psbt.extract_tx().unwrap();
+    let mut cache = SigHashCache::new(&unsigned_tx);
+    let prevouts = [utxo];
+    let prevouts = sighash::Prevouts::All(&prevouts);
+    let hash_ty = sighash::SchnorrSigHashType::Default;
+    let mut aux_rand = [0u8; 32];
+
+    let internal = x_only_pks
+        .iter()
+        .position(|x| x.to_public_key() == *tr.internal_key());
+    if let Some(idx) = internal {
+        let tweak = Scalar::from_be_bytes(tr.spend_info().tap_tweak().into_inner()).unwrap();
+        let keypair = keypairs[idx].add_xonly_tweak(&secp, &tweak).unwrap();
+        let msg = cache
+            .taproot_key_spend_signature_hash(0, &prevouts, hash_ty, genesis_hash)
+            .unwrap();
+        let msg = secp256k1::Message::from_slice(&msg[..]).unwrap();
+        rand::thread_rng().fill_bytes(&mut aux_rand);
+        let sig = secp.sign_schnorr_with_aux_rand(&msg, &keypair, &aux_rand);
+        psbt.inputs_mut()[0].tap_key_sig = Some(SchnorrSig { sig, hash_ty });
+    }
+    for (_depth, ms) in tr.iter_scripts() {
+        let leaf_hash = TapLeafHash::from_script(&ms.encode(), LeafVersion::default());
+        for pk in ms.iter_pk() {
+            let idx = match x_only_pks.iter().position(|x| x.to_public_key() == pk) {
+                Some(idx) => idx,
+                None => continue,
+            };
+            let msg = cache
+                .taproot_script_spend_signature_hash(0, &prevouts, leaf_hash, hash_ty, genesis_hash)
+                .unwrap();
+            let msg = secp256k1::Message::from_slice(&msg[..]).unwrap();
+            rand::thread_rng().fill_bytes(&mut aux_rand);
+            let sig = secp.sign_schnorr_with_aux_rand(&msg, &keypairs[idx], &aux_rand);
+            psbt.inputs_mut()[0]
+                .tap_script_sigs
+                .insert((x_only_pks[idx], leaf_hash), SchnorrSig { sig, hash_ty });
+        }
+    }
+
+    psbt.finalize_mut(&secp, genesis_hash).unwrap();
+    let tx = psbt.extract(&secp, genesis_hash).unwrap();
+    assert!(cl.test_mempool_accept(&tx));
+    let txid = cl.send_raw_transaction(&tx);
+    cl.generate(1);
+    let num_conf = cl.call("gettransaction", &[txid.to_string().into()])["confirmations"]
+        .as_u64()
+        .unwrap();
+    assert!(num_conf > 0);
+    tx.input[0].witness.script_witness.clone()
+}
+
+#[test]
+fn differential_rpc() {
+    let (cl, _, genesis_hash) = &setup::setup(false);
+    let testdata = TestData::new_fixed_data(50, *genesis_hash);
+
+    test_addresses(cl, &testdata);
+    test_blinded_receive(cl, &testdata);
+
+    // Key spend
+    let wit = test_tr_spend(cl, &testdata, "tr(X)");
+    assert_eq!(wit.len(), 1);
+    // Script spend: signature, script and control block
+    let wit = test_tr_spend(cl, &testdata, "tr(X!,{pk(X1),pk(X2!)})");
+    assert_eq!(wit.len(), 3);
+}
@@ -44,7 +44,8 @@ pub fn test_desc_satisfy(cl: &ElementsD, testdata: &TestData, desc: &str) -> Vec
 
     let definite_desc = test_util::parse_test_desc(&desc, &testdata.pubdata)
         .unwrap()
-        .at_derivation_index(0);
+        .at_derivation_index(0)
+        .unwrap();
 
     let derived_desc = definite_desc.derived_descriptor(&secp).unwrap();
     let desc_address = derived_desc.address(&PARAMS).unwrap(); // No blinding